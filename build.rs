@@ -0,0 +1,100 @@
+//! Embeds the frontend. Every CSS and JS file in `static/` is served under a
+//! content-hashed path, and the HTML pages are rewritten to reference those
+//! paths with Subresource Integrity attributes. A reference to a file that
+//! does not exist is left as it is; the tests in `src/assets.rs` catch it.
+
+use base64::Engine;
+use sha2::{Digest, Sha256, Sha384};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PAGES: &[&str] = &["dashboard.html", "login.html", "register.html", "reset-password.html", "share.html"];
+
+struct Asset {
+    name: String,
+    hashed_name: String,
+    content_type: &'static str,
+    integrity: String,
+    path: PathBuf,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=static");
+    let static_dir = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("static");
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&static_dir)
+        .expect("static/ is missing")
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+
+    let mut assets = Vec::new();
+    for path in paths {
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let Some((stem, extension)) = name.rsplit_once('.') else {
+            continue;
+        };
+        let content_type = match extension {
+            "css" => "text/css; charset=utf-8",
+            "js" => "text/javascript; charset=utf-8",
+            _ => continue,
+        };
+
+        let body = fs::read(&path).unwrap();
+        let hash: String = Sha256::digest(&body)[..6].iter().map(|b| format!("{:02x}", b)).collect();
+        let integrity = format!(
+            "sha384-{}",
+            base64::engine::general_purpose::STANDARD.encode(Sha384::digest(&body))
+        );
+        assets.push(Asset {
+            hashed_name: format!("{}.{}.{}", stem, hash, extension),
+            name,
+            content_type,
+            integrity,
+            path,
+        });
+    }
+
+    for page in PAGES {
+        let html = fs::read_to_string(static_dir.join(page)).unwrap();
+        fs::write(out_dir.join(page), rewrite_references(page, &html, &assets)).unwrap();
+    }
+
+    let mut code = String::from("pub static ASSETS: &[Asset] = &[\n");
+    for asset in &assets {
+        writeln!(
+            code,
+            "    Asset {{ path: {:?}, content_type: {:?}, body: include_bytes!({:?}) }},",
+            format!("/static/{}", asset.hashed_name),
+            asset.content_type,
+            asset.path.display().to_string(),
+        )
+        .unwrap();
+    }
+    code.push_str("];\n");
+    fs::write(out_dir.join("assets.rs"), code).unwrap();
+}
+
+/// Point every `"/static/<name>"` attribute naming a file in `static/` at
+/// the hashed path and add its integrity hash.
+fn rewrite_references(page: &str, html: &str, assets: &[Asset]) -> String {
+    let mut rewritten = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("\"/static/") {
+        let (before, after) = rest.split_at(start + 1);
+        let end = after
+            .find('"')
+            .unwrap_or_else(|| panic!("{}: unterminated /static/ reference", page));
+        let name = &after["/static/".len()..end];
+        rewritten.push_str(before);
+        match assets.iter().find(|asset| asset.name == name) {
+            Some(asset) => write!(rewritten, "/static/{}\" integrity=\"{}", asset.hashed_name, asset.integrity).unwrap(),
+            None => rewritten.push_str(&after[..end]),
+        }
+        rest = &after[end..];
+    }
+    rewritten.push_str(rest);
+    rewritten
+}
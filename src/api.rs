@@ -0,0 +1,313 @@
+//! Request and response bodies of the HTTP API. The server writes its
+//! responses with these types and `client::ApiClient` reads them back, so
+//! the two cannot drift apart.
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ErrorCode;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+/// Query of `POST /api/power/on`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PowerOnQuery {
+    #[serde(default)]
+    pub verify: bool,
+    pub verify_timeout_secs: Option<u64>,
+    /// `host:port` that accepts connections once the OS is up, for the boot report.
+    pub wait_for_os: Option<String>,
+    /// Server alias; defaults to the `IDRAC_HOST` server.
+    pub server: Option<String>,
+}
+
+/// What to do when a graceful shutdown has not powered the server off in time.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EscalationMode {
+    Force,
+    Alert,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShutdownRequest {
+    pub escalate_after_secs: Option<u64>,
+    pub escalate: Option<EscalationMode>,
+    #[serde(default)]
+    pub i_understand_this_hosts_the_controller: bool,
+}
+
+/// Body of a disruptive power action; the field is only required when the
+/// target server hosts this application.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostsThisAppAcknowledgment {
+    #[serde(default)]
+    pub i_understand_this_hosts_the_controller: bool,
+}
+
+/// Body of `POST /api/power/nmi`. The interrupt usually crashes the OS
+/// into a dump, so it is only sent with `confirm`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NmiRequest {
+    #[serde(default)]
+    pub confirm: bool,
+    #[serde(default)]
+    pub i_understand_this_hosts_the_controller: bool,
+}
+
+/// `limit` and `cursor` of a paginated list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageQuery {
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerByHostQuery {
+    pub host: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl ApiResponse {
+    pub fn success(message: impl Into<String>) -> Self {
+        ApiResponse {
+            success: true,
+            message: message.into(),
+            error_code: None,
+            warning: None,
+        }
+    }
+
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiResponse {
+            success: false,
+            message: message.into(),
+            error_code: Some(code),
+            warning: None,
+        }
+    }
+
+    pub fn with_warning(self, warning: Option<String>) -> Self {
+        ApiResponse { warning, ..self }
+    }
+
+    /// Error response for a failed iDRAC call, classified from its message.
+    pub fn idrac_error(message: String) -> Self {
+        let code = ErrorCode::from_idrac_error(&message);
+        if code == ErrorCode::IdracAuthFailed && message.contains("HTTP 401") {
+            let message = format!("{}; the iDRAC rejected the stored credentials, update them and run a connection test", message);
+            return ApiResponse::error(code, message);
+        }
+        ApiResponse::error(code, message)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldErrorResponse {
+    pub success: bool,
+    pub message: String,
+    pub error_code: ErrorCode,
+    pub field: String,
+}
+
+/// `page` of a paginated list response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageInfo {
+    pub limit: u32,
+    /// Pass as `cursor` to get the rows after this page; `None` on the
+    /// last page.
+    pub next_cursor: Option<String>,
+    /// Pass as `cursor` to get the rows before this page; `None` on the
+    /// first page.
+    pub prev_cursor: Option<String>,
+    /// Rough number of rows across all pages, where that is cheap to tell.
+    pub total_estimate: Option<u64>,
+}
+
+/// Every paginated list answers with `{"success", "items", "page"}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub success: bool,
+    pub items: Vec<T>,
+    pub page: PageInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub success: bool,
+    /// Alias of the server that answered.
+    #[serde(default)]
+    pub server_name: String,
+    pub power_state: String,
+    /// `None` when the iDRAC does not report it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_power_reason: Option<PowerChangeReason>,
+}
+
+/// Why a server last changed power state, from Dell's
+/// `LastPowerChangeReasonCode`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerChangeReason {
+    /// As the iDRAC reports it, e.g. `Operator Initiated`.
+    pub code: String,
+    /// `operator`, `ac_power_restored`, `watchdog` or `other`.
+    pub source: String,
+}
+
+impl PowerChangeReason {
+    pub fn from_code(code: &str) -> Self {
+        let key = code.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_ascii_lowercase();
+        let source = if key.contains("operator") {
+            "operator"
+        } else if key.contains("acpower") {
+            "ac_power_restored"
+        } else if key.contains("watchdog") {
+            "watchdog"
+        } else {
+            "other"
+        };
+        PowerChangeReason {
+            code: code.to_string(),
+            source: source.to_string(),
+        }
+    }
+}
+
+/// A power state observed on a server, stored when it or the reason for
+/// the last change differs from the previous observation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerEvent {
+    pub id: i64,
+    pub server_alias: String,
+    pub power_state: String,
+    pub reason: Option<PowerChangeReason>,
+    pub observed_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerOnVerifiedResponse {
+    pub success: bool,
+    pub message: String,
+    /// Whether the server reported `On` within the verification timeout.
+    pub verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_to_on_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationStartedResponse {
+    pub success: bool,
+    pub message: String,
+    pub operation_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// A long-running action tracked through its individual stages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: String,
+    pub kind: String,
+    /// The server acted on, for operations concerning a single one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_alias: Option<String>,
+    pub status: String,
+    pub stages: Vec<OperationStage>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationStage {
+    pub name: String,
+    pub at: String,
+    pub detail: Option<String>,
+}
+
+/// A stored iDRAC event that followed an operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedEvent {
+    pub server_alias: String,
+    pub entry_id: String,
+    pub created: String,
+    pub severity: String,
+    pub message: String,
+    pub message_id: Option<String>,
+    /// `sel` or `redfish_event`.
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationResponse {
+    pub success: bool,
+    pub operation: Operation,
+    #[serde(default)]
+    pub related_events: Vec<RelatedEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerSummary {
+    pub id: Option<i64>,
+    pub alias: String,
+    pub name: String,
+    pub base_url: String,
+    pub tags: Vec<String>,
+    pub location: Option<String>,
+    pub hosts_this_app: bool,
+    /// Reverse DNS name of the iDRAC, where it has one. Only filled in by
+    /// listings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Why the server is in maintenance, e.g. a firmware rollout updating
+    /// it; background checks skip it meanwhile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<String>,
+    /// Agent endpoint on the server's OS that is probed for OS health.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_health_url: Option<String>,
+    /// Latest OS health probe. Only filled in by listings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_health: Option<OsHealth>,
+}
+
+/// Outcome of the latest probe of a server's OS health endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsHealth {
+    /// `reachable`, `unreachable`, or `powered_off` when the probe failed
+    /// while the iDRAC reported the server off.
+    pub status: String,
+    pub latency_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at: String,
+    /// When `status` last changed.
+    pub since: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerResponse {
+    pub success: bool,
+    pub server: ServerSummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerByHostResponse {
+    pub success: bool,
+    pub alias: String,
+    pub host: String,
+    pub tags: Vec<String>,
+}
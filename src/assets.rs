@@ -0,0 +1,81 @@
+//! The frontend, embedded at compile time by `build.rs`. CSS and JS are
+//! served under content-hashed paths; the pages reference them with
+//! Subresource Integrity hashes.
+
+/// A CSS or JS file from `static/`.
+pub struct Asset {
+    /// `/static/<stem>.<hash>.<ext>`
+    pub path: &'static str,
+    pub content_type: &'static str,
+    pub body: &'static [u8],
+}
+
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+pub const DASHBOARD_HTML: &str = include_str!(concat!(env!("OUT_DIR"), "/dashboard.html"));
+pub const LOGIN_HTML: &str = include_str!(concat!(env!("OUT_DIR"), "/login.html"));
+pub const REGISTER_HTML: &str = include_str!(concat!(env!("OUT_DIR"), "/register.html"));
+pub const RESET_PASSWORD_HTML: &str = include_str!(concat!(env!("OUT_DIR"), "/reset-password.html"));
+pub const SHARE_HTML: &str = include_str!(concat!(env!("OUT_DIR"), "/share.html"));
+
+pub fn find(path: &str) -> Option<&'static Asset> {
+    ASSETS.iter().find(|asset| asset.path == path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    const PAGES: &[(&str, &str)] = &[
+        ("dashboard.html", DASHBOARD_HTML),
+        ("login.html", LOGIN_HTML),
+        ("register.html", REGISTER_HTML),
+        ("reset-password.html", RESET_PASSWORD_HTML),
+        ("share.html", SHARE_HTML),
+    ];
+
+    /// Every `"/static/..."` attribute in `html`, with the `integrity`
+    /// attribute following it if there is one.
+    fn references(html: &str) -> Vec<(&str, Option<&str>)> {
+        let mut found = Vec::new();
+        let mut rest = html;
+        while let Some(start) = rest.find("\"/static/") {
+            let after = &rest[start + 1..];
+            let end = after.find('"').expect("unterminated /static/ reference");
+            let integrity = after[end..]
+                .strip_prefix("\" integrity=\"")
+                .and_then(|value| value.split('"').next());
+            found.push((&after[..end], integrity));
+            rest = &after[end..];
+        }
+        found
+    }
+
+    #[test]
+    fn every_asset_referenced_by_a_page_is_served() {
+        let mut missing = Vec::new();
+        for (page, html) in PAGES {
+            for (path, integrity) in references(html) {
+                match find(path) {
+                    None => missing.push(format!("{} references {}, which is not in static/", page, path)),
+                    Some(asset) => {
+                        let expected = format!(
+                            "sha384-{}",
+                            base64::engine::general_purpose::STANDARD.encode(openssl::sha::sha384(asset.body))
+                        );
+                        assert_eq!(integrity, Some(expected.as_str()), "{}: integrity of {}", page, path);
+                    }
+                }
+            }
+        }
+        assert!(missing.is_empty(), "{}", missing.join("\n"));
+    }
+
+    #[test]
+    fn every_page_references_assets() {
+        for (page, html) in PAGES {
+            assert!(!references(html).is_empty(), "{} references no assets", page);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt;
+
+/// What an audit log entry records. The name is stored in the `action`
+/// column, so a variant's `as_str` must never change once released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    PowerOn,
+    PowerOnVerify,
+    ForceOff,
+    GracefulShutdown,
+    ForceRestart,
+    GracefulRestart,
+    PowerCycle,
+    Nmi,
+    ShutdownEscalationForce,
+    ShutdownEscalationAlert,
+    HostsThisAppAcknowledged,
+    VirtualMediaBootOnce,
+    GroupPowerOn,
+    FirmwareRollout,
+    FirmwareRolloutResume,
+    FirmwareRolloutCancel,
+    FirmwareUpdate,
+    FirmwareUpdateSchedule,
+    FirmwareUpdateApply,
+    GroupApplyConfig,
+    GroupCreate,
+    GroupUpdate,
+    GroupDelete,
+    ServerCreate,
+    ServerDelete,
+    ServerCredentialsUpdate,
+    ServerOsHealthUpdate,
+    ServerImport,
+    SystemProfileSet,
+    PostWatchdogSet,
+    NicModeSet,
+    IdracUserCreate,
+    IdracUserUpdate,
+    BootOrderSet,
+    IdracFactoryResetRequested,
+    IdracFactoryReset,
+    IdracTimeSync,
+    NtpConfigure,
+    IdracOemAction,
+    BiosResetToDefaults,
+    LicenseActivate,
+    EventSelfSubscribe,
+    TelemetryDefinitionCreate,
+    TelemetryDefinitionDelete,
+    FirmwareInventoryRefresh,
+    FirmwareBaselineSet,
+    ComplianceProfileSet,
+    PowerCapSchedule,
+    PowerCapScheduleCreate,
+    PowerCapScheduleDelete,
+    TariffWindowCreate,
+    TariffWindowDelete,
+    TariffCapApply,
+    TariffCapRestore,
+    OneShotScheduleCreate,
+    OneShotScheduleDelete,
+    UserCreate,
+    UserExpiryUpdate,
+    AccountExpired,
+    ServerGrantUpdate,
+    ServerGrantRevoke,
+    ApiTokenCreate,
+    ApiTokenRevoke,
+    ShareCreate,
+    ShareRevoke,
+    ApiTokenQuotaUpdate,
+    RetentionPolicyUpdate,
+    BreakGlassIssued,
+    BreakGlassLogin,
+    BreakGlassRequest,
+    PasswordResetRequested,
+    PasswordReset,
+    PromoteStandby,
+}
+
+impl AuditAction {
+    pub const ALL: &'static [AuditAction] = &[
+        AuditAction::PowerOn,
+        AuditAction::PowerOnVerify,
+        AuditAction::ForceOff,
+        AuditAction::GracefulShutdown,
+        AuditAction::ForceRestart,
+        AuditAction::GracefulRestart,
+        AuditAction::PowerCycle,
+        AuditAction::Nmi,
+        AuditAction::ShutdownEscalationForce,
+        AuditAction::ShutdownEscalationAlert,
+        AuditAction::HostsThisAppAcknowledged,
+        AuditAction::VirtualMediaBootOnce,
+        AuditAction::GroupPowerOn,
+        AuditAction::FirmwareRollout,
+        AuditAction::FirmwareRolloutResume,
+        AuditAction::FirmwareRolloutCancel,
+        AuditAction::FirmwareUpdate,
+        AuditAction::FirmwareUpdateSchedule,
+        AuditAction::FirmwareUpdateApply,
+        AuditAction::GroupApplyConfig,
+        AuditAction::GroupCreate,
+        AuditAction::GroupUpdate,
+        AuditAction::GroupDelete,
+        AuditAction::ServerCreate,
+        AuditAction::ServerDelete,
+        AuditAction::ServerCredentialsUpdate,
+        AuditAction::ServerOsHealthUpdate,
+        AuditAction::ServerImport,
+        AuditAction::SystemProfileSet,
+        AuditAction::PostWatchdogSet,
+        AuditAction::NicModeSet,
+        AuditAction::IdracUserCreate,
+        AuditAction::IdracUserUpdate,
+        AuditAction::BootOrderSet,
+        AuditAction::IdracFactoryResetRequested,
+        AuditAction::IdracFactoryReset,
+        AuditAction::IdracTimeSync,
+        AuditAction::NtpConfigure,
+        AuditAction::IdracOemAction,
+        AuditAction::BiosResetToDefaults,
+        AuditAction::LicenseActivate,
+        AuditAction::EventSelfSubscribe,
+        AuditAction::TelemetryDefinitionCreate,
+        AuditAction::TelemetryDefinitionDelete,
+        AuditAction::FirmwareInventoryRefresh,
+        AuditAction::FirmwareBaselineSet,
+        AuditAction::ComplianceProfileSet,
+        AuditAction::PowerCapSchedule,
+        AuditAction::PowerCapScheduleCreate,
+        AuditAction::PowerCapScheduleDelete,
+        AuditAction::TariffWindowCreate,
+        AuditAction::TariffWindowDelete,
+        AuditAction::TariffCapApply,
+        AuditAction::TariffCapRestore,
+        AuditAction::OneShotScheduleCreate,
+        AuditAction::OneShotScheduleDelete,
+        AuditAction::UserCreate,
+        AuditAction::UserExpiryUpdate,
+        AuditAction::AccountExpired,
+        AuditAction::ServerGrantUpdate,
+        AuditAction::ServerGrantRevoke,
+        AuditAction::ApiTokenCreate,
+        AuditAction::ApiTokenRevoke,
+        AuditAction::ShareCreate,
+        AuditAction::ShareRevoke,
+        AuditAction::ApiTokenQuotaUpdate,
+        AuditAction::RetentionPolicyUpdate,
+        AuditAction::BreakGlassIssued,
+        AuditAction::BreakGlassLogin,
+        AuditAction::BreakGlassRequest,
+        AuditAction::PasswordResetRequested,
+        AuditAction::PasswordReset,
+        AuditAction::PromoteStandby,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::PowerOn => "PowerOn",
+            AuditAction::PowerOnVerify => "PowerOnVerify",
+            AuditAction::ForceOff => "ForceOff",
+            AuditAction::GracefulShutdown => "GracefulShutdown",
+            AuditAction::ForceRestart => "ForceRestart",
+            AuditAction::GracefulRestart => "GracefulRestart",
+            AuditAction::PowerCycle => "PowerCycle",
+            AuditAction::Nmi => "Nmi",
+            AuditAction::ShutdownEscalationForce => "ShutdownEscalation:Force",
+            AuditAction::ShutdownEscalationAlert => "ShutdownEscalation:Alert",
+            AuditAction::HostsThisAppAcknowledged => "HostsThisAppAcknowledged",
+            AuditAction::VirtualMediaBootOnce => "VirtualMediaBootOnce",
+            AuditAction::GroupPowerOn => "GroupPowerOn",
+            AuditAction::FirmwareRollout => "FirmwareRollout",
+            AuditAction::FirmwareRolloutResume => "FirmwareRolloutResume",
+            AuditAction::FirmwareRolloutCancel => "FirmwareRolloutCancel",
+            AuditAction::FirmwareUpdate => "FirmwareUpdate",
+            AuditAction::FirmwareUpdateSchedule => "FirmwareUpdateSchedule",
+            AuditAction::FirmwareUpdateApply => "FirmwareUpdateApply",
+            AuditAction::GroupApplyConfig => "GroupApplyConfig",
+            AuditAction::GroupCreate => "GroupCreate",
+            AuditAction::GroupUpdate => "GroupUpdate",
+            AuditAction::GroupDelete => "GroupDelete",
+            AuditAction::ServerCreate => "ServerCreate",
+            AuditAction::ServerDelete => "ServerDelete",
+            AuditAction::ServerCredentialsUpdate => "ServerCredentialsUpdate",
+            AuditAction::ServerOsHealthUpdate => "ServerOsHealthUpdate",
+            AuditAction::ServerImport => "ServerImport",
+            AuditAction::SystemProfileSet => "SystemProfileSet",
+            AuditAction::PostWatchdogSet => "PostWatchdogSet",
+            AuditAction::NicModeSet => "NicModeSet",
+            AuditAction::IdracUserCreate => "IdracUserCreate",
+            AuditAction::IdracUserUpdate => "IdracUserUpdate",
+            AuditAction::BootOrderSet => "BootOrderSet",
+            AuditAction::IdracFactoryResetRequested => "IdracFactoryResetRequested",
+            AuditAction::IdracFactoryReset => "IdracFactoryReset",
+            AuditAction::IdracTimeSync => "IdracTimeSync",
+            AuditAction::NtpConfigure => "NtpConfigure",
+            AuditAction::IdracOemAction => "IdracOemAction",
+            AuditAction::BiosResetToDefaults => "BiosResetToDefaults",
+            AuditAction::LicenseActivate => "LicenseActivate",
+            AuditAction::EventSelfSubscribe => "EventSelfSubscribe",
+            AuditAction::TelemetryDefinitionCreate => "TelemetryDefinitionCreate",
+            AuditAction::TelemetryDefinitionDelete => "TelemetryDefinitionDelete",
+            AuditAction::FirmwareInventoryRefresh => "FirmwareInventoryRefresh",
+            AuditAction::FirmwareBaselineSet => "FirmwareBaselineSet",
+            AuditAction::ComplianceProfileSet => "ComplianceProfileSet",
+            AuditAction::PowerCapSchedule => "PowerCapSchedule",
+            AuditAction::PowerCapScheduleCreate => "PowerCapScheduleCreate",
+            AuditAction::PowerCapScheduleDelete => "PowerCapScheduleDelete",
+            AuditAction::TariffWindowCreate => "TariffWindowCreate",
+            AuditAction::TariffWindowDelete => "TariffWindowDelete",
+            AuditAction::TariffCapApply => "TariffCapApply",
+            AuditAction::TariffCapRestore => "TariffCapRestore",
+            AuditAction::OneShotScheduleCreate => "OneShotScheduleCreate",
+            AuditAction::OneShotScheduleDelete => "OneShotScheduleDelete",
+            AuditAction::UserCreate => "UserCreate",
+            AuditAction::UserExpiryUpdate => "UserExpiryUpdate",
+            AuditAction::ServerGrantUpdate => "ServerGrantUpdate",
+            AuditAction::ServerGrantRevoke => "ServerGrantRevoke",
+            AuditAction::AccountExpired => "AccountExpired",
+            AuditAction::ApiTokenCreate => "ApiTokenCreate",
+            AuditAction::ApiTokenRevoke => "ApiTokenRevoke",
+            AuditAction::ShareCreate => "ShareCreate",
+            AuditAction::ShareRevoke => "ShareRevoke",
+            AuditAction::ApiTokenQuotaUpdate => "ApiTokenQuotaUpdate",
+            AuditAction::RetentionPolicyUpdate => "RetentionPolicyUpdate",
+            AuditAction::BreakGlassIssued => "BreakGlassIssued",
+            AuditAction::BreakGlassLogin => "BreakGlassLogin",
+            AuditAction::BreakGlassRequest => "BreakGlassRequest",
+            AuditAction::PasswordResetRequested => "PasswordResetRequested",
+            AuditAction::PasswordReset => "PasswordReset",
+            AuditAction::PromoteStandby => "PromoteStandby",
+        }
+    }
+
+    /// The action stored as `action`, or `None` for one this version does
+    /// not know.
+    pub fn parse(action: &str) -> Option<AuditAction> {
+        AuditAction::ALL.iter().copied().find(|known| known.as_str() == action)
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Variant names declared in `enum AuditAction`, read from this file so
+    /// a variant left out of `ALL` is caught.
+    fn declared_variants() -> Vec<&'static str> {
+        let source = include_str!("audit.rs");
+        let start = source.find("pub enum AuditAction {").unwrap();
+        let body = &source[start..];
+        let body = &body[body.find('{').unwrap() + 1..body.find('}').unwrap()];
+        body.split(',').map(str::trim).filter(|name| !name.is_empty()).collect()
+    }
+
+    #[test]
+    fn every_variant_is_listed() {
+        let listed: Vec<String> = AuditAction::ALL.iter().map(|action| format!("{:?}", action)).collect();
+        assert_eq!(listed, declared_variants());
+    }
+
+    #[test]
+    fn names_are_unique_and_parse_back() {
+        let mut seen = std::collections::HashSet::new();
+        for &action in AuditAction::ALL {
+            assert!(seen.insert(action.as_str()), "{} is used twice", action);
+            assert_eq!(AuditAction::parse(action.as_str()), Some(action));
+        }
+        assert_eq!(AuditAction::parse("NoSuchAction"), None);
+    }
+}
@@ -0,0 +1,1290 @@
+//! Minimal Redfish simulator standing in for a Dell iDRAC during development
+//! and demos.
+//!
+//! ```text
+//! cargo run --bin fake-idrac -- --port 8443 --fail-503-for 30
+//! IDRAC_HOST=https://localhost:8443 IDRAC_USERNAME=root IDRAC_PASSWORD=calvin cargo run
+//! ```
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use base64::Engine;
+use log::info;
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslMethod};
+use openssl::x509::{X509NameBuilder, X509};
+use rand::Rng;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "Usage: fake-idrac [--port N] [--no-tls] [--username U] [--password P]
+                  [--power-delay-secs N] [--sel-interval-secs N] [--fail-503-for SECS]
+                  [--clock-skew-secs N] [--select supported|unadvertised|rejected]
+                  [--body-quirk bom|text-plain|trailing-nul|html|empty|truncated]
+                  [--psu-fault failed|input-lost|degraded]";
+
+/// Malformed bodies older iDRAC firmware has been seen to send, applied to
+/// the system resource.
+#[derive(Clone, Copy, PartialEq)]
+enum BodyQuirk {
+    None,
+    Bom,
+    TextPlain,
+    TrailingNul,
+    Html,
+    Empty,
+    Truncated,
+}
+
+/// Fault reported by the second power supply.
+#[derive(Clone, Copy, PartialEq)]
+enum PsuFault {
+    None,
+    Failed,
+    InputLost,
+    Degraded,
+}
+
+/// How the simulator treats `$select`: honour it, not advertise and ignore
+/// it like older iDRACs, or advertise it but answer 400.
+#[derive(Clone, Copy, PartialEq)]
+enum SelectMode {
+    Supported,
+    Unadvertised,
+    Rejected,
+}
+
+struct Options {
+    port: u16,
+    tls: bool,
+    username: String,
+    password: String,
+    power_delay: Duration,
+    sel_interval: Duration,
+    fail_for: Option<Duration>,
+    clock_skew_secs: i64,
+    select: SelectMode,
+    body_quirk: BodyQuirk,
+    psu_fault: PsuFault,
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options = Options {
+            port: 8443,
+            tls: true,
+            username: "root".to_string(),
+            password: "calvin".to_string(),
+            power_delay: Duration::from_secs(5),
+            sel_interval: Duration::from_secs(60),
+            fail_for: None,
+            clock_skew_secs: 0,
+            select: SelectMode::Supported,
+            body_quirk: BodyQuirk::None,
+            psu_fault: PsuFault::None,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} requires a value", arg));
+            let secs = |v: String| {
+                v.parse::<u64>()
+                    .map(Duration::from_secs)
+                    .map_err(|_| format!("'{}' is not a number of seconds", v))
+            };
+            match arg.as_str() {
+                "--port" => options.port = value()?.parse().map_err(|_| "invalid port".to_string())?,
+                "--no-tls" => options.tls = false,
+                "--username" => options.username = value()?,
+                "--password" => options.password = value()?,
+                "--power-delay-secs" => options.power_delay = secs(value()?)?,
+                "--sel-interval-secs" => options.sel_interval = secs(value()?)?,
+                "--fail-503-for" => options.fail_for = Some(secs(value()?)?),
+                "--clock-skew-secs" => {
+                    let v = value()?;
+                    options.clock_skew_secs = v.parse().map_err(|_| format!("'{}' is not a number of seconds", v))?
+                }
+                "--select" => {
+                    options.select = match value()?.as_str() {
+                        "supported" => SelectMode::Supported,
+                        "unadvertised" => SelectMode::Unadvertised,
+                        "rejected" => SelectMode::Rejected,
+                        other => return Err(format!("Unknown --select mode '{}'\n{}", other, USAGE)),
+                    }
+                }
+                "--body-quirk" => {
+                    options.body_quirk = match value()?.as_str() {
+                        "bom" => BodyQuirk::Bom,
+                        "text-plain" => BodyQuirk::TextPlain,
+                        "trailing-nul" => BodyQuirk::TrailingNul,
+                        "html" => BodyQuirk::Html,
+                        "empty" => BodyQuirk::Empty,
+                        "truncated" => BodyQuirk::Truncated,
+                        other => return Err(format!("Unknown --body-quirk '{}'\n{}", other, USAGE)),
+                    }
+                }
+                "--psu-fault" => {
+                    options.psu_fault = match value()?.as_str() {
+                        "failed" => PsuFault::Failed,
+                        "input-lost" => PsuFault::InputLost,
+                        "degraded" => PsuFault::Degraded,
+                        other => return Err(format!("Unknown --psu-fault '{}'\n{}", other, USAGE)),
+                    }
+                }
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                other => return Err(format!("Unknown argument '{}'\n{}", other, USAGE)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+struct Simulator {
+    power_state: Mutex<String>,
+    /// When the host last reached `On`, for its boot progress.
+    powered_on_at: Mutex<Option<Instant>>,
+    /// Dell `LastPowerChangeReasonCode`.
+    last_power_reason: Mutex<String>,
+    sel: Mutex<Vec<serde_json::Value>>,
+    /// Lifecycle Controller log; holds a copy of every SEL entry too.
+    lc: Mutex<Vec<serde_json::Value>>,
+    expected_auth: String,
+    power_delay: Duration,
+    unavailable_until: Mutex<Option<Instant>>,
+    clock_skew: Mutex<chrono::Duration>,
+    power_limit: Mutex<Option<u64>>,
+    boot_order: Mutex<Vec<String>>,
+    /// `BootSourceOverrideTarget` and `BootSourceOverrideEnabled`.
+    boot_override: Mutex<(String, String)>,
+    /// Image in the virtual CD drive.
+    virtual_cd: Mutex<Option<String>>,
+    /// BIOS attributes as applied, and those staged in `Bios/Settings`
+    /// until the next power on.
+    bios_attributes: Mutex<serde_json::Map<String, serde_json::Value>>,
+    bios_pending: Mutex<serde_json::Map<String, serde_json::Value>>,
+    /// Manager attributes; unlike BIOS ones they apply immediately.
+    idrac_attributes: Mutex<serde_json::Map<String, serde_json::Value>>,
+    metric_report_definitions: Mutex<Vec<String>>,
+    /// Local accounts by slot; slot 1 is reserved and stays empty.
+    accounts: Mutex<Vec<serde_json::Value>>,
+    /// Firmware update jobs; scheduled ones run on the next power on.
+    jobs: Mutex<Vec<serde_json::Value>>,
+    select: SelectMode,
+    body_quirk: BodyQuirk,
+    psu_fault: PsuFault,
+}
+
+impl Simulator {
+    fn push_sel(&self, severity: &str, message: &str, message_id: &str) {
+        let created = chrono::Utc::now().to_rfc3339();
+        let mut sel = self.sel.lock().unwrap();
+        let id = sel.len() + 1;
+        sel.push(json!({
+            "@odata.id": format!("/redfish/v1/Managers/iDRAC.Embedded.1/LogServices/Sel/Entries/{}", id),
+            "Id": id.to_string(),
+            "Created": created,
+            "Severity": severity,
+            "Message": message,
+            "MessageId": message_id,
+            "EntryType": "SEL",
+        }));
+        self.push_lc_at(&created, severity, message, message_id);
+    }
+
+    /// Finish the firmware update jobs waiting for a power on.
+    fn run_scheduled_jobs(&self) {
+        for job in self.jobs.lock().unwrap().iter_mut().filter(|job| job["JobState"] == "Scheduled") {
+            let failed = job["Name"].as_str().is_some_and(|name| name.contains("corrupt"));
+            job["JobState"] = json!(if failed { "Failed" } else { "Completed" });
+            job["PercentComplete"] = json!(100);
+            job["Message"] = json!(if failed {
+                "Unable to verify Update Package signature."
+            } else {
+                "The specified job has completed successfully."
+            });
+            info!("Job {} {}", job["Id"], job["JobState"]);
+        }
+    }
+
+    fn push_lc(&self, severity: &str, message: &str, message_id: &str) {
+        self.push_lc_at(&chrono::Utc::now().to_rfc3339(), severity, message, message_id);
+    }
+
+    fn push_lc_at(&self, created: &str, severity: &str, message: &str, message_id: &str) {
+        let mut lc = self.lc.lock().unwrap();
+        let id = lc.len() + 1;
+        lc.push(json!({
+            "@odata.id": format!("/redfish/v1/Managers/iDRAC.Embedded.1/LogServices/Lclog/Entries/{}", id),
+            "Id": id.to_string(),
+            "Created": created,
+            "Severity": severity,
+            "Message": message,
+            "MessageId": message_id,
+            "EntryType": "Oem",
+        }));
+    }
+
+    /// Failure injection and Basic auth, applied to every request.
+    fn check(&self, req: &HttpRequest) -> Option<HttpResponse> {
+        if let Some(until) = *self.unavailable_until.lock().unwrap() {
+            if Instant::now() < until {
+                return Some(HttpResponse::ServiceUnavailable().json(redfish_error("Service temporarily unavailable")));
+            }
+        }
+
+        let authorized = req
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v == self.expected_auth)
+            .unwrap_or(false);
+        if !authorized {
+            return Some(HttpResponse::Unauthorized().json(redfish_error("Invalid credentials")));
+        }
+
+        None
+    }
+
+    /// Answer with `resource`, narrowed to the properties named in a
+    /// `$select` query if `--select` allows it.
+    fn respond_selected(&self, req: &HttpRequest, resource: serde_json::Value) -> HttpResponse {
+        let select = req
+            .query_string()
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("$select=").or_else(|| pair.strip_prefix("%24select=")));
+        let Some(select) = select else {
+            return self.respond_quirky(resource);
+        };
+
+        match self.select {
+            SelectMode::Unadvertised => self.respond_quirky(resource),
+            SelectMode::Rejected => HttpResponse::BadRequest().json(redfish_error("Query parameter $select is not supported")),
+            SelectMode::Supported => {
+                let mut selected = serde_json::Map::new();
+                for key in std::iter::once("@odata.id").chain(select.split(',')) {
+                    if let Some(value) = resource.get(key) {
+                        selected.insert(key.to_string(), value.clone());
+                    }
+                }
+                self.respond_quirky(serde_json::Value::Object(selected))
+            }
+        }
+    }
+
+    /// A 200 carrying `resource`, mangled according to `--body-quirk`.
+    fn respond_quirky(&self, resource: serde_json::Value) -> HttpResponse {
+        let json = resource.to_string();
+        let (content_type, body) = match self.body_quirk {
+            BodyQuirk::None => return HttpResponse::Ok().json(resource),
+            BodyQuirk::Bom => ("application/json", format!("\u{feff}{}", json)),
+            BodyQuirk::TextPlain => ("text/plain", json),
+            BodyQuirk::TrailingNul => ("application/json", format!("{}\0\0", json)),
+            BodyQuirk::Html => (
+                "text/html",
+                "<html><head><title>500 Internal Server Error</title></head>\n<body><h1>Internal Server Error</h1>\n<p>The server encountered an internal error.</p></body></html>".to_string(),
+            ),
+            BodyQuirk::Empty => ("application/json", String::new()),
+            BodyQuirk::Truncated => ("application/json", json[..json.len() / 2].to_string()),
+        };
+        HttpResponse::Ok().content_type(content_type).body(body)
+    }
+}
+
+fn redfish_error(message: &str) -> serde_json::Value {
+    json!({
+        "error": {
+            "code": "Base.1.0.GeneralError",
+            "message": message,
+        }
+    })
+}
+
+async fn service_root(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1",
+        "RedfishVersion": "1.11.0",
+        "Systems": { "@odata.id": "/redfish/v1/Systems" },
+        "Chassis": { "@odata.id": "/redfish/v1/Chassis" },
+        "Managers": { "@odata.id": "/redfish/v1/Managers" },
+        "SessionService": { "@odata.id": "/redfish/v1/SessionService" },
+        "EventService": { "@odata.id": "/redfish/v1/EventService" },
+        "ProtocolFeaturesSupported": { "SelectQuery": sim.select != SelectMode::Unadvertised },
+    }))
+}
+
+async fn system(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let power_state = sim.power_state.lock().unwrap().clone();
+    let boot_order = sim.boot_order.lock().unwrap().clone();
+    let (override_target, override_enabled) = sim.boot_override.lock().unwrap().clone();
+    let last_power_reason = sim.last_power_reason.lock().unwrap().clone();
+    // POST takes one power delay after reaching On, the OS one more.
+    let boot_progress = match *sim.powered_on_at.lock().unwrap() {
+        Some(at) if power_state == "On" && at.elapsed() < sim.power_delay => "MemoryInitializationStarted",
+        Some(at) if power_state == "On" && at.elapsed() < sim.power_delay * 2 => "OSBootStarted",
+        Some(_) if power_state == "On" => "OSRunning",
+        _ => "None",
+    };
+    sim.respond_selected(&req, json!({
+        "BootProgress": { "LastState": boot_progress },
+        "@odata.id": "/redfish/v1/Systems/System.Embedded.1",
+        "Boot": {
+            "BootOrder": boot_order,
+            "BootOptions": { "@odata.id": BOOT_OPTIONS_PATH },
+            "BootSourceOverrideTarget": override_target,
+            "BootSourceOverrideEnabled": override_enabled,
+        },
+        "Id": "System.Embedded.1",
+        "HostName": "fake-host.lab",
+        "Model": "PowerEdge R740 (simulated)",
+        "SKU": "FAKE123",
+        "PowerState": power_state,
+        "BiosVersion": "2.19.1",
+        "Status": { "Health": "OK", "HealthRollup": "OK", "State": "Enabled" },
+        "ProcessorSummary": { "Count": 2, "Model": "Intel(R) Xeon(R) Gold 6130 CPU @ 2.10GHz" },
+        "MemorySummary": { "TotalSystemMemoryGiB": 192.0 },
+        "Oem": {
+            "Dell": {
+                "DellSystem": {
+                    "CPURollupStatus": "OK",
+                    "FanRollupStatus": "OK",
+                    "PSRollupStatus": "OK",
+                    "StorageRollupStatus": "OK",
+                    "SysMemPrimaryStatus": "OK",
+                    "TempRollupStatus": "OK",
+                    "VoltRollupStatus": "OK",
+                    "LastPowerChangeReasonCode": last_power_reason,
+                }
+            }
+        },
+        "Actions": {
+            "#ComputerSystem.Reset": {
+                "target": "/redfish/v1/Systems/System.Embedded.1/Actions/ComputerSystem.Reset",
+                "ResetType@Redfish.AllowableValues": ["On", "ForceOff", "GracefulShutdown", "GracefulRestart", "ForceRestart", "PowerCycle", "Nmi"],
+            }
+        },
+    }))
+}
+
+async fn reset(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+
+    let reset_type = body["ResetType"].as_str().unwrap_or_default().to_string();
+    let current = sim.power_state.lock().unwrap().clone();
+
+    let target = match (reset_type.as_str(), current.as_str()) {
+        ("On", "On") | ("ForceOff", "Off") | ("GracefulShutdown", "Off") => {
+            return HttpResponse::Conflict().json(redfish_error("Server is already in the requested power state"));
+        }
+        ("On", _) => "On",
+        ("ForceOff", _) | ("GracefulShutdown", _) => "Off",
+        ("GracefulRestart", _) | ("ForceRestart", _) | ("PowerCycle", _) | ("Nmi", _) => "On",
+        _ => return HttpResponse::BadRequest().json(redfish_error("Unsupported ResetType")),
+    };
+
+    info!("Reset {} requested: {} -> {}", reset_type, current, target);
+    sim.push_lc("OK", &format!("Successfully performed the {} operation.", reset_type), "SYS1001");
+    let transitional = if target == "On" { "PoweringOn" } else { "PoweringOff" };
+    *sim.power_state.lock().unwrap() = transitional.to_string();
+
+    let sim = sim.into_inner();
+    let delay = sim.power_delay;
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        *sim.power_state.lock().unwrap() = target.to_string();
+        *sim.last_power_reason.lock().unwrap() = "Operator Initiated".to_string();
+        if target == "On" {
+            *sim.powered_on_at.lock().unwrap() = Some(Instant::now());
+            // Staged BIOS settings apply as the host boots.
+            let pending = std::mem::take(&mut *sim.bios_pending.lock().unwrap());
+            sim.bios_attributes.lock().unwrap().extend(pending);
+            sim.run_scheduled_jobs();
+        }
+        sim.push_sel("OK", &format!("The system power state changed to {}.", target), "SYS1003");
+    });
+
+    HttpResponse::NoContent().finish()
+}
+
+async fn thermal(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let on = *sim.power_state.lock().unwrap() == "On";
+    let mut rng = rand::thread_rng();
+    let base = if on { 45.0 } else { 24.0 };
+    let temperatures: Vec<serde_json::Value> = ["CPU1 Temp", "CPU2 Temp", "System Board Inlet Temp"]
+        .iter()
+        .map(|name| json!({
+            "Name": name,
+            "ReadingCelsius": base + rng.gen_range(-3.0..6.0_f64).round(),
+            "UpperThresholdCritical": 90.0,
+            "Status": { "Health": "OK", "State": "Enabled" },
+        }))
+        .collect();
+    let fans: Vec<serde_json::Value> = (1..=4)
+        .map(|i| json!({
+            "Name": format!("System Board Fan{}", i),
+            "Reading": if on { rng.gen_range(4800..7200) } else { 0 },
+            "ReadingUnits": "RPM",
+            "Status": { "Health": "OK", "State": "Enabled" },
+        }))
+        .collect();
+
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Chassis/System.Embedded.1/Thermal",
+        "Temperatures": temperatures,
+        "Fans": fans,
+    }))
+}
+
+async fn power(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let on = *sim.power_state.lock().unwrap() == "On";
+    let mut rng = rand::thread_rng();
+    let limit = *sim.power_limit.lock().unwrap();
+    let mut watts = if on { rng.gen_range(180..320) } else { rng.gen_range(8..14) };
+    if let Some(limit) = limit {
+        watts = watts.min(limit);
+    }
+
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Chassis/System.Embedded.1/Power",
+        "PowerControl": [{
+            "Name": "System Power Control",
+            "PowerConsumedWatts": watts,
+            "PowerCapacityWatts": 1100,
+            "PowerLimit": { "LimitInWatts": limit },
+        }],
+        "PowerSupplies": (1..=2).map(|i| {
+            let fault = if i == 2 { sim.psu_fault } else { PsuFault::None };
+            let (voltage, health) = match fault {
+                PsuFault::None => (230 + rng.gen_range(-4..4), "OK"),
+                PsuFault::Failed => (230, "Critical"),
+                PsuFault::InputLost => (0, "Critical"),
+                PsuFault::Degraded => (230, "Warning"),
+            };
+            json!({
+                "Name": format!("PS{} Status", i),
+                "LineInputVoltage": voltage,
+                "PowerCapacityWatts": 750,
+                "Status": { "Health": health, "State": "Enabled" },
+            })
+        }).collect::<Vec<_>>(),
+    }))
+}
+
+async fn set_power_limit(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let limit = &body["PowerControl"][0]["PowerLimit"]["LimitInWatts"];
+    if !(limit.is_null() || limit.is_u64()) {
+        return HttpResponse::BadRequest().json(json!({ "error": "LimitInWatts must be an integer or null" }));
+    }
+    *sim.power_limit.lock().unwrap() = limit.as_u64();
+    info!("Power limit set to {:?}", limit.as_u64());
+    HttpResponse::NoContent().finish()
+}
+
+const BOOT_OPTIONS_PATH: &str = "/redfish/v1/Systems/System.Embedded.1/BootOptions";
+
+/// `(BootOptionReference, DisplayName, UefiDevicePath)`
+const BOOT_OPTIONS: &[(&str, &str, &str)] = &[
+    ("Boot0001", "Integrated RAID Controller 1: Ubuntu", "HD(1,GPT,8C3F...,0x800,0x100000)/\\EFI\\ubuntu\\shimx64.efi"),
+    ("Boot0002", "PXE Device 1: Integrated NIC 1 Port 1 Partition 1", "VenHw(3A191845-5F86-4E78-8FCE-C4CFF59F9DAA)"),
+    ("Boot0003", "Virtual Optical Drive", "PciRoot(0x0)/Pci(0x14,0x0)/USB(0xD,0x0)/USB(0x0,0x0)/Unit(0x1)"),
+];
+
+async fn boot_options(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    HttpResponse::Ok().json(json!({
+        "@odata.id": BOOT_OPTIONS_PATH,
+        "Members": BOOT_OPTIONS
+            .iter()
+            .map(|(id, _, _)| json!({ "@odata.id": format!("{}/{}", BOOT_OPTIONS_PATH, id) }))
+            .collect::<Vec<_>>(),
+        "Members@odata.count": BOOT_OPTIONS.len(),
+    }))
+}
+
+async fn boot_option(req: HttpRequest, sim: web::Data<Simulator>, path: web::Path<String>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let id = path.into_inner();
+    match BOOT_OPTIONS.iter().find(|(option, _, _)| *option == id) {
+        Some((id, name, device_path)) => HttpResponse::Ok().json(json!({
+            "@odata.id": format!("{}/{}", BOOT_OPTIONS_PATH, id),
+            "Id": id,
+            "BootOptionReference": id,
+            "DisplayName": name,
+            "UefiDevicePath": device_path,
+            "BootOptionEnabled": true,
+        })),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// Accepts `Boot.BootOrder` and stages it as a job, like a real iDRAC, or
+/// a boot source override, which applies immediately.
+async fn patch_system(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    if let Some(target) = body["Boot"]["BootSourceOverrideTarget"].as_str() {
+        let enabled = body["Boot"]["BootSourceOverrideEnabled"].as_str().unwrap_or("Once");
+        info!("Boot override set to {} ({})", target, enabled);
+        *sim.boot_override.lock().unwrap() = (target.to_string(), enabled.to_string());
+        return HttpResponse::NoContent().finish();
+    }
+    let Some(order) = body["Boot"]["BootOrder"].as_array() else {
+        return HttpResponse::BadRequest().json(json!({ "error": "only Boot.BootOrder can be patched" }));
+    };
+    let order: Vec<String> = order.iter().filter_map(|id| id.as_str().map(str::to_string)).collect();
+    info!("Boot order set to {:?}", order);
+    *sim.boot_order.lock().unwrap() = order;
+
+    let job = format!("JID_{}", rand::thread_rng().gen_range(100_000_000u64..999_999_999));
+    HttpResponse::Accepted()
+        .insert_header(("Location", format!("/redfish/v1/Managers/iDRAC.Embedded.1/Jobs/{}", job)))
+        .finish()
+}
+
+/// The slice of the BIOS attribute registry the simulator implements.
+fn bios_registry() -> serde_json::Value {
+    let enumeration = |name: &str, values: &[&str]| json!({
+        "AttributeName": name,
+        "Type": "Enumeration",
+        "ReadOnly": false,
+        "Value": values.iter().map(|v| json!({ "ValueName": v })).collect::<Vec<_>>(),
+    });
+    json!({
+        "@odata.id": "/redfish/v1/Registries/BiosAttributeRegistry.json",
+        "RegistryEntries": {
+            "Attributes": [
+                enumeration("SysProfile", &[
+                    "PerfOptimized",
+                    "PerfPerWattOptimizedDapc",
+                    "PerfPerWattOptimizedOs",
+                    "DenseCfgOptimized",
+                    "Custom",
+                ]),
+                enumeration("PostWatchdogTimer", &["Enabled", "Disabled"]),
+                {
+                    "AttributeName": "PostWatchdogTimeout",
+                    "Type": "Integer",
+                    "ReadOnly": false,
+                    "LowerBound": 1,
+                    "UpperBound": 60,
+                },
+            ]
+        }
+    })
+}
+
+fn initial_bios_attributes() -> serde_json::Map<String, serde_json::Value> {
+    let mut attributes = serde_json::Map::new();
+    attributes.insert("SysProfile".to_string(), json!("PerfPerWattOptimizedDapc"));
+    attributes.insert("PostWatchdogTimer".to_string(), json!("Disabled"));
+    attributes.insert("PostWatchdogTimeout".to_string(), json!(10));
+    attributes
+}
+
+async fn bios_attribute_registry(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    HttpResponse::Ok().json(bios_registry())
+}
+
+async fn bios(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let applied = sim.bios_attributes.lock().unwrap().clone();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Systems/System.Embedded.1/Bios",
+        "Attributes": applied,
+        "@Redfish.Settings": {
+            "SettingsObject": { "@odata.id": "/redfish/v1/Systems/System.Embedded.1/Bios/Settings" },
+        },
+    }))
+}
+
+/// Like a real iDRAC, lists only the attributes staged for the next boot.
+async fn bios_settings(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let staged = sim.bios_pending.lock().unwrap().clone();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Systems/System.Embedded.1/Bios/Settings",
+        "Attributes": staged,
+    }))
+}
+
+/// Stages known attributes until the next power on.
+async fn patch_bios_settings(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let Some(changes) = body["Attributes"].as_object() else {
+        return HttpResponse::BadRequest().json(redfish_error("Attributes must be an object"));
+    };
+    let known = sim.bios_attributes.lock().unwrap();
+    if let Some(unknown) = changes.keys().find(|name| !known.contains_key(*name)) {
+        return HttpResponse::BadRequest().json(redfish_error(&format!("Unknown BIOS attribute {}", unknown)));
+    }
+    drop(known);
+    info!("BIOS attributes staged: {}", serde_json::Value::Object(changes.clone()));
+    sim.bios_pending.lock().unwrap().extend(changes.clone());
+    HttpResponse::Accepted().finish()
+}
+
+/// Stages a firmware update as a job that runs on the next power on. An
+/// `ImageURI` containing `corrupt` makes the job fail.
+async fn simple_update(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let Some(image_uri) = body["ImageURI"].as_str() else {
+        return HttpResponse::BadRequest().json(redfish_error("ImageURI is required"));
+    };
+    let job = format!("JID_{}", rand::thread_rng().gen_range(100_000_000u64..999_999_999));
+    info!("Firmware update from {} staged as {}", image_uri, job);
+    sim.jobs.lock().unwrap().push(json!({
+        "@odata.id": format!("/redfish/v1/Managers/iDRAC.Embedded.1/Jobs/{}", job),
+        "Id": job,
+        "Name": format!("Firmware Update: {}", image_uri),
+        "JobType": "FirmwareUpdate",
+        "JobState": "Scheduled",
+        "PercentComplete": 0,
+        "Message": "Task successfully scheduled.",
+    }));
+    HttpResponse::Accepted()
+        .insert_header(("Location", format!("/redfish/v1/Managers/iDRAC.Embedded.1/Jobs/{}", job)))
+        .finish()
+}
+
+/// The job queue, always expanded.
+async fn jobs(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let members = sim.jobs.lock().unwrap().clone();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Managers/iDRAC.Embedded.1/Jobs",
+        "Members@odata.count": members.len(),
+        "Members": members,
+    }))
+}
+
+async fn job(req: HttpRequest, sim: web::Data<Simulator>, path: web::Path<String>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let id = path.into_inner();
+    match sim.jobs.lock().unwrap().iter().find(|job| job["Id"] == id.as_str()) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().json(redfish_error("No such job")),
+    }
+}
+
+/// Accepts a BIOS reset as a job that would run on the next reboot.
+async fn reset_bios(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let job = format!("JID_{}", rand::thread_rng().gen_range(100_000_000u64..999_999_999));
+    info!("BIOS reset to defaults staged as {}", job);
+    HttpResponse::Accepted()
+        .insert_header(("Location", format!("/redfish/v1/Managers/iDRAC.Embedded.1/Jobs/{}", job)))
+        .finish()
+}
+
+async fn manager(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Managers/iDRAC.Embedded.1",
+        "Id": "iDRAC.Embedded.1",
+        "FirmwareVersion": "6.10.30.00",
+        "DateTime": (chrono::Utc::now() + *sim.clock_skew.lock().unwrap()).to_rfc3339(),
+        "Model": "14G Monolithic",
+    }))
+}
+
+fn initial_idrac_attributes() -> serde_json::Map<String, serde_json::Value> {
+    let mut attributes = serde_json::Map::new();
+    attributes.insert("NIC.1.Selection".to_string(), json!("Dedicated"));
+    attributes.insert("NIC.1.Failover".to_string(), json!("None"));
+    attributes
+}
+
+async fn idrac_attributes(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let attributes = sim.idrac_attributes.lock().unwrap().clone();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Managers/iDRAC.Embedded.1/Attributes",
+        "Attributes": attributes,
+    }))
+}
+
+/// Applies any attribute; a real iDRAC has hundreds this does not model.
+async fn patch_idrac_attributes(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let Some(changes) = body["Attributes"].as_object() else {
+        return HttpResponse::BadRequest().json(redfish_error("Attributes must be an object"));
+    };
+    info!("iDRAC attributes set: {}", serde_json::Value::Object(changes.clone()));
+    sim.idrac_attributes.lock().unwrap().extend(changes.clone());
+    HttpResponse::Ok().finish()
+}
+
+async fn set_manager(req: HttpRequest, sim: web::Data<Simulator>, body: web::Json<serde_json::Value>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let Some(raw) = body["DateTime"].as_str() else {
+        return HttpResponse::BadRequest().json(redfish_error("Only DateTime can be changed"));
+    };
+    match chrono::DateTime::parse_from_rfc3339(raw) {
+        Ok(time) => {
+            *sim.clock_skew.lock().unwrap() = time.with_timezone(&chrono::Utc) - chrono::Utc::now();
+            info!("Manager clock set to {}", raw);
+            HttpResponse::NoContent().finish()
+        }
+        Err(_) => HttpResponse::BadRequest().json(redfish_error(&format!("Invalid DateTime {}", raw))),
+    }
+}
+
+const METRIC_REPORT_DEFINITIONS_PATH: &str = "/redfish/v1/TelemetryService/MetricReportDefinitions";
+
+async fn create_metric_report_definition(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let Some(id) = body["Id"].as_str().filter(|id| !id.is_empty()) else {
+        return HttpResponse::BadRequest().json(redfish_error("Id is required"));
+    };
+    if body["Metrics"].as_array().is_none_or(|metrics| metrics.is_empty()) {
+        return HttpResponse::BadRequest().json(redfish_error("Metrics is required"));
+    }
+
+    let mut definitions = sim.metric_report_definitions.lock().unwrap();
+    if definitions.iter().any(|existing| existing == id) {
+        return HttpResponse::Conflict().json(redfish_error(&format!("{} already exists", id)));
+    }
+    definitions.push(id.to_string());
+    info!("Metric report definition {} created", id);
+    HttpResponse::Created()
+        .insert_header(("Location", format!("{}/{}", METRIC_REPORT_DEFINITIONS_PATH, id)))
+        .json(body.into_inner())
+}
+
+async fn delete_metric_report_definition(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let id = path.into_inner();
+    let mut definitions = sim.metric_report_definitions.lock().unwrap();
+    match definitions.iter().position(|existing| *existing == id) {
+        Some(index) => {
+            definitions.remove(index);
+            info!("Metric report definition {} deleted", id);
+            HttpResponse::NoContent().finish()
+        }
+        None => HttpResponse::NotFound().json(redfish_error(&format!("No metric report definition {}", id))),
+    }
+}
+
+const CERTIFICATES_PATH: &str = "/redfish/v1/Managers/iDRAC.Embedded.1/NetworkProtocol/HTTPS/Certificates";
+
+async fn certificates(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    HttpResponse::Ok().json(json!({
+        "@odata.id": CERTIFICATES_PATH,
+        "Members": [{ "@odata.id": format!("{}/SecurityCertificate.1", CERTIFICATES_PATH) }],
+        "Members@odata.count": 1,
+    }))
+}
+
+async fn certificate(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let now = chrono::Utc::now();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": format!("{}/SecurityCertificate.1", CERTIFICATES_PATH),
+        "Id": "SecurityCertificate.1",
+        "Subject": { "CommonName": "idrac-fake", "Organization": "Dell Inc.", "Country": "US" },
+        "Issuer": { "CommonName": "idrac-fake", "Organization": "Dell Inc.", "Country": "US" },
+        "ValidNotBefore": (now - chrono::Duration::days(335)).to_rfc3339(),
+        "ValidNotAfter": (now + chrono::Duration::days(30)).to_rfc3339(),
+        "SignatureAlgorithm": "sha256WithRSAEncryption",
+    }))
+}
+
+async fn import_license(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    match body.get("LicenseFile").and_then(|v| v.as_str()) {
+        Some(file) if !file.is_empty() => {
+            info!("Imported license ({} bytes base64)", file.len());
+            HttpResponse::Ok().json(json!({}))
+        }
+        _ => HttpResponse::BadRequest().json(json!({
+            "error": { "message": "LicenseFile is required" }
+        })),
+    }
+}
+
+async fn insert_virtual_media(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    match body.get("Image").and_then(|v| v.as_str()) {
+        Some(image) if !image.is_empty() => {
+            info!("Virtual CD inserted: {}", image);
+            *sim.virtual_cd.lock().unwrap() = Some(image.to_string());
+            HttpResponse::NoContent().finish()
+        }
+        _ => HttpResponse::BadRequest().json(redfish_error("Image is required")),
+    }
+}
+
+async fn reset_to_defaults(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    match body.get("ResetType").and_then(|v| v.as_str()) {
+        Some(reset_type @ ("All" | "ResetAllWithRootDefaults" | "Default")) => {
+            info!("Factory reset requested (ResetType {})", reset_type);
+            HttpResponse::Ok().json(json!({}))
+        }
+        _ => HttpResponse::BadRequest().json(json!({
+            "error": { "message": "ResetType must be All, ResetAllWithRootDefaults or Default" }
+        })),
+    }
+}
+
+const FIRMWARE_INVENTORY_PATH: &str = "/redfish/v1/UpdateService/FirmwareInventory";
+
+/// (id, name, version) of each firmware inventory entry.
+const FIRMWARE: &[(&str, &str, &str)] = &[
+    ("Installed-25227-6.10.30.00__iDRAC.Embedded.1-1", "Integrated Dell Remote Access Controller", "6.10.30.00"),
+    ("Previous-25227-6.00.30.00__iDRAC.Embedded.1-1", "Integrated Dell Remote Access Controller", "6.00.30.00"),
+    ("Installed-159-2.19.1__BIOS.Setup.1-1", "BIOS", "2.19.1"),
+    ("Installed-108255-22.31.6__NIC.Integrated.1-1-1", "Broadcom Gigabit Ethernet BCM5720", "22.31.6"),
+];
+
+/// Always answers as if `$expand` was requested.
+async fn firmware_inventory(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let members: Vec<serde_json::Value> = FIRMWARE
+        .iter()
+        .map(|(id, name, version)| {
+            json!({
+                "@odata.id": format!("{}/{}", FIRMWARE_INVENTORY_PATH, id),
+                "Id": id,
+                "Name": name,
+                "Version": version,
+                "Updateable": true,
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": FIRMWARE_INVENTORY_PATH,
+        "Members@odata.count": members.len(),
+        "Members": members,
+    }))
+}
+
+async fn session_service(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/SessionService",
+        "ServiceEnabled": true,
+        "SessionTimeout": 1800,
+        "Sessions": { "@odata.id": "/redfish/v1/SessionService/Sessions" },
+    }))
+}
+
+async fn sel_entries(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let sel = sim.sel.lock().unwrap();
+    let members: Vec<serde_json::Value> = sel.iter().rev().cloned().collect();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Managers/iDRAC.Embedded.1/LogServices/Sel/Entries",
+        "Members@odata.count": members.len(),
+        "Members": members,
+    }))
+}
+
+async fn lc_entries(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let lc = sim.lc.lock().unwrap();
+    let members: Vec<serde_json::Value> = lc.iter().rev().cloned().collect();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Managers/iDRAC.Embedded.1/LogServices/Lclog/Entries",
+        "Members@odata.count": members.len(),
+        "Members": members,
+    }))
+}
+
+const STORAGE_PATH: &str = "/redfish/v1/Systems/System.Embedded.1/Storage";
+
+async fn storage(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    HttpResponse::Ok().json(json!({
+        "@odata.id": STORAGE_PATH,
+        "Members@odata.count": 1,
+        "Members": [{ "@odata.id": format!("{}/RAID.Integrated.1-1", STORAGE_PATH) }],
+    }))
+}
+
+async fn storage_controller(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let drives: Vec<serde_json::Value> = (0..4)
+        .map(|i| json!({ "@odata.id": format!("{}/Drives/Disk.Bay.{}:Enclosure.Internal.0-1:RAID.Integrated.1-1", STORAGE_PATH, i) }))
+        .collect();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": format!("{}/RAID.Integrated.1-1", STORAGE_PATH),
+        "Id": "RAID.Integrated.1-1",
+        "Name": "PERC H730P Mini",
+        "Status": { "Health": "OK", "HealthRollup": "OK", "State": "Enabled" },
+        "Drives@odata.count": drives.len(),
+        "Drives": drives,
+    }))
+}
+
+const ACCOUNTS_PATH: &str = "/redfish/v1/AccountService/Accounts";
+
+fn initial_accounts(root: &str) -> Vec<serde_json::Value> {
+    (1..=16)
+        .map(|slot| {
+            let (username, role, enabled) = if slot == 2 { (root, "Administrator", true) } else { ("", "None", false) };
+            json!({
+                "@odata.id": format!("{}/{}", ACCOUNTS_PATH, slot),
+                "Id": slot.to_string(),
+                "UserName": username,
+                "Password": null,
+                "RoleId": role,
+                "Enabled": enabled,
+            })
+        })
+        .collect()
+}
+
+/// The account collection; members are links unless `$expand` is asked
+/// for.
+async fn accounts(req: HttpRequest, sim: web::Data<Simulator>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let expand = req.query_string().contains("expand=");
+    let accounts = sim.accounts.lock().unwrap();
+    let members: Vec<serde_json::Value> = accounts
+        .iter()
+        .map(|account| if expand { account.clone() } else { json!({ "@odata.id": account["@odata.id"] }) })
+        .collect();
+    HttpResponse::Ok().json(json!({
+        "@odata.id": ACCOUNTS_PATH,
+        "Members@odata.count": members.len(),
+        "Members": members,
+    }))
+}
+
+async fn account(req: HttpRequest, sim: web::Data<Simulator>, path: web::Path<String>) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let accounts = sim.accounts.lock().unwrap();
+    match accounts.iter().find(|account| account["Id"] == *path) {
+        Some(account) => HttpResponse::Ok().json(account),
+        None => HttpResponse::NotFound().json(redfish_error("No such account")),
+    }
+}
+
+async fn patch_account(
+    req: HttpRequest,
+    sim: web::Data<Simulator>,
+    path: web::Path<String>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    if let Some(denied) = sim.check(&req) {
+        return denied;
+    }
+    let Some(changes) = body.as_object() else {
+        return HttpResponse::BadRequest().json(redfish_error("Body must be an object"));
+    };
+    if let Some(role) = changes.get("RoleId").and_then(|role| role.as_str()) {
+        if !["Administrator", "Operator", "ReadOnly", "None"].contains(&role) {
+            return HttpResponse::BadRequest().json(redfish_error("Unknown RoleId"));
+        }
+    }
+    let mut accounts = sim.accounts.lock().unwrap();
+    let Some(account) = accounts.iter_mut().find(|account| account["Id"] == *path) else {
+        return HttpResponse::NotFound().json(redfish_error("No such account"));
+    };
+    if *path == "1" {
+        return HttpResponse::BadRequest().json(redfish_error("Account 1 is reserved"));
+    }
+    for (key, value) in changes {
+        // Passwords are write-only, as on a real iDRAC.
+        if key != "Password" {
+            account[key] = value.clone();
+        }
+    }
+    info!("Account {} set: {}", path, account);
+    HttpResponse::Ok().finish()
+}
+
+fn self_signed_acceptor() -> Result<SslAcceptorBuilder, openssl::error::ErrorStack> {
+    let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_text("CN", "fake-idrac")?;
+    let name = name.build();
+
+    let mut cert = X509::builder()?;
+    cert.set_version(2)?;
+    cert.set_subject_name(&name)?;
+    cert.set_issuer_name(&name)?;
+    cert.set_pubkey(&key)?;
+    cert.set_not_before(Asn1Time::days_from_now(0)?.as_ref())?;
+    cert.set_not_after(Asn1Time::days_from_now(365)?.as_ref())?;
+    cert.sign(&key, MessageDigest::sha256())?;
+    let cert = cert.build();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    acceptor.set_private_key(&key)?;
+    acceptor.set_certificate(&cert)?;
+    Ok(acceptor)
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let options = match Options::parse() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let credentials = format!("{}:{}", options.username, options.password);
+    let simulator = web::Data::new(Simulator {
+        power_state: Mutex::new("Off".to_string()),
+        powered_on_at: Mutex::new(None),
+        last_power_reason: Mutex::new("AC Power Restored".to_string()),
+        sel: Mutex::new(Vec::new()),
+        lc: Mutex::new(Vec::new()),
+        expected_auth: format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials)),
+        power_delay: options.power_delay,
+        unavailable_until: Mutex::new(options.fail_for.map(|d| Instant::now() + d)),
+        clock_skew: Mutex::new(chrono::Duration::seconds(options.clock_skew_secs)),
+        power_limit: Mutex::new(None),
+        boot_order: Mutex::new(BOOT_OPTIONS.iter().map(|(id, _, _)| id.to_string()).collect()),
+        boot_override: Mutex::new(("None".to_string(), "Disabled".to_string())),
+        virtual_cd: Mutex::new(None),
+        bios_attributes: Mutex::new(initial_bios_attributes()),
+        bios_pending: Mutex::new(serde_json::Map::new()),
+        idrac_attributes: Mutex::new(initial_idrac_attributes()),
+        metric_report_definitions: Mutex::new(Vec::new()),
+        accounts: Mutex::new(initial_accounts(&options.username)),
+        jobs: Mutex::new(Vec::new()),
+        select: options.select,
+        body_quirk: options.body_quirk,
+        psu_fault: options.psu_fault,
+    });
+    simulator.push_sel("OK", "Log cleared.", "SEL9901");
+
+    // Grow the SEL over time so pollers have something to pick up
+    let sel_sim = simulator.clone();
+    let sel_interval = options.sel_interval;
+    tokio::spawn(async move {
+        let messages = [
+            ("OK", "The system inlet temperature is within range.", "TMP0118"),
+            ("Warning", "The system inlet temperature is greater than the upper warning threshold.", "TMP0120"),
+            ("OK", "A fan redundancy check completed successfully.", "FAN1000"),
+        ];
+        let mut interval = tokio::time::interval(sel_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let (severity, message, id) = messages[rand::thread_rng().gen_range(0..messages.len())];
+            sel_sim.push_sel(severity, message, id);
+        }
+    });
+
+    let address = ("0.0.0.0", options.port);
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(simulator.clone())
+            .route("/redfish/v1", web::get().to(service_root))
+            .route("/redfish/v1/Systems/System.Embedded.1", web::get().to(system))
+            .route("/redfish/v1/Systems/System.Embedded.1", web::patch().to(patch_system))
+            .route("/redfish/v1/Registries/BiosAttributeRegistry.json", web::get().to(bios_attribute_registry))
+            .route("/redfish/v1/Systems/System.Embedded.1/Bios", web::get().to(bios))
+            .route("/redfish/v1/Systems/System.Embedded.1/Bios/Settings", web::get().to(bios_settings))
+            .route("/redfish/v1/Systems/System.Embedded.1/Bios/Settings", web::patch().to(patch_bios_settings))
+            .route(
+                "/redfish/v1/Systems/System.Embedded.1/Bios/Actions/Bios.ResetBios",
+                web::post().to(reset_bios),
+            )
+            .route(
+                "/redfish/v1/UpdateService/Actions/UpdateService.SimpleUpdate",
+                web::post().to(simple_update),
+            )
+            .route("/redfish/v1/Managers/iDRAC.Embedded.1/Jobs", web::get().to(jobs))
+            .route("/redfish/v1/Managers/iDRAC.Embedded.1/Jobs/{id}", web::get().to(job))
+            .route(BOOT_OPTIONS_PATH, web::get().to(boot_options))
+            .route(
+                "/redfish/v1/Systems/System.Embedded.1/BootOptions/{id}",
+                web::get().to(boot_option),
+            )
+            .route(
+                "/redfish/v1/Systems/System.Embedded.1/Actions/ComputerSystem.Reset",
+                web::post().to(reset),
+            )
+            .route("/redfish/v1/Chassis/System.Embedded.1/Thermal", web::get().to(thermal))
+            .route("/redfish/v1/Chassis/System.Embedded.1/Power", web::get().to(power))
+            .route("/redfish/v1/Chassis/System.Embedded.1/Power", web::patch().to(set_power_limit))
+            .route("/redfish/v1/Managers/iDRAC.Embedded.1", web::get().to(manager))
+            .route("/redfish/v1/Managers/iDRAC.Embedded.1", web::patch().to(set_manager))
+            .route("/redfish/v1/Managers/iDRAC.Embedded.1/Attributes", web::get().to(idrac_attributes))
+            .route("/redfish/v1/Managers/iDRAC.Embedded.1/Attributes", web::patch().to(patch_idrac_attributes))
+            .route("/redfish/v1/SessionService", web::get().to(session_service))
+            .route(METRIC_REPORT_DEFINITIONS_PATH, web::post().to(create_metric_report_definition))
+            .route(
+                "/redfish/v1/TelemetryService/MetricReportDefinitions/{id}",
+                web::delete().to(delete_metric_report_definition),
+            )
+            .route(CERTIFICATES_PATH, web::get().to(certificates))
+            .route(FIRMWARE_INVENTORY_PATH, web::get().to(firmware_inventory))
+            .route(
+                "/redfish/v1/Managers/iDRAC.Embedded.1/NetworkProtocol/HTTPS/Certificates/SecurityCertificate.1",
+                web::get().to(certificate),
+            )
+            .route(
+                "/redfish/v1/Managers/iDRAC.Embedded.1/Oem/Dell/DellLicenseManagementService/Actions/DellLicenseManagementService.ImportLicense",
+                web::post().to(import_license),
+            )
+            .route(
+                "/redfish/v1/Managers/iDRAC.Embedded.1/VirtualMedia/CD/Actions/VirtualMedia.InsertMedia",
+                web::post().to(insert_virtual_media),
+            )
+            .route(
+                "/redfish/v1/Managers/iDRAC.Embedded.1/Actions/Oem/DellManager.ResetToDefaults",
+                web::post().to(reset_to_defaults),
+            )
+            .route(
+                "/redfish/v1/Managers/iDRAC.Embedded.1/LogServices/Sel/Entries",
+                web::get().to(sel_entries),
+            )
+            .route(
+                "/redfish/v1/Managers/iDRAC.Embedded.1/LogServices/Lclog/Entries",
+                web::get().to(lc_entries),
+            )
+            .route(STORAGE_PATH, web::get().to(storage))
+            .route(
+                "/redfish/v1/Systems/System.Embedded.1/Storage/RAID.Integrated.1-1",
+                web::get().to(storage_controller),
+            )
+            .route(ACCOUNTS_PATH, web::get().to(accounts))
+            .route("/redfish/v1/AccountService/Accounts/{id}", web::get().to(account))
+            .route("/redfish/v1/AccountService/Accounts/{id}", web::patch().to(patch_account))
+    });
+
+    if options.tls {
+        let acceptor = self_signed_acceptor().map_err(std::io::Error::other)?;
+        info!("Fake iDRAC listening on https://localhost:{} (self-signed)", options.port);
+        server.bind_openssl(address, acceptor)?.run().await
+    } else {
+        info!("Fake iDRAC listening on http://localhost:{}", options.port);
+        server.bind(address)?.run().await
+    }
+}
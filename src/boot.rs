@@ -0,0 +1,269 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+use crate::database::Operation;
+use crate::idrac::{IdracClient, SelEntry};
+use crate::operations::{record_stage, wait_for_power_state};
+use crate::state::AppState;
+
+/// Operation kind of a tracked boot.
+pub const OPERATION_KIND: &str = "boot";
+
+pub const STAGE_REQUESTED: &str = "power_on_requested";
+const STAGE_BMC_ON: &str = "bmc_on";
+const STAGE_POST_COMPLETE: &str = "post_complete";
+const STAGE_OS_REACHABLE: &str = "os_reachable";
+
+/// A boot that has not reached its last stage by then is given up on.
+const BOOT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const OS_PROBE_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// `BootProgress.LastState` values reached once POST has finished.
+pub const POST_COMPLETE_STATES: &[&str] = &["SystemHardwareInitializationComplete", "SetupEntered", "OSBootStarted", "OSRunning"];
+
+/// The latest boot is flagged when a stage takes this many times the
+/// median of the earlier boots, and at least `REGRESSION_MIN_EXTRA_SECS`
+/// longer, with `REGRESSION_MIN_SAMPLES` earlier boots to compare with.
+const REGRESSION_FACTOR: f64 = 1.5;
+const REGRESSION_MIN_EXTRA_SECS: f64 = 10.0;
+const REGRESSION_MIN_SAMPLES: usize = 3;
+
+/// Check a `host:port` to probe for OS readiness.
+pub fn validate_os_probe(target: &str) -> Result<String, String> {
+    let target = target.trim();
+    let valid = target
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok_and(|port| port > 0));
+    if valid {
+        Ok(target.to_string())
+    } else {
+        Err(format!("'{}' is not a host:port to probe", target))
+    }
+}
+
+/// Follow a boot started by a power-on command through BMC `On`, POST
+/// complete and, with `os_probe`, a TCP port on the host accepting
+/// connections, recording each as a stage of `operation_id`. Stages the
+/// iDRAC cannot report are recorded as unavailable instead.
+pub async fn track_boot(state: AppState, client: Arc<IdracClient>, operation_id: String, os_probe: Option<String>) {
+    let deadline = Instant::now() + BOOT_TIMEOUT;
+    let give_up = |e: &str| record_stage(&state, &operation_id, "timed_out", Some(e), "needs_attention");
+
+    match wait_for_power_state(&client, "On", deadline - Instant::now()).await {
+        Ok(elapsed) => {
+            let detail = format!("after {}s", elapsed.as_secs());
+            record_stage(&state, &operation_id, STAGE_BMC_ON, Some(&detail), "running");
+        }
+        Err(e) => return give_up(&e),
+    }
+
+    match wait_for_post(&client, deadline).await {
+        Ok(Some(last_state)) => record_stage(&state, &operation_id, STAGE_POST_COMPLETE, Some(&last_state), "running"),
+        Ok(None) => record_stage(
+            &state,
+            &operation_id,
+            "post_progress_unavailable",
+            Some("The iDRAC does not report BootProgress"),
+            "running",
+        ),
+        Err(e) => return give_up(&e),
+    }
+
+    if let Some(target) = os_probe {
+        match wait_for_port(&target, deadline).await {
+            Ok(()) => record_stage(&state, &operation_id, STAGE_OS_REACHABLE, Some(&target), "running"),
+            Err(e) => return give_up(&e),
+        }
+    }
+
+    info!("Boot {} finished", operation_id);
+    record_stage(&state, &operation_id, "finished", None, "completed");
+}
+
+/// Poll boot progress until POST has finished, returning the state that
+/// showed it, or `None` if the iDRAC does not report boot progress.
+async fn wait_for_post(client: &IdracClient, deadline: Instant) -> Result<Option<String>, String> {
+    loop {
+        match client.get_boot_progress().await {
+            Ok(None) => return Ok(None),
+            Ok(Some(last_state)) if POST_COMPLETE_STATES.contains(&last_state.as_str()) => return Ok(Some(last_state)),
+            Ok(Some(_)) => {}
+            Err(e) => warn!("Boot progress poll failed: {}", e),
+        }
+        if Instant::now() + POLL_INTERVAL >= deadline {
+            return Err(format!("POST did not complete within {}s", BOOT_TIMEOUT.as_secs()));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn wait_for_port(target: &str, deadline: Instant) -> Result<(), String> {
+    loop {
+        if let Ok(Ok(_)) = tokio::time::timeout(OS_PROBE_CONNECT_TIMEOUT, TcpStream::connect(target)).await {
+            return Ok(());
+        }
+        if Instant::now() + POLL_INTERVAL >= deadline {
+            return Err(format!("{} did not accept connections within {}s", target, BOOT_TIMEOUT.as_secs()));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// One tracked boot. Durations count from the power-on command and are
+/// `None` for stages that were not reached or could not be observed.
+#[derive(Debug, Serialize)]
+pub struct BootRecord {
+    pub operation_id: String,
+    pub status: String,
+    pub started_at: String,
+    pub to_bmc_on_secs: Option<i64>,
+    pub to_post_complete_secs: Option<i64>,
+    pub to_os_reachable_secs: Option<i64>,
+    /// SEL entries logged between the command and the end of the boot.
+    pub sel_entries: Vec<SelEntry>,
+}
+
+impl BootRecord {
+    fn secs_to(&self, stage: &str) -> Option<i64> {
+        match stage {
+            STAGE_BMC_ON => self.to_bmc_on_secs,
+            STAGE_POST_COMPLETE => self.to_post_complete_secs,
+            STAGE_OS_REACHABLE => self.to_os_reachable_secs,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct StageMedians {
+    pub to_bmc_on_secs: Option<f64>,
+    pub to_post_complete_secs: Option<f64>,
+    pub to_os_reachable_secs: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StageRegression {
+    pub stage: &'static str,
+    pub latest_secs: i64,
+    pub median_secs: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BootReport {
+    pub server: String,
+    /// Newest first.
+    pub boots: Vec<BootRecord>,
+    /// Medians of the boots before the latest; unknown durations are left out.
+    pub medians: StageMedians,
+    /// Stages of the latest boot that were much slower than `medians`.
+    pub regressions: Vec<StageRegression>,
+    /// Why `sel_entries` could not be filled in.
+    pub sel_error: Option<String>,
+}
+
+fn parse_time(at: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(at).ok().map(|at| at.with_timezone(&Utc))
+}
+
+fn boot_record(operation: Operation, sel: &[SelEntry]) -> BootRecord {
+    let started = operation
+        .stages
+        .iter()
+        .find(|stage| stage.name == STAGE_REQUESTED)
+        .and_then(|stage| parse_time(&stage.at));
+    let secs_to = |name: &str| {
+        let reached = operation.stages.iter().find(|stage| stage.name == name)?;
+        Some((parse_time(&reached.at)? - started?).num_seconds())
+    };
+    let to_bmc_on_secs = secs_to(STAGE_BMC_ON);
+    let to_post_complete_secs = secs_to(STAGE_POST_COMPLETE);
+    let to_os_reachable_secs = secs_to(STAGE_OS_REACHABLE);
+
+    let ended = match operation.status.as_str() {
+        "running" => Some(Utc::now()),
+        _ => operation.stages.last().and_then(|stage| parse_time(&stage.at)),
+    };
+    let sel_entries = match (started, ended) {
+        (Some(started), Some(ended)) => sel
+            .iter()
+            .filter(|entry| parse_time(&entry.created).is_some_and(|created| created >= started && created <= ended))
+            .cloned()
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    BootRecord {
+        started_at: started.map(|at| at.to_rfc3339()).unwrap_or(operation.created_at),
+        operation_id: operation.id,
+        status: operation.status,
+        to_bmc_on_secs,
+        to_post_complete_secs,
+        to_os_reachable_secs,
+        sel_entries,
+    }
+}
+
+fn median(values: impl Iterator<Item = i64>) -> Option<(f64, usize)> {
+    let mut values: Vec<i64> = values.collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let middle = values.len() / 2;
+    let median = if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) as f64 / 2.0
+    } else {
+        values[middle] as f64
+    };
+    Some((median, values.len()))
+}
+
+/// Summarize tracked boots, newest first, with the SEL of the server.
+pub fn build_report(server: &str, operations: Vec<Operation>, sel: Result<Vec<SelEntry>, String>) -> BootReport {
+    let (sel, sel_error) = match sel {
+        Ok(sel) => (sel, None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    let boots: Vec<BootRecord> = operations.into_iter().map(|operation| boot_record(operation, &sel)).collect();
+
+    let mut medians = StageMedians::default();
+    let mut regressions = Vec::new();
+    if let Some((latest, earlier)) = boots.split_first() {
+        for stage in [STAGE_BMC_ON, STAGE_POST_COMPLETE, STAGE_OS_REACHABLE] {
+            let Some((median_secs, samples)) = median(earlier.iter().filter_map(|boot| boot.secs_to(stage))) else {
+                continue;
+            };
+            match stage {
+                STAGE_BMC_ON => medians.to_bmc_on_secs = Some(median_secs),
+                STAGE_POST_COMPLETE => medians.to_post_complete_secs = Some(median_secs),
+                _ => medians.to_os_reachable_secs = Some(median_secs),
+            }
+            let Some(latest_secs) = latest.secs_to(stage) else {
+                continue;
+            };
+            let slower = latest_secs as f64 > median_secs * REGRESSION_FACTOR
+                && latest_secs as f64 - median_secs >= REGRESSION_MIN_EXTRA_SECS;
+            if samples >= REGRESSION_MIN_SAMPLES && slower {
+                regressions.push(StageRegression {
+                    stage,
+                    latest_secs,
+                    median_secs,
+                });
+            }
+        }
+    }
+
+    BootReport {
+        server: server.to_string(),
+        boots,
+        medians,
+        regressions,
+        sel_error,
+    }
+}
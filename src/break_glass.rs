@@ -0,0 +1,144 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::audit::AuditAction;
+use crate::config::Config;
+use crate::database::{BreakGlassGrant, Database, UserRole, SQLITE_TIMESTAMP_FORMAT};
+use crate::scrub;
+use crate::tokens;
+
+/// Account every break-glass session runs as, so its actions are
+/// attributable and flagged. Nobody knows its password.
+pub const BREAK_GLASS_USERNAME: &str = "break-glass";
+
+/// Session keys set when a session was started from a break-glass link.
+pub const SESSION_GRANT_KEY: &str = "break_glass_id";
+pub const SESSION_EXPIRES_KEY: &str = "break_glass_expires_at";
+
+const DEFAULT_DURATION: &str = "30m";
+const MAX_DURATION_HOURS: i64 = 24;
+
+const USAGE: &str = "Usage: idrac-controller break-glass --reason TEXT [--duration 30m]
+
+Issues a one-time link that starts a single admin session, for when nobody
+can log in. Reads the same environment as the server. The link and its
+session expire after --duration (s, m or h suffix; at most 24h).";
+
+/// Parse `90s`, `30m` or `2h`; a bare number is minutes.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let (number, unit) = match input.char_indices().last() {
+        Some((i, unit)) if unit.is_ascii_alphabetic() => (&input[..i], unit.to_ascii_lowercase()),
+        _ => (input, 'm'),
+    };
+    let amount: i64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a duration like 30m or 2h", input))?;
+
+    let duration = match unit {
+        's' => Duration::seconds(amount),
+        'm' => Duration::minutes(amount),
+        'h' => Duration::hours(amount),
+        _ => return Err(format!("'{}' is not a duration like 30m or 2h", input)),
+    };
+    if duration < Duration::minutes(1) || duration > Duration::hours(MAX_DURATION_HOURS) {
+        return Err(format!("duration must be between 1m and {}h", MAX_DURATION_HOURS));
+    }
+    Ok(duration)
+}
+
+/// Unix time at which a grant, and the session it started, expire.
+pub fn expires_timestamp(grant: &BreakGlassGrant) -> Option<i64> {
+    NaiveDateTime::parse_from_str(&grant.expires_at, SQLITE_TIMESTAMP_FORMAT)
+        .ok()
+        .map(|expires_at| expires_at.and_utc().timestamp())
+}
+
+/// `idrac-controller break-glass ...`. Shell access to the host is the
+/// trust anchor, so grants can only be issued here and never over HTTP.
+pub fn run(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut reason = None;
+    let mut duration = DEFAULT_DURATION.to_string();
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} requires a value\n{}", arg, USAGE));
+        match arg.as_str() {
+            "--reason" => reason = Some(value()?),
+            "--duration" => duration = value()?,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            other => return Err(format!("Unknown argument '{}'\n{}", other, USAGE)),
+        }
+    }
+    let reason = reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .ok_or_else(|| format!("--reason is required and is recorded in the audit log\n{}", USAGE))?;
+    let duration = parse_duration(&duration)?;
+
+    let config = Config::from_env();
+    if config.standby_mode {
+        return Err("STANDBY_MODE is set; issue break-glass access on the primary".to_string());
+    }
+    let db = Database::open(config.database_location()).map_err(|e| format!("Failed to open database: {}", e))?;
+
+    let expires_at = (Utc::now() + duration).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    prepare_account(&db, &expires_at)?;
+
+    let issued_by = std::env::var("SUDO_USER")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    let token = tokens::generate_token();
+    let grant = db
+        .create_break_glass_grant(&tokens::hash_token(&token), &reason, &issued_by, &expires_at)
+        .map_err(|e| format!("Failed to store break-glass grant: {}", e))?;
+
+    let mut details = serde_json::json!({
+        "break_glass": true,
+        "grant_id": grant.id,
+        "reason": grant.reason,
+        "issued_by": grant.issued_by,
+        "expires_at": grant.expires_at,
+    });
+    scrub::scrub_json(&mut details);
+    db.record_audit(
+        None,
+        AuditAction::BreakGlassIssued.as_str(),
+        "app",
+        "success",
+        None,
+        Some(&details.to_string()),
+    )
+    .map_err(|e| format!("Failed to audit break-glass grant: {}", e))?;
+
+    let base_url = match &config.self_url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => format!("http://{}", config.bind_address.replace("0.0.0.0", "localhost")),
+    };
+    println!("Break-glass access issued (grant {}), valid until {} UTC.", grant.id, grant.expires_at);
+    println!("Open this link once to start a single admin session:");
+    println!();
+    println!("  {}/api/break-glass/{}", base_url, token);
+    println!();
+    println!("The session ends when the grant expires. Everything done in it is flagged in the audit log.");
+    Ok(())
+}
+
+/// Create the break-glass account, or make sure it stays usable until
+/// `expires_at`. Its expiry also ends any API access it might have.
+fn prepare_account(db: &Database, expires_at: &str) -> Result<(), String> {
+    let existing = db
+        .get_user_by_username(BREAK_GLASS_USERNAME)
+        .map_err(|e| format!("Failed to look up break-glass account: {}", e))?;
+
+    match existing {
+        None => {
+            db.create_user(BREAK_GLASS_USERNAME, &tokens::generate_token(), Some(expires_at), UserRole::Admin)
+                .map_err(|e| format!("Failed to create break-glass account: {}", e))?;
+        }
+        Some(user) if user.disabled_at.is_some() || user.expires_at.as_deref().is_none_or(|e| e < expires_at) => {
+            db.set_user_expiry(user.id, Some(expires_at))
+                .map_err(|e| format!("Failed to extend break-glass account: {}", e))?;
+        }
+        Some(_) => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,369 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::audit::AuditAction;
+use crate::database::AuditEntry;
+use crate::servers::ServerRegistry;
+
+/// One audit entry as a sentence for the "what changed recently" feed.
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub id: i64,
+    pub action: String,
+    /// Username, or `None` for changes made by the app itself.
+    pub actor: Option<String>,
+    pub success: bool,
+    pub message: String,
+    pub at: String,
+    /// `5 minutes ago`, relative to when the feed was read.
+    pub relative_time: String,
+}
+
+impl Change {
+    pub fn from_audit(entry: &AuditEntry, servers: &ServerRegistry, now: DateTime<Utc>) -> Self {
+        let target = match entry.server_name.as_str() {
+            "" | "app" | "fleet" => None,
+            name => Some(
+                servers
+                    .get(name)
+                    .or_else(|| servers.find_by_host(name))
+                    .map(|server| format!("'{}'", server.alias))
+                    .unwrap_or_else(|| name.to_string()),
+            ),
+        };
+        let actor = entry
+            .username
+            .clone()
+            .or_else(|| entry.user_id.map(|id| format!("User #{}", id)));
+        let success = entry.result == "success";
+
+        let mut message = format!(
+            "{} {}",
+            actor.as_deref().unwrap_or("The system"),
+            render(entry, target.as_deref())
+        );
+        if !success {
+            message.push_str(" (failed)");
+        }
+
+        Change {
+            id: entry.id,
+            action: entry.action.clone(),
+            actor,
+            success,
+            message,
+            at: entry.created_at.clone(),
+            relative_time: relative_time(&entry.created_at, now),
+        }
+    }
+}
+
+fn text<'a>(details: &'a Value, key: &str) -> Option<&'a str> {
+    details.get(key).and_then(Value::as_str)
+}
+
+/// What the actor did, after their name. Every `AuditAction` has a
+/// template; actions this version does not know (written by a newer one)
+/// get a generic rendering so they never break the feed.
+pub fn render(entry: &AuditEntry, target: Option<&str>) -> String {
+    let details = entry.details.clone().unwrap_or(Value::Null);
+    let on = target.unwrap_or("the server");
+
+    let Some(action) = AuditAction::parse(&entry.action) else {
+        return match target {
+            Some(target) => format!("ran {} on {}", action_words(&entry.action), target),
+            None => format!("ran {}", action_words(&entry.action)),
+        };
+    };
+    match action {
+        AuditAction::PowerOn => format!("powered on {}", on),
+        AuditAction::PowerOnVerify => format!("waited for {} to come up after a power-on", on),
+        AuditAction::ForceOff => format!("forced {} off", on),
+        AuditAction::GracefulShutdown => format!("shut down {}", on),
+        AuditAction::ForceRestart => format!("forced a restart of {}", on),
+        AuditAction::GracefulRestart => format!("restarted {}", on),
+        AuditAction::PowerCycle => format!("power-cycled {}", on),
+        AuditAction::Nmi => format!("sent a diagnostic interrupt (NMI) to {}", on),
+        AuditAction::ShutdownEscalationForce => format!("escalated a shutdown of {} to a forced power-off", on),
+        AuditAction::ShutdownEscalationAlert => format!("flagged a shutdown of {} that did not finish", on),
+        AuditAction::HostsThisAppAcknowledged => match text(&details, "action") {
+            Some(action) => format!("acknowledged that {} hosts this app before {}", on, action),
+            None => format!("acknowledged that {} hosts this app", on),
+        },
+        AuditAction::VirtualMediaBootOnce => match text(&details, "image_url") {
+            Some(image) => format!("booted {} once from {}", on, image),
+            None => format!("booted {} once from virtual media", on),
+        },
+        AuditAction::GroupPowerOn => format!("powered on group '{}'", entry.server_name),
+        AuditAction::FirmwareRollout => match text(&details, "image_uri") {
+            Some(image) => format!("rolled out {} to group '{}'", image, entry.server_name),
+            None => format!("rolled out firmware to group '{}'", entry.server_name),
+        },
+        AuditAction::FirmwareRolloutResume => format!("resumed a firmware rollout of group '{}'", entry.server_name),
+        AuditAction::FirmwareRolloutCancel => format!("cancelled a firmware rollout of group '{}'", entry.server_name),
+        AuditAction::FirmwareUpdate => match text(&details, "image_uri") {
+            Some(image) => format!("staged {} on {}", image, on),
+            None => format!("staged a firmware update on {}", on),
+        },
+        AuditAction::FirmwareUpdateSchedule => match (text(&details, "job_id"), text(&details, "reboot_at")) {
+            (Some(job), Some(at)) => format!("scheduled {} to reboot at {} to apply job {}", on, at, job),
+            _ => format!("scheduled a firmware update reboot of {}", on),
+        },
+        AuditAction::FirmwareUpdateApply => match text(&details, "job_id") {
+            Some(job) => format!("rebooted {} to apply firmware job {}", on, job),
+            None => format!("rebooted {} to apply a firmware update", on),
+        },
+        AuditAction::GroupApplyConfig => format!(
+            "applied {} to {}",
+            match text(&details, "setting") {
+                Some("bios_attributes") => "BIOS settings",
+                Some("idrac_attributes") => "iDRAC settings",
+                Some("power_cap_watts") => "a power cap",
+                _ => "group settings",
+            },
+            text(&details, "server").map(|alias| format!("'{}'", alias)).unwrap_or_else(|| on.to_string())
+        ),
+        AuditAction::GroupCreate => match text(&details, "name") {
+            Some(name) => format!("created group '{}'", name),
+            None => "created a group".to_string(),
+        },
+        AuditAction::GroupUpdate => match text(&details, "name") {
+            Some(name) => format!("updated group '{}'", name),
+            None => "updated a group".to_string(),
+        },
+        AuditAction::GroupDelete => "deleted a group".to_string(),
+        AuditAction::ServerCreate => "registered a server".to_string(),
+        AuditAction::ServerDelete => format!("deleted server {}", on),
+        AuditAction::ServerCredentialsUpdate => match text(&details, "changed") {
+            Some(changed) => format!("updated the iDRAC {} of {}", changed, on),
+            None => format!("updated the iDRAC credentials of {}", on),
+        },
+        AuditAction::ServerOsHealthUpdate => match text(&details, "url") {
+            Some(url) => format!("set the OS health endpoint of {} to {}", on, url),
+            None => format!("stopped the OS health probes of {}", on),
+        },
+        AuditAction::ServerImport => match details.get("created").and_then(Value::as_array) {
+            Some(created) => format!("imported {} server(s) from CSV", created.len()),
+            None => "imported servers from CSV".to_string(),
+        },
+        AuditAction::SystemProfileSet => match text(&details, "profile") {
+            Some(profile) => format!("set the system profile of {} to {}", on, profile),
+            None => format!("set the system profile of {}", on),
+        },
+        AuditAction::PostWatchdogSet => match details.get("enabled").and_then(Value::as_bool) {
+            Some(true) => format!("enabled the POST watchdog on {}", on),
+            Some(false) => format!("disabled the POST watchdog on {}", on),
+            None => format!("configured the POST watchdog on {}", on),
+        },
+        AuditAction::NicModeSet => match text(&details, "mode") {
+            Some(mode) => format!("set the iDRAC NIC mode of {} to {}", on, mode),
+            None => format!("set the iDRAC NIC mode of {}", on),
+        },
+        AuditAction::IdracUserCreate => match (text(&details, "username"), text(&details, "privilege")) {
+            (Some(username), Some(privilege)) => format!("created iDRAC account '{}' ({}) on {}", username, privilege, on),
+            _ => format!("created an iDRAC account on {}", on),
+        },
+        AuditAction::IdracUserUpdate => match text(&details, "account_id") {
+            Some(id) => format!("updated iDRAC account {} on {}", id, on),
+            None => format!("updated an iDRAC account on {}", on),
+        },
+        AuditAction::BootOrderSet => format!("changed the boot order of {}", on),
+        AuditAction::IdracFactoryResetRequested => format!("requested a factory reset of the iDRAC of {}", on),
+        AuditAction::IdracFactoryReset => format!("factory reset the iDRAC of {}", on),
+        AuditAction::IdracTimeSync => format!("synced the iDRAC clock of {}", on),
+        AuditAction::NtpConfigure => match details.get("enabled").and_then(Value::as_bool) {
+            Some(false) => format!("disabled NTP on {}", on),
+            _ => format!("configured NTP on {}", on),
+        },
+        AuditAction::IdracOemAction => format!("ran an OEM action on {}", on),
+        AuditAction::BiosResetToDefaults => format!("staged a BIOS reset to defaults on {}", on),
+        AuditAction::LicenseActivate => format!("activated a license on {}", on),
+        AuditAction::EventSelfSubscribe => format!("subscribed this app to events from {}", on),
+        AuditAction::TelemetryDefinitionCreate => match details.get("metrics").and_then(Value::as_array) {
+            Some(metrics) => format!("created a telemetry report of {} metric(s) on {}", metrics.len(), on),
+            None => format!("created a telemetry report on {}", on),
+        },
+        AuditAction::TelemetryDefinitionDelete => format!("deleted a telemetry report on {}", on),
+        AuditAction::FirmwareInventoryRefresh => "refreshed the firmware inventory".to_string(),
+        AuditAction::FirmwareBaselineSet => match (text(&details, "component"), text(&details, "version")) {
+            (Some(component), Some(version)) => format!("set the firmware baseline of {} to {}", component, version),
+            _ => "set a firmware baseline".to_string(),
+        },
+        AuditAction::ComplianceProfileSet => match text(&details, "name") {
+            Some(name) => format!("set compliance profile '{}'", name),
+            None => "set a compliance profile".to_string(),
+        },
+        AuditAction::PowerCapSchedule => format!("applied a scheduled power cap to {}", on),
+        AuditAction::PowerCapScheduleCreate => format!("scheduled a power cap on {}", on),
+        AuditAction::PowerCapScheduleDelete => format!("removed a power cap schedule from {}", on),
+        AuditAction::TariffWindowCreate => match (text(&details, "server"), text(&details, "start"), text(&details, "end")) {
+            (Some(alias), Some(start), Some(end)) => format!("added a tariff window capping '{}' from {} to {}", alias, start, end),
+            (None, Some(start), Some(end)) => format!("added a tariff window capping a group from {} to {}", start, end),
+            _ => "added a tariff window".to_string(),
+        },
+        AuditAction::TariffWindowDelete => "removed a tariff window".to_string(),
+        AuditAction::TariffCapApply => format!("applied a tariff window power cap to {}", on),
+        AuditAction::TariffCapRestore => format!("restored the power cap of {} after a tariff window", on),
+        AuditAction::OneShotScheduleCreate => format!("scheduled a one-off power action on {}", on),
+        AuditAction::OneShotScheduleDelete => format!("cancelled a scheduled power action on {}", on),
+        AuditAction::UserCreate => match text(&details, "username") {
+            Some(user) => format!("created user '{}'", user),
+            None => "created a user".to_string(),
+        },
+        AuditAction::UserExpiryUpdate => match (text(&details, "user"), text(&details, "after")) {
+            (Some(user), Some(after)) => format!("set user '{}' to expire at {}", user, after),
+            (Some(user), None) => format!("removed the expiry of user '{}'", user),
+            (None, _) => "changed the expiry of a user".to_string(),
+        },
+        AuditAction::AccountExpired => match text(&details, "user") {
+            Some(user) => format!("disabled expired account '{}'", user),
+            None => "disabled an expired account".to_string(),
+        },
+        AuditAction::ServerGrantUpdate => {
+            let granted = details.get("granted").and_then(Value::as_bool) == Some(true);
+            match (text(&details, "user"), text(&details, "after"), granted) {
+                (Some(user), Some(after), true) => format!("let user '{}' run power actions on {} until {}", user, on, after),
+                (Some(user), None, true) => format!("let user '{}' run power actions on {}", user, on),
+                (Some(user), Some(after), false) => format!("set the grant of user '{}' on {} to expire at {}", user, on, after),
+                (Some(user), None, false) => format!("removed the expiry of the grant of user '{}' on {}", user, on),
+                (None, _, _) => format!("changed a grant on {}", on),
+            }
+        }
+        AuditAction::ServerGrantRevoke => match text(&details, "user") {
+            Some(user) => format!("revoked the grant of user '{}' on {}", user, on),
+            None => format!("revoked a grant on {}", on),
+        },
+        AuditAction::ApiTokenCreate => match details.get("scopes").and_then(Value::as_array) {
+            Some(scopes) => format!(
+                "created an API token with scopes {}",
+                scopes.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(", ")
+            ),
+            None => "created an API token".to_string(),
+        },
+        AuditAction::ApiTokenRevoke => "revoked an API token".to_string(),
+        AuditAction::ShareCreate => match text(&details, "name") {
+            Some(name) => format!("created share link '{}'", name),
+            None => "created a share link".to_string(),
+        },
+        AuditAction::ShareRevoke => "revoked a share link".to_string(),
+        AuditAction::ApiTokenQuotaUpdate => {
+            match (details.get("token_id").and_then(Value::as_i64), details.get("requests_per_hour").and_then(Value::as_u64)) {
+                (Some(id), Some(quota)) => format!("set the quota of API token #{} to {} requests an hour", id, quota),
+                (Some(id), None) => format!("removed the quota of API token #{}", id),
+                (None, _) => "changed the quota of an API token".to_string(),
+            }
+        }
+        AuditAction::RetentionPolicyUpdate => "changed the retention policy".to_string(),
+        AuditAction::BreakGlassIssued => match text(&details, "expires_at") {
+            Some(expires_at) => format!("issued break-glass access valid until {} UTC", expires_at),
+            None => "issued break-glass access".to_string(),
+        },
+        AuditAction::BreakGlassLogin => "signed in with break-glass access".to_string(),
+        AuditAction::PasswordResetRequested => "was mailed a password reset link".to_string(),
+        AuditAction::PasswordReset => "reset their password from an emailed link".to_string(),
+        AuditAction::BreakGlassRequest => match (text(&details, "method"), text(&details, "path")) {
+            (Some(method), Some(path)) => format!("called {} {} under break-glass access", method, path),
+            _ => "made a request under break-glass access".to_string(),
+        },
+        AuditAction::PromoteStandby => "promoted this standby to primary".to_string(),
+    }
+}
+
+
+
+/// `ShutdownEscalation:Force` as `shutdown escalation force`.
+fn action_words(action: &str) -> String {
+    let mut words = String::new();
+    for c in action.chars() {
+        if c.is_ascii_uppercase() && !words.is_empty() && !words.ends_with(' ') {
+            words.push(' ');
+        }
+        if c.is_alphanumeric() {
+            words.push(c.to_ascii_lowercase());
+        } else if !words.ends_with(' ') {
+            words.push(' ');
+        }
+    }
+    words.trim().to_string()
+}
+
+/// `created_at` as `just now`, `3 hours ago` and so on, or the date for
+/// anything older than a month.
+pub fn relative_time(created_at: &str, now: DateTime<Utc>) -> String {
+    let Ok(at) = NaiveDateTime::parse_from_str(created_at, "%Y-%m-%d %H:%M:%S") else {
+        return created_at.to_string();
+    };
+    let seconds = (now.naive_utc() - at).num_seconds().max(0);
+    let (count, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        86400..=2591999 => (seconds / 86400, "day"),
+        _ => return at.format("%Y-%m-%d").to_string(),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(action: &str, details: Option<Value>) -> AuditEntry {
+        AuditEntry {
+            id: 1,
+            user_id: Some(7),
+            username: Some("alice".to_string()),
+            action: action.to_string(),
+            server_name: "rack-1".to_string(),
+            result: "success".to_string(),
+            error_message: None,
+            details,
+            created_at: "2026-01-01 00:00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn every_action_has_a_template() {
+        for &action in AuditAction::ALL {
+            for details in [None, Some(serde_json::json!({}))] {
+                let sentence = render(&entry(action.as_str(), details), Some("'rack-1'"));
+                let generic = format!("ran {} on 'rack-1'", action_words(action.as_str()));
+                assert_ne!(sentence, generic, "{} has no template", action);
+                assert!(!sentence.is_empty(), "{}", action);
+            }
+        }
+    }
+
+    #[test]
+    fn details_fill_in_templates() {
+        let details = serde_json::json!({ "user": "bob", "after": "2026-02-01 00:00:00" });
+        assert_eq!(
+            render(&entry("UserExpiryUpdate", Some(details)), None),
+            "set user 'bob' to expire at 2026-02-01 00:00:00"
+        );
+        let details = serde_json::json!({ "user": "vendor", "granted": true, "after": "2026-02-01 18:00:00" });
+        assert_eq!(
+            render(&entry("ServerGrantUpdate", Some(details)), Some("'rack-1'")),
+            "let user 'vendor' run power actions on 'rack-1' until 2026-02-01 18:00:00"
+        );
+        let details = serde_json::json!({ "enabled": false, "servers": [] });
+        assert_eq!(render(&entry("NtpConfigure", Some(details)), Some("'rack-1'")), "disabled NTP on 'rack-1'");
+        assert_eq!(render(&entry("PowerOn", None), None), "powered on the server");
+    }
+
+    #[test]
+    fn unknown_actions_fall_back_to_their_words() {
+        assert_eq!(render(&entry("FrobnicateWidget", None), Some("'rack-1'")), "ran frobnicate widget on 'rack-1'");
+        assert_eq!(render(&entry("ShutdownEscalation:Later", None), None), "ran shutdown escalation later");
+    }
+
+    #[test]
+    fn failed_changes_are_marked() {
+        let (state, _dir) = crate::testing::app_state();
+        let mut failed = entry("ForceOff", None);
+        failed.result = "failure".to_string();
+        let change = Change::from_audit(&failed, &state.servers, Utc::now());
+        assert_eq!(change.message, "alice forced rack-1 off (failed)");
+    }
+}
@@ -0,0 +1,399 @@
+//! A small async client for the HTTP API, authenticating with an API token.
+
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fmt;
+
+use crate::api::{
+    ApiResponse, HostsThisAppAcknowledgment, OperationResponse, OperationStartedResponse, Page, PageQuery, PowerOnQuery,
+    PowerOnVerifiedResponse, ServerByHostQuery, ServerByHostResponse, ServerSummary, ShutdownRequest, StatusResponse,
+};
+use crate::errors::ErrorCode;
+
+/// A request that failed, either on the way or because the API refused it.
+#[derive(Debug, Clone)]
+pub struct ClientError {
+    /// HTTP status of the refusal; `None` when no response arrived.
+    pub status: Option<u16>,
+    pub error_code: Option<ErrorCode>,
+    /// The request field a validation error is about.
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ClientError {
+    fn transport(message: String) -> Self {
+        ClientError {
+            status: None,
+            error_code: None,
+            field: None,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.status, self.error_code) {
+            (Some(status), Some(code)) => write!(f, "HTTP {} ({}): {}", status, code, self.message),
+            (Some(status), None) => write!(f, "HTTP {}: {}", status, self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// The fields every error response has, whether `ApiResponse` or
+/// `FieldErrorResponse`. The code is read loosely so that one added by a
+/// newer server still leaves the message readable.
+#[derive(Deserialize)]
+struct ErrorBody {
+    message: String,
+    error_code: Option<String>,
+    field: Option<String>,
+}
+
+/// Answer to `POST /api/power/shutdown`.
+#[derive(Debug, Clone)]
+pub enum ShutdownResponse {
+    /// The shutdown was sent and nothing else happens.
+    Sent(ApiResponse),
+    /// The shutdown was sent and an escalation is tracked as an operation.
+    Escalating(OperationStartedResponse),
+}
+
+/// Client for one controller, e.g.
+/// `ApiClient::new("https://idrac.example.com").with_token(token)`.
+#[derive(Clone)]
+pub struct ApiClient {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl ApiClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        ApiClient {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Send `token`, an API token from `POST /api/tokens`, with every request.
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        ApiClient {
+            token: Some(token.into()),
+            ..self
+        }
+    }
+
+    /// Use `http` instead of a default client, e.g. for custom TLS roots
+    /// or timeouts.
+    pub fn with_http_client(self, http: reqwest::Client) -> Self {
+        ApiClient { http, ..self }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<(StatusCode, T), ClientError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ClientError::transport(format!("Request failed: {}", e)))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| ClientError::transport(format!("Failed to read the response: {}", e)))?;
+
+        if !status.is_success() {
+            let error = serde_json::from_slice::<ErrorBody>(&body).ok();
+            return Err(ClientError {
+                status: Some(status.as_u16()),
+                error_code: error.as_ref().and_then(|e| e.error_code.as_deref()).and_then(ErrorCode::parse),
+                field: error.as_ref().and_then(|e| e.field.clone()),
+                message: error
+                    .map(|e| e.message)
+                    .unwrap_or_else(|| String::from_utf8_lossy(&body).into_owned()),
+            });
+        }
+        serde_json::from_slice(&body)
+            .map(|parsed| (status, parsed))
+            .map_err(|e| ClientError {
+                status: Some(status.as_u16()),
+                error_code: None,
+                field: None,
+                message: format!("Unexpected response: {}", e),
+            })
+    }
+
+    /// `GET /api/power/status`
+    pub async fn power_status(&self) -> Result<StatusResponse, ClientError> {
+        Ok(self.send(self.request(Method::GET, "/api/power/status")).await?.1)
+    }
+
+    /// `POST /api/power/on`, answering once the iDRAC accepted the request.
+    /// `wait_for_os` is the `host:port` the boot report waits for.
+    pub async fn power_on(&self, wait_for_os: Option<&str>) -> Result<ApiResponse, ClientError> {
+        let query = PowerOnQuery {
+            wait_for_os: wait_for_os.map(str::to_string),
+            ..PowerOnQuery::default()
+        };
+        Ok(self.send(self.request(Method::POST, "/api/power/on").query(&query)).await?.1)
+    }
+
+    /// `POST /api/power/on?verify=true`, answering once the server reports
+    /// `On` or `verify_timeout_secs` passed.
+    pub async fn power_on_verified(
+        &self,
+        verify_timeout_secs: Option<u64>,
+        wait_for_os: Option<&str>,
+    ) -> Result<PowerOnVerifiedResponse, ClientError> {
+        let query = PowerOnQuery {
+            verify: true,
+            verify_timeout_secs,
+            wait_for_os: wait_for_os.map(str::to_string),
+            ..PowerOnQuery::default()
+        };
+        Ok(self.send(self.request(Method::POST, "/api/power/on").query(&query)).await?.1)
+    }
+
+    /// `POST /api/power/off`. `acknowledge_hosts_this_app` is required when
+    /// the server runs this controller.
+    pub async fn power_off(&self, acknowledge_hosts_this_app: bool) -> Result<ApiResponse, ClientError> {
+        let body = HostsThisAppAcknowledgment {
+            i_understand_this_hosts_the_controller: acknowledge_hosts_this_app,
+        };
+        Ok(self.send(self.request(Method::POST, "/api/power/off").json(&body)).await?.1)
+    }
+
+    /// `POST /api/power/shutdown`
+    pub async fn shutdown(&self, request: &ShutdownRequest) -> Result<ShutdownResponse, ClientError> {
+        // The status tells which shape the body has.
+        let (status, body) = self
+            .send::<serde_json::Value>(self.request(Method::POST, "/api/power/shutdown").json(request))
+            .await?;
+        let parse_error = |e: serde_json::Error| ClientError {
+            status: Some(status.as_u16()),
+            error_code: None,
+            field: None,
+            message: format!("Unexpected response: {}", e),
+        };
+        if status == StatusCode::ACCEPTED {
+            serde_json::from_value(body).map(ShutdownResponse::Escalating).map_err(parse_error)
+        } else {
+            serde_json::from_value(body).map(ShutdownResponse::Sent).map_err(parse_error)
+        }
+    }
+
+    /// `GET /api/servers`, one page.
+    pub async fn list_servers(&self, query: &PageQuery) -> Result<Page<ServerSummary>, ClientError> {
+        Ok(self.send(self.request(Method::GET, "/api/servers").query(query)).await?.1)
+    }
+
+    /// `GET /api/servers/by-host`
+    pub async fn server_by_host(&self, host: &str) -> Result<ServerByHostResponse, ClientError> {
+        let query = ServerByHostQuery { host: host.to_string() };
+        Ok(self.send(self.request(Method::GET, "/api/servers/by-host").query(&query)).await?.1)
+    }
+
+    /// `GET /api/operations/{id}`
+    pub async fn operation(&self, id: &str) -> Result<OperationResponse, ClientError> {
+        Ok(self.send(self.request(Method::GET, &format!("/api/operations/{}", id))).await?.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{EscalationMode, FieldErrorResponse, OperationStartedResponse, PageInfo, PowerChangeReason};
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+    const TOKEN: &str = "idrac_round_trip_token";
+
+    fn authorized(req: &HttpRequest) -> Result<(), Box<HttpResponse>> {
+        match req.headers().get("Authorization").and_then(|value| value.to_str().ok()) {
+            Some(value) if value == format!("Bearer {}", TOKEN) => Ok(()),
+            _ => Err(Box::new(HttpResponse::Unauthorized().json(ApiResponse::error(ErrorCode::AuthInvalidToken, "Invalid API token")))),
+        }
+    }
+
+    async fn power_status(req: HttpRequest) -> HttpResponse {
+        if let Err(response) = authorized(&req) {
+            return *response;
+        }
+        HttpResponse::Ok().json(StatusResponse {
+            success: true,
+            server_name: "rack-1".to_string(),
+            power_state: "On".to_string(),
+            last_power_reason: Some(PowerChangeReason::from_code("PowerButton")),
+        })
+    }
+
+    async fn list_servers(query: web::Query<PageQuery>) -> HttpResponse {
+        let query = query.into_inner();
+        HttpResponse::Ok().json(Page {
+            success: true,
+            items: vec![ServerSummary {
+                id: Some(2),
+                alias: "rack-2".to_string(),
+                name: "Rack 2".to_string(),
+                base_url: "https://10.0.0.2".to_string(),
+                tags: vec!["prod".to_string()],
+                location: None,
+                hosts_this_app: false,
+                hostname: Some("idrac-2.example.com".to_string()),
+                maintenance: None,
+                os_health_url: None,
+                os_health: None,
+            }],
+            page: PageInfo {
+                limit: query.limit.unwrap_or(100),
+                next_cursor: Some(format!("after-{}", query.cursor.unwrap_or_default())),
+                prev_cursor: None,
+                total_estimate: Some(2),
+            },
+        })
+    }
+
+    async fn shutdown(body: web::Json<ShutdownRequest>) -> HttpResponse {
+        match body.escalate {
+            Some(_) => HttpResponse::Accepted().json(OperationStartedResponse {
+                success: true,
+                message: "Shutdown sent".to_string(),
+                operation_id: "op-1".to_string(),
+                warning: None,
+            }),
+            None => HttpResponse::Ok().json(ApiResponse::success("Shutdown sent")),
+        }
+    }
+
+    async fn power_on() -> HttpResponse {
+        HttpResponse::BadRequest().json(FieldErrorResponse {
+            success: false,
+            message: "wait_for_os must be host:port".to_string(),
+            error_code: ErrorCode::ValidationInvalidField,
+            field: "wait_for_os".to_string(),
+        })
+    }
+
+    async fn operation(path: web::Path<String>) -> HttpResponse {
+        HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::OperationNotFound,
+            format!("No operation with id {}", path.into_inner()),
+        ))
+    }
+
+    async fn server_by_host() -> HttpResponse {
+        // An error code added by a newer server.
+        HttpResponse::Conflict().json(serde_json::json!({
+            "success": false,
+            "message": "Host is ambiguous",
+            "error_code": "server.ambiguous_host",
+        }))
+    }
+
+    /// The API routes the client calls, answering with the shared `api`
+    /// types, on a free local port.
+    fn stub_server() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = HttpServer::new(|| {
+            App::new()
+                .route("/api/power/status", web::get().to(power_status))
+                .route("/api/power/on", web::post().to(power_on))
+                .route("/api/power/shutdown", web::post().to(shutdown))
+                .route("/api/servers", web::get().to(list_servers))
+                .route("/api/servers/by-host", web::get().to(server_by_host))
+                .route("/api/operations/{id}", web::get().to(operation))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+        format!("http://127.0.0.1:{}", port)
+    }
+
+    #[test]
+    fn the_client_calls_documented_endpoints() {
+        let document: serde_json::Value = serde_json::from_str(include_str!("../openapi.json")).unwrap();
+        let endpoints = [
+            ("get", "/api/power/status"),
+            ("post", "/api/power/on"),
+            ("post", "/api/power/off"),
+            ("post", "/api/power/shutdown"),
+            ("get", "/api/servers"),
+            ("get", "/api/servers/by-host"),
+            ("get", "/api/operations/{id}"),
+        ];
+        for (method, path) in endpoints {
+            assert!(document["paths"][path][method].is_object(), "{} {} is not in openapi.json", method, path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn responses_round_trip_through_the_shared_types() {
+        let client = ApiClient::new(format!("{}/", stub_server())).with_token(TOKEN);
+
+        let status = client.power_status().await.unwrap();
+        assert_eq!((status.server_name.as_str(), status.power_state.as_str()), ("rack-1", "On"));
+        assert_eq!(status.last_power_reason, Some(PowerChangeReason::from_code("PowerButton")));
+
+        let page = client
+            .list_servers(&PageQuery { limit: Some(1), cursor: Some("rack-1".to_string()) })
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].alias, "rack-2");
+        assert_eq!(page.items[0].hostname.as_deref(), Some("idrac-2.example.com"));
+        assert_eq!(page.page.limit, 1);
+        assert_eq!(page.page.next_cursor.as_deref(), Some("after-rack-1"));
+
+        match client.shutdown(&ShutdownRequest::default()).await.unwrap() {
+            ShutdownResponse::Sent(response) => assert!(response.success && response.error_code.is_none()),
+            other => panic!("expected Sent, got {:?}", other),
+        }
+        let escalating = ShutdownRequest {
+            escalate_after_secs: Some(60),
+            escalate: Some(EscalationMode::Force),
+            ..ShutdownRequest::default()
+        };
+        match client.shutdown(&escalating).await.unwrap() {
+            ShutdownResponse::Escalating(started) => assert_eq!(started.operation_id, "op-1"),
+            other => panic!("expected Escalating, got {:?}", other),
+        }
+    }
+
+    #[actix_web::test]
+    async fn error_responses_map_to_error_codes() {
+        let base_url = stub_server();
+
+        let err = ApiClient::new(&base_url).with_token("idrac_wrong").power_status().await.unwrap_err();
+        assert_eq!((err.status, err.error_code), (Some(401), Some(ErrorCode::AuthInvalidToken)));
+        assert_eq!(err.message, "Invalid API token");
+        assert_eq!(err.to_string(), "HTTP 401 (auth.invalid_token): Invalid API token");
+
+        let client = ApiClient::new(&base_url).with_token(TOKEN);
+        let err = client.operation("op-9").await.unwrap_err();
+        assert_eq!((err.status, err.error_code), (Some(404), Some(ErrorCode::OperationNotFound)));
+        assert_eq!(err.message, "No operation with id op-9");
+
+        let err = client.power_on(Some("db-1")).await.unwrap_err();
+        assert_eq!((err.status, err.error_code), (Some(400), Some(ErrorCode::ValidationInvalidField)));
+        assert_eq!(err.field.as_deref(), Some("wait_for_os"));
+
+        let err = client.server_by_host("10.0.0.2").await.unwrap_err();
+        assert_eq!((err.status, err.error_code), (Some(409), None));
+        assert_eq!(err.message, "Host is ambiguous");
+    }
+}
@@ -0,0 +1,366 @@
+use crate::ldap::LdapConfig;
+use crate::oidc::OidcConfig;
+use crate::secret::SecretString;
+
+/// How the SMTP connection is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with `STARTTLS`, usually on port 587.
+    StartTls,
+    /// TLS from the first byte, usually on port 465.
+    Tls,
+    /// No encryption, for a relay on the local network.
+    None,
+}
+
+/// Outgoing mail server; present when `SMTP_HOST` is set.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    /// Credentials for `AUTH PLAIN`; relays that need none leave them unset.
+    pub username: Option<String>,
+    pub password: Option<SecretString>,
+    pub from: String,
+}
+
+/// Application settings read from the environment at startup.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bind_address: String,
+    pub database_path: String,
+    /// `postgres://` URL of a PostgreSQL database to use instead of the
+    /// SQLite file at `database_path`.
+    pub database_url: Option<String>,
+    /// Externally reachable base URL of this application, used when asking
+    /// the iDRAC to push events back to us.
+    pub self_url: Option<String>,
+    /// Fraction (0.0-1.0) of successful requests written to the access log.
+    pub log_sample_rate: f64,
+    /// Requests slower than this are always logged.
+    pub slow_request_threshold_ms: u64,
+    /// Warn when the iDRAC certificate expires within this many days.
+    pub cert_expiry_warn_days: i64,
+    /// Warn when the iDRAC clock differs from ours by more than this.
+    pub clock_drift_threshold_secs: i64,
+    /// NTP servers a clock drift warning offers to configure on the iDRAC;
+    /// no remediation is offered when empty.
+    pub ntp_servers: Vec<String>,
+    /// Per-server time budget for `GET /api/fleet/health`.
+    pub fleet_health_timeout_ms: u64,
+    /// Interval between keepalive requests to each iDRAC; 0 disables them.
+    pub idrac_session_keepalive_secs: u64,
+    /// Interval between power state reads of each `/ws/power` connection.
+    pub idrac_poll_interval_secs: u64,
+    /// Interval between power draw samples of every server; 0 disables
+    /// sampling.
+    pub power_sample_interval_secs: u64,
+    /// Interval between power supply checks of every server; 0 disables
+    /// them.
+    pub psu_check_interval_secs: u64,
+    /// A power supply reading a lower line input voltage has lost input.
+    pub psu_min_input_voltage: f64,
+    /// Interval between ICMP pings of every iDRAC; 0 disables them.
+    pub ping_interval_secs: u64,
+    /// Packet loss above which a server gets a connectivity alert.
+    pub ping_alert_loss_pct: f64,
+    /// Interval between OS health probes of servers with an endpoint; 0
+    /// disables them.
+    pub os_health_interval_secs: u64,
+    /// Time an OS health endpoint has to answer.
+    pub os_health_timeout_ms: u64,
+    /// OS health endpoint of the `IDRAC_HOST` server; see `os_health`.
+    pub os_health_url: Option<String>,
+    pub os_health_insecure: bool,
+    pub os_health_token: Option<SecretString>,
+    /// A power sample further than this many median absolute deviations
+    /// from its baseline deviates.
+    pub power_anomaly_threshold: f64,
+    /// Deviating samples in a row that raise a power anomaly.
+    pub power_anomaly_samples: u32,
+    /// Weeks of the same hour a power baseline is summarized from.
+    pub power_anomaly_weeks: u32,
+    /// Cron expression, in local time, of the nightly inventory and SEL
+    /// sweep; `None` when `NIGHTLY_SWEEP_ENABLED` turns it off.
+    pub nightly_sweep_cron: Option<String>,
+    /// Cron expression, in local time, of the daily health report; `None`
+    /// when `HEALTH_REPORT_ENABLED` turns it off.
+    pub health_report_cron: Option<String>,
+    /// Where health reports are mailed; nothing is sent when empty or
+    /// when `smtp` is unset.
+    pub health_report_recipients: Vec<String>,
+    pub smtp: Option<SmtpConfig>,
+    /// Directory sign-in; set from `LdapConfig::from_env` once it has been
+    /// validated at startup.
+    pub ldap: Option<LdapConfig>,
+    /// Single sign-on; set from `OidcConfig::from_env` the same way.
+    pub oidc: Option<OidcConfig>,
+    /// Base64 AES-256 key for stored server passwords. When unset a key
+    /// file is created next to the database.
+    pub credential_key: Option<SecretString>,
+    /// Run as a warm standby: open the database read-only and refuse
+    /// writes until promoted.
+    pub standby_mode: bool,
+    /// The `IDRAC_HOST` server runs this application; see
+    /// `RegisteredServer::hosts_this_app`.
+    pub idrac_hosts_this_app: bool,
+    /// Browser origins allowed to call the API cross-origin; `*` allows
+    /// any. Empty disables CORS.
+    pub cors_allowed_origins: Vec<String>,
+    /// How long browsers may cache a preflight answer.
+    pub cors_max_age_secs: u32,
+    /// Hostnames and IPs requests may be addressed to, without ports.
+    /// Empty accepts any `Host`.
+    pub allowed_hosts: Vec<String>,
+    /// Initial retention policy; see `RetentionPolicy`. Changes made
+    /// through the API are stored in the database and take precedence.
+    pub audit_retention_days: u32,
+    pub history_retention_days: u32,
+    pub connectivity_log_retention_days: u32,
+    pub session_ttl_hours: u32,
+    /// Seconds without reported activity after which
+    /// `POST /api/auth/activity` warns that the session will be ended.
+    pub idle_timeout_secs: u64,
+}
+
+fn smtp_from_env() -> Option<SmtpConfig> {
+    let host = std::env::var("SMTP_HOST").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())?;
+    let security = match std::env::var("SMTP_SECURITY").map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+        Ok("tls") => SmtpSecurity::Tls,
+        Ok("none") => SmtpSecurity::None,
+        _ => SmtpSecurity::StartTls,
+    };
+    let default_port = if security == SmtpSecurity::Tls { 465 } else { 587 };
+    Some(SmtpConfig {
+        port: std::env::var("SMTP_PORT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default_port),
+        security,
+        username: std::env::var("SMTP_USERNAME").ok().filter(|v| !v.trim().is_empty()),
+        password: std::env::var("SMTP_PASSWORD")
+            .ok()
+            .map(SecretString::from)
+            .filter(|p| !p.expose().is_empty()),
+        from: std::env::var("SMTP_FROM")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| format!("idrac-controller@{}", host)),
+        host,
+    })
+}
+
+impl Config {
+    /// Whether accounts may log in or register with a local password.
+    pub fn local_login_allowed(&self) -> bool {
+        self.ldap.as_ref().is_none_or(|ldap| ldap.allow_local_login) && self.oidc.as_ref().is_none_or(|oidc| !oidc.only)
+    }
+
+    pub fn from_env() -> Self {
+        Config {
+            bind_address: std::env::var("BIND_ADDRESS").unwrap_or_else(|_| "0.0.0.0:8080".to_string()),
+            database_path: std::env::var("DATABASE_PATH").unwrap_or_else(|_| "./data/idrac.db".to_string()),
+            database_url: std::env::var("DATABASE_URL").ok().filter(|url| !url.trim().is_empty()),
+            self_url: std::env::var("SELF_URL")
+                .ok()
+                .map(|url| url.trim().trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            log_sample_rate: std::env::var("LOG_SAMPLE_RATE")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|rate| rate.clamp(0.0, 1.0))
+                .unwrap_or(1.0),
+            slow_request_threshold_ms: std::env::var("SLOW_REQUEST_THRESHOLD_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+            cert_expiry_warn_days: std::env::var("CERT_EXPIRY_WARN_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            clock_drift_threshold_secs: std::env::var("CLOCK_DRIFT_THRESHOLD_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            ntp_servers: std::env::var("NTP_SERVERS")
+                .map(|v| v.split(',').map(|server| server.trim().to_string()).filter(|server| !server.is_empty()).collect())
+                .unwrap_or_default(),
+            fleet_health_timeout_ms: std::env::var("FLEET_HEALTH_TIMEOUT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5000),
+            idrac_session_keepalive_secs: std::env::var("IDRAC_SESSION_KEEPALIVE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            idrac_poll_interval_secs: std::env::var("IDRAC_POLL_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(5),
+            power_sample_interval_secs: std::env::var("POWER_SAMPLE_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            psu_check_interval_secs: std::env::var("PSU_CHECK_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            psu_min_input_voltage: std::env::var("PSU_MIN_INPUT_VOLTAGE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(90.0),
+            ping_interval_secs: std::env::var("PING_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            ping_alert_loss_pct: std::env::var("PING_ALERT_LOSS_PCT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|pct: &f64| (0.0..100.0).contains(pct))
+                .unwrap_or(50.0),
+            os_health_interval_secs: std::env::var("OS_HEALTH_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            os_health_timeout_ms: std::env::var("OS_HEALTH_TIMEOUT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|ms| *ms > 0)
+                .unwrap_or(2000),
+            os_health_url: std::env::var("OS_HEALTH_URL")
+                .ok()
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty()),
+            os_health_insecure: std::env::var("OS_HEALTH_INSECURE")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+            os_health_token: std::env::var("OS_HEALTH_TOKEN")
+                .ok()
+                .map(SecretString::from)
+                .filter(|token| !token.expose().trim().is_empty()),
+            power_anomaly_threshold: std::env::var("POWER_ANOMALY_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|threshold: &f64| *threshold > 0.0)
+                .unwrap_or(5.0),
+            power_anomaly_samples: std::env::var("POWER_ANOMALY_SAMPLES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|samples| *samples > 0)
+                .unwrap_or(5),
+            power_anomaly_weeks: std::env::var("POWER_ANOMALY_WEEKS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|weeks| *weeks > 0)
+                .unwrap_or(4),
+            nightly_sweep_cron: match std::env::var("NIGHTLY_SWEEP_ENABLED") {
+                Ok(v) if matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no") => None,
+                _ => Some(
+                    std::env::var("NIGHTLY_SWEEP_CRON")
+                        .ok()
+                        .filter(|expr| !expr.trim().is_empty())
+                        .unwrap_or_else(|| "30 3 * * *".to_string()),
+                ),
+            },
+            health_report_cron: match std::env::var("HEALTH_REPORT_ENABLED") {
+                Ok(v) if matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no") => None,
+                _ => Some(
+                    std::env::var("HEALTH_REPORT_CRON")
+                        .ok()
+                        .filter(|expr| !expr.trim().is_empty())
+                        .unwrap_or_else(|| "0 6 * * *".to_string()),
+                ),
+            },
+            health_report_recipients: std::env::var("HEALTH_REPORT_EMAIL_TO")
+                .map(|v| {
+                    v.split(',')
+                        .map(|address| address.trim().to_string())
+                        .filter(|address| !address.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            smtp: smtp_from_env(),
+            ldap: None,
+            oidc: None,
+            credential_key: std::env::var("CREDENTIAL_KEY")
+                .ok()
+                .map(SecretString::from)
+                .filter(|k| !k.expose().trim().is_empty()),
+            standby_mode: std::env::var("STANDBY_MODE")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+            idrac_hosts_this_app: std::env::var("IDRAC_HOSTS_THIS_APP")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+            cors_allowed_origins: std::env::var("CORS_ALLOWED_ORIGINS")
+                .map(|v| {
+                    v.split(',')
+                        .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                        .filter(|origin| !origin.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            cors_max_age_secs: std::env::var("CORS_MAX_AGE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+            allowed_hosts: std::env::var("ALLOWED_HOSTS")
+                .map(|v| v.split(',').map(host_name).filter(|host| !host.is_empty()).collect())
+                .unwrap_or_default(),
+            audit_retention_days: std::env::var("AUDIT_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(365),
+            history_retention_days: std::env::var("HISTORY_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(90),
+            connectivity_log_retention_days: std::env::var("CONNECTIVITY_LOG_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            session_ttl_hours: std::env::var("SESSION_TTL_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|hours| *hours > 0)
+                .unwrap_or(24),
+            idle_timeout_secs: std::env::var("IDLE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(3600),
+        }
+    }
+
+    /// Where `Database::open` finds the database.
+    pub fn database_location(&self) -> &str {
+        self.database_url.as_deref().unwrap_or(&self.database_path)
+    }
+
+    pub fn cors_origin_allowed(&self, origin: &str) -> bool {
+        self.cors_allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed == origin)
+    }
+
+    /// Whether a request addressed to `host` (a `Host` header value, port
+    /// optional) is accepted.
+    pub fn host_allowed(&self, host: &str) -> bool {
+        let host = host_name(host);
+        self.allowed_hosts.is_empty() || self.allowed_hosts.contains(&host)
+    }
+}
+
+/// `host` lowercased, without its port, IPv6 brackets or trailing dot.
+fn host_name(host: &str) -> String {
+    let host = host.trim();
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        // More than one colon is a bare IPv6 address, not a port.
+        None if host.matches(':').count() == 1 => host.split(':').next().unwrap_or(host),
+        None => host,
+    };
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
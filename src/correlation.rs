@@ -0,0 +1,191 @@
+//! Links stored events to the application action that most plausibly
+//! caused them: the newest successful audit entry against the event's
+//! server, and the operation on that server, within `WINDOW_SECS` before
+//! the event. Events with neither keep no cause.
+
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+
+use crate::database::{EventCause, SQLITE_TIMESTAMP_FORMAT};
+use crate::state::AppState;
+
+/// Seconds after an action that an event is still attributed to it.
+pub const WINDOW_SECS: i64 = 300;
+
+/// Link the uncorrelated events of `server_alias` stored since
+/// `recorded_since`, returning how many were linked. Events are placed by
+/// their own timestamp, so order of arrival does not matter, and ones the
+/// previous pass could not place are tried again as long as they are
+/// within `recorded_since`: an event can arrive before its action's audit
+/// entry is written.
+pub fn correlate(state: &AppState, server_alias: &str, recorded_since: DateTime<Utc>) -> usize {
+    let Some(server) = state.servers.get(server_alias) else {
+        return 0;
+    };
+    let recorded_since = recorded_since.format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    let entries = match state.db.list_uncorrelated_sel_entries(server_alias, &recorded_since) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to load events of {} to correlate: {}", server_alias, e);
+            return 0;
+        }
+    };
+
+    let mut linked = 0;
+    for entry in entries {
+        let Ok(at) = DateTime::parse_from_rfc3339(&entry.created) else {
+            continue;
+        };
+        let at = at.with_timezone(&Utc);
+        let until = at.format(SQLITE_TIMESTAMP_FORMAT).to_string();
+        let since = (at - Duration::seconds(WINDOW_SECS)).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+
+        let audit = state.db.latest_successful_audit(server.client.base_url(), &since, &until);
+        let operation = state.db.latest_operation_active_at(server_alias, &until, &since);
+        let cause = match (audit, operation) {
+            (Ok(None), Ok(None)) => continue,
+            (Ok(audit), Ok(operation)) => EventCause {
+                action: audit
+                    .as_ref()
+                    .map(|(_, action)| action.clone())
+                    .or_else(|| operation.as_ref().map(|operation| operation.kind.clone()))
+                    .unwrap_or_default(),
+                audit_id: audit.map(|(id, _)| id),
+                operation_id: operation.map(|operation| operation.id),
+            },
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("Failed to correlate event {} of {}: {}", entry.entry_id, server_alias, e);
+                continue;
+            }
+        };
+        match state.db.set_sel_entry_cause(&entry, &cause) {
+            Ok(()) => linked += 1,
+            Err(e) => warn!("Failed to link event {} of {}: {}", entry.entry_id, server_alias, e),
+        }
+    }
+    linked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditAction;
+    use crate::database::{Keyset, SelRecord, EVENT_SOURCE_REDFISH, EVENT_SOURCE_SEL};
+    use crate::servers::DEFAULT_SERVER_ALIAS;
+    use crate::testing::{app_state_over, databases, unique, ScratchDir};
+
+    fn event(entry_id: &str, created: DateTime<Utc>, source: &str) -> SelRecord {
+        SelRecord {
+            server_alias: DEFAULT_SERVER_ALIAS.to_string(),
+            entry_id: entry_id.to_string(),
+            created: created.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            severity: "OK".to_string(),
+            message: "The OS graceful shut-down occurred.".to_string(),
+            message_id: Some("SYS1003".to_string()),
+            source: source.to_string(),
+            caused_by: None,
+        }
+    }
+
+    /// Stored events of the default server with the given ids, by id.
+    fn stored(state: &AppState, ids: &[&str]) -> Vec<SelRecord> {
+        let mut entries: Vec<SelRecord> = state
+            .db
+            .list_sel_entries(DEFAULT_SERVER_ALIAS, &Keyset::First, 1000)
+            .unwrap()
+            .into_iter()
+            .filter(|entry| ids.contains(&entry.entry_id.as_str()))
+            .collect();
+        entries.sort_by_key(|entry| ids.iter().position(|id| *id == entry.entry_id));
+        entries
+    }
+
+    fn audit_shutdown(state: &AppState) -> i64 {
+        let base_url = state.servers.default_server().client.base_url().to_string();
+        state.audit_server(None, AuditAction::GracefulShutdown, &base_url, &Ok("shut down".to_string()));
+        let (id, action) = state
+            .db
+            .latest_successful_audit(&base_url, "1970-01-01 00:00:00", "9999-12-31 23:59:59")
+            .unwrap()
+            .unwrap();
+        assert_eq!(action, "GracefulShutdown");
+        id
+    }
+
+    #[test]
+    fn duplicate_deliveries_are_stored_and_linked_once() {
+        for db in databases() {
+            let dir = ScratchDir::new();
+            let state = app_state_over(db.db.clone(), &dir);
+            let since = Utc::now() - Duration::minutes(1);
+            let audit_id = audit_shutdown(&state);
+            let id = unique("evt");
+            let at = Utc::now() + Duration::seconds(5);
+
+            assert_eq!(db.record_sel_entries(&[event(&id, at, EVENT_SOURCE_REDFISH)]).unwrap(), 1, "{}", db.backend);
+            // Redelivered, then read again from the SEL.
+            assert_eq!(db.record_sel_entries(&[event(&id, at, EVENT_SOURCE_REDFISH)]).unwrap(), 0, "{}", db.backend);
+            assert_eq!(db.record_sel_entries(&[event(&id, at, EVENT_SOURCE_SEL)]).unwrap(), 0, "{}", db.backend);
+
+            correlate(&state, DEFAULT_SERVER_ALIAS, since);
+            let entries = stored(&state, &[&id]);
+            assert_eq!(entries.len(), 1, "{}", db.backend);
+            let cause = entries[0].caused_by.as_ref().unwrap_or_else(|| panic!("{}: not linked", db.backend));
+            assert_eq!(cause.audit_id, Some(audit_id), "{}", db.backend);
+            assert_eq!(cause.action, "GracefulShutdown", "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn out_of_order_events_are_placed_by_their_own_time() {
+        for db in databases() {
+            let dir = ScratchDir::new();
+            let state = app_state_over(db.db.clone(), &dir);
+            let since = Utc::now() - Duration::minutes(1);
+            let (early, late, before_action) = (unique("evt"), unique("evt"), unique("evt"));
+
+            // The later event, and one from before the action, arrive
+            // before the action has even been audited.
+            db.record_sel_entries(&[
+                event(&late, Utc::now() + Duration::seconds(60), EVENT_SOURCE_REDFISH),
+                event(&before_action, Utc::now() - Duration::minutes(20), EVENT_SOURCE_REDFISH),
+            ])
+            .unwrap();
+            assert_eq!(correlate(&state, DEFAULT_SERVER_ALIAS, since), 0, "{}", db.backend);
+
+            let audit_id = audit_shutdown(&state);
+            db.record_sel_entries(&[event(&early, Utc::now() + Duration::seconds(10), EVENT_SOURCE_SEL)]).unwrap();
+            correlate(&state, DEFAULT_SERVER_ALIAS, since);
+
+            let entries = stored(&state, &[&early, &late, &before_action]);
+            let causes: Vec<Option<i64>> =
+                entries.iter().map(|entry| entry.caused_by.as_ref().and_then(|cause| cause.audit_id)).collect();
+            assert_eq!(causes, [Some(audit_id), Some(audit_id), None], "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn events_without_a_plausible_cause_stay_unlinked() {
+        for db in databases() {
+            let dir = ScratchDir::new();
+            let state = app_state_over(db.db.clone(), &dir);
+            let since = Utc::now() - Duration::minutes(1);
+            let base_url = state.servers.default_server().client.base_url().to_string();
+            let (after_failure, unparsable) = (unique("evt"), unique("evt"));
+
+            // Failed actions and actions against other servers cause nothing.
+            state.audit_server(None, AuditAction::ForceOff, &base_url, &Err("HTTP 500".to_string()));
+            state.audit_server(None, AuditAction::GracefulShutdown, "https://10.9.9.9", &Ok("shut down".to_string()));
+            let mut garbled = event(&unparsable, Utc::now(), EVENT_SOURCE_REDFISH);
+            garbled.created = "yesterday".to_string();
+            db.record_sel_entries(&[event(&after_failure, Utc::now() + Duration::seconds(5), EVENT_SOURCE_SEL), garbled])
+                .unwrap();
+
+            assert_eq!(correlate(&state, DEFAULT_SERVER_ALIAS, since), 0, "{}", db.backend);
+            let entries = stored(&state, &[&after_failure, &unparsable]);
+            assert_eq!(entries.len(), 2, "{}", db.backend);
+            assert!(entries.iter().all(|entry| entry.caused_by.is_none()), "{}", db.backend);
+            assert_eq!(correlate(&state, "no-such-server", since), 0, "{}", db.backend);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{AeadCore, Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use log::info;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::secret::SecretString;
+
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// Encrypts stored iDRAC passwords with AES-256-GCM.
+///
+/// The key comes from `CREDENTIAL_KEY` (base64, 32 bytes) or, failing that,
+/// from a key file next to the database that is generated on first start.
+pub struct CredentialCipher {
+    cipher: Aes256Gcm,
+}
+
+impl CredentialCipher {
+    pub fn load(env_key: Option<&SecretString>, key_path: &Path) -> Result<Self, String> {
+        let key_bytes = match env_key {
+            Some(encoded) => Zeroizing::new(
+                base64::engine::general_purpose::STANDARD
+                    .decode(encoded.expose().trim())
+                    .map_err(|e| format!("CREDENTIAL_KEY is not valid base64: {}", e))?,
+            ),
+            None => load_or_create_key_file(key_path)?,
+        };
+
+        if key_bytes.len() != 32 {
+            return Err(format!("Credential key must be 32 bytes, got {}", key_bytes.len()));
+        }
+
+        Ok(CredentialCipher {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key_bytes)),
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| "Failed to encrypt credential".to_string())?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        Ok(format!(
+            "{}{}",
+            ENCRYPTED_PREFIX,
+            base64::engine::general_purpose::STANDARD.encode(payload)
+        ))
+    }
+
+    /// Decrypt a stored value. Values written before encryption was
+    /// introduced carry no prefix and are returned unchanged.
+    pub fn decrypt(&self, stored: &str) -> Result<SecretString, String> {
+        let Some(encoded) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(SecretString::from(stored.to_string()));
+        };
+
+        let payload = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("Stored credential is not valid base64: {}", e))?;
+        if payload.len() <= NONCE_LEN {
+            return Err("Stored credential is truncated".to_string());
+        }
+
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt credential; was the credential key changed?".to_string())?;
+        String::from_utf8(plaintext)
+            .map(SecretString::from)
+            .map_err(|e| {
+                // The rejected bytes are still the plaintext.
+                drop(Zeroizing::new(e.into_bytes()));
+                "Decrypted credential is not UTF-8".to_string()
+            })
+    }
+}
+
+fn load_or_create_key_file(path: &Path) -> Result<Zeroizing<Vec<u8>>, String> {
+    if path.exists() {
+        let encoded = Zeroizing::new(
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        );
+        return base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map(Zeroizing::new)
+            .map_err(|e| format!("{} is not valid base64: {}", path.display(), e));
+    }
+
+    let key = Zeroizing::new(Aes256Gcm::generate_key(&mut OsRng).to_vec());
+    let encoded = Zeroizing::new(base64::engine::general_purpose::STANDARD.encode(&*key));
+    write_private_file(path, &encoded)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    info!("Generated credential encryption key at {}", path.display());
+    Ok(key)
+}
+
+#[cfg(unix)]
+fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    std::fs::write(path, contents)
+}
@@ -0,0 +1,340 @@
+//! CSV downloads shared by every `.../csv` endpoint: column selection,
+//! delimiter, byte order mark and time zone come from the query, and rows
+//! are fetched and written a chunk at a time so large exports stream.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Utc};
+use futures_util::StreamExt;
+use log::warn;
+use serde::Deserialize;
+
+use crate::api::ApiResponse;
+use crate::database::SQLITE_TIMESTAMP_FORMAT;
+use crate::errors::ErrorCode;
+use crate::validation::FieldError;
+
+/// Rows read from the database for each chunk of a response.
+pub const CHUNK_ROWS: u32 = 1000;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Format options every CSV export takes, next to its own filters.
+#[derive(Deserialize)]
+pub struct CsvQuery {
+    /// Comma-separated column names in output order; every column when absent.
+    pub columns: Option<String>,
+    /// `comma` (the default), `semicolon` or `tab`.
+    pub delimiter: Option<String>,
+    /// Start with a UTF-8 byte order mark, which Excel needs to read
+    /// non-ASCII text.
+    #[serde(default)]
+    pub bom: bool,
+    /// `utc` (the default) for ISO 8601 timestamps, `local` for the
+    /// server's local time, or an offset like `+02:00`.
+    pub tz: Option<String>,
+}
+
+/// A value of one cell.
+pub enum Cell {
+    Text(String),
+    /// A stored timestamp, written in the export's time zone.
+    Time(String),
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Cell::Text(text)
+    }
+}
+
+impl From<Option<String>> for Cell {
+    fn from(text: Option<String>) -> Self {
+        Cell::Text(text.unwrap_or_default())
+    }
+}
+
+/// A column an export offers, by the name `columns` selects it with.
+pub struct Column<T> {
+    pub name: &'static str,
+    pub value: fn(&T) -> Cell,
+}
+
+enum Zone {
+    Utc,
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    fn parse(tz: Option<&str>) -> Result<Self, FieldError> {
+        let tz = tz.map(str::trim).unwrap_or("utc");
+        if tz.eq_ignore_ascii_case("utc") || tz == "Z" {
+            return Ok(Zone::Utc);
+        }
+        if tz.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+        // An RFC 3339 offset is the simplest thing chrono reads one from.
+        DateTime::parse_from_rfc3339(&format!("2000-01-01T00:00:00{}", tz))
+            .map(|time| Zone::Fixed(*time.offset()))
+            .map_err(|_| FieldError {
+                field: "tz",
+                message: "must be utc, local or an offset like +02:00".to_string(),
+            })
+    }
+
+    /// ISO 8601 in UTC; a plain `YYYY-MM-DD HH:MM:SS`, which spreadsheets
+    /// read as a date, in any other zone. Text that is not a timestamp is
+    /// written as it is.
+    fn format(&self, stored: &str) -> String {
+        let time = NaiveDateTime::parse_from_str(stored, SQLITE_TIMESTAMP_FORMAT)
+            .map(|time| time.and_utc())
+            .or_else(|_| DateTime::parse_from_rfc3339(stored).map(|time| time.with_timezone(&Utc)));
+        let Ok(time) = time else {
+            return stored.to_string();
+        };
+        match self {
+            Zone::Utc => time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            Zone::Local => time.with_timezone(&Local).format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+            Zone::Fixed(offset) => time.with_timezone(offset).format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+        }
+    }
+}
+
+/// How one export is written, parsed from its `CsvQuery`.
+pub struct CsvFormat<T: 'static> {
+    columns: Vec<&'static Column<T>>,
+    delimiter: u8,
+    bom: bool,
+    zone: Zone,
+}
+
+impl<T> CsvFormat<T> {
+    pub fn parse(query: &CsvQuery, available: &'static [Column<T>]) -> Result<Self, FieldError> {
+        let columns = match query.columns.as_deref() {
+            None => available.iter().collect(),
+            Some(names) => {
+                let mut columns = Vec::new();
+                for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                    let column = available
+                        .iter()
+                        .find(|column| column.name.eq_ignore_ascii_case(name))
+                        .ok_or_else(|| FieldError {
+                            field: "columns",
+                            message: format!(
+                                "'{}' is not a column; choose from {}",
+                                name,
+                                available.iter().map(|column| column.name).collect::<Vec<_>>().join(", ")
+                            ),
+                        })?;
+                    columns.push(column);
+                }
+                if columns.is_empty() {
+                    return Err(FieldError {
+                        field: "columns",
+                        message: "must name at least one column".to_string(),
+                    });
+                }
+                columns
+            }
+        };
+        let delimiter = match query.delimiter.as_deref().map(str::trim).unwrap_or("comma") {
+            "comma" | "," => b',',
+            "semicolon" | ";" => b';',
+            "tab" | "\t" => b'\t',
+            _ => {
+                return Err(FieldError {
+                    field: "delimiter",
+                    message: "must be comma, semicolon or tab".to_string(),
+                })
+            }
+        };
+        Ok(CsvFormat {
+            columns,
+            delimiter,
+            bom: query.bom,
+            zone: Zone::parse(query.tz.as_deref())?,
+        })
+    }
+
+    /// Write records as RFC 4180: CRLF line ends, and fields holding the
+    /// delimiter, quotes or line breaks quoted with quotes doubled.
+    fn write(&self, records: impl Iterator<Item = Vec<String>>) -> Result<Vec<u8>, String> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .terminator(csv::Terminator::CRLF)
+            .quote_style(csv::QuoteStyle::Necessary)
+            .from_writer(Vec::new());
+        for record in records {
+            writer.write_record(&record).map_err(|e| e.to_string())?;
+        }
+        writer.into_inner().map_err(|e| e.to_string())
+    }
+
+    fn header(&self) -> Result<Vec<u8>, String> {
+        let mut chunk = if self.bom { UTF8_BOM.to_vec() } else { Vec::new() };
+        let names = self.columns.iter().map(|column| column.name.to_string()).collect();
+        chunk.extend(self.write(std::iter::once(names))?);
+        Ok(chunk)
+    }
+
+    fn rows(&self, rows: &[T]) -> Result<Vec<u8>, String> {
+        self.write(rows.iter().map(|row| {
+            self.columns
+                .iter()
+                .map(|column| match (column.value)(row) {
+                    Cell::Text(text) => text,
+                    Cell::Time(stored) => self.zone.format(&stored),
+                })
+                .collect()
+        }))
+    }
+}
+
+/// Answer with a CSV attachment named `filename`. `fetch` returns the next
+/// chunk of rows on every call and `None` once there are no more; the first
+/// chunk is read before answering so that a failing query is still a 500.
+pub fn respond<T, F>(filename: &str, format: CsvFormat<T>, mut fetch: F) -> HttpResponse
+where
+    T: 'static,
+    F: FnMut() -> Result<Option<Vec<T>>, String> + 'static,
+{
+    let first = format
+        .header()
+        .and_then(|mut chunk| {
+            if let Some(rows) = fetch()? {
+                chunk.extend(format.rows(&rows)?);
+            }
+            Ok(chunk)
+        });
+    let first = match first {
+        Ok(first) => first,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to export {}: {}", filename, e),
+            ));
+        }
+    };
+
+    // A failure once the body has started can only cut it short.
+    let filename_owned = filename.to_string();
+    let rest = futures_util::stream::unfold(Some((format, fetch)), move |state| {
+        let filename = filename_owned.clone();
+        async move {
+            let (format, mut fetch) = state?;
+            match fetch().and_then(|rows| rows.map(|rows| format.rows(&rows)).transpose()) {
+                Ok(Some(chunk)) => Some((Ok(web::Bytes::from(chunk)), Some((format, fetch)))),
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("Export of {} stopped early: {}", filename, e);
+                    Some((Err(actix_web::error::ErrorInternalServerError(e)), None))
+                }
+            }
+        }
+    });
+    let body = futures_util::stream::once(async move { Ok::<_, actix_web::Error>(web::Bytes::from(first)) })
+        .chain(rest);
+
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+        .streaming(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Row {
+        name: String,
+        at: String,
+    }
+
+    static COLUMNS: [Column<Row>; 2] = [
+        Column { name: "name", value: |row| row.name.clone().into() },
+        Column { name: "at", value: |row| Cell::Time(row.at.clone()) },
+    ];
+
+    fn query(delimiter: Option<&str>, bom: bool) -> CsvQuery {
+        CsvQuery {
+            columns: None,
+            delimiter: delimiter.map(str::to_string),
+            bom,
+            tz: None,
+        }
+    }
+
+    fn row(name: &str) -> Row {
+        Row {
+            name: name.to_string(),
+            at: "2024-05-01 12:30:00".to_string(),
+        }
+    }
+
+    /// The whole body `respond` streams for `rows`, fetched `CHUNK_ROWS` at a time.
+    async fn export(query: &CsvQuery, mut rows: Vec<Row>) -> Vec<u8> {
+        let format = CsvFormat::parse(query, &COLUMNS).unwrap();
+        let fetch = move || {
+            if rows.is_empty() {
+                return Ok(None);
+            }
+            let rest = rows.split_off(rows.len().min(CHUNK_ROWS as usize));
+            Ok(Some(std::mem::replace(&mut rows, rest)))
+        };
+        let response = respond("test.csv", format, fetch);
+        actix_web::body::to_bytes(response.into_body()).await.unwrap().to_vec()
+    }
+
+    #[actix_web::test]
+    async fn special_characters_are_quoted() {
+        let rows = vec![row("say \"hi\""), row("a,b"), row("line\r\nbreak"), row("line\nfeed"), row("plain")];
+        let body = export(&query(None, false), rows).await;
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "name,at\r\n\
+             \"say \"\"hi\"\"\",2024-05-01T12:30:00Z\r\n\
+             \"a,b\",2024-05-01T12:30:00Z\r\n\
+             \"line\r\nbreak\",2024-05-01T12:30:00Z\r\n\
+             \"line\nfeed\",2024-05-01T12:30:00Z\r\n\
+             plain,2024-05-01T12:30:00Z\r\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn bom_is_written_only_when_asked_for() {
+        let with_bom = export(&query(None, true), vec![row("é")]).await;
+        assert!(with_bom.starts_with(b"\xEF\xBB\xBFname,at\r\n"));
+        assert_eq!(with_bom.windows(3).filter(|bytes| *bytes == UTF8_BOM).count(), 1);
+
+        let without_bom = export(&query(None, false), vec![row("é")]).await;
+        assert!(without_bom.starts_with(b"name,at\r\n"));
+    }
+
+    #[actix_web::test]
+    async fn each_delimiter_separates_and_quotes_fields() {
+        for (name, delimiter) in [("comma", ','), ("semicolon", ';'), ("tab", '\t')] {
+            let rows = vec![row("plain"), row(&format!("has{}delimiter", delimiter))];
+            let body = String::from_utf8(export(&query(Some(name), false), rows).await).unwrap();
+            let expected = format!(
+                "name{d}at\r\nplain{d}2024-05-01T12:30:00Z\r\n\"has{d}delimiter\"{d}2024-05-01T12:30:00Z\r\n",
+                d = delimiter
+            );
+            assert_eq!(body, expected, "{}", name);
+        }
+        assert!(CsvFormat::parse(&query(Some("pipe"), false), &COLUMNS).is_err());
+    }
+
+    #[actix_web::test]
+    async fn exports_span_several_chunks() {
+        let count = CHUNK_ROWS as usize * 2 + 5;
+        let rows = (0..count).map(|i| row(&format!("row {}", i))).collect();
+        let body = String::from_utf8(export(&query(None, true), rows).await).unwrap();
+        let lines: Vec<&str> = body.trim_start_matches('\u{feff}').split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), count + 1);
+        assert_eq!(lines[0], "name,at");
+        for (i, line) in lines[1..].iter().enumerate() {
+            assert_eq!(*line, format!("row {},2024-05-01T12:30:00Z", i));
+        }
+    }
+}
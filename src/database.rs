@@ -1,150 +1,1637 @@
-use r2d2::Pool;
-use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::Result;
 use bcrypt::{hash, verify, DEFAULT_COST};
-use log::info;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::RwLock;
+
+pub use crate::api::{Operation, OperationStage, PowerChangeReason, PowerEvent};
+use crate::scrub;
+use crate::secret::SecretString;
+
+#[cfg(feature = "postgres")]
+mod postgres;
+mod sqlite;
+
+/// Format SQLite uses for `CURRENT_TIMESTAMP`; every backend stores
+/// timestamps in this format so they compare correctly as strings.
+pub const SQLITE_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// `auth_source` of accounts with a local password.
+pub const AUTH_SOURCE_LOCAL: &str = "local";
+/// `auth_source` of accounts that sign in through LDAP.
+pub const AUTH_SOURCE_LDAP: &str = "ldap";
+/// `auth_source` of accounts that sign in through OpenID Connect.
+pub const AUTH_SOURCE_OIDC: &str = "oidc";
+
+/// What a user may do. Stored lowercase in `users.role`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    /// Full access.
+    Admin,
+    /// Can read power state and inventory but not change anything.
+    ReadOnly,
+}
+
+impl UserRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserRole::Admin => "admin",
+            UserRole::ReadOnly => "readonly",
+        }
+    }
+
+    /// Unknown values read from the database fall back to `ReadOnly`.
+    pub fn parse(value: &str) -> UserRole {
+        match value {
+            "admin" => UserRole::Admin,
+            _ => UserRole::ReadOnly,
+        }
+    }
+
+    /// Whether a user with this role may do what `required` allows.
+    pub fn includes(&self, required: UserRole) -> bool {
+        *self == required || *self == UserRole::Admin
+    }
+}
+
+impl fmt::Display for UserRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct User {
     pub id: i64,
     pub username: String,
     pub password_hash: String,
+    /// Login is refused from this time on.
+    pub expires_at: Option<String>,
+    /// Set when an expired account is switched off by the expiry task.
+    pub disabled_at: Option<String>,
+    /// Bumped to invalidate every existing session of the user.
+    pub session_generation: i64,
+    /// `local`, or `ldap` or `oidc` for accounts provisioned on their first
+    /// directory or single sign-on login, which have no password hash.
+    pub auth_source: String,
+    /// Where password reset links are sent.
+    pub email: Option<String>,
+    pub role: UserRole,
+}
+
+impl User {
+    /// Whether the account signs in through LDAP or OIDC rather than a
+    /// local password.
+    pub fn is_external_account(&self) -> bool {
+        self.auth_source != AUTH_SOURCE_LOCAL
+    }
+
+    /// Whether the account may log in and use its sessions and tokens.
+    pub fn is_active(&self) -> bool {
+        let now = chrono::Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string();
+        self.disabled_at.is_none() && self.expires_at.as_ref().is_none_or(|expires_at| *expires_at > now)
+    }
+}
+
+/// Lets a user without the admin role run power actions on one server,
+/// e.g. a vendor engineer for the length of a visit.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerGrant {
+    pub id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub server_alias: String,
+    pub created_at: String,
+    /// The grant stops working at this time; `None` never expires.
+    pub expires_at: Option<String>,
+}
+
+impl ServerGrant {
+    pub fn is_active(&self) -> bool {
+        let now = chrono::Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string();
+        self.expires_at.as_ref().is_none_or(|expires_at| *expires_at > now)
+    }
+}
+
+/// A user as shown in listings, without the password hash.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserSummary {
+    pub id: i64,
+    pub username: String,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub disabled_at: Option<String>,
+    pub auth_source: String,
+    pub role: UserRole,
+}
+
+/// A configuration applied to every member of a group, one after another.
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupApplyJob {
+    pub id: String,
+    pub group: String,
+    pub config: serde_json::Value,
+    /// `running`, `completed`, `partial`, `failed` or `interrupted`.
+    pub status: String,
+    /// One entry per member applied so far.
+    pub results: serde_json::Value,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// An iDRAC registered through the API, in addition to the one configured
+/// from the environment.
+#[derive(Debug, Clone)]
+pub struct ServerRecord {
+    pub id: i64,
+    pub name: String,
+    pub slug: String,
+    pub host: String,
+    pub username: String,
+    /// Encrypted; see `CredentialCipher`.
+    pub password: String,
+    pub tags: Vec<String>,
+    pub location: Option<String>,
+    /// Cap restored when no power cap schedule is active.
+    pub default_power_cap_watts: Option<u32>,
+    /// The server runs this application; see `RegisteredServer`.
+    pub hosts_this_app: bool,
+    /// Agent endpoint on the server's OS probed by `os_health`.
+    pub os_health_url: Option<String>,
+    /// Accept any TLS certificate from `os_health_url`.
+    pub os_health_insecure: bool,
+    /// Bearer token for `os_health_url`. Encrypted; see `CredentialCipher`.
+    pub os_health_token: Option<String>,
+}
+
+/// Fields for a server about to be inserted.
+#[derive(Debug, Clone)]
+pub struct NewServer {
+    pub name: String,
+    pub host: String,
+    pub username: String,
+    /// Plaintext until `ServerRegistry::create` encrypts it for storage.
+    pub password: SecretString,
+    pub tags: Vec<String>,
+    pub location: Option<String>,
+    pub default_power_cap_watts: Option<u32>,
+    pub hosts_this_app: bool,
+    pub os_health_url: Option<String>,
+    pub os_health_insecure: bool,
+    /// Plaintext until `ServerRegistry::create` encrypts it for storage.
+    pub os_health_token: Option<SecretString>,
+}
+
+/// Applies `watts` for `duration_minutes` each time `cron_expr` fires.
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerCapSchedule {
+    pub id: i64,
+    pub server_alias: String,
+    pub cron_expr: String,
+    pub watts: u32,
+    pub duration_minutes: u32,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+/// Peak-rate hours during which a server, or every member of a group, is
+/// capped at `watts`. Exactly one of `server_alias` and `group_id` is set.
+#[derive(Debug, Clone, Serialize)]
+pub struct TariffWindow {
+    pub id: i64,
+    pub server_alias: Option<String>,
+    pub group_id: Option<i64>,
+    /// Weekdays the window opens on, `mon` to `sun`.
+    pub days: Vec<String>,
+    /// `HH:MM` in the app host's local time. An `end` at or before `start`
+    /// runs past midnight.
+    pub start: String,
+    pub end: String,
+    pub watts: u32,
+    pub created_at: String,
+}
+
+/// The cap a tariff window put on a server, kept until the window ends.
+#[derive(Debug, Clone, Serialize)]
+pub struct TariffCap {
+    pub server_alias: String,
+    pub window_id: i64,
+    pub watts: u32,
+    /// Cap to restore when the window ends; `None` removes the cap.
+    pub previous_watts: Option<u32>,
+    pub applied_at: String,
+}
+
+/// A power action run once when `execute_at` is reached.
+#[derive(Debug, Clone, Serialize)]
+pub struct OneShotSchedule {
+    pub id: i64,
+    pub server_alias: String,
+    /// `on`, `off` or `shutdown`.
+    pub action: String,
+    pub execute_at: String,
+    pub created_by: Option<i64>,
+    pub created_at: String,
+    /// `pending`, `running` while the action is in flight, then `executed`.
+    pub status: String,
+    pub executed_at: Option<String>,
+    /// `success`, `failure` or `skipped` once executed.
+    pub outcome: Option<String>,
+    pub message: Option<String>,
+}
+
+/// A reboot planned for `reboot_at` to apply a firmware update staged on
+/// the iDRAC as `job_id`; see `firmware_schedule`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FirmwareUpdateSchedule {
+    pub id: i64,
+    pub server_alias: String,
+    pub job_id: String,
+    pub reboot_at: String,
+    pub created_by: Option<i64>,
+    pub created_at: String,
+    /// `pending`, `running` from the reboot until the job ends, then `executed`.
+    pub status: String,
+    /// Operation following the reboot and the job, once started.
+    pub operation_id: Option<String>,
+    pub executed_at: Option<String>,
+    /// `success`, `failure` or `skipped` once executed.
+    pub outcome: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Emergency admin access issued from the host's shell; see `break_glass`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakGlassGrant {
+    pub id: i64,
+    pub reason: String,
+    /// OS account that ran the command.
+    pub issued_by: String,
+    pub created_at: String,
+    pub expires_at: String,
+    /// When the link started its session; it cannot be used again.
+    pub used_at: Option<String>,
+}
+
+/// A read-only status link covering a fixed list of servers or every
+/// server with a tag; see `shares`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Share {
+    pub id: i64,
+    pub name: String,
+    /// Server aliases; empty when the share covers `tag` instead.
+    pub servers: Vec<String>,
+    pub tag: Option<String>,
+    #[serde(skip)]
+    pub passcode_hash: Option<String>,
+    pub has_passcode: bool,
+    pub created_by: i64,
+    pub created_at: String,
+    pub expires_at: String,
+    pub last_accessed_at: Option<String>,
+    pub access_count: i64,
+}
+
+/// What `create_share` stores; `token_hash` comes from
+/// `tokens::hash_token` and `passcode_hash` from bcrypt.
+pub struct NewShare<'a> {
+    pub name: &'a str,
+    pub servers: &'a [String],
+    pub tag: Option<&'a str>,
+    pub token_hash: &'a str,
+    pub passcode_hash: Option<&'a str>,
+    pub created_by: i64,
+    pub expires_at: &'a str,
+}
+
+/// One row of the audit log as exported to external collectors.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub action: String,
+    pub server_name: String,
+    pub result: String,
+    pub error_message: Option<String>,
+    pub details: Option<serde_json::Value>,
+    pub created_at: String,
+}
+
+/// A personal API token. The token itself is only ever stored hashed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    /// Requests allowed per clock hour; `None` is unlimited.
+    pub requests_per_hour: Option<u32>,
+}
+
+/// One user's requests within one clock hour, through a session or through
+/// one API token.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UsageKey {
+    pub user_id: i64,
+    /// `None` for requests made with a session.
+    pub token_id: Option<i64>,
+    /// Start of the hour, in `SQLITE_TIMESTAMP_FORMAT`.
+    pub hour: String,
+}
+
+/// Stored request count of one user or token in one hour.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiUsage {
+    pub user_id: i64,
+    pub username: Option<String>,
+    pub token_id: Option<i64>,
+    pub token_name: Option<String>,
+    pub hour: String,
+    pub requests: u64,
+}
+
+/// One firmware component as last collected from a server.
+#[derive(Debug, Clone, Serialize)]
+pub struct FirmwareInventoryEntry {
+    pub server_alias: String,
+    pub component: String,
+    pub version: String,
+    pub collected_at: String,
+}
+
+/// Version requirement for one component in a compliance profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceRequirement {
+    pub component: String,
+    pub minimum_version: String,
+    pub recommended_version: Option<String>,
+}
+
+/// A named set of servers sharing a power budget.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerGroup {
+    pub id: i64,
+    pub name: String,
+    /// Server aliases; `default` is the server from the environment.
+    pub members: Vec<String>,
+    pub power_budget_watts: Option<u32>,
+    pub created_at: String,
+}
+
+/// Power draw of one server at one sampling round.
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerSample {
+    pub server_alias: String,
+    pub watts: u32,
+    pub sampled_at: String,
+}
+
+/// One round of ICMP echoes to a server's iDRAC; see `ping`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PingSample {
+    pub id: i64,
+    pub server_alias: String,
+    /// Mean round trip of the echoes answered; `None` when none were.
+    pub latency_ms: Option<f64>,
+    pub packet_loss_pct: f64,
+    pub recorded_at: String,
+}
+
+/// Power draw of one server during one past hour.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HourSummary {
+    /// Start of the hour, UTC.
+    pub hour_start: String,
+    pub median_watts: f64,
+    /// Median absolute deviation of the hour's samples.
+    pub mad_watts: f64,
+    pub samples: u32,
+}
+
+/// Usual draw of a server in one hour of the week (local time, 0 is
+/// Monday 00:00), summarized from the same hour in recent weeks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerBaseline {
+    pub server_alias: String,
+    pub hour_of_week: u32,
+    /// Oldest first.
+    pub weeks: Vec<HourSummary>,
+    pub median_watts: f64,
+    pub mad_watts: f64,
+    pub updated_at: String,
+}
+
+/// Highest combined draw of a set of servers within one sampling round.
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerPeak {
+    pub watts: u64,
+    pub sampled_at: String,
+}
+
+/// Where a keyset page starts, relative to the sort key `K` of a row in
+/// list order. Rows are returned in list order for `First` and `After`
+/// and in reverse for `Before`, so the nearest rows to the key come first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Keyset<K> {
+    First,
+    After(K),
+    Before(K),
+}
+
+impl<K> Keyset<K> {
+    pub fn key(&self) -> Option<&K> {
+        match self {
+            Keyset::First => None,
+            Keyset::After(key) | Keyset::Before(key) => Some(key),
+        }
+    }
+
+    /// Comparison against the key and `ORDER BY` direction of the query,
+    /// for a list sorted ascending or, with `descending`, descending.
+    pub fn sql(&self, descending: bool) -> (&'static str, &'static str) {
+        let forward = !matches!(self, Keyset::Before(_));
+        if forward != descending {
+            (">", "ASC")
+        } else {
+            ("<", "DESC")
+        }
+    }
+}
+
+/// One value from a telemetry metric report pushed by an iDRAC.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricSample {
+    /// Row id, breaking timestamp ties when paging; 0 until stored.
+    #[serde(skip)]
+    pub id: i64,
+    pub server_alias: String,
+    /// Id of the report, normally its metric report definition.
+    pub report_id: Option<String>,
+    pub metric_id: String,
+    /// The resource property the value was read from.
+    pub metric_property: Option<String>,
+    /// As reported; Redfish sends metric values as text.
+    pub value: String,
+    /// `value` parsed as a number, if it is one.
+    pub numeric_value: Option<f64>,
+    pub timestamp: String,
+}
+
+/// Bucket size of a metric rollup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollupGranularity {
+    Hour,
+    Day,
+}
+
+impl RollupGranularity {
+    pub const ALL: [RollupGranularity; 2] = [RollupGranularity::Hour, RollupGranularity::Day];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RollupGranularity::Hour => "hour",
+            RollupGranularity::Day => "day",
+        }
+    }
+
+    /// SQL truncating a `timestamp` column to the start of its bucket;
+    /// timestamps are stored as `YYYY-MM-DD HH:MM:SS` text.
+    pub fn bucket_sql(self) -> &'static str {
+        match self {
+            RollupGranularity::Hour => "substr(timestamp, 1, 13) || ':00:00'",
+            RollupGranularity::Day => "substr(timestamp, 1, 10) || ' 00:00:00'",
+        }
+    }
+
+    pub fn seconds(self) -> i64 {
+        match self {
+            RollupGranularity::Hour => 3600,
+            RollupGranularity::Day => 86400,
+        }
+    }
+}
+
+/// Minimum, mean and maximum of one metric of one server over an hour or
+/// a day, computed from the samples with a numeric value.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricRollup {
+    /// Row id, breaking `bucket_start` ties when paging.
+    #[serde(skip)]
+    pub id: i64,
+    pub server_alias: String,
+    pub metric_id: String,
+    pub bucket_start: String,
+    pub sample_count: i64,
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+/// `SelRecord::source` of entries read from the SEL by the nightly sweep.
+pub const EVENT_SOURCE_SEL: &str = "sel";
+/// `SelRecord::source` of events the iDRAC pushed to `/api/events/ingest`.
+pub const EVENT_SOURCE_REDFISH: &str = "redfish_event";
+
+/// A System Event Log entry pulled from a server's iDRAC, or an event it
+/// pushed. The same event from both is stored once, keyed on server,
+/// message id, `created` and `entry_id`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SelRecord {
+    pub server_alias: String,
+    /// The iDRAC's sequence number of the entry: the SEL entry's `Id`, or
+    /// a pushed event's `EventId`. Ids start over when the SEL is cleared.
+    pub entry_id: String,
+    pub created: String,
+    pub severity: String,
+    pub message: String,
+    pub message_id: Option<String>,
+    pub source: String,
+    /// The application action the event followed; see `correlation`.
+    pub caused_by: Option<EventCause>,
+}
+
+/// An audited action or tracked operation an event is linked to.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventCause {
+    /// The audit action, or the operation kind when no entry was audited.
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation_id: Option<String>,
+}
+
+/// One server's daily health report; see `health_report`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub id: i64,
+    pub server_alias: String,
+    /// Local date of the run, `YYYY-MM-DD`.
+    pub report_date: String,
+    pub created_at: String,
+    pub report: serde_json::Value,
+}
+
+/// Audit log totals over a time range.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditStatistics {
+    pub total_actions: u64,
+    pub actions_by_type: BTreeMap<String, u64>,
+    pub actions_by_user: Vec<UserActionCount>,
+    pub actions_by_server: Vec<ServerActionCount>,
+    /// Share of entries whose result was a failure, from 0.0 to 1.0.
+    pub failure_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserActionCount {
+    /// `system` for actions taken by background tasks.
+    pub user: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerActionCount {
+    pub server: String,
+    pub count: u64,
+}
+
+/// Failure reported by a `Store`.
+#[derive(Debug)]
+pub enum DbError {
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "postgres")]
+    Postgres(::postgres::Error),
+    Pool(r2d2::Error),
+    /// Configuration and data problems not raised by a driver.
+    Other(String),
+}
+
+impl DbError {
+    /// `disk_full` or `read_only` when the statement failed because the
+    /// database cannot take writes at all, rather than because of what
+    /// was written.
+    pub fn write_failure_kind(&self) -> Option<&'static str> {
+        match self {
+            DbError::Sqlite(rusqlite::Error::SqliteFailure(err, _)) => match err.code {
+                rusqlite::ErrorCode::DiskFull => Some("disk_full"),
+                rusqlite::ErrorCode::ReadOnly => Some("read_only"),
+                _ => None,
+            },
+            #[cfg(feature = "postgres")]
+            DbError::Postgres(e) => match e.code() {
+                Some(code) if *code == ::postgres::error::SqlState::DISK_FULL => Some("disk_full"),
+                Some(code) if *code == ::postgres::error::SqlState::READ_ONLY_SQL_TRANSACTION => Some("read_only"),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether the statement violated a UNIQUE constraint.
+    pub fn is_unique_violation(&self) -> bool {
+        match self {
+            DbError::Sqlite(rusqlite::Error::SqliteFailure(err, _)) => {
+                err.code == rusqlite::ErrorCode::ConstraintViolation
+            }
+            #[cfg(feature = "postgres")]
+            DbError::Postgres(e) => e.code() == Some(&::postgres::error::SqlState::UNIQUE_VIOLATION),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Sqlite(e) => e.fmt(f),
+            #[cfg(feature = "postgres")]
+            DbError::Postgres(e) => e.fmt(f),
+            DbError::Pool(e) => e.fmt(f),
+            DbError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        note_write_failure(DbError::Sqlite(e))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<::postgres::Error> for DbError {
+    fn from(e: ::postgres::Error) -> Self {
+        note_write_failure(DbError::Postgres(e))
+    }
+}
+
+/// Why the database stopped taking writes; see `Database::write_failure`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteFailure {
+    /// `disk_full` or `read_only`.
+    pub kind: &'static str,
+    pub error: String,
+    pub since: String,
 }
 
-pub type DbPool = Pool<SqliteConnectionManager>;
+/// Set by the first driver error saying writes cannot succeed, and cleared
+/// by `Database::probe_writes`. Every driver error passes through the
+/// `From` conversions above, so no call site has to report it.
+static WRITE_FAILURE: RwLock<Option<WriteFailure>> = RwLock::new(None);
 
+fn note_write_failure(e: DbError) -> DbError {
+    if let Some(kind) = e.write_failure_kind() {
+        let mut failure = WRITE_FAILURE.write().unwrap();
+        if failure.is_none() {
+            error!("Database writes are failing ({}); entering degraded mode until a probe write succeeds", e);
+            *failure = Some(WriteFailure {
+                kind,
+                error: e.to_string(),
+                since: chrono::Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+            });
+        }
+    }
+    e
+}
+
+/// Bytes available to unprivileged users on the file system holding a
+/// SQLite database; `None` for PostgreSQL or when `df` cannot tell.
+pub fn free_space_bytes(location: &str) -> Option<u64> {
+    if location.starts_with("postgres://") || location.starts_with("postgresql://") {
+        return None;
+    }
+    let path = std::path::Path::new(location.strip_prefix("sqlite://").unwrap_or(location));
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+    let output = std::process::Command::new("df").arg("-Pk").arg(dir).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let available_kib: u64 = stdout.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available_kib * 1024)
+}
+
+impl From<r2d2::Error> for DbError {
+    fn from(e: r2d2::Error) -> Self {
+        DbError::Pool(e)
+    }
+}
+
+impl From<serde_json::Error> for DbError {
+    fn from(e: serde_json::Error) -> Self {
+        DbError::Other(e.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, DbError>;
+
+/// Everything the application reads from or writes to its database. Each
+/// backend implements it with its own SQL; nothing outside this module
+/// depends on which one is in use.
+pub trait Store: Send + Sync {
+    fn is_read_only(&self) -> bool;
+    /// Re-open read-write, bringing the schema up to date.
+    fn promote(&self) -> Result<()>;
+
+    fn has_users(&self) -> Result<bool>;
+    fn insert_user(&self, username: &str, password_hash: &str, expires_at: Option<&str>, role: UserRole) -> Result<i64>;
+    /// Add an account signing in through `auth_source`, which has no
+    /// password hash.
+    fn insert_external_user(&self, username: &str, auth_source: &str, role: UserRole) -> Result<i64>;
+    /// Returns whether the user exists.
+    fn set_user_role(&self, user_id: i64, role: UserRole) -> Result<bool>;
+    fn get_user_by_username(&self, username: &str) -> Result<Option<User>>;
+    fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>>;
+    /// Users ordered by name. Expired and disabled accounts are left out
+    /// unless `include_expired` is set.
+    fn list_users(&self, include_expired: bool) -> Result<Vec<UserSummary>>;
+    /// Accounts expiring between `from` and `until`, soonest first.
+    fn list_user_expirations(&self, from: &str, until: &str) -> Result<Vec<UserSummary>>;
+    /// Change or clear an account's expiry. Moving the expiry into the
+    /// future re-enables an account the expiry task disabled. Returns
+    /// whether the user exists.
+    fn set_user_expiry(&self, user_id: i64, expires_at: Option<&str>) -> Result<bool>;
+    /// Disable every account past its expiry and invalidate its sessions.
+    /// Accounts are kept so their audit history stays attributable.
+    fn disable_expired_users(&self) -> Result<Vec<UserSummary>>;
+    /// Grant `user_id` power actions on `server_alias` until `expires_at`,
+    /// or change the expiry of the grant they already hold.
+    fn set_server_grant(&self, user_id: i64, server_alias: &str, expires_at: Option<&str>) -> Result<ServerGrant>;
+    fn get_server_grant(&self, user_id: i64, server_alias: &str) -> Result<Option<ServerGrant>>;
+    /// A user's grants by server. Expired ones are left out unless
+    /// `include_expired` is set.
+    fn list_server_grants(&self, user_id: i64, include_expired: bool) -> Result<Vec<ServerGrant>>;
+    /// Grants expiring between `from` and `until`, soonest first.
+    fn list_server_grant_expirations(&self, from: &str, until: &str) -> Result<Vec<ServerGrant>>;
+    /// Returns whether the grant existed.
+    fn delete_server_grant(&self, user_id: i64, server_alias: &str) -> Result<bool>;
+    /// Set or clear the address password reset links are sent to. Returns
+    /// whether the user exists.
+    fn set_user_email(&self, user_id: i64, email: Option<&str>) -> Result<bool>;
+    /// The account with this email address, compared case-insensitively.
+    fn get_user_by_email(&self, email: &str) -> Result<Option<User>>;
+
+    fn record_audit(
+        &self,
+        user_id: Option<i64>,
+        action: &str,
+        server_name: &str,
+        result: &str,
+        error_message: Option<&str>,
+        details: Option<&str>,
+    ) -> Result<()>;
+    /// Aggregate audit entries created within `[since, until)`. `None`
+    /// leaves a bound open.
+    fn audit_statistics(&self, since: Option<&str>, until: Option<&str>) -> Result<AuditStatistics>;
+    /// Audit entries in id order, newest first with `descending`. Paging by
+    /// id must never skip an entry that was still being written.
+    fn list_audit_page(&self, keyset: &Keyset<i64>, limit: u32, descending: bool) -> Result<Vec<AuditEntry>>;
+    /// Span of audit ids, which the purge and rare rollbacks make an
+    /// overestimate of the entry count.
+    fn audit_count_estimate(&self) -> Result<u64>;
+    /// Delete audit entries older than `days` days. Returns how many were removed.
+    fn purge_audit_older_than(&self, days: u32) -> Result<usize>;
+    /// Id and action of the newest successful entry against `server_name`
+    /// created within `[since, until]`.
+    fn latest_successful_audit(&self, server_name: &str, since: &str, until: &str) -> Result<Option<(i64, String)>>;
+
+    fn create_operation(&self, kind: &str, server_alias: Option<&str>) -> Result<String>;
+    fn get_operation(&self, id: &str) -> Result<Option<Operation>>;
+    /// The latest `limit` operations of `kind` on one server, newest first.
+    fn list_server_operations(&self, server_alias: &str, kind: &str, limit: u32) -> Result<Vec<Operation>>;
+    /// Operations of `kind` still `running`, oldest first.
+    fn list_running_operations(&self, kind: &str) -> Result<Vec<Operation>>;
+    /// The newest operation on one server created by `at` that is still
+    /// running or was last updated no earlier than `since`.
+    fn latest_operation_active_at(&self, server_alias: &str, at: &str, since: &str) -> Result<Option<Operation>>;
+    fn update_operation(&self, id: &str, stages: &[OperationStage], status: &str) -> Result<()>;
+    /// Delete finished operations last updated more than `days` days ago.
+    fn purge_operations_older_than(&self, days: u32) -> Result<usize>;
+
+    fn create_group_apply_job(&self, group: &str, config: &str) -> Result<String>;
+    fn update_group_apply_job(&self, id: &str, status: &str, results: &str) -> Result<()>;
+    fn get_group_apply_job(&self, id: &str) -> Result<Option<GroupApplyJob>>;
+    /// Mark jobs left `running` by a previous process as `interrupted`.
+    fn interrupt_running_group_apply_jobs(&self) -> Result<usize>;
+    /// Delete finished jobs last updated more than `days` days ago.
+    fn purge_group_apply_jobs_older_than(&self, days: u32) -> Result<usize>;
+
+    fn create_event_subscription(&self, destination: &str, context: &str, subscription_uri: &str) -> Result<()>;
+    /// Whether `context` matches a subscription this application created.
+    fn is_known_event_context(&self, context: &str) -> Result<bool>;
+
+    /// Renew the primary lease for `holder`, taking it over if necessary.
+    fn heartbeat_lease(&self, holder: &str) -> Result<()>;
+    /// Seconds since the primary last renewed its lease, if it ever has.
+    fn primary_lease_age_secs(&self) -> Result<Option<i64>>;
+
+    /// All stored preferences for a user as raw JSON strings, keyed by name.
+    fn get_user_preferences(&self, user_id: i64) -> Result<Vec<(String, String)>>;
+    /// Replace every preference for a user in one transaction.
+    fn replace_user_preferences(&self, user_id: i64, preferences: &[(String, String)]) -> Result<()>;
+
+    /// Insert a server. `server.password` must already be encrypted.
+    fn create_server(&self, server: &NewServer) -> Result<ServerRecord>;
+    fn list_servers(&self) -> Result<Vec<ServerRecord>>;
+    /// Replace the username and/or the (already encrypted) password of a
+    /// server. Returns the updated record, or `None` if there is no such server.
+    fn update_server_credentials(&self, id: i64, username: Option<&str>, password: Option<&str>) -> Result<Option<ServerRecord>>;
+    /// Replace the OS health settings of a server; the token is already
+    /// encrypted. Returns the updated record, or `None` if there is no such server.
+    fn update_server_os_health(
+        &self,
+        id: i64,
+        url: Option<&str>,
+        insecure: bool,
+        token: Option<&str>,
+    ) -> Result<Option<ServerRecord>>;
+    /// Delete a server with its power cap schedules, tariff windows, pending
+    /// one-shot schedules and power baselines. History stays until retention purges
+    /// it. Returns whether a server was deleted.
+    fn delete_server(&self, id: i64) -> Result<bool>;
+
+    fn create_power_cap_schedule(
+        &self,
+        server_alias: &str,
+        cron_expr: &str,
+        watts: u32,
+        duration_minutes: u32,
+        enabled: bool,
+    ) -> Result<PowerCapSchedule>;
+    /// Schedules for one server, or for every server when `server_alias` is `None`.
+    fn list_power_cap_schedules(&self, server_alias: Option<&str>) -> Result<Vec<PowerCapSchedule>>;
+    /// Returns whether a schedule was deleted.
+    fn delete_power_cap_schedule(&self, server_alias: &str, id: i64) -> Result<bool>;
+
+    fn create_tariff_window(
+        &self,
+        server_alias: Option<&str>,
+        group_id: Option<i64>,
+        days: &[String],
+        start: &str,
+        end: &str,
+        watts: u32,
+    ) -> Result<TariffWindow>;
+    fn list_tariff_windows(&self) -> Result<Vec<TariffWindow>>;
+    /// Returns whether a window was deleted.
+    fn delete_tariff_window(&self, id: i64) -> Result<bool>;
+    fn list_tariff_caps(&self) -> Result<Vec<TariffCap>>;
+    /// Record the cap in force on `cap.server_alias`, replacing any earlier one.
+    fn set_tariff_cap(&self, cap: &TariffCap) -> Result<()>;
+    fn delete_tariff_cap(&self, server_alias: &str) -> Result<()>;
+
+    fn create_one_shot_schedule(
+        &self,
+        server_alias: &str,
+        action: &str,
+        execute_at: &str,
+        created_by: Option<i64>,
+    ) -> Result<OneShotSchedule>;
+    /// Every one-shot schedule, executed ones included, by execution time.
+    fn list_one_shot_schedules(&self) -> Result<Vec<OneShotSchedule>>;
+    /// Delete a schedule that has not started yet, returning it.
+    fn delete_one_shot_schedule(&self, id: i64) -> Result<Option<OneShotSchedule>>;
+    /// Mark every pending schedule whose time has come as `running` and
+    /// return them, so each is picked up exactly once.
+    fn claim_due_one_shot_schedules(&self) -> Result<Vec<OneShotSchedule>>;
+    fn finish_one_shot_schedule(&self, id: i64, outcome: &str, message: &str) -> Result<()>;
+
+    fn create_firmware_update_schedule(
+        &self,
+        server_alias: &str,
+        job_id: &str,
+        reboot_at: &str,
+        created_by: Option<i64>,
+    ) -> Result<FirmwareUpdateSchedule>;
+    /// Every firmware update schedule, executed ones included, by reboot time.
+    fn list_firmware_update_schedules(&self) -> Result<Vec<FirmwareUpdateSchedule>>;
+    /// Mark every pending schedule whose reboot time has come as `running`
+    /// and return them, so each is picked up exactly once.
+    fn claim_due_firmware_update_schedules(&self) -> Result<Vec<FirmwareUpdateSchedule>>;
+    fn set_firmware_update_operation(&self, id: i64, operation_id: &str) -> Result<()>;
+    fn finish_firmware_update_schedule(&self, id: i64, outcome: &str, message: &str) -> Result<()>;
+
+    /// Store a token for `user_id`; `token_hash` comes from `tokens::hash_token`.
+    fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+        scopes: &[String],
+        requests_per_hour: Option<u32>,
+    ) -> Result<ApiToken>;
+    fn list_api_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>>;
+    /// Returns whether a token was deleted.
+    fn delete_api_token(&self, user_id: i64, id: i64) -> Result<bool>;
+    /// Look up a token by hash and note that it was used. The usage stamp
+    /// is best effort so tokens keep working on a read-only standby.
+    fn find_api_token(&self, token_hash: &str) -> Result<Option<ApiToken>>;
+    /// Set or clear (`None`) a token's hourly quota. Returns whether the
+    /// token exists.
+    fn set_api_token_quota(&self, id: i64, requests_per_hour: Option<u32>) -> Result<bool>;
+
+    /// Add request counts to the stored hourly totals.
+    fn record_api_usage(&self, counts: &[(UsageKey, u64)]) -> Result<()>;
+    /// Hourly totals since `since`, newest first, of one user or everyone.
+    fn list_api_usage(&self, user_id: Option<i64>, since: &str) -> Result<Vec<ApiUsage>>;
+    /// Stored requests of one token within `hour`.
+    fn api_token_usage(&self, token_id: i64, hour: &str) -> Result<u64>;
+    fn purge_api_usage_older_than(&self, days: u32) -> Result<usize>;
+
+    /// Store a break-glass grant; `token_hash` comes from `tokens::hash_token`.
+    fn create_break_glass_grant(
+        &self,
+        token_hash: &str,
+        reason: &str,
+        issued_by: &str,
+        expires_at: &str,
+    ) -> Result<BreakGlassGrant>;
+    /// Mark an unused, unexpired grant as used and return it, so each link
+    /// starts exactly one session.
+    fn claim_break_glass_grant(&self, token_hash: &str) -> Result<Option<BreakGlassGrant>>;
+    /// Grants that have not expired, used or not, newest first.
+    fn list_active_break_glass_grants(&self) -> Result<Vec<BreakGlassGrant>>;
+
+    /// Store a password reset token, replacing any the user had, and drop
+    /// expired ones; `token_hash` comes from `tokens::hash_token`.
+    fn create_password_reset_token(&self, user_id: i64, token_hash: &str, expires_at: &str) -> Result<()>;
+    /// Spend an unexpired token: set the password hash of its local account,
+    /// invalidate every session of the user and delete the token, in one
+    /// transaction. Returns the user id, or `None` if the token is unknown,
+    /// expired or belongs to an external account.
+    fn reset_password_with_token(&self, token_hash: &str, password_hash: &str) -> Result<Option<i64>>;
+
+    fn create_share(&self, share: &NewShare) -> Result<Share>;
+    /// Every share, expired ones included, newest first.
+    fn list_shares(&self) -> Result<Vec<Share>>;
+    /// Returns whether a share was deleted.
+    fn delete_share(&self, id: i64) -> Result<bool>;
+    /// Look up an unexpired share by hash and count the access. The count
+    /// is best effort so shares keep working on a read-only standby.
+    fn find_share(&self, token_hash: &str) -> Result<Option<Share>>;
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>>;
+    fn put_setting(&self, key: &str, value: &str) -> Result<()>;
+
+    /// Replace the cached firmware inventory of one server.
+    fn replace_firmware_inventory(&self, server_alias: &str, components: &[(String, String)]) -> Result<()>;
+    fn list_firmware_inventory(&self) -> Result<Vec<FirmwareInventoryEntry>>;
+    /// Baseline version per component name.
+    fn list_firmware_baselines(&self) -> Result<Vec<(String, String)>>;
+    /// Set the baseline for `component`, or clear it when `version` is `None`.
+    fn set_firmware_baseline(&self, component: &str, version: Option<&str>) -> Result<()>;
+    /// Define a compliance profile, replacing any with the same name.
+    fn replace_compliance_profile(&self, name: &str, requirements: &[ComplianceRequirement]) -> Result<()>;
+    /// Requirements of a profile by component; empty if it does not exist.
+    fn get_compliance_profile(&self, name: &str) -> Result<Vec<ComplianceRequirement>>;
+
+    fn create_server_group(&self, name: &str, members: &[String], power_budget_watts: Option<u32>) -> Result<ServerGroup>;
+    fn list_server_groups(&self) -> Result<Vec<ServerGroup>>;
+    fn get_server_group(&self, id: i64) -> Result<Option<ServerGroup>>;
+    /// Replace a group's members and budget; `None` if it does not exist.
+    fn update_server_group(
+        &self,
+        id: i64,
+        members: &[String],
+        power_budget_watts: Option<u32>,
+    ) -> Result<Option<ServerGroup>>;
+    /// Delete a group with its tariff windows. Returns whether a group was
+    /// deleted.
+    fn delete_server_group(&self, id: i64) -> Result<bool>;
+
+    /// Store one sampling round; every sample shares `sampled_at`.
+    fn record_power_samples(&self, sampled_at: &str, samples: &[(String, u32)]) -> Result<()>;
+    /// The newest sample of each server taken at or after `since`.
+    fn latest_power_samples(&self, since: &str) -> Result<Vec<PowerSample>>;
+    /// The sampling round since `since` in which `aliases` drew the most
+    /// in total.
+    fn peak_power_total(&self, aliases: &[String], since: &str) -> Result<Option<PowerPeak>>;
+    fn purge_power_samples_older_than(&self, days: u32) -> Result<usize>;
+
+    /// Store a power state observed on a server and the reason for its
+    /// last change, unless both match the newest stored for the server.
+    /// Returns whether it was stored.
+    fn record_power_event(&self, server_alias: &str, power_state: &str, reason: Option<&PowerChangeReason>) -> Result<bool>;
+    /// Newest first, optionally of one server.
+    fn list_power_events(&self, server_alias: Option<&str>, limit: u32) -> Result<Vec<PowerEvent>>;
+    fn purge_power_events_older_than(&self, days: u32) -> Result<usize>;
+
+    fn record_ping(&self, server_alias: &str, latency_ms: Option<f64>, packet_loss_pct: f64) -> Result<()>;
+    /// Newest first, optionally only rounds recorded at or after `since`.
+    fn list_ping_history(&self, server_alias: &str, since: Option<&str>, limit: u32) -> Result<Vec<PingSample>>;
+    fn purge_ping_history_older_than(&self, days: u32) -> Result<usize>;
+
+    /// Draw of one server in samples taken within `since`..`until`.
+    fn power_sample_watts(&self, server_alias: &str, since: &str, until: &str) -> Result<Vec<u32>>;
+    fn get_power_baseline(&self, server_alias: &str, hour_of_week: u32) -> Result<Option<PowerBaseline>>;
+    /// Insert or replace the baseline of its server and hour of the week.
+    fn save_power_baseline(&self, baseline: &PowerBaseline) -> Result<()>;
+
+    fn record_metric_samples(&self, samples: &[MetricSample]) -> Result<()>;
+    /// Samples ordered by time and id, optionally of one metric and within
+    /// `since`..=`until`.
+    fn list_metric_samples(
+        &self,
+        metric_id: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        keyset: &Keyset<(String, i64)>,
+        limit: u32,
+    ) -> Result<Vec<MetricSample>>;
+    fn purge_metric_samples_older_than(&self, days: u32) -> Result<usize>;
+    /// Recompute the rollups of every bucket starting at or after `from`
+    /// (every bucket when `None`) from the stored samples, replacing rows
+    /// already there. Returns how many buckets were written.
+    fn roll_up_metric_samples(&self, granularity: RollupGranularity, from: Option<&str>) -> Result<usize>;
+    /// Start of the newest stored bucket.
+    fn latest_metric_rollup(&self, granularity: RollupGranularity) -> Result<Option<String>>;
+    /// Rollups ordered by bucket start and id, with the same filters as
+    /// `list_metric_samples`.
+    fn list_metric_rollups(
+        &self,
+        granularity: RollupGranularity,
+        metric_id: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        keyset: &Keyset<(String, i64)>,
+        limit: u32,
+    ) -> Result<Vec<MetricRollup>>;
+
+    /// Store the entries not stored yet, returning how many were new.
+    fn record_sel_entries(&self, entries: &[SelRecord]) -> Result<usize>;
+    /// Entries of one server without a cause that were stored at or after
+    /// `recorded_since`, oldest event first.
+    fn list_uncorrelated_sel_entries(&self, server_alias: &str, recorded_since: &str) -> Result<Vec<SelRecord>>;
+    fn set_sel_entry_cause(&self, entry: &SelRecord, cause: &EventCause) -> Result<()>;
+    /// Entries linked to an operation, oldest first.
+    fn list_operation_events(&self, operation_id: &str) -> Result<Vec<SelRecord>>;
+    /// Newest first, keyed by `created` and `entry_id`.
+    fn list_sel_entries(&self, server_alias: &str, keyset: &Keyset<(String, String)>, limit: u32) -> Result<Vec<SelRecord>>;
+    fn count_sel_entries(&self, server_alias: &str) -> Result<u64>;
+    /// Delete entries stored more than `days` days ago.
+    fn purge_sel_entries_older_than(&self, days: u32) -> Result<usize>;
+
+    /// Store a server's report for `report_date`, replacing one stored
+    /// earlier that day.
+    fn store_health_report(&self, server_alias: &str, report_date: &str, report: &serde_json::Value) -> Result<()>;
+    /// Newest first, optionally of one server and one date.
+    fn list_health_reports(&self, server_alias: Option<&str>, report_date: Option<&str>, limit: u32) -> Result<Vec<HealthReport>>;
+    /// The newest report of each server, ordered by alias.
+    fn latest_health_reports(&self) -> Result<Vec<HealthReport>>;
+    fn purge_health_reports_older_than(&self, days: u32) -> Result<usize>;
+}
+
+/// The application database. Backend-independent logic lives here; the
+/// rest is forwarded to the `Store` picked by `open`.
 pub struct Database {
-    pool: DbPool,
+    store: Box<dyn Store>,
 }
 
+/// Setting overwritten by `Database::probe_writes`.
+const WRITE_PROBE_SETTING: &str = "write_probe";
+
 impl Database {
-    pub fn new(db_path: &str) -> Result<Self> {
-        // Ensure parent directory exists
-        if let Some(parent) = std::path::Path::new(db_path).parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        }
-
-        // Create the database file if it doesn't exist by opening a connection first
-        {
-            let _conn = rusqlite::Connection::open(db_path)?;
-            info!("Database file created/verified at {}", db_path);
-        }
-
-        let manager = SqliteConnectionManager::file(db_path);
-        let pool = Pool::new(manager)
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        
-        // Initialize schema
-        let conn = pool.get()
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS users (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                username TEXT NOT NULL UNIQUE,
-                password_hash TEXT NOT NULL,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
-        
-        info!("Database initialized at {}", db_path);
-        
-        let db = Database { pool };
-        
+    /// Open the database at `location`, creating the schema and a default
+    /// admin account if needed. `postgres://` and `postgresql://` URLs use
+    /// PostgreSQL; anything else is a SQLite file path, optionally prefixed
+    /// with `sqlite://`.
+    pub fn open(location: &str) -> Result<Self> {
+        let db = Database {
+            store: open_store(location, false)?,
+        };
+
         // Create default admin account if no users exist
         if !db.has_users()? {
             info!("No users found, creating default admin account");
-            db.create_user("admin", "")?;
+            db.create_user("admin", "", None, UserRole::Admin)?;
             info!("Default admin account created (username: admin)");
         }
-        
+
         Ok(db)
     }
 
-    pub fn has_users(&self) -> Result<bool> {
-        let conn = self.pool.get()
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        
-        let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM users",
-            [],
-            |row| row.get(0),
-        )?;
-        Ok(count > 0)
-    }
-
-    pub fn create_user(&self, username: &str, password: &str) -> Result<i64> {
-        let password_hash = hash(password, DEFAULT_COST)
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        
-        let conn = self.pool.get()
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        
-        conn.execute(
-            "INSERT INTO users (username, password_hash) VALUES (?1, ?2)",
-            [username, &password_hash],
-        )?;
-        
+    /// Open an existing database without write access, for a warm standby
+    /// sharing the primary's database. No schema changes are made.
+    pub fn open_read_only(location: &str) -> Result<Self> {
+        Ok(Database {
+            store: open_store(location, true)?,
+        })
+    }
+
+    /// Set while writes fail because the disk is full or the database was
+    /// made read-only underneath the app. Never set on a standby, which is
+    /// read-only by design.
+    pub fn write_failure(&self) -> Option<WriteFailure> {
+        if self.store.is_read_only() {
+            return None;
+        }
+        WRITE_FAILURE.read().unwrap().clone()
+    }
+
+    /// Whether background writers should skip their work: on a standby, or
+    /// while writes are failing.
+    pub fn writes_paused(&self) -> bool {
+        self.store.is_read_only() || self.write_failure().is_some()
+    }
+
+    /// Try a small write while writes are failing, and leave degraded mode
+    /// once one succeeds.
+    pub fn probe_writes(&self) {
+        if self.write_failure().is_none() {
+            return;
+        }
+        let probed_at = chrono::Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string();
+        if self.store.put_setting(WRITE_PROBE_SETTING, &probed_at).is_ok() {
+            if let Some(failure) = WRITE_FAILURE.write().unwrap().take() {
+                info!("Database writes succeed again; leaving degraded mode entered at {}", failure.since);
+            }
+        }
+    }
+
+    pub fn create_user(&self, username: &str, password: &str, expires_at: Option<&str>, role: UserRole) -> Result<i64> {
+        let password_hash = hash(password, DEFAULT_COST).map_err(|e| DbError::Other(e.to_string()))?;
+        let id = self.store.insert_user(username, &password_hash, expires_at, role)?;
         info!("User created: {}", username);
-        Ok(conn.last_insert_rowid())
+        Ok(id)
+    }
+
+    /// `reset_password_with_token` with `password` hashed as `create_user` does.
+    pub fn reset_password(&self, token_hash: &str, password: &str) -> Result<Option<i64>> {
+        let password_hash = hash(password, DEFAULT_COST).map_err(|e| DbError::Other(e.to_string()))?;
+        self.store.reset_password_with_token(token_hash, &password_hash)
     }
 
     pub fn verify_user(&self, username: &str, password: &str) -> Result<Option<User>> {
-        let conn = self.pool.get()
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        
-        let mut stmt = conn.prepare(
-            "SELECT id, username, password_hash FROM users WHERE username = ?1"
-        )?;
-        
-        let user = stmt.query_row([username], |row| {
-            Ok(User {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                password_hash: row.get(2)?,
-            })
+        let user = match self.store.get_user_by_username(username)? {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+
+        // External accounts have no local password to check.
+        if user.is_external_account() {
+            return Ok(None);
+        }
+        let valid = verify(password, &user.password_hash).map_err(|e| DbError::Other(e.to_string()))?;
+        if valid {
+            info!("User authenticated: {}", username);
+            Ok(Some(user))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Append a timestamped stage to an operation and update its status.
+    pub fn add_operation_stage(
+        &self,
+        id: &str,
+        stage: &str,
+        detail: Option<&str>,
+        status: &str,
+    ) -> Result<()> {
+        let mut operation = self
+            .store
+            .get_operation(id)?
+            .ok_or_else(|| DbError::Other(format!("Operation {} not found", id)))?;
+
+        operation.stages.push(OperationStage {
+            name: stage.to_string(),
+            at: chrono::Utc::now().to_rfc3339(),
+            detail: detail.map(scrub::scrub_text),
         });
+        self.store.update_operation(id, &operation.stages, status)
+    }
+}
+
+impl Deref for Database {
+    type Target = dyn Store;
+
+    fn deref(&self) -> &Self::Target {
+        self.store.as_ref()
+    }
+}
+
+fn open_store(location: &str, read_only: bool) -> Result<Box<dyn Store>> {
+    if location.starts_with("postgres://") || location.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return Ok(Box::new(postgres::PostgresStore::open(location, read_only)?));
+        #[cfg(not(feature = "postgres"))]
+        return Err(DbError::Other(
+            "DATABASE_URL points at PostgreSQL but this build lacks the `postgres` feature".to_string(),
+        ));
+    }
+
+    let path = location.strip_prefix("sqlite://").unwrap_or(location);
+    Ok(Box::new(sqlite::SqliteStore::open(path, read_only)?))
+}
 
-        match user {
-            Ok(user) => {
-                let valid = verify(password, &user.password_hash)
-                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-                
-                if valid {
-                    info!("User authenticated: {}", username);
-                    Ok(Some(user))
-                } else {
-                    Ok(None)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{databases, unique};
+    use std::collections::HashSet;
+
+    fn new_server(name: &str) -> NewServer {
+        NewServer {
+            name: name.to_string(),
+            host: "https://192.0.2.10".to_string(),
+            username: "root".to_string(),
+            password: SecretString::from("calvin".to_string()),
+            tags: Vec::new(),
+            location: None,
+            default_power_cap_watts: None,
+            hosts_this_app: false,
+            os_health_url: None,
+            os_health_insecure: false,
+            os_health_token: None,
+        }
+    }
+
+    #[test]
+    fn colliding_server_names_get_suffixed_slugs() {
+        for db in databases() {
+            let base = unique("rack");
+            let first = db.create_server(&new_server(&base)).unwrap();
+            let second = db.create_server(&new_server(&format!("{}!", base))).unwrap();
+            assert_eq!(first.slug, base, "{}", db.backend);
+            assert_eq!(second.slug, format!("{}-2", base), "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn concurrent_inserts_take_distinct_slugs() {
+        for db in databases() {
+            let base = unique("rack");
+            let slugs: HashSet<String> = std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..8)
+                    .map(|i| {
+                        let (db, name) = (&db, format!("{}{}", base, "!".repeat(i)));
+                        scope.spawn(move || db.create_server(&new_server(&name)).unwrap().slug)
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            assert_eq!(slugs.len(), 8, "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn colliding_usernames_are_both_created() {
+        for db in databases() {
+            let base = unique("operator");
+            db.create_user(&base, "password123", None, UserRole::ReadOnly).unwrap();
+            db.create_user(&format!("{}.", base), "password123", None, UserRole::ReadOnly).unwrap();
+            db.insert_external_user(&format!("{}_", base), "ldap", UserRole::ReadOnly).unwrap();
+        }
+    }
+
+    #[test]
+    fn accounts_from_before_roles_stay_admins() {
+        let dir = crate::testing::ScratchDir::new();
+        let path = dir.path().join("db.sqlite");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                username TEXT NOT NULL UNIQUE,
+                password_hash TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO users (username, password_hash) VALUES ('operator', '');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = Database::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(db.get_user_by_username("operator").unwrap().unwrap().role, UserRole::Admin);
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute("INSERT INTO users (username, password_hash, slug) VALUES ('newcomer', '', 'newcomer')", []).unwrap();
+        assert_eq!(db.get_user_by_username("newcomer").unwrap().unwrap().role, UserRole::ReadOnly);
+    }
+
+    #[test]
+    fn disabled_expired_users_keep_their_role() {
+        for db in databases() {
+            let expired_at = (chrono::Utc::now() - chrono::Duration::hours(1)).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+            let admin = unique("vendor-admin");
+            let reader = unique("vendor-reader");
+            db.create_user(&admin, "password123", Some(&expired_at), UserRole::Admin).unwrap();
+            db.create_user(&reader, "password123", Some(&expired_at), UserRole::ReadOnly).unwrap();
+
+            let disabled = db.disable_expired_users().unwrap();
+            let role_of = |name: &str| disabled.iter().find(|user| user.username == name).map(|user| user.role);
+            assert_eq!(role_of(&admin), Some(UserRole::Admin), "{}", db.backend);
+            assert_eq!(role_of(&reader), Some(UserRole::ReadOnly), "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn users_and_expiry_behave_alike() {
+        for db in databases() {
+            let name = unique("operator");
+            let id = db.create_user(&name, "password123", None, UserRole::Admin).unwrap();
+            let user = db.verify_user(&name, "password123").unwrap().expect("password accepted");
+            assert_eq!((user.id, user.role, user.auth_source.as_str()), (id, UserRole::Admin, AUTH_SOURCE_LOCAL), "{}", db.backend);
+            assert!(db.verify_user(&name, "wrong-password").unwrap().is_none(), "{}", db.backend);
+            assert!(db.get_user_by_id(id).unwrap().is_some(), "{}", db.backend);
+            let duplicate = db.create_user(&name, "password123", None, UserRole::ReadOnly).unwrap_err();
+            assert!(duplicate.is_unique_violation(), "{}: {}", db.backend, duplicate);
+
+            let ldap_id = db.insert_external_user(&unique("directory"), AUTH_SOURCE_LDAP, UserRole::ReadOnly).unwrap();
+            let ldap_user = db.get_user_by_id(ldap_id).unwrap().unwrap();
+            assert!(ldap_user.is_external_account(), "{}", db.backend);
+            assert_eq!(ldap_user.role, UserRole::ReadOnly, "{}", db.backend);
+            assert!(db.set_user_role(ldap_id, UserRole::Admin).unwrap(), "{}", db.backend);
+            assert_eq!(db.get_user_by_id(ldap_id).unwrap().unwrap().role, UserRole::Admin, "{}", db.backend);
+            assert!(!db.set_user_role(-1, UserRole::Admin).unwrap(), "{}", db.backend);
+
+            let soon = (chrono::Utc::now() + chrono::Duration::hours(1)).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+            let later = (chrono::Utc::now() + chrono::Duration::hours(2)).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+            let now = chrono::Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string();
+            assert!(db.set_user_expiry(id, Some(&soon)).unwrap(), "{}", db.backend);
+            let expiring = db.list_user_expirations(&now, &later).unwrap();
+            assert!(expiring.iter().any(|user| user.id == id && user.expires_at.as_deref() == Some(soon.as_str())), "{}", db.backend);
+            assert!(db.list_users(false).unwrap().iter().any(|user| user.id == id), "{}", db.backend);
+
+            let past = (chrono::Utc::now() - chrono::Duration::hours(1)).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+            db.set_user_expiry(id, Some(&past)).unwrap();
+            assert!(!db.list_users(false).unwrap().iter().any(|user| user.id == id), "{}", db.backend);
+            assert!(db.list_users(true).unwrap().iter().any(|user| user.id == id), "{}", db.backend);
+            assert!(!db.set_user_expiry(-1, None).unwrap(), "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn server_grants_and_their_expiry_behave_alike() {
+        for db in databases() {
+            let at = |hours: i64| (chrono::Utc::now() + chrono::Duration::hours(hours)).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+            let name = unique("vendor");
+            let id = db.create_user(&name, "password123", None, UserRole::ReadOnly).unwrap();
+            let alias = unique("rack");
+            assert!(db.get_server_grant(id, &alias).unwrap().is_none(), "{}", db.backend);
+
+            let grant = db.set_server_grant(id, &alias, Some(&at(1))).unwrap();
+            assert_eq!((grant.user_id, grant.username.as_str(), grant.server_alias.as_str()), (id, name.as_str(), alias.as_str()), "{}", db.backend);
+            assert!(grant.is_active(), "{}", db.backend);
+            let expiring = db.list_server_grant_expirations(&at(0), &at(2)).unwrap();
+            assert!(expiring.iter().any(|g| g.id == grant.id), "{}", db.backend);
+
+            // Extending keeps the one grant.
+            let extended = db.set_server_grant(id, &alias, Some(&at(48))).unwrap();
+            assert_eq!((extended.id, extended.expires_at.as_deref()), (grant.id, Some(at(48).as_str())), "{}", db.backend);
+            let other = db.set_server_grant(id, &unique("rack"), None).unwrap();
+            assert!(other.is_active(), "{}", db.backend);
+            assert_eq!(db.list_server_grants(id, false).unwrap().len(), 2, "{}", db.backend);
+
+            db.set_server_grant(id, &alias, Some(&at(-1))).unwrap();
+            assert!(!db.get_server_grant(id, &alias).unwrap().unwrap().is_active(), "{}", db.backend);
+            let active: Vec<i64> = db.list_server_grants(id, false).unwrap().iter().map(|g| g.id).collect();
+            assert_eq!(active, vec![other.id], "{}", db.backend);
+            assert_eq!(db.list_server_grants(id, true).unwrap().len(), 2, "{}", db.backend);
+            let expired = db.list_server_grant_expirations(&at(-2), &at(0)).unwrap();
+            assert!(expired.iter().any(|g| g.id == grant.id), "{}", db.backend);
+
+            assert!(db.delete_server_grant(id, &alias).unwrap(), "{}", db.backend);
+            assert!(!db.delete_server_grant(id, &alias).unwrap(), "{}", db.backend);
+            assert!(db.get_server_grant(id, &alias).unwrap().is_none(), "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn preferences_tokens_and_settings_behave_alike() {
+        for db in databases() {
+            let user_id = db.insert_external_user(&unique("viewer"), AUTH_SOURCE_OIDC, UserRole::ReadOnly).unwrap();
+            db.replace_user_preferences(user_id, &[("theme".to_string(), "\"dark\"".to_string())]).unwrap();
+            db.replace_user_preferences(user_id, &[("layout".to_string(), "[1,2]".to_string())]).unwrap();
+            assert_eq!(db.get_user_preferences(user_id).unwrap(), vec![("layout".to_string(), "[1,2]".to_string())], "{}", db.backend);
+
+            let hash = unique("token-hash");
+            let scopes = vec!["power:read".to_string(), "audit:read".to_string()];
+            let token = db.create_api_token(user_id, "ci", &hash, &scopes, Some(60)).unwrap();
+            let found = db.find_api_token(&hash).unwrap().expect("token found");
+            assert_eq!((found.id, &found.scopes, found.requests_per_hour), (token.id, &scopes, Some(60)), "{}", db.backend);
+            assert!(db.set_api_token_quota(token.id, None).unwrap(), "{}", db.backend);
+            let listed = db.list_api_tokens(user_id).unwrap();
+            assert_eq!(listed.len(), 1, "{}", db.backend);
+            assert_eq!(listed[0].requests_per_hour, None, "{}", db.backend);
+            assert!(listed[0].last_used_at.is_some(), "{}", db.backend);
+            assert!(db.delete_api_token(user_id, token.id).unwrap(), "{}", db.backend);
+            assert!(db.find_api_token(&hash).unwrap().is_none(), "{}", db.backend);
+
+            let key = unique("setting");
+            assert_eq!(db.get_setting(&key).unwrap(), None, "{}", db.backend);
+            db.put_setting(&key, "30").unwrap();
+            db.put_setting(&key, "90").unwrap();
+            assert_eq!(db.get_setting(&key).unwrap().as_deref(), Some("90"), "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn servers_and_power_cap_schedules_behave_alike() {
+        for db in databases() {
+            let mut server = new_server(&unique("rack"));
+            server.tags = vec!["lab".to_string(), "gpu".to_string()];
+            server.default_power_cap_watts = Some(450);
+            let created = db.create_server(&server).unwrap();
+            let listed = db.list_servers().unwrap().into_iter().find(|s| s.id == created.id).expect("server listed");
+            assert_eq!((&listed.tags, listed.default_power_cap_watts), (&server.tags, Some(450)), "{}", db.backend);
+
+            let updated = db.update_server_credentials(created.id, Some("operator"), None).unwrap().unwrap();
+            assert_eq!((updated.username.as_str(), updated.password.as_str()), ("operator", created.password.as_str()), "{}", db.backend);
+            assert!(db.update_server_credentials(-1, Some("operator"), None).unwrap().is_none(), "{}", db.backend);
+
+            let schedule = db.create_power_cap_schedule(&created.slug, "0 0 18 * * *", 300, 120, true).unwrap();
+            let schedules = db.list_power_cap_schedules(Some(&created.slug)).unwrap();
+            assert_eq!(schedules.len(), 1, "{}", db.backend);
+            assert_eq!((schedules[0].id, schedules[0].watts, schedules[0].duration_minutes), (schedule.id, 300, 120), "{}", db.backend);
+            assert!(!db.delete_power_cap_schedule("some-other-server", schedule.id).unwrap(), "{}", db.backend);
+            assert!(db.delete_power_cap_schedule(&created.slug, schedule.id).unwrap(), "{}", db.backend);
+
+            db.create_power_cap_schedule(&created.slug, "0 0 18 * * *", 300, 120, true).unwrap();
+            assert!(db.delete_server(created.id).unwrap(), "{}", db.backend);
+            assert!(db.list_power_cap_schedules(Some(&created.slug)).unwrap().is_empty(), "{}", db.backend);
+            assert!(!db.delete_server(created.id).unwrap(), "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn firmware_inventory_and_baselines_behave_alike() {
+        for db in databases() {
+            let alias = unique("rack");
+            let component = unique("BIOS");
+            db.replace_firmware_inventory(&alias, &[(component.clone(), "2.1.0".to_string()), ("iDRAC".to_string(), "7.0".to_string())]).unwrap();
+            db.replace_firmware_inventory(&alias, &[(component.clone(), "2.2.0".to_string())]).unwrap();
+            let inventory: Vec<_> = db.list_firmware_inventory().unwrap().into_iter().filter(|entry| entry.server_alias == alias).collect();
+            assert_eq!(inventory.len(), 1, "{}", db.backend);
+            assert_eq!(inventory[0].version, "2.2.0", "{}", db.backend);
+
+            db.set_firmware_baseline(&component, Some("2.0.0")).unwrap();
+            db.set_firmware_baseline(&component, Some("2.2.0")).unwrap();
+            let baseline = |db: &Database| db.list_firmware_baselines().unwrap().into_iter().find(|(name, _)| *name == component);
+            assert_eq!(baseline(&db).map(|(_, version)| version).as_deref(), Some("2.2.0"), "{}", db.backend);
+            db.set_firmware_baseline(&component, None).unwrap();
+            assert_eq!(baseline(&db), None, "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn audit_statistics_and_export_behave_alike() {
+        for db in databases() {
+            let action = unique("ParityCheck");
+            let server = unique("rack");
+            db.record_audit(None, &action, &server, "success", None, Some("{\"n\":1}")).unwrap();
+            db.record_audit(None, &action, &server, "failure", Some("timed out"), None).unwrap();
+
+            let stats = db.audit_statistics(None, None).unwrap();
+            assert_eq!(stats.actions_by_type.get(&action), Some(&2), "{}", db.backend);
+
+            let entries: Vec<_> = db
+                .list_audit_page(&Keyset::First, 1000, true)
+                .unwrap()
+                .into_iter()
+                .filter(|entry| entry.action == action)
+                .collect();
+            assert_eq!(entries.len(), 2, "{}", db.backend);
+            assert!(entries[0].id > entries[1].id, "{}", db.backend);
+            assert_eq!((entries[0].result.as_str(), entries[0].error_message.as_deref()), ("failure", Some("timed out")), "{}", db.backend);
+            assert_eq!(entries[1].details, Some(serde_json::json!({ "n": 1 })), "{}", db.backend);
+            assert!(db.audit_count_estimate().unwrap() >= 2, "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn standby_opens_read_only_and_promotes() {
+        for db in databases() {
+            let key = unique("setting");
+            db.put_setting(&key, "primary").unwrap();
+
+            let standby = Database::open_read_only(&db.location).unwrap();
+            assert!(standby.is_read_only(), "{}", db.backend);
+            assert!(standby.writes_paused(), "{}", db.backend);
+            assert_eq!(standby.get_setting(&key).unwrap().as_deref(), Some("primary"), "{}", db.backend);
+
+            standby.promote().unwrap();
+            assert!(!standby.is_read_only(), "{}", db.backend);
+            standby.put_setting(&key, "promoted").unwrap();
+            assert_eq!(db.get_setting(&key).unwrap().as_deref(), Some("promoted"), "{}", db.backend);
+        }
+    }
+
+    fn record_paging_entry(db: &Database, action: &str) {
+        db.record_audit(None, action, "app", "success", None, None).unwrap();
+    }
+
+    /// Ids of the `action` entries on one page, plus the keyset of the next
+    /// page, or `None` once the list is exhausted.
+    fn audit_page(db: &Database, keyset: &Keyset<i64>, descending: bool, action: &str) -> (Vec<i64>, Option<Keyset<i64>>) {
+        let rows = db.list_audit_page(keyset, 4, descending).unwrap();
+        let next = rows.last().map(|row| Keyset::After(row.id));
+        let ids = rows.into_iter().filter(|row| row.action == action).map(|row| row.id).collect();
+        (ids, next)
+    }
+
+    #[test]
+    fn ascending_audit_pages_see_rows_written_while_paging() {
+        for db in databases() {
+            let action = unique("Paging");
+            for _ in 0..6 {
+                record_paging_entry(&db, &action);
+            }
+            // Start just before the first entry so rows from other tests
+            // sharing the database do not have to be paged through.
+            let first_id = db
+                .list_audit_page(&Keyset::First, 1000, true)
+                .unwrap()
+                .into_iter()
+                .filter(|row| row.action == action)
+                .map(|row| row.id)
+                .min()
+                .unwrap();
+
+            let mut seen = Vec::new();
+            let mut keyset = Keyset::After(first_id - 1);
+            std::thread::scope(|scope| {
+                let writer = scope.spawn(|| {
+                    for _ in 0..20 {
+                        record_paging_entry(&db, &action);
+                    }
+                });
+                while !writer.is_finished() {
+                    let (ids, next) = audit_page(&db, &keyset, false, &action);
+                    seen.extend(ids);
+                    record_paging_entry(&db, &action);
+                    if let Some(next) = next {
+                        keyset = next;
+                    }
+                }
+            });
+            loop {
+                let (ids, next) = audit_page(&db, &keyset, false, &action);
+                seen.extend(ids);
+                match next {
+                    Some(next) => keyset = next,
+                    None => break,
                 }
             }
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e),
-        }
-    }
-
-    #[allow(dead_code)]
-    pub fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>> {
-        let conn = self.pool.get()
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        
-        let mut stmt = conn.prepare(
-            "SELECT id, username, password_hash FROM users WHERE id = ?1"
-        )?;
-        
-        let user = stmt.query_row([user_id], |row| {
-            Ok(User {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                password_hash: row.get(2)?,
-            })
-        });
 
-        match user {
-            Ok(user) => Ok(Some(user)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e),
+            let written: Vec<i64> = db
+                .list_audit_page(&Keyset::After(first_id - 1), 10_000, false)
+                .unwrap()
+                .into_iter()
+                .filter(|row| row.action == action)
+                .map(|row| row.id)
+                .collect();
+            assert!(seen.windows(2).all(|pair| pair[0] < pair[1]), "{}: duplicate or out of order ids {:?}", db.backend, seen);
+            assert_eq!(seen, written, "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn descending_audit_pages_skip_nothing_written_before_the_first_page() {
+        for db in databases() {
+            let action = unique("Paging");
+            for _ in 0..10 {
+                record_paging_entry(&db, &action);
+            }
+            let mut expected: Vec<i64> = db
+                .list_audit_page(&Keyset::First, 1000, true)
+                .unwrap()
+                .into_iter()
+                .filter(|row| row.action == action)
+                .map(|row| row.id)
+                .collect();
+            assert_eq!(expected.len(), 10, "{}", db.backend);
+
+            // Newest first, starting at our newest entry; rows written while
+            // paging are newer than the first page and are never reached.
+            let mut seen = Vec::new();
+            let mut keyset = Keyset::After(expected[0] + 1);
+            let oldest = *expected.last().unwrap();
+            loop {
+                let (ids, next) = audit_page(&db, &keyset, true, &action);
+                seen.extend(ids);
+                record_paging_entry(&db, &action);
+                match next {
+                    Some(Keyset::After(id)) if id > oldest => keyset = Keyset::After(id),
+                    _ => break,
+                }
+            }
+
+            assert!(seen.windows(2).all(|pair| pair[0] > pair[1]), "{}: duplicate or out of order ids {:?}", db.backend, seen);
+            expected.sort_unstable_by(|a, b| b.cmp(a));
+            assert_eq!(seen, expected, "{}", db.backend);
+
+            // Paging back from the oldest entry returns the next ones up, nearest first.
+            let (newer, _) = audit_page(&db, &Keyset::Before(oldest), true, &action);
+            let ascending: Vec<i64> = expected.iter().rev().skip(1).take(newer.len()).copied().collect();
+            assert!(!newer.is_empty(), "{}", db.backend);
+            assert_eq!(newer, ascending, "{}", db.backend);
         }
     }
 }
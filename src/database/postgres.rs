@@ -0,0 +1,2638 @@
+use ::postgres::error::SqlState;
+use ::postgres::{Client, NoTls, Row};
+use log::{info, warn};
+use r2d2::Pool;
+use r2d2_postgres::PostgresConnectionManager;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use super::{
+    ApiToken, ApiUsage, AuditEntry, AuditStatistics, BreakGlassGrant, ComplianceRequirement, DbError, EventCause, FirmwareInventoryEntry, GroupApplyJob, HealthReport, Keyset,
+    FirmwareUpdateSchedule, MetricRollup, MetricSample, NewServer, NewShare, OneShotSchedule, Operation, OperationStage, PingSample, PowerBaseline, PowerCapSchedule, PowerChangeReason, PowerEvent, PowerPeak, PowerSample, Result, RollupGranularity, SelRecord, ServerActionCount,
+    ServerGrant, ServerGroup, ServerRecord, Share, Store, TariffCap, TariffWindow, UsageKey, User, UserActionCount, UserRole, UserSummary,
+};
+use crate::validation::slugify;
+
+pub type PgPool = Pool<PostgresConnectionManager<NoTls>>;
+
+/// The current UTC time as text in `SQLITE_TIMESTAMP_FORMAT`. Timestamps are
+/// stored as text like in SQLite, so the two backends return identical values
+/// and string comparisons against them behave the same.
+const NOW: &str = "to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')";
+
+/// A PostgreSQL database selected with a `postgres://` `DATABASE_URL`.
+pub struct PostgresStore {
+    // Swapped out when a standby instance is promoted to read-write. Only
+    // `None` while the store is being dropped.
+    pool: RwLock<Option<PgPool>>,
+    read_only: AtomicBool,
+    url: String,
+}
+
+impl PostgresStore {
+    pub fn open(url: &str, read_only: bool) -> Result<Self> {
+        let pool = off_runtime(|| open_pool(url, read_only))?;
+        if read_only {
+            info!("PostgreSQL database opened read-only");
+        } else {
+            info!("PostgreSQL database initialized");
+        }
+
+        Ok(PostgresStore {
+            pool: RwLock::new(Some(pool)),
+            read_only: AtomicBool::new(read_only),
+            url: url.to_string(),
+        })
+    }
+
+    fn with_conn<T: Send>(&self, f: impl FnOnce(&mut Client) -> Result<T> + Send) -> Result<T> {
+        let pool = self.pool.read().unwrap().clone().expect("database pool in use after drop");
+        off_runtime(move || f(&mut *pool.get()?))
+    }
+}
+
+impl Drop for PostgresStore {
+    fn drop(&mut self) {
+        let pool = self.pool.get_mut().unwrap().take();
+        off_runtime(move || drop(pool));
+    }
+}
+
+impl Store for PostgresStore {
+    fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    fn promote(&self) -> Result<()> {
+        let pool = off_runtime(|| open_pool(&self.url, false))?;
+        let previous = self.pool.write().unwrap().replace(pool);
+        off_runtime(move || drop(previous));
+        self.read_only.store(false, Ordering::SeqCst);
+        info!("PostgreSQL database re-opened read-write");
+        Ok(())
+    }
+
+    fn has_users(&self) -> Result<bool> {
+        self.with_conn(|conn| {
+            let count: i64 = conn.query_one("SELECT COUNT(*) FROM users", &[])?.get(0);
+            Ok(count > 0)
+        })
+    }
+
+    fn insert_user(&self, username: &str, password_hash: &str, expires_at: Option<&str>, role: UserRole) -> Result<i64> {
+        self.with_conn(|conn| {
+            let (row, _) = insert_with_unique_slug(&slugify(username), |slug| {
+                conn.query_one(
+                    "INSERT INTO users (username, password_hash, slug, expires_at, role) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+                    &[&username, &password_hash, &slug, &expires_at, &role.as_str()],
+                )
+            })?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn insert_external_user(&self, username: &str, auth_source: &str, role: UserRole) -> Result<i64> {
+        self.with_conn(|conn| {
+            let (row, _) = insert_with_unique_slug(&slugify(username), |slug| {
+                conn.query_one(
+                    "INSERT INTO users (username, password_hash, slug, auth_source, role) VALUES ($1, '', $2, $3, $4) RETURNING id",
+                    &[&username, &slug, &auth_source, &role.as_str()],
+                )
+            })?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn set_user_role(&self, user_id: i64, role: UserRole) -> Result<bool> {
+        self.with_conn(|conn| {
+            let updated = conn.execute("UPDATE users SET role = $2 WHERE id = $1", &[&user_id, &role.as_str()])?;
+            Ok(updated > 0)
+        })
+    }
+
+    fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                "SELECT id, username, password_hash, expires_at, disabled_at, session_generation, auth_source, email, role
+                 FROM users WHERE username = $1",
+                &[&username],
+            )?;
+            Ok(row.as_ref().map(user_from_row))
+        })
+    }
+
+    fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                "SELECT id, username, password_hash, expires_at, disabled_at, session_generation, auth_source, email, role
+                 FROM users WHERE id = $1",
+                &[&user_id],
+            )?;
+            Ok(row.as_ref().map(user_from_row))
+        })
+    }
+
+    fn list_users(&self, include_expired: bool) -> Result<Vec<UserSummary>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT id, username, created_at, expires_at, disabled_at, auth_source, role FROM users
+                     WHERE $1 OR (disabled_at IS NULL AND (expires_at IS NULL OR expires_at > {}))
+                     ORDER BY username",
+                    NOW
+                ),
+                &[&include_expired],
+            )?;
+            Ok(rows.iter().map(user_summary_from_row).collect())
+        })
+    }
+
+    fn list_user_expirations(&self, from: &str, until: &str) -> Result<Vec<UserSummary>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT id, username, created_at, expires_at, disabled_at, auth_source, role FROM users
+                 WHERE expires_at >= $1 AND expires_at <= $2
+                 ORDER BY expires_at",
+                &[&from, &until],
+            )?;
+            Ok(rows.iter().map(user_summary_from_row).collect())
+        })
+    }
+
+    fn set_user_expiry(&self, user_id: i64, expires_at: Option<&str>) -> Result<bool> {
+        self.with_conn(|conn| {
+            let updated = conn.execute(
+                &format!(
+                    "UPDATE users SET expires_at = $1,
+                        disabled_at = CASE WHEN $1::TEXT IS NULL OR $1 > {} THEN NULL ELSE disabled_at END
+                     WHERE id = $2",
+                    NOW
+                ),
+                &[&expires_at, &user_id],
+            )?;
+            Ok(updated > 0)
+        })
+    }
+
+    fn disable_expired_users(&self) -> Result<Vec<UserSummary>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "UPDATE users SET disabled_at = {now}, session_generation = session_generation + 1
+                     WHERE disabled_at IS NULL AND expires_at <= {now}
+                     RETURNING id, username, created_at, expires_at, disabled_at, auth_source, role",
+                    now = NOW
+                ),
+                &[],
+            )?;
+            Ok(rows.iter().map(user_summary_from_row).collect())
+        })
+    }
+
+    fn set_server_grant(&self, user_id: i64, server_alias: &str, expires_at: Option<&str>) -> Result<ServerGrant> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO server_grants (user_id, server_alias, expires_at) VALUES ($1, $2, $3)
+                 ON CONFLICT (user_id, server_alias) DO UPDATE SET expires_at = excluded.expires_at",
+                &[&user_id, &server_alias, &expires_at],
+            )?;
+            let row = conn.query_one(
+                &format!("SELECT {} WHERE g.user_id = $1 AND g.server_alias = $2", SERVER_GRANT_FROM),
+                &[&user_id, &server_alias],
+            )?;
+            Ok(server_grant_from_row(&row))
+        })
+    }
+
+    fn get_server_grant(&self, user_id: i64, server_alias: &str) -> Result<Option<ServerGrant>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!("SELECT {} WHERE g.user_id = $1 AND g.server_alias = $2", SERVER_GRANT_FROM),
+                &[&user_id, &server_alias],
+            )?;
+            Ok(row.as_ref().map(server_grant_from_row))
+        })
+    }
+
+    fn list_server_grants(&self, user_id: i64, include_expired: bool) -> Result<Vec<ServerGrant>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {} WHERE g.user_id = $1 AND ($2 OR g.expires_at IS NULL OR g.expires_at > {})
+                     ORDER BY g.server_alias",
+                    SERVER_GRANT_FROM, NOW
+                ),
+                &[&user_id, &include_expired],
+            )?;
+            Ok(rows.iter().map(server_grant_from_row).collect())
+        })
+    }
+
+    fn list_server_grant_expirations(&self, from: &str, until: &str) -> Result<Vec<ServerGrant>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {} WHERE g.expires_at >= $1 AND g.expires_at <= $2 ORDER BY g.expires_at",
+                    SERVER_GRANT_FROM
+                ),
+                &[&from, &until],
+            )?;
+            Ok(rows.iter().map(server_grant_from_row).collect())
+        })
+    }
+
+    fn delete_server_grant(&self, user_id: i64, server_alias: &str) -> Result<bool> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute(
+                "DELETE FROM server_grants WHERE user_id = $1 AND server_alias = $2",
+                &[&user_id, &server_alias],
+            )?;
+            Ok(deleted > 0)
+        })
+    }
+
+    fn set_user_email(&self, user_id: i64, email: Option<&str>) -> Result<bool> {
+        self.with_conn(|conn| {
+            let updated = conn.execute("UPDATE users SET email = $2 WHERE id = $1", &[&user_id, &email])?;
+            Ok(updated > 0)
+        })
+    }
+
+    fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                "SELECT id, username, password_hash, expires_at, disabled_at, session_generation, auth_source, email, role
+                 FROM users WHERE lower(email) = lower($1)",
+                &[&email],
+            )?;
+            Ok(row.as_ref().map(user_from_row))
+        })
+    }
+
+    fn record_audit(
+        &self,
+        user_id: Option<i64>,
+        action: &str,
+        server_name: &str,
+        result: &str,
+        error_message: Option<&str>,
+        details: Option<&str>,
+    ) -> Result<()> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            // Concurrent inserts may commit out of id order, which would let
+            // `list_audit_page` page past an entry that becomes visible later.
+            // Audit writes are rare enough to serialize; readers are not blocked.
+            tx.batch_execute("LOCK TABLE audit_log IN EXCLUSIVE MODE")?;
+            tx.execute(
+                "INSERT INTO audit_log (user_id, action, server_name, result, error_message, details)
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+                &[&user_id, &action, &server_name, &result, &error_message, &details],
+            )?;
+            Ok(tx.commit()?)
+        })
+    }
+
+    fn audit_statistics(&self, since: Option<&str>, until: Option<&str>) -> Result<AuditStatistics> {
+        self.with_conn(|conn| {
+
+            const RANGE: &str = "($1::TEXT IS NULL OR a.created_at >= $1) AND ($2::TEXT IS NULL OR a.created_at < $2)";
+            let params: [&(dyn ::postgres::types::ToSql + Sync); 2] = [&since, &until];
+
+            let row = conn.query_one(
+                &format!(
+                    "SELECT COUNT(*), COUNT(*) FILTER (WHERE a.result = 'failure') FROM audit_log a WHERE {}",
+                    RANGE
+                ),
+                &params,
+            )?;
+            let (total_actions, failures): (i64, i64) = (row.get(0), row.get(1));
+
+            let actions_by_type = conn
+                .query(
+                    &format!(
+                        "SELECT a.action, COUNT(*) FROM audit_log a WHERE {} GROUP BY a.action ORDER BY a.action",
+                        RANGE
+                    ),
+                    &params,
+                )?
+                .iter()
+                .map(|row| (row.get(0), row.get::<_, i64>(1) as u64))
+                .collect();
+
+            let actions_by_user = conn
+                .query(
+                    &format!(
+                        "SELECT COALESCE(u.username, 'system'), COUNT(*) FROM audit_log a
+                         LEFT JOIN users u ON u.id = a.user_id
+                         WHERE {} GROUP BY 1 ORDER BY 2 DESC, 1",
+                        RANGE
+                    ),
+                    &params,
+                )?
+                .iter()
+                .map(|row| UserActionCount { user: row.get(0), count: row.get::<_, i64>(1) as u64 })
+                .collect();
+
+            let actions_by_server = conn
+                .query(
+                    &format!(
+                        "SELECT a.server_name, COUNT(*) FROM audit_log a WHERE {} GROUP BY 1 ORDER BY 2 DESC, 1",
+                        RANGE
+                    ),
+                    &params,
+                )?
+                .iter()
+                .map(|row| ServerActionCount { server: row.get(0), count: row.get::<_, i64>(1) as u64 })
+                .collect();
+
+            let failure_rate = if total_actions == 0 {
+                0.0
+            } else {
+                failures as f64 / total_actions as f64
+            };
+
+            Ok(AuditStatistics {
+                total_actions: total_actions as u64,
+                actions_by_type,
+                actions_by_user,
+                actions_by_server,
+                failure_rate,
+            })
+        })
+    }
+
+    fn list_audit_page(&self, keyset: &Keyset<i64>, limit: u32, descending: bool) -> Result<Vec<AuditEntry>> {
+        self.with_conn(|conn| {
+            let (cmp, order) = keyset.sql(descending);
+            let rows = conn.query(
+                &format!(
+                    "SELECT a.id, a.user_id, u.username, a.action, a.server_name, a.result, a.error_message,
+                            a.details, a.created_at
+                     FROM audit_log a LEFT JOIN users u ON u.id = a.user_id
+                     WHERE ($1::BIGINT IS NULL OR a.id {cmp} $1) ORDER BY a.id {order} LIMIT $2"
+                ),
+                &[&keyset.key(), &(limit as i64)],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| {
+                    let details: Option<String> = row.get(7);
+                    AuditEntry {
+                        id: row.get(0),
+                        user_id: row.get(1),
+                        username: row.get(2),
+                        action: row.get(3),
+                        server_name: row.get(4),
+                        result: row.get(5),
+                        error_message: row.get(6),
+                        details: details.and_then(|d| serde_json::from_str(&d).ok()),
+                        created_at: row.get(8),
+                    }
+                })
+                .collect())
+        })
+    }
+
+    fn audit_count_estimate(&self) -> Result<u64> {
+        self.with_conn(|conn| {
+            let span: i64 = conn
+                .query_one("SELECT COALESCE(MAX(id) - MIN(id) + 1, 0) FROM audit_log", &[])?
+                .get(0);
+            Ok(span as u64)
+        })
+    }
+
+    fn purge_audit_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM audit_log
+                 WHERE created_at < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn latest_successful_audit(&self, server_name: &str, since: &str, until: &str) -> Result<Option<(i64, String)>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                "SELECT id, action FROM audit_log
+                 WHERE server_name = $1 AND result = 'success' AND created_at >= $2 AND created_at <= $3
+                 ORDER BY created_at DESC, id DESC LIMIT 1",
+                &[&server_name, &since, &until],
+            )?;
+            Ok(row.map(|row| (row.get(0), row.get(1))))
+        })
+    }
+
+    fn create_operation(&self, kind: &str, server_alias: Option<&str>) -> Result<String> {
+        self.with_conn(|conn| {
+            let id = uuid::Uuid::new_v4().to_string();
+            conn.execute(
+                "INSERT INTO operations (id, kind, server_alias, status) VALUES ($1, $2, $3, 'running')",
+                &[&id, &kind, &server_alias],
+            )?;
+            Ok(id)
+        })
+    }
+
+    fn get_operation(&self, id: &str) -> Result<Option<Operation>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!("SELECT {} FROM operations WHERE id = $1", OPERATION_COLUMNS),
+                &[&id],
+            )?;
+            Ok(row.as_ref().map(operation_from_row))
+        })
+    }
+
+    fn list_server_operations(&self, server_alias: &str, kind: &str, limit: u32) -> Result<Vec<Operation>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {} FROM operations WHERE server_alias = $1 AND kind = $2
+                     ORDER BY created_at DESC LIMIT $3",
+                    OPERATION_COLUMNS
+                ),
+                &[&server_alias, &kind, &(limit as i64)],
+            )?;
+            Ok(rows.iter().map(operation_from_row).collect())
+        })
+    }
+
+    fn list_running_operations(&self, kind: &str) -> Result<Vec<Operation>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {} FROM operations WHERE kind = $1 AND status = 'running' ORDER BY created_at",
+                    OPERATION_COLUMNS
+                ),
+                &[&kind],
+            )?;
+            Ok(rows.iter().map(operation_from_row).collect())
+        })
+    }
+
+    fn latest_operation_active_at(&self, server_alias: &str, at: &str, since: &str) -> Result<Option<Operation>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!(
+                    "SELECT {} FROM operations
+                     WHERE server_alias = $1 AND created_at <= $2 AND (status = 'running' OR updated_at >= $3)
+                     ORDER BY created_at DESC LIMIT 1",
+                    OPERATION_COLUMNS
+                ),
+                &[&server_alias, &at, &since],
+            )?;
+            Ok(row.as_ref().map(operation_from_row))
+        })
+    }
+
+    fn update_operation(&self, id: &str, stages: &[OperationStage], status: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            let stages_json = serde_json::to_string(stages)?;
+            conn.execute(
+                &format!("UPDATE operations SET stages = $1, status = $2, updated_at = {} WHERE id = $3", NOW),
+                &[&stages_json, &status, &id],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn purge_operations_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM operations
+                 WHERE status != 'running'
+                   AND updated_at < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn create_group_apply_job(&self, group: &str, config: &str) -> Result<String> {
+        self.with_conn(|conn| {
+            let id = uuid::Uuid::new_v4().to_string();
+            conn.execute(
+                "INSERT INTO group_apply_jobs (id, group_name, config, status) VALUES ($1, $2, $3, 'running')",
+                &[&id, &group, &config],
+            )?;
+            Ok(id)
+        })
+    }
+
+    fn update_group_apply_job(&self, id: &str, status: &str, results: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                &format!("UPDATE group_apply_jobs SET status = $1, results = $2, updated_at = {} WHERE id = $3", NOW),
+                &[&status, &results, &id],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn get_group_apply_job(&self, id: &str) -> Result<Option<GroupApplyJob>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                "SELECT id, group_name, config, status, results, created_at, updated_at FROM group_apply_jobs WHERE id = $1",
+                &[&id],
+            )?;
+            Ok(row.map(|row| {
+                let config: String = row.get(2);
+                let results: String = row.get(4);
+                GroupApplyJob {
+                    id: row.get(0),
+                    group: row.get(1),
+                    config: serde_json::from_str(&config).unwrap_or_default(),
+                    status: row.get(3),
+                    results: serde_json::from_str(&results).unwrap_or_default(),
+                    created_at: row.get(5),
+                    updated_at: row.get(6),
+                }
+            }))
+        })
+    }
+
+    fn interrupt_running_group_apply_jobs(&self) -> Result<usize> {
+        self.with_conn(|conn| {
+            let updated = conn.execute(
+                &format!(
+                    "UPDATE group_apply_jobs SET status = 'interrupted', updated_at = {} WHERE status = 'running'",
+                    NOW
+                ),
+                &[],
+            )?;
+            Ok(updated as usize)
+        })
+    }
+
+    fn purge_group_apply_jobs_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM group_apply_jobs
+                 WHERE status != 'running'
+                   AND updated_at < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn create_event_subscription(&self, destination: &str, context: &str, subscription_uri: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO event_subscriptions (destination, context, subscription_uri) VALUES ($1, $2, $3)",
+                &[&destination, &context, &subscription_uri],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn is_known_event_context(&self, context: &str) -> Result<bool> {
+        self.with_conn(|conn| {
+            let count: i64 = conn
+                .query_one("SELECT COUNT(*) FROM event_subscriptions WHERE context = $1", &[&context])?
+                .get(0);
+            Ok(count > 0)
+        })
+    }
+
+    fn heartbeat_lease(&self, holder: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO leases (name, holder, heartbeat_at) VALUES ('primary', $1, {})
+                     ON CONFLICT (name) DO UPDATE SET holder = excluded.holder, heartbeat_at = excluded.heartbeat_at",
+                    NOW
+                ),
+                &[&holder],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn primary_lease_age_secs(&self) -> Result<Option<i64>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                "SELECT EXTRACT(EPOCH FROM (now() AT TIME ZONE 'UTC') - heartbeat_at::TIMESTAMP)::BIGINT
+                 FROM leases WHERE name = 'primary'",
+                &[],
+            )?;
+            Ok(row.map(|row| row.get(0)))
+        })
+    }
+
+    fn get_user_preferences(&self, user_id: i64) -> Result<Vec<(String, String)>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT key, value FROM user_preferences WHERE user_id = $1 ORDER BY key",
+                &[&user_id],
+            )?;
+            Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+        })
+    }
+
+    fn replace_user_preferences(&self, user_id: i64, preferences: &[(String, String)]) -> Result<()> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            tx.execute("DELETE FROM user_preferences WHERE user_id = $1", &[&user_id])?;
+            for (key, value) in preferences {
+                tx.execute(
+                    "INSERT INTO user_preferences (user_id, key, value) VALUES ($1, $2, $3)",
+                    &[&user_id, key, value],
+                )?;
+            }
+            Ok(tx.commit()?)
+        })
+    }
+
+    fn create_server(&self, server: &NewServer) -> Result<ServerRecord> {
+        self.with_conn(|conn| {
+            let tags = serde_json::to_string(&server.tags)?;
+            let watts = server.default_power_cap_watts.map(i64::from);
+            let token = server.os_health_token.as_ref().map(|token| token.expose());
+            let (row, slug) = insert_with_unique_slug(&slugify(&server.name), |slug| {
+                conn.query_one(
+                    "INSERT INTO servers (name, slug, host, username, password, tags, location, default_power_cap_watts, hosts_this_app,
+                                          os_health_url, os_health_insecure, os_health_token)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING id",
+                    &[
+                        &server.name,
+                        &slug,
+                        &server.host,
+                        &server.username,
+                        &server.password.expose(),
+                        &tags,
+                        &server.location,
+                        &watts,
+                        &server.hosts_this_app,
+                        &server.os_health_url,
+                        &server.os_health_insecure,
+                        &token,
+                    ],
+                )
+            })?;
+
+            info!("Server registered: {} ({})", server.name, server.host);
+            Ok(ServerRecord {
+                id: row.get(0),
+                name: server.name.clone(),
+                slug,
+                host: server.host.clone(),
+                username: server.username.clone(),
+                password: server.password.expose().to_string(),
+                tags: server.tags.clone(),
+                location: server.location.clone(),
+                default_power_cap_watts: server.default_power_cap_watts,
+                hosts_this_app: server.hosts_this_app,
+                os_health_url: server.os_health_url.clone(),
+                os_health_insecure: server.os_health_insecure,
+                os_health_token: token.map(str::to_string),
+            })
+        })
+    }
+
+    fn list_servers(&self) -> Result<Vec<ServerRecord>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(&format!("SELECT {} FROM servers ORDER BY name", SERVER_COLUMNS), &[])?;
+            Ok(rows.iter().map(server_record_from_row).collect())
+        })
+    }
+
+    fn update_server_credentials(&self, id: i64, username: Option<&str>, password: Option<&str>) -> Result<Option<ServerRecord>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!(
+                    "UPDATE servers SET username = COALESCE($2, username), password = COALESCE($3, password)
+                     WHERE id = $1 RETURNING {}",
+                    SERVER_COLUMNS
+                ),
+                &[&id, &username, &password],
+            )?;
+            Ok(row.as_ref().map(server_record_from_row))
+        })
+    }
+
+    fn update_server_os_health(
+        &self,
+        id: i64,
+        url: Option<&str>,
+        insecure: bool,
+        token: Option<&str>,
+    ) -> Result<Option<ServerRecord>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!(
+                    "UPDATE servers SET os_health_url = $2, os_health_insecure = $3, os_health_token = $4
+                     WHERE id = $1 RETURNING {}",
+                    SERVER_COLUMNS
+                ),
+                &[&id, &url, &insecure, &token],
+            )?;
+            Ok(row.as_ref().map(server_record_from_row))
+        })
+    }
+
+    fn delete_server(&self, id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            let Some(row) = tx.query_opt("SELECT slug FROM servers WHERE id = $1", &[&id])? else {
+                return Ok(false);
+            };
+            let slug: String = row.get(0);
+            tx.execute("DELETE FROM power_cap_schedules WHERE server_alias = $1", &[&slug])?;
+            tx.execute("DELETE FROM tariff_windows WHERE server_alias = $1", &[&slug])?;
+            tx.execute("DELETE FROM tariff_caps WHERE server_alias = $1", &[&slug])?;
+            tx.execute("DELETE FROM one_shot_schedules WHERE server_alias = $1 AND status = 'pending'", &[&slug])?;
+            tx.execute("DELETE FROM firmware_update_schedules WHERE server_alias = $1 AND status = 'pending'", &[&slug])?;
+            tx.execute("DELETE FROM power_baselines WHERE server_alias = $1", &[&slug])?;
+            tx.execute("DELETE FROM servers WHERE id = $1", &[&id])?;
+            tx.commit()?;
+
+            info!("Server deleted: {}", slug);
+            Ok(true)
+        })
+    }
+
+    fn create_power_cap_schedule(
+        &self,
+        server_alias: &str,
+        cron_expr: &str,
+        watts: u32,
+        duration_minutes: u32,
+        enabled: bool,
+    ) -> Result<PowerCapSchedule> {
+        self.with_conn(|conn| {
+            let row = conn.query_one(
+                "INSERT INTO power_cap_schedules (server_alias, cron_expr, watts, duration_minutes, enabled)
+                 VALUES ($1, $2, $3, $4, $5)
+                 RETURNING id, server_alias, cron_expr, watts, duration_minutes, enabled, created_at",
+                &[&server_alias, &cron_expr, &i64::from(watts), &i64::from(duration_minutes), &enabled],
+            )?;
+            Ok(power_cap_schedule_from_row(&row))
+        })
+    }
+
+    fn list_power_cap_schedules(&self, server_alias: Option<&str>) -> Result<Vec<PowerCapSchedule>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT id, server_alias, cron_expr, watts, duration_minutes, enabled, created_at
+                 FROM power_cap_schedules
+                 WHERE $1::TEXT IS NULL OR server_alias = $1
+                 ORDER BY id",
+                &[&server_alias],
+            )?;
+            Ok(rows.iter().map(power_cap_schedule_from_row).collect())
+        })
+    }
+
+    fn delete_power_cap_schedule(&self, server_alias: &str, id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute(
+                "DELETE FROM power_cap_schedules WHERE id = $1 AND server_alias = $2",
+                &[&id, &server_alias],
+            )?;
+            Ok(deleted > 0)
+        })
+    }
+
+    fn create_tariff_window(
+        &self,
+        server_alias: Option<&str>,
+        group_id: Option<i64>,
+        days: &[String],
+        start: &str,
+        end: &str,
+        watts: u32,
+    ) -> Result<TariffWindow> {
+        let days = serde_json::to_string(days).unwrap_or_else(|_| "[]".to_string());
+        self.with_conn(|conn| {
+            let row = conn.query_one(
+                &format!(
+                    "INSERT INTO tariff_windows (server_alias, group_id, days, start_time, end_time, watts)
+                     VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+                    TARIFF_WINDOW_COLUMNS
+                ),
+                &[&server_alias, &group_id, &days, &start, &end, &i64::from(watts)],
+            )?;
+            Ok(tariff_window_from_row(&row))
+        })
+    }
+
+    fn list_tariff_windows(&self) -> Result<Vec<TariffWindow>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(&format!("SELECT {} FROM tariff_windows ORDER BY id", TARIFF_WINDOW_COLUMNS), &[])?;
+            Ok(rows.iter().map(tariff_window_from_row).collect())
+        })
+    }
+
+    fn delete_tariff_window(&self, id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute("DELETE FROM tariff_windows WHERE id = $1", &[&id])?;
+            Ok(deleted > 0)
+        })
+    }
+
+    fn list_tariff_caps(&self) -> Result<Vec<TariffCap>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT server_alias, window_id, watts, previous_watts, applied_at FROM tariff_caps ORDER BY server_alias",
+                &[],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| TariffCap {
+                    server_alias: row.get(0),
+                    window_id: row.get(1),
+                    watts: row.get::<_, i64>(2) as u32,
+                    previous_watts: row.get::<_, Option<i64>>(3).map(|watts| watts as u32),
+                    applied_at: row.get(4),
+                })
+                .collect())
+        })
+    }
+
+    fn set_tariff_cap(&self, cap: &TariffCap) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO tariff_caps (server_alias, window_id, watts, previous_watts, applied_at)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (server_alias) DO UPDATE SET
+                    window_id = EXCLUDED.window_id,
+                    watts = EXCLUDED.watts,
+                    previous_watts = EXCLUDED.previous_watts,
+                    applied_at = EXCLUDED.applied_at",
+                &[
+                    &cap.server_alias,
+                    &cap.window_id,
+                    &i64::from(cap.watts),
+                    &cap.previous_watts.map(i64::from),
+                    &cap.applied_at,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn delete_tariff_cap(&self, server_alias: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM tariff_caps WHERE server_alias = $1", &[&server_alias])?;
+            Ok(())
+        })
+    }
+
+    fn create_one_shot_schedule(
+        &self,
+        server_alias: &str,
+        action: &str,
+        execute_at: &str,
+        created_by: Option<i64>,
+    ) -> Result<OneShotSchedule> {
+        self.with_conn(|conn| {
+            let row = conn.query_one(
+                &format!(
+                    "INSERT INTO one_shot_schedules (server_alias, action, execute_at, created_by)
+                     VALUES ($1, $2, $3, $4) RETURNING {}",
+                    ONE_SHOT_COLUMNS
+                ),
+                &[&server_alias, &action, &execute_at, &created_by],
+            )?;
+            Ok(one_shot_schedule_from_row(&row))
+        })
+    }
+
+    fn list_one_shot_schedules(&self) -> Result<Vec<OneShotSchedule>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!("SELECT {} FROM one_shot_schedules ORDER BY execute_at, id", ONE_SHOT_COLUMNS),
+                &[],
+            )?;
+            Ok(rows.iter().map(one_shot_schedule_from_row).collect())
+        })
+    }
+
+    fn delete_one_shot_schedule(&self, id: i64) -> Result<Option<OneShotSchedule>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!(
+                    "DELETE FROM one_shot_schedules WHERE id = $1 AND status = 'pending' RETURNING {}",
+                    ONE_SHOT_COLUMNS
+                ),
+                &[&id],
+            )?;
+            Ok(row.as_ref().map(one_shot_schedule_from_row))
+        })
+    }
+
+    fn claim_due_one_shot_schedules(&self) -> Result<Vec<OneShotSchedule>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "UPDATE one_shot_schedules SET status = 'running'
+                     WHERE status = 'pending' AND execute_at <= {}
+                     RETURNING {}",
+                    NOW, ONE_SHOT_COLUMNS
+                ),
+                &[],
+            )?;
+            Ok(rows.iter().map(one_shot_schedule_from_row).collect())
+        })
+    }
+
+    fn finish_one_shot_schedule(&self, id: i64, outcome: &str, message: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                &format!(
+                    "UPDATE one_shot_schedules
+                     SET status = 'executed', executed_at = {}, outcome = $1, message = $2
+                     WHERE id = $3",
+                    NOW
+                ),
+                &[&outcome, &message, &id],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn create_firmware_update_schedule(
+        &self,
+        server_alias: &str,
+        job_id: &str,
+        reboot_at: &str,
+        created_by: Option<i64>,
+    ) -> Result<FirmwareUpdateSchedule> {
+        self.with_conn(|conn| {
+            let row = conn.query_one(
+                &format!(
+                    "INSERT INTO firmware_update_schedules (server_alias, job_id, reboot_at, created_by)
+                     VALUES ($1, $2, $3, $4) RETURNING {}",
+                    FIRMWARE_UPDATE_SCHEDULE_COLUMNS
+                ),
+                &[&server_alias, &job_id, &reboot_at, &created_by],
+            )?;
+            Ok(firmware_update_schedule_from_row(&row))
+        })
+    }
+
+    fn list_firmware_update_schedules(&self) -> Result<Vec<FirmwareUpdateSchedule>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {} FROM firmware_update_schedules ORDER BY reboot_at, id",
+                    FIRMWARE_UPDATE_SCHEDULE_COLUMNS
+                ),
+                &[],
+            )?;
+            Ok(rows.iter().map(firmware_update_schedule_from_row).collect())
+        })
+    }
+
+    fn claim_due_firmware_update_schedules(&self) -> Result<Vec<FirmwareUpdateSchedule>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "UPDATE firmware_update_schedules SET status = 'running'
+                     WHERE status = 'pending' AND reboot_at <= {}
+                     RETURNING {}",
+                    NOW, FIRMWARE_UPDATE_SCHEDULE_COLUMNS
+                ),
+                &[],
+            )?;
+            Ok(rows.iter().map(firmware_update_schedule_from_row).collect())
+        })
+    }
+
+    fn set_firmware_update_operation(&self, id: i64, operation_id: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE firmware_update_schedules SET operation_id = $1 WHERE id = $2",
+                &[&operation_id, &id],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn finish_firmware_update_schedule(&self, id: i64, outcome: &str, message: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                &format!(
+                    "UPDATE firmware_update_schedules
+                     SET status = 'executed', executed_at = {}, outcome = $1, message = $2
+                     WHERE id = $3",
+                    NOW
+                ),
+                &[&outcome, &message, &id],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+        scopes: &[String],
+        requests_per_hour: Option<u32>,
+    ) -> Result<ApiToken> {
+        self.with_conn(|conn| {
+            let scopes_json = serde_json::to_string(scopes)?;
+            let requests_per_hour = requests_per_hour.map(i64::from);
+            let row = conn.query_one(
+                "INSERT INTO api_tokens (user_id, name, token_hash, scopes, requests_per_hour) VALUES ($1, $2, $3, $4, $5)
+                 RETURNING id, user_id, name, scopes, created_at, last_used_at, requests_per_hour",
+                &[&user_id, &name, &token_hash, &scopes_json, &requests_per_hour],
+            )?;
+            Ok(api_token_from_row(&row))
+        })
+    }
+
+    fn list_api_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT id, user_id, name, scopes, created_at, last_used_at, requests_per_hour
+                 FROM api_tokens WHERE user_id = $1 ORDER BY id",
+                &[&user_id],
+            )?;
+            Ok(rows.iter().map(api_token_from_row).collect())
+        })
+    }
+
+    fn delete_api_token(&self, user_id: i64, id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute("DELETE FROM api_tokens WHERE id = $1 AND user_id = $2", &[&id, &user_id])?;
+            Ok(deleted > 0)
+        })
+    }
+
+    fn find_api_token(&self, token_hash: &str) -> Result<Option<ApiToken>> {
+        self.with_conn(|conn| {
+            let token = match conn.query_opt(
+                "SELECT id, user_id, name, scopes, created_at, last_used_at, requests_per_hour
+                 FROM api_tokens WHERE token_hash = $1",
+                &[&token_hash],
+            )? {
+                Some(row) => api_token_from_row(&row),
+                None => return Ok(None),
+            };
+            if let Err(e) = conn.execute(
+                &format!("UPDATE api_tokens SET last_used_at = {} WHERE id = $1", NOW),
+                &[&token.id],
+            ) {
+                warn!("Failed to record use of API token {}: {}", token.id, e);
+            }
+            Ok(Some(token))
+        })
+    }
+
+    fn set_api_token_quota(&self, id: i64, requests_per_hour: Option<u32>) -> Result<bool> {
+        self.with_conn(|conn| {
+            let updated = conn.execute(
+                "UPDATE api_tokens SET requests_per_hour = $1 WHERE id = $2",
+                &[&requests_per_hour.map(i64::from), &id],
+            )?;
+            Ok(updated > 0)
+        })
+    }
+
+    fn record_api_usage(&self, counts: &[(UsageKey, u64)]) -> Result<()> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            for (key, requests) in counts {
+                tx.execute(
+                    "INSERT INTO api_usage (user_id, token_id, hour, requests) VALUES ($1, $2, $3, $4)
+                     ON CONFLICT (user_id, token_id, hour) DO UPDATE SET requests = api_usage.requests + excluded.requests",
+                    &[&key.user_id, &key.token_id.unwrap_or(0), &key.hour, &(*requests as i64)],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    fn list_api_usage(&self, user_id: Option<i64>, since: &str) -> Result<Vec<ApiUsage>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT a.user_id, u.username, a.token_id, t.name, a.hour, a.requests
+                 FROM api_usage a
+                 LEFT JOIN users u ON u.id = a.user_id
+                 LEFT JOIN api_tokens t ON t.id = a.token_id
+                 WHERE ($1::BIGINT IS NULL OR a.user_id = $1) AND a.hour >= $2
+                 ORDER BY a.hour DESC, a.requests DESC",
+                &[&user_id, &since],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| {
+                    let token_id: i64 = row.get(2);
+                    ApiUsage {
+                        user_id: row.get(0),
+                        username: row.get(1),
+                        token_id: (token_id != 0).then_some(token_id),
+                        token_name: row.get(3),
+                        hour: row.get(4),
+                        requests: row.get::<_, i64>(5) as u64,
+                    }
+                })
+                .collect())
+        })
+    }
+
+    fn api_token_usage(&self, token_id: i64, hour: &str) -> Result<u64> {
+        self.with_conn(|conn| {
+            let row = conn.query_one(
+                "SELECT COALESCE(SUM(requests), 0)::BIGINT FROM api_usage WHERE token_id = $1 AND hour = $2",
+                &[&token_id, &hour],
+            )?;
+            Ok(row.get::<_, i64>(0) as u64)
+        })
+    }
+
+    fn purge_api_usage_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM api_usage
+                 WHERE hour < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn create_break_glass_grant(
+        &self,
+        token_hash: &str,
+        reason: &str,
+        issued_by: &str,
+        expires_at: &str,
+    ) -> Result<BreakGlassGrant> {
+        self.with_conn(|conn| {
+            let row = conn.query_one(
+                &format!(
+                    "INSERT INTO break_glass_grants (token_hash, reason, issued_by, expires_at)
+                     VALUES ($1, $2, $3, $4) RETURNING {}",
+                    BREAK_GLASS_COLUMNS
+                ),
+                &[&token_hash, &reason, &issued_by, &expires_at],
+            )?;
+            Ok(break_glass_grant_from_row(&row))
+        })
+    }
+
+    fn claim_break_glass_grant(&self, token_hash: &str) -> Result<Option<BreakGlassGrant>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!(
+                    "UPDATE break_glass_grants SET used_at = {now}
+                     WHERE token_hash = $1 AND used_at IS NULL AND expires_at > {now}
+                     RETURNING {}",
+                    BREAK_GLASS_COLUMNS,
+                    now = NOW
+                ),
+                &[&token_hash],
+            )?;
+            Ok(row.as_ref().map(break_glass_grant_from_row))
+        })
+    }
+
+    fn list_active_break_glass_grants(&self) -> Result<Vec<BreakGlassGrant>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {} FROM break_glass_grants WHERE expires_at > {} ORDER BY id DESC",
+                    BREAK_GLASS_COLUMNS, NOW
+                ),
+                &[],
+            )?;
+            Ok(rows.iter().map(break_glass_grant_from_row).collect())
+        })
+    }
+
+    fn create_password_reset_token(&self, user_id: i64, token_hash: &str, expires_at: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            tx.execute(
+                &format!("DELETE FROM password_reset_tokens WHERE user_id = $1 OR expires_at <= {}", NOW),
+                &[&user_id],
+            )?;
+            tx.execute(
+                "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)",
+                &[&user_id, &token_hash, &expires_at],
+            )?;
+            Ok(tx.commit()?)
+        })
+    }
+
+    fn reset_password_with_token(&self, token_hash: &str, password_hash: &str) -> Result<Option<i64>> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            let Some(row) = tx.query_opt(
+                &format!(
+                    "DELETE FROM password_reset_tokens WHERE token_hash = $1 AND expires_at > {}
+                     RETURNING user_id",
+                    NOW
+                ),
+                &[&token_hash],
+            )?
+            else {
+                return Ok(None);
+            };
+            let user_id: i64 = row.get(0);
+            let updated = tx.execute(
+                "UPDATE users SET password_hash = $2, session_generation = session_generation + 1
+                 WHERE id = $1 AND auth_source = 'local'",
+                &[&user_id, &password_hash],
+            )?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            tx.commit()?;
+            Ok(Some(user_id))
+        })
+    }
+
+    fn create_share(&self, share: &NewShare) -> Result<Share> {
+        self.with_conn(|conn| {
+            let servers_json = serde_json::to_string(share.servers)?;
+            let row = conn.query_one(
+                &format!(
+                    "INSERT INTO shares (name, servers, tag, token_hash, passcode_hash, created_by, expires_at)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+                    SHARE_COLUMNS
+                ),
+                &[
+                    &share.name,
+                    &servers_json,
+                    &share.tag,
+                    &share.token_hash,
+                    &share.passcode_hash,
+                    &share.created_by,
+                    &share.expires_at,
+                ],
+            )?;
+            Ok(share_from_row(&row))
+        })
+    }
+
+    fn list_shares(&self) -> Result<Vec<Share>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(&format!("SELECT {} FROM shares ORDER BY id DESC", SHARE_COLUMNS), &[])?;
+            Ok(rows.iter().map(share_from_row).collect())
+        })
+    }
+
+    fn delete_share(&self, id: i64) -> Result<bool> {
+        self.with_conn(|conn| Ok(conn.execute("DELETE FROM shares WHERE id = $1", &[&id])? > 0))
+    }
+
+    fn find_share(&self, token_hash: &str) -> Result<Option<Share>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!(
+                    "SELECT {} FROM shares WHERE token_hash = $1 AND expires_at > {}",
+                    SHARE_COLUMNS, NOW
+                ),
+                &[&token_hash],
+            )?;
+            let Some(share) = row.as_ref().map(share_from_row) else {
+                return Ok(None);
+            };
+            if let Err(e) = conn.execute(
+                &format!(
+                    "UPDATE shares SET last_accessed_at = {}, access_count = access_count + 1 WHERE id = $1",
+                    NOW
+                ),
+                &[&share.id],
+            ) {
+                warn!("Failed to record access to share {}: {}", share.id, e);
+            }
+            Ok(Some(share))
+        })
+    }
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt("SELECT value FROM settings WHERE key = $1", &[&key])?;
+            Ok(row.map(|row| row.get(0)))
+        })
+    }
+
+    fn put_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO settings (key, value, updated_at) VALUES ($1, $2, {})
+                     ON CONFLICT (key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+                    NOW
+                ),
+                &[&key, &value],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn replace_firmware_inventory(&self, server_alias: &str, components: &[(String, String)]) -> Result<()> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            tx.execute("DELETE FROM firmware_inventory WHERE server_alias = $1", &[&server_alias])?;
+            for (component, version) in components {
+                tx.execute(
+                    "INSERT INTO firmware_inventory (server_alias, component, version) VALUES ($1, $2, $3)
+                     ON CONFLICT DO NOTHING",
+                    &[&server_alias, component, version],
+                )?;
+            }
+            Ok(tx.commit()?)
+        })
+    }
+
+    fn list_firmware_inventory(&self) -> Result<Vec<FirmwareInventoryEntry>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT server_alias, component, version, collected_at FROM firmware_inventory
+                 ORDER BY component, server_alias",
+                &[],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| FirmwareInventoryEntry {
+                    server_alias: row.get(0),
+                    component: row.get(1),
+                    version: row.get(2),
+                    collected_at: row.get(3),
+                })
+                .collect())
+        })
+    }
+
+    fn list_firmware_baselines(&self) -> Result<Vec<(String, String)>> {
+        self.with_conn(|conn| {
+            let rows = conn.query("SELECT component, version FROM firmware_baselines ORDER BY component", &[])?;
+            Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+        })
+    }
+
+    fn set_firmware_baseline(&self, component: &str, version: Option<&str>) -> Result<()> {
+        self.with_conn(|conn| {
+            match version {
+                Some(version) => conn.execute(
+                    &format!(
+                        "INSERT INTO firmware_baselines (component, version, updated_at) VALUES ($1, $2, {})
+                         ON CONFLICT (component) DO UPDATE SET version = excluded.version, updated_at = excluded.updated_at",
+                        NOW
+                    ),
+                    &[&component, &version],
+                )?,
+                None => conn.execute("DELETE FROM firmware_baselines WHERE component = $1", &[&component])?,
+            };
+            Ok(())
+        })
+    }
+
+    fn replace_compliance_profile(&self, name: &str, requirements: &[ComplianceRequirement]) -> Result<()> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            tx.execute("DELETE FROM compliance_profiles WHERE profile_name = $1", &[&name])?;
+            for requirement in requirements {
+                tx.execute(
+                    "INSERT INTO compliance_profiles (profile_name, component, minimum_version, recommended_version)
+                     VALUES ($1, $2, $3, $4)",
+                    &[
+                        &name,
+                        &requirement.component,
+                        &requirement.minimum_version,
+                        &requirement.recommended_version,
+                    ],
+                )?;
+            }
+            Ok(tx.commit()?)
+        })
+    }
+
+    fn get_compliance_profile(&self, name: &str) -> Result<Vec<ComplianceRequirement>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT component, minimum_version, recommended_version FROM compliance_profiles
+                 WHERE profile_name = $1 ORDER BY component",
+                &[&name],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| ComplianceRequirement {
+                    component: row.get(0),
+                    minimum_version: row.get(1),
+                    recommended_version: row.get(2),
+                })
+                .collect())
+        })
+    }
+
+    fn create_server_group(&self, name: &str, members: &[String], power_budget_watts: Option<u32>) -> Result<ServerGroup> {
+        self.with_conn(|conn| {
+            let members = serde_json::to_string(members)?;
+            let row = conn.query_one(
+                &format!(
+                    "INSERT INTO server_groups (name, members, power_budget_watts) VALUES ($1, $2, $3) RETURNING {}",
+                    SERVER_GROUP_COLUMNS
+                ),
+                &[&name, &members, &power_budget_watts.map(i64::from)],
+            )?;
+            Ok(server_group_from_row(&row))
+        })
+    }
+
+    fn list_server_groups(&self) -> Result<Vec<ServerGroup>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!("SELECT {} FROM server_groups ORDER BY name", SERVER_GROUP_COLUMNS),
+                &[],
+            )?;
+            Ok(rows.iter().map(server_group_from_row).collect())
+        })
+    }
+
+    fn get_server_group(&self, id: i64) -> Result<Option<ServerGroup>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                &format!("SELECT {} FROM server_groups WHERE id = $1", SERVER_GROUP_COLUMNS),
+                &[&id],
+            )?;
+            Ok(row.as_ref().map(server_group_from_row))
+        })
+    }
+
+    fn update_server_group(
+        &self,
+        id: i64,
+        members: &[String],
+        power_budget_watts: Option<u32>,
+    ) -> Result<Option<ServerGroup>> {
+        self.with_conn(|conn| {
+            let members = serde_json::to_string(members)?;
+            let row = conn.query_opt(
+                &format!(
+                    "UPDATE server_groups SET members = $2, power_budget_watts = $3 WHERE id = $1 RETURNING {}",
+                    SERVER_GROUP_COLUMNS
+                ),
+                &[&id, &members, &power_budget_watts.map(i64::from)],
+            )?;
+            Ok(row.as_ref().map(server_group_from_row))
+        })
+    }
+
+    fn delete_server_group(&self, id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            tx.execute("DELETE FROM tariff_windows WHERE group_id = $1", &[&id])?;
+            let deleted = tx.execute("DELETE FROM server_groups WHERE id = $1", &[&id])?;
+            tx.commit()?;
+            Ok(deleted > 0)
+        })
+    }
+
+    fn record_power_samples(&self, sampled_at: &str, samples: &[(String, u32)]) -> Result<()> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            for (server_alias, watts) in samples {
+                tx.execute(
+                    "INSERT INTO power_samples (server_alias, watts, sampled_at) VALUES ($1, $2, $3)",
+                    &[server_alias, &i64::from(*watts), &sampled_at],
+                )?;
+            }
+            Ok(tx.commit()?)
+        })
+    }
+
+    fn latest_power_samples(&self, since: &str) -> Result<Vec<PowerSample>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT p.server_alias, p.watts, p.sampled_at FROM power_samples p
+                 JOIN (SELECT server_alias, MAX(sampled_at) AS sampled_at FROM power_samples
+                       WHERE sampled_at >= $1 GROUP BY server_alias) latest
+                   ON latest.server_alias = p.server_alias AND latest.sampled_at = p.sampled_at
+                 ORDER BY p.server_alias",
+                &[&since],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| PowerSample {
+                    server_alias: row.get(0),
+                    watts: row.get::<_, i64>(1) as u32,
+                    sampled_at: row.get(2),
+                })
+                .collect())
+        })
+    }
+
+    fn peak_power_total(&self, aliases: &[String], since: &str) -> Result<Option<PowerPeak>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                "SELECT SUM(watts)::BIGINT AS total, sampled_at FROM power_samples
+                 WHERE sampled_at >= $2 AND server_alias = ANY($1)
+                 GROUP BY sampled_at ORDER BY total DESC, sampled_at DESC LIMIT 1",
+                &[&aliases, &since],
+            )?;
+            Ok(row.map(|row| PowerPeak {
+                watts: row.get::<_, i64>(0) as u64,
+                sampled_at: row.get(1),
+            }))
+        })
+    }
+
+    fn purge_power_samples_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM power_samples
+                 WHERE sampled_at < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn record_power_event(&self, server_alias: &str, power_state: &str, reason: Option<&PowerChangeReason>) -> Result<bool> {
+        self.with_conn(|conn| {
+            let inserted = conn.execute(
+                "INSERT INTO power_events (server_alias, power_state, reason_code)
+                 SELECT $1::TEXT, $2::TEXT, $3::TEXT
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM (SELECT power_state, reason_code FROM power_events
+                                    WHERE server_alias = $1 ORDER BY id DESC LIMIT 1) latest
+                     WHERE latest.power_state = $2 AND latest.reason_code IS NOT DISTINCT FROM $3
+                 )",
+                &[&server_alias, &power_state, &reason.map(|reason| reason.code.as_str())],
+            )?;
+            Ok(inserted > 0)
+        })
+    }
+
+    fn list_power_events(&self, server_alias: Option<&str>, limit: u32) -> Result<Vec<PowerEvent>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT id, server_alias, power_state, reason_code, observed_at FROM power_events
+                 WHERE $1::TEXT IS NULL OR server_alias = $1
+                 ORDER BY id DESC
+                 LIMIT $2",
+                &[&server_alias, &i64::from(limit)],
+            )?;
+            Ok(rows.iter().map(power_event_from_row).collect())
+        })
+    }
+
+    fn purge_power_events_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM power_events
+                 WHERE observed_at < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn record_ping(&self, server_alias: &str, latency_ms: Option<f64>, packet_loss_pct: f64) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO ping_history (server_alias, latency_ms, packet_loss_pct) VALUES ($1, $2, $3)",
+                &[&server_alias, &latency_ms, &packet_loss_pct],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn list_ping_history(&self, server_alias: &str, since: Option<&str>, limit: u32) -> Result<Vec<PingSample>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT id, server_alias, latency_ms, packet_loss_pct, recorded_at FROM ping_history
+                 WHERE server_alias = $1 AND ($2::TEXT IS NULL OR recorded_at >= $2)
+                 ORDER BY id DESC
+                 LIMIT $3",
+                &[&server_alias, &since, &i64::from(limit)],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| PingSample {
+                    id: row.get(0),
+                    server_alias: row.get(1),
+                    latency_ms: row.get(2),
+                    packet_loss_pct: row.get(3),
+                    recorded_at: row.get(4),
+                })
+                .collect())
+        })
+    }
+
+    fn purge_ping_history_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM ping_history
+                 WHERE recorded_at < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn power_sample_watts(&self, server_alias: &str, since: &str, until: &str) -> Result<Vec<u32>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                "SELECT watts FROM power_samples WHERE server_alias = $1 AND sampled_at >= $2 AND sampled_at < $3",
+                &[&server_alias, &since, &until],
+            )?;
+            Ok(rows.iter().map(|row| row.get::<_, i64>(0) as u32).collect())
+        })
+    }
+
+    fn get_power_baseline(&self, server_alias: &str, hour_of_week: u32) -> Result<Option<PowerBaseline>> {
+        self.with_conn(|conn| {
+            let row = conn.query_opt(
+                "SELECT server_alias, hour_of_week, weeks, median_watts, mad_watts, updated_at FROM power_baselines
+                 WHERE server_alias = $1 AND hour_of_week = $2",
+                &[&server_alias, &(hour_of_week as i32)],
+            )?;
+            Ok(row.map(|row| {
+                let weeks: String = row.get(2);
+                PowerBaseline {
+                    server_alias: row.get(0),
+                    hour_of_week: row.get::<_, i32>(1) as u32,
+                    weeks: serde_json::from_str(&weeks).unwrap_or_default(),
+                    median_watts: row.get(3),
+                    mad_watts: row.get(4),
+                    updated_at: row.get(5),
+                }
+            }))
+        })
+    }
+
+    fn save_power_baseline(&self, baseline: &PowerBaseline) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO power_baselines (server_alias, hour_of_week, weeks, median_watts, mad_watts, updated_at)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 ON CONFLICT (server_alias, hour_of_week) DO UPDATE SET
+                     weeks = EXCLUDED.weeks, median_watts = EXCLUDED.median_watts,
+                     mad_watts = EXCLUDED.mad_watts, updated_at = EXCLUDED.updated_at",
+                &[
+                    &baseline.server_alias,
+                    &(baseline.hour_of_week as i32),
+                    &serde_json::to_string(&baseline.weeks)?,
+                    &baseline.median_watts,
+                    &baseline.mad_watts,
+                    &baseline.updated_at,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn record_metric_samples(&self, samples: &[MetricSample]) -> Result<()> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            for sample in samples {
+                tx.execute(
+                    "INSERT INTO metric_samples
+                         (server_alias, report_id, metric_id, metric_property, value, numeric_value, timestamp)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                    &[
+                        &sample.server_alias,
+                        &sample.report_id,
+                        &sample.metric_id,
+                        &sample.metric_property,
+                        &sample.value,
+                        &sample.numeric_value,
+                        &sample.timestamp,
+                    ],
+                )?;
+            }
+            Ok(tx.commit()?)
+        })
+    }
+
+    fn list_metric_samples(
+        &self,
+        metric_id: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        keyset: &Keyset<(String, i64)>,
+        limit: u32,
+    ) -> Result<Vec<MetricSample>> {
+        self.with_conn(|conn| {
+            let (cmp, order) = keyset.sql(false);
+            let (key_timestamp, key_id) = keyset.key().map(|(t, id)| (t.as_str(), *id)).unzip();
+            let rows = conn.query(
+                &format!(
+                    "SELECT id, server_alias, report_id, metric_id, metric_property, value, numeric_value, timestamp
+                     FROM metric_samples
+                     WHERE ($1::TEXT IS NULL OR metric_id = $1)
+                       AND ($2::TEXT IS NULL OR timestamp >= $2)
+                       AND ($3::TEXT IS NULL OR timestamp <= $3)
+                       AND ($4::TEXT IS NULL OR (timestamp, id) {cmp} ($4, $5::BIGINT))
+                     ORDER BY timestamp {order}, id {order}
+                     LIMIT $6"
+                ),
+                &[&metric_id, &since, &until, &key_timestamp, &key_id, &i64::from(limit)],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| MetricSample {
+                    id: row.get(0),
+                    server_alias: row.get(1),
+                    report_id: row.get(2),
+                    metric_id: row.get(3),
+                    metric_property: row.get(4),
+                    value: row.get(5),
+                    numeric_value: row.get(6),
+                    timestamp: row.get(7),
+                })
+                .collect())
+        })
+    }
+
+    fn purge_metric_samples_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM metric_samples
+                 WHERE timestamp < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn roll_up_metric_samples(&self, granularity: RollupGranularity, from: Option<&str>) -> Result<usize> {
+        self.with_conn(|conn| {
+            let written = conn.execute(
+                &format!(
+                    "INSERT INTO metric_rollups
+                         (granularity, server_alias, metric_id, bucket_start, sample_count, min_value, avg_value, max_value)
+                     SELECT $1, server_alias, metric_id, {bucket} AS bucket,
+                            COUNT(*), MIN(numeric_value), AVG(numeric_value), MAX(numeric_value)
+                     FROM metric_samples
+                     WHERE numeric_value IS NOT NULL AND ($2::TEXT IS NULL OR timestamp >= $2)
+                     GROUP BY server_alias, metric_id, bucket
+                     ON CONFLICT (granularity, server_alias, metric_id, bucket_start) DO UPDATE SET
+                         sample_count = excluded.sample_count,
+                         min_value = excluded.min_value,
+                         avg_value = excluded.avg_value,
+                         max_value = excluded.max_value",
+                    bucket = granularity.bucket_sql(),
+                ),
+                &[&granularity.as_str(), &from],
+            )?;
+            Ok(written as usize)
+        })
+    }
+
+    fn latest_metric_rollup(&self, granularity: RollupGranularity) -> Result<Option<String>> {
+        self.with_conn(|conn| {
+            let row = conn.query_one(
+                "SELECT MAX(bucket_start) FROM metric_rollups WHERE granularity = $1",
+                &[&granularity.as_str()],
+            )?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn list_metric_rollups(
+        &self,
+        granularity: RollupGranularity,
+        metric_id: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        keyset: &Keyset<(String, i64)>,
+        limit: u32,
+    ) -> Result<Vec<MetricRollup>> {
+        self.with_conn(|conn| {
+            let (cmp, order) = keyset.sql(false);
+            let (key_start, key_id) = keyset.key().map(|(t, id)| (t.as_str(), *id)).unzip();
+            let rows = conn.query(
+                &format!(
+                    "SELECT id, server_alias, metric_id, bucket_start, sample_count, min_value, avg_value, max_value
+                     FROM metric_rollups
+                     WHERE granularity = $1
+                       AND ($2::TEXT IS NULL OR metric_id = $2)
+                       AND ($3::TEXT IS NULL OR bucket_start >= $3)
+                       AND ($4::TEXT IS NULL OR bucket_start <= $4)
+                       AND ($5::TEXT IS NULL OR (bucket_start, id) {cmp} ($5, $6::BIGINT))
+                     ORDER BY bucket_start {order}, id {order}
+                     LIMIT $7"
+                ),
+                &[&granularity.as_str(), &metric_id, &since, &until, &key_start, &key_id, &i64::from(limit)],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| MetricRollup {
+                    id: row.get(0),
+                    server_alias: row.get(1),
+                    metric_id: row.get(2),
+                    bucket_start: row.get(3),
+                    sample_count: row.get(4),
+                    min: row.get(5),
+                    avg: row.get(6),
+                    max: row.get(7),
+                })
+                .collect())
+        })
+    }
+
+    fn record_sel_entries(&self, entries: &[SelRecord]) -> Result<usize> {
+        self.with_conn(|conn| {
+            let mut tx = conn.transaction()?;
+            let mut added = 0;
+            for entry in entries {
+                added += tx.execute(
+                    "INSERT INTO sel_entries (server_alias, entry_id, created, severity, message, message_id, source)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7)
+                     ON CONFLICT DO NOTHING",
+                    &[
+                        &entry.server_alias,
+                        &entry.entry_id,
+                        &entry.created,
+                        &entry.severity,
+                        &entry.message,
+                        &entry.message_id,
+                        &entry.source,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(added as usize)
+        })
+    }
+
+    fn list_sel_entries(&self, server_alias: &str, keyset: &Keyset<(String, String)>, limit: u32) -> Result<Vec<SelRecord>> {
+        self.with_conn(|conn| {
+            let (cmp, order) = keyset.sql(true);
+            let (key_created, key_entry) = keyset.key().map(|(c, e)| (c.as_str(), e.as_str())).unzip();
+            let rows = conn.query(
+                &format!(
+                    "SELECT {SEL_COLUMNS}
+                     FROM sel_entries
+                     WHERE server_alias = $1
+                       AND ($2::TEXT IS NULL OR (created, entry_id) {cmp} ($2, $3::TEXT))
+                     ORDER BY created {order}, entry_id {order}
+                     LIMIT $4"
+                ),
+                &[&server_alias, &key_created, &key_entry, &i64::from(limit)],
+            )?;
+            Ok(rows.iter().map(sel_record_from_row).collect())
+        })
+    }
+
+    fn list_uncorrelated_sel_entries(&self, server_alias: &str, recorded_since: &str) -> Result<Vec<SelRecord>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {SEL_COLUMNS} FROM sel_entries
+                     WHERE server_alias = $1 AND caused_by_action IS NULL AND recorded_at >= $2
+                     ORDER BY created, entry_id"
+                ),
+                &[&server_alias, &recorded_since],
+            )?;
+            Ok(rows.iter().map(sel_record_from_row).collect())
+        })
+    }
+
+    fn set_sel_entry_cause(&self, entry: &SelRecord, cause: &EventCause) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE sel_entries SET caused_by_action = $5, caused_by_audit_id = $6, caused_by_operation_id = $7
+                 WHERE server_alias = $1 AND COALESCE(message_id, '') = COALESCE($2, '') AND created = $3 AND entry_id = $4",
+                &[
+                    &entry.server_alias,
+                    &entry.message_id,
+                    &entry.created,
+                    &entry.entry_id,
+                    &cause.action,
+                    &cause.audit_id,
+                    &cause.operation_id,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn list_operation_events(&self, operation_id: &str) -> Result<Vec<SelRecord>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!("SELECT {SEL_COLUMNS} FROM sel_entries WHERE caused_by_operation_id = $1 ORDER BY created, entry_id"),
+                &[&operation_id],
+            )?;
+            Ok(rows.iter().map(sel_record_from_row).collect())
+        })
+    }
+
+    fn count_sel_entries(&self, server_alias: &str) -> Result<u64> {
+        self.with_conn(|conn| {
+            let count: i64 = conn
+                .query_one("SELECT COUNT(*) FROM sel_entries WHERE server_alias = $1", &[&server_alias])?
+                .get(0);
+            Ok(count as u64)
+        })
+    }
+
+    fn purge_sel_entries_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM sel_entries
+                 WHERE recorded_at < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+
+    fn store_health_report(&self, server_alias: &str, report_date: &str, report: &serde_json::Value) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO health_reports (server_alias, report_date, report) VALUES ($1, $2, $3)
+                     ON CONFLICT (server_alias, report_date)
+                     DO UPDATE SET report = EXCLUDED.report, created_at = {}",
+                    NOW
+                ),
+                &[&server_alias, &report_date, &report.to_string()],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn list_health_reports(&self, server_alias: Option<&str>, report_date: Option<&str>, limit: u32) -> Result<Vec<HealthReport>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {} FROM health_reports
+                     WHERE ($1::TEXT IS NULL OR server_alias = $1) AND ($2::TEXT IS NULL OR report_date = $2)
+                     ORDER BY report_date DESC, server_alias, id DESC
+                     LIMIT $3",
+                    HEALTH_REPORT_COLUMNS
+                ),
+                &[&server_alias, &report_date, &i64::from(limit)],
+            )?;
+            Ok(rows.iter().map(health_report_from_row).collect())
+        })
+    }
+
+    fn latest_health_reports(&self) -> Result<Vec<HealthReport>> {
+        self.with_conn(|conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {} FROM health_reports r
+                     WHERE report_date = (SELECT MAX(report_date) FROM health_reports WHERE server_alias = r.server_alias)
+                     ORDER BY server_alias",
+                    HEALTH_REPORT_COLUMNS
+                ),
+                &[],
+            )?;
+            Ok(rows.iter().map(health_report_from_row).collect())
+        })
+    }
+
+    fn purge_health_reports_older_than(&self, days: u32) -> Result<usize> {
+        self.with_conn(|conn| {
+            let removed = conn.execute(
+                "DELETE FROM health_reports
+                 WHERE created_at < to_char(now() AT TIME ZONE 'UTC' - make_interval(days => $1), 'YYYY-MM-DD HH24:MI:SS')",
+                &[&(days as i32)],
+            )?;
+            Ok(removed as usize)
+        })
+    }
+}
+
+/// The synchronous `postgres` client drives its own Tokio runtime and
+/// panics when a connection is used or closed on a thread that is already
+/// running one, as every actix worker is. All connection handling therefore
+/// happens on a short-lived helper thread.
+fn off_runtime<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(f)
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Connect and, unless `read_only`, create any missing tables.
+fn open_pool(url: &str, read_only: bool) -> Result<PgPool> {
+    let mut config = ::postgres::Config::from_str(url)
+        .map_err(|e| DbError::Other(format!("Invalid DATABASE_URL: {}", e)))?;
+    if read_only {
+        config.options("-c default_transaction_read_only=on");
+    }
+    let pool = Pool::new(PostgresConnectionManager::new(config, NoTls))?;
+    if !read_only {
+        create_schema(&mut *pool.get()?)?;
+    }
+    Ok(pool)
+}
+
+/// Run `insert` with `base` as the slug, then `base-2`, `base-3`, ... for
+/// as long as the slug's UNIQUE constraint turns it away. Leaving the check
+/// to the constraint keeps two concurrent inserts from taking the same slug.
+fn insert_with_unique_slug<T>(
+    base: &str,
+    mut insert: impl FnMut(&str) -> std::result::Result<T, ::postgres::Error>,
+) -> std::result::Result<(T, String), ::postgres::Error> {
+    let mut candidate = base.to_string();
+    let mut suffix = 2;
+    loop {
+        match insert(&candidate) {
+            Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION)
+                && e.as_db_error().and_then(|db| db.constraint()).is_some_and(|name| name.contains("slug")) =>
+            {
+                candidate = format!("{}-{}", base, suffix);
+                suffix += 1;
+            }
+            result => return result.map(|value| (value, candidate)),
+        }
+    }
+}
+
+fn user_from_row(row: &Row) -> User {
+    User {
+        id: row.get(0),
+        username: row.get(1),
+        password_hash: row.get(2),
+        expires_at: row.get(3),
+        disabled_at: row.get(4),
+        session_generation: row.get(5),
+        auth_source: row.get(6),
+        email: row.get(7),
+        role: UserRole::parse(row.get(8)),
+    }
+}
+
+fn user_summary_from_row(row: &Row) -> UserSummary {
+    UserSummary {
+        id: row.get(0),
+        username: row.get(1),
+        created_at: row.get(2),
+        expires_at: row.get(3),
+        disabled_at: row.get(4),
+        auth_source: row.get(5),
+        role: UserRole::parse(row.get(6)),
+    }
+}
+
+fn power_cap_schedule_from_row(row: &Row) -> PowerCapSchedule {
+    PowerCapSchedule {
+        id: row.get(0),
+        server_alias: row.get(1),
+        cron_expr: row.get(2),
+        watts: row.get::<_, i64>(3) as u32,
+        duration_minutes: row.get::<_, i64>(4) as u32,
+        enabled: row.get(5),
+        created_at: row.get(6),
+    }
+}
+
+const TARIFF_WINDOW_COLUMNS: &str = "id, server_alias, group_id, days, start_time, end_time, watts, created_at";
+
+fn tariff_window_from_row(row: &Row) -> TariffWindow {
+    let days: String = row.get(3);
+    TariffWindow {
+        id: row.get(0),
+        server_alias: row.get(1),
+        group_id: row.get(2),
+        days: serde_json::from_str(&days).unwrap_or_default(),
+        start: row.get(4),
+        end: row.get(5),
+        watts: row.get::<_, i64>(6) as u32,
+        created_at: row.get(7),
+    }
+}
+
+const ONE_SHOT_COLUMNS: &str =
+    "id, server_alias, action, execute_at, created_by, created_at, status, executed_at, outcome, message";
+
+fn one_shot_schedule_from_row(row: &Row) -> OneShotSchedule {
+    OneShotSchedule {
+        id: row.get(0),
+        server_alias: row.get(1),
+        action: row.get(2),
+        execute_at: row.get(3),
+        created_by: row.get(4),
+        created_at: row.get(5),
+        status: row.get(6),
+        executed_at: row.get(7),
+        outcome: row.get(8),
+        message: row.get(9),
+    }
+}
+
+const FIRMWARE_UPDATE_SCHEDULE_COLUMNS: &str =
+    "id, server_alias, job_id, reboot_at, created_by, created_at, status, operation_id, executed_at, outcome, message";
+
+fn firmware_update_schedule_from_row(row: &Row) -> FirmwareUpdateSchedule {
+    FirmwareUpdateSchedule {
+        id: row.get(0),
+        server_alias: row.get(1),
+        job_id: row.get(2),
+        reboot_at: row.get(3),
+        created_by: row.get(4),
+        created_at: row.get(5),
+        status: row.get(6),
+        operation_id: row.get(7),
+        executed_at: row.get(8),
+        outcome: row.get(9),
+        message: row.get(10),
+    }
+}
+
+const BREAK_GLASS_COLUMNS: &str = "id, reason, issued_by, created_at, expires_at, used_at";
+
+fn break_glass_grant_from_row(row: &Row) -> BreakGlassGrant {
+    BreakGlassGrant {
+        id: row.get(0),
+        reason: row.get(1),
+        issued_by: row.get(2),
+        created_at: row.get(3),
+        expires_at: row.get(4),
+        used_at: row.get(5),
+    }
+}
+
+const SERVER_GRANT_FROM: &str = "g.id, g.user_id, u.username, g.server_alias, g.created_at, g.expires_at
+     FROM server_grants g JOIN users u ON u.id = g.user_id";
+
+fn server_grant_from_row(row: &Row) -> ServerGrant {
+    ServerGrant {
+        id: row.get(0),
+        user_id: row.get(1),
+        username: row.get(2),
+        server_alias: row.get(3),
+        created_at: row.get(4),
+        expires_at: row.get(5),
+    }
+}
+
+const HEALTH_REPORT_COLUMNS: &str = "id, server_alias, report_date, created_at, report";
+
+const SERVER_COLUMNS: &str = "id, name, slug, host, username, password, tags, location, default_power_cap_watts, hosts_this_app,
+     os_health_url, os_health_insecure, os_health_token";
+
+fn server_record_from_row(row: &Row) -> ServerRecord {
+    let tags: String = row.get(6);
+    ServerRecord {
+        id: row.get(0),
+        name: row.get(1),
+        slug: row.get(2),
+        host: row.get(3),
+        username: row.get(4),
+        password: row.get(5),
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        location: row.get(7),
+        default_power_cap_watts: row.get::<_, Option<i64>>(8).map(|watts| watts as u32),
+        hosts_this_app: row.get(9),
+        os_health_url: row.get(10),
+        os_health_insecure: row.get(11),
+        os_health_token: row.get(12),
+    }
+}
+
+fn health_report_from_row(row: &Row) -> HealthReport {
+    let report: String = row.get(4);
+    HealthReport {
+        id: row.get(0),
+        server_alias: row.get(1),
+        report_date: row.get(2),
+        created_at: row.get(3),
+        report: serde_json::from_str(&report).unwrap_or_default(),
+    }
+}
+
+fn power_event_from_row(row: &Row) -> PowerEvent {
+    let code: Option<String> = row.get(3);
+    PowerEvent {
+        id: row.get(0),
+        server_alias: row.get(1),
+        power_state: row.get(2),
+        reason: code.as_deref().map(PowerChangeReason::from_code),
+        observed_at: row.get(4),
+    }
+}
+
+const SHARE_COLUMNS: &str =
+    "id, name, servers, tag, passcode_hash, created_by, created_at, expires_at, last_accessed_at, access_count";
+
+fn share_from_row(row: &Row) -> Share {
+    let servers: String = row.get(2);
+    let passcode_hash: Option<String> = row.get(4);
+    Share {
+        id: row.get(0),
+        name: row.get(1),
+        servers: serde_json::from_str(&servers).unwrap_or_default(),
+        tag: row.get(3),
+        has_passcode: passcode_hash.is_some(),
+        passcode_hash,
+        created_by: row.get(5),
+        created_at: row.get(6),
+        expires_at: row.get(7),
+        last_accessed_at: row.get(8),
+        access_count: row.get(9),
+    }
+}
+
+const OPERATION_COLUMNS: &str = "id, kind, server_alias, status, stages, created_at, updated_at";
+
+fn operation_from_row(row: &Row) -> Operation {
+    let stages_json: String = row.get(4);
+    Operation {
+        id: row.get(0),
+        kind: row.get(1),
+        server_alias: row.get(2),
+        status: row.get(3),
+        stages: serde_json::from_str(&stages_json).unwrap_or_default(),
+        created_at: row.get(5),
+        updated_at: row.get(6),
+    }
+}
+
+const SEL_COLUMNS: &str = "server_alias, entry_id, created, severity, message, message_id, source, \
+                           caused_by_action, caused_by_audit_id, caused_by_operation_id";
+
+fn sel_record_from_row(row: &Row) -> SelRecord {
+    SelRecord {
+        server_alias: row.get(0),
+        entry_id: row.get(1),
+        created: row.get(2),
+        severity: row.get(3),
+        message: row.get(4),
+        message_id: row.get(5),
+        source: row.get(6),
+        caused_by: row.get::<_, Option<String>>(7).map(|action| EventCause {
+            action,
+            audit_id: row.get(8),
+            operation_id: row.get(9),
+        }),
+    }
+}
+
+const SERVER_GROUP_COLUMNS: &str = "id, name, members, power_budget_watts, created_at";
+
+fn server_group_from_row(row: &Row) -> ServerGroup {
+    let members: String = row.get(2);
+    ServerGroup {
+        id: row.get(0),
+        name: row.get(1),
+        members: serde_json::from_str(&members).unwrap_or_default(),
+        power_budget_watts: row.get::<_, Option<i64>>(3).map(|watts| watts as u32),
+        created_at: row.get(4),
+    }
+}
+
+fn api_token_from_row(row: &Row) -> ApiToken {
+    let scopes: String = row.get(3);
+    ApiToken {
+        id: row.get(0),
+        user_id: row.get(1),
+        name: row.get(2),
+        scopes: serde_json::from_str(&scopes).unwrap_or_default(),
+        created_at: row.get(4),
+        last_used_at: row.get(5),
+        requests_per_hour: row.get::<_, Option<i64>>(6).map(|v| v as u32),
+    }
+}
+
+/// Create any missing tables. The layout mirrors the SQLite schema after
+/// all of its migrations; columns added later belong in an
+/// `ALTER TABLE ... ADD COLUMN IF NOT EXISTS` below the table.
+fn create_schema(conn: &mut Client) -> Result<()> {
+    conn.batch_execute(&format!(
+        "CREATE TABLE IF NOT EXISTS users (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            slug TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL DEFAULT {now},
+            expires_at TEXT,
+            disabled_at TEXT,
+            session_generation BIGINT NOT NULL DEFAULT 0
+        );
+        ALTER TABLE users ADD COLUMN IF NOT EXISTS auth_source TEXT NOT NULL DEFAULT 'local';
+        ALTER TABLE users ADD COLUMN IF NOT EXISTS email TEXT;
+        -- Accounts from before roles keep full access; new ones default to readonly.
+        ALTER TABLE users ADD COLUMN IF NOT EXISTS role TEXT NOT NULL DEFAULT 'admin';
+        ALTER TABLE users ALTER COLUMN role SET DEFAULT 'readonly';
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email ON users (lower(email));
+
+        CREATE TABLE IF NOT EXISTS password_reset_tokens (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            token_hash TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL DEFAULT {now},
+            expires_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS server_grants (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            server_alias TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT {now},
+            expires_at TEXT,
+            UNIQUE (user_id, server_alias)
+        );
+
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            user_id BIGINT,
+            action TEXT NOT NULL,
+            server_name TEXT NOT NULL,
+            result TEXT NOT NULL,
+            error_message TEXT,
+            details TEXT,
+            created_at TEXT NOT NULL DEFAULT {now}
+        );
+
+        CREATE TABLE IF NOT EXISTS event_subscriptions (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            destination TEXT NOT NULL,
+            context TEXT NOT NULL UNIQUE,
+            subscription_uri TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT {now}
+        );
+
+        CREATE TABLE IF NOT EXISTS operations (
+            id TEXT PRIMARY KEY,
+            kind TEXT NOT NULL,
+            status TEXT NOT NULL,
+            stages TEXT NOT NULL DEFAULT '[]',
+            created_at TEXT NOT NULL DEFAULT {now},
+            updated_at TEXT NOT NULL DEFAULT {now}
+        );
+        ALTER TABLE operations ADD COLUMN IF NOT EXISTS server_alias TEXT;
+
+        CREATE TABLE IF NOT EXISTS user_preferences (
+            user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT {now},
+            PRIMARY KEY (user_id, key)
+        );
+
+        CREATE TABLE IF NOT EXISTS servers (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            slug TEXT NOT NULL UNIQUE,
+            host TEXT NOT NULL,
+            username TEXT NOT NULL,
+            password TEXT NOT NULL,
+            tags TEXT NOT NULL DEFAULT '[]',
+            location TEXT,
+            default_power_cap_watts BIGINT,
+            created_at TEXT NOT NULL DEFAULT {now}
+        );
+        ALTER TABLE servers ADD COLUMN IF NOT EXISTS hosts_this_app BOOLEAN NOT NULL DEFAULT FALSE;
+        ALTER TABLE servers ADD COLUMN IF NOT EXISTS os_health_url TEXT;
+        ALTER TABLE servers ADD COLUMN IF NOT EXISTS os_health_insecure BOOLEAN NOT NULL DEFAULT FALSE;
+        ALTER TABLE servers ADD COLUMN IF NOT EXISTS os_health_token TEXT;
+
+        CREATE TABLE IF NOT EXISTS power_cap_schedules (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            server_alias TEXT NOT NULL,
+            cron_expr TEXT NOT NULL,
+            watts BIGINT NOT NULL,
+            duration_minutes BIGINT NOT NULL,
+            enabled BOOLEAN NOT NULL DEFAULT TRUE,
+            created_at TEXT NOT NULL DEFAULT {now}
+        );
+
+        CREATE TABLE IF NOT EXISTS tariff_windows (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            server_alias TEXT,
+            group_id BIGINT,
+            days TEXT NOT NULL,
+            start_time TEXT NOT NULL,
+            end_time TEXT NOT NULL,
+            watts BIGINT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT {now}
+        );
+
+        CREATE TABLE IF NOT EXISTS tariff_caps (
+            server_alias TEXT PRIMARY KEY,
+            window_id BIGINT NOT NULL,
+            watts BIGINT NOT NULL,
+            previous_watts BIGINT,
+            applied_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS one_shot_schedules (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            server_alias TEXT NOT NULL,
+            action TEXT NOT NULL,
+            execute_at TEXT NOT NULL,
+            created_by BIGINT,
+            created_at TEXT NOT NULL DEFAULT {now},
+            status TEXT NOT NULL DEFAULT 'pending',
+            executed_at TEXT,
+            outcome TEXT,
+            message TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS firmware_update_schedules (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            server_alias TEXT NOT NULL,
+            job_id TEXT NOT NULL,
+            reboot_at TEXT NOT NULL,
+            created_by BIGINT,
+            created_at TEXT NOT NULL DEFAULT {now},
+            status TEXT NOT NULL DEFAULT 'pending',
+            operation_id TEXT,
+            executed_at TEXT,
+            outcome TEXT,
+            message TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS break_glass_grants (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            token_hash TEXT NOT NULL UNIQUE,
+            reason TEXT NOT NULL,
+            issued_by TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT {now},
+            expires_at TEXT NOT NULL,
+            used_at TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS shares (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            name TEXT NOT NULL,
+            servers TEXT NOT NULL DEFAULT '[]',
+            tag TEXT,
+            token_hash TEXT NOT NULL UNIQUE,
+            passcode_hash TEXT,
+            created_by BIGINT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT {now},
+            expires_at TEXT NOT NULL,
+            last_accessed_at TEXT,
+            access_count BIGINT NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS leases (
+            name TEXT PRIMARY KEY,
+            holder TEXT NOT NULL,
+            heartbeat_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS firmware_inventory (
+            server_alias TEXT NOT NULL,
+            component TEXT NOT NULL,
+            version TEXT NOT NULL,
+            collected_at TEXT NOT NULL DEFAULT {now},
+            UNIQUE (server_alias, component, version)
+        );
+
+        CREATE TABLE IF NOT EXISTS firmware_baselines (
+            component TEXT PRIMARY KEY,
+            version TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT {now}
+        );
+
+        CREATE TABLE IF NOT EXISTS compliance_profiles (
+            profile_name TEXT NOT NULL,
+            component TEXT NOT NULL,
+            minimum_version TEXT NOT NULL,
+            recommended_version TEXT,
+            updated_at TEXT NOT NULL DEFAULT {now},
+            PRIMARY KEY (profile_name, component)
+        );
+
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT {now}
+        );
+
+        CREATE TABLE IF NOT EXISTS api_tokens (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            user_id BIGINT NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT UNIQUE NOT NULL,
+            scopes TEXT NOT NULL DEFAULT '[]',
+            created_at TEXT NOT NULL DEFAULT {now},
+            last_used_at TEXT
+        );
+
+        ALTER TABLE api_tokens ADD COLUMN IF NOT EXISTS requests_per_hour BIGINT;
+
+        -- Requests made with a session are stored under token 0.
+        CREATE TABLE IF NOT EXISTS api_usage (
+            user_id BIGINT NOT NULL,
+            token_id BIGINT NOT NULL DEFAULT 0,
+            hour TEXT NOT NULL,
+            requests BIGINT NOT NULL,
+            PRIMARY KEY (user_id, token_id, hour)
+        );
+
+        CREATE TABLE IF NOT EXISTS server_groups (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            members TEXT NOT NULL DEFAULT '[]',
+            power_budget_watts BIGINT,
+            created_at TEXT NOT NULL DEFAULT {now}
+        );
+
+        CREATE TABLE IF NOT EXISTS group_apply_jobs (
+            id TEXT PRIMARY KEY,
+            group_name TEXT NOT NULL,
+            config TEXT NOT NULL,
+            status TEXT NOT NULL,
+            results TEXT NOT NULL DEFAULT '[]',
+            created_at TEXT NOT NULL DEFAULT {now},
+            updated_at TEXT NOT NULL DEFAULT {now}
+        );
+
+        CREATE TABLE IF NOT EXISTS power_samples (
+            server_alias TEXT NOT NULL,
+            watts BIGINT NOT NULL,
+            sampled_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_power_samples_sampled_at ON power_samples (sampled_at);
+
+        CREATE TABLE IF NOT EXISTS power_events (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            server_alias TEXT NOT NULL,
+            power_state TEXT NOT NULL,
+            reason_code TEXT,
+            observed_at TEXT NOT NULL DEFAULT {now}
+        );
+        CREATE INDEX IF NOT EXISTS idx_power_events_server ON power_events (server_alias, id);
+
+        CREATE TABLE IF NOT EXISTS ping_history (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            server_alias TEXT NOT NULL,
+            latency_ms DOUBLE PRECISION,
+            packet_loss_pct DOUBLE PRECISION NOT NULL,
+            recorded_at TEXT NOT NULL DEFAULT {now}
+        );
+        CREATE INDEX IF NOT EXISTS idx_ping_history_server ON ping_history (server_alias, id);
+
+        CREATE TABLE IF NOT EXISTS power_baselines (
+            server_alias TEXT NOT NULL,
+            hour_of_week INTEGER NOT NULL,
+            weeks TEXT NOT NULL,
+            median_watts DOUBLE PRECISION NOT NULL,
+            mad_watts DOUBLE PRECISION NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (server_alias, hour_of_week)
+        );
+
+        CREATE TABLE IF NOT EXISTS metric_samples (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            server_alias TEXT NOT NULL,
+            report_id TEXT,
+            metric_id TEXT NOT NULL,
+            metric_property TEXT,
+            value TEXT NOT NULL,
+            numeric_value DOUBLE PRECISION,
+            timestamp TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_metric_samples_metric_timestamp ON metric_samples (metric_id, timestamp);
+
+        CREATE TABLE IF NOT EXISTS metric_rollups (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            granularity TEXT NOT NULL,
+            server_alias TEXT NOT NULL,
+            metric_id TEXT NOT NULL,
+            bucket_start TEXT NOT NULL,
+            sample_count BIGINT NOT NULL,
+            min_value DOUBLE PRECISION NOT NULL,
+            avg_value DOUBLE PRECISION NOT NULL,
+            max_value DOUBLE PRECISION NOT NULL,
+            UNIQUE (granularity, server_alias, metric_id, bucket_start)
+        );
+        CREATE INDEX IF NOT EXISTS idx_metric_rollups_bucket ON metric_rollups (granularity, bucket_start);
+
+        CREATE TABLE IF NOT EXISTS sel_entries (
+            server_alias TEXT NOT NULL,
+            entry_id TEXT NOT NULL,
+            created TEXT NOT NULL,
+            severity TEXT NOT NULL,
+            message TEXT NOT NULL,
+            message_id TEXT,
+            recorded_at TEXT NOT NULL DEFAULT {now}
+        );
+        ALTER TABLE sel_entries ADD COLUMN IF NOT EXISTS source TEXT NOT NULL DEFAULT 'sel';
+        ALTER TABLE sel_entries ADD COLUMN IF NOT EXISTS caused_by_action TEXT;
+        ALTER TABLE sel_entries ADD COLUMN IF NOT EXISTS caused_by_audit_id BIGINT;
+        ALTER TABLE sel_entries ADD COLUMN IF NOT EXISTS caused_by_operation_id TEXT;
+        ALTER TABLE sel_entries DROP CONSTRAINT IF EXISTS sel_entries_pkey;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_sel_entries_key
+            ON sel_entries (server_alias, COALESCE(message_id, ''), created, entry_id);
+        CREATE INDEX IF NOT EXISTS idx_sel_entries_operation ON sel_entries (caused_by_operation_id);
+
+        CREATE TABLE IF NOT EXISTS health_reports (
+            id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            server_alias TEXT NOT NULL,
+            report_date TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT {now},
+            report TEXT NOT NULL,
+            UNIQUE (server_alias, report_date)
+        );",
+        now = NOW
+    ))?;
+    Ok(())
+}
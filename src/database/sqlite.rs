@@ -0,0 +1,2987 @@
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::OpenFlags;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use log::{info, warn};
+
+use super::{
+    ApiToken, ApiUsage, AuditEntry, AuditStatistics, BreakGlassGrant, ComplianceRequirement, EventCause, FirmwareInventoryEntry, GroupApplyJob, HealthReport, Keyset, MetricRollup, MetricSample,
+    FirmwareUpdateSchedule, NewServer, NewShare, OneShotSchedule, Operation, OperationStage, PingSample, PowerBaseline, PowerCapSchedule, PowerChangeReason, PowerEvent, PowerPeak, PowerSample, Result, RollupGranularity, SelRecord, ServerActionCount, ServerGroup,
+    ServerGrant, ServerRecord, Share, Store, TariffCap, TariffWindow, UsageKey, User, UserActionCount, UserRole, UserSummary,
+};
+use crate::validation::slugify;
+
+pub type DbPool = Pool<SqliteConnectionManager>;
+
+/// The default backend: a single SQLite file.
+pub struct SqliteStore {
+    // Swapped out when a standby instance is promoted to read-write.
+    pool: RwLock<DbPool>,
+    read_only: AtomicBool,
+    path: String,
+}
+
+impl SqliteStore {
+    pub fn open(db_path: &str, read_only: bool) -> Result<Self> {
+        let pool = if read_only {
+            let manager = SqliteConnectionManager::file(db_path).with_flags(
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            );
+            let pool = Pool::new(manager)?;
+            info!("Database opened read-only at {}", db_path);
+            pool
+        } else {
+            // Ensure parent directory exists
+            if let Some(parent) = std::path::Path::new(db_path).parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            }
+
+            // Create the database file if it doesn't exist by opening a connection first
+            {
+                let _conn = rusqlite::Connection::open(db_path)?;
+                info!("Database file created/verified at {}", db_path);
+            }
+
+            let pool = open_read_write_pool(db_path)?;
+            info!("Database initialized at {}", db_path);
+            pool
+        };
+
+        Ok(SqliteStore {
+            pool: RwLock::new(pool),
+            read_only: AtomicBool::new(read_only),
+            path: db_path.to_string(),
+        })
+    }
+
+    fn pool(&self) -> DbPool {
+        self.pool.read().unwrap().clone()
+    }
+}
+
+impl Store for SqliteStore {
+    fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    fn promote(&self) -> Result<()> {
+        let pool = open_read_write_pool(&self.path)?;
+        *self.pool.write().unwrap() = pool;
+        self.read_only.store(false, Ordering::SeqCst);
+        info!("Database re-opened read-write at {}", self.path);
+        Ok(())
+    }
+
+    fn has_users(&self) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM users",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    fn insert_user(&self, username: &str, password_hash: &str, expires_at: Option<&str>, role: UserRole) -> Result<i64> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        
+        insert_with_unique_slug(&slugify(username), |slug| {
+            conn.execute(
+                "INSERT INTO users (username, password_hash, slug, expires_at, role) VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![username, password_hash, slug, expires_at, role.as_str()],
+            )
+        })?;
+        
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn insert_external_user(&self, username: &str, auth_source: &str, role: UserRole) -> Result<i64> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        insert_with_unique_slug(&slugify(username), |slug| {
+            conn.execute(
+                "INSERT INTO users (username, password_hash, slug, auth_source, role) VALUES (?1, '', ?2, ?3, ?4)",
+                rusqlite::params![username, slug, auth_source, role.as_str()],
+            )
+        })?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn set_user_role(&self, user_id: i64, role: UserRole) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let updated = conn.execute("UPDATE users SET role = ?2 WHERE id = ?1", rusqlite::params![user_id, role.as_str()])?;
+        Ok(updated > 0)
+    }
+
+    fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        
+        let mut stmt = conn.prepare(
+            "SELECT id, username, password_hash, expires_at, disabled_at, session_generation, auth_source, email, role
+             FROM users WHERE username = ?1"
+        )?;
+        
+        match stmt.query_row([username], user_from_row) {
+            Ok(user) => Ok(Some(user)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        
+        let mut stmt = conn.prepare(
+            "SELECT id, username, password_hash, expires_at, disabled_at, session_generation, auth_source, email, role
+             FROM users WHERE id = ?1"
+        )?;
+        
+        let user = stmt.query_row([user_id], user_from_row);
+
+        match user {
+            Ok(user) => Ok(Some(user)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set_user_email(&self, user_id: i64, email: Option<&str>) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let updated = conn.execute("UPDATE users SET email = ?2 WHERE id = ?1", rusqlite::params![user_id, email])?;
+        Ok(updated > 0)
+    }
+
+    fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let user = conn.query_row(
+            "SELECT id, username, password_hash, expires_at, disabled_at, session_generation, auth_source, email, role
+             FROM users WHERE lower(email) = lower(?1)",
+            [email],
+            user_from_row,
+        );
+        match user {
+            Ok(user) => Ok(Some(user)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn record_audit(
+        &self,
+        user_id: Option<i64>,
+        action: &str,
+        server_name: &str,
+        result: &str,
+        error_message: Option<&str>,
+        details: Option<&str>,
+    ) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO audit_log (user_id, action, server_name, result, error_message, details)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![user_id, action, server_name, result, error_message, details],
+        )?;
+
+        Ok(())
+    }
+
+    fn create_operation(&self, kind: &str, server_alias: Option<&str>) -> Result<String> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO operations (id, kind, server_alias, status) VALUES (?1, ?2, ?3, 'running')",
+            rusqlite::params![id, kind, server_alias],
+        )?;
+
+        Ok(id)
+    }
+
+    fn latest_operation_active_at(&self, server_alias: &str, at: &str, since: &str) -> Result<Option<Operation>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let operation = conn.query_row(
+            &format!(
+                "SELECT {} FROM operations WHERE server_alias = ?1 AND created_at <= ?2 AND (status = 'running' OR updated_at >= ?3)
+                 ORDER BY created_at DESC, rowid DESC LIMIT 1",
+                OPERATION_COLUMNS
+            ),
+            rusqlite::params![server_alias, at, since],
+            operation_from_row,
+        );
+        match operation {
+            Ok(operation) => Ok(Some(operation)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn update_operation(&self, id: &str, stages: &[OperationStage], status: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let stages_json = serde_json::to_string(stages)?;
+        conn.execute(
+            "UPDATE operations SET stages = ?1, status = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+            [&stages_json, status, id],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_operation(&self, id: &str) -> Result<Option<Operation>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let operation = conn.query_row(
+            &format!("SELECT {} FROM operations WHERE id = ?1", OPERATION_COLUMNS),
+            [id],
+            operation_from_row,
+        );
+
+        match operation {
+            Ok(operation) => Ok(Some(operation)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list_server_operations(&self, server_alias: &str, kind: &str, limit: u32) -> Result<Vec<Operation>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM operations WHERE server_alias = ?1 AND kind = ?2
+             ORDER BY created_at DESC, rowid DESC LIMIT ?3",
+            OPERATION_COLUMNS
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![server_alias, kind, limit], operation_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn list_running_operations(&self, kind: &str) -> Result<Vec<Operation>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM operations WHERE kind = ?1 AND status = 'running' ORDER BY created_at, rowid",
+            OPERATION_COLUMNS
+        ))?;
+        let rows = stmt.query_map([kind], operation_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn create_event_subscription(&self, destination: &str, context: &str, subscription_uri: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO event_subscriptions (destination, context, subscription_uri) VALUES (?1, ?2, ?3)",
+            [destination, context, subscription_uri],
+        )?;
+
+        Ok(())
+    }
+
+    fn is_known_event_context(&self, context: &str) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM event_subscriptions WHERE context = ?1",
+            [context],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    fn heartbeat_lease(&self, holder: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO leases (name, holder, heartbeat_at) VALUES ('primary', ?1, CURRENT_TIMESTAMP)
+             ON CONFLICT(name) DO UPDATE SET holder = excluded.holder, heartbeat_at = excluded.heartbeat_at",
+            [holder],
+        )?;
+        Ok(())
+    }
+
+    fn primary_lease_age_secs(&self) -> Result<Option<i64>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let age = conn.query_row(
+            "SELECT CAST(strftime('%s', 'now') AS INTEGER) - CAST(strftime('%s', heartbeat_at) AS INTEGER)
+             FROM leases WHERE name = 'primary'",
+            [],
+            |row| row.get(0),
+        );
+
+        match age {
+            Ok(age) => Ok(Some(age)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get_user_preferences(&self, user_id: i64) -> Result<Vec<(String, String)>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT key, value FROM user_preferences WHERE user_id = ?1 ORDER BY key"
+        )?;
+        let rows = stmt.query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn replace_user_preferences(&self, user_id: i64, preferences: &[(String, String)]) -> Result<()> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM user_preferences WHERE user_id = ?1", [user_id])?;
+        for (key, value) in preferences {
+            tx.execute(
+                "INSERT INTO user_preferences (user_id, key, value) VALUES (?1, ?2, ?3)",
+                rusqlite::params![user_id, key, value],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    fn create_server(&self, server: &NewServer) -> Result<ServerRecord> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tags = serde_json::to_string(&server.tags)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let (_, slug) = insert_with_unique_slug(&slugify(&server.name), |slug| {
+            conn.execute(
+                "INSERT INTO servers (name, slug, host, username, password, tags, location, default_power_cap_watts, hosts_this_app,
+                                      os_health_url, os_health_insecure, os_health_token)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                rusqlite::params![
+                    server.name,
+                    slug,
+                    server.host,
+                    server.username,
+                    server.password.expose(),
+                    tags,
+                    server.location,
+                    server.default_power_cap_watts,
+                    server.hosts_this_app,
+                    server.os_health_url,
+                    server.os_health_insecure,
+                    server.os_health_token.as_ref().map(|token| token.expose())
+                ],
+            )
+        })?;
+
+        info!("Server registered: {} ({})", server.name, server.host);
+        Ok(ServerRecord {
+            id: conn.last_insert_rowid(),
+            name: server.name.clone(),
+            slug,
+            host: server.host.clone(),
+            username: server.username.clone(),
+            password: server.password.expose().to_string(),
+            tags: server.tags.clone(),
+            location: server.location.clone(),
+            default_power_cap_watts: server.default_power_cap_watts,
+            hosts_this_app: server.hosts_this_app,
+            os_health_url: server.os_health_url.clone(),
+            os_health_insecure: server.os_health_insecure,
+            os_health_token: server.os_health_token.as_ref().map(|token| token.expose().to_string()),
+        })
+    }
+
+    fn list_servers(&self) -> Result<Vec<ServerRecord>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM servers ORDER BY name", SERVER_COLUMNS))?;
+        let rows = stmt.query_map([], server_record_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn update_server_credentials(&self, id: i64, username: Option<&str>, password: Option<&str>) -> Result<Option<ServerRecord>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "UPDATE servers SET username = COALESCE(?2, username), password = COALESCE(?3, password) WHERE id = ?1",
+            rusqlite::params![id, username, password],
+        )?;
+        let server = conn.query_row(
+            &format!("SELECT {} FROM servers WHERE id = ?1", SERVER_COLUMNS),
+            [id],
+            server_record_from_row,
+        );
+
+        match server {
+            Ok(server) => Ok(Some(server)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn update_server_os_health(
+        &self,
+        id: i64,
+        url: Option<&str>,
+        insecure: bool,
+        token: Option<&str>,
+    ) -> Result<Option<ServerRecord>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "UPDATE servers SET os_health_url = ?2, os_health_insecure = ?3, os_health_token = ?4 WHERE id = ?1",
+            rusqlite::params![id, url, insecure, token],
+        )?;
+        let server = conn.query_row(
+            &format!("SELECT {} FROM servers WHERE id = ?1", SERVER_COLUMNS),
+            [id],
+            server_record_from_row,
+        );
+
+        match server {
+            Ok(server) => Ok(Some(server)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn delete_server(&self, id: i64) -> Result<bool> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        let slug: String = match tx.query_row("SELECT slug FROM servers WHERE id = ?1", [id], |row| row.get(0)) {
+            Ok(slug) => slug,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        tx.execute("DELETE FROM power_cap_schedules WHERE server_alias = ?1", [&slug])?;
+        tx.execute("DELETE FROM tariff_windows WHERE server_alias = ?1", [&slug])?;
+        tx.execute("DELETE FROM tariff_caps WHERE server_alias = ?1", [&slug])?;
+        tx.execute("DELETE FROM one_shot_schedules WHERE server_alias = ?1 AND status = 'pending'", [&slug])?;
+        tx.execute("DELETE FROM firmware_update_schedules WHERE server_alias = ?1 AND status = 'pending'", [&slug])?;
+        tx.execute("DELETE FROM power_baselines WHERE server_alias = ?1", [&slug])?;
+        tx.execute("DELETE FROM servers WHERE id = ?1", [id])?;
+        tx.commit()?;
+
+        info!("Server deleted: {}", slug);
+        Ok(true)
+    }
+
+    fn create_power_cap_schedule(
+        &self,
+        server_alias: &str,
+        cron_expr: &str,
+        watts: u32,
+        duration_minutes: u32,
+        enabled: bool,
+    ) -> Result<PowerCapSchedule> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO power_cap_schedules (server_alias, cron_expr, watts, duration_minutes, enabled)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![server_alias, cron_expr, watts, duration_minutes, enabled],
+        )?;
+
+        let id = conn.last_insert_rowid();
+        Ok(conn.query_row(
+            "SELECT id, server_alias, cron_expr, watts, duration_minutes, enabled, created_at
+             FROM power_cap_schedules WHERE id = ?1",
+            [id],
+            power_cap_schedule_from_row,
+        )?)
+    }
+
+    fn list_power_cap_schedules(&self, server_alias: Option<&str>) -> Result<Vec<PowerCapSchedule>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, server_alias, cron_expr, watts, duration_minutes, enabled, created_at
+             FROM power_cap_schedules
+             WHERE ?1 IS NULL OR server_alias = ?1
+             ORDER BY id"
+        )?;
+        let rows = stmt.query_map([server_alias], power_cap_schedule_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn delete_power_cap_schedule(&self, server_alias: &str, id: i64) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let deleted = conn.execute(
+            "DELETE FROM power_cap_schedules WHERE id = ?1 AND server_alias = ?2",
+            rusqlite::params![id, server_alias],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn create_tariff_window(
+        &self,
+        server_alias: Option<&str>,
+        group_id: Option<i64>,
+        days: &[String],
+        start: &str,
+        end: &str,
+        watts: u32,
+    ) -> Result<TariffWindow> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let days = serde_json::to_string(days).unwrap_or_else(|_| "[]".to_string());
+        conn.execute(
+            "INSERT INTO tariff_windows (server_alias, group_id, days, start_time, end_time, watts)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![server_alias, group_id, days, start, end, watts],
+        )?;
+
+        let id = conn.last_insert_rowid();
+        Ok(conn.query_row(
+            &format!("SELECT {} FROM tariff_windows WHERE id = ?1", TARIFF_WINDOW_COLUMNS),
+            [id],
+            tariff_window_from_row,
+        )?)
+    }
+
+    fn list_tariff_windows(&self) -> Result<Vec<TariffWindow>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM tariff_windows ORDER BY id", TARIFF_WINDOW_COLUMNS))?;
+        let rows = stmt.query_map([], tariff_window_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn delete_tariff_window(&self, id: i64) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute("DELETE FROM tariff_windows WHERE id = ?1", [id])? > 0)
+    }
+
+    fn list_tariff_caps(&self) -> Result<Vec<TariffCap>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT server_alias, window_id, watts, previous_watts, applied_at FROM tariff_caps ORDER BY server_alias",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(TariffCap {
+                server_alias: row.get(0)?,
+                window_id: row.get(1)?,
+                watts: row.get(2)?,
+                previous_watts: row.get(3)?,
+                applied_at: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn set_tariff_cap(&self, cap: &TariffCap) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO tariff_caps (server_alias, window_id, watts, previous_watts, applied_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(server_alias) DO UPDATE SET
+                window_id = excluded.window_id,
+                watts = excluded.watts,
+                previous_watts = excluded.previous_watts,
+                applied_at = excluded.applied_at",
+            rusqlite::params![cap.server_alias, cap.window_id, cap.watts, cap.previous_watts, cap.applied_at],
+        )?;
+        Ok(())
+    }
+
+    fn delete_tariff_cap(&self, server_alias: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute("DELETE FROM tariff_caps WHERE server_alias = ?1", [server_alias])?;
+        Ok(())
+    }
+
+    fn create_one_shot_schedule(
+        &self,
+        server_alias: &str,
+        action: &str,
+        execute_at: &str,
+        created_by: Option<i64>,
+    ) -> Result<OneShotSchedule> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.query_row(
+            &format!(
+                "INSERT INTO one_shot_schedules (server_alias, action, execute_at, created_by)
+                 VALUES (?1, ?2, ?3, ?4) RETURNING {}",
+                ONE_SHOT_COLUMNS
+            ),
+            rusqlite::params![server_alias, action, execute_at, created_by],
+            one_shot_schedule_from_row,
+        )?)
+    }
+
+    fn list_one_shot_schedules(&self) -> Result<Vec<OneShotSchedule>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM one_shot_schedules ORDER BY execute_at, id",
+            ONE_SHOT_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], one_shot_schedule_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn delete_one_shot_schedule(&self, id: i64) -> Result<Option<OneShotSchedule>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let deleted = conn.query_row(
+            &format!(
+                "DELETE FROM one_shot_schedules WHERE id = ?1 AND status = 'pending' RETURNING {}",
+                ONE_SHOT_COLUMNS
+            ),
+            [id],
+            one_shot_schedule_from_row,
+        );
+        match deleted {
+            Ok(schedule) => Ok(Some(schedule)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn claim_due_one_shot_schedules(&self) -> Result<Vec<OneShotSchedule>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "UPDATE one_shot_schedules SET status = 'running'
+             WHERE status = 'pending' AND execute_at <= CURRENT_TIMESTAMP
+             RETURNING {}",
+            ONE_SHOT_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], one_shot_schedule_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn finish_one_shot_schedule(&self, id: i64, outcome: &str, message: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "UPDATE one_shot_schedules
+             SET status = 'executed', executed_at = CURRENT_TIMESTAMP, outcome = ?1, message = ?2
+             WHERE id = ?3",
+            rusqlite::params![outcome, message, id],
+        )?;
+        Ok(())
+    }
+
+    fn create_firmware_update_schedule(
+        &self,
+        server_alias: &str,
+        job_id: &str,
+        reboot_at: &str,
+        created_by: Option<i64>,
+    ) -> Result<FirmwareUpdateSchedule> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.query_row(
+            &format!(
+                "INSERT INTO firmware_update_schedules (server_alias, job_id, reboot_at, created_by)
+                 VALUES (?1, ?2, ?3, ?4) RETURNING {}",
+                FIRMWARE_UPDATE_SCHEDULE_COLUMNS
+            ),
+            rusqlite::params![server_alias, job_id, reboot_at, created_by],
+            firmware_update_schedule_from_row,
+        )?)
+    }
+
+    fn list_firmware_update_schedules(&self) -> Result<Vec<FirmwareUpdateSchedule>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM firmware_update_schedules ORDER BY reboot_at, id",
+            FIRMWARE_UPDATE_SCHEDULE_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], firmware_update_schedule_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn claim_due_firmware_update_schedules(&self) -> Result<Vec<FirmwareUpdateSchedule>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "UPDATE firmware_update_schedules SET status = 'running'
+             WHERE status = 'pending' AND reboot_at <= CURRENT_TIMESTAMP
+             RETURNING {}",
+            FIRMWARE_UPDATE_SCHEDULE_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], firmware_update_schedule_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn set_firmware_update_operation(&self, id: i64, operation_id: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "UPDATE firmware_update_schedules SET operation_id = ?1 WHERE id = ?2",
+            rusqlite::params![operation_id, id],
+        )?;
+        Ok(())
+    }
+
+    fn finish_firmware_update_schedule(&self, id: i64, outcome: &str, message: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "UPDATE firmware_update_schedules
+             SET status = 'executed', executed_at = CURRENT_TIMESTAMP, outcome = ?1, message = ?2
+             WHERE id = ?3",
+            rusqlite::params![outcome, message, id],
+        )?;
+        Ok(())
+    }
+
+    fn audit_statistics(&self, since: Option<&str>, until: Option<&str>) -> Result<AuditStatistics> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        const RANGE: &str = "(?1 IS NULL OR a.created_at >= ?1) AND (?2 IS NULL OR a.created_at < ?2)";
+        let params = rusqlite::params![since, until];
+
+        let (total_actions, failures): (u64, u64) = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(a.result = 'failure'), 0) FROM audit_log a WHERE {}",
+                RANGE
+            ),
+            params,
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT a.action, COUNT(*) FROM audit_log a WHERE {} GROUP BY a.action ORDER BY a.action",
+            RANGE
+        ))?;
+        let actions_by_type = stmt
+            .query_map(params, |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT COALESCE(u.username, 'system'), COUNT(*) FROM audit_log a
+             LEFT JOIN users u ON u.id = a.user_id
+             WHERE {} GROUP BY 1 ORDER BY 2 DESC, 1",
+            RANGE
+        ))?;
+        let actions_by_user = stmt
+            .query_map(params, |row| Ok(UserActionCount { user: row.get(0)?, count: row.get(1)? }))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT a.server_name, COUNT(*) FROM audit_log a WHERE {} GROUP BY 1 ORDER BY 2 DESC, 1",
+            RANGE
+        ))?;
+        let actions_by_server = stmt
+            .query_map(params, |row| Ok(ServerActionCount { server: row.get(0)?, count: row.get(1)? }))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let failure_rate = if total_actions == 0 {
+            0.0
+        } else {
+            failures as f64 / total_actions as f64
+        };
+
+        Ok(AuditStatistics {
+            total_actions,
+            actions_by_type,
+            actions_by_user,
+            actions_by_server,
+            failure_rate,
+        })
+    }
+
+    fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+        scopes: &[String],
+        requests_per_hour: Option<u32>,
+    ) -> Result<ApiToken> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let scopes_json = serde_json::to_string(scopes)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        conn.execute(
+            "INSERT INTO api_tokens (user_id, name, token_hash, scopes, requests_per_hour) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![user_id, name, token_hash, scopes_json, requests_per_hour],
+        )?;
+
+        let id = conn.last_insert_rowid();
+        Ok(conn.query_row(
+            "SELECT id, user_id, name, scopes, created_at, last_used_at, requests_per_hour FROM api_tokens WHERE id = ?1",
+            [id],
+            api_token_from_row,
+        )?)
+    }
+
+    fn list_api_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, user_id, name, scopes, created_at, last_used_at, requests_per_hour
+             FROM api_tokens WHERE user_id = ?1 ORDER BY id"
+        )?;
+        let rows = stmt.query_map([user_id], api_token_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn delete_api_token(&self, user_id: i64, id: i64) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let deleted = conn.execute(
+            "DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2",
+            [id, user_id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn find_api_token(&self, token_hash: &str) -> Result<Option<ApiToken>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let token = conn.query_row(
+            "SELECT id, user_id, name, scopes, created_at, last_used_at, requests_per_hour FROM api_tokens WHERE token_hash = ?1",
+            [token_hash],
+            api_token_from_row,
+        );
+        match token {
+            Ok(token) => {
+                if let Err(e) = conn.execute(
+                    "UPDATE api_tokens SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                    [token.id],
+                ) {
+                    warn!("Failed to record use of API token {}: {}", token.id, e);
+                }
+                Ok(Some(token))
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set_api_token_quota(&self, id: i64, requests_per_hour: Option<u32>) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let updated = conn.execute(
+            "UPDATE api_tokens SET requests_per_hour = ?1 WHERE id = ?2",
+            rusqlite::params![requests_per_hour, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn record_api_usage(&self, counts: &[(UsageKey, u64)]) -> Result<()> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        for (key, requests) in counts {
+            tx.execute(
+                "INSERT INTO api_usage (user_id, token_id, hour, requests) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (user_id, token_id, hour) DO UPDATE SET requests = requests + excluded.requests",
+                rusqlite::params![key.user_id, key.token_id.unwrap_or(0), key.hour, *requests as i64],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    fn list_api_usage(&self, user_id: Option<i64>, since: &str) -> Result<Vec<ApiUsage>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT a.user_id, u.username, a.token_id, t.name, a.hour, a.requests
+             FROM api_usage a
+             LEFT JOIN users u ON u.id = a.user_id
+             LEFT JOIN api_tokens t ON t.id = a.token_id
+             WHERE (?1 IS NULL OR a.user_id = ?1) AND a.hour >= ?2
+             ORDER BY a.hour DESC, a.requests DESC",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![user_id, since], |row| {
+            let token_id: i64 = row.get(2)?;
+            Ok(ApiUsage {
+                user_id: row.get(0)?,
+                username: row.get(1)?,
+                token_id: (token_id != 0).then_some(token_id),
+                token_name: row.get(3)?,
+                hour: row.get(4)?,
+                requests: row.get::<_, i64>(5)? as u64,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn api_token_usage(&self, token_id: i64, hour: &str) -> Result<u64> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let requests: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(requests), 0) FROM api_usage WHERE token_id = ?1 AND hour = ?2",
+            rusqlite::params![token_id, hour],
+            |row| row.get(0),
+        )?;
+        Ok(requests as u64)
+    }
+
+    fn purge_api_usage_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM api_usage WHERE hour < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    // SQLite allows a single writer and readers only see committed rows, so
+    // an entry can never become visible after one with a higher id.
+    fn list_audit_page(&self, keyset: &Keyset<i64>, limit: u32, descending: bool) -> Result<Vec<AuditEntry>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let (cmp, order) = keyset.sql(descending);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT a.id, a.user_id, u.username, a.action, a.server_name, a.result, a.error_message,
+                    a.details, a.created_at
+             FROM audit_log a LEFT JOIN users u ON u.id = a.user_id
+             WHERE (?1 IS NULL OR a.id {cmp} ?1) ORDER BY a.id {order} LIMIT ?2"
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![keyset.key(), limit], |row| {
+            let details: Option<String> = row.get(7)?;
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                user_id: row.get(1)?,
+                username: row.get(2)?,
+                action: row.get(3)?,
+                server_name: row.get(4)?,
+                result: row.get(5)?,
+                error_message: row.get(6)?,
+                details: details.and_then(|d| serde_json::from_str(&d).ok()),
+                created_at: row.get(8)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn audit_count_estimate(&self) -> Result<u64> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let span: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(id) - MIN(id) + 1, 0) FROM audit_log",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(span as u64)
+    }
+
+    fn list_users(&self, include_expired: bool) -> Result<Vec<UserSummary>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, username, created_at, expires_at, disabled_at, auth_source, role FROM users
+             WHERE ?1 OR (disabled_at IS NULL AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP))
+             ORDER BY username"
+        )?;
+        let rows = stmt.query_map([include_expired], user_summary_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn list_user_expirations(&self, from: &str, until: &str) -> Result<Vec<UserSummary>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, username, created_at, expires_at, disabled_at, auth_source, role FROM users
+             WHERE expires_at >= ?1 AND expires_at <= ?2
+             ORDER BY expires_at"
+        )?;
+        let rows = stmt.query_map([from, until], user_summary_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn set_user_expiry(&self, user_id: i64, expires_at: Option<&str>) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let updated = conn.execute(
+            "UPDATE users SET expires_at = ?1,
+                disabled_at = CASE WHEN ?1 IS NULL OR ?1 > CURRENT_TIMESTAMP THEN NULL ELSE disabled_at END
+             WHERE id = ?2",
+            rusqlite::params![expires_at, user_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn disable_expired_users(&self) -> Result<Vec<UserSummary>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "UPDATE users SET disabled_at = CURRENT_TIMESTAMP, session_generation = session_generation + 1
+             WHERE disabled_at IS NULL AND expires_at <= CURRENT_TIMESTAMP
+             RETURNING id, username, created_at, expires_at, disabled_at, auth_source, role"
+        )?;
+        let rows = stmt.query_map([], user_summary_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn set_server_grant(&self, user_id: i64, server_alias: &str, expires_at: Option<&str>) -> Result<ServerGrant> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO server_grants (user_id, server_alias, expires_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (user_id, server_alias) DO UPDATE SET expires_at = excluded.expires_at",
+            rusqlite::params![user_id, server_alias, expires_at],
+        )?;
+        Ok(conn.query_row(
+            &format!("SELECT {} WHERE g.user_id = ?1 AND g.server_alias = ?2", SERVER_GRANT_FROM),
+            rusqlite::params![user_id, server_alias],
+            server_grant_from_row,
+        )?)
+    }
+
+    fn get_server_grant(&self, user_id: i64, server_alias: &str) -> Result<Option<ServerGrant>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let grant = conn.query_row(
+            &format!("SELECT {} WHERE g.user_id = ?1 AND g.server_alias = ?2", SERVER_GRANT_FROM),
+            rusqlite::params![user_id, server_alias],
+            server_grant_from_row,
+        );
+        match grant {
+            Ok(grant) => Ok(Some(grant)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list_server_grants(&self, user_id: i64, include_expired: bool) -> Result<Vec<ServerGrant>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} WHERE g.user_id = ?1 AND (?2 OR g.expires_at IS NULL OR g.expires_at > CURRENT_TIMESTAMP)
+             ORDER BY g.server_alias",
+            SERVER_GRANT_FROM
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![user_id, include_expired], server_grant_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn list_server_grant_expirations(&self, from: &str, until: &str) -> Result<Vec<ServerGrant>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} WHERE g.expires_at >= ?1 AND g.expires_at <= ?2 ORDER BY g.expires_at",
+            SERVER_GRANT_FROM
+        ))?;
+        let rows = stmt.query_map([from, until], server_grant_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn delete_server_grant(&self, user_id: i64, server_alias: &str) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let deleted = conn.execute(
+            "DELETE FROM server_grants WHERE user_id = ?1 AND server_alias = ?2",
+            rusqlite::params![user_id, server_alias],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn create_break_glass_grant(
+        &self,
+        token_hash: &str,
+        reason: &str,
+        issued_by: &str,
+        expires_at: &str,
+    ) -> Result<BreakGlassGrant> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.query_row(
+            &format!(
+                "INSERT INTO break_glass_grants (token_hash, reason, issued_by, expires_at)
+                 VALUES (?1, ?2, ?3, ?4) RETURNING {}",
+                BREAK_GLASS_COLUMNS
+            ),
+            rusqlite::params![token_hash, reason, issued_by, expires_at],
+            break_glass_grant_from_row,
+        )?)
+    }
+
+    fn claim_break_glass_grant(&self, token_hash: &str) -> Result<Option<BreakGlassGrant>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let claimed = conn.query_row(
+            &format!(
+                "UPDATE break_glass_grants SET used_at = CURRENT_TIMESTAMP
+                 WHERE token_hash = ?1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+                 RETURNING {}",
+                BREAK_GLASS_COLUMNS
+            ),
+            [token_hash],
+            break_glass_grant_from_row,
+        );
+        match claimed {
+            Ok(grant) => Ok(Some(grant)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list_active_break_glass_grants(&self) -> Result<Vec<BreakGlassGrant>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM break_glass_grants WHERE expires_at > CURRENT_TIMESTAMP ORDER BY id DESC",
+            BREAK_GLASS_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], break_glass_grant_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn create_password_reset_token(&self, user_id: i64, token_hash: &str, expires_at: &str) -> Result<()> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM password_reset_tokens WHERE user_id = ?1 OR expires_at <= CURRENT_TIMESTAMP",
+            [user_id],
+        )?;
+        tx.execute(
+            "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES (?1, ?2, ?3)",
+            rusqlite::params![user_id, token_hash, expires_at],
+        )?;
+        Ok(tx.commit()?)
+    }
+
+    fn reset_password_with_token(&self, token_hash: &str, password_hash: &str) -> Result<Option<i64>> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        let claimed = tx.query_row(
+            "DELETE FROM password_reset_tokens WHERE token_hash = ?1 AND expires_at > CURRENT_TIMESTAMP
+             RETURNING user_id",
+            [token_hash],
+            |row| row.get::<_, i64>(0),
+        );
+        let user_id = match claimed {
+            Ok(user_id) => user_id,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let updated = tx.execute(
+            "UPDATE users SET password_hash = ?2, session_generation = session_generation + 1
+             WHERE id = ?1 AND auth_source = 'local'",
+            rusqlite::params![user_id, password_hash],
+        )?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        tx.commit()?;
+        Ok(Some(user_id))
+    }
+
+    fn create_share(&self, share: &NewShare) -> Result<Share> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let servers_json = serde_json::to_string(share.servers)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        Ok(conn.query_row(
+            &format!(
+                "INSERT INTO shares (name, servers, tag, token_hash, passcode_hash, created_by, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) RETURNING {}",
+                SHARE_COLUMNS
+            ),
+            rusqlite::params![
+                share.name,
+                servers_json,
+                share.tag,
+                share.token_hash,
+                share.passcode_hash,
+                share.created_by,
+                share.expires_at
+            ],
+            share_from_row,
+        )?)
+    }
+
+    fn list_shares(&self) -> Result<Vec<Share>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM shares ORDER BY id DESC", SHARE_COLUMNS))?;
+        let rows = stmt.query_map([], share_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn delete_share(&self, id: i64) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute("DELETE FROM shares WHERE id = ?1", [id])? > 0)
+    }
+
+    fn find_share(&self, token_hash: &str) -> Result<Option<Share>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let share = conn.query_row(
+            &format!(
+                "SELECT {} FROM shares WHERE token_hash = ?1 AND expires_at > CURRENT_TIMESTAMP",
+                SHARE_COLUMNS
+            ),
+            [token_hash],
+            share_from_row,
+        );
+        match share {
+            Ok(share) => {
+                if let Err(e) = conn.execute(
+                    "UPDATE shares SET last_accessed_at = CURRENT_TIMESTAMP, access_count = access_count + 1 WHERE id = ?1",
+                    [share.id],
+                ) {
+                    warn!("Failed to record access to share {}: {}", share.id, e);
+                }
+                Ok(Some(share))
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        match conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0)) {
+            Ok(value) => Ok(Some(value)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put_setting(&self, key: &str, value: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    fn purge_audit_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM audit_log WHERE created_at < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    fn latest_successful_audit(&self, server_name: &str, since: &str, until: &str) -> Result<Option<(i64, String)>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let entry = conn.query_row(
+            "SELECT id, action FROM audit_log
+             WHERE server_name = ?1 AND result = 'success' AND created_at >= ?2 AND created_at <= ?3
+             ORDER BY created_at DESC, id DESC LIMIT 1",
+            rusqlite::params![server_name, since, until],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        );
+        match entry {
+            Ok(entry) => Ok(Some(entry)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn purge_operations_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM operations
+             WHERE status != 'running' AND updated_at < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    fn create_group_apply_job(&self, group: &str, config: &str) -> Result<String> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO group_apply_jobs (id, group_name, config, status) VALUES (?1, ?2, ?3, 'running')",
+            [&id, group, config],
+        )?;
+        Ok(id)
+    }
+
+    fn update_group_apply_job(&self, id: &str, status: &str, results: &str) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "UPDATE group_apply_jobs SET status = ?1, results = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+            [status, results, id],
+        )?;
+        Ok(())
+    }
+
+    fn get_group_apply_job(&self, id: &str) -> Result<Option<GroupApplyJob>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let job = conn.query_row(
+            "SELECT id, group_name, config, status, results, created_at, updated_at FROM group_apply_jobs WHERE id = ?1",
+            [id],
+            |row| {
+                let config: String = row.get(2)?;
+                let results: String = row.get(4)?;
+                Ok(GroupApplyJob {
+                    id: row.get(0)?,
+                    group: row.get(1)?,
+                    config: serde_json::from_str(&config).unwrap_or_default(),
+                    status: row.get(3)?,
+                    results: serde_json::from_str(&results).unwrap_or_default(),
+                    created_at: row.get(5)?,
+                    updated_at: row.get(6)?,
+                })
+            },
+        );
+
+        match job {
+            Ok(job) => Ok(Some(job)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn interrupt_running_group_apply_jobs(&self) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "UPDATE group_apply_jobs SET status = 'interrupted', updated_at = CURRENT_TIMESTAMP WHERE status = 'running'",
+            [],
+        )?)
+    }
+
+    fn purge_group_apply_jobs_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM group_apply_jobs
+             WHERE status != 'running' AND updated_at < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    fn replace_firmware_inventory(&self, server_alias: &str, components: &[(String, String)]) -> Result<()> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM firmware_inventory WHERE server_alias = ?1", [server_alias])?;
+        for (component, version) in components {
+            tx.execute(
+                "INSERT OR IGNORE INTO firmware_inventory (server_alias, component, version) VALUES (?1, ?2, ?3)",
+                [server_alias, component, version],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    fn list_firmware_inventory(&self) -> Result<Vec<FirmwareInventoryEntry>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT server_alias, component, version, collected_at FROM firmware_inventory
+             ORDER BY component, server_alias"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(FirmwareInventoryEntry {
+                server_alias: row.get(0)?,
+                component: row.get(1)?,
+                version: row.get(2)?,
+                collected_at: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn list_firmware_baselines(&self) -> Result<Vec<(String, String)>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare("SELECT component, version FROM firmware_baselines ORDER BY component")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn set_firmware_baseline(&self, component: &str, version: Option<&str>) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        match version {
+            Some(version) => conn.execute(
+                "INSERT INTO firmware_baselines (component, version, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)
+                 ON CONFLICT(component) DO UPDATE SET version = excluded.version, updated_at = excluded.updated_at",
+                [component, version],
+            )?,
+            None => conn.execute("DELETE FROM firmware_baselines WHERE component = ?1", [component])?,
+        };
+        Ok(())
+    }
+
+    fn replace_compliance_profile(&self, name: &str, requirements: &[ComplianceRequirement]) -> Result<()> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM compliance_profiles WHERE profile_name = ?1", [name])?;
+        for requirement in requirements {
+            tx.execute(
+                "INSERT INTO compliance_profiles (profile_name, component, minimum_version, recommended_version)
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![
+                    name,
+                    requirement.component,
+                    requirement.minimum_version,
+                    requirement.recommended_version
+                ],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    fn get_compliance_profile(&self, name: &str) -> Result<Vec<ComplianceRequirement>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT component, minimum_version, recommended_version FROM compliance_profiles
+             WHERE profile_name = ?1 ORDER BY component"
+        )?;
+        let rows = stmt.query_map([name], |row| {
+            Ok(ComplianceRequirement {
+                component: row.get(0)?,
+                minimum_version: row.get(1)?,
+                recommended_version: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn create_server_group(&self, name: &str, members: &[String], power_budget_watts: Option<u32>) -> Result<ServerGroup> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let members = serde_json::to_string(members)?;
+        Ok(conn.query_row(
+            &format!(
+                "INSERT INTO server_groups (name, members, power_budget_watts) VALUES (?1, ?2, ?3) RETURNING {}",
+                SERVER_GROUP_COLUMNS
+            ),
+            rusqlite::params![name, members, power_budget_watts],
+            server_group_from_row,
+        )?)
+    }
+
+    fn list_server_groups(&self) -> Result<Vec<ServerGroup>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM server_groups ORDER BY name", SERVER_GROUP_COLUMNS))?;
+        let rows = stmt.query_map([], server_group_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn get_server_group(&self, id: i64) -> Result<Option<ServerGroup>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let group = conn.query_row(
+            &format!("SELECT {} FROM server_groups WHERE id = ?1", SERVER_GROUP_COLUMNS),
+            [id],
+            server_group_from_row,
+        );
+        match group {
+            Ok(group) => Ok(Some(group)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn update_server_group(
+        &self,
+        id: i64,
+        members: &[String],
+        power_budget_watts: Option<u32>,
+    ) -> Result<Option<ServerGroup>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let members = serde_json::to_string(members)?;
+        let group = conn.query_row(
+            &format!(
+                "UPDATE server_groups SET members = ?2, power_budget_watts = ?3 WHERE id = ?1 RETURNING {}",
+                SERVER_GROUP_COLUMNS
+            ),
+            rusqlite::params![id, members, power_budget_watts],
+            server_group_from_row,
+        );
+        match group {
+            Ok(group) => Ok(Some(group)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn delete_server_group(&self, id: i64) -> Result<bool> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM tariff_windows WHERE group_id = ?1", [id])?;
+        let deleted = tx.execute("DELETE FROM server_groups WHERE id = ?1", [id])?;
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    fn record_power_samples(&self, sampled_at: &str, samples: &[(String, u32)]) -> Result<()> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        for (server_alias, watts) in samples {
+            tx.execute(
+                "INSERT INTO power_samples (server_alias, watts, sampled_at) VALUES (?1, ?2, ?3)",
+                rusqlite::params![server_alias, watts, sampled_at],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    fn latest_power_samples(&self, since: &str) -> Result<Vec<PowerSample>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT p.server_alias, p.watts, p.sampled_at FROM power_samples p
+             JOIN (SELECT server_alias, MAX(sampled_at) AS sampled_at FROM power_samples
+                   WHERE sampled_at >= ?1 GROUP BY server_alias) latest
+               ON latest.server_alias = p.server_alias AND latest.sampled_at = p.sampled_at
+             ORDER BY p.server_alias",
+        )?;
+        let rows = stmt.query_map([since], |row| {
+            Ok(PowerSample {
+                server_alias: row.get(0)?,
+                watts: row.get(1)?,
+                sampled_at: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn peak_power_total(&self, aliases: &[String], since: &str) -> Result<Option<PowerPeak>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let aliases = serde_json::to_string(aliases)?;
+        let peak = conn.query_row(
+            "SELECT SUM(watts) AS total, sampled_at FROM power_samples
+             WHERE sampled_at >= ?2 AND server_alias IN (SELECT value FROM json_each(?1))
+             GROUP BY sampled_at ORDER BY total DESC, sampled_at DESC LIMIT 1",
+            [&aliases, since],
+            |row| {
+                Ok(PowerPeak {
+                    watts: row.get::<_, i64>(0)? as u64,
+                    sampled_at: row.get(1)?,
+                })
+            },
+        );
+        match peak {
+            Ok(peak) => Ok(Some(peak)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn purge_power_samples_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM power_samples WHERE sampled_at < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    fn record_power_event(&self, server_alias: &str, power_state: &str, reason: Option<&PowerChangeReason>) -> Result<bool> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let inserted = conn.execute(
+            "INSERT INTO power_events (server_alias, power_state, reason_code)
+             SELECT ?1, ?2, ?3
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM (SELECT power_state, reason_code FROM power_events
+                                WHERE server_alias = ?1 ORDER BY id DESC LIMIT 1) latest
+                 WHERE latest.power_state = ?2 AND latest.reason_code IS ?3
+             )",
+            rusqlite::params![server_alias, power_state, reason.map(|reason| &reason.code)],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    fn list_power_events(&self, server_alias: Option<&str>, limit: u32) -> Result<Vec<PowerEvent>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, server_alias, power_state, reason_code, observed_at FROM power_events
+             WHERE ?1 IS NULL OR server_alias = ?1
+             ORDER BY id DESC
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![server_alias, limit], power_event_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn purge_power_events_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM power_events WHERE observed_at < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    fn record_ping(&self, server_alias: &str, latency_ms: Option<f64>, packet_loss_pct: f64) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO ping_history (server_alias, latency_ms, packet_loss_pct) VALUES (?1, ?2, ?3)",
+            rusqlite::params![server_alias, latency_ms, packet_loss_pct],
+        )?;
+        Ok(())
+    }
+
+    fn list_ping_history(&self, server_alias: &str, since: Option<&str>, limit: u32) -> Result<Vec<PingSample>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, server_alias, latency_ms, packet_loss_pct, recorded_at FROM ping_history
+             WHERE server_alias = ?1 AND (?2 IS NULL OR recorded_at >= ?2)
+             ORDER BY id DESC
+             LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![server_alias, since, limit], |row| {
+            Ok(PingSample {
+                id: row.get(0)?,
+                server_alias: row.get(1)?,
+                latency_ms: row.get(2)?,
+                packet_loss_pct: row.get(3)?,
+                recorded_at: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn purge_ping_history_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM ping_history WHERE recorded_at < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    fn power_sample_watts(&self, server_alias: &str, since: &str, until: &str) -> Result<Vec<u32>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT watts FROM power_samples WHERE server_alias = ?1 AND sampled_at >= ?2 AND sampled_at < ?3",
+        )?;
+        let rows = stmt.query_map([server_alias, since, until], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn get_power_baseline(&self, server_alias: &str, hour_of_week: u32) -> Result<Option<PowerBaseline>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let baseline = conn.query_row(
+            "SELECT server_alias, hour_of_week, weeks, median_watts, mad_watts, updated_at FROM power_baselines
+             WHERE server_alias = ?1 AND hour_of_week = ?2",
+            rusqlite::params![server_alias, hour_of_week],
+            |row| {
+                let weeks: String = row.get(2)?;
+                Ok(PowerBaseline {
+                    server_alias: row.get(0)?,
+                    hour_of_week: row.get(1)?,
+                    weeks: serde_json::from_str(&weeks).unwrap_or_default(),
+                    median_watts: row.get(3)?,
+                    mad_watts: row.get(4)?,
+                    updated_at: row.get(5)?,
+                })
+            },
+        );
+        match baseline {
+            Ok(baseline) => Ok(Some(baseline)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save_power_baseline(&self, baseline: &PowerBaseline) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO power_baselines (server_alias, hour_of_week, weeks, median_watts, mad_watts, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                baseline.server_alias,
+                baseline.hour_of_week,
+                serde_json::to_string(&baseline.weeks)?,
+                baseline.median_watts,
+                baseline.mad_watts,
+                baseline.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn record_metric_samples(&self, samples: &[MetricSample]) -> Result<()> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        for sample in samples {
+            tx.execute(
+                "INSERT INTO metric_samples
+                     (server_alias, report_id, metric_id, metric_property, value, numeric_value, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    sample.server_alias,
+                    sample.report_id,
+                    sample.metric_id,
+                    sample.metric_property,
+                    sample.value,
+                    sample.numeric_value,
+                    sample.timestamp,
+                ],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    fn list_metric_samples(
+        &self,
+        metric_id: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        keyset: &Keyset<(String, i64)>,
+        limit: u32,
+    ) -> Result<Vec<MetricSample>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let (cmp, order) = keyset.sql(false);
+        let (key_timestamp, key_id) = keyset.key().map(|(t, id)| (t.as_str(), *id)).unzip();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, server_alias, report_id, metric_id, metric_property, value, numeric_value, timestamp
+             FROM metric_samples
+             WHERE (?1 IS NULL OR metric_id = ?1)
+               AND (?2 IS NULL OR timestamp >= ?2)
+               AND (?3 IS NULL OR timestamp <= ?3)
+               AND (?4 IS NULL OR (timestamp, id) {cmp} (?4, ?5))
+             ORDER BY timestamp {order}, id {order}
+             LIMIT ?6",
+        ))?;
+        let params = rusqlite::params![metric_id, since, until, key_timestamp, key_id, limit];
+        let rows = stmt.query_map(params, |row| {
+            Ok(MetricSample {
+                id: row.get(0)?,
+                server_alias: row.get(1)?,
+                report_id: row.get(2)?,
+                metric_id: row.get(3)?,
+                metric_property: row.get(4)?,
+                value: row.get(5)?,
+                numeric_value: row.get(6)?,
+                timestamp: row.get(7)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn purge_metric_samples_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM metric_samples WHERE timestamp < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    fn roll_up_metric_samples(&self, granularity: RollupGranularity, from: Option<&str>) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            &format!(
+                "INSERT INTO metric_rollups
+                     (granularity, server_alias, metric_id, bucket_start, sample_count, min_value, avg_value, max_value)
+                 SELECT ?1, server_alias, metric_id, {bucket} AS bucket,
+                        COUNT(*), MIN(numeric_value), AVG(numeric_value), MAX(numeric_value)
+                 FROM metric_samples
+                 WHERE numeric_value IS NOT NULL AND (?2 IS NULL OR timestamp >= ?2)
+                 GROUP BY server_alias, metric_id, bucket
+                 ON CONFLICT (granularity, server_alias, metric_id, bucket_start) DO UPDATE SET
+                     sample_count = excluded.sample_count,
+                     min_value = excluded.min_value,
+                     avg_value = excluded.avg_value,
+                     max_value = excluded.max_value",
+                bucket = granularity.bucket_sql(),
+            ),
+            rusqlite::params![granularity.as_str(), from],
+        )?)
+    }
+
+    fn latest_metric_rollup(&self, granularity: RollupGranularity) -> Result<Option<String>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.query_row(
+            "SELECT MAX(bucket_start) FROM metric_rollups WHERE granularity = ?1",
+            [granularity.as_str()],
+            |row| row.get(0),
+        )?)
+    }
+
+    fn list_metric_rollups(
+        &self,
+        granularity: RollupGranularity,
+        metric_id: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        keyset: &Keyset<(String, i64)>,
+        limit: u32,
+    ) -> Result<Vec<MetricRollup>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let (cmp, order) = keyset.sql(false);
+        let (key_start, key_id) = keyset.key().map(|(t, id)| (t.as_str(), *id)).unzip();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, server_alias, metric_id, bucket_start, sample_count, min_value, avg_value, max_value
+             FROM metric_rollups
+             WHERE granularity = ?1
+               AND (?2 IS NULL OR metric_id = ?2)
+               AND (?3 IS NULL OR bucket_start >= ?3)
+               AND (?4 IS NULL OR bucket_start <= ?4)
+               AND (?5 IS NULL OR (bucket_start, id) {cmp} (?5, ?6))
+             ORDER BY bucket_start {order}, id {order}
+             LIMIT ?7",
+        ))?;
+        let params = rusqlite::params![granularity.as_str(), metric_id, since, until, key_start, key_id, limit];
+        let rows = stmt.query_map(params, |row| {
+            Ok(MetricRollup {
+                id: row.get(0)?,
+                server_alias: row.get(1)?,
+                metric_id: row.get(2)?,
+                bucket_start: row.get(3)?,
+                sample_count: row.get(4)?,
+                min: row.get(5)?,
+                avg: row.get(6)?,
+                max: row.get(7)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn record_sel_entries(&self, entries: &[SelRecord]) -> Result<usize> {
+        let mut conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let tx = conn.transaction()?;
+        let mut added = 0;
+        for entry in entries {
+            added += tx.execute(
+                "INSERT OR IGNORE INTO sel_entries (server_alias, entry_id, created, severity, message, message_id, source)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    entry.server_alias,
+                    entry.entry_id,
+                    entry.created,
+                    entry.severity,
+                    entry.message,
+                    entry.message_id,
+                    entry.source,
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    fn list_sel_entries(&self, server_alias: &str, keyset: &Keyset<(String, String)>, limit: u32) -> Result<Vec<SelRecord>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let (cmp, order) = keyset.sql(true);
+        let (key_created, key_entry) = keyset.key().map(|(c, e)| (c.as_str(), e.as_str())).unzip();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SEL_COLUMNS}
+             FROM sel_entries
+             WHERE server_alias = ?1
+               AND (?2 IS NULL OR (created, entry_id) {cmp} (?2, ?3))
+             ORDER BY created {order}, entry_id {order}
+             LIMIT ?4",
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![server_alias, key_created, key_entry, limit], sel_record_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn list_uncorrelated_sel_entries(&self, server_alias: &str, recorded_since: &str) -> Result<Vec<SelRecord>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SEL_COLUMNS} FROM sel_entries
+             WHERE server_alias = ?1 AND caused_by_action IS NULL AND recorded_at >= ?2
+             ORDER BY created, entry_id",
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![server_alias, recorded_since], sel_record_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn set_sel_entry_cause(&self, entry: &SelRecord, cause: &EventCause) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "UPDATE sel_entries SET caused_by_action = ?5, caused_by_audit_id = ?6, caused_by_operation_id = ?7
+             WHERE server_alias = ?1 AND COALESCE(message_id, '') = COALESCE(?2, '') AND created = ?3 AND entry_id = ?4",
+            rusqlite::params![
+                entry.server_alias,
+                entry.message_id,
+                entry.created,
+                entry.entry_id,
+                cause.action,
+                cause.audit_id,
+                cause.operation_id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn list_operation_events(&self, operation_id: &str) -> Result<Vec<SelRecord>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SEL_COLUMNS} FROM sel_entries WHERE caused_by_operation_id = ?1 ORDER BY created, entry_id",
+        ))?;
+        let rows = stmt.query_map([operation_id], sel_record_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn count_sel_entries(&self, server_alias: &str) -> Result<u64> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sel_entries WHERE server_alias = ?1",
+            [server_alias],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    fn purge_sel_entries_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM sel_entries WHERE recorded_at < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+
+    fn store_health_report(&self, server_alias: &str, report_date: &str, report: &serde_json::Value) -> Result<()> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO health_reports (server_alias, report_date, report) VALUES (?1, ?2, ?3)
+             ON CONFLICT (server_alias, report_date)
+             DO UPDATE SET report = excluded.report, created_at = CURRENT_TIMESTAMP",
+            rusqlite::params![server_alias, report_date, report.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn list_health_reports(&self, server_alias: Option<&str>, report_date: Option<&str>, limit: u32) -> Result<Vec<HealthReport>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM health_reports
+             WHERE (?1 IS NULL OR server_alias = ?1) AND (?2 IS NULL OR report_date = ?2)
+             ORDER BY report_date DESC, server_alias, id DESC
+             LIMIT ?3",
+            HEALTH_REPORT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![server_alias, report_date, limit], health_report_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn latest_health_reports(&self) -> Result<Vec<HealthReport>> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM health_reports r
+             WHERE report_date = (SELECT MAX(report_date) FROM health_reports WHERE server_alias = r.server_alias)
+             ORDER BY server_alias",
+            HEALTH_REPORT_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], health_report_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn purge_health_reports_older_than(&self, days: u32) -> Result<usize> {
+        let conn = self.pool().get()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        Ok(conn.execute(
+            "DELETE FROM health_reports WHERE created_at < datetime('now', '-' || ?1 || ' days')",
+            [days],
+        )?)
+    }
+}
+
+fn power_cap_schedule_from_row(row: &rusqlite::Row) -> rusqlite::Result<PowerCapSchedule> {
+    Ok(PowerCapSchedule {
+        id: row.get(0)?,
+        server_alias: row.get(1)?,
+        cron_expr: row.get(2)?,
+        watts: row.get(3)?,
+        duration_minutes: row.get(4)?,
+        enabled: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+const TARIFF_WINDOW_COLUMNS: &str = "id, server_alias, group_id, days, start_time, end_time, watts, created_at";
+
+fn tariff_window_from_row(row: &rusqlite::Row) -> rusqlite::Result<TariffWindow> {
+    let days: String = row.get(3)?;
+    Ok(TariffWindow {
+        id: row.get(0)?,
+        server_alias: row.get(1)?,
+        group_id: row.get(2)?,
+        days: serde_json::from_str(&days).unwrap_or_default(),
+        start: row.get(4)?,
+        end: row.get(5)?,
+        watts: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+const ONE_SHOT_COLUMNS: &str =
+    "id, server_alias, action, execute_at, created_by, created_at, status, executed_at, outcome, message";
+
+fn one_shot_schedule_from_row(row: &rusqlite::Row) -> rusqlite::Result<OneShotSchedule> {
+    Ok(OneShotSchedule {
+        id: row.get(0)?,
+        server_alias: row.get(1)?,
+        action: row.get(2)?,
+        execute_at: row.get(3)?,
+        created_by: row.get(4)?,
+        created_at: row.get(5)?,
+        status: row.get(6)?,
+        executed_at: row.get(7)?,
+        outcome: row.get(8)?,
+        message: row.get(9)?,
+    })
+}
+
+const FIRMWARE_UPDATE_SCHEDULE_COLUMNS: &str =
+    "id, server_alias, job_id, reboot_at, created_by, created_at, status, operation_id, executed_at, outcome, message";
+
+fn firmware_update_schedule_from_row(row: &rusqlite::Row) -> rusqlite::Result<FirmwareUpdateSchedule> {
+    Ok(FirmwareUpdateSchedule {
+        id: row.get(0)?,
+        server_alias: row.get(1)?,
+        job_id: row.get(2)?,
+        reboot_at: row.get(3)?,
+        created_by: row.get(4)?,
+        created_at: row.get(5)?,
+        status: row.get(6)?,
+        operation_id: row.get(7)?,
+        executed_at: row.get(8)?,
+        outcome: row.get(9)?,
+        message: row.get(10)?,
+    })
+}
+
+const BREAK_GLASS_COLUMNS: &str = "id, reason, issued_by, created_at, expires_at, used_at";
+
+fn break_glass_grant_from_row(row: &rusqlite::Row) -> rusqlite::Result<BreakGlassGrant> {
+    Ok(BreakGlassGrant {
+        id: row.get(0)?,
+        reason: row.get(1)?,
+        issued_by: row.get(2)?,
+        created_at: row.get(3)?,
+        expires_at: row.get(4)?,
+        used_at: row.get(5)?,
+    })
+}
+
+const SERVER_GRANT_FROM: &str = "g.id, g.user_id, u.username, g.server_alias, g.created_at, g.expires_at
+     FROM server_grants g JOIN users u ON u.id = g.user_id";
+
+fn server_grant_from_row(row: &rusqlite::Row) -> rusqlite::Result<ServerGrant> {
+    Ok(ServerGrant {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        username: row.get(2)?,
+        server_alias: row.get(3)?,
+        created_at: row.get(4)?,
+        expires_at: row.get(5)?,
+    })
+}
+
+const HEALTH_REPORT_COLUMNS: &str = "id, server_alias, report_date, created_at, report";
+
+const SERVER_COLUMNS: &str = "id, name, slug, host, username, password, tags, location, default_power_cap_watts, hosts_this_app,
+     os_health_url, os_health_insecure, os_health_token";
+
+fn server_record_from_row(row: &rusqlite::Row) -> rusqlite::Result<ServerRecord> {
+    let tags: String = row.get(6)?;
+    Ok(ServerRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        slug: row.get(2)?,
+        host: row.get(3)?,
+        username: row.get(4)?,
+        password: row.get(5)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        location: row.get(7)?,
+        default_power_cap_watts: row.get(8)?,
+        hosts_this_app: row.get(9)?,
+        os_health_url: row.get(10)?,
+        os_health_insecure: row.get(11)?,
+        os_health_token: row.get(12)?,
+    })
+}
+
+fn health_report_from_row(row: &rusqlite::Row) -> rusqlite::Result<HealthReport> {
+    let report: String = row.get(4)?;
+    Ok(HealthReport {
+        id: row.get(0)?,
+        server_alias: row.get(1)?,
+        report_date: row.get(2)?,
+        created_at: row.get(3)?,
+        report: serde_json::from_str(&report).unwrap_or_default(),
+    })
+}
+
+fn power_event_from_row(row: &rusqlite::Row) -> rusqlite::Result<PowerEvent> {
+    let code: Option<String> = row.get(3)?;
+    Ok(PowerEvent {
+        id: row.get(0)?,
+        server_alias: row.get(1)?,
+        power_state: row.get(2)?,
+        reason: code.as_deref().map(PowerChangeReason::from_code),
+        observed_at: row.get(4)?,
+    })
+}
+
+const SHARE_COLUMNS: &str =
+    "id, name, servers, tag, passcode_hash, created_by, created_at, expires_at, last_accessed_at, access_count";
+
+fn share_from_row(row: &rusqlite::Row) -> rusqlite::Result<Share> {
+    let servers: String = row.get(2)?;
+    let passcode_hash: Option<String> = row.get(4)?;
+    Ok(Share {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        servers: serde_json::from_str(&servers).unwrap_or_default(),
+        tag: row.get(3)?,
+        has_passcode: passcode_hash.is_some(),
+        passcode_hash,
+        created_by: row.get(5)?,
+        created_at: row.get(6)?,
+        expires_at: row.get(7)?,
+        last_accessed_at: row.get(8)?,
+        access_count: row.get(9)?,
+    })
+}
+
+const SERVER_GROUP_COLUMNS: &str = "id, name, members, power_budget_watts, created_at";
+
+fn server_group_from_row(row: &rusqlite::Row) -> rusqlite::Result<ServerGroup> {
+    let members: String = row.get(2)?;
+    Ok(ServerGroup {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        members: serde_json::from_str(&members).unwrap_or_default(),
+        power_budget_watts: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+const OPERATION_COLUMNS: &str = "id, kind, server_alias, status, stages, created_at, updated_at";
+
+fn operation_from_row(row: &rusqlite::Row) -> rusqlite::Result<Operation> {
+    let stages_json: String = row.get(4)?;
+    Ok(Operation {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        server_alias: row.get(2)?,
+        status: row.get(3)?,
+        stages: serde_json::from_str(&stages_json).unwrap_or_default(),
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+fn has_column(conn: &rusqlite::Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(1)?;
+        if name == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Run `insert` with `base` as the slug, then `base-2`, `base-3`, ... for
+/// as long as the slug's UNIQUE constraint turns it away. Leaving the check
+/// to the constraint keeps two concurrent inserts from taking the same slug.
+fn insert_with_unique_slug<T>(
+    base: &str,
+    mut insert: impl FnMut(&str) -> rusqlite::Result<T>,
+) -> rusqlite::Result<(T, String)> {
+    let mut candidate = base.to_string();
+    let mut suffix = 2;
+    loop {
+        match insert(&candidate) {
+            Err(rusqlite::Error::SqliteFailure(err, Some(message)))
+                if err.code == rusqlite::ErrorCode::ConstraintViolation && message.ends_with(".slug") =>
+            {
+                candidate = format!("{}-{}", base, suffix);
+                suffix += 1;
+            }
+            result => return result.map(|value| (value, candidate)),
+        }
+    }
+}
+
+/// Pick `base`, or `base-2`, `base-3`, ... if another user already has it.
+/// Only for the backfill, which runs before the UNIQUE index exists.
+fn unique_slug(conn: &rusqlite::Connection, table: &str, base: &str) -> rusqlite::Result<String> {
+    let mut candidate = base.to_string();
+    let mut suffix = 2;
+    loop {
+        let taken: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM {} WHERE slug = ?1", table),
+            [&candidate],
+            |row| row.get(0),
+        )?;
+        if taken == 0 {
+            return Ok(candidate);
+        }
+        candidate = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+}
+
+/// Add the `slug` column to `users` and backfill it for existing rows.
+fn migrate_user_slugs(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    if !has_column(conn, "users", "slug")? {
+        conn.execute("ALTER TABLE users ADD COLUMN slug TEXT", [])?;
+        info!("Added slug column to users table");
+    }
+
+    let mut stmt = conn.prepare("SELECT id, username FROM users WHERE slug IS NULL ORDER BY id")?;
+    let pending: Vec<(i64, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    for (id, username) in pending {
+        let base = slugify(&username);
+        let slug = unique_slug(conn, "users", &base)?;
+        if slug != base {
+            warn!("Slug collision for user '{}': '{}' already taken, using '{}'", username, base, slug);
+        }
+        conn.execute("UPDATE users SET slug = ?1 WHERE id = ?2", rusqlite::params![slug, id])?;
+    }
+
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_users_slug ON users(slug)", [])?;
+    Ok(())
+}
+
+const SEL_COLUMNS: &str = "server_alias, entry_id, created, severity, message, message_id, source, \
+                           caused_by_action, caused_by_audit_id, caused_by_operation_id";
+
+fn sel_record_from_row(row: &rusqlite::Row) -> rusqlite::Result<SelRecord> {
+    let action: Option<String> = row.get(7)?;
+    Ok(SelRecord {
+        server_alias: row.get(0)?,
+        entry_id: row.get(1)?,
+        created: row.get(2)?,
+        severity: row.get(3)?,
+        message: row.get(4)?,
+        message_id: row.get(5)?,
+        source: row.get(6)?,
+        caused_by: match action {
+            Some(action) => Some(EventCause {
+                action,
+                audit_id: row.get(8)?,
+                operation_id: row.get(9)?,
+            }),
+            None => None,
+        },
+    })
+}
+
+fn user_from_row(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        expires_at: row.get(3)?,
+        disabled_at: row.get(4)?,
+        session_generation: row.get(5)?,
+        auth_source: row.get(6)?,
+        email: row.get(7)?,
+        role: UserRole::parse(&row.get::<_, String>(8)?),
+    })
+}
+
+fn user_summary_from_row(row: &rusqlite::Row) -> rusqlite::Result<UserSummary> {
+    Ok(UserSummary {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        created_at: row.get(2)?,
+        expires_at: row.get(3)?,
+        disabled_at: row.get(4)?,
+        auth_source: row.get(5)?,
+        role: UserRole::parse(&row.get::<_, String>(6)?),
+    })
+}
+
+fn api_token_from_row(row: &rusqlite::Row) -> rusqlite::Result<ApiToken> {
+    let scopes: String = row.get(3)?;
+    Ok(ApiToken {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        scopes: serde_json::from_str(&scopes).unwrap_or_default(),
+        created_at: row.get(4)?,
+        last_used_at: row.get(5)?,
+        requests_per_hour: row.get(6)?,
+    })
+}
+
+/// Columns added to `api_tokens`, `audit_log`, `operations`, `servers` and `users`
+/// after they were first created.
+const SEL_ENTRIES_TABLE: &str = "CREATE TABLE IF NOT EXISTS sel_entries (
+    server_alias TEXT NOT NULL,
+    entry_id TEXT NOT NULL,
+    created TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    message TEXT NOT NULL,
+    message_id TEXT,
+    source TEXT NOT NULL DEFAULT 'sel',
+    caused_by_action TEXT,
+    caused_by_audit_id INTEGER,
+    caused_by_operation_id TEXT,
+    recorded_at DATETIME DEFAULT CURRENT_TIMESTAMP
+)";
+
+/// `sel_entries` used to have a primary key on server, entry id and
+/// creation time, which SQLite cannot drop; copy it into a table keyed on
+/// the message id as well.
+fn migrate_sel_entries_key(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    if has_column(conn, "sel_entries", "source")? {
+        return Ok(());
+    }
+    conn.execute_batch(&format!(
+        "BEGIN;
+         ALTER TABLE sel_entries RENAME TO sel_entries_old;
+         {SEL_ENTRIES_TABLE};
+         INSERT INTO sel_entries (server_alias, entry_id, created, severity, message, message_id, recorded_at)
+             SELECT server_alias, entry_id, created, severity, message, message_id, recorded_at FROM sel_entries_old;
+         DROP TABLE sel_entries_old;
+         COMMIT;"
+    ))?;
+    info!("Rebuilt sel_entries table with source and cause columns");
+    Ok(())
+}
+
+/// New accounts default to `readonly`, but accounts that existed before
+/// roles were introduced keep full access.
+fn migrate_user_roles(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    if has_column(conn, "users", "role")? {
+        return Ok(());
+    }
+    conn.execute_batch(
+        "BEGIN;
+         ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'readonly';
+         UPDATE users SET role = 'admin';
+         COMMIT;",
+    )?;
+    info!("Added role column to users table");
+    Ok(())
+}
+
+fn migrate_added_columns(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    let added = [
+        ("users", "expires_at", "DATETIME"),
+        ("users", "disabled_at", "DATETIME"),
+        ("users", "session_generation", "INTEGER NOT NULL DEFAULT 0"),
+        ("users", "auth_source", "TEXT NOT NULL DEFAULT 'local'"),
+        ("users", "email", "TEXT"),
+        ("audit_log", "details", "TEXT"),
+        ("servers", "tags", "TEXT NOT NULL DEFAULT '[]'"),
+        ("servers", "location", "TEXT"),
+        ("servers", "default_power_cap_watts", "INTEGER"),
+        ("servers", "hosts_this_app", "BOOLEAN NOT NULL DEFAULT 0"),
+        ("servers", "os_health_url", "TEXT"),
+        ("servers", "os_health_insecure", "BOOLEAN NOT NULL DEFAULT 0"),
+        ("servers", "os_health_token", "TEXT"),
+        ("operations", "server_alias", "TEXT"),
+        ("api_tokens", "requests_per_hour", "INTEGER"),
+    ];
+
+    for (table, column, definition) in added {
+        if !has_column(conn, table, column)? {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+            info!("Added {} column to {} table", column, table);
+        }
+    }
+    Ok(())
+}
+
+fn open_read_write_pool(db_path: &str) -> rusqlite::Result<DbPool> {
+    let manager = SqliteConnectionManager::file(db_path);
+    let pool = Pool::new(manager)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+    // Initialize schema
+    let conn = pool.get()
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    migrate_user_slugs(&conn)?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            action TEXT NOT NULL,
+            server_name TEXT NOT NULL,
+            result TEXT NOT NULL,
+            error_message TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS event_subscriptions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            destination TEXT NOT NULL,
+            context TEXT NOT NULL UNIQUE,
+            subscription_uri TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operations (
+            id TEXT PRIMARY KEY,
+            kind TEXT NOT NULL,
+            status TEXT NOT NULL,
+            stages TEXT NOT NULL DEFAULT '[]',
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS user_preferences (
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (user_id, key)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS servers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            slug TEXT NOT NULL UNIQUE,
+            host TEXT NOT NULL,
+            username TEXT NOT NULL,
+            password TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS power_cap_schedules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            server_alias TEXT NOT NULL,
+            cron_expr TEXT NOT NULL,
+            watts INTEGER NOT NULL,
+            duration_minutes INTEGER NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tariff_windows (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            server_alias TEXT,
+            group_id INTEGER,
+            days TEXT NOT NULL,
+            start_time TEXT NOT NULL,
+            end_time TEXT NOT NULL,
+            watts INTEGER NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tariff_caps (
+            server_alias TEXT PRIMARY KEY,
+            window_id INTEGER NOT NULL,
+            watts INTEGER NOT NULL,
+            previous_watts INTEGER,
+            applied_at DATETIME NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS one_shot_schedules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            server_alias TEXT NOT NULL,
+            action TEXT NOT NULL,
+            execute_at DATETIME NOT NULL,
+            created_by INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            status TEXT NOT NULL DEFAULT 'pending',
+            executed_at DATETIME,
+            outcome TEXT,
+            message TEXT
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS firmware_update_schedules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            server_alias TEXT NOT NULL,
+            job_id TEXT NOT NULL,
+            reboot_at DATETIME NOT NULL,
+            created_by INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            status TEXT NOT NULL DEFAULT 'pending',
+            operation_id TEXT,
+            executed_at DATETIME,
+            outcome TEXT,
+            message TEXT
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS break_glass_grants (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            token_hash TEXT NOT NULL UNIQUE,
+            reason TEXT NOT NULL,
+            issued_by TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            expires_at DATETIME NOT NULL,
+            used_at DATETIME
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS shares (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            servers TEXT NOT NULL DEFAULT '[]',
+            tag TEXT,
+            token_hash TEXT NOT NULL UNIQUE,
+            passcode_hash TEXT,
+            created_by INTEGER NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            expires_at DATETIME NOT NULL,
+            last_accessed_at DATETIME,
+            access_count INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS leases (
+            name TEXT PRIMARY KEY,
+            holder TEXT NOT NULL,
+            heartbeat_at DATETIME NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS firmware_inventory (
+            server_alias TEXT NOT NULL,
+            component TEXT NOT NULL,
+            version TEXT NOT NULL,
+            collected_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (server_alias, component, version)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS firmware_baselines (
+            component TEXT PRIMARY KEY,
+            version TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS compliance_profiles (
+            profile_name TEXT NOT NULL,
+            component TEXT NOT NULL,
+            minimum_version TEXT NOT NULL,
+            recommended_version TEXT,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (profile_name, component)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS api_tokens (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT UNIQUE NOT NULL,
+            scopes TEXT NOT NULL DEFAULT '[]',
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            last_used_at DATETIME
+        )",
+        [],
+    )?;
+
+    // Requests made with a session are stored under token 0.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS api_usage (
+            user_id INTEGER NOT NULL,
+            token_id INTEGER NOT NULL DEFAULT 0,
+            hour DATETIME NOT NULL,
+            requests INTEGER NOT NULL,
+            PRIMARY KEY (user_id, token_id, hour)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS server_groups (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE NOT NULL,
+            members TEXT NOT NULL DEFAULT '[]',
+            power_budget_watts INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS group_apply_jobs (
+            id TEXT PRIMARY KEY,
+            group_name TEXT NOT NULL,
+            config TEXT NOT NULL,
+            status TEXT NOT NULL,
+            results TEXT NOT NULL DEFAULT '[]',
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS power_samples (
+            server_alias TEXT NOT NULL,
+            watts INTEGER NOT NULL,
+            sampled_at DATETIME NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_power_samples_sampled_at ON power_samples (sampled_at)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS power_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            server_alias TEXT NOT NULL,
+            power_state TEXT NOT NULL,
+            reason_code TEXT,
+            observed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_power_events_server ON power_events (server_alias, id)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ping_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            server_alias TEXT NOT NULL,
+            latency_ms REAL,
+            packet_loss_pct REAL NOT NULL,
+            recorded_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_ping_history_server ON ping_history (server_alias, id)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS power_baselines (
+            server_alias TEXT NOT NULL,
+            hour_of_week INTEGER NOT NULL,
+            weeks TEXT NOT NULL,
+            median_watts REAL NOT NULL,
+            mad_watts REAL NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (server_alias, hour_of_week)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS metric_samples (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            server_alias TEXT NOT NULL,
+            report_id TEXT,
+            metric_id TEXT NOT NULL,
+            metric_property TEXT,
+            value TEXT NOT NULL,
+            numeric_value REAL,
+            timestamp DATETIME NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_metric_samples_metric_timestamp ON metric_samples (metric_id, timestamp)",
+        [],
+    )?;
+
+    // Hourly and daily min/avg/max per server and metric, kept beyond the
+    // samples they were computed from.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS metric_rollups (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            granularity TEXT NOT NULL,
+            server_alias TEXT NOT NULL,
+            metric_id TEXT NOT NULL,
+            bucket_start TEXT NOT NULL,
+            sample_count INTEGER NOT NULL,
+            min_value REAL NOT NULL,
+            avg_value REAL NOT NULL,
+            max_value REAL NOT NULL,
+            UNIQUE (granularity, server_alias, metric_id, bucket_start)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_metric_rollups_bucket ON metric_rollups (granularity, bucket_start)",
+        [],
+    )?;
+
+    conn.execute(SEL_ENTRIES_TABLE, [])?;
+    migrate_sel_entries_key(&conn)?;
+    // An entry is identified by its creation time as well, since the iDRAC
+    // reuses ids once the SEL is cleared.
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_sel_entries_key
+         ON sel_entries (server_alias, COALESCE(message_id, ''), created, entry_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_sel_entries_operation ON sel_entries (caused_by_operation_id)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS health_reports (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            server_alias TEXT NOT NULL,
+            report_date TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            report TEXT NOT NULL,
+            UNIQUE (server_alias, report_date)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS password_reset_tokens (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            token_hash TEXT NOT NULL UNIQUE,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            expires_at DATETIME NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS server_grants (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            server_alias TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            expires_at DATETIME,
+            UNIQUE (user_id, server_alias)
+        )",
+        [],
+    )?;
+
+    migrate_added_columns(&conn)?;
+    migrate_user_roles(&conn)?;
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email ON users (lower(email))", [])?;
+
+    Ok(pool)
+}
@@ -0,0 +1,353 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Prefix of the error returned when a request is turned away by the
+/// iDRAC rate limiter; `ErrorCode::from_idrac_error` matches on it.
+pub const RATE_LIMITED_PREFIX: &str = "iDRAC rate limit reached";
+
+/// Prefix of the error returned instead of sending a request once the
+/// iDRAC has rejected the credentials; `ErrorCode::from_idrac_error`
+/// matches on it.
+pub const CREDENTIALS_REJECTED_PREFIX: &str = "iDRAC credentials rejected";
+
+/// Prefix of the error returned when a BIOS change fails the attribute
+/// registry check before anything is sent; `ErrorCode::from_idrac_error`
+/// matches on it.
+pub const BIOS_SETTING_REJECTED_PREFIX: &str = "Invalid BIOS setting";
+
+/// Stable, machine-readable error identifiers returned in the `error_code`
+/// field of every API error response. Clients should branch on these rather
+/// than on the human-readable message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    AuthNotAuthenticated,
+    AuthInvalidCredentials,
+    AuthRegistrationClosed,
+    AuthInvalidEventContext,
+    AuthInvalidToken,
+    AuthMissingScope,
+    AuthAccountExpired,
+    AuthBreakGlassRestricted,
+    AuthQuotaExceeded,
+    AuthSharePasscodeRequired,
+    AuthShareReadOnly,
+    AuthPasswordLoginDisabled,
+    AuthTooManyRequests,
+    AuthInsufficientRole,
+    ValidationMissingField,
+    ValidationInvalidField,
+    ValidationInvalidValue,
+    ValidationInvalidBody,
+    ValidationPasswordMismatch,
+    ValidationPasswordTooShort,
+    ValidationConfirmationRequired,
+    ValidationHostNotAllowed,
+    PowerAlreadyInState,
+    IdracUnreachable,
+    IdracAuthFailed,
+    IdracRequestFailed,
+    IdracInvalidResponse,
+    IdracRateLimited,
+    OperationNotFound,
+    OperationTimeout,
+    OperationInvalidState,
+    ServerDuplicate,
+    ServerNotFound,
+    ScheduleNotFound,
+    ScheduleOverlap,
+    GroupDuplicate,
+    GroupNotFound,
+    ComplianceProfileNotFound,
+    TokenNotFound,
+    ShareNotFound,
+    GrantNotFound,
+    UserDuplicate,
+    UserNotFound,
+    ConfigSelfUrlMissing,
+    ConfigOidcNotConfigured,
+    ConfigSmtpNotConfigured,
+    StandbyReadOnly,
+    StandbyNotStandby,
+    StandbyLeaseHeld,
+    StorageDegraded,
+    InternalDatabase,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::AuthNotAuthenticated,
+        ErrorCode::AuthInvalidCredentials,
+        ErrorCode::AuthRegistrationClosed,
+        ErrorCode::AuthInvalidEventContext,
+        ErrorCode::AuthInvalidToken,
+        ErrorCode::AuthMissingScope,
+        ErrorCode::AuthAccountExpired,
+        ErrorCode::AuthBreakGlassRestricted,
+        ErrorCode::AuthQuotaExceeded,
+        ErrorCode::AuthSharePasscodeRequired,
+        ErrorCode::AuthShareReadOnly,
+        ErrorCode::AuthPasswordLoginDisabled,
+        ErrorCode::AuthTooManyRequests,
+        ErrorCode::AuthInsufficientRole,
+        ErrorCode::ValidationMissingField,
+        ErrorCode::ValidationInvalidField,
+        ErrorCode::ValidationInvalidValue,
+        ErrorCode::ValidationInvalidBody,
+        ErrorCode::ValidationPasswordMismatch,
+        ErrorCode::ValidationPasswordTooShort,
+        ErrorCode::ValidationConfirmationRequired,
+        ErrorCode::ValidationHostNotAllowed,
+        ErrorCode::PowerAlreadyInState,
+        ErrorCode::IdracUnreachable,
+        ErrorCode::IdracAuthFailed,
+        ErrorCode::IdracRequestFailed,
+        ErrorCode::IdracInvalidResponse,
+        ErrorCode::IdracRateLimited,
+        ErrorCode::OperationNotFound,
+        ErrorCode::OperationTimeout,
+        ErrorCode::OperationInvalidState,
+        ErrorCode::ServerDuplicate,
+        ErrorCode::ServerNotFound,
+        ErrorCode::ScheduleNotFound,
+        ErrorCode::ScheduleOverlap,
+        ErrorCode::GroupDuplicate,
+        ErrorCode::GroupNotFound,
+        ErrorCode::ComplianceProfileNotFound,
+        ErrorCode::TokenNotFound,
+        ErrorCode::ShareNotFound,
+        ErrorCode::GrantNotFound,
+        ErrorCode::UserDuplicate,
+        ErrorCode::UserNotFound,
+        ErrorCode::ConfigSelfUrlMissing,
+        ErrorCode::ConfigOidcNotConfigured,
+        ErrorCode::ConfigSmtpNotConfigured,
+        ErrorCode::StandbyReadOnly,
+        ErrorCode::StandbyNotStandby,
+        ErrorCode::StandbyLeaseHeld,
+        ErrorCode::StorageDegraded,
+        ErrorCode::InternalDatabase,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::AuthNotAuthenticated => "auth.not_authenticated",
+            ErrorCode::AuthInvalidCredentials => "auth.invalid_credentials",
+            ErrorCode::AuthRegistrationClosed => "auth.registration_closed",
+            ErrorCode::AuthInvalidEventContext => "auth.invalid_event_context",
+            ErrorCode::AuthInvalidToken => "auth.invalid_token",
+            ErrorCode::AuthMissingScope => "auth.missing_scope",
+            ErrorCode::AuthAccountExpired => "auth.account_expired",
+            ErrorCode::AuthBreakGlassRestricted => "auth.break_glass_restricted",
+            ErrorCode::AuthQuotaExceeded => "auth.quota_exceeded",
+            ErrorCode::AuthSharePasscodeRequired => "auth.share_passcode_required",
+            ErrorCode::AuthShareReadOnly => "auth.share_read_only",
+            ErrorCode::AuthPasswordLoginDisabled => "auth.password_login_disabled",
+            ErrorCode::AuthTooManyRequests => "auth.too_many_requests",
+            ErrorCode::AuthInsufficientRole => "auth.insufficient_role",
+            ErrorCode::ValidationMissingField => "validation.missing_field",
+            ErrorCode::ValidationInvalidField => "validation.invalid_field",
+            ErrorCode::ValidationInvalidValue => "validation.invalid_value",
+            ErrorCode::ValidationInvalidBody => "validation.invalid_body",
+            ErrorCode::ValidationPasswordMismatch => "validation.password_mismatch",
+            ErrorCode::ValidationPasswordTooShort => "validation.password_too_short",
+            ErrorCode::ValidationConfirmationRequired => "validation.confirmation_required",
+            ErrorCode::ValidationHostNotAllowed => "validation.host_not_allowed",
+            ErrorCode::PowerAlreadyInState => "power.already_in_state",
+            ErrorCode::IdracUnreachable => "idrac.unreachable",
+            ErrorCode::IdracAuthFailed => "idrac.auth_failed",
+            ErrorCode::IdracRequestFailed => "idrac.request_failed",
+            ErrorCode::IdracInvalidResponse => "idrac.invalid_response",
+            ErrorCode::IdracRateLimited => "idrac.rate_limited",
+            ErrorCode::OperationNotFound => "operation.not_found",
+            ErrorCode::OperationTimeout => "operation.timeout",
+            ErrorCode::OperationInvalidState => "operation.invalid_state",
+            ErrorCode::ServerDuplicate => "server.duplicate",
+            ErrorCode::ServerNotFound => "server.not_found",
+            ErrorCode::ScheduleNotFound => "schedule.not_found",
+            ErrorCode::ScheduleOverlap => "schedule.overlap",
+            ErrorCode::GroupDuplicate => "group.duplicate",
+            ErrorCode::GroupNotFound => "group.not_found",
+            ErrorCode::ComplianceProfileNotFound => "compliance.profile_not_found",
+            ErrorCode::TokenNotFound => "token.not_found",
+            ErrorCode::ShareNotFound => "share.not_found",
+            ErrorCode::GrantNotFound => "grant.not_found",
+            ErrorCode::UserDuplicate => "user.duplicate",
+            ErrorCode::UserNotFound => "user.not_found",
+            ErrorCode::ConfigSelfUrlMissing => "config.self_url_missing",
+            ErrorCode::ConfigOidcNotConfigured => "config.oidc_not_configured",
+            ErrorCode::ConfigSmtpNotConfigured => "config.smtp_not_configured",
+            ErrorCode::StandbyReadOnly => "standby.read_only",
+            ErrorCode::StandbyNotStandby => "standby.not_standby",
+            ErrorCode::StandbyLeaseHeld => "standby.lease_held",
+            ErrorCode::StorageDegraded => "storage.degraded",
+            ErrorCode::InternalDatabase => "internal.database",
+        }
+    }
+
+    /// The code spelled `code`, as `as_str` returns it.
+    pub fn parse(code: &str) -> Option<ErrorCode> {
+        ErrorCode::ALL.iter().copied().find(|known| known.as_str() == code)
+    }
+
+    /// Classify an error message produced by `IdracClient`.
+    pub fn from_idrac_error(message: &str) -> ErrorCode {
+        if message.starts_with("Failed to connect") {
+            ErrorCode::IdracUnreachable
+        } else if message.contains("HTTP 401") || message.starts_with(CREDENTIALS_REJECTED_PREFIX) {
+            ErrorCode::IdracAuthFailed
+        } else if message.starts_with(RATE_LIMITED_PREFIX) {
+            ErrorCode::IdracRateLimited
+        } else if message.starts_with(BIOS_SETTING_REJECTED_PREFIX) {
+            ErrorCode::ValidationInvalidValue
+        } else if message.starts_with("Invalid iDRAC response") {
+            ErrorCode::IdracInvalidResponse
+        } else if message.starts_with("Failed to set power state: HTTP 409") {
+            ErrorCode::PowerAlreadyInState
+        } else {
+            ErrorCode::IdracRequestFailed
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        ErrorCode::parse(&code).ok_or_else(|| serde::de::Error::custom(format!("unknown error code '{}'", code)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Variant names declared in `enum ErrorCode`, read from this file so a
+    /// variant left out of `ALL` is caught.
+    fn declared_variants() -> Vec<&'static str> {
+        let source = include_str!("errors.rs");
+        let start = source.find("pub enum ErrorCode {").unwrap();
+        let body = &source[start..];
+        let body = &body[body.find('{').unwrap() + 1..body.find('}').unwrap()];
+        body.split(',').map(str::trim).filter(|name| !name.is_empty()).collect()
+    }
+
+    #[test]
+    fn every_variant_is_listed() {
+        let listed: Vec<String> = ErrorCode::ALL.iter().map(|code| format!("{:?}", code)).collect();
+        assert_eq!(listed, declared_variants());
+    }
+
+    #[test]
+    fn codes_are_unique_and_well_formed() {
+        let mut seen = std::collections::HashSet::new();
+        for code in ErrorCode::ALL {
+            let text = code.as_str();
+            assert!(seen.insert(text), "{} is used twice", text);
+            let (area, name) = text.split_once('.').unwrap_or_else(|| panic!("{} has no area", text));
+            for part in [area, name] {
+                assert!(
+                    !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase() || c == '_'),
+                    "{} is not area.snake_case",
+                    text
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn codes_round_trip_through_serde() {
+        for &code in ErrorCode::ALL {
+            let json = serde_json::to_string(&code).unwrap();
+            assert_eq!(json, format!("\"{}\"", code));
+            assert_eq!(serde_json::from_str::<ErrorCode>(&json).unwrap(), code);
+            assert_eq!(ErrorCode::parse(code.as_str()), Some(code));
+        }
+        assert!(serde_json::from_str::<ErrorCode>("\"auth.no_such_code\"").is_err());
+    }
+
+    fn openapi_document() -> serde_json::Value {
+        serde_json::from_str(include_str!("../openapi.json")).expect("openapi.json is not valid JSON")
+    }
+
+    /// Every `$ref` in `value`, e.g. `#/components/examples/auth_invalid_token`.
+    fn references<'a>(value: &'a serde_json::Value, found: &mut Vec<&'a str>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(target) = map.get("$ref").and_then(|target| target.as_str()) {
+                    found.push(target);
+                }
+                map.values().for_each(|child| references(child, found));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|child| references(child, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn every_code_has_an_error_example_in_the_openapi_document() {
+        let document = openapi_document();
+        let examples = document["components"]["examples"].as_object().unwrap();
+        let mut referenced = Vec::new();
+        references(&document["paths"], &mut referenced);
+        references(&document["components"]["responses"], &mut referenced);
+
+        let mut documented = Vec::new();
+        for (name, example) in examples {
+            let value = &example["value"];
+            let text = value["error_code"].as_str().unwrap_or_else(|| panic!("example {} has no error_code", name));
+            let code = ErrorCode::parse(text).unwrap_or_else(|| panic!("example {} uses unknown code {}", name, text));
+            assert_eq!(value["success"], false, "example {}", name);
+            assert!(value["message"].as_str().is_some_and(|m| !m.is_empty()), "example {} has no message", name);
+            assert!(
+                referenced.contains(&format!("#/components/examples/{}", name).as_str()),
+                "example {} is not used by any response",
+                name
+            );
+            documented.push(code);
+        }
+        for code in ErrorCode::ALL {
+            assert!(documented.contains(code), "{} has no error example in openapi.json", code);
+        }
+    }
+
+    #[test]
+    fn the_openapi_error_code_schema_lists_every_code() {
+        let document = openapi_document();
+        let listed: Vec<&str> = document["components"]["schemas"]["ErrorCode"]["enum"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|code| code.as_str().unwrap())
+            .collect();
+        let expected: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        assert_eq!(listed, expected);
+    }
+
+    #[test]
+    fn idrac_errors_are_classified() {
+        let cases = [
+            ("Failed to connect to iDRAC: timed out", ErrorCode::IdracUnreachable),
+            ("Power action failed: HTTP 401", ErrorCode::IdracAuthFailed),
+            (CREDENTIALS_REJECTED_PREFIX, ErrorCode::IdracAuthFailed),
+            (RATE_LIMITED_PREFIX, ErrorCode::IdracRateLimited),
+            ("Invalid BIOS setting: BootMode must be one of Bios, Uefi", ErrorCode::ValidationInvalidValue),
+            ("Invalid iDRAC response: expected value", ErrorCode::IdracInvalidResponse),
+            ("Failed to set power state: HTTP 409 Conflict", ErrorCode::PowerAlreadyInState),
+            ("Failed to read sensors: HTTP 500", ErrorCode::IdracRequestFailed),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(ErrorCode::from_idrac_error(message), expected, "{}", message);
+        }
+    }
+}
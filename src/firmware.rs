@@ -0,0 +1,224 @@
+use futures_util::stream::{self, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::database::{ComplianceRequirement, FirmwareInventoryEntry};
+use crate::idrac::FirmwareComponent;
+use crate::state::AppState;
+
+/// Inventories are collected from at most this many servers at a time.
+const REFRESH_CONCURRENCY: usize = 4;
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Servers running one version of a component.
+#[derive(Debug, Serialize)]
+pub struct VersionServers {
+    pub version: String,
+    pub servers: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentReport {
+    pub component: String,
+    /// Newest version first.
+    pub versions: Vec<VersionServers>,
+    /// More than one version of this component is installed across the fleet.
+    pub drift: bool,
+    pub baseline: Option<String>,
+    /// Servers with a version older than `baseline`.
+    pub below_baseline: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FirmwareReport {
+    pub components: Vec<ComponentReport>,
+    /// Registered servers with nothing cached yet.
+    pub servers_without_inventory: Vec<String>,
+    /// When the stalest cached inventory was collected.
+    pub oldest_collected_at: Option<String>,
+}
+
+/// One component of a compliance profile checked against a server.
+#[derive(Debug, Serialize)]
+pub struct ComponentCompliance {
+    pub component: String,
+    /// `None` when the server has no component by that name.
+    pub current_version: Option<String>,
+    pub minimum_version: String,
+    pub recommended_version: Option<String>,
+    /// Installed and at least `minimum_version`.
+    pub compliant: bool,
+}
+
+/// Compare dotted firmware versions numerically where both sides are
+/// numbers (`2.19.1` < `2.100.0`), falling back to text comparison.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut left = a.split(['.', '-']);
+    let mut right = b.split(['.', '-']);
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) => {
+                let ordering = match (l.parse::<u64>(), r.parse::<u64>()) {
+                    (Ok(l), Ok(r)) => l.cmp(&r),
+                    _ => l.cmp(r),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// Build the per-component version matrix from cached inventory.
+pub fn build_report(
+    entries: Vec<FirmwareInventoryEntry>,
+    baselines: Vec<(String, String)>,
+    server_aliases: &[String],
+) -> FirmwareReport {
+    let baselines: HashMap<String, String> = baselines.into_iter().collect();
+    let oldest_collected_at = entries.iter().map(|e| e.collected_at.clone()).min();
+    let servers_without_inventory = server_aliases
+        .iter()
+        .filter(|alias| !entries.iter().any(|e| &e.server_alias == *alias))
+        .cloned()
+        .collect();
+
+    let mut by_component: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    for entry in entries {
+        by_component
+            .entry(entry.component)
+            .or_default()
+            .entry(entry.version)
+            .or_default()
+            .push(entry.server_alias);
+    }
+
+    let components = by_component
+        .into_iter()
+        .map(|(component, versions)| {
+            let mut versions: Vec<VersionServers> = versions
+                .into_iter()
+                .map(|(version, servers)| VersionServers { version, servers })
+                .collect();
+            versions.sort_by(|a, b| compare_versions(&b.version, &a.version));
+
+            let baseline = baselines.get(&component).cloned();
+            let mut below_baseline: Vec<String> = baseline
+                .as_deref()
+                .map(|baseline| {
+                    versions
+                        .iter()
+                        .filter(|v| compare_versions(&v.version, baseline) == Ordering::Less)
+                        .flat_map(|v| v.servers.iter().cloned())
+                        .collect()
+                })
+                .unwrap_or_default();
+            below_baseline.sort();
+            below_baseline.dedup();
+
+            ComponentReport {
+                drift: versions.len() > 1,
+                component,
+                versions,
+                baseline,
+                below_baseline,
+            }
+        })
+        .collect();
+
+    FirmwareReport {
+        components,
+        servers_without_inventory,
+        oldest_collected_at,
+    }
+}
+
+/// Check installed firmware against each requirement of a profile.
+/// Components are matched by name, ignoring case; when several are
+/// installed under one name (e.g. identical NICs) the oldest counts.
+pub fn check_compliance(
+    installed: &[FirmwareComponent],
+    requirements: Vec<ComplianceRequirement>,
+) -> Vec<ComponentCompliance> {
+    requirements
+        .into_iter()
+        .map(|requirement| {
+            let current_version = installed
+                .iter()
+                .filter(|c| c.name.eq_ignore_ascii_case(&requirement.component))
+                .map(|c| c.version.clone())
+                .min_by(|a, b| compare_versions(a, b));
+            let compliant = current_version
+                .as_deref()
+                .is_some_and(|current| compare_versions(current, &requirement.minimum_version) != Ordering::Less);
+            ComponentCompliance {
+                component: requirement.component,
+                current_version,
+                minimum_version: requirement.minimum_version,
+                recommended_version: requirement.recommended_version,
+                compliant,
+            }
+        })
+        .collect()
+}
+
+/// Collect and cache the firmware inventory of every server that can be
+/// polled.
+/// Returns each server's alias with the number of components stored or
+/// the reason it failed.
+pub async fn refresh_all(state: &AppState) -> Vec<(String, Result<usize, String>)> {
+    stream::iter(state.servers.pollable())
+        .map(|server| async move {
+            let result = match tokio::time::timeout(REFRESH_TIMEOUT, server.client.get_firmware_inventory()).await {
+                Ok(Ok(components)) => {
+                    let pairs: Vec<(String, String)> =
+                        components.into_iter().map(|c| (c.name, c.version)).collect();
+                    state
+                        .db
+                        .replace_firmware_inventory(&server.alias, &pairs)
+                        .map(|_| pairs.len())
+                        .map_err(|e| format!("Failed to cache inventory: {}", e))
+                }
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err(format!("Timed out after {}s", REFRESH_TIMEOUT.as_secs())),
+            };
+            (server.alias.clone(), result)
+        })
+        .buffer_unordered(REFRESH_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Run `refresh_all` as a tracked operation with one stage per server.
+pub async fn run_refresh_operation(state: AppState, operation_id: String) {
+    let results = refresh_all(&state).await;
+    let mut failures = 0;
+
+    for (alias, result) in &results {
+        let (stage, detail) = match result {
+            Ok(count) => ("refreshed", format!("{}: {} component(s)", alias, count)),
+            Err(e) => {
+                failures += 1;
+                warn!("Firmware inventory refresh for '{}' failed: {}", alias, e);
+                ("refresh_failed", format!("{}: {}", alias, e))
+            }
+        };
+        if let Err(e) = state.db.add_operation_stage(&operation_id, stage, Some(&detail), "running") {
+            warn!("Failed to update operation {}: {}", operation_id, e);
+        }
+    }
+
+    let status = if failures == 0 { "completed" } else { "needs_attention" };
+    let summary = format!("{} of {} server(s) refreshed", results.len() - failures, results.len());
+    info!("Firmware inventory refresh {}: {}", operation_id, summary);
+    if let Err(e) = state.db.add_operation_stage(&operation_id, "finished", Some(&summary), status) {
+        warn!("Failed to update operation {}: {}", operation_id, e);
+    }
+}
@@ -0,0 +1,384 @@
+//! Firmware updates rolled out over a group a batch at a time. Each member
+//! is put in maintenance, has the update staged and is restarted to run
+//! the job, and must then pass a health gate: power on, health `OK` and
+//! POST complete. Any member failing pauses the rollout once its batch is
+//! done, until it is resumed or cancelled.
+
+use futures_util::future::join_all;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::audit::AuditAction;
+use crate::boot::POST_COMPLETE_STATES;
+use crate::database::{Operation, ServerGroup};
+use crate::group_power_on::{member_timelines, stage_server, ServerTimeline};
+use crate::idrac::RestartType;
+use crate::operations::record_stage;
+use crate::servers::RegisteredServer;
+use crate::state::{AppEvent, AppState};
+
+/// Operation kind of a firmware rollout.
+pub const OPERATION_KIND: &str = "firmware_rollout";
+/// Status of a rollout stopped by a failed member.
+pub const STATUS_PAUSED: &str = "paused";
+
+const STAGE_REQUESTED: &str = "requested";
+const STAGE_CANCEL_REQUESTED: &str = "cancel_requested";
+
+/// Stages that settle a member. Resuming skips settled members, so ones
+/// that failed are left for an operator to look at.
+const OUTCOMES: &[&str] = &["updated", "update_failed", "gate_failed", "not_found"];
+
+pub const DEFAULT_BATCH_SIZE: u32 = 1;
+pub const MAX_BATCH_SIZE: u32 = 20;
+pub const DEFAULT_JOB_TIMEOUT_MINUTES: u32 = 60;
+pub const MAX_JOB_TIMEOUT_MINUTES: u32 = 240;
+pub const DEFAULT_GATE_TIMEOUT_MINUTES: u32 = 30;
+pub const MAX_GATE_TIMEOUT_MINUTES: u32 = 120;
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// What a rollout was asked to do, stored as the detail of its
+/// `requested` stage so the operation can resume after a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutPlan {
+    pub group_id: i64,
+    pub group: String,
+    /// Updated in this order.
+    pub members: Vec<String>,
+    /// Update package URL, or the path of a package already uploaded to
+    /// the iDRAC's firmware inventory.
+    pub image_uri: String,
+    pub batch_size: usize,
+    /// How long each member's update job may take, reboot included.
+    pub job_timeout_secs: u64,
+    /// How long each member may take to pass the health gate afterwards.
+    pub gate_timeout_secs: u64,
+    pub user_id: Option<i64>,
+}
+
+impl RolloutPlan {
+    pub fn new(
+        group: &ServerGroup,
+        image_uri: String,
+        batch_size: u32,
+        job_timeout_minutes: u32,
+        gate_timeout_minutes: u32,
+        user_id: i64,
+    ) -> Self {
+        RolloutPlan {
+            group_id: group.id,
+            group: group.name.clone(),
+            members: group.members.clone(),
+            image_uri,
+            batch_size: batch_size as usize,
+            job_timeout_secs: u64::from(job_timeout_minutes) * 60,
+            gate_timeout_secs: u64::from(gate_timeout_minutes) * 60,
+            user_id: Some(user_id),
+        }
+    }
+
+    /// The plan of a rollout operation.
+    pub fn of(operation: &Operation) -> Option<Self> {
+        let requested = operation.stages.iter().find(|stage| stage.name == STAGE_REQUESTED)?;
+        serde_json::from_str(requested.detail.as_deref()?).ok()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RolloutReport {
+    pub operation_id: String,
+    pub status: String,
+    pub plan: RolloutPlan,
+    pub servers: Vec<ServerTimeline>,
+}
+
+/// Split the operation's stages into one timeline per member.
+pub fn build_report(operation: &Operation, plan: &RolloutPlan) -> RolloutReport {
+    RolloutReport {
+        operation_id: operation.id.clone(),
+        status: operation.status.clone(),
+        plan: plan.clone(),
+        servers: member_timelines(operation, &plan.members, OUTCOMES),
+    }
+}
+
+/// Members with an outcome.
+fn settled(operation: &Operation) -> HashSet<String> {
+    operation
+        .stages
+        .iter()
+        .filter(|stage| OUTCOMES.contains(&stage.name.as_str()))
+        .filter_map(|stage| stage_server(stage.detail.as_deref()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Record the plan and roll the update out in the background.
+pub fn start(state: &AppState, plan: RolloutPlan) -> Result<String, String> {
+    let detail = serde_json::to_string(&plan).map_err(|e| e.to_string())?;
+    let operation_id = state
+        .db
+        .create_operation(OPERATION_KIND, None)
+        .map_err(|e| format!("Failed to track operation: {}", e))?;
+    record_stage(state, &operation_id, STAGE_REQUESTED, Some(&detail), "running");
+    tokio::spawn(run(state.clone(), operation_id.clone(), plan, HashSet::new()));
+    Ok(operation_id)
+}
+
+/// Continue a paused rollout with the members not settled yet.
+pub fn resume(state: &AppState, operation: &Operation, user_id: i64) -> Result<String, String> {
+    if operation.status != STATUS_PAUSED {
+        return Err(format!("Rollout {} is {}, not paused", operation.id, operation.status));
+    }
+    let plan = RolloutPlan::of(operation).ok_or("The stored plan could not be read")?;
+    let done = settled(operation);
+    let detail = format!(
+        "{} of {} server(s) left, resumed by user {}",
+        plan.members.len() - done.len(),
+        plan.members.len(),
+        user_id
+    );
+    record_stage(state, &operation.id, "resumed", Some(&detail), "running");
+    tokio::spawn(run(state.clone(), operation.id.clone(), plan, done));
+    Ok(detail)
+}
+
+/// Cancel a paused rollout at once, or a running one before its next
+/// batch. Updates already staged are left to finish: stopping a flash
+/// half way could leave a server unbootable.
+pub fn cancel(state: &AppState, operation: &Operation, user_id: i64) -> Result<String, String> {
+    let detail = format!("Cancelled by user {}", user_id);
+    match operation.status.as_str() {
+        STATUS_PAUSED => {
+            record_stage(state, &operation.id, "cancelled", Some(&detail), "cancelled");
+            Ok("Rollout cancelled".to_string())
+        }
+        "running" => {
+            record_stage(state, &operation.id, STAGE_CANCEL_REQUESTED, Some(&detail), "running");
+            Ok("Rollout will stop once the current batch is done".to_string())
+        }
+        status => Err(format!("Rollout {} is already {}", operation.id, status)),
+    }
+}
+
+/// Pick up rollouts left running by a previous process, skipping the
+/// members they had already settled. Paused ones stay paused.
+pub fn resume_interrupted(state: &AppState) {
+    let operations = match state.db.list_running_operations(OPERATION_KIND) {
+        Ok(operations) => operations,
+        Err(e) => {
+            warn!("Failed to look for interrupted firmware rollouts: {}", e);
+            return;
+        }
+    };
+
+    for operation in operations {
+        let Some(plan) = RolloutPlan::of(&operation) else {
+            record_stage(state, &operation.id, "abandoned", Some("The stored plan could not be read"), "failed");
+            continue;
+        };
+        let done = settled(&operation);
+        let detail = format!("{} of {} server(s) left", plan.members.len() - done.len(), plan.members.len());
+        info!("Resuming firmware rollout {} for '{}': {}", operation.id, plan.group, detail);
+        record_stage(state, &operation.id, "resumed", Some(&detail), "running");
+        tokio::spawn(run(state.clone(), operation.id, plan, done));
+    }
+}
+
+fn cancel_requested(state: &AppState, operation_id: &str) -> bool {
+    match state.db.get_operation(operation_id) {
+        Ok(Some(operation)) => {
+            // Only a request made since the latest resume counts.
+            let resumed = operation.stages.iter().rposition(|stage| stage.name == "resumed");
+            let cancelled = operation.stages.iter().rposition(|stage| stage.name == STAGE_CANCEL_REQUESTED);
+            cancelled > resumed
+        }
+        Ok(None) => true,
+        Err(e) => {
+            warn!("Failed to read firmware rollout {}: {}", operation_id, e);
+            false
+        }
+    }
+}
+
+async fn run(state: AppState, operation_id: String, plan: RolloutPlan, done: HashSet<String>) {
+    let pending: Vec<&String> = plan.members.iter().filter(|alias| !done.contains(*alias)).collect();
+    let batches: Vec<&[&String]> = pending.chunks(plan.batch_size.max(1)).collect();
+
+    for (i, batch) in batches.iter().enumerate() {
+        if cancel_requested(&state, &operation_id) {
+            finish(&state, &operation_id, &plan, "cancelled", "Cancelled before the next batch");
+            return;
+        }
+        let names: Vec<&str> = batch.iter().map(|alias| alias.as_str()).collect();
+        let detail = format!("batch {} of {}: {}", i + 1, batches.len(), names.join(", "));
+        record_stage(&state, &operation_id, "batch_started", Some(&detail), "running");
+
+        let outcomes = join_all(batch.iter().map(|alias| update_member(&state, &operation_id, &plan, alias))).await;
+        let mut failed = Vec::new();
+        for (alias, (outcome, detail)) in batch.iter().zip(outcomes) {
+            record_stage(&state, &operation_id, outcome, Some(&detail), "running");
+            if outcome != "updated" {
+                failed.push(alias.as_str());
+            }
+        }
+        if !failed.is_empty() {
+            let summary = format!("Paused after {} failed: {}", failed.len(), failed.join(", "));
+            finish(&state, &operation_id, &plan, STATUS_PAUSED, &summary);
+            return;
+        }
+    }
+
+    // Members that failed before a resume still need attention.
+    let updated = match state.db.get_operation(&operation_id) {
+        Ok(Some(operation)) => member_timelines(&operation, &plan.members, OUTCOMES)
+            .iter()
+            .filter(|server| server.outcome.as_deref() == Some("updated"))
+            .count(),
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to read firmware rollout {}: {}", operation_id, e);
+            return;
+        }
+    };
+    let summary = format!("{} of {} server(s) updated", updated, plan.members.len());
+    let status = if updated == plan.members.len() { "completed" } else { "needs_attention" };
+    finish(&state, &operation_id, &plan, status, &summary);
+}
+
+/// Record where the rollout stopped and publish every member's timeline.
+fn finish(state: &AppState, operation_id: &str, plan: &RolloutPlan, status: &str, summary: &str) {
+    info!("Firmware rollout {} for '{}': {}", operation_id, plan.group, summary);
+    let stage = match status {
+        "completed" | "needs_attention" => "finished",
+        status => status,
+    };
+    record_stage(state, operation_id, stage, Some(summary), status);
+
+    let servers = match state.db.get_operation(operation_id) {
+        Ok(Some(operation)) => member_timelines(&operation, &plan.members, OUTCOMES),
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to read firmware rollout {}: {}", operation_id, e);
+            return;
+        }
+    };
+    state.publish(AppEvent::FirmwareRollout {
+        operation_id: operation_id.to_string(),
+        group: plan.group.clone(),
+        status: status.to_string(),
+        servers,
+    });
+}
+
+/// Update one member inside its maintenance window. Returns the outcome
+/// stage and its detail.
+async fn update_member(state: &AppState, operation_id: &str, plan: &RolloutPlan, alias: &str) -> (&'static str, String) {
+    let Some(server) = state.servers.get(alias) else {
+        return ("not_found", format!("{}: no server with this alias", alias));
+    };
+    state
+        .servers
+        .begin_maintenance(alias, &format!("firmware rollout {}", operation_id));
+    let outcome = match update_and_gate(state, operation_id, plan, &server).await {
+        Ok(detail) => ("updated", format!("{}: {}", alias, detail)),
+        Err((outcome, e)) => (outcome, format!("{}: {}", alias, e)),
+    };
+    state.servers.end_maintenance(alias);
+    outcome
+}
+
+async fn update_and_gate(
+    state: &AppState,
+    operation_id: &str,
+    plan: &RolloutPlan,
+    server: &RegisteredServer,
+) -> Result<String, (&'static str, String)> {
+    let alias = server.alias.as_str();
+    let client = &server.client;
+    let update_failed = |e: String| ("update_failed", e);
+
+    let result = client.start_firmware_update(&plan.image_uri).await;
+    state.audit_with_details(
+        plan.user_id,
+        AuditAction::FirmwareUpdate,
+        client.base_url(),
+        &result.clone().map(|job_id| format!("Firmware update staged as job {}", job_id)),
+        &serde_json::json!({ "firmware_rollout": operation_id, "image_uri": plan.image_uri }),
+    );
+    let job_id = result.map_err(update_failed)?;
+    record_stage(state, operation_id, "update_staged", Some(&format!("{}: job {}", alias, job_id)), "running");
+
+    // The job runs while the host reboots.
+    let (action, result) = match client.get_power_state().await.map_err(update_failed)?.as_str() {
+        "Off" => ("PowerOn", client.power_on().await),
+        _ => {
+            let restart_type = RestartType::Graceful;
+            (restart_type.reset_type(), client.restart(restart_type).await)
+        }
+    };
+    state.record_server_power_action(alias, client, action, &result);
+    let message = result.map_err(update_failed)?;
+    record_stage(state, operation_id, "restarting", Some(&format!("{}: {}", alias, message)), "running");
+
+    let deadline = Instant::now() + Duration::from_secs(plan.job_timeout_secs);
+    loop {
+        match client.get_job(&job_id).await {
+            Ok(job) if job.succeeded() => break,
+            Ok(job) if job.is_finished() => {
+                return Err(update_failed(format!(
+                    "job {} ended {}: {}",
+                    job_id,
+                    job.state,
+                    job.message.unwrap_or_default()
+                )))
+            }
+            Ok(_) => {}
+            // Expected while the iDRAC applies the update.
+            Err(e) => warn!("Job {} on '{}' could not be read: {}", job_id, alias, e),
+        }
+        if Instant::now() + POLL_INTERVAL >= deadline {
+            return Err(update_failed(format!("job {} did not finish within {}s", job_id, plan.job_timeout_secs)));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    record_stage(state, operation_id, "job_completed", Some(&format!("{}: job {}", alias, job_id)), "running");
+
+    let deadline = Instant::now() + Duration::from_secs(plan.gate_timeout_secs);
+    loop {
+        let failing = match health_gate(server).await {
+            Ok(detail) => return Ok(detail),
+            Err(e) => e,
+        };
+        if Instant::now() + POLL_INTERVAL >= deadline {
+            return Err((
+                "gate_failed",
+                format!("health gate not passed within {}s: {}", plan.gate_timeout_secs, failing),
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Power on, health `OK` and POST complete, or what is still missing.
+/// A server that does not report boot progress passes on the other two.
+async fn health_gate(server: &RegisteredServer) -> Result<String, String> {
+    let power_state = server.client.get_power_state().await?;
+    if power_state != "On" {
+        return Err(format!("power state is {}", power_state));
+    }
+    let health = server.client.get_health().await?.health;
+    if health != "OK" {
+        return Err(format!("health is {}", health));
+    }
+    match server.client.get_boot_progress().await? {
+        Some(last_state) if POST_COMPLETE_STATES.contains(&last_state.as_str()) => {
+            Ok(format!("passed health gate at {}", last_state))
+        }
+        Some(last_state) => Err(format!("boot progress is {}", last_state)),
+        None => Ok("passed health gate; boot progress not reported".to_string()),
+    }
+}
@@ -0,0 +1,276 @@
+//! Firmware updates staged on an iDRAC and applied by a reboot at a
+//! planned time. When the time comes the server is put in maintenance and
+//! rebooted, and the update job is followed to its end as a
+//! `firmware_update` operation.
+
+use futures_util::future::join_all;
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::audit::AuditAction;
+use crate::database::{FirmwareUpdateSchedule, SQLITE_TIMESTAMP_FORMAT};
+use crate::firmware_rollout::DEFAULT_JOB_TIMEOUT_MINUTES;
+use crate::idrac::{JobStatus, RestartType};
+use crate::operations::record_stage;
+use crate::servers::RegisteredServer;
+use crate::state::AppState;
+
+/// Operation kind of a scheduled firmware update.
+pub const OPERATION_KIND: &str = "firmware_update";
+
+/// Job states of an update that is staged and waits for a reboot.
+const STAGED_STATES: &[&str] = &["New", "Downloaded", "Scheduling", "Scheduled"];
+const STAGE_RESTARTING: &str = "restarting";
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// A reboot found more than this late, e.g. because the app was down at
+/// its time, is skipped rather than done at an unplanned moment.
+const MAX_LATENESS_SECS: i64 = 15 * 60;
+
+/// Whether `job` is a firmware update still waiting to be applied.
+pub fn is_staged(job: &JobStatus) -> bool {
+    job.is_firmware_update() && STAGED_STATES.contains(&job.state.as_str())
+}
+
+/// A staged update, with the reboot planned to apply it if there is one.
+#[derive(Debug, Serialize)]
+pub struct PendingUpdate {
+    #[serde(flatten)]
+    pub job: JobStatus,
+    pub schedule: Option<FirmwareUpdateSchedule>,
+}
+
+/// The staged updates of one server, or why they could not be read.
+#[derive(Debug, Serialize)]
+pub struct ServerPendingUpdates {
+    pub server: String,
+    pub updates: Vec<PendingUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Read the job queue of each server concurrently and keep the staged
+/// firmware updates.
+pub async fn pending_updates(
+    servers: Vec<Arc<RegisteredServer>>,
+    schedules: Vec<FirmwareUpdateSchedule>,
+) -> Vec<ServerPendingUpdates> {
+    let schedules = &schedules;
+    let reads = servers.into_iter().map(|server| async move {
+        match server.client.list_jobs().await {
+            Ok(jobs) => ServerPendingUpdates {
+                updates: jobs
+                    .into_iter()
+                    .filter(is_staged)
+                    .map(|job| PendingUpdate {
+                        schedule: schedules
+                            .iter()
+                            .find(|s| s.status != "executed" && s.server_alias == server.alias && s.job_id == job.id)
+                            .cloned(),
+                        job,
+                    })
+                    .collect(),
+                server: server.alias.clone(),
+                error: None,
+            },
+            Err(e) => ServerPendingUpdates {
+                server: server.alias.clone(),
+                updates: Vec::new(),
+                error: Some(e),
+            },
+        }
+    });
+    join_all(reads).await
+}
+
+/// Reboot the server of a due schedule and follow its update job, then
+/// record the outcome on the schedule.
+pub async fn run(state: AppState, schedule: FirmwareUpdateSchedule) {
+    let (outcome, message) = match start(&state, &schedule).await {
+        Ok((server, operation_id)) => follow(&state, &schedule, &server, &operation_id).await,
+        Err(outcome) => outcome,
+    };
+    finish(&state, &schedule, outcome, &message);
+}
+
+/// Pick up schedules whose job was being followed when the app stopped.
+/// One stopped before its reboot is not rebooted now, at an unplanned
+/// moment, but marked failed.
+pub fn resume_interrupted(state: &AppState) {
+    let schedules = match state.db.list_firmware_update_schedules() {
+        Ok(schedules) => schedules,
+        Err(e) => {
+            warn!("Failed to look for interrupted firmware updates: {}", e);
+            return;
+        }
+    };
+
+    for schedule in schedules.into_iter().filter(|s| s.status == "running") {
+        let operation = schedule
+            .operation_id
+            .as_deref()
+            .and_then(|id| state.db.get_operation(id).ok().flatten());
+        let rebooted = operation
+            .as_ref()
+            .is_some_and(|operation| operation.stages.iter().any(|stage| stage.name == STAGE_RESTARTING));
+        let server = state.servers.get(&schedule.server_alias);
+
+        match (operation, server) {
+            (Some(operation), Some(server)) if rebooted => {
+                info!("Resuming firmware update {} of job {} on '{}'", operation.id, schedule.job_id, server.alias);
+                record_stage(state, &operation.id, "resumed", None, "running");
+                let state = state.clone();
+                tokio::spawn(async move {
+                    state
+                        .servers
+                        .begin_maintenance(&server.alias, &format!("firmware update job {}", schedule.job_id));
+                    let (outcome, message) = follow(&state, &schedule, &server, &operation.id).await;
+                    finish(&state, &schedule, outcome, &message);
+                });
+            }
+            (operation, _) => {
+                let message = "Interrupted by a restart of the app before the job finished";
+                if let Some(operation) = operation {
+                    record_stage(state, &operation.id, "abandoned", Some(message), "failed");
+                }
+                finish(state, &schedule, "failure", message);
+            }
+        }
+    }
+}
+
+fn finish(state: &AppState, schedule: &FirmwareUpdateSchedule, outcome: &str, message: &str) {
+    info!(
+        "Firmware update schedule {} (job {} on {}): {}",
+        schedule.id, schedule.job_id, schedule.server_alias, message
+    );
+    if let Err(e) = state.db.finish_firmware_update_schedule(schedule.id, outcome, message) {
+        warn!("Failed to record outcome of firmware update schedule {}: {}", schedule.id, e);
+    }
+}
+
+/// Check the job is still waiting, then put the server in maintenance and
+/// reboot it. Returns the server and the operation following the job, or
+/// the outcome when nothing was rebooted.
+async fn start(
+    state: &AppState,
+    schedule: &FirmwareUpdateSchedule,
+) -> Result<(Arc<RegisteredServer>, String), (&'static str, String)> {
+    let lateness = chrono::NaiveDateTime::parse_from_str(&schedule.reboot_at, SQLITE_TIMESTAMP_FORMAT)
+        .map(|reboot_at| (chrono::Utc::now().naive_utc() - reboot_at).num_seconds())
+        .unwrap_or(0);
+    if lateness > MAX_LATENESS_SECS {
+        return Err(("skipped", format!("Not rebooted: found {} minute(s) after its scheduled time", lateness / 60)));
+    }
+
+    let Some(server) = state.servers.get(&schedule.server_alias) else {
+        return Err(("failure", format!("No server with alias '{}'", schedule.server_alias)));
+    };
+    match server.client.get_job(&schedule.job_id).await {
+        Ok(job) if job.is_finished() => {
+            return Err(("skipped", format!("Not rebooted: job {} already ended {}", job.id, job.state)));
+        }
+        Ok(_) => {}
+        Err(e) => return Err(("failure", format!("Cannot read job {}: {}", schedule.job_id, e))),
+    }
+
+    let operation_id = state
+        .db
+        .create_operation(OPERATION_KIND, Some(&server.alias))
+        .map_err(|e| ("failure", format!("Failed to track operation: {}", e)))?;
+    if let Err(e) = state.db.set_firmware_update_operation(schedule.id, &operation_id) {
+        warn!("Failed to link firmware update schedule {} to {}: {}", schedule.id, operation_id, e);
+    }
+    let detail = format!("job {} scheduled for {}", schedule.job_id, schedule.reboot_at);
+    record_stage(state, &operation_id, "requested", Some(&detail), "running");
+    state
+        .servers
+        .begin_maintenance(&server.alias, &format!("firmware update job {}", schedule.job_id));
+
+    let client = &server.client;
+    // The job runs while the host reboots.
+    let (action, result) = match client.get_power_state().await {
+        Ok(power_state) if power_state == "Off" => ("PowerOn", client.power_on().await),
+        Ok(_) => {
+            let restart_type = RestartType::Graceful;
+            (restart_type.reset_type(), client.restart(restart_type).await)
+        }
+        Err(e) => ("GracefulRestart", Err(e)),
+    };
+    state.record_server_power_action(&server.alias, client, action, &result);
+    match result {
+        Ok(message) => {
+            record_stage(state, &operation_id, STAGE_RESTARTING, Some(&message), "running");
+            Ok((server, operation_id))
+        }
+        Err(e) => {
+            let e = format!("Reboot failed: {}", e);
+            state.servers.end_maintenance(&server.alias);
+            audit(state, schedule, &server, &operation_id, &Err(e.clone()));
+            record_stage(state, &operation_id, "failed", Some(&e), "failed");
+            Err(("failure", e))
+        }
+    }
+}
+
+/// Poll the job until it ends or `DEFAULT_JOB_TIMEOUT_MINUTES` pass, then
+/// take the server out of maintenance.
+async fn follow(
+    state: &AppState,
+    schedule: &FirmwareUpdateSchedule,
+    server: &RegisteredServer,
+    operation_id: &str,
+) -> (&'static str, String) {
+    let timeout = Duration::from_secs(u64::from(DEFAULT_JOB_TIMEOUT_MINUTES) * 60);
+    let deadline = Instant::now() + timeout;
+    let result = loop {
+        match server.client.get_job(&schedule.job_id).await {
+            Ok(job) if job.succeeded() => break Ok(format!("Job {} completed", job.id)),
+            Ok(job) if job.is_finished() => {
+                break Err(format!("Job {} ended {}: {}", job.id, job.state, job.message.unwrap_or_default()))
+            }
+            Ok(_) => {}
+            // Expected while the iDRAC applies the update.
+            Err(e) => warn!("Job {} on '{}' could not be read: {}", schedule.job_id, server.alias, e),
+        }
+        if Instant::now() + POLL_INTERVAL >= deadline {
+            break Err(format!("Job {} did not finish within {}s", schedule.job_id, timeout.as_secs()));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    };
+    state.servers.end_maintenance(&server.alias);
+    audit(state, schedule, server, operation_id, &result);
+
+    match result {
+        Ok(message) => {
+            record_stage(state, operation_id, "job_completed", Some(&message), "completed");
+            ("success", message)
+        }
+        Err(e) => {
+            record_stage(state, operation_id, "failed", Some(&e), "failed");
+            ("failure", e)
+        }
+    }
+}
+
+fn audit(
+    state: &AppState,
+    schedule: &FirmwareUpdateSchedule,
+    server: &RegisteredServer,
+    operation_id: &str,
+    result: &Result<String, String>,
+) {
+    state.audit_with_details(
+        schedule.created_by,
+        AuditAction::FirmwareUpdateApply,
+        server.client.base_url(),
+        result,
+        &serde_json::json!({
+            "firmware_update_schedule_id": schedule.id,
+            "job_id": schedule.job_id,
+            "operation_id": operation_id,
+        }),
+    );
+}
@@ -0,0 +1,139 @@
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::audit::AuditAction;
+use crate::database::ServerGroup;
+use crate::state::AppState;
+
+/// Settings applied to every member of a group. Absent parts are left
+/// alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupConfig {
+    /// Staged until each server's next reboot.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub bios_attributes: Map<String, Value>,
+    /// Applied immediately.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub idrac_attributes: Map<String, Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power_cap_watts: Option<u32>,
+}
+
+impl GroupConfig {
+    pub fn is_empty(&self) -> bool {
+        self.bios_attributes.is_empty() && self.idrac_attributes.is_empty() && self.power_cap_watts.is_none()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SettingResult {
+    /// `bios_attributes`, `idrac_attributes` or `power_cap_watts`, or
+    /// `server` when the member is no longer registered.
+    pub setting: &'static str,
+    pub success: bool,
+    pub message: String,
+}
+
+/// What happened on one member.
+#[derive(Debug, Serialize)]
+pub struct ServerApplyResult {
+    pub server: String,
+    /// `applied`, `partial` or `failed`.
+    pub status: &'static str,
+    pub settings: Vec<SettingResult>,
+}
+
+/// Record the job and apply `config` to the group's members in the
+/// background, one server at a time.
+pub fn start(state: &AppState, group: &ServerGroup, config: GroupConfig, user_id: i64) -> Result<String, String> {
+    let config_json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    let job_id = state
+        .db
+        .create_group_apply_job(&group.name, &config_json)
+        .map_err(|e| format!("Failed to track the job: {}", e))?;
+    tokio::spawn(run(state.clone(), job_id.clone(), group.members.clone(), config, user_id));
+    Ok(job_id)
+}
+
+async fn run(state: AppState, job_id: String, members: Vec<String>, config: GroupConfig, user_id: i64) {
+    let mut results: Vec<ServerApplyResult> = Vec::new();
+
+    for alias in members {
+        let result = apply_to_server(&state, &alias, &config, user_id).await;
+        results.push(result);
+        save(&state, &job_id, "running", &results);
+    }
+
+    let applied = results.iter().filter(|r| r.status == "applied").count();
+    let status = if applied == results.len() {
+        "completed"
+    } else if results.iter().all(|r| r.status == "failed") {
+        "failed"
+    } else {
+        "partial"
+    };
+    info!("Group apply job {}: applied to {} of {} server(s)", job_id, applied, results.len());
+    save(&state, &job_id, status, &results);
+}
+
+fn save(state: &AppState, job_id: &str, status: &str, results: &[ServerApplyResult]) {
+    let results = serde_json::to_string(results).unwrap_or_else(|_| "[]".to_string());
+    if let Err(e) = state.db.update_group_apply_job(job_id, status, &results) {
+        error!("Failed to update group apply job {}: {}", job_id, e);
+    }
+}
+
+async fn apply_to_server(state: &AppState, alias: &str, config: &GroupConfig, user_id: i64) -> ServerApplyResult {
+    let Some(server) = state.servers.get(alias) else {
+        return ServerApplyResult {
+            server: alias.to_string(),
+            status: "failed",
+            settings: vec![SettingResult {
+                setting: "server",
+                success: false,
+                message: "No server with this alias".to_string(),
+            }],
+        };
+    };
+
+    let mut settings = Vec::new();
+    if !config.bios_attributes.is_empty() {
+        let result = server.client.set_bios_attributes(&config.bios_attributes).await;
+        settings.push(("bios_attributes", result));
+    }
+    if !config.idrac_attributes.is_empty() {
+        let result = server.client.set_idrac_attributes(&config.idrac_attributes).await;
+        settings.push(("idrac_attributes", result));
+    }
+    if let Some(watts) = config.power_cap_watts {
+        let result = server.client.set_power_cap(Some(watts)).await;
+        settings.push(("power_cap_watts", result));
+    }
+
+    for (setting, result) in &settings {
+        let details = serde_json::json!({ "setting": setting, "server": alias });
+        state.audit_with_details(Some(user_id), AuditAction::GroupApplyConfig, server.client.base_url(), result, &details);
+    }
+
+    let succeeded = settings.iter().filter(|(_, result)| result.is_ok()).count();
+    ServerApplyResult {
+        server: alias.to_string(),
+        status: match succeeded {
+            n if n == settings.len() => "applied",
+            0 => "failed",
+            _ => "partial",
+        },
+        settings: settings
+            .into_iter()
+            .map(|(setting, result)| {
+                let success = result.is_ok();
+                SettingResult {
+                    setting,
+                    success,
+                    message: result.unwrap_or_else(|e| e),
+                }
+            })
+            .collect(),
+    }
+}
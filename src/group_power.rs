@@ -0,0 +1,217 @@
+use chrono::{Duration as ChronoDuration, Utc};
+use futures_util::stream::{self, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::database::{PowerPeak, ServerGroup, SQLITE_TIMESTAMP_FORMAT};
+use crate::state::{AppEvent, AppState};
+
+/// Servers are sampled at most this many at a time.
+const SAMPLE_CONCURRENCY: usize = 8;
+const SAMPLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A sample older than this many sampling intervals no longer counts as
+/// the server's current draw.
+const STALE_AFTER_INTERVALS: u64 = 3;
+
+/// One member's share of a group's draw.
+#[derive(Debug, Serialize)]
+pub struct ServerDraw {
+    pub server: String,
+    /// `None` when the member is not a registered server anymore.
+    pub name: Option<String>,
+    /// `None` when there is no recent sample.
+    pub watts: Option<u32>,
+    pub sampled_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupPowerSummary {
+    pub group_id: i64,
+    pub name: String,
+    /// Sum of the latest samples of members with a recent one.
+    pub total_watts: u64,
+    pub servers: Vec<ServerDraw>,
+    /// Members without a recent sample; `total_watts` excludes them.
+    pub unknown_draw: Vec<String>,
+    /// Highest combined draw of the members within `window_hours`.
+    pub peak: Option<PowerPeak>,
+    pub window_hours: u32,
+    pub power_budget_watts: Option<u32>,
+    /// Budget minus current draw; negative when over budget.
+    pub headroom_watts: Option<i64>,
+    pub over_budget: bool,
+}
+
+/// Group power state that `/api/alerts` reports while it lasts.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetExceeded {
+    pub group_id: i64,
+    pub group: String,
+    pub total_watts: u64,
+    pub budget_watts: u32,
+    pub since: String,
+}
+
+/// Oldest sample that still counts as current.
+fn stale_cutoff(state: &AppState) -> String {
+    let every = state.config.power_sample_interval_secs.max(1);
+    (Utc::now() - ChronoDuration::seconds((every * STALE_AFTER_INTERVALS) as i64))
+        .format(SQLITE_TIMESTAMP_FORMAT)
+        .to_string()
+}
+
+/// Current draw, per-member breakdown and peak of `group` over the last
+/// `window_hours`.
+pub fn summarize(state: &AppState, group: &ServerGroup, window_hours: u32) -> Result<GroupPowerSummary, String> {
+    let latest: HashMap<String, (u32, String)> = state
+        .db
+        .latest_power_samples(&stale_cutoff(state))
+        .map_err(|e| format!("Failed to read power samples: {}", e))?
+        .into_iter()
+        .map(|sample| (sample.server_alias, (sample.watts, sample.sampled_at)))
+        .collect();
+
+    let since = (Utc::now() - ChronoDuration::hours(window_hours as i64))
+        .format(SQLITE_TIMESTAMP_FORMAT)
+        .to_string();
+    let peak = state
+        .db
+        .peak_power_total(&group.members, &since)
+        .map_err(|e| format!("Failed to read power history: {}", e))?;
+
+    let mut total_watts = 0u64;
+    let mut unknown_draw = Vec::new();
+    let servers = group
+        .members
+        .iter()
+        .map(|alias| {
+            let sample = latest.get(alias);
+            match sample {
+                Some((watts, _)) => total_watts += *watts as u64,
+                None => unknown_draw.push(alias.clone()),
+            }
+            ServerDraw {
+                server: alias.clone(),
+                name: state.servers.get(alias).map(|server| server.name.clone()),
+                watts: sample.map(|(watts, _)| *watts),
+                sampled_at: sample.map(|(_, sampled_at)| sampled_at.clone()),
+            }
+        })
+        .collect();
+
+    let headroom_watts = group.power_budget_watts.map(|budget| budget as i64 - total_watts as i64);
+    Ok(GroupPowerSummary {
+        group_id: group.id,
+        name: group.name.clone(),
+        total_watts,
+        servers,
+        unknown_draw,
+        peak,
+        window_hours,
+        power_budget_watts: group.power_budget_watts,
+        headroom_watts,
+        over_budget: headroom_watts.is_some_and(|headroom| headroom < 0),
+    })
+}
+
+/// Read the current draw of every registered server and store the round.
+/// Servers that fail are logged and left out, so they show up as unknown.
+pub async fn sample_all(state: &AppState) -> Result<Vec<(String, u32)>, String> {
+    let sampled_at = Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    let samples: Vec<(String, u32)> = stream::iter(state.servers.pollable())
+        .map(|server| async move {
+            match tokio::time::timeout(SAMPLE_TIMEOUT, server.client.get_power_consumed_watts()).await {
+                Ok(Ok(watts)) => Some((server.alias.clone(), watts)),
+                Ok(Err(e)) => {
+                    warn!("Power sample of '{}' failed: {}", server.alias, e);
+                    None
+                }
+                Err(_) => {
+                    warn!("Power sample of '{}' timed out after {}s", server.alias, SAMPLE_TIMEOUT.as_secs());
+                    None
+                }
+            }
+        })
+        .buffer_unordered(SAMPLE_CONCURRENCY)
+        .filter_map(|sample| async move { sample })
+        .collect()
+        .await;
+
+    state
+        .db
+        .record_power_samples(&sampled_at, &samples)
+        .map_err(|e| format!("Failed to store power samples: {}", e))?;
+    Ok(samples)
+}
+
+/// Compare every budgeted group with its current draw. A group crossing
+/// its budget publishes one `power_budget_exceeded` event; it is reported
+/// again only after dropping back under budget first. A group only counts
+/// as back under budget once every member reports again.
+pub fn check_budgets(state: &AppState) -> Result<(), String> {
+    let groups = state
+        .db
+        .list_server_groups()
+        .map_err(|e| format!("Failed to list groups: {}", e))?;
+
+    let mut exceeded = state.power_budget_exceeded.write().unwrap();
+    let previous = std::mem::take(&mut *exceeded);
+    for (group, budget_watts) in groups
+        .iter()
+        .filter_map(|group| group.power_budget_watts.map(|budget| (group, budget)))
+    {
+        let summary = match summarize(state, group, 1) {
+            Ok(summary) => summary,
+            Err(e) => {
+                warn!("Budget check of group '{}' failed: {}", group.name, e);
+                if let Some(already) = previous.get(&group.id) {
+                    exceeded.insert(group.id, already.clone());
+                }
+                continue;
+            }
+        };
+        if !summary.over_budget {
+            match previous.get(&group.id) {
+                // Members that stopped reporting may still be drawing power.
+                Some(already) if !summary.unknown_draw.is_empty() => {
+                    exceeded.insert(group.id, already.clone());
+                }
+                Some(_) => info!("Group '{}' is back within its {} W budget", group.name, budget_watts),
+                None => {}
+            }
+            continue;
+        }
+
+        let since = match previous.get(&group.id) {
+            Some(already) => already.since.clone(),
+            None => {
+                warn!(
+                    "Group '{}' draws {} W, over its {} W budget",
+                    group.name, summary.total_watts, budget_watts
+                );
+                state.publish(AppEvent::PowerBudgetExceeded {
+                    group_id: group.id,
+                    group: group.name.clone(),
+                    total_watts: summary.total_watts,
+                    budget_watts,
+                    severity: "Warning".to_string(),
+                });
+                Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string()
+            }
+        };
+        exceeded.insert(
+            group.id,
+            BudgetExceeded {
+                group_id: group.id,
+                group: group.name.clone(),
+                total_watts: summary.total_watts,
+                budget_watts,
+                since,
+            },
+        );
+    }
+    Ok(())
+}
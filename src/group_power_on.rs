@@ -0,0 +1,257 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::audit::AuditAction;
+use crate::database::{Operation, ServerGroup};
+use crate::operations::record_stage;
+use crate::state::{AppEvent, AppState};
+
+/// Operation kind of a group power-on.
+pub const OPERATION_KIND: &str = "group_power_on";
+
+const STAGE_REQUESTED: &str = "requested";
+const STAGE_WAITING: &str = "waiting_for_bmc";
+const STAGE_REACHABLE: &str = "bmc_reachable";
+
+/// Stages that settle a member; members without one are still to do when
+/// an interrupted operation resumes.
+const OUTCOMES: &[&str] = &["powered_on", "already_on", "power_on_failed", "unreachable", "not_found"];
+
+pub const DEFAULT_RECOVERY_WINDOW_MINUTES: u32 = 15;
+pub const MAX_RECOVERY_WINDOW_MINUTES: u32 = 120;
+pub const MAX_STAGGER_SECS: u32 = 600;
+const BMC_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// What a group power-on was asked to do, stored as the detail of its
+/// `requested` stage so the operation can resume after a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerOnPlan {
+    pub group_id: i64,
+    pub group: String,
+    /// Powered on in this order.
+    pub members: Vec<String>,
+    /// Wait for unreachable BMCs, as after a site power outage, instead of
+    /// failing them straight away.
+    pub ac_recovery: bool,
+    /// How long to wait for each member's BMC.
+    pub recovery_window_secs: u64,
+    /// Pause after each power-on command before the next member.
+    pub stagger_secs: u64,
+    pub user_id: Option<i64>,
+}
+
+impl PowerOnPlan {
+    pub fn new(group: &ServerGroup, ac_recovery: bool, recovery_window_minutes: u32, stagger_secs: u32, user_id: i64) -> Self {
+        PowerOnPlan {
+            group_id: group.id,
+            group: group.name.clone(),
+            members: group.members.clone(),
+            ac_recovery,
+            recovery_window_secs: u64::from(recovery_window_minutes) * 60,
+            stagger_secs: u64::from(stagger_secs),
+            user_id: Some(user_id),
+        }
+    }
+
+    /// The plan of a group power-on operation.
+    pub fn of(operation: &Operation) -> Option<Self> {
+        let requested = operation.stages.iter().find(|stage| stage.name == STAGE_REQUESTED)?;
+        serde_json::from_str(requested.detail.as_deref()?).ok()
+    }
+}
+
+/// Server a per-member stage is about; its detail is `alias` or
+/// `alias: message`.
+pub fn stage_server(detail: Option<&str>) -> Option<&str> {
+    detail.map(|detail| detail.split_once(": ").map_or(detail, |(alias, _)| alias))
+}
+
+/// Record the plan and power the members on in the background.
+pub fn start(state: &AppState, plan: PowerOnPlan) -> Result<String, String> {
+    let detail = serde_json::to_string(&plan).map_err(|e| e.to_string())?;
+    let operation_id = state
+        .db
+        .create_operation(OPERATION_KIND, None)
+        .map_err(|e| format!("Failed to track operation: {}", e))?;
+    record_stage(state, &operation_id, STAGE_REQUESTED, Some(&detail), "running");
+    tokio::spawn(run(state.clone(), operation_id.clone(), plan, HashSet::new()));
+    Ok(operation_id)
+}
+
+/// Pick up group power-ons left running by a previous process, skipping
+/// the members they had already settled.
+pub fn resume_interrupted(state: &AppState) {
+    let operations = match state.db.list_running_operations(OPERATION_KIND) {
+        Ok(operations) => operations,
+        Err(e) => {
+            warn!("Failed to look for interrupted group power-ons: {}", e);
+            return;
+        }
+    };
+
+    for operation in operations {
+        let Some(plan) = PowerOnPlan::of(&operation) else {
+            record_stage(state, &operation.id, "abandoned", Some("The stored plan could not be read"), "failed");
+            continue;
+        };
+        let done: HashSet<String> = operation
+            .stages
+            .iter()
+            .filter(|stage| OUTCOMES.contains(&stage.name.as_str()))
+            .filter_map(|stage| stage_server(stage.detail.as_deref()))
+            .map(str::to_string)
+            .collect();
+        let detail = format!("{} of {} server(s) left", plan.members.len() - done.len(), plan.members.len());
+        info!("Resuming group power-on {} for '{}': {}", operation.id, plan.group, detail);
+        record_stage(state, &operation.id, "resumed", Some(&detail), "running");
+        tokio::spawn(run(state.clone(), operation.id, plan, done));
+    }
+}
+
+async fn run(state: AppState, operation_id: String, plan: PowerOnPlan, done: HashSet<String>) {
+    let pending: Vec<&String> = plan.members.iter().filter(|alias| !done.contains(*alias)).collect();
+    for (i, alias) in pending.iter().enumerate() {
+        let (outcome, detail) = power_on_member(&state, &operation_id, &plan, alias).await;
+        record_stage(&state, &operation_id, outcome, Some(&detail), "running");
+        if outcome == "powered_on" && plan.stagger_secs > 0 && i + 1 < pending.len() {
+            tokio::time::sleep(Duration::from_secs(plan.stagger_secs)).await;
+        }
+    }
+
+    let report = match state.db.get_operation(&operation_id) {
+        Ok(Some(operation)) => build_report(&operation, &plan),
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to read group power-on {}: {}", operation_id, e);
+            return;
+        }
+    };
+    let powered = report
+        .servers
+        .iter()
+        .filter(|server| matches!(server.outcome.as_deref(), Some("powered_on" | "already_on")))
+        .count();
+    let summary = format!("{} of {} server(s) on", powered, report.servers.len());
+    let status = if powered == report.servers.len() { "completed" } else { "needs_attention" };
+    info!("Group power-on {} for '{}': {}", operation_id, plan.group, summary);
+    record_stage(&state, &operation_id, "finished", Some(&summary), status);
+
+    state.publish(AppEvent::GroupPowerOn {
+        operation_id,
+        group: plan.group,
+        status: status.to_string(),
+        servers: report.servers,
+    });
+}
+
+/// Power one member on, first waiting up to the recovery window for its
+/// BMC to answer in `ac_recovery` mode. Returns the outcome stage and its
+/// detail.
+async fn power_on_member(state: &AppState, operation_id: &str, plan: &PowerOnPlan, alias: &str) -> (&'static str, String) {
+    let Some(server) = state.servers.get(alias) else {
+        return ("not_found", format!("{}: no server with this alias", alias));
+    };
+    let deadline = Instant::now() + Duration::from_secs(plan.recovery_window_secs);
+    let mut waiting = false;
+
+    loop {
+        match server.client.get_power_state().await {
+            Ok(power_state) => {
+                if waiting {
+                    record_stage(state, operation_id, STAGE_REACHABLE, Some(alias), "running");
+                }
+                if power_state == "On" {
+                    return ("already_on", alias.to_string());
+                }
+                let result = server.client.power_on().await;
+                state.record_server_power_action(alias, &server.client, "PowerOn", &result);
+                state.audit_with_details(
+                    plan.user_id,
+                    AuditAction::PowerOn,
+                    server.client.base_url(),
+                    &result,
+                    &serde_json::json!({ "group_power_on": operation_id, "ac_recovery": plan.ac_recovery }),
+                );
+                return match result {
+                    Ok(_) => ("powered_on", alias.to_string()),
+                    Err(e) => ("power_on_failed", format!("{}: {}", alias, e)),
+                };
+            }
+            Err(e) if plan.ac_recovery && Instant::now() < deadline => {
+                if !waiting {
+                    info!("Waiting up to {}s for the BMC of '{}': {}", plan.recovery_window_secs, alias, e);
+                    record_stage(state, operation_id, STAGE_WAITING, Some(&format!("{}: {}", alias, e)), "running");
+                    waiting = true;
+                }
+                tokio::time::sleep(BMC_POLL_INTERVAL.min(deadline - Instant::now())).await;
+            }
+            Err(e) => return ("unreachable", format!("{}: {}", alias, e)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineEvent {
+    pub stage: String,
+    pub at: String,
+    pub detail: Option<String>,
+}
+
+/// What happened to one member, in order.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerTimeline {
+    pub server: String,
+    /// `None` while the member has not been settled.
+    pub outcome: Option<String>,
+    pub events: Vec<TimelineEvent>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupPowerOnReport {
+    pub operation_id: String,
+    pub status: String,
+    pub plan: PowerOnPlan,
+    pub servers: Vec<ServerTimeline>,
+}
+
+/// Split the stages of a per-member operation into one timeline per
+/// member; `outcomes` are the stages that settle a member.
+pub fn member_timelines(operation: &Operation, members: &[String], outcomes: &[&str]) -> Vec<ServerTimeline> {
+    members
+        .iter()
+        .map(|alias| {
+            let events: Vec<TimelineEvent> = operation
+                .stages
+                .iter()
+                .filter(|stage| stage.name != STAGE_REQUESTED && stage_server(stage.detail.as_deref()) == Some(alias.as_str()))
+                .map(|stage| TimelineEvent {
+                    stage: stage.name.clone(),
+                    at: stage.at.clone(),
+                    detail: stage.detail.as_deref().and_then(|d| d.split_once(": ")).map(|(_, message)| message.to_string()),
+                })
+                .collect();
+            ServerTimeline {
+                server: alias.clone(),
+                outcome: events
+                    .iter()
+                    .rev()
+                    .find(|event| outcomes.contains(&event.stage.as_str()))
+                    .map(|event| event.stage.clone()),
+                events,
+            }
+        })
+        .collect()
+}
+
+/// Split the operation's stages into one timeline per member.
+pub fn build_report(operation: &Operation, plan: &PowerOnPlan) -> GroupPowerOnReport {
+    GroupPowerOnReport {
+        operation_id: operation.id.clone(),
+        status: operation.status.clone(),
+        plan: plan.clone(),
+        servers: member_timelines(operation, &plan.members, OUTCOMES),
+    }
+}
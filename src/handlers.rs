@@ -1,269 +1,7407 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
 use actix_session::Session;
 use serde::{Deserialize, Serialize};
-use log::info;
+use log::{error, info, warn};
+use std::collections::BTreeMap;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
 
-use crate::database::Database;
-use crate::idrac::IdracClient;
+use crate::api::{
+    ApiResponse, FieldErrorResponse, HostsThisAppAcknowledgment, LoginRequest, NmiRequest, OperationResponse, OperationStartedResponse,
+    OsHealth, PageQuery, PowerOnQuery, PowerOnVerifiedResponse, RelatedEvent, ServerByHostQuery, ServerByHostResponse, ServerResponse,
+    ServerSummary, ShutdownRequest, StatusResponse,
+};
+use crate::assets;
+use crate::audit::AuditAction;
+use crate::boot::{self, BootReport};
+use crate::changes::Change;
+use crate::correlation;
+use crate::csv_export::{self, Cell, Column, CsvFormat, CsvQuery};
+use crate::break_glass::{self, BREAK_GLASS_USERNAME};
+use crate::database::{
+    self, ApiToken, ApiUsage, AuditEntry, AuditStatistics, ComplianceRequirement, FirmwareUpdateSchedule, GroupApplyJob, HealthReport, Keyset, MetricSample, OneShotSchedule,
+    NewShare, Operation, PingSample, PowerBaseline, PowerEvent, PowerCapSchedule, SelRecord, ServerGrant, ServerGroup, Share, TariffWindow, User, UserRole, UserSummary, WriteFailure, SQLITE_TIMESTAMP_FORMAT,
+};
+use crate::errors::ErrorCode;
+use crate::firmware::{self, ComponentCompliance, FirmwareReport};
+use crate::firmware_rollout::{self, RolloutPlan, RolloutReport};
+use crate::firmware_schedule::{self, ServerPendingUpdates};
+use crate::group_apply::{self, GroupConfig};
+use crate::group_power::{self, BudgetExceeded, GroupPowerSummary};
+use crate::group_power_on::{self, GroupPowerOnReport, PowerOnPlan};
+use crate::idrac::{
+    AlertFilter, BootOption, ComponentHealth, IdracClient, IdracPrivilege, IdracUser, IdracUserUpdate, NicMode, NicSelection,
+    PayloadStats, ProfileType, RawRedfishResponse, RestartType, ServiceModuleStatus, SslCertInfo, SystemProfile,
+};
+use crate::ldap::{self, LdapConfig, LdapFailure};
+use crate::logs::{self, LogFilter, LogRecord};
+use crate::oidc::{OidcConfig, OidcFailure, OidcIdentity, PendingLogin};
+use crate::operations::{self, EscalationMode};
+use crate::os_health;
+use crate::pagination::{self, Page};
+use crate::power_ws;
+use crate::password_reset;
+use crate::ping::ConnectivityAlert;
+use crate::power_anomaly::{self, PowerAnomaly};
+use crate::psu::PsuIssue;
+use crate::retention::RetentionPolicy;
+use crate::rollups;
+use crate::schedule::{one_shot_audit_name, parse_cron, ONE_SHOT_ACTIONS};
+use crate::scrub;
+use crate::secret::SecretString;
+use crate::server_import::{self, ImportOptions, ImportReport};
+use crate::servers::{RegisteredServer, DEFAULT_SERVER_ALIAS};
+use crate::shares::{self, ServerSnapshot, ShareViewer};
+use crate::state::{AppEvent, AppState, ClockOffset};
+use crate::tariff::{self, TariffStatus};
+use crate::tasks;
+use crate::tokens::{self, TokenScope};
+use crate::validation::{
+    normalize_compliance_profile_name, normalize_compliance_requirements, normalize_email, normalize_group_members, normalize_group_name,
+    normalize_os_health_url, normalize_share_name, normalize_share_scope, normalize_token_name, normalize_username, parse_timestamp, validate_idrac_username,
+    validate_new_server, validate_oem_action_path, validate_preferences, validate_share_passcode, FieldError,
+};
+
+/// Time the connection test after a credentials update may take.
+const CREDENTIALS_TEST_TIMEOUT: Duration = Duration::from_secs(20);
+const AUDIT_DEFAULT_LIMIT: u32 = 100;
+const AUDIT_MAX_LIMIT: u32 = 1000;
+const AUDIT_EXPORT_DEFAULT_LIMIT: u32 = 100;
+const AUDIT_EXPORT_MAX_LIMIT: u32 = 1000;
+const CHANGES_DEFAULT_LIMIT: u32 = 20;
+const CHANGES_MAX_LIMIT: u32 = 200;
+const EXPIRATIONS_DEFAULT_DAYS: u32 = 7;
+const EXPIRATIONS_MAX_DAYS: u32 = 365;
+const POWER_ON_VERIFY_DEFAULT_TIMEOUT_SECS: u64 = 120;
+const POWER_ON_VERIFY_MAX_TIMEOUT_SECS: u64 = 900;
+const SHUTDOWN_ESCALATE_MAX_SECS: u64 = 24 * 60 * 60;
+const GROUP_POWER_DEFAULT_WINDOW_HOURS: u32 = 24;
+const GROUP_POWER_MAX_WINDOW_HOURS: u32 = 90 * 24;
+const METRIC_REPORT_MIN_INTERVAL_SECS: u32 = 5;
+const METRIC_REPORT_MAX_INTERVAL_SECS: u32 = 24 * 60 * 60;
+const METRIC_SAMPLES_DEFAULT_LIMIT: u32 = 1000;
+const METRIC_SAMPLES_MAX_LIMIT: u32 = 10000;
+const BOOT_REPORT_DEFAULT_LIMIT: u32 = 10;
+const BOOT_REPORT_MAX_LIMIT: u32 = 100;
+const SEL_DEFAULT_LIMIT: u32 = 100;
+const IDRAC_LOGS_DEFAULT_LIMIT: u32 = 100;
+const IDRAC_LOGS_MAX_LIMIT: u32 = 1000;
+const SEL_MAX_LIMIT: u32 = 1000;
+const SERVERS_DEFAULT_LIMIT: u32 = 100;
+const SERVERS_MAX_LIMIT: u32 = 1000;
+const HEALTH_REPORTS_DEFAULT_LIMIT: u32 = 100;
+const HEALTH_REPORTS_MAX_LIMIT: u32 = 1000;
+const POWER_HISTORY_DEFAULT_LIMIT: u32 = 100;
+const POWER_HISTORY_MAX_LIMIT: u32 = 1000;
+const USAGE_DEFAULT_HOURS: u32 = 24;
+const USAGE_MAX_HOURS: u32 = 90 * 24;
 
 #[derive(Deserialize)]
-pub struct LoginRequest {
+pub struct RegisterRequest {
     pub username: String,
     pub password: String,
+    pub confirm_password: String,
 }
 
 #[derive(Deserialize)]
-pub struct RegisterRequest {
+pub struct PasswordResetRequest {
+    /// Username or email address.
     pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct CompletePasswordResetRequest {
+    pub token: String,
     pub password: String,
     pub confirm_password: String,
 }
 
+#[derive(Deserialize)]
+pub struct UserEmailRequest {
+    /// `null` removes the address.
+    pub email: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct VirtualMediaBootOnceRequest {
+    pub image_url: String,
+    pub restart_type: RestartType,
+    #[serde(default)]
+    pub i_understand_this_hosts_the_controller: bool,
+}
+
+#[derive(Deserialize)]
+pub struct AlertFiltersRequest {
+    pub filters: Vec<AlertFilter>,
+}
+
+#[derive(Deserialize)]
+pub struct BiosAttributeRequest {
+    pub attribute: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+pub struct PostWatchdogRequest {
+    pub enabled: bool,
+    pub timeout_minutes: u32,
+}
+
+#[derive(Deserialize)]
+pub struct SystemProfileRequest {
+    pub profile: ProfileType,
+}
+
+#[derive(Deserialize)]
+pub struct CreateIdracUserRequest {
+    pub username: String,
+    pub password: String,
+    pub privilege: IdracPrivilege,
+}
+
+#[derive(Deserialize)]
+pub struct NicModeRequest {
+    pub mode: NicMode,
+}
+
+#[derive(Deserialize)]
+pub struct CreateServerRequest {
+    pub name: String,
+    pub host: String,
+    pub username: String,
+    pub password: SecretString,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub location: Option<String>,
+    pub default_power_cap_watts: Option<u32>,
+    #[serde(default)]
+    pub hosts_this_app: bool,
+    /// See `OsHealthRequest`.
+    pub os_health_url: Option<String>,
+    #[serde(default)]
+    pub os_health_insecure: bool,
+    pub os_health_token: Option<SecretString>,
+}
+
+/// Body of `PATCH /api/servers/{alias}/credentials`; at least one field.
+#[derive(Deserialize)]
+pub struct UpdateCredentialsRequest {
+    pub username: Option<String>,
+    pub password: Option<SecretString>,
+}
+
+/// Body of `PUT /api/servers/{alias}/os-health`. Without a `url` the
+/// server's OS is no longer probed.
+#[derive(Deserialize)]
+pub struct OsHealthRequest {
+    pub url: Option<String>,
+    /// Accept any TLS certificate from `url`.
+    #[serde(default)]
+    pub insecure: bool,
+    /// Sent as a bearer token with each probe.
+    pub token: Option<SecretString>,
+}
+
+/// Body of `PUT /api/servers/{alias}/ntp`: up to three NTP servers, tried
+/// in order.
+#[derive(Deserialize)]
+pub struct NtpRequest {
+    #[serde(default)]
+    pub servers: Vec<String>,
+    /// Defaults to `true`; `false` turns NTP off and ignores `servers`.
+    pub enabled: Option<bool>,
+}
+
+/// Body of `POST /api/groups` and `PUT /api/groups/{id}`. A PUT replaces
+/// members and budget; the name is fixed at creation.
+#[derive(Deserialize)]
+pub struct GroupRequest {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+    pub power_budget_watts: Option<u32>,
+}
+
+/// Body of `POST /api/groups/{id}/power/on`.
+#[derive(Deserialize, Default)]
+pub struct GroupPowerOnRequest {
+    /// Wait for BMCs that are still booting after a power outage.
+    #[serde(default)]
+    pub ac_recovery: bool,
+    pub recovery_window_minutes: Option<u32>,
+    #[serde(default)]
+    pub stagger_secs: u32,
+}
+
+/// Body of `POST /api/groups/{id}/firmware-update`; exactly one of
+/// `image_uri` and `component` is required.
+#[derive(Deserialize)]
+pub struct GroupFirmwareUpdateRequest {
+    /// URL the iDRACs download the update package from.
+    pub image_uri: Option<String>,
+    /// Id of a package already uploaded to the firmware inventory.
+    pub component: Option<String>,
+    pub batch_size: Option<u32>,
+    pub job_timeout_minutes: Option<u32>,
+    pub gate_timeout_minutes: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct TelemetryDefinitionRequest {
+    pub metrics: Vec<String>,
+    pub report_interval_seconds: u32,
+}
+
+#[derive(Deserialize)]
+pub struct LicenseActivationRequest {
+    pub key: String,
+}
+
+#[derive(Deserialize)]
+pub struct OemActionRequest {
+    pub url_suffix: String,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
+/// The iDRAC's answer to an OEM action; `success` is whether it was 2xx.
+#[derive(Serialize)]
+pub struct OemActionResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub response: RawRedfishResponse,
+}
+
+#[derive(Deserialize)]
+pub struct BootOrderRequest {
+    pub order: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PowerCapScheduleRequest {
+    pub cron_expr: String,
+    pub watts: u32,
+    pub duration_minutes: u32,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct TariffWindowRequest {
+    /// Server alias; set this or `group_id`.
+    pub server: Option<String>,
+    pub group_id: Option<i64>,
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+    pub watts: u32,
+}
+
+#[derive(Deserialize)]
+pub struct OneShotScheduleRequest {
+    /// Server alias; the `IDRAC_HOST` server when omitted.
+    pub server: Option<String>,
+    pub action: String,
+    pub execute_at: String,
+    #[serde(default)]
+    pub i_understand_this_hosts_the_controller: bool,
+}
+
+#[derive(Deserialize)]
+pub struct FirmwareScheduleRequest {
+    /// Server alias; the `IDRAC_HOST` server when omitted.
+    pub server: Option<String>,
+    /// Staged update job, e.g. from `POST /api/groups/{id}/firmware-update`.
+    pub job_id: String,
+    pub reboot_at: String,
+    #[serde(default)]
+    pub i_understand_this_hosts_the_controller: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PendingUpdatesQuery {
+    /// Only this server; every server when omitted.
+    pub server: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub validate_only: bool,
+    #[serde(default)]
+    pub test_connections: bool,
+}
+
+#[derive(Deserialize)]
+pub struct AuditStatisticsQuery {
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SelfSubscribeQuery {
+    /// Subscribe to telemetry metric reports instead of events.
+    #[serde(default)]
+    pub metric_reports: bool,
+}
+
+#[derive(Deserialize)]
+pub struct MetricSamplesQuery {
+    pub metric_id: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// `raw`, `hour`, `day` or `auto` (the default).
+    pub granularity: Option<String>,
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GroupPowerQuery {
+    pub window_hours: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct BootReportQuery {
+    /// Number of most recent boots to report.
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct PowerHistoryQuery {
+    pub server: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct PingHistoryQuery {
+    pub since: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct HealthReportsQuery {
+    pub server: Option<String>,
+    /// `YYYY-MM-DD`, the local date of the run.
+    pub date: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangesQuery {
+    pub limit: Option<u32>,
+}
+
+/// Filters of `GET /api/audit/export/csv`, next to its `CsvQuery`.
+#[derive(Deserialize)]
+pub struct AuditCsvQuery {
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+/// Filters of `GET /api/power/events/csv`, next to its `CsvQuery`.
+#[derive(Deserialize)]
+pub struct PowerEventsCsvQuery {
+    /// Audit `server_name`: an alias, or the `IDRAC_HOST` URL.
+    pub server: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+/// Filters of `GET /api/telemetry/samples/csv`, next to its `CsvQuery`.
+#[derive(Deserialize)]
+pub struct MetricSamplesCsvQuery {
+    pub metric_id: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Hourly request quota; unlimited when absent.
+    pub requests_per_hour: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuotaRequest {
+    /// `null` removes the quota.
+    pub requests_per_hour: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    pub hours: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    pub username: String,
+    pub password: String,
+    pub expires_at: Option<String>,
+    /// `readonly` when omitted.
+    pub role: Option<UserRole>,
+}
+
+#[derive(Deserialize)]
+pub struct UserExpiryRequest {
+    /// `null` removes the expiry.
+    pub expires_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ServerGrantRequest {
+    /// Omitted or `null` for a grant that does not expire.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    #[serde(default)]
+    pub include_expired: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ExpirationsQuery {
+    pub days: Option<u32>,
+}
+
+/// Text that must be sent as `confirm` to factory reset the iDRAC.
+const FACTORY_RESET_CONFIRMATION: &str = "FACTORY_RESET";
+const BIOS_RESET_CONFIRMATION: &str = "BIOS_RESET";
+
+#[derive(Deserialize)]
+pub struct FactoryResetRequest {
+    #[serde(default)]
+    pub confirm: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct BiosResetRequest {
+    #[serde(default)]
+    pub confirm: String,
+}
+
 #[derive(Serialize)]
-pub struct ApiResponse {
+pub struct BiosResetResponse {
     pub success: bool,
     pub message: String,
+    pub job_id: String,
 }
 
 #[derive(Serialize)]
-pub struct StatusResponse {
+pub struct FactoryResetResponse {
     pub success: bool,
-    pub power_state: String,
+    pub message: String,
+    pub warning: String,
 }
 
-pub async fn index(session: Session, db: web::Data<Arc<Database>>) -> HttpResponse {
-    // Check if user is logged in
-    if let Ok(Some(_user_id)) = session.get::<i64>("user_id") {
-        HttpResponse::Ok()
-            .content_type("text/html")
-            .body(include_str!("../static/dashboard.html"))
+#[derive(Deserialize)]
+pub struct PromoteRequest {
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// Body the iDRAC POSTs to a Redfish event subscription destination.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+/// Metric report subscriptions deliver a `MetricReport` to the same
+/// destination, with `MetricValues` instead of `Events`.
+pub struct RedfishEventPayload {
+    pub context: Option<String>,
+    #[serde(default)]
+    pub events: Vec<RedfishEventRecord>,
+    pub id: Option<String>,
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub metric_values: Vec<RedfishMetricValue>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RedfishMetricValue {
+    pub metric_id: Option<String>,
+    /// A string per the schema, though some firmware sends numbers.
+    #[serde(default)]
+    pub metric_value: serde_json::Value,
+    pub metric_property: Option<String>,
+    pub timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RedfishEventRecord {
+    #[serde(default)]
+    pub event_type: String,
+    /// The iDRAC's sequence number of the event.
+    pub event_id: Option<String>,
+    #[serde(default)]
+    pub message_id: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default, alias = "MessageSeverity")]
+    pub severity: String,
+    pub event_timestamp: Option<String>,
+    pub origin_of_condition: Option<serde_json::Value>,
+}
+
+/// Response for a failed iDRAC call: 429 when the iDRAC's rate limit
+/// turned the request away, 500 otherwise.
+fn idrac_failure(message: String) -> HttpResponse {
+    let response = ApiResponse::idrac_error(message);
+    if response.error_code == Some(ErrorCode::IdracRateLimited) {
+        HttpResponse::TooManyRequests().json(response)
+    } else if response.error_code == Some(ErrorCode::ValidationInvalidValue) {
+        HttpResponse::BadRequest().json(response)
     } else {
-        // Check if any users exist
-        match db.has_users() {
-            Ok(true) => {
-                // Users exist, show login page
-                HttpResponse::Ok()
-                    .content_type("text/html")
-                    .body(include_str!("../static/login.html"))
-            }
-            Ok(false) => {
-                // No users exist, show registration page
-                HttpResponse::Ok()
-                    .content_type("text/html")
-                    .body(include_str!("../static/register.html"))
+        HttpResponse::InternalServerError().json(response)
+    }
+}
+
+impl From<FieldError> for FieldErrorResponse {
+    fn from(e: FieldError) -> Self {
+        FieldErrorResponse {
+            success: false,
+            message: e.to_string(),
+            error_code: ErrorCode::ValidationInvalidField,
+            field: e.field.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PreferencesResponse {
+    pub success: bool,
+    pub preferences: serde_json::Map<String, serde_json::Value>,
+}
+
+impl From<&RegisteredServer> for ServerSummary {
+    fn from(server: &RegisteredServer) -> Self {
+        ServerSummary {
+            id: server.id,
+            alias: server.alias.clone(),
+            name: server.name.clone(),
+            base_url: server.client.base_url().to_string(),
+            tags: server.tags.clone(),
+            location: server.location.clone(),
+            hosts_this_app: server.hosts_this_app,
+            hostname: None,
+            maintenance: None,
+            os_health_url: server.os_health.as_ref().map(|probe| probe.url.clone()),
+            os_health: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct GroupsResponse {
+    pub success: bool,
+    pub groups: Vec<ServerGroup>,
+}
+
+#[derive(Serialize)]
+pub struct GroupResponse {
+    pub success: bool,
+    pub group: ServerGroup,
+}
+
+#[derive(Serialize)]
+pub struct GroupPowerResponse {
+    pub success: bool,
+    pub summary: GroupPowerSummary,
+}
+
+#[derive(Serialize)]
+pub struct SystemProfileResponse {
+    pub success: bool,
+    pub system_profile: SystemProfile,
+}
+
+#[derive(Serialize)]
+pub struct NicSelectionResponse {
+    pub success: bool,
+    pub nic_selection: NicSelection,
+}
+
+#[derive(Serialize)]
+pub struct BootOrderResponse {
+    pub success: bool,
+    pub boot_order: Vec<BootOption>,
+}
+
+#[derive(Serialize)]
+pub struct BootOrderUpdatedResponse {
+    pub success: bool,
+    pub message: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    /// Set when the change is staged until the next reboot.
+    pub job_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BootOrderIdError {
+    pub id: String,
+    pub error: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct BootOrderInvalidResponse {
+    pub success: bool,
+    pub message: String,
+    pub error_code: ErrorCode,
+    pub errors: Vec<BootOrderIdError>,
+}
+
+#[derive(Serialize)]
+pub struct PowerCapScheduleResponse {
+    pub success: bool,
+    pub schedule: PowerCapSchedule,
+}
+
+#[derive(Serialize)]
+pub struct PowerCapSchedulesResponse {
+    pub success: bool,
+    pub schedules: Vec<PowerCapSchedule>,
+}
+
+#[derive(Serialize)]
+pub struct OneShotScheduleResponse {
+    pub success: bool,
+    pub schedule: OneShotSchedule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct OneShotSchedulesResponse {
+    pub success: bool,
+    pub schedules: Vec<OneShotSchedule>,
+}
+
+#[derive(Serialize)]
+pub struct FirmwareScheduleResponse {
+    pub success: bool,
+    pub schedule: FirmwareUpdateSchedule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PendingUpdatesResponse {
+    pub success: bool,
+    pub servers: Vec<ServerPendingUpdates>,
+}
+
+#[derive(Serialize)]
+pub struct TariffWindowResponse {
+    pub success: bool,
+    pub window: TariffWindow,
+}
+
+#[derive(Serialize)]
+pub struct TariffWindowsResponse {
+    pub success: bool,
+    pub windows: Vec<TariffWindow>,
+}
+
+#[derive(Serialize)]
+pub struct TariffStatusResponse {
+    pub success: bool,
+    pub servers: Vec<TariffStatus>,
+}
+
+#[derive(Serialize)]
+pub struct ChangesResponse {
+    pub success: bool,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Serialize)]
+pub struct AuditStatisticsResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub statistics: AuditStatistics,
+}
+
+/// State an audit collector persists between pulls.
+#[derive(Serialize)]
+pub struct AuditCheckpoint {
+    /// Where the next pull starts, even when this one was the last page.
+    pub cursor: String,
+    pub exported_at: String,
+}
+
+/// Raw samples or rollups, saying which.
+#[derive(Serialize)]
+pub struct MetricSamplesResponse<T> {
+    pub granularity: &'static str,
+    #[serde(flatten)]
+    pub page: Page<T>,
+}
+
+#[derive(Serialize)]
+pub struct AuditExportResponse {
+    #[serde(flatten)]
+    pub page: Page<AuditEntry>,
+    pub checkpoint: AuditCheckpoint,
+}
+
+#[derive(Serialize)]
+pub struct UsersResponse {
+    pub success: bool,
+    pub users: Vec<UserSummary>,
+}
+
+#[derive(Serialize)]
+pub struct ExpirationsResponse {
+    pub success: bool,
+    pub days: u32,
+    /// Accounts that expire within the next `days` days.
+    pub upcoming: Vec<UserSummary>,
+    /// Accounts that expired within the past `days` days.
+    pub recent: Vec<UserSummary>,
+    /// Server grants that expire within the next `days` days.
+    pub upcoming_grants: Vec<ServerGrant>,
+    /// Server grants that expired within the past `days` days.
+    pub recent_grants: Vec<ServerGrant>,
+}
+
+#[derive(Serialize)]
+pub struct ServerGrantsResponse {
+    pub success: bool,
+    pub grants: Vec<ServerGrant>,
+}
+
+#[derive(Serialize)]
+pub struct ServerGrantResponse {
+    pub success: bool,
+    pub grant: ServerGrant,
+}
+
+#[derive(Serialize)]
+pub struct RetentionPolicyResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub policy: RetentionPolicy,
+    pub warnings: Vec<String>,
+}
+
+/// Something every admin should see while it lasts.
+#[derive(Serialize)]
+pub struct Alert {
+    pub kind: &'static str,
+    pub severity: &'static str,
+    pub message: String,
+    /// `None` for conditions that last until they clear.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    pub details: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct AlertsResponse {
+    pub success: bool,
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateShareRequest {
+    pub name: String,
+    /// Server aliases; give either these or `tag`.
+    pub servers: Option<Vec<String>>,
+    pub tag: Option<String>,
+    pub expires_at: String,
+    pub passcode: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ShareResponse {
+    pub success: bool,
+    pub share: Share,
+    /// Present only in the response that created the share.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PowerHistoryResponse {
+    pub success: bool,
+    pub events: Vec<PowerEvent>,
+}
+
+#[derive(Serialize)]
+pub struct PingHistoryResponse {
+    pub success: bool,
+    pub server: String,
+    pub samples: Vec<PingSample>,
+}
+
+#[derive(Serialize)]
+pub struct PowerAnomalyResponse {
+    pub success: bool,
+    pub server: String,
+    /// Current hour of the week, local time; 0 is Monday 00:00.
+    pub hour_of_week: u32,
+    /// Its baseline, if any hour was summarized yet.
+    pub baseline: Option<PowerBaseline>,
+    /// Whether the baseline holds enough weeks to be compared with.
+    pub baseline_ready: bool,
+    pub consecutive_deviations: u32,
+    pub anomaly: Option<PowerAnomaly>,
+}
+
+#[derive(Serialize)]
+pub struct HealthReportsResponse {
+    pub success: bool,
+    pub reports: Vec<HealthReport>,
+}
+
+#[derive(Serialize)]
+pub struct ShareListResponse {
+    pub success: bool,
+    pub shares: Vec<Share>,
+}
+
+#[derive(Serialize)]
+pub struct SharedStatusResponse {
+    pub success: bool,
+    pub name: String,
+    pub servers: Vec<ServerSnapshot>,
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub success: bool,
+    pub token: ApiToken,
+    /// Present only in the response that created the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TokenListResponse {
+    pub success: bool,
+    pub tokens: Vec<ApiToken>,
+}
+
+#[derive(Serialize)]
+pub struct GroupApplyJobResponse {
+    pub success: bool,
+    pub job: GroupApplyJob,
+}
+
+#[derive(Serialize)]
+pub struct GroupPowerOnReportResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub report: GroupPowerOnReport,
+}
+
+#[derive(Serialize)]
+pub struct FirmwareRolloutReportResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub report: RolloutReport,
+}
+
+#[derive(Serialize)]
+pub struct UsageResponse {
+    pub success: bool,
+    pub hours: u32,
+    pub total_requests: u64,
+    /// Newest hour first; requests of the last minute may not be included yet.
+    pub usage: Vec<ApiUsage>,
+}
+
+#[derive(Serialize)]
+pub struct BootReportResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub report: BootReport,
+}
+
+#[derive(Serialize)]
+pub struct ImportResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub report: ImportReport,
+}
+
+#[derive(Serialize)]
+pub struct ServerHealth {
+    pub alias: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub hosts_this_app: bool,
+    /// `ok`, `unreachable`, `credentials_invalid` or `error`.
+    pub status: &'static str,
+    pub health: &'static str,
+    pub degraded_components: Vec<ComponentHealth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Latest probe of the server's OS, apart from the iDRAC's `status`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_health: Option<OsHealth>,
+    /// Latest clock check of the server; absent until one has completed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock: Option<ClockOffset>,
+}
+
+#[derive(Serialize)]
+pub struct IdracClientStats {
+    pub alias: String,
+    /// `None` until the iDRAC's service root has been checked.
+    pub select_supported: Option<bool>,
+    pub payloads: Vec<PayloadStats>,
+}
+
+#[derive(Serialize)]
+pub struct IdracStatsResponse {
+    pub success: bool,
+    pub servers: Vec<IdracClientStats>,
+    /// Requests that overran their time budget since startup, by route.
+    pub route_timeouts: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+pub struct FleetHealthResponse {
+    pub success: bool,
+    pub servers: Vec<ServerHealth>,
+}
+
+#[derive(Serialize)]
+pub struct ClockResponse {
+    pub success: bool,
+    pub base_url: String,
+    /// `None` until the first clock check has completed.
+    pub clock: Option<ClockOffset>,
+}
+
+#[derive(Serialize)]
+pub struct IdracTimeResponse {
+    pub success: bool,
+    pub idrac_time: String,
+    pub app_time: String,
+    /// iDRAC time minus app host time; positive means the iDRAC is ahead.
+    pub offset_secs: i64,
+    /// Offset before a sync; absent if it could not be measured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_offset_secs: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub standby: bool,
+    /// Set while the database refuses writes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_failure: Option<WriteFailure>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ConnectionTestResponse {
+    pub success: bool,
+    pub base_url: String,
+    pub redfish_version: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+}
+
+/// The new credentials are stored whether or not `test_passed`.
+#[derive(Serialize)]
+pub struct CredentialsUpdateResponse {
+    pub success: bool,
+    pub test_passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+}
+
+#[derive(Serialize)]
+pub struct FirmwareReportResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub report: FirmwareReport,
+}
+
+#[derive(Deserialize)]
+pub struct FirmwareBaselineRequest {
+    pub component: String,
+    pub version: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ComplianceProfileRequest {
+    pub name: String,
+    pub components: Vec<ComplianceRequirement>,
+}
+
+#[derive(Serialize)]
+pub struct ComplianceProfileResponse {
+    pub success: bool,
+    pub name: String,
+    pub components: Vec<ComplianceRequirement>,
+}
+
+#[derive(Deserialize)]
+pub struct ServerQuery {
+    /// Server alias; defaults to the `IDRAC_HOST` server.
+    pub server: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct IdracLogsQuery {
+    /// Server alias; defaults to the `IDRAC_HOST` server.
+    pub server: Option<String>,
+    /// Comma-separated `sel` and `lc`; both by default.
+    pub sources: Option<String>,
+    /// Comma-separated `OK`, `Warning` and `Critical`; all by default.
+    pub severity: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct IdracLogsResponse {
+    pub success: bool,
+    pub entries: Vec<LogRecord>,
+}
+
+#[derive(Deserialize)]
+pub struct ComplianceQuery {
+    pub profile: String,
+    /// Server alias; defaults to the `IDRAC_HOST` server.
+    pub server: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ComplianceReportResponse {
+    pub success: bool,
+    pub profile: String,
+    pub server: String,
+    /// Every component in the profile is compliant.
+    pub compliant: bool,
+    pub components: Vec<ComponentCompliance>,
+}
+
+#[derive(Serialize)]
+pub struct TelemetryDefinitionResponse {
+    pub success: bool,
+    pub id: String,
+}
+
+#[derive(Serialize)]
+pub struct SubscriptionResponse {
+    pub success: bool,
+    pub destination: String,
+    pub subscription_uri: String,
+}
+
+#[derive(Serialize)]
+pub struct CertificateResponse {
+    pub success: bool,
+    pub certificate: SslCertInfo,
+    pub days_remaining: Option<i64>,
+    pub expiring_soon: bool,
+}
+
+#[derive(Serialize)]
+pub struct ServiceModuleResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub service_module: ServiceModuleStatus,
+}
+
+/// Pages are revalidated on every load so they always point at the
+/// current release's hashed assets.
+fn html_page(body: &'static str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .body(body)
+}
+
+pub async fn index(session: Session, state: web::Data<AppState>) -> HttpResponse {
+    // Check if user is logged in
+    if let Ok(Some(_user_id)) = session.get::<i64>("user_id") {
+        html_page(assets::DASHBOARD_HTML)
+    } else {
+        // Check if any users exist
+        match state.db.has_users() {
+            Ok(true) => {
+                // Users exist, show login page
+                html_page(assets::LOGIN_HTML)
+            }
+            Ok(false) if state.config.local_login_allowed() => {
+                // No users exist, show registration page
+                html_page(assets::REGISTER_HTML)
+            }
+            // Registration is closed; the first account signs in through
+            // the directory or single sign-on.
+            Ok(false) => html_page(assets::LOGIN_HTML),
+            Err(e) => {
+                HttpResponse::InternalServerError()
+                    .body(format!("Database error: {}", e))
+            }
+        }
+    }
+}
+
+/// A CSS or JS file by its content-hashed path. The content behind a path
+/// never changes, so browsers may cache it indefinitely.
+pub async fn static_asset(http_req: HttpRequest) -> HttpResponse {
+    match assets::find(http_req.path()) {
+        Some(asset) => HttpResponse::Ok()
+            .content_type(asset.content_type)
+            .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+            .body(asset.body),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+pub async fn register(
+    form: web::Json<RegisterRequest>,
+    state: web::Data<AppState>,
+    session: Session,
+) -> HttpResponse {
+    if !state.config.local_login_allowed() {
+        return HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthRegistrationClosed,
+            "Registration is closed. Sign in with your directory or single sign-on account.",
+        ));
+    }
+
+    // Check if users already exist
+    match state.db.has_users() {
+        Ok(true) => {
+            return HttpResponse::Forbidden().json(ApiResponse::error(
+                ErrorCode::AuthRegistrationClosed,
+                "Registration is closed. An account already exists.",
+            ));
+        }
+        Ok(false) => {}
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Database error: {}", e),
+            ));
+        }
+    }
+
+    if form.username.trim().is_empty() || form.password.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationMissingField,
+            "Username and password are required",
+        ));
+    }
+
+    let username = match normalize_username(&form.username) {
+        Ok(username) => username,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    if form.password != form.confirm_password {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationPasswordMismatch,
+            "Passwords do not match",
+        ));
+    }
+
+    if form.password.len() < 8 {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationPasswordTooShort,
+            "Password must be at least 8 characters",
+        ));
+    }
+
+    // Registration only opens while no account exists, so this is the
+    // first user.
+    match state.db.create_user(&username, &form.password, None, UserRole::Admin) {
+        Ok(user_id) => {
+            // Auto-login after registration
+            let _ = session.insert("user_id", user_id);
+            let _ = session.insert("session_generation", 0i64);
+            let _ = session.insert("issued_at", chrono::Utc::now().timestamp());
+            info!("New user registered and logged in: {}", username);
+            
+            HttpResponse::Ok().json(ApiResponse::success("Account created successfully"))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to create user: {}", e),
+        )),
+    }
+}
+
+pub async fn login(
+    form: web::Json<LoginRequest>,
+    state: web::Data<AppState>,
+    session: Session,
+) -> HttpResponse {
+    if form.username.trim().is_empty() || form.password.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationMissingField,
+            "Username and password are required",
+        ));
+    }
+
+    if state.config.oidc.as_ref().is_some_and(|oidc| oidc.only) {
+        return HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthPasswordLoginDisabled,
+            "Password login is disabled. Sign in with single sign-on.",
+        ));
+    }
+
+    let username = form.username.trim();
+    let account = match state.db.get_user_by_username(username) {
+        Ok(account) => account,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Database error: {}", e),
+            ))
+        }
+    };
+    let local = account.as_ref().is_some_and(|account| account.auth_source == database::AUTH_SOURCE_LOCAL);
+    let (verified, scopes) = match &state.config.ldap {
+        Some(ldap) if local && !ldap.allow_local_login => {
+            warn!("Refused login for local account {}: LDAP_ALLOW_LOCAL_LOGIN is off", username);
+            (Ok(None), None)
+        }
+        Some(ldap) if !local => match directory_login(ldap, &state, username, &form.password).await {
+            Ok(Some((user, scopes))) => (Ok(Some(user)), Some(scopes)),
+            Ok(None) => (Ok(None), None),
+            Err(response) => return response,
+        },
+        _ => (state.db.verify_user(username, &form.password), None),
+    };
+
+    match verified {
+        Ok(Some(user)) if !user.is_active() => {
+            warn!("Refused login for expired account: {}", user.username);
+            HttpResponse::Forbidden().json(ApiResponse::error(
+                ErrorCode::AuthAccountExpired,
+                "This account has expired",
+            ))
+        }
+        Ok(Some(user)) => {
+            let _ = session.insert("user_id", user.id);
+            let _ = session.insert("session_generation", user.session_generation);
+            let _ = session.insert("issued_at", chrono::Utc::now().timestamp());
+            if let Some(scopes) = scopes {
+                let _ = session.insert("scopes", scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+            }
+            info!("User logged in: {}", user.username);
+            
+            HttpResponse::Ok().json(ApiResponse::success("Login successful"))
+        }
+        Ok(None) => invalid_login(),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Database error: {}", e),
+        )),
+    }
+}
+
+/// The answer to every failed login, so that it does not reveal whether
+/// the account exists or where it is kept.
+fn invalid_login() -> HttpResponse {
+    HttpResponse::Unauthorized().json(ApiResponse::error(
+        ErrorCode::AuthInvalidCredentials,
+        "Invalid username or password",
+    ))
+}
+
+/// Store the role a directory or single sign-on account's scopes map to,
+/// so it follows changes to the group and default scope mappings.
+fn sync_external_role(state: &AppState, mut user: User, role: UserRole) -> Result<User, database::DbError> {
+    if user.role != role {
+        state.db.set_user_role(user.id, role)?;
+        info!("Changed the role of {} from {} to {}", user.username, user.role, role);
+        user.role = role;
+    }
+    Ok(user)
+}
+
+/// Sign in through the directory, creating the local row of a directory
+/// account on its first login. `Ok(None)` for a refused login; a directory
+/// that cannot be reached is a 503 with the usual message.
+async fn directory_login(
+    ldap: &LdapConfig,
+    state: &AppState,
+    username: &str,
+    password: &str,
+) -> Result<Option<(User, Vec<TokenScope>)>, HttpResponse> {
+    let directory_user = match ldap::authenticate(ldap, username, password).await {
+        Ok(directory_user) => directory_user,
+        Err(LdapFailure::InvalidCredentials(reason)) => {
+            warn!("Refused LDAP login for {} (401 bad credentials): {}", username, reason);
+            return Ok(None);
+        }
+        Err(LdapFailure::Unreachable(reason)) => {
+            warn!("Refused LDAP login for {} (503 directory unreachable): {}", username, reason);
+            return Err(HttpResponse::ServiceUnavailable().json(ApiResponse::error(
+                ErrorCode::AuthInvalidCredentials,
+                "Invalid username or password",
+            )));
+        }
+    };
+    let scopes = ldap.scopes_for(&directory_user.groups);
+    if scopes.is_empty() {
+        warn!("Refused LDAP login for {}: none of the groups of {} is mapped", username, directory_user.dn);
+        return Ok(None);
+    }
+    // Directory names are case-insensitive, so one account serves every spelling.
+    let username = match normalize_username(&username.to_lowercase()) {
+        Ok(username) => username,
+        Err(e) => {
+            warn!("Refused LDAP login for {}: username {}", username, e.message);
+            return Ok(None);
+        }
+    };
+    let username = username.as_str();
+    let database_error = |e: database::DbError| {
+        HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, format!("Database error: {}", e)))
+    };
+    let role = TokenScope::role_for(&scopes);
+    let user = match state.db.get_user_by_username(username).map_err(database_error)? {
+        Some(user) if user.auth_source == database::AUTH_SOURCE_LDAP => sync_external_role(state, user, role).map_err(database_error)?,
+        Some(user) => {
+            warn!("Refused LDAP login for {}: the account signs in through {}", username, user.auth_source);
+            return Ok(None);
+        }
+        None => {
+            let id = state.db.insert_external_user(username, database::AUTH_SOURCE_LDAP, role).map_err(database_error)?;
+            info!("Created directory account {} for {}", username, directory_user.dn);
+            state.audit_with_details(
+                Some(id),
+                AuditAction::UserCreate,
+                "app",
+                &Ok(username.to_string()),
+                &serde_json::json!({ "username": username, "auth_source": database::AUTH_SOURCE_LDAP, "dn": directory_user.dn, "role": role }),
+            );
+            match state.db.get_user_by_id(id).map_err(database_error)? {
+                Some(user) => user,
+                None => return Ok(None),
+            }
+        }
+    };
+    Ok(Some((user, scopes)))
+}
+
+#[derive(Serialize)]
+pub struct LogoutResponse {
+    pub success: bool,
+    pub message: String,
+    /// Provider page that ends the single sign-on session too; the browser
+    /// should go there next.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_session_url: Option<String>,
+}
+
+pub async fn logout(session: Session, state: web::Data<AppState>) -> HttpResponse {
+    let signed_on = session.get::<bool>(OIDC_SESSION_KEY).ok().flatten().unwrap_or(false);
+    session.purge();
+    info!("User logged out");
+
+    let end_session_url = match (&state.oidc, signed_on) {
+        (Some(oidc), true) => oidc.end_session_url().await,
+        _ => None,
+    };
+    HttpResponse::Ok().json(LogoutResponse {
+        success: true,
+        message: "Logged out successfully".to_string(),
+        end_session_url,
+    })
+}
+
+/// Session key of the single sign-on login waiting for its callback.
+const OIDC_PENDING_KEY: &str = "oidc_pending";
+/// Set in sessions started through single sign-on.
+const OIDC_SESSION_KEY: &str = "oidc";
+
+#[derive(Serialize)]
+pub struct AuthMethodsResponse {
+    pub success: bool,
+    /// The username and password form.
+    pub password: bool,
+    /// `GET /api/auth/oidc/login`.
+    pub oidc: bool,
+    /// `POST /api/password-reset/request`.
+    pub password_reset: bool,
+}
+
+/// How the login page may sign in.
+pub async fn auth_methods(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(AuthMethodsResponse {
+        success: true,
+        password: state.config.oidc.as_ref().is_none_or(|oidc| !oidc.only),
+        oidc: state.oidc.is_some(),
+        password_reset: state.config.local_login_allowed() && state.config.smtp.is_some() && state.config.self_url.is_some(),
+    })
+}
+
+fn oidc_not_configured() -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::error(ErrorCode::ConfigOidcNotConfigured, "Single sign-on is not configured"))
+}
+
+/// Back to the login page, which explains `sso_error`.
+fn sso_failed(reason: &str) -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((header::LOCATION, format!("/?sso_error={}", reason))).finish()
+}
+
+/// Send the browser to the provider to sign in.
+pub async fn oidc_login(session: Session, state: web::Data<AppState>) -> HttpResponse {
+    let Some(oidc) = &state.oidc else {
+        return oidc_not_configured();
+    };
+    match oidc.start_login().await {
+        Ok((url, pending)) => {
+            let _ = session.insert(OIDC_PENDING_KEY, pending);
+            HttpResponse::SeeOther().insert_header((header::LOCATION, url)).finish()
+        }
+        Err(OidcFailure::Unreachable(reason) | OidcFailure::Rejected(reason)) => {
+            warn!("Could not start single sign-on (503 provider unreachable): {}", reason);
+            sso_failed("unavailable")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Where the provider sends the browser back: check the ID token and start
+/// the session of the user it names.
+pub async fn oidc_callback(
+    query: web::Query<OidcCallbackQuery>,
+    session: Session,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let Some(oidc) = &state.oidc else {
+        return oidc_not_configured();
+    };
+    let pending = session.remove_as::<PendingLogin>(OIDC_PENDING_KEY).and_then(Result::ok);
+    if let Some(error) = &query.error {
+        warn!(
+            "Single sign-on refused by the provider: {} {}",
+            error,
+            query.error_description.as_deref().unwrap_or_default()
+        );
+        return sso_failed("denied");
+    }
+    let (Some(pending), Some(code), Some(returned_state)) = (pending, &query.code, &query.state) else {
+        warn!("Refused single sign-on callback (401 rejected): no login in progress in this session, or no code and state");
+        return sso_failed("failed");
+    };
+
+    let identity = match oidc.finish_login(&pending, returned_state, code).await {
+        Ok(identity) => identity,
+        Err(OidcFailure::Rejected(reason)) => {
+            warn!("Refused single sign-on (401 rejected): {}", reason);
+            return sso_failed("failed");
+        }
+        Err(OidcFailure::Unreachable(reason)) => {
+            warn!("Refused single sign-on (503 provider unreachable): {}", reason);
+            return sso_failed("unavailable");
+        }
+    };
+    let (user, scopes) = match oidc_account(&state, &oidc.config, &identity) {
+        Ok(Some(account)) => account,
+        Ok(None) => return sso_failed("failed"),
+        Err(e) => {
+            error!("Single sign-on of {} failed: {}", identity.subject, e);
+            return sso_failed("failed");
+        }
+    };
+    if !user.is_active() {
+        warn!("Refused single sign-on for expired account: {}", user.username);
+        return sso_failed("expired");
+    }
+
+    let _ = session.insert("user_id", user.id);
+    let _ = session.insert("session_generation", user.session_generation);
+    let _ = session.insert("issued_at", chrono::Utc::now().timestamp());
+    let _ = session.insert(OIDC_SESSION_KEY, true);
+    if let Some(scopes) = scopes {
+        let _ = session.insert("scopes", scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+    }
+    info!("User logged in through single sign-on: {} (subject {})", user.username, identity.subject);
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, "/")).finish()
+}
+
+/// An account and the scopes its session is limited to, if any.
+type ScopedAccount = (User, Option<Vec<TokenScope>>);
+
+/// The account an ID token signs in as, creating it on first login. Local
+/// accounts with a matching username are used as they are and keep
+/// unlimited sessions.
+fn oidc_account(state: &AppState, config: &OidcConfig, identity: &OidcIdentity) -> Result<Option<ScopedAccount>, database::DbError> {
+    let username = match normalize_username(&identity.username.to_lowercase()) {
+        Ok(username) => username,
+        Err(e) => {
+            warn!("Refused single sign-on for subject {}: username {}", identity.subject, e.message);
+            return Ok(None);
+        }
+    };
+    let role = TokenScope::role_for(&config.default_scopes);
+    let user = match state.db.get_user_by_username(&username)? {
+        Some(user) if user.auth_source == database::AUTH_SOURCE_LOCAL => return Ok(Some((user, None))),
+        Some(user) if user.auth_source == database::AUTH_SOURCE_OIDC => sync_external_role(state, user, role)?,
+        Some(user) => {
+            warn!("Refused single sign-on for {}: the account signs in through {}", username, user.auth_source);
+            return Ok(None);
+        }
+        None => {
+            let id = state.db.insert_external_user(&username, database::AUTH_SOURCE_OIDC, role)?;
+            info!("Created single sign-on account {} for subject {}", username, identity.subject);
+            state.audit_with_details(
+                Some(id),
+                AuditAction::UserCreate,
+                "app",
+                &Ok(username.clone()),
+                &serde_json::json!({ "username": username, "auth_source": database::AUTH_SOURCE_OIDC, "subject": identity.subject, "role": role }),
+            );
+            match state.db.get_user_by_id(id)? {
+                Some(user) => user,
+                None => return Ok(None),
+            }
+        }
+    };
+    Ok(Some((user, Some(config.default_scopes.clone()))))
+}
+
+/// Session key of the last time the browser reported user activity.
+const LAST_ACTIVITY_KEY: &str = "last_activity_at";
+
+#[derive(Serialize)]
+pub struct ActivityResponse {
+    pub success: bool,
+    /// When the session ends however active the user is.
+    pub session_expires_at: String,
+    pub idle_timeout_secs: u64,
+    /// `session_will_expire_soon` when the user had been idle for longer
+    /// than `idle_timeout_secs` before this report.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<&'static str>,
+}
+
+/// Record user activity reported by the browser, which can use the answer
+/// to show an idle countdown. Idle sessions are only warned about, not
+/// ended.
+pub async fn report_activity(session: Session, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = check_auth(session.clone(), &state).await {
+        return response;
+    }
+    let now = chrono::Utc::now().timestamp();
+    let issued_at = session.get::<i64>("issued_at").ok().flatten();
+    let last_activity = session.get::<i64>(LAST_ACTIVITY_KEY).ok().flatten().or(issued_at).unwrap_or(now);
+    let break_glass_expires_at = session.get::<i64>(break_glass::SESSION_EXPIRES_KEY).ok().flatten();
+    let _ = session.insert(LAST_ACTIVITY_KEY, now);
+
+    let ttl_secs = state.retention.read().unwrap().sessions_ttl_hours as i64 * 3600;
+    let expires_at = break_glass_expires_at
+        .into_iter()
+        .chain(Some(issued_at.unwrap_or(now) + ttl_secs))
+        .min()
+        .unwrap_or(now);
+    let idle_timeout_secs = state.config.idle_timeout_secs;
+    HttpResponse::Ok().json(ActivityResponse {
+        success: true,
+        session_expires_at: chrono::DateTime::from_timestamp(expires_at, 0).unwrap_or_default().to_rfc3339(),
+        idle_timeout_secs,
+        warning: (now - last_activity > idle_timeout_secs as i64).then_some("session_will_expire_soon"),
+    })
+}
+
+/// Start the single session a break-glass link grants. The link works once
+/// and the session ends when the grant expires.
+pub async fn break_glass_login(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+    session: Session,
+) -> HttpResponse {
+    let grant = match state.db.claim_break_glass_grant(&tokens::hash_token(&path.into_inner())) {
+        Ok(grant) => grant,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Database error: {}", e),
+            ))
+        }
+    };
+    let account = match state.db.get_user_by_username(BREAK_GLASS_USERNAME) {
+        Ok(account) => account,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Database error: {}", e),
+            ))
+        }
+    };
+
+    let (grant, account, expires_at) = match (grant, account) {
+        (Some(grant), Some(account)) if account.is_active() => match break_glass::expires_timestamp(&grant) {
+            Some(expires_at) => (grant, account, expires_at),
+            None => {
+                return HttpResponse::InternalServerError().json(ApiResponse::error(
+                    ErrorCode::InternalDatabase,
+                    format!("Break-glass grant {} has an unreadable expiry", grant.id),
+                ))
+            }
+        },
+        _ => {
+            warn!("Refused break-glass link: invalid, already used or expired");
+            state.audit_with_details(
+                None,
+                AuditAction::BreakGlassLogin,
+                "app",
+                &Err("Break-glass link is invalid, already used or expired".to_string()),
+                &serde_json::json!({ "break_glass": true }),
+            );
+            return HttpResponse::Unauthorized().json(ApiResponse::error(
+                ErrorCode::AuthInvalidToken,
+                "Break-glass link is invalid, already used or expired",
+            ));
+        }
+    };
+
+    session.renew();
+    let _ = session.insert("user_id", account.id);
+    let _ = session.insert("session_generation", account.session_generation);
+    let _ = session.insert("issued_at", chrono::Utc::now().timestamp());
+    let _ = session.insert(break_glass::SESSION_GRANT_KEY, grant.id);
+    let _ = session.insert(break_glass::SESSION_EXPIRES_KEY, expires_at);
+    warn!("Break-glass session started for grant {} (reason: {})", grant.id, grant.reason);
+    state.audit_with_details(
+        Some(account.id),
+        AuditAction::BreakGlassLogin,
+        "app",
+        &Ok(format!("grant {}", grant.id)),
+        &serde_json::json!({
+            "grant_id": grant.id,
+            "reason": grant.reason,
+            "issued_by": grant.issued_by,
+            "expires_at": grant.expires_at,
+        }),
+    );
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, "/")).finish()
+}
+
+/// Mail a password reset link to the account named by username or email.
+/// The answer is the same whether or not such an account exists, so it
+/// cannot be used to find out which accounts do; the mail is sent after
+/// the response.
+pub async fn request_password_reset(
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<PasswordResetRequest>,
+) -> HttpResponse {
+    if !state.config.local_login_allowed() {
+        return HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthPasswordLoginDisabled,
+            "Password login is disabled. Reset your password with your directory or single sign-on provider.",
+        ));
+    }
+    if state.config.smtp.is_none() {
+        return HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::ConfigSmtpNotConfigured,
+            "Password reset is not available: SMTP is not configured",
+        ));
+    }
+    // The link must not be built from the Host header, which the caller picks.
+    if state.config.self_url.is_none() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ConfigSelfUrlMissing,
+            "SELF_URL is not configured; reset links need it",
+        ));
+    }
+    if req.username.trim().is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationMissingField,
+            "Username or email is required",
+        ));
+    }
+
+    let ip = http_req.connection_info().realip_remote_addr().unwrap_or("unknown").to_string();
+    if !state.password_reset_limits.allow_ip(&ip) {
+        warn!("Refused password reset request from {}: too many requests", ip);
+        return HttpResponse::TooManyRequests().json(ApiResponse::error(
+            ErrorCode::AuthTooManyRequests,
+            "Too many password reset requests. Try again later.",
+        ));
+    }
+
+    tokio::spawn(password_reset::send_link(state.get_ref().clone(), req.into_inner().username));
+    HttpResponse::Ok().json(ApiResponse::success(
+        "If that account exists and has an email address, a reset link is on its way",
+    ))
+}
+
+/// Set a new password with the token from a reset link. Every session of
+/// the account ends, this one included.
+pub async fn complete_password_reset(
+    state: web::Data<AppState>,
+    req: web::Json<CompletePasswordResetRequest>,
+) -> HttpResponse {
+    if !state.config.local_login_allowed() {
+        return HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthPasswordLoginDisabled,
+            "Password login is disabled. Reset your password with your directory or single sign-on provider.",
+        ));
+    }
+    if req.token.trim().is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationMissingField, "token is required"));
+    }
+    if req.password != req.confirm_password {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationPasswordMismatch,
+            "Passwords do not match",
+        ));
+    }
+    if req.password.len() < 8 {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationPasswordTooShort,
+            "Password must be at least 8 characters",
+        ));
+    }
+
+    match state.db.reset_password(&tokens::hash_token(req.token.trim()), &req.password) {
+        Ok(Some(user_id)) => {
+            info!("Password of user {} reset through an emailed link", user_id);
+            state.audit_server(Some(user_id), AuditAction::PasswordReset, "app", &Ok("Password reset; sessions ended".to_string()));
+            HttpResponse::Ok().json(ApiResponse::success("Password changed. Sign in with your new password."))
+        }
+        Ok(None) => {
+            warn!("Refused password reset: link invalid, already used or expired");
+            HttpResponse::BadRequest().json(ApiResponse::error(
+                ErrorCode::AuthInvalidToken,
+                "This reset link is invalid, already used or expired",
+            ))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Database error: {}", e),
+        )),
+    }
+}
+
+pub async fn reset_password_page() -> HttpResponse {
+    html_page(assets::RESET_PASSWORD_HTML)
+}
+
+/// Set or remove the address the current user's password reset links are
+/// mailed to.
+pub async fn set_my_email(
+    session: Session,
+    state: web::Data<AppState>,
+    req: web::Json<UserEmailRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth(session, &state).await {
+        Ok(user) => user.id,
+        Err(response) => return response,
+    };
+    if state.is_break_glass_user(user_id) {
+        return HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthBreakGlassRestricted,
+            "The break-glass account cannot have an email address",
+        ));
+    }
+
+    let email = match req.email.as_deref().map(normalize_email).transpose() {
+        Ok(email) => email,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    match state.db.set_user_email(user_id, email.as_deref()) {
+        Ok(true) => HttpResponse::Ok().json(ApiResponse::success(match email {
+            Some(_) => "Email address updated",
+            None => "Email address removed",
+        })),
+        Ok(false) => HttpResponse::NotFound().json(ApiResponse::error(ErrorCode::UserNotFound, format!("No user with id {}", user_id))),
+        Err(e) if e.is_unique_violation() => HttpResponse::Conflict().json(ApiResponse::error(
+            ErrorCode::UserDuplicate,
+            "Another account uses this email address",
+        )),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// Conditions other admins should be warned about, such as break-glass
+/// access that has been issued or is in use.
+pub async fn list_alerts(session: Session, http_req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.db.list_active_break_glass_grants() {
+        Ok(grants) => {
+            let mut alerts: Vec<Alert> = grants
+                .into_iter()
+                .map(|grant| {
+                    let message = match &grant.used_at {
+                        Some(used_at) => format!(
+                            "Break-glass admin session active since {} UTC, until {} UTC: {}",
+                            used_at, grant.expires_at, grant.reason
+                        ),
+                        None => format!(
+                            "Break-glass link issued by {}, unused, valid until {} UTC: {}",
+                            grant.issued_by, grant.expires_at, grant.reason
+                        ),
+                    };
+                    Alert {
+                        kind: "break_glass",
+                        severity: if grant.used_at.is_some() { "critical" } else { "warning" },
+                        message,
+                        expires_at: Some(grant.expires_at.clone()),
+                        details: serde_json::to_value(&grant).unwrap_or_default(),
+                    }
+                })
+                .collect();
+            let exceeded: Vec<BudgetExceeded> = state.power_budget_exceeded.read().unwrap().values().cloned().collect();
+            alerts.extend(exceeded.into_iter().map(|exceeded| Alert {
+                kind: "power_budget",
+                severity: "warning",
+                message: format!(
+                    "Group '{}' draws {} W, over its {} W power budget since {} UTC",
+                    exceeded.group, exceeded.total_watts, exceeded.budget_watts, exceeded.since
+                ),
+                expires_at: None,
+                details: serde_json::to_value(&exceeded).unwrap_or_default(),
+            }));
+            alerts.extend(state.servers.all().iter().filter_map(|server| {
+                let rejected_at = server.client.credentials_rejected_at()?;
+                Some(Alert {
+                    kind: "credentials_invalid",
+                    severity: "critical",
+                    message: format!(
+                        "The iDRAC of '{}' rejected the stored credentials at {}; it is not polled until they are updated and a connection test succeeds",
+                        server.alias, rejected_at
+                    ),
+                    expires_at: None,
+                    details: serde_json::json!({ "server": server.alias, "rejected_at": rejected_at }),
+                })
+            }));
+            alerts.extend(state.power_anomalies.active().into_iter().map(|anomaly| Alert {
+                kind: "power_anomaly",
+                severity: "warning",
+                message: format!(
+                    "'{}' draws {} W since {} UTC, against a usual {:.0} W (± {:.0} W) at this hour of the week",
+                    anomaly.server, anomaly.watts, anomaly.since, anomaly.median_watts, anomaly.mad_watts
+                ),
+                expires_at: None,
+                details: serde_json::to_value(&anomaly).unwrap_or_default(),
+            }));
+            let connectivity: Vec<ConnectivityAlert> = state.connectivity_alerts.read().unwrap().values().cloned().collect();
+            alerts.extend(connectivity.into_iter().map(|alert| Alert {
+                kind: "connectivity",
+                severity: if alert.severity == "Critical" { "critical" } else { "warning" },
+                message: format!(
+                    "The iDRAC of '{}' lost {}% of its pings since {} UTC",
+                    alert.server, alert.packet_loss_pct, alert.since
+                ),
+                expires_at: None,
+                details: serde_json::to_value(&alert).unwrap_or_default(),
+            }));
+            let os_unreachable: Vec<(String, OsHealth)> = state
+                .os_health
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|(_, health)| health.status == os_health::STATUS_UNREACHABLE)
+                .map(|(alias, health)| (alias.clone(), health.clone()))
+                .collect();
+            alerts.extend(os_unreachable.into_iter().map(|(alias, health)| Alert {
+                kind: "os_unreachable",
+                severity: "critical",
+                message: format!(
+                    "The OS of '{}' has not answered its health probe since {} UTC: {}",
+                    alias,
+                    health.since,
+                    health.error.as_deref().unwrap_or("no answer")
+                ),
+                expires_at: None,
+                details: serde_json::json!({ "server": alias, "os_health": health }),
+            }));
+            let psu_issues: Vec<PsuIssue> = state.psu_issues.read().unwrap().values().cloned().collect();
+            alerts.extend(psu_issues.into_iter().map(|issue| Alert {
+                kind: "psu",
+                severity: if issue.severity == "Critical" { "critical" } else { "warning" },
+                message: format!(
+                    "PSU '{}' of '{}' is {} since {} UTC",
+                    issue.psu_name,
+                    issue.server,
+                    match issue.issue {
+                        "input_lost" => "without input power",
+                        other => other,
+                    },
+                    issue.since
+                ),
+                expires_at: None,
+                details: serde_json::to_value(&issue).unwrap_or_default(),
+            }));
+            if let Some(failure) = state.db.write_failure() {
+                let free_bytes = database::free_space_bytes(state.config.database_location());
+                let free = free_bytes
+                    .map(|bytes| format!("; {} MiB free", bytes / (1024 * 1024)))
+                    .unwrap_or_default();
+                alerts.push(Alert {
+                    kind: "storage_degraded",
+                    severity: "critical",
+                    message: format!(
+                        "The database has refused writes since {} UTC ({}){}; changes are rejected until it accepts them again",
+                        failure.since, failure.error, free
+                    ),
+                    expires_at: None,
+                    details: serde_json::json!({
+                        "kind": failure.kind,
+                        "error": failure.error,
+                        "since": failure.since,
+                        "free_bytes": free_bytes,
+                    }),
+                });
+            }
+            HttpResponse::Ok().json(AlertsResponse { success: true, alerts })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to list alerts: {}", e),
+        )),
+    }
+}
+
+#[derive(Serialize)]
+struct QuotaExceededResponse {
+    #[serde(flatten)]
+    response: ApiResponse,
+    reset_at: String,
+}
+
+/// 429 for a token that used up its hourly quota, with the time it resets
+/// in `reset_at` and `Retry-After`.
+fn quota_exceeded(token: &ApiToken, reset_at: chrono::DateTime<chrono::Utc>) -> HttpResponse {
+    let retry_after = (reset_at - chrono::Utc::now()).num_seconds().max(1);
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+        .json(QuotaExceededResponse {
+            response: ApiResponse::error(
+                ErrorCode::AuthQuotaExceeded,
+                format!(
+                    "API token '{}' has used its {} requests this hour",
+                    token.name,
+                    token.requests_per_hour.unwrap_or(0)
+                ),
+            ),
+            reset_at: reset_at.to_rfc3339(),
+        })
+}
+
+// Middleware to check authentication
+pub async fn check_auth(session: Session, state: &AppState) -> Result<User, HttpResponse> {
+    let not_authenticated =
+        || HttpResponse::Unauthorized().json(ApiResponse::error(ErrorCode::AuthNotAuthenticated, "Not authenticated"));
+    let Ok(Some(user_id)) = session.get::<i64>("user_id") else {
+        return Err(not_authenticated());
+    };
+    match state.db.get_user_by_id(user_id) {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(not_authenticated()),
+        Err(e) => Err(HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()))),
+    }
+}
+
+/// Scopes a directory or single sign-on login was granted; `None` for local
+/// accounts, whose sessions are not limited.
+fn session_scopes(session: &Session) -> Option<Vec<TokenScope>> {
+    session
+        .get::<Vec<String>>("scopes")
+        .ok()
+        .flatten()
+        .map(|scopes| scopes.iter().filter_map(|s| TokenScope::parse(s)).collect())
+}
+
+/// Authenticate with an `Authorization: Bearer` API token carrying `scope`
+/// when the header is present, otherwise with the session. Only sessions of
+/// directory and single sign-on accounts are limited by scopes. Scopes that
+/// change something or read the audit log also need the user to hold the
+/// role they require.
+pub async fn check_auth_or_token(
+    session: Session,
+    req: &HttpRequest,
+    state: &AppState,
+    scope: TokenScope,
+) -> Result<i64, HttpResponse> {
+    match scope.required_role() {
+        UserRole::ReadOnly => authenticate(session, req, state, scope).await.map(|user| user.id),
+        role => require_role(session, req, state, scope, role).await,
+    }
+}
+
+/// Authenticate like `check_auth_or_token` and require the user to hold
+/// `role`, answering `403` when they do not.
+pub async fn require_role(
+    session: Session,
+    req: &HttpRequest,
+    state: &AppState,
+    scope: TokenScope,
+    role: UserRole,
+) -> Result<i64, HttpResponse> {
+    let user = authenticate(session, req, state, scope).await?;
+    if !user.role.includes(role) {
+        return Err(HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthInsufficientRole,
+            format!("This action requires the '{}' role; '{}' has the '{}' role", role, user.username, user.role),
+        )));
+    }
+    Ok(user.id)
+}
+
+/// Authenticate for a power action on the server `server` names, the
+/// `IDRAC_HOST` one by default. Admins may act on any server; other users
+/// need an unexpired grant on this one.
+pub async fn require_power_write(
+    session: Session,
+    req: &HttpRequest,
+    state: &AppState,
+    server: Option<&str>,
+) -> Result<i64, HttpResponse> {
+    let alias = server.map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let user = authenticate(session, req, state, TokenScope::PowerWrite).await?;
+    if user.role.includes(UserRole::Admin) {
+        return Ok(user.id);
+    }
+    match state.db.get_server_grant(user.id, alias) {
+        Ok(Some(grant)) if grant.is_active() => Ok(user.id),
+        Ok(_) => Err(HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthInsufficientRole,
+            format!(
+                "This action requires the '{}' role or a grant on server '{}'; '{}' has the '{}' role",
+                UserRole::Admin,
+                alias,
+                user.username,
+                user.role
+            ),
+        ))),
+        Err(e) => Err(HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()))),
+    }
+}
+
+async fn authenticate(
+    session: Session,
+    req: &HttpRequest,
+    state: &AppState,
+    scope: TokenScope,
+) -> Result<User, HttpResponse> {
+    let Some(header) = req.headers().get(header::AUTHORIZATION) else {
+        let scopes = session_scopes(&session);
+        let user = check_auth(session, state).await?;
+        if scopes.is_some_and(|scopes| !scopes.iter().any(|granted| granted.grants(scope))) {
+            return Err(HttpResponse::Forbidden().json(ApiResponse::error(
+                ErrorCode::AuthMissingScope,
+                format!("This login is not granted the '{}' scope", scope),
+            )));
+        }
+        state.usage.record_session(user.id);
+        return Ok(user);
+    };
+    let invalid_token = || HttpResponse::Unauthorized().json(ApiResponse::error(ErrorCode::AuthInvalidToken, "Invalid API token"));
+
+    let Some(token) = header.to_str().ok().and_then(|value| value.strip_prefix("Bearer ")) else {
+        return Err(invalid_token());
+    };
+    match state.db.find_api_token(&tokens::hash_token(token.trim())) {
+        Ok(Some(token))
+            if token
+                .scopes
+                .iter()
+                .filter_map(|s| TokenScope::parse(s))
+                .any(|granted| granted.grants(scope)) =>
+        {
+            match state.db.get_user_by_id(token.user_id) {
+                Ok(Some(user)) if user.is_active() => match state.usage.record_token(&state.db, &token) {
+                    Ok(()) => Ok(user),
+                    Err(reset_at) => Err(quota_exceeded(&token, reset_at)),
+                },
+                Ok(_) => Err(HttpResponse::Forbidden().json(ApiResponse::error(
+                    ErrorCode::AuthAccountExpired,
+                    "The account owning this API token has expired",
+                ))),
+                Err(e) => Err(HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()))),
+            }
+        }
+        Ok(Some(_)) => Err(HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthMissingScope,
+            format!("API token is missing the '{}' scope", scope),
+        ))),
+        Ok(None) => Err(invalid_token()),
+        Err(e) => Err(HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()))),
+    }
+}
+
+/// Why `check_share` turned a share link away.
+enum ShareRejection {
+    Invalid,
+    RateLimited,
+    PasscodeRequired,
+    Database(String),
+}
+
+impl ShareRejection {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            ShareRejection::Invalid => HttpResponse::Unauthorized().json(ApiResponse::error(
+                ErrorCode::AuthInvalidToken,
+                "This share link is invalid, revoked or expired",
+            )),
+            ShareRejection::RateLimited => HttpResponse::TooManyRequests().json(ApiResponse::error(
+                ErrorCode::AuthQuotaExceeded,
+                "Too many requests through this share link; try again in a minute",
+            )),
+            ShareRejection::PasscodeRequired => HttpResponse::Unauthorized().json(ApiResponse::error(
+                ErrorCode::AuthSharePasscodeRequired,
+                "This share link needs its passcode",
+            )),
+            ShareRejection::Database(e) => {
+                HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e))
+            }
+        }
+    }
+}
+
+/// Authenticate a share link from its `Authorization: Share` header. The
+/// result is a `ShareViewer`, never a user id, so it cannot reach any
+/// handler that acts on behalf of a user.
+fn check_share(req: &HttpRequest, state: &AppState) -> Result<ShareViewer, ShareRejection> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(shares::credential)
+        .ok_or(ShareRejection::Invalid)?;
+
+    let share = state
+        .db
+        .find_share(&tokens::hash_token(token))
+        .map_err(|e| ShareRejection::Database(e.to_string()))?
+        .ok_or(ShareRejection::Invalid)?;
+    if !state.share_access.allow(share.id) {
+        return Err(ShareRejection::RateLimited);
+    }
+    let passcode = req
+        .headers()
+        .get(shares::PASSCODE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if !shares::passcode_matches(&share, passcode) {
+        return Err(ShareRejection::PasscodeRequired);
+    }
+
+    let peer = req.connection_info().realip_remote_addr().unwrap_or("-").to_string();
+    info!("Share {} ('{}') viewed from {}", share.id, share.name, peer);
+    Ok(ShareViewer::new(&share, &state.servers))
+}
+
+const CORS_ALLOWED_METHODS: &str = "GET, POST, PUT, PATCH, DELETE, OPTIONS";
+const CORS_DEFAULT_HEADERS: &str = "Authorization, Content-Type";
+
+/// Answer a CORS preflight for any API path. `middleware::Cors` adds the
+/// allowed origin; requests from other origins get no CORS headers and are
+/// blocked by the browser.
+pub async fn cors_preflight(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    let allowed = req
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|origin| origin.to_str().ok())
+        .map(|origin| state.config.cors_origin_allowed(origin))
+        .unwrap_or(false);
+
+    let mut response = HttpResponse::NoContent();
+    if allowed {
+        let request_headers = req
+            .headers()
+            .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or(CORS_DEFAULT_HEADERS)
+            .to_string();
+        response
+            .insert_header((header::ACCESS_CONTROL_ALLOW_METHODS, CORS_ALLOWED_METHODS))
+            .insert_header((header::ACCESS_CONTROL_ALLOW_HEADERS, request_headers))
+            .insert_header((header::ACCESS_CONTROL_MAX_AGE, state.config.cors_max_age_secs.to_string()));
+    }
+    response.finish()
+}
+
+pub async fn power_status(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return server_not_found(alias);
+    };
+
+    let (power_state, reason) = tokio::join!(server.client.get_power_state(), server.client.get_last_power_reason());
+    match power_state {
+        Ok(power_state) => {
+            let reason = reason.ok();
+            state.record_power_event(&server.alias, &power_state, reason.as_ref());
+            HttpResponse::Ok().json(StatusResponse {
+                success: true,
+                server_name: server.alias.clone(),
+                power_state,
+                last_power_reason: reason,
+            })
+        }
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// With `verify=true`, waits for the server to actually report `On` before
+/// answering, since an accepted power-on can still fail to POST. Every
+/// power-on is tracked as a boot in the background for the boot report.
+pub async fn power_on_handler(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<PowerOnQuery>,
+) -> HttpResponse {
+    let user_id = match require_power_write(session, &http_req, &state, query.server.as_deref()).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+    let verify_timeout = query.verify_timeout_secs.unwrap_or(POWER_ON_VERIFY_DEFAULT_TIMEOUT_SECS);
+    if query.verify && !(1..=POWER_ON_VERIFY_MAX_TIMEOUT_SECS).contains(&verify_timeout) {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("verify_timeout_secs must be between 1 and {}", POWER_ON_VERIFY_MAX_TIMEOUT_SECS),
+        ));
+    }
+    let os_probe = match query.wait_for_os.as_deref().map(boot::validate_os_probe).transpose() {
+        Ok(os_probe) => os_probe,
+        Err(message) => {
+            return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+                field: "wait_for_os",
+                message,
+            }))
+        }
+    };
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return server_not_found(alias);
+    };
+
+    // The boot report is a side view, so a power-on goes ahead without it.
+    let boot_id = match state.db.create_operation(boot::OPERATION_KIND, Some(&server.alias)) {
+        Ok(id) => {
+            operations::record_stage(&state, &id, boot::STAGE_REQUESTED, None, "running");
+            Some(id)
+        }
+        Err(e) => {
+            error!("Failed to start tracking the boot: {}", e);
+            None
+        }
+    };
+
+    let result = server.client.power_on().await;
+    state.record_server_power_action(&server.alias, &server.client, "PowerOn", &result);
+    state.audit_server(Some(user_id), AuditAction::PowerOn, server.client.base_url(), &result);
+    if let Some(boot_id) = boot_id {
+        match &result {
+            Ok(_) => {
+                tokio::spawn(boot::track_boot(state.get_ref().clone(), server.client.clone(), boot_id, os_probe));
+            }
+            Err(e) => operations::record_stage(&state, &boot_id, "power_on_failed", Some(e), "failed"),
+        }
+    }
+
+    let message = match result {
+        Ok(msg) if !query.verify => return HttpResponse::Ok().json(ApiResponse::success(msg)),
+        Ok(msg) => msg,
+        Err(e) => return idrac_failure(e),
+    };
+
+    let verification =
+        operations::wait_for_power_state(&server.client, "On", Duration::from_secs(verify_timeout)).await;
+    state.audit_server(
+        Some(user_id),
+        AuditAction::PowerOnVerify,
+        server.client.base_url(),
+        &verification
+            .as_ref()
+            .map(|elapsed| format!("Server reached On after {}s", elapsed.as_secs()))
+            .map_err(|e| e.clone()),
+    );
+
+    HttpResponse::Ok().json(PowerOnVerifiedResponse {
+        success: true,
+        message,
+        verified: verification.is_ok(),
+        time_to_on_secs: verification.as_ref().ok().map(|elapsed| elapsed.as_secs()),
+        error: verification.err(),
+    })
+}
+
+/// A disruptive action against a server hosting this application that the
+/// request did not acknowledge; holds the server's alias.
+struct UnacknowledgedHostsThisApp(String);
+
+impl UnacknowledgedHostsThisApp {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationConfirmationRequired,
+            format!(
+                "Server '{}' hosts this controller; confirm with {{\"i_understand_this_hosts_the_controller\": true}}",
+                self.0
+            ),
+        ))
+    }
+}
+
+/// Refuse a disruptive action against a server that hosts this application
+/// unless the request acknowledged it. Returns the warning to pass back
+/// when the server is flagged and the action may go ahead.
+fn hosts_this_app_guard(server: &RegisteredServer, acknowledged: bool) -> Result<Option<String>, UnacknowledgedHostsThisApp> {
+    if !server.hosts_this_app {
+        return Ok(None);
+    }
+    if !acknowledged {
+        return Err(UnacknowledgedHostsThisApp(server.alias.clone()));
+    }
+    Ok(Some(format!(
+        "Server '{}' hosts this controller; the dashboard and API will go down with it",
+        server.alias
+    )))
+}
+
+/// Record that a disruptive `action` against a server hosting this
+/// application went ahead on the user's acknowledgment.
+fn audit_hosts_this_app(state: &AppState, user_id: i64, server: &RegisteredServer, action: &str, warning: &str) {
+    let details = serde_json::json!({ "action": action, "server": server.alias });
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::HostsThisAppAcknowledged,
+        server.client.base_url(),
+        &Ok(warning.to_string()),
+        &details,
+    );
+}
+
+/// A power action sent to a server, for handlers that follow up on it.
+struct SentPowerAction {
+    user_id: i64,
+    server: Arc<RegisteredServer>,
+    message: String,
+    /// Set when the server hosts this application.
+    warning: Option<String>,
+}
+
+impl SentPowerAction {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::Ok().json(ApiResponse::success(self.message).with_warning(self.warning))
+    }
+}
+
+/// Authorize `action` on the server `query` names, let `check` refuse the
+/// request or tell whether it acknowledged that the server hosts this
+/// application, guard such a server, then `send` the action and record and
+/// audit its result. The error is the response to answer with.
+async fn send_power_action<Fut>(
+    session: Session,
+    http_req: &HttpRequest,
+    state: &AppState,
+    query: &ServerQuery,
+    action: AuditAction,
+    check: impl FnOnce(&RegisteredServer) -> Result<bool, HttpResponse>,
+    send: impl FnOnce(Arc<IdracClient>) -> Fut,
+) -> Result<SentPowerAction, HttpResponse>
+where
+    Fut: Future<Output = Result<String, String>>,
+{
+    let user_id = require_power_write(session, http_req, state, query.server.as_deref()).await?;
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return Err(server_not_found(alias));
+    };
+    let acknowledged = check(&server)?;
+    let warning = hosts_this_app_guard(&server, acknowledged).map_err(UnacknowledgedHostsThisApp::into_response)?;
+    if let Some(warning) = &warning {
+        audit_hosts_this_app(state, user_id, &server, action.as_str(), warning);
+    }
+
+    let result = send(server.client.clone()).await;
+    state.record_server_power_action(&server.alias, &server.client, action.as_str(), &result);
+
+    state.audit_server(Some(user_id), action, server.client.base_url(), &result);
+
+    let message = result.map_err(idrac_failure)?;
+    Ok(SentPowerAction { user_id, server, message, warning })
+}
+
+/// `send_power_action` for actions that need nothing but the
+/// acknowledgment, answering with its message.
+async fn run_power_action<Fut>(
+    session: Session,
+    http_req: &HttpRequest,
+    state: &AppState,
+    query: &ServerQuery,
+    acknowledged: bool,
+    action: AuditAction,
+    send: impl FnOnce(Arc<IdracClient>) -> Fut,
+) -> HttpResponse
+where
+    Fut: Future<Output = Result<String, String>>,
+{
+    match send_power_action(session, http_req, state, query, action, |_| Ok(acknowledged), send).await {
+        Ok(sent) => sent.into_response(),
+        Err(response) => response,
+    }
+}
+
+pub async fn power_off_handler(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+    body: Option<web::Json<HostsThisAppAcknowledgment>>,
+) -> HttpResponse {
+    let acknowledged = body.is_some_and(|body| body.i_understand_this_hosts_the_controller);
+    run_power_action(session, &http_req, &state, &query, acknowledged, AuditAction::ForceOff, |client| async move {
+        client.power_off().await
+    })
+    .await
+}
+
+pub async fn force_restart_handler(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+    body: Option<web::Json<HostsThisAppAcknowledgment>>,
+) -> HttpResponse {
+    let acknowledged = body.is_some_and(|body| body.i_understand_this_hosts_the_controller);
+    run_power_action(session, &http_req, &state, &query, acknowledged, AuditAction::ForceRestart, |client| async move {
+        client.force_restart().await
+    })
+    .await
+}
+
+/// Cold reboot: power off and back on without waiting for the OS.
+pub async fn power_cycle_handler(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+    body: Option<web::Json<HostsThisAppAcknowledgment>>,
+) -> HttpResponse {
+    let acknowledged = body.is_some_and(|body| body.i_understand_this_hosts_the_controller);
+    run_power_action(session, &http_req, &state, &query, acknowledged, AuditAction::PowerCycle, |client| async move {
+        client.power_cycle().await
+    })
+    .await
+}
+
+/// Restart through the OS, for servers that must never be reset under a
+/// running workload.
+pub async fn graceful_restart_handler(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+    body: Option<web::Json<HostsThisAppAcknowledgment>>,
+) -> HttpResponse {
+    let acknowledged = body.is_some_and(|body| body.i_understand_this_hosts_the_controller);
+    run_power_action(session, &http_req, &state, &query, acknowledged, AuditAction::GracefulRestart, |client| async move {
+        client.graceful_restart().await
+    })
+    .await
+}
+
+/// Send a diagnostic interrupt for a kernel dump of a hung host. Unlike the
+/// other power actions it needs `{"confirm": true}`.
+pub async fn nmi_handler(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+    body: Option<web::Json<NmiRequest>>,
+) -> HttpResponse {
+    let body = body.map(web::Json::into_inner).unwrap_or_default();
+    let confirmed = |server: &RegisteredServer| {
+        if !body.confirm {
+            return Err(HttpResponse::BadRequest().json(ApiResponse::error(
+                ErrorCode::ValidationConfirmationRequired,
+                format!(
+                    "An NMI usually crashes the OS of '{}' into a dump; confirm with {{\"confirm\": true}}",
+                    server.alias
+                ),
+            )));
+        }
+        Ok(body.i_understand_this_hosts_the_controller)
+    };
+    let sent = send_power_action(session, &http_req, &state, &query, AuditAction::Nmi, confirmed, |client| async move {
+        client.send_nmi().await
+    });
+    match sent.await {
+        Ok(sent) => sent.into_response(),
+        Err(response) => response,
+    }
+}
+
+/// The deadline and mode of a shutdown's escalation, if it asks for one.
+fn shutdown_escalation(body: &ShutdownRequest) -> Result<Option<(Duration, EscalationMode)>, FieldError> {
+    let Some(secs) = body.escalate_after_secs else {
+        if body.escalate.is_some() {
+            return Err(FieldError {
+                field: "escalate_after_secs",
+                message: "is required with escalate".to_string(),
+            });
+        }
+        return Ok(None);
+    };
+    if !(1..=SHUTDOWN_ESCALATE_MAX_SECS).contains(&secs) {
+        return Err(FieldError {
+            field: "escalate_after_secs",
+            message: format!("must be between 1 and {}", SHUTDOWN_ESCALATE_MAX_SECS),
+        });
+    }
+    Ok(Some((Duration::from_secs(secs), body.escalate.unwrap_or(EscalationMode::Alert))))
+}
+
+pub async fn graceful_shutdown_handler(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+    body: Option<web::Json<ShutdownRequest>>,
+) -> HttpResponse {
+    let mut escalation = None;
+    let checked = |_: &RegisteredServer| {
+        escalation = body
+            .as_deref()
+            .map(shutdown_escalation)
+            .transpose()
+            .map_err(|e| HttpResponse::BadRequest().json(FieldErrorResponse::from(e)))?
+            .flatten();
+        Ok(body.as_ref().is_some_and(|body| body.i_understand_this_hosts_the_controller))
+    };
+    let sent = send_power_action(session, &http_req, &state, &query, AuditAction::GracefulShutdown, checked, |client| async move {
+        client.graceful_shutdown().await
+    });
+    let SentPowerAction { user_id, server, message: msg, warning } = match sent.await {
+        Ok(sent) => sent,
+        Err(response) => return response,
+    };
+
+    let (escalate_after, mode) = match escalation {
+        Some(escalation) => escalation,
+        None => {
+            return HttpResponse::Ok().json(ApiResponse::success(msg).with_warning(warning));
+        }
+    };
+
+    let operation_id = match state.db.create_operation("graceful_shutdown", Some(&server.alias)) {
+        Ok(id) => id,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Shutdown sent but failed to track operation: {}", e),
+            ));
+        }
+    };
+    let requested = format!("escalate={:?} after {}s", mode, escalate_after.as_secs());
+    if let Err(e) = state.db.add_operation_stage(&operation_id, "shutdown_requested", Some(&requested), "running") {
+        error!("Failed to update operation {}: {}", operation_id, e);
+    }
+
+    tokio::spawn(operations::run_shutdown_escalation(
+        state.get_ref().clone(),
+        server,
+        operation_id.clone(),
+        user_id,
+        escalate_after,
+        mode,
+    ));
+
+    HttpResponse::Accepted().json(OperationStartedResponse {
+        success: true,
+        message: msg,
+        operation_id,
+        warning,
+    })
+}
+
+/// Mount an ISO, boot from it once and restart, as one tracked operation.
+pub async fn virtual_media_boot_once(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<VirtualMediaBootOnceRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+    let req = req.into_inner();
+
+    let image_url = req.image_url.trim().to_string();
+    let scheme_ok = reqwest::Url::parse(&image_url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https" | "nfs" | "cifs") && url.host_str().is_some());
+    if !scheme_ok {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "image_url",
+            message: "must be an http, https, nfs or cifs URL".to_string(),
+        }));
+    }
+
+    let server = state.servers.default_server();
+    let warning = match hosts_this_app_guard(&server, req.i_understand_this_hosts_the_controller) {
+        Ok(warning) => warning,
+        Err(unacknowledged) => return unacknowledged.into_response(),
+    };
+    if let Some(warning) = &warning {
+        audit_hosts_this_app(&state, user_id, &server, "VirtualMediaBootOnce", warning);
+    }
+
+    let operation_id = match state.db.create_operation("virtual_media_boot_once", Some(&server.alias)) {
+        Ok(id) => id,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to track operation: {}", e),
+            ));
+        }
+    };
+    let requested = format!("{} ({:?} restart)", image_url, req.restart_type);
+    operations::record_stage(&state, &operation_id, "requested", Some(&requested), "running");
+
+    tokio::spawn(operations::run_virtual_media_boot_once(
+        state.get_ref().clone(),
+        operation_id.clone(),
+        user_id,
+        image_url,
+        req.restart_type,
+    ));
+
+    HttpResponse::Accepted().json(OperationStartedResponse {
+        success: true,
+        message: "Mounting virtual media and restarting".to_string(),
+        operation_id,
+        warning,
+    })
+}
+
+pub async fn get_operation(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    match state.db.get_operation(&path.into_inner()) {
+        Ok(Some(operation)) => {
+            let related_events = match state.db.list_operation_events(&operation.id) {
+                Ok(events) => events,
+                Err(e) => {
+                    warn!("Failed to load events of operation {}: {}", operation.id, e);
+                    Vec::new()
+                }
+            };
+            HttpResponse::Ok().json(OperationResponse {
+                success: true,
+                operation,
+                related_events: related_events
+                    .into_iter()
+                    .map(|event| RelatedEvent {
+                        server_alias: event.server_alias,
+                        entry_id: event.entry_id,
+                        created: event.created,
+                        severity: event.severity,
+                        message: event.message,
+                        message_id: event.message_id,
+                        source: event.source,
+                    })
+                    .collect(),
+            })
+        }
+        Ok(None) => HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::OperationNotFound,
+            "Operation not found",
+        )),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Database error: {}", e),
+        )),
+    }
+}
+
+/// Also resumes requests to a server whose credentials were rejected, once
+/// they work again.
+pub async fn test_connection(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::ServerNotFound,
+            format!("No server '{}'", alias),
+        ));
+    };
+
+    match server.client.test_connection().await {
+        Ok(version) => HttpResponse::Ok().json(ConnectionTestResponse {
+            success: true,
+            base_url: server.client.base_url().to_string(),
+            redfish_version: Some(version),
+            message: "Connection successful".to_string(),
+            error_code: None,
+        }),
+        Err(e) => HttpResponse::BadGateway().json(ConnectionTestResponse {
+            success: false,
+            base_url: server.client.base_url().to_string(),
+            redfish_version: None,
+            error_code: Some(ErrorCode::from_idrac_error(&e)),
+            message: e,
+        }),
+    }
+}
+
+fn idrac_logs_filter(query: &IdracLogsQuery) -> Result<(Vec<&'static str>, LogFilter), FieldError> {
+    let sources = logs::parse_sources(query.sources.as_deref())?;
+    let severities = logs::parse_severities(query.severity.as_deref())?;
+    let since = query.since.as_deref().map(|v| parse_timestamp("since", v)).transpose()?;
+    let until = query.until.as_deref().map(|v| parse_timestamp("until", v)).transpose()?;
+    let limit = query.limit.unwrap_or(IDRAC_LOGS_DEFAULT_LIMIT);
+    if limit == 0 || limit > IDRAC_LOGS_MAX_LIMIT {
+        return Err(FieldError {
+            field: "limit",
+            message: format!("must be between 1 and {}", IDRAC_LOGS_MAX_LIMIT),
+        });
+    }
+    let filter = LogFilter {
+        severities,
+        since,
+        until,
+        limit: limit as usize,
+    };
+    Ok((sources, filter))
+}
+
+/// The SEL and Lifecycle Controller log of one server, read live and
+/// merged newest first.
+pub async fn idrac_logs(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<IdracLogsQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return server_not_found(alias);
+    };
+
+    let (sources, filter) = match idrac_logs_filter(&query) {
+        Ok(parsed) => parsed,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    let reads = sources.iter().map(|&source| {
+        let client = &server.client;
+        async move {
+            let entries = if source == "sel" {
+                client.get_sel_entries().await
+            } else {
+                client.get_lc_entries().await
+            };
+            entries.map(|entries| (source, entries))
+        }
+    });
+    match futures_util::future::try_join_all(reads).await {
+        Ok(read) => HttpResponse::Ok().json(IdracLogsResponse {
+            success: true,
+            entries: logs::merge(read, &filter),
+        }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+pub async fn configure_alert_filters(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<AlertFiltersRequest>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    let filters = body.into_inner().filters;
+    let count = filters.len();
+
+    match state.idrac.configure_alert_filters(filters).await {
+        Ok(()) => HttpResponse::Ok().json(ApiResponse::success(format!("Applied {} alert filter(s)", count))),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+pub async fn set_bios_attribute(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<BiosAttributeRequest>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    let BiosAttributeRequest { attribute, value } = body.into_inner();
+
+    match state.idrac.set_bios_attribute(&attribute, value).await {
+        Ok(msg) => HttpResponse::Ok().json(ApiResponse::success(msg)),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Stage a reset of every BIOS setting to its default. Nothing changes
+/// until the next reboot runs the job.
+pub async fn reset_bios_to_defaults(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<BiosResetRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    if req.confirm != BIOS_RESET_CONFIRMATION {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationConfirmationRequired,
+            format!("BIOS reset must be confirmed with {{\"confirm\": \"{}\"}}", BIOS_RESET_CONFIRMATION),
+        ));
+    }
+
+    let result = state.idrac.reset_bios_to_defaults().await;
+    state.audit(
+        Some(user_id),
+        AuditAction::BiosResetToDefaults,
+        &result.clone().map(|job_id| format!("BIOS reset staged as job {}", job_id)),
+    );
+
+    match result {
+        Ok(job_id) => HttpResponse::Ok().json(BiosResetResponse {
+            success: true,
+            message: format!("BIOS reset to defaults staged as job {}; it applies on next reboot", job_id),
+            job_id,
+        }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+pub async fn get_system_profile(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.idrac.get_system_profile().await {
+        Ok(system_profile) => HttpResponse::Ok().json(SystemProfileResponse {
+            success: true,
+            system_profile,
+        }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Stage a new system profile; like any BIOS change it applies on the
+/// next reboot.
+pub async fn set_system_profile(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<SystemProfileRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let result = state.idrac.set_system_profile(req.profile).await;
+    let details = serde_json::json!({ "profile": req.profile });
+    state.audit_with_details(Some(user_id), AuditAction::SystemProfileSet, state.idrac.base_url(), &result, &details);
+
+    match result {
+        Ok(msg) => HttpResponse::Ok().json(ApiResponse::success(msg)),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Stage the BIOS POST watchdog settings; they apply on the next reboot.
+pub async fn configure_post_watchdog(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<PostWatchdogRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let result = state
+        .idrac
+        .configure_post_watchdog(req.enabled, req.timeout_minutes)
+        .await
+        .map(|()| "POST watchdog settings staged; they will apply on next reboot".to_string());
+    let details = serde_json::json!({ "enabled": req.enabled, "timeout_minutes": req.timeout_minutes });
+    state.audit_with_details(Some(user_id), AuditAction::PostWatchdogSet, state.idrac.base_url(), &result, &details);
+
+    match result {
+        Ok(msg) => HttpResponse::Ok().json(ApiResponse::success(msg)),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+pub async fn service_module_status(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.idrac.get_service_module_status().await {
+        Ok(service_module) => HttpResponse::Ok().json(ServiceModuleResponse {
+            success: true,
+            service_module,
+        }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+pub async fn summary_text(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await.is_err() {
+        return HttpResponse::Unauthorized()
+            .content_type("text/plain; charset=utf-8")
+            .body("Not authenticated\n");
+    }
+
+    let (system, firmware, disks) = tokio::join!(
+        state.idrac.get_system_info(),
+        state.idrac.get_idrac_firmware_version(),
+        state.idrac.get_disk_count(),
+    );
+
+    let system = match system {
+        Ok(system) => system,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .content_type("text/plain; charset=utf-8")
+                .body(format!("Error: {}\n", e));
+        }
+    };
+    let firmware = firmware.unwrap_or_else(|_| "Unknown".to_string());
+    let disks = disks.map(|d| d.to_string()).unwrap_or_else(|_| "Unknown".to_string());
+
+    let lines = [
+        ("Hostname", system.hostname),
+        ("Model", system.model),
+        ("Service Tag", system.service_tag),
+        ("Power State", system.power_state),
+        ("Health", system.health),
+        ("BIOS Version", system.bios_version),
+        ("iDRAC Firmware", firmware),
+        ("CPUs", format!("{} x {}", system.cpu_count, system.cpu_model)),
+        ("Memory", format!("{} GiB", system.memory_gib)),
+        ("Disks", disks),
+        ("Last Boot", system.last_boot_time.unwrap_or_else(|| "Unknown".to_string())),
+    ];
+
+    let body: String = lines
+        .iter()
+        .map(|(label, value)| format!("{:<16}{}\n", format!("{}:", label), value))
+        .collect();
+
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(body)
+}
+
+pub async fn self_subscribe(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<SelfSubscribeQuery>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let self_url = match &state.config.self_url {
+        Some(url) => url.clone(),
+        None => {
+            return HttpResponse::BadRequest().json(ApiResponse::error(
+                ErrorCode::ConfigSelfUrlMissing,
+                "SELF_URL is not configured; the iDRAC needs it to reach this application",
+            ));
+        }
+    };
+
+    let destination = format!("{}/api/events/ingest", self_url);
+    let context = uuid::Uuid::new_v4().simple().to_string();
+
+    let event_format = if query.metric_reports { "MetricReport" } else { "Event" };
+    let result = state.idrac.create_event_subscription(&destination, &context, event_format).await;
+    state.audit(Some(user_id), AuditAction::EventSelfSubscribe, &result);
+
+    let subscription_uri = match result {
+        Ok(uri) => uri,
+        Err(e) => {
+            return idrac_failure(e);
+        }
+    };
+
+    if let Err(e) = state.db.create_event_subscription(&destination, &context, &subscription_uri) {
+        return HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Subscription created on iDRAC but could not be saved: {}", e),
+        ));
+    }
+
+    HttpResponse::Ok().json(SubscriptionResponse {
+        success: true,
+        destination,
+        subscription_uri,
+    })
+}
+
+pub async fn certificate_info(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.idrac.get_ssl_certificate_info().await {
+        Ok(certificate) => {
+            let days_remaining = certificate.days_remaining();
+            HttpResponse::Ok().json(CertificateResponse {
+                success: true,
+                expiring_soon: days_remaining
+                    .map(|days| days <= state.config.cert_expiry_warn_days)
+                    .unwrap_or(false),
+                days_remaining,
+                certificate,
+            })
+        }
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Receives events pushed by the iDRAC. Authenticated by the subscription
+/// context token rather than a session, since the caller is the BMC.
+pub async fn ingest_events(
+    state: web::Data<AppState>,
+    body: web::Json<RedfishEventPayload>,
+) -> HttpResponse {
+    let payload = body.into_inner();
+
+    let known = match payload.context.as_deref() {
+        Some(context) => state.db.is_known_event_context(context),
+        None => Ok(false),
+    };
+    match known {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!("Rejected Redfish event delivery with unknown context");
+            return HttpResponse::Unauthorized().json(ApiResponse::error(
+                ErrorCode::AuthInvalidEventContext,
+                "Unknown event context",
+            ));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Database error: {}", e),
+            ));
+        }
+    }
+
+    if !payload.metric_values.is_empty() {
+        return ingest_metric_report(&state, payload);
+    }
+
+    let count = payload.events.len();
+    let mut duplicates = 0;
+    for event in payload.events {
+        // Subscriptions are only created on the `IDRAC_HOST` server.
+        let record = SelRecord {
+            server_alias: DEFAULT_SERVER_ALIAS.to_string(),
+            entry_id: event.event_id.clone().unwrap_or_default(),
+            created: event
+                .event_timestamp
+                .clone()
+                .unwrap_or_else(|| chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false)),
+            severity: event.severity.clone(),
+            message: event.message.clone(),
+            message_id: Some(event.message_id.clone()).filter(|id| !id.is_empty()),
+            source: database::EVENT_SOURCE_REDFISH.to_string(),
+            caused_by: None,
+        };
+        match state.db.record_sel_entries(std::slice::from_ref(&record)) {
+            Ok(0) => {
+                // A redelivery, or already read from the SEL.
+                duplicates += 1;
+                continue;
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to store Redfish event {}: {}", record.entry_id, e),
+        }
+
+        let origin = event.origin_of_condition.and_then(|origin| {
+            origin["@odata.id"]
+                .as_str()
+                .or_else(|| origin.as_str())
+                .map(|o| o.to_string())
+        });
+
+        state.publish(AppEvent::RedfishEvent {
+            event_type: event.event_type,
+            message_id: event.message_id,
+            message: event.message,
+            severity: event.severity,
+            timestamp: event.event_timestamp,
+            origin,
+        });
+    }
+    correlation::correlate(
+        &state,
+        DEFAULT_SERVER_ALIAS,
+        chrono::Utc::now() - chrono::Duration::seconds(correlation::WINDOW_SECS),
+    );
+
+    info!("Ingested {} Redfish event(s), {} duplicate(s)", count, duplicates);
+    HttpResponse::Ok().json(ApiResponse::success(format!(
+        "Accepted {} event(s), {} already stored",
+        count, duplicates
+    )))
+}
+
+/// Redfish timestamps carry an offset; store them in UTC like every other
+/// timestamp.
+fn normalize_redfish_timestamp(timestamp: Option<&str>) -> Option<String> {
+    chrono::DateTime::parse_from_rfc3339(timestamp?)
+        .ok()
+        .map(|time| time.with_timezone(&chrono::Utc).format(SQLITE_TIMESTAMP_FORMAT).to_string())
+}
+
+/// Store the values of a pushed metric report. Subscriptions are only
+/// created on the `IDRAC_HOST` server, so that is where reports come from.
+fn ingest_metric_report(state: &AppState, payload: RedfishEventPayload) -> HttpResponse {
+    let report_timestamp = normalize_redfish_timestamp(payload.timestamp.as_deref())
+        .unwrap_or_else(|| chrono::Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string());
+
+    let samples: Vec<MetricSample> = payload
+        .metric_values
+        .into_iter()
+        .filter_map(|metric| {
+            let value = match metric.metric_value {
+                serde_json::Value::String(value) => value,
+                serde_json::Value::Null => return None,
+                other => other.to_string(),
+            };
+            Some(MetricSample {
+                id: 0,
+                server_alias: DEFAULT_SERVER_ALIAS.to_string(),
+                report_id: payload.id.clone(),
+                metric_id: metric.metric_id.filter(|id| !id.is_empty())?,
+                metric_property: metric.metric_property,
+                numeric_value: value.trim().parse::<f64>().ok().filter(|v| v.is_finite()),
+                value,
+                timestamp: normalize_redfish_timestamp(metric.timestamp.as_deref())
+                    .unwrap_or_else(|| report_timestamp.clone()),
+            })
+        })
+        .collect();
+
+    if let Err(e) = state.db.record_metric_samples(&samples) {
+        return HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to store metric samples: {}", e),
+        ));
+    }
+
+    info!(
+        "Ingested {} metric value(s) from report {}",
+        samples.len(),
+        payload.id.as_deref().unwrap_or("(unnamed)")
+    );
+    HttpResponse::Ok().json(ApiResponse::success(format!("Accepted {} metric value(s)", samples.len())))
+}
+
+/// Define a telemetry report on the iDRAC. Its values reach
+/// `/api/events/ingest` through a metric report subscription, see
+/// `POST /api/idrac/self-subscribe?metric_reports=true`.
+pub async fn create_telemetry_definition(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<TelemetryDefinitionRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let metrics: Vec<&str> = req.metrics.iter().map(|m| m.trim()).filter(|m| !m.is_empty()).collect();
+    if metrics.is_empty() {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "metrics",
+            message: "must name at least one metric".to_string(),
+        }));
+    }
+    if !(METRIC_REPORT_MIN_INTERVAL_SECS..=METRIC_REPORT_MAX_INTERVAL_SECS).contains(&req.report_interval_seconds) {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "report_interval_seconds",
+            message: format!(
+                "must be between {} and {}",
+                METRIC_REPORT_MIN_INTERVAL_SECS, METRIC_REPORT_MAX_INTERVAL_SECS
+            ),
+        }));
+    }
+
+    let result = state
+        .idrac
+        .create_metric_report_definition(&metrics, req.report_interval_seconds)
+        .await;
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::TelemetryDefinitionCreate,
+        state.idrac.base_url(),
+        &result,
+        &serde_json::json!({ "metrics": metrics, "report_interval_seconds": req.report_interval_seconds }),
+    );
+
+    match result {
+        Ok(id) => HttpResponse::Created().json(TelemetryDefinitionResponse { success: true, id }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+pub async fn delete_telemetry_definition(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    let result = state
+        .idrac
+        .delete_metric_report_definition(&id)
+        .await
+        .map(|_| format!("Metric report definition {} deleted", id));
+    state.audit(Some(user_id), AuditAction::TelemetryDefinitionDelete, &result);
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) if e.starts_with("Invalid metric report definition id") => {
+            HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationInvalidValue, e))
+        }
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Stored telemetry samples in time order, or their hourly or daily
+/// rollups for longer ranges.
+pub async fn list_metric_samples(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<MetricSamplesQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let bound = |field, value: &Option<String>| value.as_deref().map(|v| parse_timestamp(field, v)).transpose();
+    let (since, until) = match (bound("since", &query.since), bound("until", &query.until)) {
+        (Ok(since), Ok(until)) => (since, until),
+        (Err(e), _) | (_, Err(e)) => {
+            return HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationInvalidValue, e.to_string()));
+        }
+    };
+    let (keyset, limit) = match pagination::request(
+        query.limit,
+        query.cursor.as_deref(),
+        METRIC_SAMPLES_DEFAULT_LIMIT,
+        METRIC_SAMPLES_MAX_LIMIT,
+    ) {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    let granularity = match rollups::choose(query.granularity.as_deref(), since.as_deref(), until.as_deref()) {
+        Ok(granularity) => granularity,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    let Some(granularity) = granularity else {
+        let samples = state.db.list_metric_samples(
+            query.metric_id.as_deref(),
+            since.as_deref(),
+            until.as_deref(),
+            &keyset,
+            limit + 1,
+        );
+        return match samples {
+            Ok(samples) => HttpResponse::Ok().json(MetricSamplesResponse {
+                granularity: "raw",
+                page: pagination::page(samples, &keyset, limit, None, |sample| (sample.timestamp.clone(), sample.id)),
+            }),
+            Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to list metric samples: {}", e),
+            )),
+        };
+    };
+
+    let rollups = state.db.list_metric_rollups(
+        granularity,
+        query.metric_id.as_deref(),
+        since.as_deref(),
+        until.as_deref(),
+        &keyset,
+        limit + 1,
+    );
+    match rollups {
+        Ok(rollups) => HttpResponse::Ok().json(MetricSamplesResponse {
+            granularity: granularity.as_str(),
+            page: pagination::page(rollups, &keyset, limit, None, |rollup| (rollup.bucket_start.clone(), rollup.id)),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to list metric rollups: {}", e),
+        )),
+    }
+}
+
+/// Server-sent events stream of everything published on the event bus.
+pub async fn event_stream(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    let receiver = state.events.subscribe();
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let data = serde_json::to_string(&event).unwrap_or_default();
+                    let chunk = web::Bytes::from(format!("data: {}\n\n", data));
+                    return Some((Ok::<_, actix_web::Error>(chunk), receiver));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Event stream subscriber lagged, skipped {} event(s)", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream)
+}
+
+/// WebSocket pushing a server's power state each time it changes; see
+/// `power_ws`. `?server=<alias>` as for the power routes.
+pub async fn power_websocket(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+    payload: web::Payload,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return server_not_found(alias);
+    };
+    let every = Duration::from_secs(state.config.idrac_poll_interval_secs);
+    power_ws::start(&http_req, payload, server.alias.clone(), server.client.clone(), every)
+}
+
+const OPENAPI_DOCUMENT: &str = include_str!("../openapi.json");
+
+#[derive(Serialize)]
+pub struct ErrorCodesResponse {
+    pub success: bool,
+    pub codes: Vec<&'static str>,
+}
+
+/// Registered servers by alias.
+pub async fn list_servers(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<PageQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+    let (keyset, limit) =
+        match pagination::request::<String>(query.limit, query.cursor.as_deref(), SERVERS_DEFAULT_LIMIT, SERVERS_MAX_LIMIT) {
+            Ok(request) => request,
+            Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+        };
+
+    let mut servers = state.servers.all();
+    servers.sort_by(|a, b| a.alias.cmp(&b.alias));
+    let page = pagination::page_sorted(servers, &keyset, limit, |server| server.alias.clone());
+
+    // Only the servers on this page are resolved.
+    let state = &state;
+    let summaries = page.items.iter().map(|server| async move {
+        ServerSummary {
+            hostname: server.client.hostname().await,
+            maintenance: state.servers.maintenance(&server.alias),
+            os_health: state.os_health.read().unwrap().get(&server.alias).cloned(),
+            ..ServerSummary::from(server.as_ref())
+        }
+    });
+    HttpResponse::Ok().json(Page {
+        success: true,
+        items: futures_util::future::join_all(summaries).await,
+        page: page.page,
+    })
+}
+
+/// Register an additional iDRAC. The password is never returned.
+/// Map an iDRAC address (as seen in event payloads) back to its alias.
+pub async fn server_by_host(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerByHostQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.servers.find_by_host(&query.host) {
+        Some(server) => HttpResponse::Ok().json(ServerByHostResponse {
+            success: true,
+            alias: server.alias.clone(),
+            host: server.client.base_url().to_string(),
+            tags: server.tags.clone(),
+        }),
+        None => HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::ServerNotFound,
+            format!("No server registered for host '{}'", query.host.trim()),
+        )),
+    }
+}
+
+pub async fn create_server(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<CreateServerRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let req = req.into_inner();
+    let mut server = match validate_new_server(
+        &req.name,
+        &req.host,
+        &req.username,
+        req.password,
+        &req.tags,
+        req.location.as_deref(),
+    ) {
+        Ok(server) => server,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    if req.default_power_cap_watts == Some(0) {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "default_power_cap_watts",
+            message: "must be greater than zero".to_string(),
+        }));
+    }
+    server.default_power_cap_watts = req.default_power_cap_watts;
+    server.hosts_this_app = req.hosts_this_app;
+    if let Some(url) = req.os_health_url.as_deref().filter(|url| !url.trim().is_empty()) {
+        match normalize_os_health_url("os_health_url", url) {
+            Ok(url) => server.os_health_url = Some(url),
+            Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+        }
+        server.os_health_insecure = req.os_health_insecure;
+        server.os_health_token = req.os_health_token.filter(|token| !token.is_empty());
+    }
+
+    if let Some(reason) = state.servers.find_duplicate(&server.name, &server.host) {
+        return HttpResponse::Conflict().json(ApiResponse::error(ErrorCode::ServerDuplicate, reason));
+    }
+
+    match state.servers.create(&state.db, server) {
+        Ok(server) => {
+            state.audit(Some(user_id), AuditAction::ServerCreate, &Ok(format!("Registered {}", server.alias)));
+            HttpResponse::Created().json(ServerResponse {
+                success: true,
+                server: ServerSummary::from(server.as_ref()),
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Rotate the iDRAC username and/or password of a registered server, then
+/// test the connection with them right away.
+pub async fn update_server_credentials(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<UpdateCredentialsRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = path.into_inner();
+    let Some(server) = state.servers.get(&alias) else {
+        return server_not_found(&alias);
+    };
+    let Some(id) = server.id else {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!(
+                "The credentials of '{}' come from IDRAC_USERNAME and IDRAC_PASSWORD or Vault",
+                DEFAULT_SERVER_ALIAS
+            ),
+        ));
+    };
+
+    let req = req.into_inner();
+    let username = req.username.as_deref().map(str::trim);
+    if username == Some("") {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "username",
+            message: "must not be empty".to_string(),
+        }));
+    }
+    if req.password.as_ref().is_some_and(|password| password.is_empty()) {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "password",
+            message: "must not be empty".to_string(),
+        }));
+    }
+    if username.is_none() && req.password.is_none() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationMissingField,
+            "Give a username, a password or both",
+        ));
+    }
+
+    let server = match state.servers.update_credentials(&state.db, id, username, req.password.as_ref()) {
+        Ok(server) => server,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    };
+
+    let started = std::time::Instant::now();
+    let test = match tokio::time::timeout(CREDENTIALS_TEST_TIMEOUT, server.client.test_connection()).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("Connection test timed out after {}s", CREDENTIALS_TEST_TIMEOUT.as_secs())),
+    };
+    let changed = match (username, &req.password) {
+        (Some(_), Some(_)) => "username and password",
+        (Some(_), None) => "username",
+        _ => "password",
+    };
+    let outcome = match &test {
+        Ok(_) => "connection test passed",
+        Err(_) => "connection test failed",
+    };
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::ServerCredentialsUpdate,
+        &server.alias,
+        &Ok(format!("Updated the {} of {}; {}", changed, server.alias, outcome)),
+        &serde_json::json!({ "changed": changed, "test_passed": test.is_ok() }),
+    );
+
+    HttpResponse::Ok().json(match test {
+        Ok(_) => CredentialsUpdateResponse {
+            success: true,
+            test_passed: true,
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            error: None,
+            error_code: None,
+        },
+        Err(e) => CredentialsUpdateResponse {
+            success: true,
+            test_passed: false,
+            latency_ms: None,
+            error_code: Some(ErrorCode::from_idrac_error(&e)),
+            error: Some(e),
+        },
+    })
+}
+
+/// Set or clear the OS health endpoint of a registered server. The first
+/// probe runs on the next round of `OS_HEALTH_INTERVAL_SECS`.
+pub async fn update_server_os_health(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<OsHealthRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = path.into_inner();
+    let Some(server) = state.servers.get(&alias) else {
+        return server_not_found(&alias);
+    };
+    let Some(id) = server.id else {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("The OS health endpoint of '{}' comes from OS_HEALTH_URL", DEFAULT_SERVER_ALIAS),
+        ));
+    };
+
+    let req = req.into_inner();
+    let url = match req.url.as_deref().filter(|url| !url.trim().is_empty()) {
+        Some(url) => match normalize_os_health_url("url", url) {
+            Ok(url) => Some(url),
+            Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+        },
+        None => None,
+    };
+    // Clearing the endpoint clears its settings too.
+    let insecure = url.is_some() && req.insecure;
+    let token = req.token.filter(|token| url.is_some() && !token.is_empty());
+
+    let server = match state.servers.update_os_health(&state.db, id, url.as_deref(), insecure, token.as_ref()) {
+        Ok(server) => server,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    };
+    let message = match &url {
+        Some(url) => format!("Probing the OS of {} at {}", server.alias, url),
+        None => format!("Stopped probing the OS of {}", server.alias),
+    };
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::ServerOsHealthUpdate,
+        &server.alias,
+        &Ok(message),
+        &serde_json::json!({ "url": url, "insecure": insecure, "has_token": token.is_some() }),
+    );
+
+    HttpResponse::Ok().json(ServerResponse {
+        success: true,
+        server: ServerSummary::from(server.as_ref()),
+    })
+}
+
+/// Unregister a server added through `POST /api/servers`. It is also taken
+/// out of every group it belongs to.
+pub async fn delete_server(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = path.into_inner();
+    let Some(server) = state.servers.get(&alias) else {
+        return server_not_found(&alias);
+    };
+    let Some(id) = server.id else {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("'{}' is configured through IDRAC_HOST and cannot be deleted", DEFAULT_SERVER_ALIAS),
+        ));
+    };
+    if let Some(reason) = state.servers.maintenance(&alias) {
+        return HttpResponse::Conflict().json(ApiResponse::error(
+            ErrorCode::OperationInvalidState,
+            format!("Server '{}' is in maintenance ({}); try again once it is done", alias, reason),
+        ));
+    }
+
+    match state.servers.remove(&state.db, id) {
+        Ok(true) => {}
+        Ok(false) => return server_not_found(&alias),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+
+    let groups = state.db.list_server_groups().unwrap_or_else(|e| {
+        error!("Failed to load groups to remove '{}' from: {}", alias, e);
+        Vec::new()
+    });
+    for group in groups.into_iter().filter(|group| group.members.contains(&alias)) {
+        let members: Vec<String> = group.members.into_iter().filter(|member| *member != alias).collect();
+        if let Err(e) = state.db.update_server_group(group.id, &members, group.power_budget_watts) {
+            error!("Failed to remove '{}' from group '{}': {}", alias, group.name, e);
+        }
+    }
+
+    state.audit_server(Some(user_id), AuditAction::ServerDelete, &alias, &Ok(format!("Deleted {}", server.client.base_url())));
+    HttpResponse::Ok().json(ApiResponse::success(format!("Server '{}' deleted", alias)))
+}
+
+/// Bulk-register servers from a CSV request body with columns name, host,
+/// username, password (or credential_profile), tags and location.
+pub async fn import_servers(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let options = ImportOptions {
+        validate_only: query.validate_only,
+        test_connections: query.test_connections,
+    };
+    let report = match server_import::import_servers(&state, &body, options).await {
+        Ok(report) => report,
+        Err(e) => return HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationInvalidBody, e)),
+    };
+
+    if !options.validate_only {
+        info!("Server import: {}", report.summary());
+        let details = serde_json::to_value(&report).unwrap_or_default();
+        state.audit_with_details(Some(user_id), AuditAction::ServerImport, "fleet", &Ok(report.summary()), &details);
+    }
+
+    HttpResponse::Ok().json(ImportResponse {
+        success: true,
+        report,
+    })
+}
+
+/// Audit log totals by action, user and server for management reporting.
+pub async fn audit_statistics(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<AuditStatisticsQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::AuditRead).await {
+        return response;
+    }
+
+    let bound = |field, value: &Option<String>| value.as_deref().map(|v| parse_timestamp(field, v)).transpose();
+    let (since, until) = match (bound("since", &query.since), bound("until", &query.until)) {
+        (Ok(since), Ok(until)) => (since, until),
+        (Err(e), _) | (_, Err(e)) => {
+            return HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationInvalidValue, e.to_string()));
+        }
+    };
+
+    match state.db.audit_statistics(since.as_deref(), until.as_deref()) {
+        Ok(statistics) => HttpResponse::Ok().json(AuditStatisticsResponse {
+            success: true,
+            statistics,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// The latest audit entries, newest first, each rendered as a sentence.
+pub async fn list_changes(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ChangesQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::AuditRead).await {
+        return response;
+    }
+
+    let limit = query.limit.unwrap_or(CHANGES_DEFAULT_LIMIT);
+    if limit == 0 || limit > CHANGES_MAX_LIMIT {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "limit",
+            message: format!("must be between 1 and {}", CHANGES_MAX_LIMIT),
+        }));
+    }
+
+    // Paging back from past the newest id lists newest first.
+    match state.db.list_audit_page(&Keyset::Before(i64::MAX), limit, false) {
+        Ok(entries) => {
+            let now = chrono::Utc::now();
+            HttpResponse::Ok().json(ChangesResponse {
+                success: true,
+                changes: entries.iter().map(|entry| Change::from_audit(entry, &state.servers, now)).collect(),
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// The audit log newest first, paginated by id. Admin only, also with an
+/// `audit:read` token.
+pub async fn list_audit(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<PageQuery>,
+) -> HttpResponse {
+    if let Err(response) = require_role(session, &http_req, &state, TokenScope::AuditRead, UserRole::Admin).await {
+        return response;
+    }
+
+    let (keyset, limit) = match pagination::request(query.limit, query.cursor.as_deref(), AUDIT_DEFAULT_LIMIT, AUDIT_MAX_LIMIT) {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    match state.db.list_audit_page(&keyset, limit + 1, true) {
+        Ok(entries) => {
+            let total_estimate = state.db.audit_count_estimate().ok();
+            HttpResponse::Ok().json(pagination::page(entries, &keyset, limit, total_estimate, |entry| entry.id))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// Page through the audit log in id order for external collectors. Resume
+/// from the returned `next_after_id` (also in `checkpoint`) on the next pull.
+pub async fn audit_export(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<PageQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::AuditRead).await {
+        return response;
+    }
+
+    let (keyset, limit) =
+        match pagination::request(query.limit, query.cursor.as_deref(), AUDIT_EXPORT_DEFAULT_LIMIT, AUDIT_EXPORT_MAX_LIMIT) {
+            Ok(request) => request,
+            Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+        };
+
+    let entries = match state.db.list_audit_page(&keyset, limit + 1, false) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()));
+        }
+    };
+    let total_estimate = state.db.audit_count_estimate().ok();
+    let page = pagination::page(entries, &keyset, limit, total_estimate, |entry| entry.id);
+
+    // Resume after the newest entry seen, or where this pull started.
+    let resume = match page.items.iter().map(|entry| entry.id).max() {
+        Some(id) => Keyset::After(id),
+        None => keyset,
+    };
+    HttpResponse::Ok().json(AuditExportResponse {
+        page,
+        checkpoint: AuditCheckpoint {
+            cursor: pagination::encode_cursor(&resume),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+        },
+    })
+}
+
+/// Audit actions that changed a server's power state, for the power event export.
+const POWER_EVENT_ACTIONS: &[&str] = &[
+    "PowerOn",
+    "PowerOnVerify",
+    "ForceOff",
+    "GracefulShutdown",
+    "ForceRestart",
+    "GracefulRestart",
+    "PowerCycle",
+    "GroupPowerOn",
+];
+
+const AUDIT_CSV_COLUMNS: &[Column<AuditEntry>] = &[
+    Column { name: "id", value: |entry| entry.id.to_string().into() },
+    Column { name: "time", value: |entry| Cell::Time(entry.created_at.clone()) },
+    Column { name: "username", value: |entry| entry.username.clone().into() },
+    Column { name: "action", value: |entry| entry.action.clone().into() },
+    Column { name: "server", value: |entry| entry.server_name.clone().into() },
+    Column { name: "result", value: |entry| entry.result.clone().into() },
+    Column { name: "error", value: |entry| entry.error_message.clone().into() },
+    Column { name: "details", value: |entry| entry.details.as_ref().map(|details| details.to_string()).into() },
+];
+
+const METRIC_SAMPLE_CSV_COLUMNS: &[Column<MetricSample>] = &[
+    Column { name: "time", value: |sample| Cell::Time(sample.timestamp.clone()) },
+    Column { name: "server", value: |sample| sample.server_alias.clone().into() },
+    Column { name: "report_id", value: |sample| sample.report_id.clone().into() },
+    Column { name: "metric_id", value: |sample| sample.metric_id.clone().into() },
+    Column { name: "metric_property", value: |sample| sample.metric_property.clone().into() },
+    Column { name: "value", value: |sample| sample.value.clone().into() },
+];
+
+/// `since` and `until` in the database's format.
+fn csv_time_range(since: &Option<String>, until: &Option<String>) -> Result<(Option<String>, Option<String>), FieldError> {
+    let bound = |field, value: &Option<String>| value.as_deref().map(|v| parse_timestamp(field, v)).transpose();
+    Ok((bound("since", since)?, bound("until", until)?))
+}
+
+/// Chunks of the audit log in id order, within `since`..=`until` and
+/// narrowed by `keep`. Ids grow with time, so the first entry past `until`
+/// ends the export.
+fn audit_csv_chunks(
+    state: web::Data<AppState>,
+    since: Option<String>,
+    until: Option<String>,
+    keep: impl Fn(&AuditEntry) -> bool + 'static,
+) -> impl FnMut() -> Result<Option<Vec<AuditEntry>>, String> {
+    let mut keyset = Keyset::First;
+    let mut done = false;
+    move || {
+        if done {
+            return Ok(None);
+        }
+        let entries = state.db.list_audit_page(&keyset, csv_export::CHUNK_ROWS, false).map_err(|e| e.to_string())?;
+        done = entries.len() < csv_export::CHUNK_ROWS as usize;
+        if let Some(last) = entries.last() {
+            keyset = Keyset::After(last.id);
+        }
+        let mut chunk = Vec::with_capacity(entries.len());
+        for entry in entries {
+            if until.as_deref().is_some_and(|until| entry.created_at.as_str() > until) {
+                done = true;
+                break;
+            }
+            if since.as_deref().is_none_or(|since| entry.created_at.as_str() >= since) && keep(&entry) {
+                chunk.push(entry);
+            }
+        }
+        Ok(Some(chunk))
+    }
+}
+
+/// The audit log as a CSV download, optionally within `since`..=`until`.
+pub async fn audit_export_csv(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<AuditCsvQuery>,
+    csv: web::Query<CsvQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::AuditRead).await {
+        return response;
+    }
+
+    let format = match CsvFormat::parse(&csv, AUDIT_CSV_COLUMNS) {
+        Ok(format) => format,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let (since, until) = match csv_time_range(&query.since, &query.until) {
+        Ok(range) => range,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    csv_export::respond("audit.csv", format, audit_csv_chunks(state, since, until, |_| true))
+}
+
+/// The server's power baseline for the current hour of the week and its
+/// active power anomaly, if any.
+pub async fn server_power_anomaly(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    let alias = path.into_inner();
+    if state.servers.get(&alias).is_none() {
+        return server_not_found(&alias);
+    }
+    let hour_of_week = power_anomaly::hour_of_week(chrono::Local::now());
+    let baseline = match state.db.get_power_baseline(&alias, hour_of_week) {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()))
+        }
+    };
+    let (anomaly, consecutive_deviations) = state.power_anomalies.get(&alias);
+    HttpResponse::Ok().json(PowerAnomalyResponse {
+        success: true,
+        server: alias,
+        hour_of_week,
+        baseline_ready: baseline
+            .as_ref()
+            .is_some_and(|baseline| baseline.weeks.len() >= power_anomaly::MIN_BASELINE_WEEKS),
+        baseline,
+        consecutive_deviations,
+        anomaly,
+    })
+}
+
+/// Power states observed on servers, newest first, with the reason the
+/// iDRAC gives for each change.
+pub async fn power_history(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<PowerHistoryQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    let server = query.server.as_deref().map(str::trim).filter(|alias| !alias.is_empty());
+    let limit = query.limit.unwrap_or(POWER_HISTORY_DEFAULT_LIMIT);
+    if limit == 0 || limit > POWER_HISTORY_MAX_LIMIT {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "limit",
+            message: format!("must be between 1 and {}", POWER_HISTORY_MAX_LIMIT),
+        }));
+    }
+
+    match state.db.list_power_events(server, limit) {
+        Ok(events) => HttpResponse::Ok().json(PowerHistoryResponse { success: true, events }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// Power actions from the audit log as a CSV download, optionally of one
+/// server and within `since`..=`until`.
+pub async fn power_events_csv(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<PowerEventsCsvQuery>,
+    csv: web::Query<CsvQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    let format = match CsvFormat::parse(&csv, AUDIT_CSV_COLUMNS) {
+        Ok(format) => format,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let (since, until) = match csv_time_range(&query.since, &query.until) {
+        Ok(range) => range,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let server = query.server.clone();
+    let keep = move |entry: &AuditEntry| {
+        POWER_EVENT_ACTIONS.contains(&entry.action.as_str())
+            && server.as_deref().is_none_or(|server| entry.server_name == server)
+    };
+    csv_export::respond("power-events.csv", format, audit_csv_chunks(state, since, until, keep))
+}
+
+/// Stored telemetry samples as a CSV download, with the filters of
+/// `list_metric_samples`. Always the raw samples, never rollups.
+pub async fn metric_samples_csv(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<MetricSamplesCsvQuery>,
+    csv: web::Query<CsvQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let format = match CsvFormat::parse(&csv, METRIC_SAMPLE_CSV_COLUMNS) {
+        Ok(format) => format,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let (since, until) = match csv_time_range(&query.since, &query.until) {
+        Ok(range) => range,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let metric_id = query.metric_id.clone();
+    let mut keyset = Keyset::First;
+    let mut done = false;
+    let fetch = move || {
+        if done {
+            return Ok(None);
+        }
+        let samples = state
+            .db
+            .list_metric_samples(metric_id.as_deref(), since.as_deref(), until.as_deref(), &keyset, csv_export::CHUNK_ROWS)
+            .map_err(|e| e.to_string())?;
+        done = samples.len() < csv_export::CHUNK_ROWS as usize;
+        if let Some(last) = samples.last() {
+            keyset = Keyset::After((last.timestamp.clone(), last.id));
+        }
+        Ok(Some(samples))
+    };
+    csv_export::respond("telemetry-samples.csv", format, fetch)
+}
+
+/// Active accounts, or every account with `?include_expired=true`.
+pub async fn list_users(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ListUsersQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    match state.db.list_users(query.include_expired) {
+        Ok(users) => HttpResponse::Ok().json(UsersResponse {
+            success: true,
+            users,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// Add another account, optionally one that stops working at `expires_at`.
+pub async fn create_user(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<CreateUserRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let username = match normalize_username(&req.username) {
+        Ok(username) => username,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    if req.password.len() < 8 {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationPasswordTooShort,
+            "Password must be at least 8 characters",
+        ));
+    }
+    let expires_at = match req.expires_at.as_deref().map(|v| parse_timestamp("expires_at", v)).transpose() {
+        Ok(expires_at) => expires_at,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    let role = req.role.unwrap_or(UserRole::ReadOnly);
+
+    let result = state
+        .db
+        .create_user(&username, &req.password, expires_at.as_deref(), role)
+        .map_err(|e| {
+            if e.is_unique_violation() {
+                format!("A user named '{}' already exists", username)
+            } else {
+                e.to_string()
+            }
+        });
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::UserCreate,
+        "app",
+        &result.as_ref().map(|_| username.clone()).map_err(|e| e.clone()),
+        &serde_json::json!({ "username": username, "expires_at": expires_at, "role": role }),
+    );
+
+    match result {
+        Ok(_) => HttpResponse::Created().json(ApiResponse::success(format!("User '{}' created", username))),
+        Err(e) if e.ends_with("already exists") => {
+            HttpResponse::Conflict().json(ApiResponse::error(ErrorCode::UserDuplicate, e))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Set, extend or clear an account's expiry.
+pub async fn set_user_expiry(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    req: web::Json<UserExpiryRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let target_id = path.into_inner();
+    let expires_at = match req.expires_at.as_deref().map(|v| parse_timestamp("expires_at", v)).transpose() {
+        Ok(expires_at) => expires_at,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    let target = match state.db.get_user_by_id(target_id) {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ApiResponse::error(ErrorCode::UserNotFound, format!("No user with id {}", target_id)));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()));
+        }
+    };
+
+    let result = state
+        .db
+        .set_user_expiry(target_id, expires_at.as_deref())
+        .map(|_| format!("Expiry of '{}' updated", target.username))
+        .map_err(|e| e.to_string());
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::UserExpiryUpdate,
+        "app",
+        &result,
+        &serde_json::json!({
+            "user": target.username,
+            "before": target.expires_at,
+            "after": expires_at,
+        }),
+    );
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Accounts expiring within the next `days` days and those that expired
+/// within the past `days` days.
+pub async fn user_expirations(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ExpirationsQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    let days = query.days.unwrap_or(EXPIRATIONS_DEFAULT_DAYS);
+    if days == 0 || days > EXPIRATIONS_MAX_DAYS {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("days must be between 1 and {}", EXPIRATIONS_MAX_DAYS),
+        ));
+    }
+
+    match expirations_within(&state, days) {
+        Ok(expirations) => HttpResponse::Ok().json(expirations),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// Accounts and server grants expiring within `days` days either side of
+/// now; the recent ones most recent first.
+fn expirations_within(state: &AppState, days: u32) -> database::Result<ExpirationsResponse> {
+    let now = chrono::Utc::now();
+    let window = chrono::Duration::days(days as i64);
+    let format = |time: chrono::DateTime<chrono::Utc>| time.format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    let (past, now, soon) = (format(now - window), format(now), format(now + window));
+
+    let mut recent = state.db.list_user_expirations(&past, &now)?;
+    recent.reverse();
+    let mut recent_grants = state.db.list_server_grant_expirations(&past, &now)?;
+    recent_grants.reverse();
+    Ok(ExpirationsResponse {
+        success: true,
+        days,
+        upcoming: state.db.list_user_expirations(&now, &soon)?,
+        recent,
+        upcoming_grants: state.db.list_server_grant_expirations(&now, &soon)?,
+        recent_grants,
+    })
+}
+
+/// A user's server grants; expired ones only with `?include_expired=true`.
+pub async fn list_server_grants(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    query: web::Query<ListUsersQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    match state.db.list_server_grants(path.into_inner(), query.include_expired) {
+        Ok(grants) => HttpResponse::Ok().json(ServerGrantsResponse { success: true, grants }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// Let a user run power actions on one server, or set, extend or clear the
+/// expiry of the grant they already hold.
+pub async fn set_server_grant(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(i64, String)>,
+    req: web::Json<ServerGrantRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let (target_id, alias) = path.into_inner();
+    let expires_at = match req.expires_at.as_deref().map(|v| parse_timestamp("expires_at", v)).transpose() {
+        Ok(expires_at) => expires_at,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    if state.servers.get(&alias).is_none() {
+        return server_not_found(&alias);
+    }
+    let target = match state.db.get_user_by_id(target_id) {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ApiResponse::error(ErrorCode::UserNotFound, format!("No user with id {}", target_id)));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()));
+        }
+    };
+
+    let before = match state.db.get_server_grant(target_id, &alias) {
+        Ok(before) => before,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    };
+    let result = state.db.set_server_grant(target_id, &alias, expires_at.as_deref()).map_err(|e| e.to_string());
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::ServerGrantUpdate,
+        &alias,
+        &result.as_ref().map(|_| format!("Grant of '{}' on '{}' updated", target.username, alias)).map_err(|e| e.clone()),
+        &serde_json::json!({
+            "user": target.username,
+            "granted": before.is_none(),
+            "before": before.and_then(|grant| grant.expires_at),
+            "after": expires_at,
+        }),
+    );
+
+    match result {
+        Ok(grant) => HttpResponse::Ok().json(ServerGrantResponse { success: true, grant }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Take a server grant away before it expires.
+pub async fn delete_server_grant(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(i64, String)>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let (target_id, alias) = path.into_inner();
+    let grant = match state.db.get_server_grant(target_id, &alias) {
+        Ok(Some(grant)) => grant,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ApiResponse::error(
+                ErrorCode::GrantNotFound,
+                format!("User {} has no grant on server '{}'", target_id, alias),
+            ));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string()));
+        }
+    };
+
+    let result = state
+        .db
+        .delete_server_grant(target_id, &alias)
+        .map(|_| format!("Grant of '{}' on '{}' revoked", grant.username, alias))
+        .map_err(|e| e.to_string());
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::ServerGrantRevoke,
+        &alias,
+        &result,
+        &serde_json::json!({ "user": grant.username, "expires_at": grant.expires_at }),
+    );
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+fn retention_policy_response(policy: RetentionPolicy) -> RetentionPolicyResponse {
+    RetentionPolicyResponse {
+        success: true,
+        policy,
+        warnings: policy.warnings(),
+    }
+}
+
+/// The data retention policy in effect, with a warning for anything kept forever.
+pub async fn get_retention_policy(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    let policy = *state.retention.read().unwrap();
+    HttpResponse::Ok().json(retention_policy_response(policy))
+}
+
+/// Replace the retention policy. Takes effect at the next cleanup run and
+/// is kept across restarts.
+pub async fn put_retention_policy(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<RetentionPolicy>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let policy = req.into_inner();
+    if let Err(e) = policy.validate() {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(e));
+    }
+
+    let before = *state.retention.read().unwrap();
+    let result = policy.save(&state.db).map(|_| "Retention policy updated".to_string());
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::RetentionPolicyUpdate,
+        "app",
+        &result,
+        &serde_json::json!({ "before": before, "after": policy }),
+    );
+
+    match result {
+        Ok(_) => {
+            *state.retention.write().unwrap() = policy;
+            HttpResponse::Ok().json(retention_policy_response(policy))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+pub async fn list_tokens(
+    session: Session,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let user_id = match check_auth(session, &state).await {
+        Ok(user) => user.id,
+        Err(response) => return response,
+    };
+
+    match state.db.list_api_tokens(user_id) {
+        Ok(tokens) => HttpResponse::Ok().json(TokenListResponse {
+            success: true,
+            tokens,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// Create an API token for the current user. The secret is returned once.
+pub async fn create_token(
+    session: Session,
+    state: web::Data<AppState>,
+    req: web::Json<CreateTokenRequest>,
+) -> HttpResponse {
+    let granted_scopes = session_scopes(&session);
+    let user_id = match check_auth(session, &state).await {
+        Ok(user) => user.id,
+        Err(response) => return response,
+    };
+
+    // Break-glass access must not outlive its session.
+    if state.is_break_glass_user(user_id) {
+        return HttpResponse::Forbidden().json(ApiResponse::error(
+            ErrorCode::AuthBreakGlassRestricted,
+            "API tokens cannot be created during break-glass access",
+        ));
+    }
+
+    let name = match normalize_token_name(&req.name) {
+        Ok(name) => name,
+        Err(e) => return HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationInvalidField, e.to_string())),
+    };
+    if req.scopes.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationMissingField, "scopes must not be empty"));
+    }
+    if let Some(unknown) = req.scopes.iter().find(|s| TokenScope::parse(s).is_none()) {
+        let known: Vec<&str> = TokenScope::ALL.iter().map(|s| s.as_str()).collect();
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("Unknown scope '{}'; expected one of {}", unknown, known.join(", ")),
+        ));
+    }
+    if req.requests_per_hour == Some(0) {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            "requests_per_hour must be at least 1",
+        ));
+    }
+    // A token must not outgrow the session it is created from.
+    if let Some(granted) = granted_scopes {
+        if let Some(scope) = req
+            .scopes
+            .iter()
+            .filter_map(|s| TokenScope::parse(s))
+            .find(|&scope| !granted.iter().any(|g| g.grants(scope)))
+        {
+            return HttpResponse::Forbidden().json(ApiResponse::error(
+                ErrorCode::AuthMissingScope,
+                format!("This login is not granted the '{}' scope", scope),
+            ));
+        }
+    }
+
+    let secret = tokens::generate_token();
+    let result = state.db.create_api_token(user_id, &name, &tokens::hash_token(&secret), &req.scopes, req.requests_per_hour);
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::ApiTokenCreate,
+        "app",
+        &result.as_ref().map(|t| t.name.clone()).map_err(|e| e.to_string()),
+        &serde_json::json!({ "scopes": req.scopes, "requests_per_hour": req.requests_per_hour }),
+    );
+
+    match result {
+        Ok(token) => HttpResponse::Created().json(TokenResponse {
+            success: true,
+            token,
+            secret: Some(secret),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+pub async fn delete_token(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> HttpResponse {
+    let user_id = match check_auth(session, &state).await {
+        Ok(user) => user.id,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    let result = match state.db.delete_api_token(user_id, id) {
+        Ok(true) => Ok(format!("Token {} revoked", id)),
+        Ok(false) => {
+            return HttpResponse::NotFound().json(ApiResponse::error(ErrorCode::TokenNotFound, format!("No token with id {}", id)));
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    state.audit_server(Some(user_id), AuditAction::ApiTokenRevoke, "app", &result);
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Create a read-only status link. The URL, which holds the token, is
+/// returned once.
+pub async fn create_share(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<CreateShareRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let validated = normalize_share_name(&req.name).and_then(|name| {
+        let (servers, tag) = normalize_share_scope(req.servers.as_deref(), req.tag.as_deref(), |alias| {
+            state.servers.get(alias).is_some()
+        })?;
+        let expires_at = shares::parse_expiry(&req.expires_at)?;
+        if let Some(passcode) = &req.passcode {
+            validate_share_passcode(passcode)?;
+        }
+        Ok((name, servers, tag, expires_at))
+    });
+    let (name, servers, tag, expires_at) = match validated {
+        Ok(validated) => validated,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let passcode_hash = match req.passcode.as_deref().map(|passcode| bcrypt::hash(passcode, bcrypt::DEFAULT_COST)).transpose() {
+        Ok(passcode_hash) => passcode_hash,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    };
+
+    let token = tokens::generate_token();
+    let result = state.db.create_share(&NewShare {
+        name: &name,
+        servers: &servers,
+        tag: tag.as_deref(),
+        token_hash: &tokens::hash_token(&token),
+        passcode_hash: passcode_hash.as_deref(),
+        created_by: user_id,
+        expires_at: &expires_at,
+    });
+    let details = serde_json::json!({
+        "name": name,
+        "servers": servers,
+        "tag": tag,
+        "expires_at": expires_at,
+        "has_passcode": passcode_hash.is_some(),
+    });
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::ShareCreate,
+        "app",
+        &result.as_ref().map(|share| format!("Share {} created", share.id)).map_err(|e| e.to_string()),
+        &details,
+    );
+
+    match result {
+        Ok(share) => {
+            let base = match &state.config.self_url {
+                Some(self_url) => self_url.clone(),
+                None => {
+                    let info = http_req.connection_info();
+                    format!("{}://{}", info.scheme(), info.host())
+                }
+            };
+            HttpResponse::Created().json(ShareResponse {
+                success: true,
+                share,
+                url: Some(format!("{}{}#{}", base, shares::PAGE_PATH, token)),
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+pub async fn list_shares(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    match state.db.list_shares() {
+        Ok(shares) => HttpResponse::Ok().json(ShareListResponse { success: true, shares }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// Revoke a share; its link stops working immediately.
+pub async fn delete_share(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    let result = match state.db.delete_share(id) {
+        Ok(true) => Ok(format!("Share {} revoked", id)),
+        Ok(false) => {
+            return HttpResponse::NotFound().json(ApiResponse::error(ErrorCode::ShareNotFound, format!("No share with id {}", id)));
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    state.audit_server(Some(user_id), AuditAction::ShareRevoke, "app", &result);
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Cached power state, health and draw of the servers a share link covers.
+/// Authenticated by the link alone; sessions and API tokens are ignored.
+pub async fn shared_status(http_req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    let viewer = match check_share(&http_req, &state) {
+        Ok(viewer) => viewer,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let servers = state.share_access.snapshots(viewer.servers()).await;
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(SharedStatusResponse {
+            success: true,
+            name: viewer.name().to_string(),
+            servers,
+        })
+}
+
+/// The page a share link opens; it reads the token from the URL fragment.
+pub async fn share_page() -> HttpResponse {
+    html_page(assets::SHARE_HTML)
+}
+
+/// Set or remove the hourly quota of any user's API token.
+pub async fn set_token_quota(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    req: web::Json<TokenQuotaRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+    if req.requests_per_hour == Some(0) {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            "requests_per_hour must be at least 1",
+        ));
+    }
+
+    let id = path.into_inner();
+    let result = match state.db.set_api_token_quota(id, req.requests_per_hour) {
+        Ok(true) => Ok(match req.requests_per_hour {
+            Some(limit) => format!("Token {} limited to {} requests per hour", id, limit),
+            None => format!("Quota of token {} removed", id),
+        }),
+        Ok(false) => {
+            return HttpResponse::NotFound().json(ApiResponse::error(ErrorCode::TokenNotFound, format!("No token with id {}", id)));
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::ApiTokenQuotaUpdate,
+        "app",
+        &result,
+        &serde_json::json!({ "token_id": id, "requests_per_hour": req.requests_per_hour }),
+    );
+
+    match result {
+        Ok(message) => {
+            state.usage.reset_window(id);
+            HttpResponse::Ok().json(ApiResponse::success(message))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Hourly request counts of one user, or of everyone with `user_id` `None`,
+/// over the last `hours` hours.
+fn usage_response(state: &AppState, user_id: Option<i64>, hours: Option<u32>) -> HttpResponse {
+    let hours = hours.unwrap_or(USAGE_DEFAULT_HOURS);
+    if hours == 0 || hours > USAGE_MAX_HOURS {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("hours must be between 1 and {}", USAGE_MAX_HOURS),
+        ));
+    }
+
+    // Include the current, partial hour.
+    let since = (chrono::Utc::now() - chrono::Duration::hours(hours as i64))
+        .format(SQLITE_TIMESTAMP_FORMAT)
+        .to_string();
+    match state.db.list_api_usage(user_id, &since) {
+        Ok(usage) => HttpResponse::Ok().json(UsageResponse {
+            success: true,
+            hours,
+            total_requests: usage.iter().map(|u| u.requests).sum(),
+            usage,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+pub async fn account_usage(
+    session: Session,
+    state: web::Data<AppState>,
+    query: web::Query<UsageQuery>,
+) -> HttpResponse {
+    match check_auth(session, &state).await {
+        Ok(user) => usage_response(&state, Some(user.id), query.hours),
+        Err(response) => response,
+    }
+}
+
+pub async fn admin_usage(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<UsageQuery>,
+) -> HttpResponse {
+    match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(_) => usage_response(&state, None, query.hours),
+        Err(response) => response,
+    }
+}
+
+/// Which port (dedicated or a shared LOM) carries iDRAC traffic.
+pub async fn get_nic_mode(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.idrac.get_nic_selection().await {
+        Ok(nic_selection) => HttpResponse::Ok().json(NicSelectionResponse {
+            success: true,
+            nic_selection,
+        }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Switch the iDRAC NIC mode. Takes effect immediately; the iDRAC is
+/// unreachable until the new port is up.
+pub async fn set_nic_mode(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<NicModeRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let result = state
+        .idrac
+        .set_nic_selection(req.mode)
+        .await
+        .map(|_| format!("iDRAC NIC mode set to {:?}", req.mode));
+    let details = serde_json::json!({ "mode": req.mode });
+    state.audit_with_details(Some(user_id), AuditAction::NicModeSet, state.idrac.base_url(), &result, &details);
+
+    match result {
+        Ok(msg) => HttpResponse::Ok().json(ApiResponse::success(msg)),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+#[derive(Serialize)]
+pub struct IdracUsersResponse {
+    pub success: bool,
+    pub users: Vec<IdracUser>,
+}
+
+#[derive(Serialize)]
+pub struct IdracUserResponse {
+    pub success: bool,
+    pub user: IdracUser,
+}
+
+/// Local accounts of a server's iDRAC with their privilege.
+pub async fn list_idrac_users(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return server_not_found(alias);
+    };
+
+    match server.client.get_idrac_users().await {
+        Ok(users) => HttpResponse::Ok().json(IdracUsersResponse { success: true, users }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+pub async fn create_idrac_user(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ServerQuery>,
+    req: web::Json<CreateIdracUserRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return server_not_found(alias);
+    };
+    let username = match validate_idrac_username(&req.username) {
+        Ok(username) => username,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    if req.password.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationMissingField, "password is required"));
+    }
+
+    let created = server.client.create_idrac_user(&username, &req.password, req.privilege).await;
+    let result = created
+        .as_ref()
+        .map(|user| format!("iDRAC account '{}' created in slot {}", user.username, user.id))
+        .map_err(Clone::clone);
+    let details = serde_json::json!({ "username": username, "privilege": req.privilege });
+    state.audit_with_details(Some(user_id), AuditAction::IdracUserCreate, server.client.base_url(), &result, &details);
+
+    match created {
+        Ok(user) => HttpResponse::Created().json(IdracUserResponse { success: true, user }),
+        Err(e) if e.ends_with("already exists") => {
+            HttpResponse::Conflict().json(ApiResponse::error(ErrorCode::UserDuplicate, e))
+        }
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Change an iDRAC account's privilege, password or enabled state.
+pub async fn update_idrac_user(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<ServerQuery>,
+    req: web::Json<IdracUserUpdate>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return server_not_found(alias);
+    };
+    let id = path.into_inner();
+    if !id.parse::<u8>().is_ok_and(|slot| (2..=16).contains(&slot)) {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "id",
+            message: "must be an account slot from 2 to 16".to_string(),
+        }));
+    }
+    if req.privilege.is_none() && req.password.is_none() && req.enabled.is_none() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationMissingField,
+            "Set at least one of privilege, password and enabled",
+        ));
+    }
+    if req.password.as_deref() == Some("") {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "password",
+            message: "must not be empty".to_string(),
+        }));
+    }
+
+    let result = server
+        .client
+        .update_idrac_user(&id, &req)
+        .await
+        .map(|_| format!("iDRAC account {} updated", id));
+    let details = serde_json::json!({
+        "account_id": id,
+        "privilege": req.privilege,
+        "password_changed": req.password.is_some(),
+        "enabled": req.enabled,
+    });
+    state.audit_with_details(Some(user_id), AuditAction::IdracUserUpdate, server.client.base_url(), &result, &details);
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Import an iDRAC license (e.g. Enterprise) from its license file.
+pub async fn activate_license(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<LicenseActivationRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    if req.key.trim().is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationMissingField, "key is required"));
+    }
+
+    let result = state
+        .idrac
+        .activate_license(&req.key)
+        .await
+        .map(|_| "License activated".to_string());
+    state.audit(Some(user_id), AuditAction::LicenseActivate, &result);
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) if e.starts_with("License key must be") => {
+            HttpResponse::BadRequest().json(ApiResponse::error(ErrorCode::ValidationInvalidValue, e))
+        }
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// POST a vendor action the API has no endpoint for to the iDRAC and pass
+/// its answer back unchanged. Only the path and status are audited; the
+/// payload may hold credentials.
+pub async fn oem_action(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<OemActionRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let path = match validate_oem_action_path(&req.url_suffix) {
+        Ok(path) => path,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let payload = if req.payload.is_null() { serde_json::json!({}) } else { req.payload.clone() };
+
+    let response = match state.idrac.post_raw(&path, &payload).await {
+        Ok(response) => response,
+        Err(e) => {
+            state.audit_with_details(
+                Some(user_id),
+                AuditAction::IdracOemAction,
+                state.idrac.base_url(),
+                &Err(e.clone()),
+                &serde_json::json!({ "url_suffix": path }),
+            );
+            return idrac_failure(e);
+        }
+    };
+    let success = (200..300).contains(&response.status);
+    let result = if success {
+        Ok(format!("{} returned HTTP {}", path, response.status))
+    } else {
+        Err(format!("{} returned HTTP {}", path, response.status))
+    };
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::IdracOemAction,
+        state.idrac.base_url(),
+        &result,
+        &serde_json::json!({ "url_suffix": path, "status": response.status }),
+    );
+
+    HttpResponse::Ok().json(OemActionResponse { success, response })
+}
+
+/// Reset the iDRAC to factory defaults. The intent is audited before the
+/// request is sent, so it is on record even if the iDRAC never answers.
+pub async fn factory_reset(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<FactoryResetRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    if req.confirm != FACTORY_RESET_CONFIRMATION {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationConfirmationRequired,
+            format!("Factory reset must be confirmed with {{\"confirm\": \"{}\"}}", FACTORY_RESET_CONFIRMATION),
+        ));
+    }
+    let reason = req.reason.trim();
+    if reason.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationMissingField,
+            "reason is required",
+        ));
+    }
+
+    let details = serde_json::json!({ "reason": reason });
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::IdracFactoryResetRequested,
+        state.idrac.base_url(),
+        &Ok(format!("Factory reset requested: {}", reason)),
+        &details,
+    );
+
+    let result = state.idrac.factory_reset_idrac().await;
+    state.audit_with_details(Some(user_id), AuditAction::IdracFactoryReset, state.idrac.base_url(), &result, &details);
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(FactoryResetResponse {
+            success: true,
+            message,
+            warning: "The iDRAC is reverting to its default credentials. IDRAC_USERNAME and IDRAC_PASSWORD \
+                      will stop working until the iDRAC is reconfigured or they are updated to match."
+                .to_string(),
+        }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+pub async fn get_boot_order(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.idrac.get_boot_order().await {
+        Ok(boot_order) => HttpResponse::Ok().json(BootOrderResponse {
+            success: true,
+            boot_order,
+        }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+/// Every id that keeps `submitted` from being a permutation of `current`.
+fn boot_order_errors(current: &[String], submitted: &[String]) -> Vec<BootOrderIdError> {
+    let mut errors = Vec::new();
+    let mut seen = Vec::new();
+
+    for id in submitted {
+        if !current.contains(id) {
+            errors.push(BootOrderIdError { id: id.clone(), error: "unknown" });
+        } else if seen.contains(&id) {
+            errors.push(BootOrderIdError { id: id.clone(), error: "duplicate" });
+        }
+        seen.push(id);
+    }
+    for id in current {
+        if !submitted.contains(id) {
+            errors.push(BootOrderIdError { id: id.clone(), error: "missing" });
+        }
+    }
+
+    errors
+}
+
+/// Permanently reorder the UEFI boot entries. The submitted list must
+/// contain each current entry exactly once.
+pub async fn set_boot_order(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<BootOrderRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let before: Vec<String> = match state.idrac.get_boot_order().await {
+        Ok(order) => order.into_iter().map(|o| o.id).collect(),
+        Err(e) => return idrac_failure(e),
+    };
+
+    let errors = boot_order_errors(&before, &req.order);
+    if !errors.is_empty() {
+        return HttpResponse::BadRequest().json(BootOrderInvalidResponse {
+            success: false,
+            message: "order must list every current boot entry exactly once".to_string(),
+            error_code: ErrorCode::ValidationInvalidValue,
+            errors,
+        });
+    }
+
+    let after = req.into_inner().order;
+    let result = state.idrac.set_boot_order(after.clone()).await;
+    let audit_result = result
+        .as_ref()
+        .map(|_| "Boot order updated".to_string())
+        .map_err(|e| e.clone());
+    let details = serde_json::json!({
+        "before": before,
+        "after": after,
+        "job_id": result.as_ref().ok().cloned().flatten(),
+    });
+    state.audit_with_details(Some(user_id), AuditAction::BootOrderSet, state.idrac.base_url(), &audit_result, &details);
+
+    match result {
+        Ok(job_id) => HttpResponse::Ok().json(BootOrderUpdatedResponse {
+            success: true,
+            message: match &job_id {
+                Some(job) => format!("Boot order staged as job {}; it applies on next reboot", job),
+                None => "Boot order updated".to_string(),
+            },
+            before,
+            after,
+            job_id,
+        }),
+        Err(e) => idrac_failure(e),
+    }
+}
+
+fn server_not_found(alias: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::error(
+        ErrorCode::ServerNotFound,
+        format!("No server with alias '{}'", alias),
+    ))
+}
+
+pub async fn list_power_cap_schedules(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let alias = path.into_inner();
+    if state.servers.get(&alias).is_none() {
+        return server_not_found(&alias);
+    }
+
+    match state.db.list_power_cap_schedules(Some(&alias)) {
+        Ok(schedules) => HttpResponse::Ok().json(PowerCapSchedulesResponse {
+            success: true,
+            schedules,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to load schedules: {}", e),
+        )),
+    }
+}
+
+/// Cap a server at `watts` for `duration_minutes` each time `cron_expr` fires.
+pub async fn create_power_cap_schedule(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<PowerCapScheduleRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = path.into_inner();
+    let server = match state.servers.get(&alias) {
+        Some(server) => server,
+        None => return server_not_found(&alias),
+    };
+
+    if let Err(message) = parse_cron(&req.cron_expr) {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "cron_expr",
+            message,
+        }));
+    }
+    if req.watts == 0 {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "watts",
+            message: "must be greater than zero".to_string(),
+        }));
+    }
+    if req.duration_minutes == 0 {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "duration_minutes",
+            message: "must be greater than zero".to_string(),
+        }));
+    }
+
+    let created = state.db.create_power_cap_schedule(
+        &alias,
+        req.cron_expr.trim(),
+        req.watts,
+        req.duration_minutes,
+        req.enabled.unwrap_or(true),
+    );
+    match created {
+        Ok(schedule) => {
+            let result = Ok(format!("Schedule {}: {} W at '{}'", schedule.id, schedule.watts, schedule.cron_expr));
+            state.audit_server(Some(user_id), AuditAction::PowerCapScheduleCreate, server.client.base_url(), &result);
+            HttpResponse::Created().json(PowerCapScheduleResponse {
+                success: true,
+                schedule,
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to save schedule: {}", e),
+        )),
+    }
+}
+
+pub async fn delete_power_cap_schedule(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(String, i64)>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let (alias, id) = path.into_inner();
+    let server = match state.servers.get(&alias) {
+        Some(server) => server,
+        None => return server_not_found(&alias),
+    };
+
+    match state.db.delete_power_cap_schedule(&alias, id) {
+        Ok(true) => {
+            let result = Ok(format!("Schedule {} deleted", id));
+            state.audit_server(Some(user_id), AuditAction::PowerCapScheduleDelete, server.client.base_url(), &result);
+            HttpResponse::Ok().json(ApiResponse::success(format!("Schedule {} deleted", id)))
+        }
+        Ok(false) => HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::ScheduleNotFound,
+            format!("No schedule {} for server '{}'", id, alias),
+        )),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to delete schedule: {}", e),
+        )),
+    }
+}
+
+/// Time from power-on to BMC `On`, POST complete and OS reachable for the
+/// latest tracked boots, with the SEL entries logged during each.
+pub async fn boot_report(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<BootReportQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let alias = path.into_inner();
+    let server = match state.servers.get(&alias) {
+        Some(server) => server,
+        None => return server_not_found(&alias),
+    };
+    let limit = query.limit.unwrap_or(BOOT_REPORT_DEFAULT_LIMIT);
+    if limit == 0 || limit > BOOT_REPORT_MAX_LIMIT {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("limit must be between 1 and {}", BOOT_REPORT_MAX_LIMIT),
+        ));
+    }
+
+    let boots = match state.db.list_server_operations(&alias, boot::OPERATION_KIND, limit) {
+        Ok(boots) => boots,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to load boots: {}", e),
+            ))
+        }
+    };
+    let sel = if boots.is_empty() {
+        Ok(Vec::new())
+    } else {
+        server.client.get_sel_entries().await
+    };
+
+    HttpResponse::Ok().json(BootReportResponse {
+        success: true,
+        report: boot::build_report(&alias, boots, sel),
+    })
+}
+
+/// SEL entries stored by the nightly sweep, newest first. Never contacts
+/// the iDRAC.
+pub async fn list_sel_entries(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<PageQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let alias = path.into_inner();
+    if state.servers.get(&alias).is_none() {
+        return server_not_found(&alias);
+    }
+    let (keyset, limit) =
+        match pagination::request(query.limit, query.cursor.as_deref(), SEL_DEFAULT_LIMIT, SEL_MAX_LIMIT) {
+            Ok(request) => request,
+            Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+        };
+
+    match state.db.list_sel_entries(&alias, &keyset, limit + 1) {
+        Ok(entries) => {
+            let total_estimate = state.db.count_sel_entries(&alias).ok();
+            HttpResponse::Ok().json(pagination::page(entries, &keyset, limit, total_estimate, |entry| {
+                (entry.created.clone(), entry.entry_id.clone())
+            }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to load SEL entries: {}", e),
+        )),
+    }
+}
+
+/// Latency and packet loss of a server's ping rounds, newest first.
+pub async fn ping_history(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<PingHistoryQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let alias = path.into_inner();
+    if state.servers.get(&alias).is_none() {
+        return server_not_found(&alias);
+    }
+    let since = match query.since.as_deref().map(|v| parse_timestamp("since", v)).transpose() {
+        Ok(since) => since,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let limit = query.limit.unwrap_or(POWER_HISTORY_DEFAULT_LIMIT);
+    if limit == 0 || limit > POWER_HISTORY_MAX_LIMIT {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "limit",
+            message: format!("must be between 1 and {}", POWER_HISTORY_MAX_LIMIT),
+        }));
+    }
+
+    match state.db.list_ping_history(&alias, since.as_deref(), limit) {
+        Ok(samples) => HttpResponse::Ok().json(PingHistoryResponse {
+            success: true,
+            server: alias,
+            samples,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+fn group_not_found(id: i64) -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::error(ErrorCode::GroupNotFound, format!("No group {}", id)))
+}
+
+/// Members and budget of a group request, or the response rejecting them.
+fn validate_group_request(state: &AppState, req: &GroupRequest) -> Result<Vec<String>, FieldError> {
+    if req.power_budget_watts == Some(0) {
+        return Err(FieldError {
+            field: "power_budget_watts",
+            message: "must be greater than zero".to_string(),
+        });
+    }
+    normalize_group_members(&req.members, |alias| state.servers.get(alias).is_some())
+}
+
+pub async fn list_groups(session: Session, http_req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.db.list_server_groups() {
+        Ok(groups) => HttpResponse::Ok().json(GroupsResponse { success: true, groups }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to list groups: {}", e),
+        )),
+    }
+}
+
+pub async fn create_group(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<GroupRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let name = match normalize_group_name(&req.name) {
+        Ok(name) => name,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let members = match validate_group_request(&state, &req) {
+        Ok(members) => members,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    match state.db.create_server_group(&name, &members, req.power_budget_watts) {
+        Ok(group) => {
+            let details = serde_json::to_value(&group).unwrap_or_default();
+            state.audit_with_details(Some(user_id), AuditAction::GroupCreate, "app", &Ok(group.name.clone()), &details);
+            HttpResponse::Created().json(GroupResponse { success: true, group })
+        }
+        Err(e) if e.is_unique_violation() => HttpResponse::Conflict().json(ApiResponse::error(
+            ErrorCode::GroupDuplicate,
+            format!("A group named '{}' already exists", name),
+        )),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to create group: {}", e),
+        )),
+    }
+}
+
+pub async fn update_group(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    req: web::Json<GroupRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    let members = match validate_group_request(&state, &req) {
+        Ok(members) => members,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    match state.db.update_server_group(id, &members, req.power_budget_watts) {
+        Ok(Some(group)) => {
+            let details = serde_json::to_value(&group).unwrap_or_default();
+            state.audit_with_details(Some(user_id), AuditAction::GroupUpdate, "app", &Ok(group.name.clone()), &details);
+            HttpResponse::Ok().json(GroupResponse { success: true, group })
+        }
+        Ok(None) => group_not_found(id),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to update group: {}", e),
+        )),
+    }
+}
+
+pub async fn delete_group(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    match state.db.delete_server_group(id) {
+        Ok(true) => {
+            state.power_budget_exceeded.write().unwrap().remove(&id);
+            state.audit_server(Some(user_id), AuditAction::GroupDelete, "app", &Ok(format!("Group {} deleted", id)));
+            HttpResponse::Ok().json(ApiResponse::success(format!("Group {} deleted", id)))
+        }
+        Ok(false) => group_not_found(id),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to delete group: {}", e),
+        )),
+    }
+}
+
+/// Apply BIOS attributes, iDRAC attributes and a power cap to every member
+/// of a group, one server after another, as a job to poll.
+pub async fn group_apply_config(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<GroupConfig>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+    let config = body.into_inner();
+    if config.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            "Set at least one of bios_attributes, idrac_attributes and power_cap_watts",
+        ));
+    }
+    if config.power_cap_watts == Some(0) {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "power_cap_watts",
+            message: "must be greater than 0".to_string(),
+        }));
+    }
+
+    let id = path.into_inner();
+    let group = match state.db.get_server_group(id) {
+        Ok(Some(group)) => group,
+        Ok(None) => return group_not_found(id),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to load group: {}", e),
+            ))
+        }
+    };
+    if group.members.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("Group '{}' has no members", group.name),
+        ));
+    }
+
+    match group_apply::start(&state, &group, config, user_id) {
+        Ok(job_id) => HttpResponse::Accepted().json(OperationStartedResponse {
+            success: true,
+            message: format!("Applying configuration to {} server(s) of group '{}'", group.members.len(), group.name),
+            operation_id: job_id,
+            warning: None,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+pub async fn get_group_apply_job(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    match state.db.get_group_apply_job(&path.into_inner()) {
+        Ok(Some(job)) => HttpResponse::Ok().json(GroupApplyJobResponse { success: true, job }),
+        Ok(None) => HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::OperationNotFound,
+            "Group apply job not found",
+        )),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Database error: {}", e),
+        )),
+    }
+}
+
+/// Power a group's members on in member order as a tracked operation. With
+/// `ac_recovery`, members whose BMC does not answer yet are waited for.
+pub async fn group_power_on(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: Option<web::Json<GroupPowerOnRequest>>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::PowerWrite).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+    let req = body.map(|body| body.into_inner()).unwrap_or_default();
+
+    let window = req.recovery_window_minutes.unwrap_or(group_power_on::DEFAULT_RECOVERY_WINDOW_MINUTES);
+    if window == 0 || window > group_power_on::MAX_RECOVERY_WINDOW_MINUTES {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "recovery_window_minutes",
+            message: format!("must be between 1 and {}", group_power_on::MAX_RECOVERY_WINDOW_MINUTES),
+        }));
+    }
+    if req.stagger_secs > group_power_on::MAX_STAGGER_SECS {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "stagger_secs",
+            message: format!("must be at most {}", group_power_on::MAX_STAGGER_SECS),
+        }));
+    }
+
+    let id = path.into_inner();
+    let group = match state.db.get_server_group(id) {
+        Ok(Some(group)) => group,
+        Ok(None) => return group_not_found(id),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to load group: {}", e),
+            ))
+        }
+    };
+    if group.members.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("Group '{}' has no members", group.name),
+        ));
+    }
+
+    let plan = PowerOnPlan::new(&group, req.ac_recovery, window, req.stagger_secs, user_id);
+    let details = serde_json::json!({
+        "members": plan.members,
+        "ac_recovery": plan.ac_recovery,
+        "recovery_window_minutes": window,
+        "stagger_secs": plan.stagger_secs,
+    });
+    let result = group_power_on::start(&state, plan)
+        .map(|operation_id| (format!("Powering on {} server(s) of group '{}'", group.members.len(), group.name), operation_id));
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::GroupPowerOn,
+        &group.name,
+        &result.as_ref().map(|(message, _)| message.clone()).map_err(|e| e.clone()),
+        &details,
+    );
+
+    match result {
+        Ok((message, operation_id)) => HttpResponse::Accepted().json(OperationStartedResponse {
+            success: true,
+            message,
+            operation_id,
+            warning: None,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Per-server timelines of a group power-on.
+pub async fn group_power_on_report(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(i64, String)>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    let (group_id, operation_id) = path.into_inner();
+    let operation = match state.db.get_operation(&operation_id) {
+        Ok(operation) => operation.filter(|operation| operation.kind == group_power_on::OPERATION_KIND),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Database error: {}", e),
+            ))
+        }
+    };
+    let found = operation.and_then(|operation| {
+        PowerOnPlan::of(&operation)
+            .filter(|plan| plan.group_id == group_id)
+            .map(|plan| group_power_on::build_report(&operation, &plan))
+    });
+
+    match found {
+        Some(report) => HttpResponse::Ok().json(GroupPowerOnReportResponse { success: true, report }),
+        None => HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::OperationNotFound,
+            format!("No power-on {} for group {}", operation_id, group_id),
+        )),
+    }
+}
+
+/// The `image_uri` a firmware rollout request names, directly or as an
+/// uploaded `component`.
+fn rollout_image_uri(req: &GroupFirmwareUpdateRequest) -> Result<String, FieldError> {
+    match (req.image_uri.as_deref().map(str::trim), req.component.as_deref().map(str::trim)) {
+        (Some(image_uri), None) => {
+            let scheme_ok = reqwest::Url::parse(image_uri)
+                .is_ok_and(|url| matches!(url.scheme(), "http" | "https" | "ftp" | "nfs" | "cifs") && url.host_str().is_some());
+            if !scheme_ok {
+                return Err(FieldError {
+                    field: "image_uri",
+                    message: "must be an http, https, ftp, nfs or cifs URL".to_string(),
+                });
+            }
+            Ok(image_uri.to_string())
+        }
+        (None, Some(component)) => {
+            let valid = (1..=128).contains(&component.len())
+                && !component.starts_with('.')
+                && component.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+            if !valid {
+                return Err(FieldError {
+                    field: "component",
+                    message: "must be 1 to 128 letters, digits, '.', '_' or '-'".to_string(),
+                });
+            }
+            Ok(format!("/redfish/v1/UpdateService/FirmwareInventory/{}", component))
+        }
+        _ => Err(FieldError {
+            field: "image_uri",
+            message: "exactly one of image_uri and component is required".to_string(),
+        }),
+    }
+}
+
+/// Update a group's firmware a batch at a time as a tracked operation.
+/// Each member must pass a health gate after its update; a failure pauses
+/// the rollout once its batch is done.
+pub async fn group_firmware_update(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    req: web::Json<GroupFirmwareUpdateRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+    let req = req.into_inner();
+
+    let image_uri = match rollout_image_uri(&req) {
+        Ok(image_uri) => image_uri,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    let limits = [
+        ("batch_size", req.batch_size, firmware_rollout::DEFAULT_BATCH_SIZE, firmware_rollout::MAX_BATCH_SIZE),
+        (
+            "job_timeout_minutes",
+            req.job_timeout_minutes,
+            firmware_rollout::DEFAULT_JOB_TIMEOUT_MINUTES,
+            firmware_rollout::MAX_JOB_TIMEOUT_MINUTES,
+        ),
+        (
+            "gate_timeout_minutes",
+            req.gate_timeout_minutes,
+            firmware_rollout::DEFAULT_GATE_TIMEOUT_MINUTES,
+            firmware_rollout::MAX_GATE_TIMEOUT_MINUTES,
+        ),
+    ];
+    let mut values = [0; 3];
+    for (value, (field, requested, default, max)) in values.iter_mut().zip(limits) {
+        *value = requested.unwrap_or(default);
+        if *value == 0 || *value > max {
+            return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+                field,
+                message: format!("must be between 1 and {}", max),
+            }));
+        }
+    }
+    let [batch_size, job_timeout, gate_timeout] = values;
+
+    let id = path.into_inner();
+    let group = match state.db.get_server_group(id) {
+        Ok(Some(group)) => group,
+        Ok(None) => return group_not_found(id),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to load group: {}", e),
+            ))
+        }
+    };
+    if group.members.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("Group '{}' has no members", group.name),
+        ));
+    }
+
+    let plan = RolloutPlan::new(&group, image_uri, batch_size, job_timeout, gate_timeout, user_id);
+    let details = serde_json::json!({
+        "members": plan.members,
+        "image_uri": plan.image_uri,
+        "batch_size": batch_size,
+        "job_timeout_minutes": job_timeout,
+        "gate_timeout_minutes": gate_timeout,
+    });
+    let result = firmware_rollout::start(&state, plan).map(|operation_id| {
+        (
+            format!("Updating firmware on {} server(s) of group '{}'", group.members.len(), group.name),
+            operation_id,
+        )
+    });
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::FirmwareRollout,
+        &group.name,
+        &result.as_ref().map(|(message, _)| message.clone()).map_err(|e| e.clone()),
+        &details,
+    );
+
+    match result {
+        Ok((message, operation_id)) => HttpResponse::Accepted().json(OperationStartedResponse {
+            success: true,
+            message,
+            operation_id,
+            warning: None,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// The rollout `operation_id` of group `group_id`, or the response
+/// saying there is none.
+fn find_rollout(state: &AppState, group_id: i64, operation_id: &str) -> Result<(Operation, RolloutPlan), Box<HttpResponse>> {
+    let operation = match state.db.get_operation(operation_id) {
+        Ok(operation) => operation.filter(|operation| operation.kind == firmware_rollout::OPERATION_KIND),
+        Err(e) => {
+            return Err(Box::new(HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Database error: {}", e),
+            ))))
+        }
+    };
+    operation
+        .and_then(|operation| {
+            RolloutPlan::of(&operation)
+                .filter(|plan| plan.group_id == group_id)
+                .map(|plan| (operation, plan))
+        })
+        .ok_or_else(|| {
+            Box::new(HttpResponse::NotFound().json(ApiResponse::error(
+                ErrorCode::OperationNotFound,
+                format!("No firmware rollout {} for group {}", operation_id, group_id),
+            )))
+        })
+}
+
+/// Per-server timelines of a firmware rollout.
+pub async fn group_firmware_update_report(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(i64, String)>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
+    }
+
+    let (group_id, operation_id) = path.into_inner();
+    match find_rollout(&state, group_id, &operation_id) {
+        Ok((operation, plan)) => HttpResponse::Ok().json(FirmwareRolloutReportResponse {
+            success: true,
+            report: firmware_rollout::build_report(&operation, &plan),
+        }),
+        Err(response) => *response,
+    }
+}
+
+/// Continue a paused firmware rollout with the members not yet done.
+pub async fn resume_group_firmware_update(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(i64, String)>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let (group_id, operation_id) = path.into_inner();
+    let (operation, plan) = match find_rollout(&state, group_id, &operation_id) {
+        Ok(found) => found,
+        Err(response) => return *response,
+    };
+    let result = firmware_rollout::resume(&state, &operation, user_id);
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::FirmwareRolloutResume,
+        &plan.group,
+        &result,
+        &serde_json::json!({ "operation_id": operation_id }),
+    );
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) => HttpResponse::Conflict().json(ApiResponse::error(ErrorCode::OperationInvalidState, e)),
+    }
+}
+
+/// Cancel a firmware rollout: a paused one at once, a running one before
+/// its next batch.
+pub async fn cancel_group_firmware_update(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(i64, String)>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let (group_id, operation_id) = path.into_inner();
+    let (operation, plan) = match find_rollout(&state, group_id, &operation_id) {
+        Ok(found) => found,
+        Err(response) => return *response,
+    };
+    let result = firmware_rollout::cancel(&state, &operation, user_id);
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::FirmwareRolloutCancel,
+        &plan.group,
+        &result,
+        &serde_json::json!({ "operation_id": operation_id }),
+    );
+
+    match result {
+        Ok(message) => HttpResponse::Ok().json(ApiResponse::success(message)),
+        Err(e) => HttpResponse::Conflict().json(ApiResponse::error(ErrorCode::OperationInvalidState, e)),
+    }
+}
+
+/// Current and peak power draw of a group against its budget, for
+/// capacity planning.
+pub async fn group_power_summary(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    query: web::Query<GroupPowerQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let window_hours = query.window_hours.unwrap_or(GROUP_POWER_DEFAULT_WINDOW_HOURS);
+    if window_hours == 0 || window_hours > GROUP_POWER_MAX_WINDOW_HOURS {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationInvalidValue,
+            format!("window_hours must be between 1 and {}", GROUP_POWER_MAX_WINDOW_HOURS),
+        ));
+    }
+
+    let id = path.into_inner();
+    let group = match state.db.get_server_group(id) {
+        Ok(Some(group)) => group,
+        Ok(None) => return group_not_found(id),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to load group: {}", e),
+            ))
+        }
+    };
+
+    match group_power::summarize(&state, &group, window_hours) {
+        Ok(summary) => HttpResponse::Ok().json(GroupPowerResponse { success: true, summary }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+pub async fn list_one_shot_schedules(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    match state.db.list_one_shot_schedules() {
+        Ok(schedules) => HttpResponse::Ok().json(OneShotSchedulesResponse {
+            success: true,
+            schedules,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to load schedules: {}", e),
+        )),
+    }
+}
+
+/// Run a power action once at `execute_at`, e.g. for a maintenance window.
+pub async fn create_one_shot_schedule(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<OneShotScheduleRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::PowerWrite).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = req.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let server = match state.servers.get(alias) {
+        Some(server) => server,
+        None => return server_not_found(alias),
+    };
+
+    let action = req.action.trim().to_ascii_lowercase();
+    if one_shot_audit_name(&action).is_none() {
+        let names: Vec<&str> = ONE_SHOT_ACTIONS.iter().map(|(name, _)| *name).collect();
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "action",
+            message: format!("must be one of: {}", names.join(", ")),
+        }));
+    }
+
+    let execute_at = match parse_timestamp("execute_at", &req.execute_at) {
+        Ok(execute_at) => execute_at,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    if execute_at <= chrono::Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string() {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "execute_at",
+            message: "must be in the future".to_string(),
+        }));
+    }
+
+    // Powering on is harmless; anything else would take this application down.
+    let warning = if action == "on" {
+        None
+    } else {
+        match hosts_this_app_guard(&server, req.i_understand_this_hosts_the_controller) {
+            Ok(warning) => warning,
+            Err(unacknowledged) => return unacknowledged.into_response(),
+        }
+    };
+
+    match state.db.create_one_shot_schedule(&server.alias, &action, &execute_at, Some(user_id)) {
+        Ok(schedule) => {
+            let result = Ok(format!("Schedule {}: {} at {}", schedule.id, schedule.action, schedule.execute_at));
+            state.audit_server(Some(user_id), AuditAction::OneShotScheduleCreate, server.client.base_url(), &result);
+            if let Some(warning) = &warning {
+                audit_hosts_this_app(&state, user_id, &server, "OneShotScheduleCreate", warning);
+            }
+            HttpResponse::Created().json(OneShotScheduleResponse {
+                success: true,
+                schedule,
+                warning,
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to save schedule: {}", e),
+        )),
+    }
+}
+
+/// Reboot a server at `reboot_at` to apply a firmware update staged on its
+/// iDRAC, then follow the job to its end.
+pub async fn schedule_firmware_update(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<FirmwareScheduleRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = req.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let server = match state.servers.get(alias) {
+        Some(server) => server,
+        None => return server_not_found(alias),
+    };
+
+    let job_id = req.job_id.trim();
+    if job_id.is_empty() {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "job_id",
+            message: "is required".to_string(),
+        }));
+    }
+    let reboot_at = match parse_timestamp("reboot_at", &req.reboot_at) {
+        Ok(reboot_at) => reboot_at,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+    if reboot_at <= chrono::Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string() {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "reboot_at",
+            message: "must be in the future".to_string(),
+        }));
+    }
+
+    let job = match server.client.get_job(job_id).await {
+        Ok(job) => job,
+        Err(e) => return idrac_failure(e),
+    };
+    if !firmware_schedule::is_staged(&job) {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "job_id",
+            message: format!("job {} is not a staged firmware update (state {})", job.id, job.state),
+        }));
+    }
+
+    let schedules = match state.db.list_firmware_update_schedules() {
+        Ok(schedules) => schedules,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to load schedules: {}", e),
+            ));
+        }
+    };
+    if let Some(existing) = schedules
+        .iter()
+        .find(|s| s.status == "pending" && s.server_alias == server.alias && s.job_id == job.id)
+    {
+        return HttpResponse::Conflict().json(ApiResponse::error(
+            ErrorCode::ScheduleOverlap,
+            format!("Job {} is already scheduled to apply at {} (schedule {})", job.id, existing.reboot_at, existing.id),
+        ));
+    }
+
+    let warning = match hosts_this_app_guard(&server, req.i_understand_this_hosts_the_controller) {
+        Ok(warning) => warning,
+        Err(unacknowledged) => return unacknowledged.into_response(),
+    };
+
+    let result = state
+        .db
+        .create_firmware_update_schedule(&server.alias, &job.id, &reboot_at, Some(user_id))
+        .map_err(|e| format!("Failed to save schedule: {}", e));
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::FirmwareUpdateSchedule,
+        server.client.base_url(),
+        &result
+            .as_ref()
+            .map(|schedule| format!("Schedule {}: apply job {} at {}", schedule.id, schedule.job_id, schedule.reboot_at))
+            .map_err(String::clone),
+        &serde_json::json!({ "job_id": job.id, "reboot_at": reboot_at }),
+    );
+
+    match result {
+        Ok(schedule) => {
+            if let Some(warning) = &warning {
+                audit_hosts_this_app(&state, user_id, &server, "FirmwareUpdateSchedule", warning);
+            }
+            HttpResponse::Created().json(FirmwareScheduleResponse {
+                success: true,
+                schedule,
+                warning,
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Firmware updates staged on the iDRACs and not applied yet, each with
+/// the reboot scheduled to apply it, if any.
+pub async fn pending_firmware_updates(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<PendingUpdatesQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let servers = match query.server.as_deref().map(str::trim) {
+        Some(alias) => match state.servers.get(alias) {
+            Some(server) => vec![server],
+            None => return server_not_found(alias),
+        },
+        None => state.servers.all(),
+    };
+    let schedules = match state.db.list_firmware_update_schedules() {
+        Ok(schedules) => schedules,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to load schedules: {}", e),
+            ));
+        }
+    };
+
+    HttpResponse::Ok().json(PendingUpdatesResponse {
+        success: true,
+        servers: firmware_schedule::pending_updates(servers, schedules).await,
+    })
+}
+
+/// Cancel a one-shot schedule that has not run yet.
+pub async fn delete_one_shot_schedule(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::PowerWrite).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    match state.db.delete_one_shot_schedule(id) {
+        Ok(Some(schedule)) => {
+            let server_name = state
+                .servers
+                .get(&schedule.server_alias)
+                .map(|server| server.client.base_url().to_string())
+                .unwrap_or(schedule.server_alias);
+            let result = Ok(format!("Schedule {} deleted", id));
+            state.audit_server(Some(user_id), AuditAction::OneShotScheduleDelete, &server_name, &result);
+            HttpResponse::Ok().json(ApiResponse::success(format!("Schedule {} deleted", id)))
+        }
+        Ok(None) => HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::ScheduleNotFound,
+            format!("No pending one-shot schedule {}", id),
+        )),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to delete schedule: {}", e),
+        )),
+    }
+}
+
+pub async fn list_tariff_windows(session: Session, http_req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
+    }
+
+    match state.db.list_tariff_windows() {
+        Ok(windows) => HttpResponse::Ok().json(TariffWindowsResponse { success: true, windows }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to load tariff windows: {}", e),
+        )),
+    }
+}
+
+/// Cap a server, or every member of a group, during a tariff window. A
+/// window may not share a minute with another window capping any of the
+/// same servers.
+pub async fn create_tariff_window(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<TariffWindowRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let invalid = |field: &'static str, message: String| HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError { field, message }));
+    let server = req.server.as_deref().map(str::trim);
+    match (server, req.group_id) {
+        (Some(alias), None) => {
+            if state.servers.get(alias).is_none() {
+                return server_not_found(alias);
             }
+        }
+        (None, Some(id)) => match state.db.get_server_group(id) {
+            Ok(Some(_)) => {}
+            Ok(None) => return group_not_found(id),
             Err(e) => {
-                HttpResponse::InternalServerError()
-                    .body(format!("Database error: {}", e))
+                return HttpResponse::InternalServerError().json(ApiResponse::error(
+                    ErrorCode::InternalDatabase,
+                    format!("Failed to load group: {}", e),
+                ))
             }
+        },
+        _ => return invalid("server", "set exactly one of server and group_id".to_string()),
+    }
+    let days = match tariff::normalize_days(&req.days) {
+        Ok(days) => days,
+        Err(message) => return invalid("days", message),
+    };
+    let (start, end) = match (tariff::parse_clock(&req.start), tariff::parse_clock(&req.end)) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(message), _) => return invalid("start", message),
+        (_, Err(message)) => return invalid("end", message),
+    };
+    if req.watts == 0 {
+        return invalid("watts", "must be greater than zero".to_string());
+    }
+
+    let (windows, groups) = match (state.db.list_tariff_windows(), state.db.list_server_groups()) {
+        (Ok(windows), Ok(groups)) => (windows, groups),
+        (Err(e), _) | (_, Err(e)) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to load tariff windows: {}", e),
+            ))
+        }
+    };
+    let candidate = TariffWindow {
+        id: 0,
+        server_alias: server.map(str::to_string),
+        group_id: req.group_id,
+        days,
+        start: format!("{:02}:{:02}", start / 60, start % 60),
+        end: format!("{:02}:{:02}", end / 60, end % 60),
+        watts: req.watts,
+        created_at: String::new(),
+    };
+    let targets = tariff::targets(&candidate, &groups);
+    for window in windows.iter().filter(|window| tariff::overlaps(&candidate, window)) {
+        let existing = tariff::targets(window, &groups);
+        if let Some(alias) = targets.iter().find(|alias| existing.contains(alias)) {
+            return HttpResponse::Conflict().json(ApiResponse::error(
+                ErrorCode::ScheduleOverlap,
+                format!("Overlaps tariff window {}, which also caps '{}'", window.id, alias),
+            ));
+        }
+    }
+
+    let created = state.db.create_tariff_window(
+        candidate.server_alias.as_deref(),
+        candidate.group_id,
+        &candidate.days,
+        &candidate.start,
+        &candidate.end,
+        candidate.watts,
+    );
+    match created {
+        Ok(window) => {
+            let details = serde_json::json!({
+                "window_id": window.id,
+                "server": window.server_alias,
+                "group_id": window.group_id,
+                "days": window.days,
+                "start": window.start,
+                "end": window.end,
+                "watts": window.watts,
+            });
+            let result = Ok(format!(
+                "Tariff window {}: {} W {}-{} on {}",
+                window.id,
+                window.watts,
+                window.start,
+                window.end,
+                window.days.join(",")
+            ));
+            state.audit_with_details(Some(user_id), AuditAction::TariffWindowCreate, "app", &result, &details);
+            HttpResponse::Created().json(TariffWindowResponse { success: true, window })
         }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to save tariff window: {}", e),
+        )),
     }
 }
 
-pub async fn register(
-    form: web::Json<RegisterRequest>,
-    db: web::Data<Arc<Database>>,
+/// Remove a tariff window. A cap it applied is restored at the next round
+/// of the tariff scheduler.
+pub async fn delete_tariff_window(
     session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
 ) -> HttpResponse {
-    // Check if users already exist
-    match db.has_users() {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    match state.db.delete_tariff_window(id) {
         Ok(true) => {
-            return HttpResponse::Forbidden().json(ApiResponse {
-                success: false,
-                message: "Registration is closed. An account already exists.".to_string(),
-            });
-        }
-        Ok(false) => {}
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(ApiResponse {
-                success: false,
-                message: format!("Database error: {}", e),
-            });
+            let result = Ok(format!("Tariff window {} deleted", id));
+            state.audit_with_details(Some(user_id), AuditAction::TariffWindowDelete, "app", &result, &serde_json::json!({ "window_id": id }));
+            HttpResponse::Ok().json(ApiResponse::success(format!("Tariff window {} deleted", id)))
         }
+        Ok(false) => HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::ScheduleNotFound,
+            format!("No tariff window {}", id),
+        )),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to delete tariff window: {}", e),
+        )),
     }
+}
 
-    if form.username.trim().is_empty() || form.password.is_empty() {
-        return HttpResponse::BadRequest().json(ApiResponse {
-            success: false,
-            message: "Username and password are required".to_string(),
-        });
+/// The tariff window covering each server now and the cap it applied.
+pub async fn tariff_status(session: Session, http_req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::PowerRead).await {
+        return response;
     }
 
-    if form.password != form.confirm_password {
-        return HttpResponse::BadRequest().json(ApiResponse {
-            success: false,
-            message: "Passwords do not match".to_string(),
-        });
+    match tariff::status(&state) {
+        Ok(servers) => HttpResponse::Ok().json(TariffStatusResponse { success: true, servers }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to load tariff status: {}", e),
+        )),
     }
+}
 
-    if form.password.len() < 8 {
-        return HttpResponse::BadRequest().json(ApiResponse {
-            success: false,
-            message: "Password must be at least 8 characters".to_string(),
-        });
+/// Map a Redfish health value onto the fleet board's vocabulary.
+fn fleet_health_label(health: &str) -> &'static str {
+    match health {
+        "OK" => "OK",
+        "Warning" => "Warning",
+        "Critical" => "Critical",
+        _ => "Unknown",
     }
+}
 
-    match db.create_user(&form.username, &form.password) {
-        Ok(user_id) => {
-            // Auto-login after registration
-            let _ = session.insert("user_id", user_id);
-            info!("New user registered and logged in: {}", form.username);
-            
-            HttpResponse::Ok().json(ApiResponse {
+/// Query every registered server concurrently, each bounded by
+/// `FLEET_HEALTH_TIMEOUT_MS`.
+pub async fn fleet_health(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let timeout = Duration::from_millis(state.config.fleet_health_timeout_ms);
+    let state = &state;
+    let checks = state.servers.all().into_iter().map(|server| async move {
+        let os_health = state.os_health.read().unwrap().get(&server.alias).cloned();
+        let clock = state.clocks.read().unwrap().get(&server.alias).cloned();
+        let (result, hostname) = tokio::join!(
+            tokio::time::timeout(timeout, server.client.get_health()),
+            server.client.hostname()
+        );
+        match result {
+            Ok(Ok(status)) => ServerHealth {
+                alias: server.alias.clone(),
+                hostname: hostname.clone(),
+                hosts_this_app: server.hosts_this_app,
+                status: "ok",
+                health: fleet_health_label(&status.health),
+                degraded_components: status
+                    .component_health
+                    .into_iter()
+                    .filter(|c| c.health != "OK")
+                    .collect(),
+                error: None,
+                os_health,
+                clock,
+            },
+            Ok(Err(e)) => ServerHealth {
+                alias: server.alias.clone(),
+                hostname: hostname.clone(),
+                hosts_this_app: server.hosts_this_app,
+                status: match ErrorCode::from_idrac_error(&e) {
+                    ErrorCode::IdracAuthFailed => "credentials_invalid",
+                    ErrorCode::IdracUnreachable => "unreachable",
+                    _ => "error",
+                },
+                health: "Unknown",
+                degraded_components: Vec::new(),
+                error: Some(e),
+                os_health,
+                clock,
+            },
+            Err(_) => ServerHealth {
+                alias: server.alias.clone(),
+                hostname: hostname.clone(),
+                hosts_this_app: server.hosts_this_app,
+                status: "unreachable",
+                health: "Unknown",
+                degraded_components: Vec::new(),
+                error: Some(format!("Timed out after {}ms", timeout.as_millis())),
+                os_health,
+                clock,
+            },
+        }
+    });
+
+    HttpResponse::Ok().json(FleetHealthResponse {
+        success: true,
+        servers: futures_util::future::join_all(checks).await,
+    })
+}
+
+/// Fleet-wide firmware versions from the cached inventory. Never contacts
+/// the iDRACs; use `refresh_firmware_inventory` to update the cache.
+pub async fn fleet_firmware(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let entries = state.db.list_firmware_inventory();
+    let baselines = state.db.list_firmware_baselines();
+    match (entries, baselines) {
+        (Ok(entries), Ok(baselines)) => {
+            let aliases: Vec<String> = state.servers.all().iter().map(|s| s.alias.clone()).collect();
+            HttpResponse::Ok().json(FirmwareReportResponse {
                 success: true,
-                message: "Account created successfully".to_string(),
+                report: firmware::build_report(entries, baselines, &aliases),
             })
         }
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse {
-            success: false,
-            message: format!("Failed to create user: {}", e),
+        (Err(e), _) | (_, Err(e)) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to load firmware inventory: {}", e),
+        )),
+    }
+}
+
+/// Collect firmware inventory from every server in the background. Poll
+/// `/api/operations/{id}` for per-server results.
+pub async fn refresh_firmware_inventory(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let operation_id = match state.db.create_operation("firmware_refresh", None) {
+        Ok(id) => id,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to track operation: {}", e),
+            ));
+        }
+    };
+    let message = format!("Refreshing firmware inventory of {} server(s)", state.servers.all().len());
+    state.audit_server(Some(user_id), AuditAction::FirmwareInventoryRefresh, "fleet", &Ok(message.clone()));
+
+    tokio::spawn(firmware::run_refresh_operation(state.get_ref().clone(), operation_id.clone()));
+
+    HttpResponse::Accepted().json(OperationStartedResponse {
+        success: true,
+        message,
+        operation_id,
+        warning: None,
+    })
+}
+
+/// Define a firmware compliance profile, replacing one of the same name.
+pub async fn create_compliance_profile(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<ComplianceProfileRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let validated = normalize_compliance_profile_name(&req.name)
+        .and_then(|name| normalize_compliance_requirements(&req.components).map(|components| (name, components)));
+    let (name, components) = match validated {
+        Ok(profile) => profile,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    let result = state
+        .db
+        .replace_compliance_profile(&name, &components)
+        .map(|_| format!("Compliance profile '{}' saved", name))
+        .map_err(|e| format!("Failed to save compliance profile: {}", e));
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::ComplianceProfileSet,
+        "app",
+        &result,
+        &serde_json::json!({ "name": name, "components": components }),
+    );
+
+    match result {
+        Ok(_) => HttpResponse::Created().json(ComplianceProfileResponse {
+            success: true,
+            name,
+            components,
         }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
     }
 }
 
-pub async fn login(
-    form: web::Json<LoginRequest>,
-    db: web::Data<Arc<Database>>,
+/// Compare a server's live firmware inventory with a compliance profile.
+pub async fn firmware_compliance(
     session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ComplianceQuery>,
 ) -> HttpResponse {
-    if form.username.trim().is_empty() || form.password.is_empty() {
-        return HttpResponse::BadRequest().json(ApiResponse {
-            success: false,
-            message: "Username and password are required".to_string(),
-        });
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
     }
 
-    match db.verify_user(&form.username, &form.password) {
-        Ok(Some(user)) => {
-            let _ = session.insert("user_id", user.id);
-            info!("User logged in: {}", user.username);
-            
-            HttpResponse::Ok().json(ApiResponse {
+    let profile = query.profile.trim();
+    let requirements = match state.db.get_compliance_profile(profile) {
+        Ok(requirements) if requirements.is_empty() => {
+            return HttpResponse::NotFound().json(ApiResponse::error(
+                ErrorCode::ComplianceProfileNotFound,
+                format!("No compliance profile '{}'", profile),
+            ));
+        }
+        Ok(requirements) => requirements,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to read compliance profile: {}", e),
+            ));
+        }
+    };
+
+    let alias = query.server.as_deref().map(str::trim).unwrap_or(DEFAULT_SERVER_ALIAS);
+    let Some(server) = state.servers.get(alias) else {
+        return HttpResponse::NotFound().json(ApiResponse::error(
+            ErrorCode::ServerNotFound,
+            format!("No server '{}'", alias),
+        ));
+    };
+
+    match server.client.get_firmware_inventory().await {
+        Ok(installed) => {
+            let components = firmware::check_compliance(&installed, requirements);
+            HttpResponse::Ok().json(ComplianceReportResponse {
                 success: true,
-                message: "Login successful".to_string(),
+                profile: profile.to_string(),
+                server: server.alias.clone(),
+                compliant: components.iter().all(|c| c.compliant),
+                components,
             })
         }
-        Ok(None) => HttpResponse::Unauthorized().json(ApiResponse {
-            success: false,
-            message: "Invalid username or password".to_string(),
-        }),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse {
-            success: false,
-            message: format!("Database error: {}", e),
-        }),
+        Err(e) => idrac_failure(e),
     }
 }
 
-pub async fn logout(session: Session) -> HttpResponse {
-    session.purge();
-    info!("User logged out");
-    
-    HttpResponse::Ok().json(ApiResponse {
+/// Set or clear (`"version": null`) the minimum expected version of a component.
+pub async fn set_firmware_baseline(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<FirmwareBaselineRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let component = req.component.trim();
+    let version = req.version.as_deref().map(str::trim);
+    if component.is_empty() {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "component",
+            message: "must not be empty".to_string(),
+        }));
+    }
+    if version == Some("") {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "version",
+            message: "must not be empty; use null to clear the baseline".to_string(),
+        }));
+    }
+
+    let result = state
+        .db
+        .set_firmware_baseline(component, version)
+        .map(|_| match version {
+            Some(version) => format!("Baseline for {} set to {}", component, version),
+            None => format!("Baseline for {} cleared", component),
+        })
+        .map_err(|e| format!("Failed to save baseline: {}", e));
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::FirmwareBaselineSet,
+        "fleet",
+        &result,
+        &serde_json::json!({ "component": component, "version": version }),
+    );
+
+    match result {
+        Ok(msg) => HttpResponse::Ok().json(ApiResponse::success(msg)),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e)),
+    }
+}
+
+/// Liveness plus anything an operator should look at. Unauthenticated so
+/// load balancers and monitors can poll it.
+pub async fn health(state: web::Data<AppState>) -> HttpResponse {
+    let mut warnings = Vec::new();
+
+    let mut clocks: Vec<(String, ClockOffset)> =
+        state.clocks.read().unwrap().iter().map(|(alias, clock)| (alias.clone(), clock.clone())).collect();
+    clocks.sort_by(|a, b| a.0.cmp(&b.0));
+    for (alias, clock) in clocks.iter().filter(|(_, clock)| clock.exceeds_threshold) {
+        warnings.push(format!(
+            "iDRAC clock offset of '{}' is {}s (threshold {}s)",
+            alias, clock.offset_secs, clock.threshold_secs
+        ));
+    }
+
+    let write_failure = state.db.write_failure();
+    if let Some(failure) = &write_failure {
+        warnings.push(format!(
+            "Database has refused writes since {} UTC ({}); only reads are served",
+            failure.since, failure.error
+        ));
+    }
+
+    HttpResponse::Ok().json(HealthResponse {
+        status: if warnings.is_empty() { "ok" } else { "degraded" },
+        standby: state.db.is_read_only(),
+        write_failure,
+        warnings,
+    })
+}
+
+/// Most recent offset between the default iDRAC's clock and the app host's.
+pub async fn clock_offset(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let clock = state.clocks.read().unwrap().get(DEFAULT_SERVER_ALIAS).cloned();
+    HttpResponse::Ok().json(ClockResponse {
         success: true,
-        message: "Logged out successfully".to_string(),
+        base_url: state.idrac.base_url().to_string(),
+        clock,
     })
 }
 
-// Middleware to check authentication
-pub async fn check_auth(session: Session) -> Result<i64, HttpResponse> {
-    match session.get::<i64>("user_id") {
-        Ok(Some(user_id)) => Ok(user_id),
-        _ => Err(HttpResponse::Unauthorized().json(ApiResponse {
-            success: false,
-            message: "Not authenticated".to_string(),
-        })),
+/// Most recent offset between a registered server's iDRAC clock and the
+/// app host's.
+pub async fn server_clock(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let alias = path.into_inner();
+    let Some(server) = state.servers.get(&alias) else {
+        return server_not_found(&alias);
+    };
+    let clock = state.clocks.read().unwrap().get(&server.alias).cloned();
+    HttpResponse::Ok().json(ClockResponse {
+        success: true,
+        base_url: server.client.base_url().to_string(),
+        clock,
+    })
+}
+
+/// Point a server's iDRAC at NTP servers; the remediation offered by a
+/// clock check that exceeds `CLOCK_DRIFT_THRESHOLD_SECS`.
+pub async fn set_server_ntp(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<NtpRequest>,
+) -> HttpResponse {
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let alias = path.into_inner();
+    let Some(server) = state.servers.get(&alias) else {
+        return server_not_found(&alias);
+    };
+
+    let req = req.into_inner();
+    let enabled = req.enabled.unwrap_or(true);
+    let servers: Vec<String> =
+        req.servers.iter().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+    if enabled && servers.is_empty() || servers.len() > 3 {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "servers",
+            message: "must list one to three NTP servers".to_string(),
+        }));
+    }
+
+    let mut attributes = serde_json::Map::new();
+    for (i, ntp_server) in servers.iter().enumerate() {
+        attributes.insert(format!("NTPConfigGroup.1.NTP{}", i + 1), serde_json::json!(ntp_server));
+    }
+    attributes.insert(
+        "NTPConfigGroup.1.NTPEnable".to_string(),
+        serde_json::json!(if enabled { "Enabled" } else { "Disabled" }),
+    );
+
+    let result = server.client.set_idrac_attributes(&attributes).await.map(|_| match enabled {
+        true => format!("NTP enabled on {} with {}", server.alias, servers.join(", ")),
+        false => format!("NTP disabled on {}", server.alias),
+    });
+    state.audit_with_details(
+        Some(user_id),
+        AuditAction::NtpConfigure,
+        &server.alias,
+        &result,
+        &serde_json::json!({ "servers": servers, "enabled": enabled }),
+    );
+
+    match result {
+        Ok(msg) => HttpResponse::Ok().json(ApiResponse::success(msg)),
+        Err(e) => idrac_failure(e),
     }
 }
 
-pub async fn power_status(
+/// The iDRAC's clock next to the app host's, measured now.
+pub async fn get_idrac_time(
     session: Session,
-    idrac: web::Data<Arc<IdracClient>>,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
 ) -> HttpResponse {
-    if check_auth(session).await.is_err() {
-        return HttpResponse::Unauthorized().json(ApiResponse {
-            success: false,
-            message: "Not authenticated".to_string(),
-        });
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
     }
 
-    match idrac.get_power_state().await {
-        Ok(state) => HttpResponse::Ok().json(StatusResponse {
-            success: true,
-            power_state: state,
-        }),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse {
-            success: false,
-            message: e,
-        }),
+    match tasks::measure_clock_offset(&state, &state.servers.default_server()).await {
+        Ok(clock) => {
+            let response = IdracTimeResponse {
+                success: true,
+                idrac_time: clock.idrac_time.clone(),
+                app_time: clock.measured_at.clone(),
+                offset_secs: clock.offset_secs,
+                previous_offset_secs: None,
+            };
+            state.clocks.write().unwrap().insert(DEFAULT_SERVER_ALIAS.to_string(), clock);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => idrac_failure(e),
     }
 }
 
-pub async fn power_on_handler(
+/// Set the iDRAC's clock to the app host's, for when NTP is not configured yet.
+pub async fn sync_idrac_time(
     session: Session,
-    idrac: web::Data<Arc<IdracClient>>,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
 ) -> HttpResponse {
-    if check_auth(session).await.is_err() {
-        return HttpResponse::Unauthorized().json(ApiResponse {
-            success: false,
-            message: "Not authenticated".to_string(),
-        });
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    let previous_offset_secs = tasks::measure_clock_offset(&state, &state.servers.default_server()).await.ok().map(|clock| clock.offset_secs);
+    let app_time = chrono::Utc::now();
+    let result = match state.idrac.set_system_time(app_time).await {
+        Ok(()) => tasks::measure_clock_offset(&state, &state.servers.default_server()).await,
+        Err(e) => Err(e),
+    };
+
+    let details = serde_json::json!({
+        "app_time": app_time.to_rfc3339(),
+        "previous_offset_secs": previous_offset_secs,
+        "offset_secs": result.as_ref().ok().map(|clock| clock.offset_secs),
+    });
+    let audit_result = result.as_ref().map(|_| format!("iDRAC clock set to {}", app_time.to_rfc3339())).map_err(|e| e.clone());
+    state.audit_with_details(Some(user_id), AuditAction::IdracTimeSync, state.idrac.base_url(), &audit_result, &details);
+
+    match result {
+        Ok(clock) => {
+            let response = IdracTimeResponse {
+                success: true,
+                idrac_time: clock.idrac_time.clone(),
+                app_time: clock.measured_at.clone(),
+                offset_secs: clock.offset_secs,
+                previous_offset_secs,
+            };
+            state.clocks.write().unwrap().insert(DEFAULT_SERVER_ALIAS.to_string(), clock);
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => idrac_failure(e),
     }
+}
 
-    match idrac.power_on().await {
-        Ok(msg) => HttpResponse::Ok().json(ApiResponse {
-            success: true,
-            message: msg,
-        }),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse {
-            success: false,
-            message: e,
-        }),
+/// Debug data on the Redfish traffic to each server: response sizes per
+/// resource and how much `$select` saves where the iDRAC supports it.
+pub async fn idrac_stats(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        return response;
     }
+
+    let servers = state
+        .servers
+        .all()
+        .iter()
+        .map(|server| IdracClientStats {
+            alias: server.alias.clone(),
+            select_supported: server.client.select_supported(),
+            payloads: server.client.payload_stats(),
+        })
+        .collect();
+    HttpResponse::Ok().json(IdracStatsResponse {
+        success: true,
+        servers,
+        route_timeouts: state.metrics.route_timeouts.lock().unwrap().clone(),
+    })
 }
 
-pub async fn power_off_handler(
+/// Promote a warm standby to primary. Refuses while the current primary's
+/// lease is still being renewed.
+pub async fn promote(
     session: Session,
-    idrac: web::Data<Arc<IdracClient>>,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    req: web::Json<PromoteRequest>,
 ) -> HttpResponse {
-    if check_auth(session).await.is_err() {
-        return HttpResponse::Unauthorized().json(ApiResponse {
-            success: false,
-            message: "Not authenticated".to_string(),
-        });
+    let user_id = match check_auth_or_token(session, &http_req, &state, TokenScope::Admin).await {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    if !req.confirm {
+        return HttpResponse::BadRequest().json(ApiResponse::error(
+            ErrorCode::ValidationConfirmationRequired,
+            "Promotion must be confirmed with {\"confirm\": true}",
+        ));
     }
 
-    match idrac.power_off().await {
-        Ok(msg) => HttpResponse::Ok().json(ApiResponse {
-            success: true,
-            message: msg,
-        }),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse {
-            success: false,
-            message: e,
-        }),
+    if !state.db.is_read_only() {
+        return HttpResponse::Conflict().json(ApiResponse::error(
+            ErrorCode::StandbyNotStandby,
+            "This instance is already the primary",
+        ));
+    }
+
+    match state.db.primary_lease_age_secs() {
+        Ok(Some(age)) if age < tasks::LEASE_TTL_SECS => {
+            return HttpResponse::Conflict().json(ApiResponse::error(
+                ErrorCode::StandbyLeaseHeld,
+                format!("Primary renewed its lease {}s ago; refusing to promote", age),
+            ));
+        }
+        Ok(_) => {}
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(ApiResponse::error(
+                ErrorCode::InternalDatabase,
+                format!("Failed to read primary lease: {}", e),
+            ));
+        }
+    }
+
+    if let Err(e) = state.db.promote() {
+        error!("Promotion failed: {}", e);
+        return HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to re-open database read-write: {}", e),
+        ));
+    }
+
+    if let Err(e) = state.db.heartbeat_lease(&state.instance_id) {
+        warn!("Promoted but failed to take primary lease: {}", e);
+    }
+    info!("Standby promoted to primary by user {}", user_id);
+    state.audit(Some(user_id), AuditAction::PromoteStandby, &Ok("Promoted to primary".to_string()));
+    group_power_on::resume_interrupted(&state);
+    firmware_rollout::resume_interrupted(&state);
+    if let Err(e) = state.db.interrupt_running_group_apply_jobs() {
+        warn!("Failed to mark interrupted group apply jobs: {}", e);
     }
+
+    HttpResponse::Ok().json(ApiResponse::success("Promoted to primary"))
 }
 
-pub async fn graceful_shutdown_handler(
+fn preferences_response(user_id: i64, state: &AppState) -> HttpResponse {
+    match state.db.get_user_preferences(user_id) {
+        Ok(rows) => {
+            let preferences = rows
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+                    (key, value)
+                })
+                .collect();
+            HttpResponse::Ok().json(PreferencesResponse {
+                success: true,
+                preferences,
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to load preferences: {}", e),
+        )),
+    }
+}
+
+pub async fn get_preferences(
     session: Session,
-    idrac: web::Data<Arc<IdracClient>>,
+    state: web::Data<AppState>,
 ) -> HttpResponse {
-    if check_auth(session).await.is_err() {
-        return HttpResponse::Unauthorized().json(ApiResponse {
-            success: false,
-            message: "Not authenticated".to_string(),
-        });
+    match check_auth(session, &state).await {
+        Ok(user) => preferences_response(user.id, &state),
+        Err(response) => response,
     }
+}
 
-    match idrac.graceful_shutdown().await {
-        Ok(msg) => HttpResponse::Ok().json(ApiResponse {
-            success: true,
-            message: msg,
-        }),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse {
-            success: false,
-            message: e,
-        }),
+/// Replace the current user's preferences with the given JSON object.
+pub async fn put_preferences(
+    session: Session,
+    state: web::Data<AppState>,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    let user_id = match check_auth(session, &state).await {
+        Ok(user) => user.id,
+        Err(response) => return response,
+    };
+
+    let pairs = match validate_preferences(&body) {
+        Ok(pairs) => pairs,
+        Err(e) => return HttpResponse::BadRequest().json(FieldErrorResponse::from(e)),
+    };
+
+    if let Err(e) = state.db.replace_user_preferences(user_id, &pairs) {
+        return HttpResponse::InternalServerError().json(ApiResponse::error(
+            ErrorCode::InternalDatabase,
+            format!("Failed to save preferences: {}", e),
+        ));
+    }
+
+    preferences_response(user_id, &state)
+}
+
+/// Lists every `error_code` value the API can return.
+pub async fn error_codes() -> HttpResponse {
+    HttpResponse::Ok().json(ErrorCodesResponse {
+        success: true,
+        codes: ErrorCode::ALL.iter().map(|code| code.as_str()).collect(),
+    })
+}
+
+/// The OpenAPI document of the API, including an error example for every
+/// `error_code`.
+pub async fn openapi_document() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(OPENAPI_DOCUMENT)
+}
+
+/// Turn JSON body deserialization failures into the standard error shape.
+pub fn json_error_handler(
+    err: actix_web::error::JsonPayloadError,
+    _req: &actix_web::HttpRequest,
+) -> actix_web::Error {
+    // Deserialization errors quote the offending input, which may be a password.
+    let message = scrub::scrub_text(&format!("Invalid request body: {}", err));
+    let response = HttpResponse::BadRequest().json(ApiResponse::error(
+        ErrorCode::ValidationInvalidBody,
+        message,
+    ));
+    actix_web::error::InternalError::from_response(err, response).into()
+}
+
+/// Stored daily health reports, newest first.
+pub async fn list_health_reports(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<HealthReportsQuery>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    let server = query.server.as_deref().map(str::trim).filter(|alias| !alias.is_empty());
+    let date = query.date.as_deref().map(str::trim).filter(|date| !date.is_empty());
+    if let Some(date) = date {
+        if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+                field: "date",
+                message: "must be a YYYY-MM-DD date".to_string(),
+            }));
+        }
+    }
+    let limit = query.limit.unwrap_or(HEALTH_REPORTS_DEFAULT_LIMIT);
+    if limit == 0 || limit > HEALTH_REPORTS_MAX_LIMIT {
+        return HttpResponse::BadRequest().json(FieldErrorResponse::from(FieldError {
+            field: "limit",
+            message: format!("must be between 1 and {}", HEALTH_REPORTS_MAX_LIMIT),
+        }));
+    }
+
+    match state.db.list_health_reports(server, date, limit) {
+        Ok(reports) => HttpResponse::Ok().json(HealthReportsResponse { success: true, reports }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+/// The newest stored health report of each server.
+pub async fn latest_health_reports(
+    session: Session,
+    http_req: HttpRequest,
+    state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Err(response) = check_auth_or_token(session, &http_req, &state, TokenScope::InventoryRead).await {
+        return response;
+    }
+
+    match state.db.latest_health_reports() {
+        Ok(reports) => HttpResponse::Ok().json(HealthReportsResponse { success: true, reports }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::error(ErrorCode::InternalDatabase, e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn error_codes_lists_every_code() {
+        let response = error_codes().await;
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        let listed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let codes = listed["codes"].as_array().unwrap();
+        for code in ErrorCode::ALL {
+            assert!(codes.contains(&code.as_str().into()), "{} is missing from /api/error-codes", code);
+        }
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+    }
+
+    #[test]
+    fn external_accounts_take_the_role_of_their_scopes() {
+        assert_eq!(TokenScope::role_for(&[TokenScope::PowerRead, TokenScope::InventoryRead]), UserRole::ReadOnly);
+        assert_eq!(TokenScope::role_for(&[TokenScope::PowerRead, TokenScope::PowerWrite]), UserRole::Admin);
+
+        let (state, _dir) = crate::testing::app_state();
+        let username = crate::testing::unique("directory");
+        let id = state.db.insert_external_user(&username, database::AUTH_SOURCE_LDAP, UserRole::ReadOnly).unwrap();
+        let user = state.db.get_user_by_id(id).unwrap().unwrap();
+        assert_eq!(user.role, UserRole::ReadOnly);
+
+        // The group mapping changed since the last login.
+        let user = sync_external_role(&state, user, UserRole::Admin).unwrap();
+        assert_eq!(user.role, UserRole::Admin);
+        assert_eq!(state.db.get_user_by_id(id).unwrap().unwrap().role, UserRole::Admin);
+        let user = sync_external_role(&state, user, UserRole::ReadOnly).unwrap();
+        assert_eq!(state.db.get_user_by_id(user.id).unwrap().unwrap().role, UserRole::ReadOnly);
+    }
+
+    fn shutdown(escalate_after_secs: Option<u64>, escalate: Option<EscalationMode>) -> ShutdownRequest {
+        ShutdownRequest {
+            escalate_after_secs,
+            escalate,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn shutdown_without_escalation() {
+        assert_eq!(shutdown_escalation(&shutdown(None, None)).unwrap(), None);
+    }
+
+    #[test]
+    fn shutdown_escalation_defaults_to_alert() {
+        assert_eq!(
+            shutdown_escalation(&shutdown(Some(180), None)).unwrap(),
+            Some((Duration::from_secs(180), EscalationMode::Alert))
+        );
+        assert_eq!(
+            shutdown_escalation(&shutdown(Some(86400), Some(EscalationMode::Force))).unwrap(),
+            Some((Duration::from_secs(86400), EscalationMode::Force))
+        );
+    }
+
+    #[test]
+    fn shutdown_escalation_deadline_is_bounded() {
+        for secs in [0, 86401, u64::MAX] {
+            let e = shutdown_escalation(&shutdown(Some(secs), Some(EscalationMode::Force))).unwrap_err();
+            assert_eq!(e.field, "escalate_after_secs");
+        }
+    }
+
+    #[test]
+    fn shutdown_escalate_needs_a_deadline() {
+        let e = shutdown_escalation(&shutdown(None, Some(EscalationMode::Force))).unwrap_err();
+        assert_eq!(e.field, "escalate_after_secs");
     }
 }
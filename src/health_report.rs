@@ -0,0 +1,247 @@
+use chrono::{Local, Utc};
+use futures_util::stream::{self, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::database::SQLITE_TIMESTAMP_FORMAT;
+use crate::idrac::{ComponentHealth, PowerSupply, StorageController, TemperatureReading};
+use crate::mailer::{self, Email};
+use crate::servers::RegisteredServer;
+use crate::state::AppState;
+
+/// Servers are read at most this many at a time.
+const REPORT_CONCURRENCY: usize = 4;
+/// Time allowed for each of a server's requests.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The temperature sensors of a server, boiled down.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemperatureSummary {
+    pub sensors: usize,
+    pub inlet_celsius: Option<f64>,
+    pub max_celsius: Option<f64>,
+    pub hottest: Option<String>,
+    /// Sensors the iDRAC reports unhealthy or that read at or above their
+    /// critical threshold.
+    pub alarms: Vec<String>,
+}
+
+impl TemperatureSummary {
+    fn new(readings: &[TemperatureReading]) -> Self {
+        let hottest = readings
+            .iter()
+            .filter_map(|sensor| sensor.reading_celsius.map(|celsius| (celsius, sensor)))
+            .max_by(|(a, _), (b, _)| a.total_cmp(b));
+        TemperatureSummary {
+            sensors: readings.len(),
+            inlet_celsius: readings
+                .iter()
+                .find(|sensor| sensor.name.to_ascii_lowercase().contains("inlet"))
+                .and_then(|sensor| sensor.reading_celsius),
+            max_celsius: hottest.map(|(celsius, _)| celsius),
+            hottest: hottest.map(|(_, sensor)| sensor.name.clone()),
+            alarms: readings
+                .iter()
+                .filter(|sensor| {
+                    matches!(sensor.health.as_deref(), Some("Warning" | "Critical"))
+                        || matches!(
+                            (sensor.reading_celsius, sensor.upper_threshold_critical),
+                            (Some(reading), Some(critical)) if reading >= critical
+                        )
+                })
+                .map(|sensor| sensor.name.clone())
+                .collect(),
+        }
+    }
+}
+
+/// What one server's daily report holds. Parts that could not be read are
+/// `None`, with the reason in `errors`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerHealthReport {
+    pub server: String,
+    pub collected_at: String,
+    pub reachable: bool,
+    pub power_state: Option<String>,
+    pub health: Option<String>,
+    pub component_health: Option<Vec<ComponentHealth>>,
+    pub temperatures: Option<TemperatureSummary>,
+    pub power_supplies: Option<Vec<PowerSupply>>,
+    pub storage: Option<Vec<StorageController>>,
+    pub errors: Vec<String>,
+}
+
+impl ServerHealthReport {
+    /// One line for the mailed summary.
+    fn summary_line(&self) -> String {
+        if !self.reachable {
+            return format!("{}: unreachable", self.server);
+        }
+        let mut line = format!(
+            "{}: {}, health {}",
+            self.server,
+            self.power_state.as_deref().unwrap_or("power unknown"),
+            self.health.as_deref().unwrap_or("unknown"),
+        );
+        if let Some(max) = self.temperatures.as_ref().and_then(|t| t.max_celsius) {
+            line.push_str(&format!(", max {:.0} C", max));
+        }
+        let bad_supplies = self
+            .power_supplies
+            .iter()
+            .flatten()
+            .filter(|supply| matches!(supply.health.as_deref(), Some("Warning" | "Critical")))
+            .count();
+        if bad_supplies > 0 {
+            line.push_str(&format!(", {} PSU(s) unhealthy", bad_supplies));
+        }
+        let bad_storage = self
+            .storage
+            .iter()
+            .flatten()
+            .filter(|controller| matches!(controller.health.as_deref(), Some("Warning" | "Critical")))
+            .count();
+        if bad_storage > 0 {
+            line.push_str(&format!(", {} storage controller(s) unhealthy", bad_storage));
+        }
+        if !self.errors.is_empty() {
+            line.push_str(&format!(" ({} part(s) unread)", self.errors.len()));
+        }
+        line
+    }
+}
+
+async fn with_timeout<T>(request: impl std::future::Future<Output = Result<T, String>>) -> Result<T, String> {
+    tokio::time::timeout(REQUEST_TIMEOUT, request)
+        .await
+        .unwrap_or_else(|_| Err(format!("Timed out after {}s", REQUEST_TIMEOUT.as_secs())))
+}
+
+/// Read the power state, which doubles as the reachability check, then
+/// the rest of the report at once.
+async fn collect(server: &RegisteredServer) -> ServerHealthReport {
+    let mut report = ServerHealthReport {
+        server: server.alias.clone(),
+        collected_at: Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+        reachable: false,
+        power_state: None,
+        health: None,
+        component_health: None,
+        temperatures: None,
+        power_supplies: None,
+        storage: None,
+        errors: Vec::new(),
+    };
+
+    match with_timeout(server.client.get_power_state()).await {
+        Ok(power_state) => {
+            report.reachable = true;
+            report.power_state = Some(power_state);
+        }
+        Err(e) => {
+            report.errors.push(e);
+            return report;
+        }
+    }
+
+    let client = &server.client;
+    let (health, temperatures, supplies, storage) = tokio::join!(
+        with_timeout(client.get_health()),
+        with_timeout(client.get_temperatures()),
+        with_timeout(client.get_power_supplies()),
+        with_timeout(client.get_storage_controllers()),
+    );
+    match health {
+        Ok(health) => {
+            report.health = Some(health.health);
+            report.component_health = Some(health.component_health);
+        }
+        Err(e) => report.errors.push(format!("Health: {}", e)),
+    }
+    match temperatures {
+        Ok(readings) => report.temperatures = Some(TemperatureSummary::new(&readings)),
+        Err(e) => report.errors.push(format!("Temperatures: {}", e)),
+    }
+    match supplies {
+        Ok(supplies) => report.power_supplies = Some(supplies),
+        Err(e) => report.errors.push(format!("Power supplies: {}", e)),
+    }
+    match storage {
+        Ok(storage) => report.storage = Some(storage),
+        Err(e) => report.errors.push(format!("Storage: {}", e)),
+    }
+    report
+}
+
+/// Mail the day's reports: a line per server, then the full reports.
+async fn email(state: &AppState, date: &str, reports: &[ServerHealthReport]) {
+    let Some(smtp) = state.config.smtp.clone() else {
+        return;
+    };
+    let recipients = state.config.health_report_recipients.clone();
+    if recipients.is_empty() {
+        return;
+    }
+
+    let healthy = reports
+        .iter()
+        .filter(|report| report.reachable && report.health.as_deref() == Some("OK"))
+        .count();
+    let subject = format!("iDRAC health report {}: {} of {} server(s) OK", date, healthy, reports.len());
+    let mut body: String = reports.iter().map(|report| report.summary_line() + "\n").collect();
+    body.push('\n');
+    body.push_str(&serde_json::to_string_pretty(reports).unwrap_or_default());
+
+    let sent = tokio::task::spawn_blocking(move || {
+        mailer::send(
+            &smtp,
+            &Email {
+                to: &recipients,
+                subject: &subject,
+                body: &body,
+            },
+        )
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    match sent {
+        Ok(()) => info!("Mailed the health report to {} recipient(s)", state.config.health_report_recipients.len()),
+        Err(e) => warn!("Failed to mail the health report: {}", e),
+    }
+}
+
+/// Report on every registered server, store one report per server under
+/// today's local date, and mail them when SMTP and recipients are set.
+pub async fn run(state: &AppState) {
+    let date = Local::now().format("%Y-%m-%d").to_string();
+    let mut reports: Vec<ServerHealthReport> = stream::iter(state.servers.pollable())
+        .map(|server| async move { collect(&server).await })
+        .buffer_unordered(REPORT_CONCURRENCY)
+        .collect()
+        .await;
+    reports.sort_by(|a, b| a.server.cmp(&b.server));
+
+    for report in &reports {
+        let stored = serde_json::to_value(report)
+            .map_err(|e| e.to_string())
+            .and_then(|value| {
+                state
+                    .db
+                    .store_health_report(&report.server, &date, &value)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = stored {
+            warn!("Failed to store the health report of '{}': {}", report.server, e);
+        }
+    }
+    let unreachable = reports.iter().filter(|report| !report.reachable).count();
+    info!(
+        "Health report for {}: {} server(s), {} unreachable",
+        date,
+        reports.len(),
+        unreachable
+    );
+
+    email(state, &date, &reports).await;
+}
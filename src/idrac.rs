@@ -1,127 +1,2523 @@
+use reqwest::header::HeaderValue;
 use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
-use log::{info, error};
+use log::{info, error, warn};
 use base64::Engine;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
+pub use crate::api::PowerChangeReason;
+use crate::errors::BIOS_SETTING_REJECTED_PREFIX;
+use crate::rate_limit::{RateLimit, RateLimitedIdracClient};
+use crate::scrub;
+use crate::secret::SecretString;
+use crate::vault::IdracCredentials;
+
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IdracError {
     pub message: String,
 }
 
+/// Which iDRAC event category/severity combinations are forwarded to
+/// subscribed event destinations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertFilter {
+    pub category: String,
+    pub severity: String,
+    pub enabled: bool,
+}
+
+const ALERT_CATEGORIES: &[&str] = &["System", "Storage", "Updates", "Audit", "Configuration", "WorkNotes"];
+const ALERT_SEVERITIES: &[&str] = &["Critical", "Warning", "Informational"];
+
+impl AlertFilter {
+    fn attribute_name(&self) -> Result<String, String> {
+        let category = ALERT_CATEGORIES
+            .iter()
+            .find(|c| c.eq_ignore_ascii_case(&self.category))
+            .ok_or_else(|| format!(
+                "Unknown alert category '{}', expected one of: {}",
+                self.category,
+                ALERT_CATEGORIES.join(", ")
+            ))?;
+        let severity = ALERT_SEVERITIES
+            .iter()
+            .find(|s| s.eq_ignore_ascii_case(&self.severity))
+            .ok_or_else(|| format!(
+                "Unknown alert severity '{}', expected one of: {}",
+                self.severity,
+                ALERT_SEVERITIES.join(", ")
+            ))?;
+        Ok(format!("EventFilters.1.{}{}", category, severity))
+    }
+}
+
+/// A single entry from the BIOS attribute registry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BiosAttributeDefinition {
+    pub attribute_name: String,
+    #[serde(rename = "Type")]
+    pub attribute_type: String,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub value: Vec<BiosAttributeValue>,
+    pub lower_bound: Option<i64>,
+    pub upper_bound: Option<i64>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BiosAttributeValue {
+    pub value_name: String,
+}
+
+/// BIOS attribute registry indexed by attribute name.
+#[derive(Debug, Default)]
+pub struct BiosRegistry {
+    attributes: HashMap<String, BiosAttributeDefinition>,
+}
+
+impl BiosRegistry {
+    fn from_json(data: &serde_json::Value) -> Result<Self, String> {
+        let entries = data["RegistryEntries"]["Attributes"]
+            .as_array()
+            .ok_or_else(|| "BIOS attribute registry has no RegistryEntries.Attributes".to_string())?;
+
+        let mut attributes = HashMap::new();
+        for entry in entries {
+            let definition: BiosAttributeDefinition = serde_json::from_value(entry.clone())
+                .map_err(|e| format!("Failed to parse BIOS registry entry: {}", e))?;
+            attributes.insert(definition.attribute_name.clone(), definition);
+        }
+
+        Ok(BiosRegistry { attributes })
+    }
+
+    /// Check a proposed attribute value against the registry definition.
+    pub fn validate(&self, name: &str, value: &serde_json::Value) -> Result<(), String> {
+        self.check(name, value)
+            .map_err(|e| format!("{}: {}", BIOS_SETTING_REJECTED_PREFIX, e))
+    }
+
+    fn check(&self, name: &str, value: &serde_json::Value) -> Result<(), String> {
+        let definition = self
+            .attributes
+            .get(name)
+            .ok_or_else(|| format!("Unknown BIOS attribute '{}'", name))?;
+
+        if definition.read_only {
+            return Err(format!("BIOS attribute '{}' is read-only", name));
+        }
+
+        match definition.attribute_type.as_str() {
+            "Enumeration" => {
+                let choice = value
+                    .as_str()
+                    .ok_or_else(|| format!("BIOS attribute '{}' expects one of its enumeration values as a string", name))?;
+                if !definition.value.iter().any(|v| v.value_name == choice) {
+                    let allowed: Vec<&str> = definition.value.iter().map(|v| v.value_name.as_str()).collect();
+                    return Err(format!(
+                        "'{}' is not a valid value for BIOS attribute '{}', expected one of: {}",
+                        choice,
+                        name,
+                        allowed.join(", ")
+                    ));
+                }
+            }
+            "Integer" => {
+                let number = value
+                    .as_i64()
+                    .ok_or_else(|| format!("BIOS attribute '{}' expects an integer", name))?;
+                if let Some(lower) = definition.lower_bound {
+                    if number < lower {
+                        return Err(format!("BIOS attribute '{}' must be at least {}", name, lower));
+                    }
+                }
+                if let Some(upper) = definition.upper_bound {
+                    if number > upper {
+                        return Err(format!("BIOS attribute '{}' must be at most {}", name, upper));
+                    }
+                }
+            }
+            "String" | "Password" => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| format!("BIOS attribute '{}' expects a string", name))?;
+                let length = text.chars().count();
+                if let Some(min) = definition.min_length {
+                    if length < min {
+                        return Err(format!("BIOS attribute '{}' must be at least {} characters", name, min));
+                    }
+                }
+                if let Some(max) = definition.max_length {
+                    if length > max {
+                        return Err(format!("BIOS attribute '{}' must be at most {} characters", name, max));
+                    }
+                }
+            }
+            "Boolean" => {
+                if !value.is_boolean() {
+                    return Err(format!("BIOS attribute '{}' expects true or false", name));
+                }
+            }
+            other => {
+                return Err(format!("BIOS attribute '{}' has unsupported type '{}'", name, other));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Dell system profiles, trading power draw against performance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProfileType {
+    Performance,
+    PerformancePerWatt,
+    DenseConfigure,
+    Custom,
+}
+
+impl ProfileType {
+    /// Value of the `SysProfile` BIOS attribute.
+    pub fn attribute_value(self) -> &'static str {
+        match self {
+            ProfileType::Performance => "PerfOptimized",
+            ProfileType::PerformancePerWatt => "PerfPerWattOptimizedDapc",
+            ProfileType::DenseConfigure => "DenseCfgOptimized",
+            ProfileType::Custom => "Custom",
+        }
+    }
+
+    fn from_attribute_value(value: &str) -> Option<Self> {
+        match value {
+            "PerfOptimized" => Some(ProfileType::Performance),
+            // The OS-controlled variant is the same profile with the OS
+            // rather than the BMC managing CPU power.
+            "PerfPerWattOptimizedDapc" | "PerfPerWattOptimizedOs" => Some(ProfileType::PerformancePerWatt),
+            "DenseCfgOptimized" => Some(ProfileType::DenseConfigure),
+            "Custom" => Some(ProfileType::Custom),
+            _ => None,
+        }
+    }
+}
+
+/// `BIOS.Setup.1-1#SysProfile`, as currently applied and as staged for
+/// the next reboot.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemProfile {
+    /// `None` when the BIOS reports a value this client does not know.
+    pub profile: Option<ProfileType>,
+    pub attribute_value: String,
+    pub pending: Option<ProfileType>,
+}
+
+/// Which network port carries the iDRAC's own traffic: its dedicated port
+/// or a LAN-on-motherboard port shared with the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NicMode {
+    Dedicated,
+    LOM1,
+    LOM2,
+    /// Shared on LOM1, failing over to the other LOMs.
+    SharedWithFailover,
+}
+
+impl NicMode {
+    /// Values of the `NIC.1.Selection` and, for shared modes,
+    /// `NIC.1.Failover` iDRAC attributes.
+    fn attributes(self) -> serde_json::Value {
+        match self {
+            NicMode::Dedicated => serde_json::json!({ "NIC.1.Selection": "Dedicated" }),
+            NicMode::LOM1 => serde_json::json!({ "NIC.1.Selection": "LOM1", "NIC.1.Failover": "None" }),
+            NicMode::LOM2 => serde_json::json!({ "NIC.1.Selection": "LOM2", "NIC.1.Failover": "None" }),
+            NicMode::SharedWithFailover => serde_json::json!({ "NIC.1.Selection": "LOM1", "NIC.1.Failover": "All" }),
+        }
+    }
+
+    fn from_attributes(selection: &str, failover: Option<&str>) -> Option<Self> {
+        let failover = failover.filter(|f| *f != "None");
+        match (selection, failover) {
+            ("Dedicated", _) => Some(NicMode::Dedicated),
+            ("LOM1" | "LOM2" | "LOM3" | "LOM4", Some(_)) => Some(NicMode::SharedWithFailover),
+            ("LOM1", None) => Some(NicMode::LOM1),
+            ("LOM2", None) => Some(NicMode::LOM2),
+            _ => None,
+        }
+    }
+}
+
+/// Privilege of an iDRAC local account, sent and read as its Redfish
+/// `RoleId`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdracPrivilege {
+    Administrator,
+    Operator,
+    ReadOnly,
+    /// No access; the role of unused account slots.
+    None,
+}
+
+impl IdracPrivilege {
+    fn from_role_id(role_id: &str) -> Option<Self> {
+        match role_id {
+            "Administrator" => Some(IdracPrivilege::Administrator),
+            "Operator" => Some(IdracPrivilege::Operator),
+            "ReadOnly" => Some(IdracPrivilege::ReadOnly),
+            "None" => Some(IdracPrivilege::None),
+            _ => None,
+        }
+    }
+}
+
+/// A local account of the iDRAC.
+#[derive(Debug, Clone, Serialize)]
+pub struct IdracUser {
+    /// Account slot, `2` to `16` on an iDRAC.
+    pub id: String,
+    pub username: String,
+    pub enabled: bool,
+    /// `None` for a `RoleId` outside the four standard ones, such as a
+    /// custom role; `role_id` still has it.
+    pub privilege: Option<IdracPrivilege>,
+    pub role_id: String,
+}
+
+/// Changes to an iDRAC account; fields left `None` are kept.
+#[derive(Debug, Default, Deserialize)]
+pub struct IdracUserUpdate {
+    pub privilege: Option<IdracPrivilege>,
+    pub password: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// `NIC.1.Selection` and `NIC.1.Failover` as the iDRAC reports them.
+#[derive(Debug, Clone, Serialize)]
+pub struct NicSelection {
+    /// `None` when the attributes describe a mode this client does not know.
+    pub mode: Option<NicMode>,
+    pub selection: String,
+    pub failover: Option<String>,
+}
+
+/// `BootSourceOverrideTarget` values, named as Redfish names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BootTarget {
+    /// Virtual or physical optical drive.
+    Cd,
+    Pxe,
+    Hdd,
+    BiosSetup,
+}
+
+/// `BootSourceOverrideEnabled` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BootMode {
+    /// For the next boot only.
+    Once,
+    Continuous,
+    Disabled,
+}
+
+/// `ResetType` values of `ComputerSystem.Reset` in the Redfish schema.
+/// Anything else is refused before it reaches the iDRAC.
+const RESET_TYPES: &[&str] = &[
+    "On",
+    "ForceOff",
+    "GracefulShutdown",
+    "GracefulRestart",
+    "ForceRestart",
+    "Nmi",
+    "ForceOn",
+    "PushPowerButton",
+    "PowerCycle",
+];
+
+/// How to restart the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RestartType {
+    /// Ask the OS to restart.
+    Graceful,
+    /// Reset without waiting for the OS.
+    Force,
+}
+
+impl RestartType {
+    /// `ResetType` of `ComputerSystem.Reset`.
+    pub fn reset_type(self) -> &'static str {
+        match self {
+            RestartType::Graceful => "GracefulRestart",
+            RestartType::Force => "ForceRestart",
+        }
+    }
+}
+
+/// BIOS attributes behind the POST watchdog timer.
+pub fn post_watchdog_attributes(enabled: bool, timeout_minutes: u32) -> [(&'static str, serde_json::Value); 2] {
+    [
+        ("PostWatchdogTimer", if enabled { "Enabled" } else { "Disabled" }.into()),
+        ("PostWatchdogTimeout", timeout_minutes.into()),
+    ]
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceModuleStatus {
+    pub installed: bool,
+    pub version: String,
+    pub status: String,
+}
+
+/// Key facts from the ComputerSystem resource.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemInfo {
+    pub hostname: String,
+    pub model: String,
+    pub service_tag: String,
+    pub power_state: String,
+    pub health: String,
+    pub bios_version: String,
+    pub cpu_count: u64,
+    pub cpu_model: String,
+    pub memory_gib: f64,
+    pub last_boot_time: Option<String>,
+    /// Per-subsystem rollups from the Dell OEM extension, when present.
+    pub component_health: Vec<ComponentHealth>,
+}
+
+/// State of an iDRAC job, e.g. a staged firmware update.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub name: Option<String>,
+    /// Dell `JobType`, e.g. `FirmwareUpdate` or `BIOSConfiguration`.
+    pub job_type: Option<String>,
+    /// `JobState`, e.g. `Scheduled`, `Running`, `Completed` or `Failed`.
+    pub state: String,
+    pub percent_complete: Option<u64>,
+    pub message: Option<String>,
+}
+
+impl JobStatus {
+    /// Whether the job has run, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.state.as_str(),
+            "Completed" | "CompletedWithErrors" | "Failed" | "CompletedWithError" | "Aborted" | "Cancelled"
+        )
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.state == "Completed"
+    }
+
+    /// Whether the job installs firmware. Jobs that do not report a type
+    /// are recognised by the name the iDRAC gives update jobs.
+    pub fn is_firmware_update(&self) -> bool {
+        match &self.job_type {
+            Some(job_type) => matches!(job_type.as_str(), "FirmwareUpdate" | "RepositoryUpdate"),
+            None => self.name.as_deref().is_some_and(|name| name.starts_with("Firmware Update")),
+        }
+    }
+
+    fn from_json(job_id: &str, job: &serde_json::Value) -> Self {
+        JobStatus {
+            id: job["Id"].as_str().unwrap_or(job_id).to_string(),
+            name: job["Name"].as_str().map(str::to_string),
+            job_type: job["JobType"].as_str().map(str::to_string),
+            state: job["JobState"].as_str().unwrap_or("Unknown").to_string(),
+            percent_complete: job["PercentComplete"].as_u64(),
+            message: job["Message"].as_str().map(str::to_string),
+        }
+    }
+}
+
+/// Overall and per-subsystem health, as shown on the fleet board.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemHealth {
+    pub health: String,
+    pub component_health: Vec<ComponentHealth>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub health: String,
+}
+
+/// A power supply as reported by the chassis `Power` resource.
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerSupply {
+    pub name: String,
+    /// `Status.Health`: `OK`, `Warning` or `Critical`.
+    pub health: Option<String>,
+    /// `Status.State`, e.g. `Enabled` or `Absent`.
+    pub state: Option<String>,
+    pub line_input_voltage: Option<f64>,
+}
+
+/// One temperature sensor of the chassis.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemperatureReading {
+    pub name: String,
+    pub reading_celsius: Option<f64>,
+    pub upper_threshold_critical: Option<f64>,
+    pub health: Option<String>,
+}
+
+/// One storage controller and the drives behind it.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageController {
+    pub name: String,
+    /// `Status.HealthRollup`, which covers the drives, or `Status.Health`.
+    pub health: Option<String>,
+    pub drives: u64,
+}
+
+/// One UEFI boot entry, in persistent boot order.
+#[derive(Debug, Clone, Serialize)]
+pub struct BootOption {
+    /// `BootOptionReference`, e.g. `Boot0003`.
+    pub id: String,
+    pub display_name: String,
+    pub device_path: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// An installed firmware component, e.g. the BIOS or a NIC.
+#[derive(Debug, Clone, Serialize)]
+pub struct FirmwareComponent {
+    pub name: String,
+    pub version: String,
+}
+
+/// One System Event Log or Lifecycle Controller log record.
+#[derive(Debug, Clone, Serialize)]
+pub struct SelEntry {
+    pub id: String,
+    /// Redfish URI of the entry on the iDRAC.
+    pub uri: String,
+    pub created: String,
+    pub severity: String,
+    pub message: String,
+    pub message_id: Option<String>,
+}
+
+/// The iDRAC web server's TLS certificate.
+#[derive(Debug, Clone, Serialize)]
+pub struct SslCertInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub valid_not_before: String,
+    pub valid_not_after: String,
+    pub signature_algorithm: String,
+}
+
+impl SslCertInfo {
+    /// Whole days until `valid_not_after`, negative once expired.
+    pub fn days_remaining(&self) -> Option<i64> {
+        chrono::DateTime::parse_from_rfc3339(&self.valid_not_after)
+            .ok()
+            .map(|expiry| (expiry.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_days())
+    }
+}
+
+/// Collect `*RollupStatus` properties, e.g. `FanRollupStatus` becomes `Fan`.
+fn parse_component_health(dell_system: &serde_json::Value) -> Vec<ComponentHealth> {
+    let mut components: Vec<ComponentHealth> = dell_system
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| {
+            let name = key.strip_suffix("RollupStatus")?;
+            Some(ComponentHealth {
+                name: name.to_string(),
+                health: value.as_str()?.to_string(),
+            })
+        })
+        .collect();
+    components.sort_by(|a, b| a.name.cmp(&b.name));
+    components
+}
+
+/// Render a Redfish certificate Identifier object as `CN=..., O=...`.
+fn format_certificate_identifier(identifier: &serde_json::Value) -> String {
+    let fields = [
+        ("CN", "CommonName"),
+        ("OU", "OrganizationalUnit"),
+        ("O", "Organization"),
+        ("L", "City"),
+        ("ST", "State"),
+        ("C", "Country"),
+    ];
+
+    fields
+        .iter()
+        .filter_map(|(short, key)| identifier[*key].as_str().map(|value| format!("{}={}", short, value)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+const BODY_EXCERPT_CHARS: usize = 200;
+
+const HOSTNAME_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+const HOSTNAME_LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Oldest TLS version offered to iDRACs, from `IDRAC_TLS_MIN_VERSION`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsMinVersion {
+    Tls10,
+    Tls11,
+    #[default]
+    Tls12,
+}
+
+impl TlsMinVersion {
+    /// `IDRAC_TLS_MIN_VERSION`: `1.0`, `1.1` or `1.2` (the default). `1.3`
+    /// is refused: the OpenSSL backend cannot require it, though it
+    /// negotiates 1.3 with every iDRAC that offers it.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("IDRAC_TLS_MIN_VERSION").map(|v| v.trim().to_string()).as_deref() {
+            Err(_) | Ok("") => Ok(TlsMinVersion::default()),
+            Ok("1.0") => Ok(TlsMinVersion::Tls10),
+            Ok("1.1") => Ok(TlsMinVersion::Tls11),
+            Ok("1.2") => Ok(TlsMinVersion::Tls12),
+            Ok("1.3") => Err("IDRAC_TLS_MIN_VERSION=1.3 cannot be enforced by the OpenSSL TLS backend; \
+                 use 1.2, which still negotiates TLS 1.3 with iDRACs that support it"
+                .to_string()),
+            Ok(other) => Err(format!("IDRAC_TLS_MIN_VERSION must be 1.0, 1.1, 1.2 or 1.3, not '{}'", other)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TlsMinVersion::Tls10 => "1.0",
+            TlsMinVersion::Tls11 => "1.1",
+            TlsMinVersion::Tls12 => "1.2",
+        }
+    }
+
+    fn to_reqwest(self) -> reqwest::tls::Version {
+        match self {
+            TlsMinVersion::Tls10 => reqwest::tls::Version::TLS_1_0,
+            TlsMinVersion::Tls11 => reqwest::tls::Version::TLS_1_1,
+            TlsMinVersion::Tls12 => reqwest::tls::Version::TLS_1_2,
+        }
+    }
+}
+
+fn response_content_type(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Id of the job a `Location` header points at, for changes the iDRAC
+/// stages until the next reboot.
+fn staged_job_id(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|location| location.rsplit('/').next())
+        .filter(|id| id.starts_with("JID_"))
+        .map(|id| id.to_string())
+}
+
+/// Read and decode a response body with `decode_json`.
+async fn read_body(response: reqwest::Response) -> Result<serde_json::Value, String> {
+    let content_type = response_content_type(&response);
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    decode_json(content_type.as_deref(), &body)
+}
+
+/// Decode a Redfish JSON body the way older firmware actually sends it: with
+/// a UTF-8 BOM, trailing NULs, or a `text/plain` content type. When it still
+/// isn't JSON (an HTML error page, a truncated document) the error quotes the
+/// start of the body and the content type.
+fn decode_json(content_type: Option<&str>, body: &[u8]) -> Result<serde_json::Value, String> {
+    let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+    let end = body.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    let body = &body[..end];
+    let content_type = content_type.unwrap_or("no content type");
+
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Err(format!("Invalid iDRAC response ({}): empty body", content_type));
+    }
+    serde_json::from_slice(body).map_err(|e| {
+        format!(
+            "Invalid iDRAC response ({}): {}; body starts with: {}",
+            content_type,
+            e,
+            body_excerpt(body)
+        )
+    })
+}
+
+/// The first characters of a body on one line, with credentials redacted.
+fn body_excerpt(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    let excerpt: String = text
+        .chars()
+        .take(BODY_EXCERPT_CHARS)
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    scrub::scrub_text(&excerpt.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// A Redfish response passed on as the iDRAC sent it.
+#[derive(Debug, Clone, Serialize)]
+pub struct RawRedfishResponse {
+    pub status: u16,
+    /// Task or job monitor of an accepted action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// The body as JSON; text when it is not JSON, `null` when empty.
+    pub body: serde_json::Value,
+}
+
+/// Response sizes seen for one Redfish resource.
+#[derive(Debug, Clone, Default)]
+struct PayloadCounter {
+    full_requests: u64,
+    full_bytes: u64,
+    selected_requests: u64,
+    selected_bytes: u64,
+}
+
+/// Average response size of a resource fetched whole and through `$select`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PayloadStats {
+    pub path: String,
+    pub full_requests: u64,
+    pub average_full_bytes: Option<u64>,
+    pub selected_requests: u64,
+    pub average_selected_bytes: Option<u64>,
+    /// Share of the full payload saved by `$select`, from 0.0 to 1.0.
+    pub reduction: Option<f64>,
+}
+
+/// Last reverse DNS lookup of an iDRAC's host and when it was made.
+#[derive(Debug, Clone)]
+struct CachedHostname {
+    resolved_at: Instant,
+    hostname: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct IdracClient {
     base_url: String,
-    username: String,
-    password: String,
-    client: Client,
+    /// `Basic ...` value of the Authorization header, encoded once.
+    auth_header: Arc<SecretString>,
+    client: RateLimitedIdracClient,
+    bios_registry: Arc<RwLock<Option<Arc<BiosRegistry>>>>,
+    /// Whether the iDRAC honours `$select`; `None` until the service root
+    /// has been checked.
+    select_supported: Arc<RwLock<Option<bool>>>,
+    payload_counters: Arc<Mutex<BTreeMap<String, PayloadCounter>>>,
+    hostname_cache: Arc<Mutex<Option<CachedHostname>>>,
 }
 
 impl IdracClient {
-    pub fn from_env() -> Result<Self, String> {
-        let base_url = std::env::var("IDRAC_HOST")
+    /// Client for `IDRAC_HOST`, logging in with `credentials` from Vault or
+    /// else `IDRAC_USERNAME` and `IDRAC_PASSWORD`.
+    pub fn from_env(credentials: Option<IdracCredentials>) -> Result<Self, String> {
+        let host = std::env::var("IDRAC_HOST")
             .map_err(|_| "IDRAC_HOST environment variable not set".to_string())?;
+        if let Some(credentials) = credentials {
+            return Self::new(&host, &credentials.username, &credentials.password);
+        }
         let username = std::env::var("IDRAC_USERNAME")
             .map_err(|_| "IDRAC_USERNAME environment variable not set".to_string())?;
         let password = std::env::var("IDRAC_PASSWORD")
+            .map(SecretString::from)
             .map_err(|_| "IDRAC_PASSWORD environment variable not set".to_string())?;
 
-        // Build client that accepts self-signed certificates (common for iDRAC)
+        Self::new(&host, &username, &password)
+    }
+
+    pub fn new(host: &str, username: &str, password: &SecretString) -> Result<Self, String> {
+        let base_url = normalize_host(host)
+            .map_err(|e| format!("Invalid iDRAC host '{}': {}", host, e))?;
+
+        // Build client that accepts self-signed certificates (common for iDRAC).
+        // An invalid IDRAC_TLS_MIN_VERSION has already stopped startup.
         let client = Client::builder()
             .danger_accept_invalid_certs(true)
+            .min_tls_version(TlsMinVersion::from_env().unwrap_or_default().to_reqwest())
             .build()
             .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
 
+        let credentials = SecretString::from(format!("{}:{}", username, password.expose()));
+        let auth_header = SecretString::from(format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials.expose())
+        ));
+
         info!("iDRAC client initialized for host: {}", base_url);
         
         Ok(IdracClient {
+            client: RateLimitedIdracClient::new(&base_url, client, RateLimit::from_env()),
             base_url,
-            username,
-            password,
-            client,
+            auth_header: Arc::new(auth_header),
+            bios_registry: Arc::new(RwLock::new(None)),
+            select_supported: Arc::new(RwLock::new(None)),
+            payload_counters: Arc::new(Mutex::new(BTreeMap::new())),
+            hostname_cache: Arc::new(Mutex::new(None)),
         })
     }
 
-    fn get_auth_header(&self) -> String {
-        let credentials = format!("{}:{}", self.username, self.password);
-        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials.as_bytes());
-        format!("Basic {}", encoded)
+    pub fn base_url(&self) -> &str {
+        &self.base_url
     }
 
-    pub async fn get_power_state(&self) -> Result<String, String> {
-        let url = format!(
-            "{}/redfish/v1/Systems/System.Embedded.1",
-            self.base_url
-        );
+    /// When the iDRAC rejected the credentials, if requests to it are
+    /// paused because of that.
+    pub fn credentials_rejected_at(&self) -> Option<String> {
+        self.client.credentials_rejected_at()
+    }
 
-        let response = self.client
+    /// DNS name of `host` (a name, IP or URL) from a reverse lookup of the
+    /// address it resolves to. `None` when there is no PTR record.
+    pub async fn resolve_hostname(host: &str) -> Option<String> {
+        let url = reqwest::Url::parse(&normalize_host(host).ok()?).ok()?;
+        let host = url.host_str()?.trim_start_matches('[').trim_end_matches(']');
+        let address = match host.parse::<IpAddr>() {
+            Ok(address) => address,
+            Err(_) => {
+                let port = url.port_or_known_default().unwrap_or(443);
+                tokio::net::lookup_host((host, port)).await.ok()?.next()?.ip()
+            }
+        };
+
+        let name = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&address))
+            .await
+            .ok()?
+            .ok()?;
+        // getnameinfo falls back to the numeric address.
+        if name.parse::<IpAddr>().is_ok() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+
+    /// `resolve_hostname` for this iDRAC, cached for five minutes.
+    pub async fn hostname(&self) -> Option<String> {
+        if let Some(cached) = self.hostname_cache.lock().unwrap().clone() {
+            if cached.resolved_at.elapsed() < HOSTNAME_CACHE_TTL {
+                return cached.hostname;
+            }
+        }
+
+        let hostname = tokio::time::timeout(HOSTNAME_LOOKUP_TIMEOUT, Self::resolve_hostname(&self.base_url))
+            .await
+            .unwrap_or(None);
+        *self.hostname_cache.lock().unwrap() = Some(CachedHostname {
+            resolved_at: Instant::now(),
+            hostname: hostname.clone(),
+        });
+        hostname
+    }
+
+    /// The header value is marked sensitive so reqwest and hyper keep it
+    /// out of their debug output.
+    fn get_auth_header(&self) -> HeaderValue {
+        let mut value = HeaderValue::from_str(self.auth_header.expose()).expect("base64 is a valid header value");
+        value.set_sensitive(true);
+        value
+    }
+
+    async fn send_get(&self, path: &str) -> Result<reqwest::Response, String> {
+        let url = format!("{}{}", self.base_url, path);
+
+        let request = self.client
             .get(&url)
             .header("Authorization", self.get_auth_header())
-            .header("Content-Type", "application/json")
-            .send()
+            .header("Content-Type", "application/json");
+        self.client.send(request).await
+    }
+
+    /// Parse a successful response, counting its size against `path`.
+    async fn read_json(
+        &self,
+        path: &str,
+        selected: bool,
+        response: reqwest::Response,
+    ) -> Result<serde_json::Value, String> {
+        let content_type = response_content_type(&response);
+        let body = response
+            .bytes()
             .await
-            .map_err(|e| format!("Failed to connect to iDRAC: {}", e))?;
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+
+        let mut counters = self.payload_counters.lock().unwrap();
+        let counter = counters.entry(path.to_string()).or_default();
+        if selected {
+            counter.selected_requests += 1;
+            counter.selected_bytes += body.len() as u64;
+        } else {
+            counter.full_requests += 1;
+            counter.full_bytes += body.len() as u64;
+        }
+        drop(counters);
+
+        decode_json(content_type.as_deref(), &body)
+    }
+
+    /// GET a Redfish resource by path (e.g. `/redfish/v1/Managers/iDRAC.Embedded.1`).
+    async fn get_json(&self, path: &str) -> Result<serde_json::Value, String> {
+        let response = self.send_get(path).await?;
 
         if response.status() == StatusCode::OK {
-            let data: serde_json::Value = response.json().await
-                .map_err(|e| format!("Failed to parse response: {}", e))?;
-            
-            let power_state = data["PowerState"]
-                .as_str()
-                .unwrap_or("Unknown")
-                .to_string();
-            
-            info!("Current power state: {}", power_state);
-            Ok(power_state)
+            self.read_json(path, false, response).await
         } else {
-            let error_msg = format!("Failed to get power state: HTTP {}", response.status());
+            let error_msg = format!("Failed to get {}: HTTP {}", path, response.status());
             error!("{}", error_msg);
             Err(error_msg)
         }
     }
 
-    pub async fn power_on(&self) -> Result<String, String> {
-        self.set_power_state("On").await
+    /// GET only `properties` (comma-separated) of a resource when the iDRAC
+    /// supports `$select`, or the whole resource when it does not. An iDRAC
+    /// that rejects the query with 400 or 501 is not asked again. The first
+    /// request for each resource is made in full so `payload_stats` has a
+    /// size to compare against.
+    async fn get_selected(&self, path: &str, properties: &str) -> Result<serde_json::Value, String> {
+        let has_baseline = self
+            .payload_counters
+            .lock()
+            .unwrap()
+            .get(path)
+            .is_some_and(|counter| counter.full_requests > 0);
+        if has_baseline && self.supports_select().await {
+            let response = self.send_get(&format!("{}?$select={}", path, properties)).await?;
+            match response.status() {
+                StatusCode::OK => return self.read_json(path, true, response).await,
+                StatusCode::BAD_REQUEST | StatusCode::NOT_IMPLEMENTED => {
+                    warn!(
+                        "{} rejected $select on {} (HTTP {}); fetching full resources from now on",
+                        self.base_url,
+                        path,
+                        response.status()
+                    );
+                    *self.select_supported.write().unwrap() = Some(false);
+                }
+                status => {
+                    let error_msg = format!("Failed to get {}: HTTP {}", path, status);
+                    error!("{}", error_msg);
+                    return Err(error_msg);
+                }
+            }
+        }
+        self.get_json(path).await
     }
 
-    pub async fn power_off(&self) -> Result<String, String> {
-        self.set_power_state("ForceOff").await
+    /// Whether the service root advertises `$select`. A failed check is
+    /// treated as unsupported and retried on the next call.
+    async fn supports_select(&self) -> bool {
+        if let Some(supported) = *self.select_supported.read().unwrap() {
+            return supported;
+        }
+        match self.get_json("/redfish/v1").await {
+            Ok(root) => {
+                let supported = root["ProtocolFeaturesSupported"]["SelectQuery"].as_bool().unwrap_or(false);
+                info!("{} $select support: {}", self.base_url, supported);
+                *self.select_supported.write().unwrap() = Some(supported);
+                supported
+            }
+            Err(_) => false,
+        }
     }
 
-    pub async fn graceful_shutdown(&self) -> Result<String, String> {
-        self.set_power_state("GracefulShutdown").await
+    /// `$select` support as last determined, if it has been checked.
+    pub fn select_supported(&self) -> Option<bool> {
+        *self.select_supported.read().unwrap()
     }
 
-    async fn set_power_state(&self, reset_type: &str) -> Result<String, String> {
-        let url = format!(
-            "{}/redfish/v1/Systems/System.Embedded.1/Actions/ComputerSystem.Reset",
-            self.base_url
-        );
+    /// Response sizes per resource since the client was created.
+    pub fn payload_stats(&self) -> Vec<PayloadStats> {
+        let average = |bytes: u64, requests: u64| (requests > 0).then(|| bytes / requests);
+        self.payload_counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, counter)| {
+                let average_full_bytes = average(counter.full_bytes, counter.full_requests);
+                let average_selected_bytes = average(counter.selected_bytes, counter.selected_requests);
+                let reduction = match (average_full_bytes, average_selected_bytes) {
+                    (Some(full), Some(selected)) if full > 0 => Some(1.0 - selected as f64 / full as f64),
+                    _ => None,
+                };
+                PayloadStats {
+                    path: path.clone(),
+                    full_requests: counter.full_requests,
+                    average_full_bytes,
+                    selected_requests: counter.selected_requests,
+                    average_selected_bytes,
+                    reduction,
+                }
+            })
+            .collect()
+    }
+
+    pub async fn get_system_info(&self) -> Result<SystemInfo, String> {
+        let data = self.get_json("/redfish/v1/Systems/System.Embedded.1").await?;
+        let text = |value: &serde_json::Value| value.as_str().unwrap_or("Unknown").to_string();
+
+        Ok(SystemInfo {
+            hostname: text(&data["HostName"]),
+            model: text(&data["Model"]),
+            service_tag: text(&data["SKU"]),
+            power_state: text(&data["PowerState"]),
+            health: text(&data["Status"]["HealthRollup"]),
+            bios_version: text(&data["BiosVersion"]),
+            cpu_count: data["ProcessorSummary"]["Count"].as_u64().unwrap_or(0),
+            cpu_model: text(&data["ProcessorSummary"]["Model"]),
+            memory_gib: data["MemorySummary"]["TotalSystemMemoryGiB"].as_f64().unwrap_or(0.0),
+            last_boot_time: data["LastResetTime"]
+                .as_str()
+                .or_else(|| data["Oem"]["Dell"]["DellSystem"]["LastSystemInventoryTime"].as_str())
+                .map(|t| t.to_string()),
+            component_health: parse_component_health(&data["Oem"]["Dell"]["DellSystem"]),
+        })
+    }
+
+    /// Like `get_system_info`, but fetches only the health properties.
+    pub async fn get_health(&self) -> Result<SystemHealth, String> {
+        let data = self.get_selected("/redfish/v1/Systems/System.Embedded.1", "Status,Oem").await?;
+        Ok(SystemHealth {
+            health: data["Status"]["HealthRollup"].as_str().unwrap_or("Unknown").to_string(),
+            component_health: parse_component_health(&data["Oem"]["Dell"]["DellSystem"]),
+        })
+    }
+
+    pub async fn get_idrac_firmware_version(&self) -> Result<String, String> {
+        let data = self.get_json("/redfish/v1/Managers/iDRAC.Embedded.1").await?;
+        Ok(data["FirmwareVersion"].as_str().unwrap_or("Unknown").to_string())
+    }
+
+    /// Installed firmware versions. Only `Installed-*` entries are kept;
+    /// `Previous-*` and `Available-*` describe rollback and staged images.
+    pub async fn get_firmware_inventory(&self) -> Result<Vec<FirmwareComponent>, String> {
+        let collection = self
+            .get_json("/redfish/v1/UpdateService/FirmwareInventory?$expand=*($levels=1)")
+            .await?;
+
+        let mut components = Vec::new();
+        for member in collection["Members"].as_array().into_iter().flatten() {
+            let Some(path) = member["@odata.id"].as_str() else {
+                continue;
+            };
+            if !path.rsplit('/').next().is_some_and(|id| id.starts_with("Installed-")) {
+                continue;
+            }
+            // Older firmware ignores $expand and returns bare links.
+            let item = if member.get("Version").is_some() {
+                member.clone()
+            } else {
+                self.get_json(path).await?
+            };
+            if let (Some(name), Some(version)) = (item["Name"].as_str(), item["Version"].as_str()) {
+                components.push(FirmwareComponent {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                });
+            }
+        }
+        Ok(components)
+    }
+
+    /// The iDRAC's current clock as reported in the manager's `DateTime` property.
+    pub async fn get_system_time(&self) -> Result<chrono::DateTime<chrono::Utc>, String> {
+        let data = self.get_json("/redfish/v1/Managers/iDRAC.Embedded.1").await?;
+        let raw = data["DateTime"]
+            .as_str()
+            .ok_or_else(|| "iDRAC did not report a DateTime".to_string())?;
+        chrono::DateTime::parse_from_rfc3339(raw)
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .map_err(|e| format!("Invalid iDRAC DateTime '{}': {}", raw, e))
+    }
 
+    /// Set the iDRAC's clock. Its `DateTimeLocalOffset` is left alone.
+    pub async fn set_system_time(&self, time: chrono::DateTime<chrono::Utc>) -> Result<(), String> {
+        let url = format!("{}/redfish/v1/Managers/iDRAC.Embedded.1", self.base_url);
+        // iDRACs take whole seconds; round rather than truncate.
+        let rounded = time + chrono::Duration::milliseconds(500);
         let payload = serde_json::json!({
-            "ResetType": reset_type
+            "DateTime": rounded.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
         });
 
-        info!("Sending power command: {}", reset_type);
-
-        let response = self.client
-            .post(&url)
+        let request = self.client
+            .patch(&url)
             .header("Authorization", self.get_auth_header())
             .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to connect to iDRAC: {}", e))?;
+            .json(&payload);
+        let response = self.client.send(request).await?;
 
-        if response.status() == StatusCode::NO_CONTENT || response.status() == StatusCode::OK {
-            let success_msg = format!("Successfully executed: {}", reset_type);
-            info!("{}", success_msg);
-            Ok(success_msg)
+        if response.status().is_success() {
+            Ok(())
         } else {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            let error_msg = format!("Failed to set power state: HTTP {} - {}", status, error_text);
+            let error_msg = format!("Failed to set iDRAC time: HTTP {} - {}", status, error_text);
             error!("{}", error_msg);
             Err(error_msg)
         }
     }
+
+    /// Count physical drives across all storage controllers.
+    pub async fn get_disk_count(&self) -> Result<u64, String> {
+        Ok(self.get_storage_controllers().await?.iter().map(|controller| controller.drives).sum())
+    }
+
+    /// Every storage controller of the system, read one by one.
+    pub async fn get_storage_controllers(&self) -> Result<Vec<StorageController>, String> {
+        let storage = self.get_json("/redfish/v1/Systems/System.Embedded.1/Storage").await?;
+
+        let mut controllers = Vec::new();
+        for member in storage["Members"].as_array().into_iter().flatten() {
+            if let Some(path) = member["@odata.id"].as_str() {
+                let controller = self.get_json(path).await?;
+                controllers.push(StorageController {
+                    name: controller["Name"]
+                        .as_str()
+                        .or_else(|| controller["Id"].as_str())
+                        .unwrap_or(path)
+                        .to_string(),
+                    health: controller["Status"]["HealthRollup"]
+                        .as_str()
+                        .or_else(|| controller["Status"]["Health"].as_str())
+                        .map(str::to_string),
+                    drives: controller["Drives@odata.count"]
+                        .as_u64()
+                        .or_else(|| controller["Drives"].as_array().map(|d| d.len() as u64))
+                        .unwrap_or(0),
+                });
+            }
+        }
+
+        Ok(controllers)
+    }
+
+    /// Every temperature sensor of the chassis.
+    pub async fn get_temperatures(&self) -> Result<Vec<TemperatureReading>, String> {
+        let data = self.get_json("/redfish/v1/Chassis/System.Embedded.1/Thermal").await?;
+        let sensors = data["Temperatures"].as_array().cloned().unwrap_or_default();
+        Ok(sensors
+            .iter()
+            .enumerate()
+            .map(|(i, sensor)| TemperatureReading {
+                name: sensor["Name"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("Sensor {}", i + 1)),
+                reading_celsius: sensor["ReadingCelsius"].as_f64(),
+                upper_threshold_critical: sensor["UpperThresholdCritical"].as_f64(),
+                health: sensor["Status"]["Health"].as_str().map(str::to_string),
+            })
+            .collect())
+    }
+
+    pub async fn get_ssl_certificate_info(&self) -> Result<SslCertInfo, String> {
+        let collection = self
+            .get_json("/redfish/v1/Managers/iDRAC.Embedded.1/NetworkProtocol/HTTPS/Certificates")
+            .await?;
+
+        let path = collection["Members"]
+            .as_array()
+            .and_then(|members| members.first())
+            .and_then(|member| member["@odata.id"].as_str())
+            .ok_or_else(|| "iDRAC reported no HTTPS certificate".to_string())?;
+
+        let cert = self.get_json(path).await?;
+        let text = |value: &serde_json::Value| value.as_str().unwrap_or("Unknown").to_string();
+
+        Ok(SslCertInfo {
+            subject: format_certificate_identifier(&cert["Subject"]),
+            issuer: format_certificate_identifier(&cert["Issuer"]),
+            valid_not_before: text(&cert["ValidNotBefore"]),
+            valid_not_after: text(&cert["ValidNotAfter"]),
+            signature_algorithm: text(&cert["SignatureAlgorithm"]),
+        })
+    }
+
+    pub async fn get_power_state(&self) -> Result<String, String> {
+        let data = self
+            .get_selected("/redfish/v1/Systems/System.Embedded.1", "PowerState")
+            .await?;
+
+        let power_state = data["PowerState"]
+            .as_str()
+            .unwrap_or("Unknown")
+            .to_string();
+
+        info!("Current power state: {}", power_state);
+        Ok(power_state)
+    }
+
+    /// Why the host last changed power state. Older iDRACs do not report
+    /// it.
+    pub async fn get_last_power_reason(&self) -> Result<PowerChangeReason, String> {
+        let data = self.get_selected("/redfish/v1/Systems/System.Embedded.1", "Oem").await?;
+        data["Oem"]["Dell"]["DellSystem"]["LastPowerChangeReasonCode"]
+            .as_str()
+            .map(PowerChangeReason::from_code)
+            .ok_or_else(|| "The iDRAC does not report LastPowerChangeReasonCode".to_string())
+    }
+
+    /// `BootProgress.LastState` of the host, e.g. `MemoryInitializationStarted`
+    /// or `OSRunning`. `None` on iDRACs that do not report boot progress.
+    pub async fn get_boot_progress(&self) -> Result<Option<String>, String> {
+        let data = self
+            .get_selected("/redfish/v1/Systems/System.Embedded.1", "BootProgress")
+            .await?;
+        Ok(data["BootProgress"]["LastState"].as_str().map(str::to_string))
+    }
+
+    /// System Event Log entries, newest first.
+    pub async fn get_sel_entries(&self) -> Result<Vec<SelEntry>, String> {
+        self.get_log_entries("/redfish/v1/Managers/iDRAC.Embedded.1/LogServices/Sel/Entries", "SEL")
+            .await
+    }
+
+    /// Lifecycle Controller log entries, newest first. The LC log also
+    /// records the events logged to the SEL.
+    pub async fn get_lc_entries(&self) -> Result<Vec<SelEntry>, String> {
+        self.get_log_entries("/redfish/v1/Managers/iDRAC.Embedded.1/LogServices/Lclog/Entries", "LC log")
+            .await
+    }
+
+    async fn get_log_entries(&self, path: &str, name: &str) -> Result<Vec<SelEntry>, String> {
+        let data = self.get_json(path).await?;
+        let members = data["Members"]
+            .as_array()
+            .ok_or_else(|| format!("{} has no Members", name))?;
+        Ok(members
+            .iter()
+            .map(|entry| SelEntry {
+                id: entry["Id"].as_str().unwrap_or_default().to_string(),
+                uri: entry["@odata.id"].as_str().unwrap_or_default().to_string(),
+                created: entry["Created"].as_str().unwrap_or_default().to_string(),
+                severity: entry["Severity"].as_str().unwrap_or("Unknown").to_string(),
+                message: entry["Message"].as_str().unwrap_or_default().to_string(),
+                message_id: entry["MessageId"].as_str().map(str::to_string),
+            })
+            .collect())
+    }
+
+    /// Cheapest authenticated request, used to keep the connection and the
+    /// iDRAC session from idling out.
+    pub async fn keepalive(&self) -> Result<(), String> {
+        self.get_json("/redfish/v1").await.map(|_| ())
+    }
+
+    /// Tries the iDRAC even while its credentials are marked rejected, and
+    /// lifts or sets that mark by the outcome.
+    pub async fn test_connection(&self) -> Result<String, String> {
+        let url = format!("{}/redfish/v1", self.base_url);
+
+        self.client.acquire().await?;
+        let response = self.client
+            .get(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to iDRAC at {}: {}", self.base_url, e))?;
+
+        if response.status() == StatusCode::OK {
+            let data: serde_json::Value = read_body(response).await?;
+
+            let version = data["RedfishVersion"]
+                .as_str()
+                .unwrap_or("Unknown")
+                .to_string();
+
+            info!("Connection test to {} succeeded (Redfish {})", self.base_url, version);
+            self.client.clear_credentials_rejected();
+            Ok(version)
+        } else {
+            if response.status() == StatusCode::UNAUTHORIZED {
+                self.client.mark_credentials_rejected();
+            }
+            let error_msg = format!("Connection test to {} failed: HTTP {}", self.base_url, response.status());
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    pub async fn get_service_module_status(&self) -> Result<ServiceModuleStatus, String> {
+        let url = format!(
+            "{}/redfish/v1/Systems/System.Embedded.1/Oem/Dell/DellServiceModuleInventory",
+            self.base_url
+        );
+
+        let request = self.client
+            .get(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json");
+        let response = self.client.send(request).await?;
+
+        // Firmware without iSM support has no inventory resource at all
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(ServiceModuleStatus {
+                installed: false,
+                version: String::new(),
+                status: "NotInstalled".to_string(),
+            });
+        }
+
+        if response.status() != StatusCode::OK {
+            let error_msg = format!("Failed to get service module status: HTTP {}", response.status());
+            error!("{}", error_msg);
+            return Err(error_msg);
+        }
+
+        let data: serde_json::Value = read_body(response).await?;
+
+        let module = match data["Members"].as_array().and_then(|m| m.first()) {
+            Some(module) => module,
+            None => {
+                return Ok(ServiceModuleStatus {
+                    installed: false,
+                    version: String::new(),
+                    status: "NotInstalled".to_string(),
+                });
+            }
+        };
+
+        let version = module["ServiceModuleVersion"]
+            .as_str()
+            .or_else(|| module["Version"].as_str())
+            .unwrap_or("Unknown")
+            .to_string();
+        let status = module["ServiceModuleState"]
+            .as_str()
+            .or_else(|| module["Status"]["State"].as_str())
+            .unwrap_or("Unknown")
+            .to_string();
+
+        info!("iDRAC Service Module {} ({})", version, status);
+        Ok(ServiceModuleStatus {
+            installed: true,
+            version,
+            status,
+        })
+    }
+
+    pub async fn power_on(&self) -> Result<String, String> {
+        self.set_power_state("On").await
+    }
+
+    pub async fn power_off(&self) -> Result<String, String> {
+        self.set_power_state("ForceOff").await
+    }
+
+    pub async fn graceful_shutdown(&self) -> Result<String, String> {
+        self.set_power_state("GracefulShutdown").await
+    }
+
+    pub async fn force_restart(&self) -> Result<String, String> {
+        self.set_power_state("ForceRestart").await
+    }
+
+    /// Cut power and restore it, for a node too hung to restart.
+    pub async fn power_cycle(&self) -> Result<String, String> {
+        self.set_power_state("PowerCycle").await
+    }
+
+    /// Ask the OS to restart; the server is never reset under it.
+    pub async fn graceful_restart(&self) -> Result<String, String> {
+        self.set_power_state("GracefulRestart").await
+    }
+
+    /// Raise a diagnostic interrupt, which a hung OS usually answers with a
+    /// crash dump.
+    pub async fn send_nmi(&self) -> Result<String, String> {
+        self.set_power_state("Nmi").await
+    }
+
+    pub async fn configure_alert_filters(&self, filters: Vec<AlertFilter>) -> Result<(), String> {
+        if filters.is_empty() {
+            return Err("At least one alert filter is required".to_string());
+        }
+
+        let mut attributes = serde_json::Map::new();
+        for filter in &filters {
+            let value = if filter.enabled { "Enabled" } else { "Disabled" };
+            attributes.insert(filter.attribute_name()?, serde_json::Value::from(value));
+        }
+
+        info!("Configuring {} iDRAC alert filter(s)", attributes.len());
+        self.patch_idrac_attributes(serde_json::Value::Object(attributes)).await
+    }
+
+    /// Ask the iDRAC to push Redfish events to `destination`, tagging each
+    /// delivery with `context`. Returns the URI of the new subscription.
+    /// Subscribe `destination` to events, or with `event_format`
+    /// `MetricReport` to telemetry metric reports.
+    pub async fn create_event_subscription(
+        &self,
+        destination: &str,
+        context: &str,
+        event_format: &str,
+    ) -> Result<String, String> {
+        let url = format!(
+            "{}/redfish/v1/EventService/Subscriptions",
+            self.base_url
+        );
+
+        let payload = serde_json::json!({
+            "Destination": destination,
+            "EventFormatType": event_format,
+            "Protocol": "Redfish",
+            "Context": context,
+        });
+
+        info!("Creating Redfish event subscription to {}", destination);
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            let location = response
+                .headers()
+                .get("Location")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            info!("Event subscription created: {}", location);
+            Ok(location)
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to create event subscription: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// Define a periodic telemetry report of `metrics` that the iDRAC pushes
+    /// to metric report subscriptions. Returns the definition's id.
+    pub async fn create_metric_report_definition(
+        &self,
+        metrics: &[&str],
+        report_interval_seconds: u32,
+    ) -> Result<String, String> {
+        let url = format!("{}/redfish/v1/TelemetryService/MetricReportDefinitions", self.base_url);
+        let id = format!("IdracController{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+        let payload = serde_json::json!({
+            "Id": id,
+            "MetricReportDefinitionType": "Periodic",
+            "MetricReportDefinitionEnabled": true,
+            "ReportActions": ["RedfishEvent"],
+            "ReportUpdates": "Overwrite",
+            "Schedule": { "RecurrenceInterval": format!("PT{}S", report_interval_seconds) },
+            "Metrics": metrics.iter().map(|metric| serde_json::json!({ "MetricId": metric })).collect::<Vec<_>>(),
+        });
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            let id = response
+                .headers()
+                .get("Location")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|location| location.trim_end_matches('/').rsplit('/').next())
+                .filter(|segment| !segment.is_empty())
+                .map(|segment| segment.to_string())
+                .unwrap_or(id);
+            info!("Metric report definition {} created on {}", id, self.base_url);
+            Ok(id)
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to create metric report definition: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    pub async fn delete_metric_report_definition(&self, id: &str) -> Result<(), String> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+            return Err(format!("Invalid metric report definition id '{}'", id));
+        }
+        let url = format!("{}/redfish/v1/TelemetryService/MetricReportDefinitions/{}", self.base_url, id);
+
+        let request = self.client
+            .delete(&url)
+            .header("Authorization", self.get_auth_header());
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            info!("Metric report definition {} deleted on {}", id, self.base_url);
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to delete metric report definition: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// Fetch the BIOS attribute registry, caching it for the lifetime of the client.
+    pub async fn get_bios_registry(&self) -> Result<Arc<BiosRegistry>, String> {
+        if let Some(registry) = self.bios_registry.read().unwrap().as_ref() {
+            return Ok(registry.clone());
+        }
+
+        let url = format!(
+            "{}/redfish/v1/Registries/BiosAttributeRegistry.json",
+            self.base_url
+        );
+
+        let request = self.client
+            .get(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json");
+        let response = self.client.send(request).await?;
+
+        if response.status() != StatusCode::OK {
+            let error_msg = format!("Failed to get BIOS attribute registry: HTTP {}", response.status());
+            error!("{}", error_msg);
+            return Err(error_msg);
+        }
+
+        let data: serde_json::Value = read_body(response).await?;
+        let registry = Arc::new(BiosRegistry::from_json(&data)?);
+
+        info!("Cached BIOS attribute registry ({} attributes)", registry.attributes.len());
+        *self.bios_registry.write().unwrap() = Some(registry.clone());
+        Ok(registry)
+    }
+
+    /// Stage a BIOS attribute change. The value is validated against the
+    /// attribute registry first; the change applies on the next reboot.
+    pub async fn set_bios_attribute(&self, name: &str, value: serde_json::Value) -> Result<String, String> {
+        self.get_bios_registry().await?.validate(name, &value)?;
+
+        // Only the name: values such as SysPassword are secrets.
+        info!("Staging BIOS attribute {} on {}", name, self.base_url);
+        self.stage_bios_attributes(serde_json::json!({ name: value })).await?;
+
+        let success_msg = format!("BIOS attribute {} staged; it will apply on next reboot", name);
+        info!("{}", success_msg);
+        Ok(success_msg)
+    }
+
+    /// Stage several BIOS attribute changes in one request, after checking
+    /// every one against the attribute registry.
+    pub async fn set_bios_attributes(&self, attributes: &serde_json::Map<String, serde_json::Value>) -> Result<String, String> {
+        let registry = self.get_bios_registry().await?;
+        for (name, value) in attributes {
+            registry.validate(name, value)?;
+        }
+
+        info!("Staging {} BIOS attribute(s) on {}", attributes.len(), self.base_url);
+        self.stage_bios_attributes(serde_json::Value::Object(attributes.clone())).await?;
+        Ok(format!("{} BIOS attribute(s) staged; they will apply on next reboot", attributes.len()))
+    }
+
+    /// PATCH `attributes` (an object of name to value) into the pending
+    /// BIOS settings in a single request.
+    async fn stage_bios_attributes(&self, attributes: serde_json::Value) -> Result<(), String> {
+        let url = format!(
+            "{}/redfish/v1/Systems/System.Embedded.1/Bios/Settings",
+            self.base_url
+        );
+
+        let payload = serde_json::json!({
+            "Attributes": attributes
+        });
+
+        let request = self.client
+            .patch(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to set BIOS attribute: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// Stage a firmware update from `image_uri`: a URL the iDRAC downloads
+    /// the package from, or the path of a package already uploaded to its
+    /// `FirmwareInventory`. Returns the id of the update job, which runs
+    /// on the next reboot.
+    pub async fn start_firmware_update(&self, image_uri: &str) -> Result<String, String> {
+        let url = format!("{}/redfish/v1/UpdateService/Actions/UpdateService.SimpleUpdate", self.base_url);
+
+        let payload = serde_json::json!({
+            "ImageURI": image_uri,
+            "@Redfish.OperationApplyTime": "OnReset",
+        });
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            let job_id = staged_job_id(&response)
+                .ok_or_else(|| "Firmware update was accepted but the iDRAC returned no job".to_string())?;
+            info!("Firmware update from {} staged on {} as job {}", image_uri, self.base_url, job_id);
+            Ok(job_id)
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to start firmware update: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// State of an iDRAC job, such as one from `start_firmware_update`.
+    pub async fn get_job(&self, job_id: &str) -> Result<JobStatus, String> {
+        let job = self
+            .get_json(&format!("/redfish/v1/Managers/iDRAC.Embedded.1/Jobs/{}", job_id))
+            .await?;
+        Ok(JobStatus::from_json(job_id, &job))
+    }
+
+    /// Every job in the iDRAC's job queue.
+    pub async fn list_jobs(&self) -> Result<Vec<JobStatus>, String> {
+        let collection = self
+            .get_json("/redfish/v1/Managers/iDRAC.Embedded.1/Jobs?$expand=*($levels=1)")
+            .await?;
+
+        let mut jobs = Vec::new();
+        for member in collection["Members"].as_array().into_iter().flatten() {
+            let Some(path) = member["@odata.id"].as_str() else {
+                continue;
+            };
+            let job_id = path.rsplit('/').next().unwrap_or_default();
+            // Older firmware ignores $expand and returns bare links.
+            if member.get("JobState").is_some() {
+                jobs.push(JobStatus::from_json(job_id, member));
+            } else {
+                jobs.push(JobStatus::from_json(job_id, &self.get_json(path).await?));
+            }
+        }
+        Ok(jobs)
+    }
+
+    /// Restore every BIOS setting to its default on the next reboot.
+    /// Returns the id of the job the iDRAC created for it.
+    pub async fn reset_bios_to_defaults(&self) -> Result<String, String> {
+        let url = format!(
+            "{}/redfish/v1/Systems/System.Embedded.1/Bios/Actions/Bios.ResetBios",
+            self.base_url
+        );
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&serde_json::json!({}));
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            let job_id = staged_job_id(&response)
+                .ok_or_else(|| "BIOS reset was accepted but the iDRAC returned no job".to_string())?;
+            warn!("BIOS reset to defaults staged on {} as job {}", self.base_url, job_id);
+            Ok(job_id)
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to reset BIOS: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// The applied system profile and any change staged for the next reboot.
+    pub async fn get_system_profile(&self) -> Result<SystemProfile, String> {
+        let bios = self.get_json("/redfish/v1/Systems/System.Embedded.1/Bios").await?;
+        let attribute_value = bios["Attributes"]["SysProfile"]
+            .as_str()
+            .ok_or_else(|| "BIOS did not report a SysProfile attribute".to_string())?
+            .to_string();
+
+        let settings = self.get_json("/redfish/v1/Systems/System.Embedded.1/Bios/Settings").await?;
+        let pending = settings["Attributes"]["SysProfile"]
+            .as_str()
+            .filter(|staged| *staged != attribute_value)
+            .and_then(ProfileType::from_attribute_value);
+
+        Ok(SystemProfile {
+            profile: ProfileType::from_attribute_value(&attribute_value),
+            attribute_value,
+            pending,
+        })
+    }
+
+    /// Stage a system profile change; it applies on the next reboot.
+    pub async fn set_system_profile(&self, profile: ProfileType) -> Result<String, String> {
+        info!("Staging system profile {:?} on {}", profile, self.base_url);
+        self.stage_bios_attributes(serde_json::json!({ "SysProfile": profile.attribute_value() })).await?;
+        Ok(format!("System profile {:?} staged; it will apply on next reboot", profile))
+    }
+
+    /// Enable or disable the BIOS POST watchdog, which reboots a server
+    /// that has not finished POST within `timeout_minutes`. Both attributes
+    /// are checked against the BIOS attribute registry, so platforms
+    /// without a POST watchdog are refused before anything is sent.
+    pub async fn configure_post_watchdog(&self, enabled: bool, timeout_minutes: u32) -> Result<(), String> {
+        let attributes = post_watchdog_attributes(enabled, timeout_minutes);
+        let registry = self.get_bios_registry().await?;
+        for (name, value) in &attributes {
+            registry.validate(name, value)?;
+        }
+
+        info!(
+            "Staging POST watchdog {} ({} minute timeout) on {}",
+            if enabled { "enabled" } else { "disabled" },
+            timeout_minutes,
+            self.base_url
+        );
+        self.stage_bios_attributes(serde_json::Value::Object(
+            attributes.into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+        ))
+        .await
+    }
+
+    /// Which port the iDRAC's management traffic currently uses.
+    pub async fn get_nic_selection(&self) -> Result<NicSelection, String> {
+        let attributes = self.get_json("/redfish/v1/Managers/iDRAC.Embedded.1/Attributes").await?;
+        let selection = attributes["Attributes"]["NIC.1.Selection"]
+            .as_str()
+            .ok_or_else(|| "iDRAC did not report a NIC.1.Selection attribute".to_string())?
+            .to_string();
+        let failover = attributes["Attributes"]["NIC.1.Failover"].as_str().map(str::to_string);
+
+        Ok(NicSelection {
+            mode: NicMode::from_attributes(&selection, failover.as_deref()),
+            selection,
+            failover,
+        })
+    }
+
+    /// Move the iDRAC's management traffic to another port. It applies
+    /// immediately, so the iDRAC drops off the network until the new port
+    /// is cabled and has an address.
+    pub async fn set_nic_selection(&self, mode: NicMode) -> Result<(), String> {
+        warn!("Switching iDRAC NIC mode on {} to {:?}", self.base_url, mode);
+        self.patch_idrac_attributes(mode.attributes()).await
+    }
+
+    /// PATCH Dell OEM attributes on the iDRAC manager resource.
+    /// Import a license through the Dell license management service.
+    /// `license_key` is the license file, either as XML or already base64
+    /// encoded.
+    pub async fn activate_license(&self, license_key: &str) -> Result<(), String> {
+        let url = format!(
+            "{}/redfish/v1/Managers/iDRAC.Embedded.1/Oem/Dell/DellLicenseManagementService/Actions/DellLicenseManagementService.ImportLicense",
+            self.base_url
+        );
+
+        let license_key = license_key.trim();
+        let license_file = if license_key.starts_with('<') {
+            base64::engine::general_purpose::STANDARD.encode(license_key.as_bytes())
+        } else {
+            base64::engine::general_purpose::STANDARD
+                .decode(license_key)
+                .map_err(|_| "License key must be the license XML or its base64 encoding".to_string())?;
+            license_key.to_string()
+        };
+
+        let payload = serde_json::json!({
+            "FQDD": "iDRAC.Embedded.1",
+            "ImportOptions": "Force",
+            "LicenseFile": license_file,
+        });
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            info!("License imported on {}", self.base_url);
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to import license: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// POST `payload` to `path`, which `validate_oem_action_path` has
+    /// checked, and return the response whatever its status. Only a request
+    /// that got no response is an error.
+    pub async fn post_raw(&self, path: &str, payload: &serde_json::Value) -> Result<RawRedfishResponse, String> {
+        let url = format!("{}{}", self.base_url, path);
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(payload);
+        let response = self.client.send(request).await?;
+
+        let status = response.status().as_u16();
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let content_type = response_content_type(&response);
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+        let body = if body.iter().all(u8::is_ascii_whitespace) {
+            serde_json::Value::Null
+        } else {
+            decode_json(content_type.as_deref(), &body)
+                .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&body).into_owned()))
+        };
+        info!("OEM action {} on {} returned HTTP {}", path, self.base_url, status);
+        Ok(RawRedfishResponse { status, location, body })
+    }
+
+    /// Reset the iDRAC to factory defaults, including its users, network
+    /// settings and credentials. The iDRAC restarts and is unreachable for
+    /// several minutes; afterwards it only accepts the default credentials.
+    pub async fn factory_reset_idrac(&self) -> Result<String, String> {
+        let url = format!(
+            "{}/redfish/v1/Managers/iDRAC.Embedded.1/Actions/Oem/DellManager.ResetToDefaults",
+            self.base_url
+        );
+
+        let payload = serde_json::json!({ "ResetType": "All" });
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            warn!("Factory reset requested on {}", self.base_url);
+            Ok("iDRAC factory reset initiated".to_string())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to factory reset iDRAC: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// The persistent boot order with each entry resolved from the
+    /// `BootOptions` collection.
+    pub async fn get_boot_order(&self) -> Result<Vec<BootOption>, String> {
+        let system = self.get_json("/redfish/v1/Systems/System.Embedded.1").await?;
+        let order: Vec<String> = system["Boot"]["BootOrder"]
+            .as_array()
+            .ok_or_else(|| "iDRAC did not report a BootOrder".to_string())?
+            .iter()
+            .filter_map(|id| id.as_str().map(|s| s.to_string()))
+            .collect();
+
+        let mut options = HashMap::new();
+        if let Some(path) = system["Boot"]["BootOptions"]["@odata.id"].as_str() {
+            let collection = self.get_json(path).await?;
+            for member in collection["Members"].as_array().into_iter().flatten() {
+                if let Some(member_path) = member["@odata.id"].as_str() {
+                    let option = self.get_json(member_path).await?;
+                    if let Some(reference) = option["BootOptionReference"].as_str() {
+                        options.insert(reference.to_string(), option);
+                    }
+                }
+            }
+        }
+
+        Ok(order
+            .into_iter()
+            .map(|id| {
+                let option = options.get(&id);
+                BootOption {
+                    display_name: option
+                        .and_then(|o| o["DisplayName"].as_str())
+                        .unwrap_or("Unknown")
+                        .to_string(),
+                    device_path: option
+                        .and_then(|o| o["UefiDevicePath"].as_str())
+                        .map(|p| p.to_string()),
+                    enabled: option.and_then(|o| o["BootOptionEnabled"].as_bool()),
+                    id,
+                }
+            })
+            .collect())
+    }
+
+    /// Replace the persistent boot order. Returns the id of the job the
+    /// iDRAC staged when the change only applies after a reboot.
+    pub async fn set_boot_order(&self, ids: Vec<String>) -> Result<Option<String>, String> {
+        let url = format!("{}/redfish/v1/Systems/System.Embedded.1", self.base_url);
+
+        let payload = serde_json::json!({
+            "Boot": { "BootOrder": ids }
+        });
+
+        let request = self.client
+            .patch(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            let job_id = staged_job_id(&response);
+            info!("Boot order updated{}", job_id.as_ref().map(|j| format!(" (job {})", j)).unwrap_or_default());
+            Ok(job_id)
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to set boot order: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// Insert `image_url` (HTTP, HTTPS, NFS or CIFS) into the iDRAC's
+    /// virtual CD drive.
+    pub async fn mount_virtual_media(&self, image_url: &str) -> Result<(), String> {
+        let url = format!(
+            "{}/redfish/v1/Managers/iDRAC.Embedded.1/VirtualMedia/CD/Actions/VirtualMedia.InsertMedia",
+            self.base_url
+        );
+
+        let payload = serde_json::json!({
+            "Image": image_url,
+            "Inserted": true,
+            "WriteProtected": true,
+        });
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            info!("Virtual media {} inserted on {}", image_url, self.base_url);
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to mount virtual media: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// Boot from `target` instead of the boot order, for `mode`.
+    pub async fn set_boot_override(&self, target: BootTarget, mode: BootMode) -> Result<(), String> {
+        let url = format!("{}/redfish/v1/Systems/System.Embedded.1", self.base_url);
+
+        let payload = serde_json::json!({
+            "Boot": {
+                "BootSourceOverrideTarget": target,
+                "BootSourceOverrideEnabled": mode,
+            }
+        });
+
+        let request = self.client
+            .patch(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            info!("Boot override set to {:?} ({:?})", target, mode);
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to set boot override: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    pub async fn restart(&self, restart_type: RestartType) -> Result<String, String> {
+        self.set_power_state(restart_type.reset_type()).await
+    }
+
+    /// Current chassis draw as reported by the first `PowerControl` entry.
+    pub async fn get_power_consumed_watts(&self) -> Result<u32, String> {
+        let data = self.get_json("/redfish/v1/Chassis/System.Embedded.1/Power").await?;
+        data["PowerControl"][0]["PowerConsumedWatts"]
+            .as_f64()
+            .map(|watts| watts.round() as u32)
+            .ok_or_else(|| "iDRAC did not report PowerConsumedWatts".to_string())
+    }
+
+    /// Every power supply of the chassis.
+    pub async fn get_power_supplies(&self) -> Result<Vec<PowerSupply>, String> {
+        let data = self.get_json("/redfish/v1/Chassis/System.Embedded.1/Power").await?;
+        let supplies = data["PowerSupplies"].as_array().cloned().unwrap_or_default();
+        Ok(supplies
+            .iter()
+            .enumerate()
+            .map(|(i, supply)| PowerSupply {
+                name: supply["Name"]
+                    .as_str()
+                    .or_else(|| supply["MemberId"].as_str())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("PSU {}", i + 1)),
+                health: supply["Status"]["Health"].as_str().map(str::to_string),
+                state: supply["Status"]["State"].as_str().map(str::to_string),
+                line_input_voltage: supply["LineInputVoltage"].as_f64(),
+            })
+            .collect())
+    }
+
+    /// The chassis power limit; `None` when the server is not capped.
+    pub async fn get_power_cap(&self) -> Result<Option<u32>, String> {
+        let data = self.get_json("/redfish/v1/Chassis/System.Embedded.1/Power").await?;
+        Ok(data["PowerControl"][0]["PowerLimit"]["LimitInWatts"]
+            .as_f64()
+            .filter(|watts| *watts > 0.0)
+            .map(|watts| watts.round() as u32))
+    }
+
+    /// Set the chassis power limit, or remove it with `None`.
+    pub async fn set_power_cap(&self, watts: Option<u32>) -> Result<String, String> {
+        let url = format!("{}/redfish/v1/Chassis/System.Embedded.1/Power", self.base_url);
+
+        let payload = serde_json::json!({
+            "PowerControl": [{ "PowerLimit": { "LimitInWatts": watts } }]
+        });
+
+        let request = self.client
+            .patch(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            let message = match watts {
+                Some(watts) => format!("Power cap set to {} W", watts),
+                None => "Power cap removed".to_string(),
+            };
+            info!("{} on {}", message, self.base_url);
+            Ok(message)
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to set power cap: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// Every account slot, used or not, in slot order.
+    async fn get_account_slots(&self) -> Result<Vec<IdracUser>, String> {
+        let data = self
+            .get_json("/redfish/v1/AccountService/Accounts?$expand=*($levels=1)")
+            .await?;
+        let members = data["Members"]
+            .as_array()
+            .ok_or_else(|| "Account collection has no Members".to_string())?;
+
+        let mut accounts = Vec::with_capacity(members.len());
+        for member in members {
+            // Firmware without $expand only lists links.
+            let account = if member.get("Id").is_some() {
+                member.clone()
+            } else {
+                let path = member["@odata.id"]
+                    .as_str()
+                    .ok_or_else(|| "Account link has no @odata.id".to_string())?;
+                self.get_json(path).await?
+            };
+            let role_id = account["RoleId"].as_str().unwrap_or("None").to_string();
+            accounts.push(IdracUser {
+                id: account["Id"].as_str().unwrap_or_default().to_string(),
+                username: account["UserName"].as_str().unwrap_or_default().to_string(),
+                enabled: account["Enabled"].as_bool().unwrap_or(false),
+                privilege: IdracPrivilege::from_role_id(&role_id),
+                role_id,
+            });
+        }
+        accounts.sort_by_key(|account| account.id.parse::<u32>().unwrap_or(u32::MAX));
+        Ok(accounts)
+    }
+
+    /// Local accounts in use, in slot order.
+    pub async fn get_idrac_users(&self) -> Result<Vec<IdracUser>, String> {
+        let accounts = self.get_account_slots().await?;
+        Ok(accounts.into_iter().filter(|account| !account.username.is_empty()).collect())
+    }
+
+    /// Create an enabled account in the first free slot, returning it.
+    /// Slot 1 is reserved by the iDRAC and never used.
+    pub async fn create_idrac_user(
+        &self,
+        username: &str,
+        password: &str,
+        privilege: IdracPrivilege,
+    ) -> Result<IdracUser, String> {
+        let accounts = self.get_account_slots().await?;
+        if accounts.iter().any(|account| account.username == username) {
+            return Err(format!("iDRAC account '{}' already exists", username));
+        }
+        let slot = accounts
+            .iter()
+            .find(|account| account.username.is_empty() && account.id != "1")
+            .map(|account| account.id.clone())
+            .ok_or_else(|| "The iDRAC has no free account slot".to_string())?;
+
+        let payload = serde_json::json!({
+            "UserName": username,
+            "Password": password,
+            "RoleId": privilege,
+            "Enabled": true,
+        });
+        self.patch_account(&slot, &payload, "create iDRAC account").await?;
+        info!("Created iDRAC account '{}' ({:?}) in slot {} on {}", username, privilege, slot, self.base_url);
+        Ok(IdracUser {
+            id: slot,
+            username: username.to_string(),
+            enabled: true,
+            privilege: Some(privilege),
+            role_id: format!("{:?}", privilege),
+        })
+    }
+
+    /// Change the privilege, password or enabled state of the account in
+    /// slot `id`.
+    pub async fn update_idrac_user(&self, id: &str, update: &IdracUserUpdate) -> Result<(), String> {
+        let mut payload = serde_json::Map::new();
+        if let Some(privilege) = update.privilege {
+            payload.insert("RoleId".to_string(), serde_json::json!(privilege));
+        }
+        if let Some(password) = &update.password {
+            payload.insert("Password".to_string(), serde_json::json!(password));
+        }
+        if let Some(enabled) = update.enabled {
+            payload.insert("Enabled".to_string(), serde_json::json!(enabled));
+        }
+        self.patch_account(id, &serde_json::Value::Object(payload), "update iDRAC account").await?;
+        info!("Updated iDRAC account {} on {}", id, self.base_url);
+        Ok(())
+    }
+
+    async fn patch_account(&self, id: &str, payload: &serde_json::Value, what: &str) -> Result<(), String> {
+        let url = format!("{}/redfish/v1/AccountService/Accounts/{}", self.base_url, id);
+
+        let request = self.client
+            .patch(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to {}: HTTP {} - {}", what, status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    /// Set iDRAC manager attributes such as `NTPConfigGroup.1.NTP1`. They
+    /// apply immediately.
+    pub async fn set_idrac_attributes(&self, attributes: &serde_json::Map<String, serde_json::Value>) -> Result<String, String> {
+        info!("Setting {} iDRAC attribute(s) on {}", attributes.len(), self.base_url);
+        self.patch_idrac_attributes(serde_json::Value::Object(attributes.clone())).await?;
+        Ok(format!("{} iDRAC attribute(s) set", attributes.len()))
+    }
+
+    async fn patch_idrac_attributes(&self, attributes: serde_json::Value) -> Result<(), String> {
+        let url = format!(
+            "{}/redfish/v1/Managers/iDRAC.Embedded.1/Attributes",
+            self.base_url
+        );
+
+        let payload = serde_json::json!({
+            "Attributes": attributes
+        });
+
+        let request = self.client
+            .patch(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to update iDRAC attributes: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+
+    async fn set_power_state(&self, reset_type: &str) -> Result<String, String> {
+        if !RESET_TYPES.contains(&reset_type) {
+            return Err(format!(
+                "Unknown reset type '{}'; expected one of {}",
+                reset_type,
+                RESET_TYPES.join(", ")
+            ));
+        }
+        let url = format!(
+            "{}/redfish/v1/Systems/System.Embedded.1/Actions/ComputerSystem.Reset",
+            self.base_url
+        );
+
+        let payload = serde_json::json!({
+            "ResetType": reset_type
+        });
+
+        info!("Sending power command: {}", reset_type);
+
+        let request = self.client
+            .post(&url)
+            .header("Authorization", self.get_auth_header())
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let response = self.client.send(request).await?;
+
+        if response.status() == StatusCode::NO_CONTENT || response.status() == StatusCode::OK {
+            let success_msg = format!("Successfully executed: {}", reset_type);
+            info!("{}", success_msg);
+            Ok(success_msg)
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_msg = format!("Failed to set power state: HTTP {} - {}", status, error_text);
+            error!("{}", error_msg);
+            Err(error_msg)
+        }
+    }
+}
+
+/// Canonicalize a user-supplied iDRAC address into an origin such as
+/// `https://10.0.0.5`, `https://idrac.lan:8443` or `https://[fe80::1]`.
+///
+/// Accepts bare hosts, an optional `http`/`https` scheme, bracketed or
+/// unbracketed IPv6 literals and an optional port. Any path, query or
+/// trailing slash (e.g. `/redfish/v1/`) is dropped.
+pub fn normalize_host(input: &str) -> Result<String, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err("host must not be empty".to_string());
+    }
+
+    let (scheme, rest) = match trimmed.split_once("://") {
+        Some((scheme, rest)) => {
+            let scheme = scheme.to_ascii_lowercase();
+            if scheme != "https" && scheme != "http" {
+                return Err(format!("unsupported scheme '{}', expected https or http", scheme));
+            }
+            (scheme, rest)
+        }
+        None => ("https".to_string(), trimmed),
+    };
+
+    let authority = rest
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or("");
+    if authority.is_empty() {
+        return Err("missing host name or address".to_string());
+    }
+    if authority.contains('@') {
+        return Err("credentials must not be embedded in the host; use IDRAC_USERNAME and IDRAC_PASSWORD".to_string());
+    }
+
+    let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        let (literal, after) = bracketed
+            .split_once(']')
+            .ok_or_else(|| "unterminated IPv6 literal, missing ']'".to_string())?;
+        let addr: Ipv6Addr = literal
+            .parse()
+            .map_err(|_| format!("'{}' is not a valid IPv6 address", literal))?;
+        let port = match after {
+            "" => None,
+            _ => Some(
+                after
+                    .strip_prefix(':')
+                    .ok_or_else(|| format!("unexpected characters '{}' after IPv6 literal", after))?,
+            ),
+        };
+        (format!("[{}]", addr), port)
+    } else if authority.matches(':').count() > 1 {
+        // More than one colon can only be an IPv6 literal. Without brackets
+        // there is no way to tell a trailing port apart from the last group.
+        let addr: Ipv6Addr = authority
+            .parse()
+            .map_err(|_| "IPv6 literals must be enclosed in brackets, e.g. [fe80::1]:443".to_string())?;
+        (format!("[{}]", addr), None)
+    } else {
+        let (host, port) = match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        };
+        if host.is_empty() {
+            return Err("missing host name or address".to_string());
+        }
+        if !host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_') {
+            return Err(format!("'{}' contains characters not allowed in a host name", host));
+        }
+        (host.to_ascii_lowercase(), port)
+    };
+
+    let port = match port {
+        Some(port) => {
+            let value: u16 = port
+                .parse()
+                .map_err(|_| format!("'{}' is not a valid port number", port))?;
+            if value == 0 {
+                return Err("port must be between 1 and 65535".to_string());
+            }
+            Some(value)
+        }
+        None => None,
+    };
+
+    let default_port = if scheme == "https" { 443 } else { 80 };
+    match port {
+        Some(port) if port != default_port => Ok(format!("{}://{}:{}", scheme, host, port)),
+        _ => Ok(format!("{}://{}", scheme, host)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_json, normalize_host, IdracClient};
+    use crate::secret::SecretString;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use std::sync::{Arc, Mutex};
+
+    const SYSTEM: &str = "/redfish/v1/Systems/System.Embedded.1";
+
+    /// A Redfish service on a free local port whose root advertises
+    /// `$select` when `advertised`, and whose system resource answers
+    /// `$select` queries with `select_status`. Returns a client for it and
+    /// the path and query of every request it receives.
+    fn select_stub(advertised: bool, select_status: u16) -> (IdracClient, Arc<Mutex<Vec<String>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = HttpServer::new(move || {
+            let seen = seen.clone();
+            App::new().default_service(web::to(move |req: HttpRequest| {
+                let seen = seen.clone();
+                async move {
+                    seen.lock().unwrap().push(req.uri().to_string());
+                    let selected = req.query_string().contains("$select");
+                    match req.path() {
+                        "/redfish/v1" => HttpResponse::Ok().json(serde_json::json!({
+                            "ProtocolFeaturesSupported": { "SelectQuery": advertised },
+                        })),
+                        SYSTEM if selected && select_status != 200 => {
+                            HttpResponse::build(actix_web::http::StatusCode::from_u16(select_status).unwrap()).finish()
+                        }
+                        SYSTEM if selected => HttpResponse::Ok().json(serde_json::json!({ "PowerState": "On" })),
+                        SYSTEM => HttpResponse::Ok().json(serde_json::json!({
+                            "PowerState": "On",
+                            "Status": { "Health": "OK", "HealthRollup": "OK" },
+                            "Description": "x".repeat(512),
+                        })),
+                        _ => HttpResponse::NotFound().finish(),
+                    }
+                }
+            }))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+
+        let client = IdracClient::new(&format!("http://127.0.0.1:{}", port), "root", &SecretString::from("calvin".to_string())).unwrap();
+        (client, requests)
+    }
+
+    async fn read_power_state_three_times(client: &IdracClient) {
+        for _ in 0..3 {
+            assert_eq!(client.get_power_state().await.unwrap(), "On");
+        }
+    }
+
+    #[test]
+    fn bare_host_defaults_to_https() {
+        assert_eq!(normalize_host("192.168.1.120").unwrap(), "https://192.168.1.120");
+        assert_eq!(normalize_host("  iDRAC-01.Lab.local  ").unwrap(), "https://idrac-01.lab.local");
+    }
+
+    #[test]
+    fn scheme_is_kept_and_lowercased() {
+        assert_eq!(normalize_host("http://10.0.0.5").unwrap(), "http://10.0.0.5");
+        assert_eq!(normalize_host("HTTPS://10.0.0.5").unwrap(), "https://10.0.0.5");
+        assert!(normalize_host("ftp://10.0.0.5").unwrap_err().contains("unsupported scheme"));
+    }
+
+    #[test]
+    fn default_port_is_dropped_and_others_kept() {
+        assert_eq!(normalize_host("10.0.0.5:443").unwrap(), "https://10.0.0.5");
+        assert_eq!(normalize_host("http://10.0.0.5:80").unwrap(), "http://10.0.0.5");
+        assert_eq!(normalize_host("10.0.0.5:8443").unwrap(), "https://10.0.0.5:8443");
+        assert_eq!(normalize_host("http://10.0.0.5:443").unwrap(), "http://10.0.0.5:443");
+    }
+
+    #[test]
+    fn bad_ports_are_rejected() {
+        assert!(normalize_host("10.0.0.5:0").is_err());
+        assert!(normalize_host("10.0.0.5:65536").is_err());
+        assert!(normalize_host("10.0.0.5:https").is_err());
+    }
+
+    #[test]
+    fn path_query_and_fragment_are_stripped() {
+        assert_eq!(
+            normalize_host("https://10.0.0.5/redfish/v1?x=1#top").unwrap(),
+            "https://10.0.0.5"
+        );
+        assert_eq!(normalize_host("10.0.0.5/").unwrap(), "https://10.0.0.5");
+    }
+
+    #[test]
+    fn ipv6_literals_need_brackets() {
+        assert_eq!(normalize_host("[FE80::1]").unwrap(), "https://[fe80::1]");
+        assert_eq!(normalize_host("[fe80::1]:8443").unwrap(), "https://[fe80::1]:8443");
+        assert_eq!(normalize_host("[fe80::1]:443").unwrap(), "https://[fe80::1]");
+        assert_eq!(normalize_host("fe80::1").unwrap(), "https://[fe80::1]");
+        assert!(normalize_host("fe80::1:zz").is_err());
+        assert!(normalize_host("[fe80::1").is_err());
+        assert!(normalize_host("[fe80::1]x").is_err());
+    }
+
+    #[test]
+    fn empty_and_malformed_hosts_are_rejected() {
+        assert!(normalize_host("").is_err());
+        assert!(normalize_host("   ").is_err());
+        assert!(normalize_host("https://").is_err());
+        assert!(normalize_host(":443").is_err());
+        assert!(normalize_host("root:calvin@10.0.0.5").unwrap_err().contains("credentials"));
+        assert!(normalize_host("bad host").is_err());
+    }
+
+    #[test]
+    fn bom_prefixed_and_nul_padded_bodies_decode() {
+        let expected = serde_json::json!({ "PowerState": "On" });
+        assert_eq!(decode_json(Some("application/json"), b"\xEF\xBB\xBF{\"PowerState\": \"On\"}").unwrap(), expected);
+        assert_eq!(decode_json(Some("application/json"), b"{\"PowerState\": \"On\"}\0\0").unwrap(), expected);
+        assert_eq!(decode_json(Some("text/plain"), b"{\"PowerState\": \"On\"}").unwrap(), expected);
+    }
+
+    #[test]
+    fn html_bodies_quote_the_page_and_content_type() {
+        let err = decode_json(Some("text/html"), b"<html>\n<body>Session limit reached</body></html>").unwrap_err();
+        assert!(err.starts_with("Invalid iDRAC response (text/html): "), "{}", err);
+        assert!(err.ends_with("body starts with: <html> <body>Session limit reached</body></html>"), "{}", err);
+    }
+
+    #[test]
+    fn empty_bodies_are_reported_as_empty() {
+        for body in [&b""[..], b"  \r\n", b"\xEF\xBB\xBF", b"\0\0"] {
+            let err = decode_json(None, body).unwrap_err();
+            assert_eq!(err, "Invalid iDRAC response (no content type): empty body");
+        }
+    }
+
+    #[test]
+    fn truncated_bodies_fail_with_an_excerpt() {
+        let err = decode_json(Some("application/json"), b"{\"PowerState\": \"O").unwrap_err();
+        assert!(err.contains("EOF"), "{}", err);
+        assert!(err.ends_with("body starts with: {\"PowerState\": \"O"), "{}", err);
+
+        let long = format!("{{\"Password\": \"calvin\", \"Members\": [{}", "1, ".repeat(200));
+        let err = decode_json(Some("application/json"), long.as_bytes()).unwrap_err();
+        assert!(!err.contains("calvin"), "{}", err);
+        assert!(err.len() < long.len(), "{}", err);
+    }
+
+    #[actix_web::test]
+    async fn select_is_used_once_a_baseline_exists() {
+        let (client, requests) = select_stub(true, 200);
+        read_power_state_three_times(&client).await;
+
+        let selected = format!("{}?$select=PowerState", SYSTEM);
+        assert_eq!(*requests.lock().unwrap(), [SYSTEM, "/redfish/v1", selected.as_str(), selected.as_str()]);
+        assert_eq!(client.select_supported(), Some(true));
+        let stats = client.payload_stats().into_iter().find(|stats| stats.path == SYSTEM).unwrap();
+        assert_eq!((stats.full_requests, stats.selected_requests), (1, 2));
+        assert!(stats.reduction.unwrap() > 0.5, "{:?}", stats.reduction);
+    }
+
+    #[actix_web::test]
+    async fn select_is_never_sent_when_not_advertised() {
+        let (client, requests) = select_stub(false, 200);
+        read_power_state_three_times(&client).await;
+
+        assert_eq!(*requests.lock().unwrap(), [SYSTEM, "/redfish/v1", SYSTEM, SYSTEM]);
+        assert_eq!(client.select_supported(), Some(false));
+    }
+
+    #[actix_web::test]
+    async fn rejected_select_falls_back_to_full_resources() {
+        for status in [400, 501] {
+            let (client, requests) = select_stub(true, status);
+            read_power_state_three_times(&client).await;
+
+            let selected = format!("{}?$select=PowerState", SYSTEM);
+            assert_eq!(*requests.lock().unwrap(), [SYSTEM, "/redfish/v1", selected.as_str(), SYSTEM, SYSTEM], "HTTP {}", status);
+            assert_eq!(client.select_supported(), Some(false), "HTTP {}", status);
+        }
+    }
 }
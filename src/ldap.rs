@@ -0,0 +1,249 @@
+//! Optional sign-in against LDAP or Active Directory. The user's own bind
+//! proves the password; their groups decide which scopes the session gets.
+
+use ldap3::{dn_escape, ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use std::time::Duration;
+
+use crate::secret::SecretString;
+use crate::tokens::TokenScope;
+
+/// Result code of a bind with a wrong password or an unknown DN.
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// How a username becomes the DN to bind with.
+#[derive(Debug, Clone)]
+enum UserLookup {
+    /// `LDAP_BIND_DN_TEMPLATE` with `{username}` substituted.
+    Template(String),
+    /// Bind as a service account and search for the user's entry.
+    Search {
+        bind_dn: String,
+        bind_password: SecretString,
+        base_dn: String,
+        filter: String,
+    },
+}
+
+/// A directory group and the scopes its members get.
+#[derive(Debug, Clone)]
+struct GroupMapping {
+    /// A full DN, or only the group's CN when it has no `=`.
+    group: String,
+    scopes: Vec<TokenScope>,
+}
+
+/// Directory to sign in against; present when `LDAP_URL` is set.
+#[derive(Debug, Clone)]
+pub struct LdapConfig {
+    url: String,
+    lookup: UserLookup,
+    /// Attribute of the user's entry listing group DNs.
+    group_attribute: String,
+    mappings: Vec<GroupMapping>,
+    /// Whether accounts created with a local password can still sign in.
+    pub allow_local_login: bool,
+    timeout: Duration,
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn parse_mappings(value: &str) -> Result<Vec<GroupMapping>, String> {
+    let mut mappings = Vec::new();
+    for entry in value.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (group, scopes) = entry
+            .rsplit_once("=>")
+            .ok_or_else(|| format!("LDAP_ROLE_MAPPING entry '{}' is not group=>scope,scope", entry))?;
+        let scopes = scopes
+            .split(',')
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(|scope| TokenScope::parse(scope).ok_or_else(|| format!("LDAP_ROLE_MAPPING has unknown scope '{}'", scope)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if group.trim().is_empty() || scopes.is_empty() {
+            return Err(format!("LDAP_ROLE_MAPPING entry '{}' needs a group and at least one scope", entry));
+        }
+        mappings.push(GroupMapping {
+            group: group.trim().to_string(),
+            scopes,
+        });
+    }
+    if mappings.is_empty() {
+        return Err("LDAP_ROLE_MAPPING must map at least one group".to_string());
+    }
+    Ok(mappings)
+}
+
+/// The value of the first RDN, which is a group's CN in every directory
+/// we have seen.
+fn common_name(dn: &str) -> &str {
+    dn.split(',').next().and_then(|rdn| rdn.split_once('=')).map(|(_, value)| value.trim()).unwrap_or(dn)
+}
+
+impl LdapConfig {
+    /// `LDAP_URL`, either `LDAP_BIND_DN_TEMPLATE` or `LDAP_BIND_DN`,
+    /// `LDAP_BIND_PASSWORD` and `LDAP_USER_BASE_DN`, and
+    /// `LDAP_ROLE_MAPPING`. `None` when LDAP is not configured.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(url) = env("LDAP_URL") else {
+            return Ok(None);
+        };
+        if !url.starts_with("ldaps://") && !url.starts_with("ldap://") {
+            return Err("LDAP_URL must start with ldaps:// or ldap://".to_string());
+        }
+        let lookup = match (env("LDAP_BIND_DN_TEMPLATE"), env("LDAP_BIND_DN")) {
+            (Some(template), _) if template.contains("{username}") => UserLookup::Template(template),
+            (Some(_), _) => return Err("LDAP_BIND_DN_TEMPLATE must contain {username}".to_string()),
+            (None, Some(bind_dn)) => UserLookup::Search {
+                bind_dn,
+                bind_password: env("LDAP_BIND_PASSWORD")
+                    .map(SecretString::from)
+                    .ok_or("LDAP_BIND_DN is set but LDAP_BIND_PASSWORD is not")?,
+                base_dn: env("LDAP_USER_BASE_DN").ok_or("LDAP_BIND_DN is set but LDAP_USER_BASE_DN is not")?,
+                filter: env("LDAP_USER_FILTER").unwrap_or_else(|| "(sAMAccountName={username})".to_string()),
+            },
+            (None, None) => return Err("LDAP_URL is set but neither LDAP_BIND_DN_TEMPLATE nor LDAP_BIND_DN is".to_string()),
+        };
+        let mappings = parse_mappings(&env("LDAP_ROLE_MAPPING").unwrap_or_default())?;
+
+        Ok(Some(LdapConfig {
+            url,
+            lookup,
+            group_attribute: env("LDAP_GROUP_ATTRIBUTE").unwrap_or_else(|| "memberOf".to_string()),
+            mappings,
+            allow_local_login: !matches!(
+                env("LDAP_ALLOW_LOCAL_LOGIN").map(|v| v.to_ascii_lowercase()).as_deref(),
+                Some("false" | "0" | "no")
+            ),
+            timeout: Duration::from_secs(
+                env("LDAP_TIMEOUT_SECS")
+                    .and_then(|v| v.parse().ok())
+                    .filter(|&secs| secs > 0)
+                    .unwrap_or(10),
+            ),
+        }))
+    }
+
+    /// Scopes granted by the mappings matching any of `groups`, in the
+    /// order of `TokenScope::ALL`.
+    pub fn scopes_for(&self, groups: &[String]) -> Vec<TokenScope> {
+        let matched: Vec<TokenScope> = self
+            .mappings
+            .iter()
+            .filter(|mapping| {
+                groups.iter().any(|group| {
+                    if mapping.group.contains('=') {
+                        group.eq_ignore_ascii_case(&mapping.group)
+                    } else {
+                        common_name(group).eq_ignore_ascii_case(&mapping.group)
+                    }
+                })
+            })
+            .flat_map(|mapping| mapping.scopes.iter().copied())
+            .collect();
+        TokenScope::ALL.iter().copied().filter(|scope| matched.contains(scope)).collect()
+    }
+}
+
+/// Why a directory sign-in failed.
+pub enum LdapFailure {
+    /// The directory could not be reached or answered with an error.
+    Unreachable(String),
+    /// Unknown user or wrong password.
+    InvalidCredentials(String),
+}
+
+/// A user whose password the directory accepted.
+pub struct DirectoryUser {
+    pub dn: String,
+    pub groups: Vec<String>,
+}
+
+fn unreachable(e: LdapError) -> LdapFailure {
+    LdapFailure::Unreachable(e.to_string())
+}
+
+/// Bind as `dn`, telling a rejected password apart from any other failure.
+async fn bind(ldap: &mut Ldap, timeout: Duration, dn: &str, password: &str) -> Result<(), LdapFailure> {
+    match ldap.with_timeout(timeout).simple_bind(dn, password).await.map_err(unreachable)?.success() {
+        Ok(_) => Ok(()),
+        Err(LdapError::LdapResult { result }) if result.rc == INVALID_CREDENTIALS => {
+            Err(LdapFailure::InvalidCredentials(format!("bind as {} rejected", dn)))
+        }
+        Err(e) => Err(unreachable(e)),
+    }
+}
+
+/// Read one entry under `base`; `None` when nothing matches.
+async fn find_entry(
+    ldap: &mut Ldap,
+    timeout: Duration,
+    base: &str,
+    scope: Scope,
+    filter: &str,
+    attribute: &str,
+) -> Result<Option<SearchEntry>, LdapFailure> {
+    let (entries, _) = ldap
+        .with_timeout(timeout)
+        .search(base, scope, filter, vec![attribute])
+        .await
+        .map_err(unreachable)?
+        .success()
+        .map_err(unreachable)?;
+    Ok(entries.into_iter().next().map(SearchEntry::construct))
+}
+
+/// Check `username` and `password` against the directory over TLS:
+/// `ldaps://` URLs are TLS from the start and `ldap://` ones must accept
+/// `STARTTLS`.
+pub async fn authenticate(config: &LdapConfig, username: &str, password: &str) -> Result<DirectoryUser, LdapFailure> {
+    // An empty password is an unauthenticated bind, which directories accept.
+    if password.is_empty() {
+        return Err(LdapFailure::InvalidCredentials("empty password".to_string()));
+    }
+    let settings = LdapConnSettings::new()
+        .set_conn_timeout(config.timeout)
+        .set_starttls(config.url.starts_with("ldap://"));
+    let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &config.url).await.map_err(unreachable)?;
+    ldap3::drive!(conn);
+
+    let attribute = config.group_attribute.as_str();
+    let result = async {
+        let dn = match &config.lookup {
+            UserLookup::Template(template) => template.replace("{username}", &dn_escape(username)),
+            UserLookup::Search {
+                bind_dn,
+                bind_password,
+                base_dn,
+                filter,
+            } => {
+                bind(&mut ldap, config.timeout, bind_dn, bind_password.expose())
+                    .await
+                    .map_err(|failure| match failure {
+                        // The service account is ours to fix, not the user's.
+                        LdapFailure::InvalidCredentials(_) => {
+                            LdapFailure::Unreachable(format!("service account {} was refused", bind_dn))
+                        }
+                        failure => failure,
+                    })?;
+                let filter = filter.replace("{username}", &ldap_escape(username));
+                match find_entry(&mut ldap, config.timeout, base_dn, Scope::Subtree, &filter, attribute).await? {
+                    Some(entry) => entry.dn,
+                    None => return Err(LdapFailure::InvalidCredentials(format!("no entry matches {}", filter))),
+                }
+            }
+        };
+        bind(&mut ldap, config.timeout, &dn, password).await?;
+        // Read the groups as the user, who can always see their own entry.
+        let groups = find_entry(&mut ldap, config.timeout, &dn, Scope::Base, "(objectClass=*)", attribute)
+            .await?
+            .and_then(|entry| entry.attrs.into_iter().find(|(name, _)| name.eq_ignore_ascii_case(attribute)))
+            .map(|(_, groups)| groups)
+            .unwrap_or_default();
+        Ok(DirectoryUser { dn, groups })
+    }
+    .await;
+    let _ = ldap.unbind().await;
+    result
+}
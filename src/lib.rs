@@ -0,0 +1,9 @@
+//! Types and a client for the iDRAC controller's HTTP API, for Rust tools
+//! that drive a running controller. The server binary is built on the same
+//! `api` and `errors` modules.
+
+pub mod api;
+pub mod client;
+pub mod errors;
+
+pub use client::{ApiClient, ClientError, ShutdownResponse};
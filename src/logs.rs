@@ -0,0 +1,121 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::database::SQLITE_TIMESTAMP_FORMAT;
+use crate::idrac::SelEntry;
+use crate::validation::FieldError;
+
+pub const SOURCES: [&str; 2] = ["sel", "lc"];
+const SEVERITIES: [&str; 3] = ["OK", "Warning", "Critical"];
+
+/// One entry of the merged SEL and Lifecycle Controller log.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogRecord {
+    /// `sel` or `lc`.
+    pub source: &'static str,
+    /// As the iDRAC reported it.
+    pub timestamp: String,
+    pub severity: String,
+    pub message: String,
+    pub message_id: Option<String>,
+}
+
+/// What to keep of the merged logs.
+pub struct LogFilter {
+    pub severities: Option<Vec<&'static str>>,
+    /// Bounds in the database timestamp format, as `parse_timestamp`
+    /// returns them.
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: usize,
+}
+
+/// Parse a comma-separated list, keeping the canonical spelling of each
+/// allowed value. `None` when the list is absent or empty.
+fn parse_list(
+    field: &'static str,
+    input: Option<&str>,
+    allowed: &[&'static str],
+) -> Result<Option<Vec<&'static str>>, FieldError> {
+    let Some(input) = input.map(str::trim).filter(|input| !input.is_empty()) else {
+        return Ok(None);
+    };
+    let mut values = Vec::new();
+    for item in input.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let value = allowed
+            .iter()
+            .find(|allowed| allowed.eq_ignore_ascii_case(item))
+            .ok_or_else(|| FieldError {
+                field,
+                message: format!("'{}' is not one of {}", item, allowed.join(", ")),
+            })?;
+        if !values.contains(value) {
+            values.push(*value);
+        }
+    }
+    Ok(Some(values))
+}
+
+/// The `sources` query parameter; both logs when absent.
+pub fn parse_sources(input: Option<&str>) -> Result<Vec<&'static str>, FieldError> {
+    Ok(parse_list("sources", input, &SOURCES)?.unwrap_or_else(|| SOURCES.to_vec()))
+}
+
+/// The `severity` query parameter; every severity when absent.
+pub fn parse_severities(input: Option<&str>) -> Result<Option<Vec<&'static str>>, FieldError> {
+    parse_list("severity", input, &SEVERITIES)
+}
+
+fn parse_created(created: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(created).ok().map(|time| time.with_timezone(&Utc))
+}
+
+/// Merge the entries read from each source, newest first. The LC log
+/// repeats SEL events, so entries with the same `MessageId` and time are
+/// kept once, from the source listed first. Entries whose time cannot be
+/// read sort last and are dropped when the range is bounded.
+pub fn merge(sources: Vec<(&'static str, Vec<SelEntry>)>, filter: &LogFilter) -> Vec<LogRecord> {
+    let mut seen = HashSet::new();
+    let mut merged: Vec<(Option<DateTime<Utc>>, LogRecord)> = Vec::new();
+
+    for (source, entries) in sources {
+        for entry in entries {
+            let at = parse_created(&entry.created);
+            if let Some(severities) = &filter.severities {
+                if !severities.iter().any(|severity| severity.eq_ignore_ascii_case(&entry.severity)) {
+                    continue;
+                }
+            }
+            if filter.since.is_some() || filter.until.is_some() {
+                let Some(at) = at else { continue };
+                let at = at.format(SQLITE_TIMESTAMP_FORMAT).to_string();
+                if filter.since.as_ref().is_some_and(|since| at < *since)
+                    || filter.until.as_ref().is_some_and(|until| at > *until)
+                {
+                    continue;
+                }
+            }
+            if let Some(message_id) = &entry.message_id {
+                let time = at.map(|at| at.to_rfc3339()).unwrap_or_else(|| entry.created.clone());
+                if !seen.insert((message_id.clone(), time)) {
+                    continue;
+                }
+            }
+            merged.push((
+                at,
+                LogRecord {
+                    source,
+                    timestamp: entry.created,
+                    severity: entry.severity,
+                    message: entry.message,
+                    message_id: entry.message_id,
+                },
+            ));
+        }
+    }
+
+    // Stable, so entries at the same time keep their source order.
+    merged.sort_by(|(a, _), (b, _)| b.cmp(a));
+    merged.into_iter().take(filter.limit).map(|(_, record)| record).collect()
+}
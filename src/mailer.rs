@@ -0,0 +1,159 @@
+use base64::Engine;
+use chrono::Local;
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::config::{SmtpConfig, SmtpSecurity};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A plain-text message to one or more recipients.
+pub struct Email<'a> {
+    pub to: &'a [String],
+    pub subject: &'a str,
+    pub body: &'a str,
+}
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+/// One SMTP session. Replies are read line by line; a multi-line reply
+/// ends at the line with a space after its code.
+struct Session {
+    stream: BufReader<Box<dyn Stream + Send>>,
+}
+
+impl Session {
+    fn reply(&mut self) -> Result<(u16, String), String> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            let read = self
+                .stream
+                .read_line(&mut line)
+                .map_err(|e| format!("Failed to read from the SMTP server: {}", e))?;
+            if read == 0 {
+                return Err("SMTP server closed the connection".to_string());
+            }
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(|| format!("Unexpected SMTP reply: {}", line.trim_end()))?;
+            text.push_str(line.get(4..).unwrap_or("").trim_end());
+            text.push('\n');
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+        }
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(line.as_bytes())
+            .and_then(|_| stream.write_all(b"\r\n"))
+            .and_then(|_| stream.flush())
+            .map_err(|e| format!("Failed to write to the SMTP server: {}", e))
+    }
+
+    /// Send `line` and fail unless the reply code is `expected`. `shown`
+    /// stands in for the line in errors, so credentials stay out of logs.
+    fn command(&mut self, line: &str, shown: &str, expected: u16) -> Result<String, String> {
+        self.send(line)?;
+        let (code, text) = self.reply()?;
+        if code != expected {
+            return Err(format!("SMTP server rejected {}: {} {}", shown, code, text.trim_end()));
+        }
+        Ok(text)
+    }
+}
+
+fn tls_wrap(host: &str, stream: TcpStream) -> Result<SslStream<TcpStream>, String> {
+    let connector = SslConnector::builder(SslMethod::tls_client())
+        .map_err(|e| format!("Failed to set up TLS: {}", e))?
+        .build();
+    connector
+        .connect(host, stream)
+        .map_err(|e| format!("TLS handshake with {} failed: {}", host, e))
+}
+
+fn connect(config: &SmtpConfig) -> Result<Session, String> {
+    let address = format!("{}:{}", config.host, config.port);
+    let tcp = TcpStream::connect(&address).map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+    tcp.set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|_| tcp.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| e.to_string())?;
+
+    // A plain connection keeps its socket so STARTTLS can wrap it later.
+    let (stream, plain): (Box<dyn Stream + Send>, _) = match config.security {
+        SmtpSecurity::Tls => (Box::new(tls_wrap(&config.host, tcp)?), None),
+        SmtpSecurity::StartTls | SmtpSecurity::None => {
+            (Box::new(tcp.try_clone().map_err(|e| e.to_string())?), Some(tcp))
+        }
+    };
+    let mut session = Session {
+        stream: BufReader::new(stream),
+    };
+    let (code, text) = session.reply()?;
+    if code != 220 {
+        return Err(format!("SMTP server refused the connection: {} {}", code, text.trim_end()));
+    }
+    session.command("EHLO idrac-controller", "EHLO", 250)?;
+
+    if let (SmtpSecurity::StartTls, Some(tcp)) = (config.security, plain) {
+        session.command("STARTTLS", "STARTTLS", 220)?;
+        session = Session {
+            stream: BufReader::new(Box::new(tls_wrap(&config.host, tcp)?)),
+        };
+        session.command("EHLO idrac-controller", "EHLO", 250)?;
+    }
+    Ok(session)
+}
+
+/// The message as sent after `DATA`: headers, the body with CRLF line
+/// endings and leading dots doubled, and the terminating dot.
+fn message(from: &str, email: &Email) -> String {
+    let mut data = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        from,
+        email.to.join(", "),
+        email.subject.replace(['\r', '\n'], " "),
+        Local::now().to_rfc2822(),
+    );
+    for line in email.body.lines() {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push('.');
+    data
+}
+
+/// Deliver `email` through the configured server. Blocking; run it on a
+/// blocking thread.
+pub fn send(config: &SmtpConfig, email: &Email) -> Result<(), String> {
+    if email.to.is_empty() {
+        return Ok(());
+    }
+    let mut session = connect(config)?;
+
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        let credentials = format!("\0{}\0{}", username, password.expose());
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+        session.command(&format!("AUTH PLAIN {}", encoded), "AUTH PLAIN", 235)?;
+    }
+    session.command(&format!("MAIL FROM:<{}>", config.from), "MAIL FROM", 250)?;
+    for recipient in email.to {
+        session.command(&format!("RCPT TO:<{}>", recipient), &format!("RCPT TO {}", recipient), 250)?;
+    }
+    session.command("DATA", "DATA", 354)?;
+    session.command(&message(&config.from, email), "the message", 250)?;
+    // The message is accepted; a failed goodbye changes nothing.
+    let _ = session.command("QUIT", "QUIT", 221);
+    Ok(())
+}
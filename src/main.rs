@@ -1,29 +1,147 @@
-use actix_web::{web, App, HttpServer, middleware};
+use actix_web::{http::Method, web, App, HttpServer};
 use actix_session::{SessionMiddleware, storage::CookieSessionStore};
 use actix_session::config::PersistentSession;
 use actix_web::cookie::{Key, time::Duration};
 use std::sync::Arc;
 use env_logger::Env;
-use log::info;
+use std::io::Write;
+use log::{info, warn};
 
+mod assets;
+mod audit;
+mod boot;
+mod break_glass;
+mod changes;
+mod config;
+mod correlation;
+mod crypto;
+mod csv_export;
 mod database;
+mod firmware;
+mod firmware_rollout;
+mod firmware_schedule;
+mod group_apply;
+mod group_power;
+mod group_power_on;
 mod idrac;
+mod ldap;
+mod logs;
 mod handlers;
+mod health_report;
+mod mailer;
+mod middleware;
+mod oidc;
+mod operations;
+mod os_health;
+mod pagination;
+mod password_reset;
+mod ping;
+mod power_anomaly;
+mod power_burst;
+mod power_ws;
+mod psu;
+mod rate_limit;
+mod retention;
+mod rollups;
+mod schedule;
+mod scrub;
+mod secret;
+mod server_import;
+mod servers;
+mod shares;
+mod state;
+mod sweep;
+mod tariff;
+mod tasks;
+#[cfg(test)]
+mod testing;
+mod tokens;
+mod usage;
+mod validation;
+mod vault;
 
+use idrac_controller::{api, errors};
+use config::Config;
+use crypto::CredentialCipher;
 use database::Database;
-use idrac::IdracClient;
+use idrac::{IdracClient, TlsMinVersion};
+use os_health::OsHealthProbe;
+use ldap::LdapConfig;
+use oidc::OidcConfig;
+use servers::ServerRegistry;
+use middleware::timeout;
+use middleware::RouteClass::{Fast, Long, Normal};
+use state::AppState;
+use vault::{VaultClient, VaultConfig};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Initialize logger
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+    let subcommand = match command.as_deref() {
+        Some("break-glass") => Some(break_glass::run(args)),
+        Some("rollup-backfill") => Some(rollups::run(args)),
+        _ => None,
+    };
+    if let Some(result) = subcommand {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        return Ok(());
+    }
+
+    // Initialize logger; every line passes through the credential scrubber
+    env_logger::Builder::from_env(Env::default().default_filter_or("info"))
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                scrub::log_message(record)
+            )
+        })
+        .init();
     
     info!("Starting iDRAC Controller application");
 
+    let mut config = Config::from_env();
+    config.ldap = match LdapConfig::from_env() {
+        Ok(ldap) => ldap,
+        Err(e) => {
+            eprintln!("Invalid LDAP configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    config.oidc = match OidcConfig::from_env(config.self_url.as_deref()) {
+        Ok(oidc) => oidc,
+        Err(e) => {
+            eprintln!("Invalid OIDC configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    match TlsMinVersion::from_env() {
+        Ok(version) if version < TlsMinVersion::Tls12 => warn!(
+            "IDRAC_TLS_MIN_VERSION={} allows TLS versions older than 1.2 to iDRACs; keep it only while old firmware needs it",
+            version.as_str()
+        ),
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
     // Initialize database
-    let db_path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| "./data/idrac.db".to_string());
-    
-    let db = match Database::new(&db_path) {
+    let opened = if config.standby_mode {
+        info!("STANDBY_MODE is set; opening database read-only");
+        Database::open_read_only(config.database_location())
+    } else {
+        Database::open(config.database_location())
+    };
+    let db = match opened {
         Ok(db) => {
             info!("Database initialized successfully");
             Arc::new(db)
@@ -34,8 +152,36 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
+    let vault_config = match VaultConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid Vault configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut vault_credentials = None;
+    if let Some(vault_config) = vault_config {
+        let logged_in = VaultClient::login(vault_config).await;
+        match logged_in {
+            Ok((vault, lease)) => {
+                match vault.idrac_credentials().await {
+                    Ok(credentials) => {
+                        info!("Read iDRAC credentials from Vault");
+                        vault_credentials = Some(credentials);
+                    }
+                    Err(e) => warn!(
+                        "Could not read iDRAC credentials from Vault ({}); falling back to IDRAC_USERNAME and IDRAC_PASSWORD",
+                        e
+                    ),
+                }
+                tasks::spawn_vault_token_renewal(vault, lease);
+            }
+            Err(e) => warn!("Could not log in to Vault ({}); falling back to IDRAC_USERNAME and IDRAC_PASSWORD", e),
+        }
+    }
+
     // Initialize iDRAC client
-    let idrac_client = match IdracClient::from_env() {
+    let idrac_client = match IdracClient::from_env(vault_credentials) {
         Ok(client) => {
             info!("iDRAC client initialized successfully");
             Arc::new(client)
@@ -50,30 +196,649 @@ async fn main() -> std::io::Result<()> {
     // Generate a secret key for sessions
     let secret_key = Key::generate();
     
-    let bind_address = "0.0.0.0:8080";
+    let key_path = std::path::Path::new(&config.database_path).with_file_name("credential.key");
+    let cipher = match CredentialCipher::load(config.credential_key.as_ref(), &key_path) {
+        Ok(cipher) => Arc::new(cipher),
+        Err(e) => {
+            eprintln!("Failed to load credential key: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let default_os_health = config.os_health_url.as_deref().and_then(|url| {
+        let probe = validation::normalize_os_health_url("OS_HEALTH_URL", url)
+            .map_err(|e| e.message)
+            .and_then(|url| OsHealthProbe::new(&url, config.os_health_insecure, config.os_health_token.clone()));
+        match probe {
+            Ok(probe) => Some(probe),
+            Err(e) => {
+                warn!("OS_HEALTH_URL ignored: {}", e);
+                None
+            }
+        }
+    });
+
+    let server_registry = match ServerRegistry::load(
+        &db,
+        cipher,
+        idrac_client.clone(),
+        config.idrac_hosts_this_app,
+        default_os_health,
+    ) {
+        Ok(registry) => Arc::new(registry),
+        Err(e) => {
+            eprintln!("Failed to load servers: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let state = web::Data::new(AppState::new(db, idrac_client, server_registry, config));
+
+    tasks::spawn_cert_expiry_check(state.get_ref().clone());
+    tasks::spawn_lease_heartbeat(state.get_ref().clone());
+    tasks::spawn_clock_check(state.get_ref().clone());
+    tasks::spawn_power_cap_scheduler(state.get_ref().clone());
+    tasks::spawn_tariff_scheduler(state.get_ref().clone());
+    tasks::spawn_one_shot_scheduler(state.get_ref().clone());
+    tasks::spawn_firmware_update_scheduler(state.get_ref().clone());
+    tasks::spawn_account_expiry(state.get_ref().clone());
+    tasks::spawn_retention_cleanup(state.get_ref().clone());
+    tasks::spawn_firmware_inventory_refresh(state.get_ref().clone());
+    tasks::spawn_nightly_sweep(state.get_ref().clone());
+    tasks::spawn_health_report(state.get_ref().clone());
+    tasks::spawn_power_sampling(state.get_ref().clone());
+    tasks::spawn_psu_check(state.get_ref().clone());
+    tasks::spawn_ping_check(state.get_ref().clone());
+    tasks::spawn_os_health_check(state.get_ref().clone());
+    tasks::spawn_usage_flush(state.get_ref().clone());
+    tasks::spawn_write_probe(state.get_ref().clone());
+    let (usage, usage_db) = (state.usage.clone(), state.db.clone());
+    let keepalive = tasks::spawn_idrac_keepalive(state.get_ref().clone());
+    if !state.db.is_read_only() {
+        group_power_on::resume_interrupted(&state);
+        firmware_rollout::resume_interrupted(&state);
+        firmware_schedule::resume_interrupted(&state);
+        match state.db.interrupt_running_group_apply_jobs() {
+            Ok(0) => {}
+            Ok(count) => warn!("Marked {} group apply job(s) left running as interrupted", count),
+            Err(e) => warn!("Failed to mark interrupted group apply jobs: {}", e),
+        }
+    }
+
+    let bind_address = state.config.bind_address.clone();
     info!("Starting HTTP server at {}", bind_address);
 
-    HttpServer::new(move || {
+    // The cookie lifetime is fixed at startup; a shorter TTL set at runtime
+    // is enforced by SessionGuard.
+    let session_ttl_hours = state.retention.read().unwrap().sessions_ttl_hours;
+
+    let request_logger = middleware::RequestLogger::new(
+        state.config.log_sample_rate,
+        std::time::Duration::from_millis(state.config.slow_request_threshold_ms),
+    );
+
+    let result = HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(db.clone()))
-            .app_data(web::Data::new(idrac_client.clone()))
-            .wrap(middleware::Logger::default())
+            .app_data(state.clone())
+            .app_data(web::JsonConfig::default().error_handler(handlers::json_error_handler))
+            .wrap(middleware::StandbyGuard)
+            .wrap(middleware::ShareGuard)
+            .wrap(request_logger.clone())
+            .wrap(middleware::SessionGuard)
             .wrap(
                 SessionMiddleware::builder(CookieSessionStore::default(), secret_key.clone())
-                    .session_lifecycle(PersistentSession::default().session_ttl(Duration::hours(24)))
+                    .session_lifecycle(PersistentSession::default().session_ttl(Duration::hours(session_ttl_hours as i64)))
                     .build()
             )
-            // Routes
-            .route("/", web::get().to(handlers::index))
-            .route("/api/register", web::post().to(handlers::register))
-            .route("/api/login", web::post().to(handlers::login))
-            .route("/api/logout", web::post().to(handlers::logout))
-            .route("/api/power/status", web::get().to(handlers::power_status))
-            .route("/api/power/on", web::post().to(handlers::power_on_handler))
-            .route("/api/power/off", web::post().to(handlers::power_off_handler))
-            .route("/api/power/shutdown", web::post().to(handlers::graceful_shutdown_handler))
+            .wrap(middleware::Cors)
+            .wrap(middleware::HostGuard)
+            .configure(routes)
     })
     .bind(bind_address)?
     .run()
-    .await
+    .await;
+
+    if let Some(keepalive) = keepalive {
+        keepalive.abort();
+        info!("Stopped iDRAC keepalive");
+    }
+    // Counts since the last periodic flush.
+    if !usage_db.writes_paused() {
+        usage.flush(&usage_db);
+    }
+    result
+}
+
+/// Every route, each with its time budget. The few without one serve
+/// static files, stream, answer 202 with a background operation, or
+/// (power on with ?verify) enforce their own limit.
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(handlers::index))
+        .route("/static/{file}", web::get().to(handlers::static_asset))
+        .route("/api/register", web::post().to(handlers::register).wrap(timeout(Fast)))
+        .route("/api/login", web::post().to(handlers::login).wrap(timeout(Fast)))
+        .route("/api/logout", web::post().to(handlers::logout).wrap(timeout(Fast)))
+        .route("/api/auth/methods", web::get().to(handlers::auth_methods).wrap(timeout(Fast)))
+        .route("/api/auth/oidc/login", web::get().to(handlers::oidc_login).wrap(timeout(Normal)))
+        .route("/api/auth/oidc/callback", web::get().to(handlers::oidc_callback).wrap(timeout(Normal)))
+        .route("/api/auth/activity", web::post().to(handlers::report_activity).wrap(timeout(Fast)))
+        .route("/api/password-reset/request", web::post().to(handlers::request_password_reset).wrap(timeout(Fast)))
+        .route("/api/password-reset/complete", web::post().to(handlers::complete_password_reset).wrap(timeout(Fast)))
+        .route("/reset-password", web::get().to(handlers::reset_password_page))
+        .route("/api/break-glass/{token}", web::get().to(handlers::break_glass_login).wrap(timeout(Fast)))
+        .route("/api/alerts", web::get().to(handlers::list_alerts).wrap(timeout(Fast)))
+        .route("/api/users", web::get().to(handlers::list_users).wrap(timeout(Fast)))
+        .route("/api/users", web::post().to(handlers::create_user).wrap(timeout(Fast)))
+        .route("/api/users/{id}/expiry", web::put().to(handlers::set_user_expiry).wrap(timeout(Fast)))
+        .route("/api/users/{id}/grants", web::get().to(handlers::list_server_grants).wrap(timeout(Fast)))
+        .route("/api/users/{id}/grants/{alias}", web::put().to(handlers::set_server_grant).wrap(timeout(Fast)))
+        .route("/api/users/{id}/grants/{alias}", web::delete().to(handlers::delete_server_grant).wrap(timeout(Fast)))
+        .route("/api/admin/expirations", web::get().to(handlers::user_expirations).wrap(timeout(Fast)))
+        .route("/api/admin/retention-policy", web::get().to(handlers::get_retention_policy).wrap(timeout(Fast)))
+        .route("/api/admin/retention-policy", web::put().to(handlers::put_retention_policy).wrap(timeout(Fast)))
+        .route("/api/users/me/email", web::put().to(handlers::set_my_email).wrap(timeout(Fast)))
+        .route("/api/users/me/preferences", web::get().to(handlers::get_preferences).wrap(timeout(Fast)))
+        .route("/api/users/me/preferences", web::put().to(handlers::put_preferences).wrap(timeout(Fast)))
+        .route("/api/admin/usage", web::get().to(handlers::admin_usage).wrap(timeout(Fast)))
+        .route("/api/account/usage", web::get().to(handlers::account_usage).wrap(timeout(Fast)))
+        .route("/api/admin/promote", web::post().to(handlers::promote).wrap(timeout(Normal)))
+        .route("/api/servers", web::get().to(handlers::list_servers).wrap(timeout(Normal)))
+        .route("/api/servers", web::post().to(handlers::create_server).wrap(timeout(Normal)))
+        .route("/api/servers/{alias}", web::delete().to(handlers::delete_server).wrap(timeout(Fast)))
+        .route("/api/servers/by-host", web::get().to(handlers::server_by_host).wrap(timeout(Fast)))
+        .route("/api/servers/import", web::post().to(handlers::import_servers).wrap(timeout(Long)))
+        .route(
+            "/api/servers/{alias}/credentials",
+            web::patch().to(handlers::update_server_credentials).wrap(timeout(Normal)),
+        )
+        .route(
+            "/api/servers/{alias}/os-health",
+            web::put().to(handlers::update_server_os_health).wrap(timeout(Fast)),
+        )
+        .route("/api/servers/{alias}/clock", web::get().to(handlers::server_clock).wrap(timeout(Fast)))
+        .route("/api/servers/{alias}/ntp", web::put().to(handlers::set_server_ntp).wrap(timeout(Normal)))
+        .route(
+            "/api/servers/{alias}/power/anomaly",
+            web::get().to(handlers::server_power_anomaly).wrap(timeout(Fast)),
+        )
+        .route(
+            "/api/servers/{alias}/boot-report",
+            web::get().to(handlers::boot_report).wrap(timeout(Normal)),
+        )
+        .route("/api/servers/{alias}/sel", web::get().to(handlers::list_sel_entries).wrap(timeout(Fast)))
+        .route("/api/servers/{alias}/ping-history", web::get().to(handlers::ping_history).wrap(timeout(Fast)))
+        .route(
+            "/api/servers/{alias}/power-cap-schedules",
+            web::get().to(handlers::list_power_cap_schedules).wrap(timeout(Fast)),
+        )
+        .route(
+            "/api/servers/{alias}/power-cap-schedules",
+            web::post().to(handlers::create_power_cap_schedule).wrap(timeout(Fast)),
+        )
+        .route(
+            "/api/servers/{alias}/power-cap-schedules/{id}",
+            web::delete().to(handlers::delete_power_cap_schedule).wrap(timeout(Fast)),
+        )
+        .route("/api/groups", web::get().to(handlers::list_groups).wrap(timeout(Fast)))
+        .route("/api/groups", web::post().to(handlers::create_group).wrap(timeout(Fast)))
+        .route("/api/groups/{id}", web::put().to(handlers::update_group).wrap(timeout(Fast)))
+        .route("/api/groups/{id}", web::delete().to(handlers::delete_group).wrap(timeout(Fast)))
+        .route("/api/groups/{id}/power/summary", web::get().to(handlers::group_power_summary).wrap(timeout(Fast)))
+        .route("/api/groups/{id}/power/on", web::post().to(handlers::group_power_on).wrap(timeout(Fast)))
+        .route("/api/groups/{id}/apply-config", web::post().to(handlers::group_apply_config))
+        .route("/api/group-apply-jobs/{id}", web::get().to(handlers::get_group_apply_job).wrap(timeout(Fast)))
+        .route(
+            "/api/groups/{id}/power/on/{operation_id}",
+            web::get().to(handlers::group_power_on_report).wrap(timeout(Fast)),
+        )
+        .route("/api/groups/{id}/firmware-update", web::post().to(handlers::group_firmware_update).wrap(timeout(Fast)))
+        .route(
+            "/api/groups/{id}/firmware-update/{operation_id}",
+            web::get().to(handlers::group_firmware_update_report).wrap(timeout(Fast)),
+        )
+        .route(
+            "/api/groups/{id}/firmware-update/{operation_id}/resume",
+            web::post().to(handlers::resume_group_firmware_update).wrap(timeout(Fast)),
+        )
+        .route(
+            "/api/groups/{id}/firmware-update/{operation_id}/cancel",
+            web::post().to(handlers::cancel_group_firmware_update).wrap(timeout(Fast)),
+        )
+        .route("/api/fleet/health", web::get().to(handlers::fleet_health).wrap(timeout(Normal)))
+        .route(
+            "/api/firmware/schedule-update",
+            web::post().to(handlers::schedule_firmware_update).wrap(timeout(Normal)),
+        )
+        .route(
+            "/api/firmware/pending-updates",
+            web::get().to(handlers::pending_firmware_updates).wrap(timeout(Normal)),
+        )
+        .route("/api/fleet/firmware", web::get().to(handlers::fleet_firmware).wrap(timeout(Fast)))
+        .route("/api/fleet/firmware/refresh", web::post().to(handlers::refresh_firmware_inventory))
+        .route("/api/fleet/firmware/baselines", web::put().to(handlers::set_firmware_baseline).wrap(timeout(Fast)))
+        .route(
+            "/api/compliance/profiles",
+            web::post().to(handlers::create_compliance_profile).wrap(timeout(Fast)),
+        )
+        .route("/api/compliance/firmware", web::get().to(handlers::firmware_compliance).wrap(timeout(Normal)))
+        .route("/api/health", web::get().to(handlers::health).wrap(timeout(Fast)))
+        .route("/share", web::get().to(handlers::share_page))
+        .route("/api/share/status", web::get().to(handlers::shared_status).wrap(timeout(Normal)))
+        .route("/api/health-reports", web::get().to(handlers::list_health_reports).wrap(timeout(Fast)))
+        .route("/api/health-reports/latest", web::get().to(handlers::latest_health_reports).wrap(timeout(Fast)))
+        .route("/api/shares", web::get().to(handlers::list_shares).wrap(timeout(Fast)))
+        .route("/api/shares", web::post().to(handlers::create_share).wrap(timeout(Fast)))
+        .route("/api/shares/{id}", web::delete().to(handlers::delete_share).wrap(timeout(Fast)))
+        .route("/api/tokens", web::get().to(handlers::list_tokens).wrap(timeout(Fast)))
+        .route("/api/tokens", web::post().to(handlers::create_token).wrap(timeout(Fast)))
+        .route("/api/tokens/{id}", web::delete().to(handlers::delete_token).wrap(timeout(Fast)))
+        .route("/api/admin/tokens/{id}/quota", web::put().to(handlers::set_token_quota).wrap(timeout(Fast)))
+        .route("/api/audit", web::get().to(handlers::list_audit).wrap(timeout(Fast)))
+        .route("/api/audit/statistics", web::get().to(handlers::audit_statistics).wrap(timeout(Fast)))
+        .route("/api/changes", web::get().to(handlers::list_changes).wrap(timeout(Fast)))
+        .route("/api/audit/export", web::get().to(handlers::audit_export).wrap(timeout(Normal)))
+        .route("/api/audit/export/csv", web::get().to(handlers::audit_export_csv).wrap(timeout(Normal)))
+        .route("/api/error-codes", web::get().to(handlers::error_codes).wrap(timeout(Fast)))
+        .route("/api/openapi.json", web::get().to(handlers::openapi_document).wrap(timeout(Fast)))
+        .route("/api/power/status", web::get().to(handlers::power_status).wrap(timeout(Normal)))
+        .route("/api/power/on", web::post().to(handlers::power_on_handler))
+        .route("/api/power/off", web::post().to(handlers::power_off_handler).wrap(timeout(Normal)))
+        .route("/api/power/shutdown", web::post().to(handlers::graceful_shutdown_handler))
+        .route("/api/power/restart", web::post().to(handlers::force_restart_handler).wrap(timeout(Normal)))
+        .route("/api/power/restart/graceful", web::post().to(handlers::graceful_restart_handler).wrap(timeout(Normal)))
+        .route("/api/power/graceful-restart", web::post().to(handlers::graceful_restart_handler).wrap(timeout(Normal)))
+        .route("/api/power/cycle", web::post().to(handlers::power_cycle_handler).wrap(timeout(Normal)))
+        .route("/api/power/nmi", web::post().to(handlers::nmi_handler).wrap(timeout(Normal)))
+        .route("/api/power/history", web::get().to(handlers::power_history).wrap(timeout(Fast)))
+        .route("/api/power/events/csv", web::get().to(handlers::power_events_csv).wrap(timeout(Normal)))
+        .route("/api/power/schedule-once", web::get().to(handlers::list_one_shot_schedules).wrap(timeout(Fast)))
+        .route("/api/power/schedule-once", web::post().to(handlers::create_one_shot_schedule).wrap(timeout(Fast)))
+        .route(
+            "/api/power/schedule-once/{id}",
+            web::delete().to(handlers::delete_one_shot_schedule).wrap(timeout(Fast)),
+        )
+        .route("/api/power/tariff-windows", web::get().to(handlers::list_tariff_windows).wrap(timeout(Fast)))
+        .route("/api/power/tariff-windows", web::post().to(handlers::create_tariff_window).wrap(timeout(Fast)))
+        .route(
+            "/api/power/tariff-windows/{id}",
+            web::delete().to(handlers::delete_tariff_window).wrap(timeout(Fast)),
+        )
+        .route("/api/power/tariff-status", web::get().to(handlers::tariff_status).wrap(timeout(Fast)))
+        .route("/api/operations/{id}", web::get().to(handlers::get_operation).wrap(timeout(Fast)))
+        .route("/api/summary/text", web::get().to(handlers::summary_text).wrap(timeout(Normal)))
+        .route("/api/idrac/test-connection", web::get().to(handlers::test_connection).wrap(timeout(Normal)))
+        .route("/api/idrac/logs", web::get().to(handlers::idrac_logs).wrap(timeout(Normal)))
+        .route("/api/idrac/alert-filters", web::put().to(handlers::configure_alert_filters).wrap(timeout(Normal)))
+        .route("/api/idrac/service-module", web::get().to(handlers::service_module_status).wrap(timeout(Normal)))
+        .route("/api/idrac/certificate", web::get().to(handlers::certificate_info).wrap(timeout(Normal)))
+        .route("/api/idrac/clock", web::get().to(handlers::clock_offset).wrap(timeout(Fast)))
+        .route("/api/idrac/time", web::get().to(handlers::get_idrac_time).wrap(timeout(Normal)))
+        .route("/api/idrac/time/sync", web::post().to(handlers::sync_idrac_time).wrap(timeout(Normal)))
+        .route("/api/idrac/stats", web::get().to(handlers::idrac_stats).wrap(timeout(Fast)))
+        .route("/api/idrac/factory-reset", web::post().to(handlers::factory_reset).wrap(timeout(Normal)))
+        .route("/api/idrac/oem-action", web::post().to(handlers::oem_action).wrap(timeout(Normal)))
+        .route("/api/idrac/licenses/activate", web::post().to(handlers::activate_license).wrap(timeout(Normal)))
+        .route("/api/idrac/users", web::get().to(handlers::list_idrac_users).wrap(timeout(Normal)))
+        .route("/api/idrac/users", web::post().to(handlers::create_idrac_user).wrap(timeout(Normal)))
+        .route("/api/idrac/users/{id}", web::patch().to(handlers::update_idrac_user).wrap(timeout(Normal)))
+        .route("/api/idrac/nic-mode", web::get().to(handlers::get_nic_mode).wrap(timeout(Normal)))
+        .route("/api/idrac/nic-mode", web::put().to(handlers::set_nic_mode).wrap(timeout(Normal)))
+        .route("/api/idrac/virtual-media/boot-once", web::post().to(handlers::virtual_media_boot_once))
+        .route("/api/idrac/self-subscribe", web::post().to(handlers::self_subscribe).wrap(timeout(Normal)))
+        .route("/api/events/ingest", web::post().to(handlers::ingest_events).wrap(timeout(Normal)))
+        .route(
+            "/api/telemetry/definitions",
+            web::post().to(handlers::create_telemetry_definition).wrap(timeout(Normal)),
+        )
+        .route(
+            "/api/telemetry/definitions/{id}",
+            web::delete().to(handlers::delete_telemetry_definition).wrap(timeout(Normal)),
+        )
+        .route("/api/telemetry/samples", web::get().to(handlers::list_metric_samples).wrap(timeout(Normal)))
+        .route("/api/telemetry/samples/csv", web::get().to(handlers::metric_samples_csv).wrap(timeout(Normal)))
+        .route("/api/events/stream", web::get().to(handlers::event_stream))
+        .route("/ws/power", web::get().to(handlers::power_websocket))
+        .route("/api/boot/order", web::get().to(handlers::get_boot_order).wrap(timeout(Normal)))
+        .route("/api/boot/order", web::put().to(handlers::set_boot_order).wrap(timeout(Normal)))
+        .route("/api/bios/boot-order", web::get().to(handlers::get_boot_order).wrap(timeout(Normal)))
+        .route("/api/bios/boot-order", web::put().to(handlers::set_boot_order).wrap(timeout(Normal)))
+        .route("/api/bios/attributes", web::patch().to(handlers::set_bios_attribute).wrap(timeout(Normal)))
+        .route("/api/bios/system-profile", web::get().to(handlers::get_system_profile).wrap(timeout(Normal)))
+        .route("/api/bios/system-profile", web::put().to(handlers::set_system_profile).wrap(timeout(Normal)))
+        .route("/api/bios/reset-to-defaults", web::post().to(handlers::reset_bios_to_defaults).wrap(timeout(Normal)))
+        .route("/api/bios/watchdog", web::put().to(handlers::configure_post_watchdog).wrap(timeout(Normal)))
+        .route("/api/{tail:.*}", web::method(Method::OPTIONS).to(handlers::cors_preflight).wrap(timeout(Fast)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+    use base64::Engine;
+    use std::sync::{Mutex, Once};
+
+    /// Every line logged while the tests run, as the app's logger writes it.
+    static CAPTURED_LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct CapturingLogger;
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            CAPTURED_LOGS.lock().unwrap().push(scrub::log_message(record));
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_logs() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(&CapturingLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    /// `(method, path)` of every `.route` in `routes`, read from this file
+    /// so a route added later is covered without touching the test.
+    fn registered_routes() -> Vec<(Method, String)> {
+        let pattern = regex::Regex::new(r#"\.route\(\s*"([^"]+)",\s*web::(get|post|put|patch|delete)\(\)"#).unwrap();
+        pattern
+            .captures_iter(include_str!("main.rs"))
+            .map(|c| (Method::from_bytes(c[2].to_uppercase().as_bytes()).unwrap(), c[1].to_string()))
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn every_route_gets_its_app_data() {
+        let (state, _dir) = testing::app_state();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::JsonConfig::default().error_handler(handlers::json_error_handler))
+                .configure(routes),
+        )
+        .await;
+
+        let routes = registered_routes();
+        assert!(routes.len() > 100, "only found {} routes", routes.len());
+
+        let placeholder = regex::Regex::new(r"\{[^}]+\}").unwrap();
+        for (method, path) in routes {
+            let uri = placeholder.replace_all(&path, "1").into_owned();
+            let req = test::TestRequest::default().method(method.clone()).uri(&uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            let body = test::read_body(resp).await;
+            assert!(
+                !String::from_utf8_lossy(&body).contains("application data is not configured"),
+                "{} {} is missing app data",
+                method,
+                path
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn power_read_token_cannot_power_off_but_the_session_can() {
+        let (state, _dir) = testing::app_state();
+        let username = testing::unique("operator");
+        let user_id = state.db.create_user(&username, "password123", None, database::UserRole::Admin).unwrap();
+        let token = tokens::generate_token();
+        state
+            .db
+            .create_api_token(user_id, "dashboard", &tokens::hash_token(&token), &["power:read".to_string()], None)
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .wrap(SessionMiddleware::builder(CookieSessionStore::default(), Key::generate()).build())
+                .configure(routes),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/power/off")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error_code"], "auth.missing_scope");
+
+        let login = test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(api::LoginRequest { username, password: "password123".to_string() })
+            .to_request();
+        let resp = test::call_service(&app, login).await;
+        assert!(resp.status().is_success(), "login answered {}", resp.status());
+        let cookie = resp.response().cookies().next().expect("session cookie").into_owned();
+
+        // The session passes authentication; the power action then fails
+        // against the unreachable iDRAC of the test state.
+        let req = test::TestRequest::post().uri("/api/power/off").cookie(cookie).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(![401, 403].contains(&resp.status().as_u16()), "session answered {}", resp.status());
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["error_code"].as_str().is_some_and(|code| code.starts_with("idrac.")), "{}", body);
+    }
+
+    #[actix_web::test]
+    async fn power_actions_are_checked_before_they_reach_the_idrac() {
+        let (state, _dir) = testing::app_state();
+        let user_id = state.db.create_user(&testing::unique("operator"), "password123", None, database::UserRole::Admin).unwrap();
+        let token = tokens::generate_token();
+        state
+            .db
+            .create_api_token(user_id, "power", &tokens::hash_token(&token), &["power:write".to_string()], None)
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .wrap(SessionMiddleware::builder(CookieSessionStore::default(), Key::generate()).build())
+                .configure(routes),
+        )
+        .await;
+        let post = |uri: &str, body: serde_json::Value| {
+            test::TestRequest::post()
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .set_json(body)
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, post("/api/power/nmi", serde_json::json!({}))).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error_code"], "validation.confirmation_required");
+
+        let resp = test::call_service(&app, post("/api/power/shutdown", serde_json::json!({ "escalate": "force" }))).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["field"], "escalate_after_secs", "{}", body);
+
+        let resp = test::call_service(&app, post("/api/power/cycle?server=rack-9", serde_json::json!({}))).await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error_code"], "server.not_found");
+
+        // Checks passed; the action then fails against the unreachable iDRAC.
+        let resp = test::call_service(&app, post("/api/power/nmi", serde_json::json!({ "confirm": true }))).await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["error_code"].as_str().is_some_and(|code| code.starts_with("idrac.")), "{}", body);
+    }
+
+    #[actix_web::test]
+    async fn readonly_sessions_cannot_read_the_audit_log() {
+        let (state, _dir) = testing::app_state();
+        let username = testing::unique("viewer");
+        state.db.create_user(&username, "password123", None, database::UserRole::ReadOnly).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .wrap(SessionMiddleware::builder(CookieSessionStore::default(), Key::generate()).build())
+                .configure(routes),
+        )
+        .await;
+
+        let login = test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(api::LoginRequest { username, password: "password123".to_string() })
+            .to_request();
+        let resp = test::call_service(&app, login).await;
+        assert!(resp.status().is_success(), "login answered {}", resp.status());
+        let cookie = resp.response().cookies().next().expect("session cookie").into_owned();
+
+        for uri in ["/api/audit", "/api/audit/export", "/api/audit/export/csv", "/api/audit/statistics", "/api/changes"] {
+            let req = test::TestRequest::get().uri(uri).cookie(cookie.clone()).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 403, "{} answered {}", uri, resp.status());
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["error_code"], "auth.insufficient_role", "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn server_grants_let_readonly_users_power_their_server_until_they_expire() {
+        let (state, _dir) = testing::app_state();
+        let db = state.db.clone();
+        let vendor = testing::unique("vendor");
+        let vendor_id = db.create_user(&vendor, "password123", None, database::UserRole::ReadOnly).unwrap();
+        let admin_id = db.create_user(&testing::unique("admin"), "password123", None, database::UserRole::Admin).unwrap();
+        let admin_token = tokens::generate_token();
+        db.create_api_token(admin_id, "grants", &tokens::hash_token(&admin_token), &["admin".to_string()], None).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .wrap(SessionMiddleware::builder(CookieSessionStore::default(), Key::generate()).build())
+                .configure(routes),
+        )
+        .await;
+        let admin = |req: test::TestRequest| req.insert_header(("Authorization", format!("Bearer {}", admin_token))).to_request();
+        let grant_uri = format!("/api/users/{}/grants/{}", vendor_id, servers::DEFAULT_SERVER_ALIAS);
+        let set_grant = |hours: i64| {
+            let expires_at = (chrono::Utc::now() + chrono::Duration::hours(hours)).to_rfc3339();
+            admin(test::TestRequest::put().uri(&grant_uri).set_json(serde_json::json!({ "expires_at": expires_at })))
+        };
+
+        let login = test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(api::LoginRequest { username: vendor.clone(), password: "password123".to_string() })
+            .to_request();
+        let resp = test::call_service(&app, login).await;
+        assert!(resp.status().is_success(), "login answered {}", resp.status());
+        let cookie = resp.response().cookies().next().expect("session cookie").into_owned();
+        let power_off = |uri: &str| test::TestRequest::post().uri(uri).cookie(cookie.clone()).to_request();
+
+        let resp = test::call_service(&app, power_off("/api/power/off")).await;
+        assert_eq!(resp.status(), 403);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error_code"], "auth.insufficient_role");
+
+        let resp = test::call_service(&app, set_grant(1)).await;
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["grant"]["username"], vendor.as_str());
+
+        // The grant gets the vendor past authorization; the power action
+        // then fails against the unreachable iDRAC of the test state.
+        let resp = test::call_service(&app, power_off("/api/power/off")).await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["error_code"].as_str().is_some_and(|code| code.starts_with("idrac.")), "{}", body);
+        let resp = test::call_service(&app, power_off("/api/power/off?server=rack-9")).await;
+        assert_eq!(resp.status(), 403, "a grant only covers its own server");
+
+        let resp = test::call_service(&app, set_grant(-1)).await;
+        assert_eq!(resp.status(), 200);
+        let resp = test::call_service(&app, power_off("/api/power/off")).await;
+        assert_eq!(resp.status(), 403, "an expired grant still let the vendor in");
+
+        let listed = |query: &str| admin(test::TestRequest::get().uri(&format!("/api/users/{}/grants{}", vendor_id, query)));
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, listed("")).await).await;
+        assert_eq!(body["grants"], serde_json::json!([]));
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, listed("?include_expired=true")).await).await;
+        assert_eq!(body["grants"].as_array().map(Vec::len), Some(1));
+        let expirations = admin(test::TestRequest::get().uri("/api/admin/expirations"));
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, expirations).await).await;
+        assert_eq!(body["recent_grants"][0]["username"], vendor.as_str(), "{}", body);
+
+        let unknown = admin(test::TestRequest::put().uri(&format!("/api/users/{}/grants/rack-9", vendor_id)).set_json(serde_json::json!({})));
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, unknown).await).await;
+        assert_eq!(body["error_code"], "server.not_found");
+        let revoke = || admin(test::TestRequest::delete().uri(&grant_uri));
+        assert_eq!(test::call_service(&app, revoke()).await.status(), 200);
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, revoke()).await).await;
+        assert_eq!(body["error_code"], "grant.not_found");
+
+        let audited: Vec<String> = db
+            .list_audit_page(&database::Keyset::First, 100, false)
+            .unwrap()
+            .into_iter()
+            .filter(|entry| entry.action.starts_with("ServerGrant"))
+            .map(|entry| entry.action)
+            .collect();
+        assert_eq!(audited, ["ServerGrantUpdate", "ServerGrantUpdate", "ServerGrantRevoke"]);
+    }
+
+    #[actix_web::test]
+    async fn credentials_never_reach_the_logs_or_the_audit_log() {
+        capture_logs();
+        let (state, _dir) = testing::app_state();
+        let db = state.db.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::JsonConfig::default().error_handler(handlers::json_error_handler))
+                .wrap(middleware::RequestLogger::new(1.0, std::time::Duration::from_secs(60)))
+                .wrap(SessionMiddleware::builder(CookieSessionStore::default(), Key::generate()).build())
+                .configure(routes),
+        )
+        .await;
+
+        let password = "Wrong-Hunter2-Pass";
+        let bearer = tokens::generate_token();
+        let basic = base64::engine::general_purpose::STANDARD.encode("root:calvin-secret");
+        let break_glass_token = tokens::generate_token();
+
+        let login = test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(api::LoginRequest { username: testing::unique("intruder"), password: password.to_string() })
+            .to_request();
+        assert_eq!(test::call_service(&app, login).await.status(), 401);
+
+        // Deserialization errors quote the body they choked on.
+        let malformed = test::TestRequest::post()
+            .uri("/api/login")
+            .insert_header(("Content-Type", "application/json"))
+            .set_payload(format!(r#"{{"username": 7, "password": "{}"}}"#, password))
+            .to_request();
+        let resp = test::call_service(&app, malformed).await;
+        assert_eq!(resp.status(), 400);
+        let body = String::from_utf8_lossy(&test::read_body(resp).await).into_owned();
+        assert!(!body.contains(password), "{}", body);
+
+        for authorization in [format!("Bearer {}", bearer), format!("Basic {}", basic)] {
+            let req = test::TestRequest::get()
+                .uri("/api/servers")
+                .insert_header(("Authorization", authorization))
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), 401);
+        }
+
+        let req = test::TestRequest::get().uri(&format!("/api/break-glass/{}", break_glass_token)).to_request();
+        assert!(test::call_service(&app, req).await.status().is_client_error());
+
+        let logs = CAPTURED_LOGS.lock().unwrap().join("\n");
+        assert!(logs.contains("/api/break-glass/[REDACTED]"), "the request log was not captured:\n{}", logs);
+        let audit = serde_json::to_string(&db.list_audit_page(&database::Keyset::First, 1000, true).unwrap()).unwrap();
+        for secret in [password, bearer.as_str(), basic.as_str(), break_glass_token.as_str()] {
+            assert!(!logs.contains(secret), "{} was logged", secret);
+            assert!(!audit.contains(secret), "{} was audited", secret);
+        }
+    }
 }
@@ -0,0 +1,597 @@
+use actix_session::SessionExt;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::Method;
+use actix_web::{web, Error, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+use log::{info, warn};
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::api::ApiResponse;
+use crate::audit::AuditAction;
+use crate::break_glass;
+use crate::errors::ErrorCode;
+use crate::scrub;
+use crate::shares;
+use crate::state::AppState;
+
+/// Access logging that only records a random sample of successful requests.
+/// Errors (status >= 400) and slow requests are always logged.
+#[derive(Clone)]
+pub struct RequestLogger {
+    sample_rate: f64,
+    slow_threshold: Duration,
+}
+
+impl RequestLogger {
+    pub fn new(sample_rate: f64, slow_threshold: Duration) -> Self {
+        RequestLogger {
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            slow_threshold,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestLogger
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestLoggerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestLoggerMiddleware {
+            service: Rc::new(service),
+            config: self.clone(),
+        }))
+    }
+}
+
+pub struct RequestLoggerMiddleware<S> {
+    service: Rc<S>,
+    config: RequestLogger,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestLoggerMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let path = scrub::scrub_text(req.path());
+        let peer = req
+            .connection_info()
+            .realip_remote_addr()
+            .unwrap_or("-")
+            .to_string();
+        let config = self.config.clone();
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let res = service.call(req).await?;
+            let elapsed = started.elapsed();
+            let status = res.status().as_u16();
+            let slow = elapsed >= config.slow_threshold;
+
+            if status >= 400 || slow {
+                warn!(
+                    "{} \"{} {}\" {} {:.1}ms{}",
+                    peer,
+                    method,
+                    path,
+                    status,
+                    elapsed.as_secs_f64() * 1000.0,
+                    if slow { " (slow)" } else { "" }
+                );
+            } else if config.sample_rate >= 1.0 || rand::random::<f64>() < config.sample_rate {
+                info!(
+                    "{} \"{} {}\" {} {:.1}ms",
+                    peer,
+                    method,
+                    path,
+                    status,
+                    elapsed.as_secs_f64() * 1000.0
+                );
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+/// Adds `Access-Control-Allow-Origin` to responses for requests from an
+/// origin listed in `CORS_ALLOWED_ORIGINS`. Preflight requests are answered
+/// by `handlers::cors_preflight`.
+#[derive(Clone, Copy)]
+pub struct Cors;
+
+impl<S, B> Transform<S, ServiceRequest> for Cors
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CorsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CorsMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct CorsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CorsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let allowed_origin = req
+            .headers()
+            .get(header::ORIGIN)
+            .filter(|origin| {
+                let config = req.app_data::<web::Data<AppState>>().map(|state| state.config.clone());
+                match (config, origin.to_str()) {
+                    (Some(config), Ok(origin)) => config.cors_origin_allowed(origin),
+                    _ => false,
+                }
+            })
+            .cloned();
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let mut res = service.call(req).await?;
+            if let Some(origin) = allowed_origin {
+                let headers = res.headers_mut();
+                headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+                headers.append(header::VARY, HeaderValue::from_static("Origin"));
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Rejects requests addressed to a host not listed in `ALLOWED_HOSTS`, so
+/// a forged `Host` (or `X-Forwarded-Host` / `Forwarded`) header never ends
+/// up in a generated URL.
+#[derive(Clone, Copy)]
+pub struct HostGuard;
+
+impl<S, B> Transform<S, ServiceRequest> for HostGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = HostGuardMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HostGuardMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct HostGuardMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for HostGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let rejected = req.app_data::<web::Data<AppState>>().and_then(|state| {
+            if state.config.allowed_hosts.is_empty() {
+                return None;
+            }
+            // The `Host` header, and the host a proxy header claims, which
+            // is what URLs are built from.
+            let host_header = req.headers().get(header::HOST).map(|host| host.to_str().unwrap_or_default().to_string());
+            let forwarded = req.connection_info().host().to_string();
+            [host_header.unwrap_or_default(), forwarded]
+                .into_iter()
+                .find(|host| !state.config.host_allowed(host))
+        });
+        if let Some(host) = rejected {
+            warn!("Rejected request for host {:?} to {}", host, req.path());
+            let response = HttpResponse::BadRequest().json(ApiResponse::error(
+                ErrorCode::ValidationHostNotAllowed,
+                "Host not allowed",
+            ));
+            return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+        }
+
+        let service = self.service.clone();
+        Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) })
+    }
+}
+
+/// Ends sessions that outlived the session TTL or whose user has expired,
+/// been disabled or had their sessions invalidated since logging in, so
+/// handlers see them as logged out. Break-glass sessions also end when their
+/// grant does, and every change made through one is audited.
+/// Must be wrapped inside the session middleware.
+#[derive(Clone, Copy)]
+pub struct SessionGuard;
+
+impl<S, B> Transform<S, ServiceRequest> for SessionGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SessionGuardMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SessionGuardMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct SessionGuardMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for SessionGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let session = req.get_session();
+        if let (Ok(Some(user_id)), Some(state)) = (session.get::<i64>("user_id"), req.app_data::<web::Data<AppState>>()) {
+            // Sessions created before generations existed count as generation 0.
+            let generation = session.get::<i64>("session_generation").ok().flatten().unwrap_or(0);
+            let ttl_secs = state.retention.read().unwrap().sessions_ttl_hours as i64 * 3600;
+            let timed_out = session
+                .get::<i64>("issued_at")
+                .ok()
+                .flatten()
+                .is_some_and(|issued_at| chrono::Utc::now().timestamp() - issued_at > ttl_secs);
+
+            let break_glass_expired = session
+                .get::<i64>(break_glass::SESSION_EXPIRES_KEY)
+                .ok()
+                .flatten()
+                .is_some_and(|expires_at| chrono::Utc::now().timestamp() >= expires_at);
+
+            match state.db.get_user_by_id(user_id) {
+                Ok(Some(_)) if timed_out => {
+                    info!("Ending session of user {}: older than the session TTL", user_id);
+                    session.purge();
+                }
+                Ok(Some(_)) if break_glass_expired => {
+                    info!("Ending break-glass session of user {}: grant expired", user_id);
+                    session.purge();
+                }
+                Ok(Some(user)) if user.is_active() && user.session_generation == generation => {}
+                Ok(_) => {
+                    info!("Ending session of user {}: account expired, disabled or signed out", user_id);
+                    session.purge();
+                }
+                Err(e) => warn!("Failed to check session of user {}: {}", user_id, e),
+            }
+        }
+
+        let break_glass_grant = session
+            .get::<i64>(break_glass::SESSION_GRANT_KEY)
+            .ok()
+            .flatten()
+            .zip(session.get::<i64>("user_id").ok().flatten())
+            .filter(|_| !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS));
+        let Some((grant_id, user_id)) = break_glass_grant else {
+            let service = self.service.clone();
+            return Box::pin(async move { service.call(req).await });
+        };
+
+        let state = req.app_data::<web::Data<AppState>>().cloned();
+        let method = req.method().to_string();
+        let path = scrub::scrub_text(req.path());
+        let service = self.service.clone();
+        Box::pin(async move {
+            let res = service.call(req).await?;
+            if let Some(state) = state {
+                let status = res.status();
+                let result = if status.is_client_error() || status.is_server_error() {
+                    Err(format!("{} {} returned {}", method, path, status.as_u16()))
+                } else {
+                    Ok(format!("{} {}", method, path))
+                };
+                let details = serde_json::json!({
+                    "grant_id": grant_id,
+                    "method": method,
+                    "path": path,
+                    "status": status.as_u16(),
+                });
+                state.audit_with_details(Some(user_id), AuditAction::BreakGlassRequest, "app", &result, &details);
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Keeps share link credentials view-only: a request carrying one is
+/// refused unless it reads `shares::STATUS_PATH`, whatever endpoints exist,
+/// so no handler has to remember to reject them.
+#[derive(Clone, Copy)]
+pub struct ShareGuard;
+
+impl<S, B> Transform<S, ServiceRequest> for ShareGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ShareGuardMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ShareGuardMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct ShareGuardMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ShareGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let is_share = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(shares::credential)
+            .is_some();
+        if is_share && !(req.method() == Method::GET && req.path() == shares::STATUS_PATH) {
+            let response = HttpResponse::Forbidden().json(ApiResponse::error(
+                ErrorCode::AuthShareReadOnly,
+                "Share links can only read the shared status",
+            ));
+            return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+        }
+
+        let service = self.service.clone();
+        Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) })
+    }
+}
+
+/// Time budget of a route, assigned when the route is registered.
+#[derive(Debug, Clone, Copy)]
+pub enum RouteClass {
+    /// Answered from the database or memory.
+    Fast,
+    /// A few round trips to an iDRAC.
+    Normal,
+    /// Work spanning many requests, like importing and testing servers.
+    Long,
+}
+
+impl RouteClass {
+    pub fn budget(self) -> Duration {
+        match self {
+            RouteClass::Fast => Duration::from_secs(5),
+            RouteClass::Normal => Duration::from_secs(30),
+            RouteClass::Long => Duration::from_secs(120),
+        }
+    }
+}
+
+/// Answers 503 with `operation.timeout` once a handler overruns its
+/// route's budget. The handler future is dropped, which cancels any
+/// iDRAC request it was waiting on. Wrap individual routes with
+/// `timeout(class)`; endpoints that hand work to a background operation
+/// and answer 202 are left unwrapped.
+#[derive(Clone, Copy)]
+pub struct RequestTimeout {
+    class: RouteClass,
+}
+
+pub fn timeout(class: RouteClass) -> RequestTimeout {
+    RequestTimeout { class }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTimeout
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestTimeoutMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTimeoutMiddleware {
+            service: Rc::new(service),
+            budget: self.class.budget(),
+        }))
+    }
+}
+
+pub struct RequestTimeoutMiddleware<S> {
+    service: Rc<S>,
+    budget: Duration,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTimeoutMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let budget = self.budget;
+        let route = format!(
+            "{} {}",
+            req.method(),
+            req.match_pattern().unwrap_or_else(|| req.path().to_string())
+        );
+        let state = req.app_data::<web::Data<AppState>>().cloned();
+        let request = req.request().clone();
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            match tokio::time::timeout(budget, service.call(req)).await {
+                Ok(res) => Ok(res?.map_into_left_body()),
+                Err(_) => {
+                    warn!("{} timed out after {}s", route, budget.as_secs());
+                    if let Some(state) = state {
+                        *state.metrics.route_timeouts.lock().unwrap().entry(route).or_insert(0) += 1;
+                    }
+                    let response = HttpResponse::ServiceUnavailable().json(ApiResponse::error(
+                        ErrorCode::OperationTimeout,
+                        format!("Request did not complete within {}s", budget.as_secs()),
+                    ));
+                    Ok(ServiceResponse::new(request, response).map_into_right_body())
+                }
+            }
+        })
+    }
+}
+
+/// Paths that may still be posted to while the database is read-only.
+const STANDBY_ALLOWED_PATHS: &[&str] = &["/api/login", "/api/logout", "/api/admin/promote"];
+
+/// Rejects state-changing requests while this instance is a read-only
+/// standby, or while its database refuses writes (see
+/// `Database::write_failure`). Reads keep working so dashboards stay
+/// available.
+#[derive(Clone, Copy)]
+pub struct StandbyGuard;
+
+impl<S, B> Transform<S, ServiceRequest> for StandbyGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = StandbyGuardMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(StandbyGuardMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct StandbyGuardMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for StandbyGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let is_write = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+        if is_write && !STANDBY_ALLOWED_PATHS.contains(&req.path()) {
+            let refusal = req.app_data::<web::Data<AppState>>().and_then(|state| {
+                if state.db.is_read_only() {
+                    return Some(HttpResponse::ServiceUnavailable().json(ApiResponse::error(
+                        ErrorCode::StandbyReadOnly,
+                        "This instance is a read-only standby",
+                    )));
+                }
+                let failure = state.db.write_failure()?;
+                let mut response = if failure.kind == "disk_full" {
+                    HttpResponse::InsufficientStorage()
+                } else {
+                    HttpResponse::ServiceUnavailable()
+                };
+                Some(response.json(ApiResponse::error(
+                    ErrorCode::StorageDegraded,
+                    format!(
+                        "The database has refused writes since {} UTC ({}); reads keep working and writes resume once it accepts them again",
+                        failure.since, failure.error
+                    ),
+                )))
+            });
+            if let Some(response) = refusal {
+                return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+            }
+        }
+
+        let service = self.service.clone();
+        Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) })
+    }
+}
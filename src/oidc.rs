@@ -0,0 +1,495 @@
+//! Optional single sign-on through an OpenID Connect provider, with the
+//! authorization code flow and PKCE. ID tokens are checked here against the
+//! provider's published keys; no token is ever logged.
+
+use base64::Engine;
+use log::{info, warn};
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+use rand::RngCore;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::secret::SecretString;
+use crate::tokens::TokenScope;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long discovery metadata and signing keys are used before they are
+/// fetched again. Keys are also fetched again when a token names an unknown
+/// one, at most once a `KEYS_REFETCH_INTERVAL`.
+const METADATA_TTL: Duration = Duration::from_secs(3600);
+const KEYS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+/// How long the user may take at the provider before the callback is refused.
+pub const LOGIN_TIMEOUT_SECS: i64 = 600;
+/// Allowed difference between our clock and the provider's.
+const CLOCK_LEEWAY_SECS: i64 = 60;
+
+/// Provider and client registration; present when `OIDC_ISSUER` is set.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    issuer: String,
+    client_id: String,
+    client_secret: SecretString,
+    redirect_url: String,
+    scopes: String,
+    /// ID token claim holding the local username.
+    username_claim: String,
+    /// Scopes of sessions of accounts provisioned through OIDC.
+    pub default_scopes: Vec<TokenScope>,
+    /// Refuse password logins and registration.
+    pub only: bool,
+    /// Send the browser to the provider's end-session endpoint on logout.
+    end_session: bool,
+    post_logout_redirect_url: Option<String>,
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn flag(name: &str) -> bool {
+    matches!(env(name).map(|v| v.to_ascii_lowercase()).as_deref(), Some("true" | "1" | "yes"))
+}
+
+impl OidcConfig {
+    /// `OIDC_ISSUER`, `OIDC_CLIENT_ID`, `OIDC_CLIENT_SECRET` and
+    /// `OIDC_REDIRECT_URL`, which defaults to the callback under
+    /// `self_url`. `None` when OIDC is not configured.
+    pub fn from_env(self_url: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(issuer) = env("OIDC_ISSUER") else {
+            return Ok(None);
+        };
+        let client_id = env("OIDC_CLIENT_ID").ok_or("OIDC_ISSUER is set but OIDC_CLIENT_ID is not")?;
+        let client_secret = env("OIDC_CLIENT_SECRET")
+            .map(SecretString::from)
+            .ok_or("OIDC_ISSUER is set but OIDC_CLIENT_SECRET is not")?;
+        let redirect_url = env("OIDC_REDIRECT_URL")
+            .or_else(|| self_url.map(|url| format!("{}/api/auth/oidc/callback", url)))
+            .ok_or("OIDC_ISSUER is set but neither OIDC_REDIRECT_URL nor SELF_URL is")?;
+        Url::parse(&redirect_url).map_err(|e| format!("OIDC_REDIRECT_URL is not a URL: {}", e))?;
+        let default_scopes = env("OIDC_DEFAULT_SCOPES")
+            .unwrap_or_else(|| "power:read,inventory:read".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(|scope| TokenScope::parse(scope).ok_or_else(|| format!("OIDC_DEFAULT_SCOPES has unknown scope '{}'", scope)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if default_scopes.is_empty() {
+            return Err("OIDC_DEFAULT_SCOPES must name at least one scope".to_string());
+        }
+
+        Ok(Some(OidcConfig {
+            // The issuer is compared with the `iss` claim as a string.
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret,
+            redirect_url,
+            scopes: env("OIDC_SCOPES").unwrap_or_else(|| "openid profile email".to_string()),
+            username_claim: env("OIDC_USERNAME_CLAIM").unwrap_or_else(|| "email".to_string()),
+            default_scopes,
+            only: flag("OIDC_ONLY"),
+            end_session: flag("OIDC_END_SESSION"),
+            post_logout_redirect_url: env("OIDC_POST_LOGOUT_REDIRECT_URL").or_else(|| self_url.map(|url| format!("{}/", url))),
+        }))
+    }
+}
+
+/// Why a single sign-on login failed.
+pub enum OidcFailure {
+    /// The provider could not be reached or answered with an error.
+    Unreachable(String),
+    /// The callback, the code or the ID token was not accepted.
+    Rejected(String),
+}
+
+/// What the callback needs to finish a login, kept in the session while
+/// the user is at the provider.
+#[derive(Serialize, Deserialize)]
+pub struct PendingLogin {
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    pub started_at: i64,
+}
+
+/// The user an accepted ID token names.
+pub struct OidcIdentity {
+    pub subject: String,
+    /// Value of the configured username claim.
+    pub username: String,
+}
+
+#[derive(Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    end_session_endpoint: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+struct Cached<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+/// Talks to the provider, caching its metadata and signing keys.
+pub struct OidcClient {
+    pub config: OidcConfig,
+    http: Client,
+    metadata: RwLock<Option<Cached<ProviderMetadata>>>,
+    keys: RwLock<Option<Cached<Vec<Jwk>>>>,
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn base64url(value: &str) -> Result<Vec<u8>, String> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|e| format!("invalid base64url: {}", e))
+}
+
+/// Compare a state or nonce in constant time.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && openssl::memcmp::eq(a.as_bytes(), b.as_bytes())
+}
+
+fn unreachable(context: &str, e: reqwest::Error) -> OidcFailure {
+    // The context names the request, so the URL is left out.
+    OidcFailure::Unreachable(format!("{}: {}", context, e.without_url()))
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        OidcClient {
+            config,
+            http: Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("failed to build OIDC HTTP client"),
+            metadata: RwLock::new(None),
+            keys: RwLock::new(None),
+        }
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, context: &str, url: &str) -> Result<T, OidcFailure> {
+        let response = self.http.get(url).send().await.map_err(|e| unreachable(context, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(OidcFailure::Unreachable(format!("{}: HTTP {}", context, status.as_u16())));
+        }
+        response.json().await.map_err(|e| unreachable(context, e))
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata, OidcFailure> {
+        if let Some(cached) = self.metadata.read().unwrap().as_ref() {
+            if cached.fetched_at.elapsed() < METADATA_TTL {
+                return Ok(cached.value.clone());
+            }
+        }
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+        let metadata: ProviderMetadata = self.get_json("discovery", &url).await?;
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer {
+            return Err(OidcFailure::Unreachable(format!(
+                "discovery names issuer {} rather than {}",
+                metadata.issuer, self.config.issuer
+            )));
+        }
+        *self.metadata.write().unwrap() = Some(Cached {
+            value: metadata.clone(),
+            fetched_at: Instant::now(),
+        });
+        Ok(metadata)
+    }
+
+    /// Signing keys, fetched again when stale or when `kid` is not among
+    /// them, so that key rotation at the provider needs no restart.
+    async fn keys(&self, jwks_uri: &str, kid: Option<&str>) -> Result<Vec<Jwk>, OidcFailure> {
+        if let Some(cached) = self.keys.read().unwrap().as_ref() {
+            let known = kid.is_none_or(|kid| cached.value.iter().any(|key| key.kid.as_deref() == Some(kid)));
+            let age = cached.fetched_at.elapsed();
+            if age < METADATA_TTL && (known || age < KEYS_REFETCH_INTERVAL) {
+                return Ok(cached.value.clone());
+            }
+        }
+        let set: JwkSet = self.get_json("signing keys", jwks_uri).await?;
+        info!("Fetched {} OIDC signing key(s)", set.keys.len());
+        *self.keys.write().unwrap() = Some(Cached {
+            value: set.keys.clone(),
+            fetched_at: Instant::now(),
+        });
+        Ok(set.keys)
+    }
+
+    /// The provider URL to send the browser to, and what to keep in the
+    /// session until it comes back.
+    pub async fn start_login(&self) -> Result<(String, PendingLogin), OidcFailure> {
+        let metadata = self.metadata().await?;
+        let pending = PendingLogin {
+            state: random_token(),
+            nonce: random_token(),
+            code_verifier: random_token(),
+            started_at: chrono::Utc::now().timestamp(),
+        };
+        let challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(openssl::sha::sha256(pending.code_verifier.as_bytes()));
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", pending.state.as_str()),
+                ("nonce", pending.nonce.as_str()),
+                ("code_challenge", challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcFailure::Unreachable(format!("authorization endpoint is not a URL: {}", e)))?;
+        Ok((url.to_string(), pending))
+    }
+
+    /// Exchange the code the provider sent back and check the ID token.
+    pub async fn finish_login(&self, pending: &PendingLogin, state: &str, code: &str) -> Result<OidcIdentity, OidcFailure> {
+        if !same(&pending.state, state) {
+            return Err(OidcFailure::Rejected("state does not match the login started in this session".to_string()));
+        }
+        if chrono::Utc::now().timestamp() - pending.started_at > LOGIN_TIMEOUT_SECS {
+            return Err(OidcFailure::Rejected(format!("login took longer than {} seconds", LOGIN_TIMEOUT_SECS)));
+        }
+
+        let metadata = self.metadata().await?;
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(self.config.client_secret.expose()))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("code_verifier", pending.code_verifier.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| unreachable("token endpoint", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            // Only the error fields; the body is not logged as a whole.
+            let reason = match response.json::<TokenErrorResponse>().await {
+                Ok(body) => format!("{}: {}", body.error, body.error_description.unwrap_or_default()),
+                Err(_) => "no error description".to_string(),
+            };
+            return Err(if status.is_client_error() {
+                OidcFailure::Rejected(format!("token endpoint refused the code (HTTP {}): {}", status.as_u16(), reason))
+            } else {
+                OidcFailure::Unreachable(format!("token endpoint returned HTTP {}: {}", status.as_u16(), reason))
+            });
+        }
+        let tokens: TokenResponse = response.json().await.map_err(|e| unreachable("token endpoint", e))?;
+        let id_token = tokens
+            .id_token
+            .ok_or_else(|| OidcFailure::Rejected("token endpoint returned no ID token".to_string()))?;
+
+        let claims = self.verify_id_token(&metadata, &id_token).await?;
+        self.check_claims(&claims, &pending.nonce).map_err(OidcFailure::Rejected)?;
+
+        let subject = claims.get("sub").and_then(Value::as_str).unwrap_or_default().to_string();
+        let claim = self.config.username_claim.as_str();
+        if claim == "email" && claims.get("email_verified").and_then(Value::as_bool) == Some(false) {
+            return Err(OidcFailure::Rejected(format!("email of subject {} is not verified", subject)));
+        }
+        let username = claims
+            .get(claim)
+            .and_then(Value::as_str)
+            .filter(|value| !value.trim().is_empty())
+            .ok_or_else(|| OidcFailure::Rejected(format!("ID token of subject {} has no '{}' claim", subject, claim)))?
+            .to_string();
+        Ok(OidcIdentity { subject, username })
+    }
+
+    /// Check the signature of a compact JWS and return its claims.
+    async fn verify_id_token(&self, metadata: &ProviderMetadata, token: &str) -> Result<Map<String, Value>, OidcFailure> {
+        let rejected = |reason: String| OidcFailure::Rejected(format!("ID token {}", reason));
+        let parts: Vec<&str> = token.split('.').collect();
+        let [header, payload, signature] = parts[..] else {
+            return Err(rejected(format!("has {} parts rather than 3", parts.len())));
+        };
+        let header: JwtHeader = base64url(header)
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+            .map_err(|e| rejected(format!("header is unreadable: {}", e)))?;
+
+        let keys = self.keys(&metadata.jwks_uri, header.kid.as_deref()).await?;
+        let key = keys
+            .iter()
+            .filter(|key| key.key_use.as_deref().is_none_or(|key_use| key_use == "sig"))
+            .find(|key| match &header.kid {
+                Some(kid) => key.kid.as_deref() == Some(kid),
+                None => key_type(&header.alg) == Some(key.kty.as_str()),
+            })
+            .ok_or_else(|| rejected(format!("names signing key {:?}, which the provider does not publish", header.kid)))?;
+        let signature = base64url(signature).map_err(|e| rejected(format!("signature is unreadable: {}", e)))?;
+        let signing_input = format!("{}.{}", parts[0], payload);
+        verify_signature(&header.alg, key, signing_input.as_bytes(), &signature)
+            .map_err(|e| rejected(format!("signature ({}, key {:?}) is not valid: {}", header.alg, key.kid, e)))?;
+
+        base64url(payload)
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+            .map_err(|e| rejected(format!("claims are unreadable: {}", e)))
+    }
+
+    fn check_claims(&self, claims: &Map<String, Value>, nonce: &str) -> Result<(), String> {
+        let now = chrono::Utc::now().timestamp();
+        let issuer = claims.get("iss").and_then(Value::as_str).unwrap_or_default();
+        if issuer.trim_end_matches('/') != self.config.issuer {
+            return Err(format!("ID token was issued by '{}' rather than {}", issuer, self.config.issuer));
+        }
+        let audiences: Vec<&str> = match claims.get("aud") {
+            Some(Value::String(audience)) => vec![audience.as_str()],
+            Some(Value::Array(audiences)) => audiences.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !audiences.contains(&self.config.client_id.as_str()) {
+            return Err(format!("ID token is for {:?} rather than client {}", audiences, self.config.client_id));
+        }
+        if audiences.len() > 1 && claims.get("azp").and_then(Value::as_str) != Some(self.config.client_id.as_str()) {
+            return Err(format!("ID token has several audiences and was not issued to client {}", self.config.client_id));
+        }
+        match claims.get("exp").and_then(Value::as_i64) {
+            Some(exp) if exp + CLOCK_LEEWAY_SECS > now => {}
+            Some(exp) => return Err(format!("ID token expired {} seconds ago", now - exp)),
+            None => return Err("ID token has no expiry".to_string()),
+        }
+        if claims.get("iat").and_then(Value::as_i64).is_some_and(|iat| iat > now + CLOCK_LEEWAY_SECS) {
+            return Err("ID token was issued in the future; check the clocks".to_string());
+        }
+        let token_nonce = claims.get("nonce").and_then(Value::as_str).unwrap_or_default();
+        if !same(token_nonce, nonce) {
+            return Err("ID token nonce does not match the login started in this session".to_string());
+        }
+        Ok(())
+    }
+
+    /// Where to send the browser after a local logout, when
+    /// `OIDC_END_SESSION` is set and the provider has an end-session
+    /// endpoint.
+    pub async fn end_session_url(&self) -> Option<String> {
+        if !self.config.end_session {
+            return None;
+        }
+        let metadata = match self.metadata().await {
+            Ok(metadata) => metadata,
+            Err(OidcFailure::Unreachable(reason) | OidcFailure::Rejected(reason)) => {
+                warn!("Skipping OIDC end-session: {}", reason);
+                return None;
+            }
+        };
+        let endpoint = metadata.end_session_endpoint?;
+        let mut params = vec![("client_id", self.config.client_id.as_str())];
+        if let Some(url) = &self.config.post_logout_redirect_url {
+            params.push(("post_logout_redirect_uri", url.as_str()));
+        }
+        Url::parse_with_params(&endpoint, &params).ok().map(|url| url.to_string())
+    }
+}
+
+/// JWK `kty` of the keys an algorithm is used with.
+fn key_type(alg: &str) -> Option<&'static str> {
+    match alg {
+        "RS256" | "RS384" | "RS512" => Some("RSA"),
+        "ES256" | "ES384" => Some("EC"),
+        _ => None,
+    }
+}
+
+fn verify_signature(alg: &str, key: &Jwk, input: &[u8], signature: &[u8]) -> Result<(), String> {
+    let component = |value: &Option<String>, name: &str| -> Result<BigNum, String> {
+        let bytes = base64url(value.as_deref().ok_or(format!("key has no '{}'", name))?)?;
+        BigNum::from_slice(&bytes).map_err(|e| e.to_string())
+    };
+    let (digest, pkey, signature): (MessageDigest, PKey<Public>, Vec<u8>) = match (alg, key.kty.as_str()) {
+        ("RS256" | "RS384" | "RS512", "RSA") => {
+            let rsa = Rsa::from_public_components(component(&key.n, "n")?, component(&key.e, "e")?).map_err(|e| e.to_string())?;
+            let digest = match alg {
+                "RS256" => MessageDigest::sha256(),
+                "RS384" => MessageDigest::sha384(),
+                _ => MessageDigest::sha512(),
+            };
+            (digest, PKey::from_rsa(rsa).map_err(|e| e.to_string())?, signature.to_vec())
+        }
+        ("ES256" | "ES384", "EC") => {
+            let (curve, digest, size) = match (alg, key.crv.as_deref()) {
+                ("ES256", Some("P-256")) => (Nid::X9_62_PRIME256V1, MessageDigest::sha256(), 32),
+                ("ES384", Some("P-384")) => (Nid::SECP384R1, MessageDigest::sha384(), 48),
+                (_, crv) => return Err(format!("curve {:?} does not match {}", crv, alg)),
+            };
+            if signature.len() != size * 2 {
+                return Err(format!("signature is {} bytes rather than {}", signature.len(), size * 2));
+            }
+            let group = EcGroup::from_curve_name(curve).map_err(|e| e.to_string())?;
+            let (x, y) = (component(&key.x, "x")?, component(&key.y, "y")?);
+            let ec = EcKey::from_public_key_affine_coordinates(&group, &x, &y).map_err(|e| e.to_string())?;
+            // JWS signatures are r and s side by side; OpenSSL wants DER.
+            let r = BigNum::from_slice(&signature[..size]).map_err(|e| e.to_string())?;
+            let s = BigNum::from_slice(&signature[size..]).map_err(|e| e.to_string())?;
+            let der = EcdsaSig::from_private_components(r, s).and_then(|sig| sig.to_der()).map_err(|e| e.to_string())?;
+            (digest, PKey::from_ec_key(ec).map_err(|e| e.to_string())?, der)
+        }
+        _ => return Err(format!("algorithm {} with a {} key is not supported", alg, key.kty)),
+    };
+    let mut verifier = Verifier::new(digest, &pkey).map_err(|e| e.to_string())?;
+    verifier.update(input).map_err(|e| e.to_string())?;
+    match verifier.verify(&signature) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("signature does not match".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,156 @@
+use log::{error, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use crate::api::EscalationMode;
+use crate::audit::AuditAction;
+use crate::idrac::{BootMode, BootTarget, IdracClient, RestartType};
+use crate::servers::RegisteredServer;
+use crate::state::{AppEvent, AppState};
+
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const VERIFY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub fn record_stage(state: &AppState, operation_id: &str, stage: &str, detail: Option<&str>, status: &str) {
+    if let Err(e) = state.db.add_operation_stage(operation_id, stage, detail, status) {
+        error!("Failed to update operation {}: {}", operation_id, e);
+    }
+}
+
+/// Poll until the server reports `target`, returning how long that took,
+/// or give up after `timeout`.
+pub async fn wait_for_power_state(client: &IdracClient, target: &str, timeout: Duration) -> Result<Duration, String> {
+    let started = tokio::time::Instant::now();
+
+    loop {
+        match client.get_power_state().await {
+            Ok(power_state) if power_state == target => return Ok(started.elapsed()),
+            Ok(_) => {}
+            Err(e) => warn!("Power state poll while waiting for {} failed: {}", target, e),
+        }
+
+        let elapsed = started.elapsed();
+        if elapsed >= timeout {
+            return Err(format!("Timed out after {}s waiting for the server to reach {}", timeout.as_secs(), target));
+        }
+        tokio::time::sleep(VERIFY_POLL_INTERVAL.min(timeout - elapsed)).await;
+    }
+}
+
+/// Wait for a graceful shutdown to reach `Off`, escalating once the deadline passes.
+pub async fn run_shutdown_escalation(
+    state: AppState,
+    server: Arc<RegisteredServer>,
+    operation_id: String,
+    user_id: i64,
+    escalate_after: Duration,
+    mode: EscalationMode,
+) {
+    let Some(deadline) = tokio::time::Instant::now().checked_add(escalate_after) else {
+        record_stage(&state, &operation_id, "escalation_failed", Some("deadline out of range"), "failed");
+        return;
+    };
+
+    loop {
+        match server.client.get_power_state().await {
+            Ok(power_state) if power_state == "Off" => {
+                info!("Operation {}: server reached Off after graceful shutdown", operation_id);
+                record_stage(&state, &operation_id, "powered_off", None, "completed");
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Operation {}: power state poll failed: {}", operation_id, e),
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            break;
+        }
+        tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+    }
+
+    let detail = format!(
+        "Server still on {}s after GracefulShutdown",
+        escalate_after.as_secs()
+    );
+
+    match mode {
+        EscalationMode::Force => {
+            warn!("Operation {}: {}, escalating to ForceOff", operation_id, detail);
+            record_stage(&state, &operation_id, "escalation_force", Some(&detail), "running");
+            let target = server.client.base_url();
+            state.audit_server(Some(user_id), AuditAction::ShutdownEscalationForce, target, &Ok(detail.clone()));
+
+            let result = server.client.power_off().await;
+            state.record_server_power_action(&server.alias, &server.client, "ForceOff", &result);
+            state.audit_server(Some(user_id), AuditAction::ForceOff, target, &result);
+
+            match result {
+                Ok(msg) => record_stage(&state, &operation_id, "forced_off", Some(&msg), "completed"),
+                Err(e) => record_stage(&state, &operation_id, "force_off_failed", Some(&e), "failed"),
+            }
+        }
+        EscalationMode::Alert => {
+            warn!("Operation {}: {}, needs attention", operation_id, detail);
+            record_stage(&state, &operation_id, "escalation_alert", Some(&detail), "needs_attention");
+            state.audit_server(Some(user_id), AuditAction::ShutdownEscalationAlert, server.client.base_url(), &Ok(detail.clone()));
+
+            state.publish(AppEvent::OperationNeedsAttention {
+                operation_id,
+                message: detail,
+            });
+        }
+    }
+}
+
+/// Mount `image_url`, boot from the virtual CD once and restart into it. A
+/// server that is off is powered on instead of restarted.
+pub async fn run_virtual_media_boot_once(
+    state: AppState,
+    operation_id: String,
+    user_id: i64,
+    image_url: String,
+    restart_type: RestartType,
+) {
+    let result = virtual_media_boot_once(&state, &operation_id, &image_url, restart_type).await;
+    let details = serde_json::json!({
+        "operation_id": operation_id,
+        "image_url": image_url,
+        "restart_type": restart_type,
+    });
+    state.audit_with_details(Some(user_id), AuditAction::VirtualMediaBootOnce, state.idrac.base_url(), &result, &details);
+}
+
+async fn virtual_media_boot_once(
+    state: &AppState,
+    operation_id: &str,
+    image_url: &str,
+    restart_type: RestartType,
+) -> Result<String, String> {
+    if let Err(e) = state.idrac.mount_virtual_media(image_url).await {
+        record_stage(state, operation_id, "mount_failed", Some(&e), "failed");
+        return Err(e);
+    }
+    record_stage(state, operation_id, "media_mounted", Some(image_url), "running");
+
+    if let Err(e) = state.idrac.set_boot_override(BootTarget::Cd, BootMode::Once).await {
+        record_stage(state, operation_id, "boot_override_failed", Some(&e), "failed");
+        return Err(e);
+    }
+    record_stage(state, operation_id, "boot_override_set", Some("Cd, once"), "running");
+
+    let powered_off = matches!(state.idrac.get_power_state().await.as_deref(), Ok("Off"));
+    let (action, stage, result) = if powered_off {
+        ("PowerOn", "power_on_sent", state.idrac.power_on().await)
+    } else {
+        (restart_type.reset_type(), "restart_sent", state.idrac.restart(restart_type).await)
+    };
+    state.record_power_action(action, &result);
+    match &result {
+        Ok(msg) => {
+            info!("Operation {}: {} sent to boot {}", operation_id, action, image_url);
+            record_stage(state, operation_id, stage, Some(msg), "completed");
+        }
+        Err(e) => record_stage(state, operation_id, "restart_failed", Some(e), "failed"),
+    }
+    result
+}
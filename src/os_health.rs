@@ -0,0 +1,148 @@
+//! Canary probes of each server's operating system through an HTTP
+//! endpoint on the host, typically node-exporter or a custom agent. Power
+//! `On` says nothing about a hung or crashed OS; a probe that gets no 2xx
+//! answer marks the OS unreachable, which `/api/alerts` reports apart
+//! from an unreachable iDRAC. Servers without an `os_health_url` are not
+//! probed and show no OS health at all.
+
+use chrono::Utc;
+use futures_util::stream::{self, StreamExt};
+use log::{info, warn};
+use reqwest::Client;
+use std::time::{Duration, Instant};
+
+use crate::api::OsHealth;
+use crate::database::SQLITE_TIMESTAMP_FORMAT;
+use crate::secret::SecretString;
+use crate::state::{AppEvent, AppState};
+
+pub const STATUS_REACHABLE: &str = "reachable";
+pub const STATUS_UNREACHABLE: &str = "unreachable";
+/// The probe failed while the iDRAC reported the server off, as expected.
+pub const STATUS_POWERED_OFF: &str = "powered_off";
+
+/// Servers are probed at most this many at a time.
+const PROBE_CONCURRENCY: usize = 8;
+
+/// Where and how to probe a server's OS.
+pub struct OsHealthProbe {
+    pub url: String,
+    /// Accept any TLS certificate from `url`.
+    pub insecure: bool,
+    token: Option<SecretString>,
+    client: Client,
+}
+
+impl OsHealthProbe {
+    pub fn new(url: &str, insecure: bool, token: Option<SecretString>) -> Result<Self, String> {
+        let client = Client::builder()
+            .danger_accept_invalid_certs(insecure)
+            .build()
+            .map_err(|e| format!("Failed to create OS health client: {}", e))?;
+        Ok(OsHealthProbe {
+            url: url.to_string(),
+            insecure,
+            token,
+            client,
+        })
+    }
+
+    pub fn has_token(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Round-trip time of a 2xx answer in milliseconds, or why there was none.
+    pub async fn probe(&self, timeout: Duration) -> Result<f64, String> {
+        let mut request = self.client.get(&self.url).timeout(timeout);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token.expose());
+        }
+
+        let started = Instant::now();
+        let response = request.send().await.map_err(|e| {
+            if e.is_timeout() {
+                format!("no answer within {}ms", timeout.as_millis())
+            } else {
+                format!("request failed: {}", e.without_url())
+            }
+        })?;
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        if !response.status().is_success() {
+            return Err(format!("answered HTTP {}", response.status()));
+        }
+        Ok(latency_ms)
+    }
+}
+
+/// Probe every server with an OS health endpoint, outside maintenance, and
+/// keep each outcome in `AppState::os_health`. An `os_unreachable` event is
+/// published for each server whose OS newly stops answering.
+pub async fn check_all(state: &AppState) {
+    let timeout = Duration::from_millis(state.config.os_health_timeout_ms);
+    let servers: Vec<_> = state
+        .servers
+        .all()
+        .into_iter()
+        .filter(|server| server.os_health.is_some() && state.servers.maintenance(&server.alias).is_none())
+        .collect();
+
+    let outcomes: Vec<(String, Result<f64, String>, bool)> = stream::iter(servers)
+        .map(|server| async move {
+            let result = match &server.os_health {
+                Some(probe) => probe.probe(timeout).await,
+                None => Err("no OS health endpoint".to_string()),
+            };
+            // A host that is off is expected to be silent.
+            let powered_off = result.is_err()
+                && server
+                    .client
+                    .get_power_state()
+                    .await
+                    .is_ok_and(|power_state| power_state == "Off");
+            (server.alias.clone(), result, powered_off)
+        })
+        .buffer_unordered(PROBE_CONCURRENCY)
+        .collect()
+        .await;
+
+    let now = Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    let mut statuses = state.os_health.write().unwrap();
+    for (alias, result, powered_off) in outcomes {
+        let (status, latency_ms, error) = match result {
+            Ok(latency_ms) => (STATUS_REACHABLE, Some(latency_ms), None),
+            Err(e) if powered_off => (STATUS_POWERED_OFF, None, Some(e)),
+            Err(e) => (STATUS_UNREACHABLE, None, Some(e)),
+        };
+        let previous = statuses.get(&alias);
+        let since = match previous {
+            Some(previous) if previous.status == status => previous.since.clone(),
+            _ => now.clone(),
+        };
+
+        let was_unreachable = previous.is_some_and(|previous| previous.status == STATUS_UNREACHABLE);
+        if status == STATUS_UNREACHABLE && !was_unreachable {
+            let error = error.clone().unwrap_or_default();
+            warn!("The OS of '{}' does not answer its health probe: {}", alias, error);
+            state.publish(AppEvent::OsUnreachable {
+                server: alias.clone(),
+                error,
+            });
+        } else if status == STATUS_REACHABLE && was_unreachable {
+            info!("The OS of '{}' answers its health probe again", alias);
+        }
+
+        statuses.insert(
+            alias,
+            OsHealth {
+                status: status.to_string(),
+                latency_ms,
+                error,
+                checked_at: now.clone(),
+                since,
+            },
+        );
+    }
+
+    // Servers removed, or whose endpoint was cleared, since their last probe.
+    statuses.retain(|alias, _| state.servers.get(alias).is_some_and(|server| server.os_health.is_some()));
+}
@@ -0,0 +1,218 @@
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub use crate::api::{Page, PageInfo};
+use crate::database::Keyset;
+use crate::validation::FieldError;
+
+/// Opaque cursor for the rows after or before `keyset`'s key.
+pub fn encode_cursor<K: Serialize>(keyset: &Keyset<K>) -> String {
+    let json = serde_json::to_vec(keyset).expect("sort keys serialize");
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+}
+
+fn decode_cursor<K: DeserializeOwned>(cursor: &str) -> Option<Keyset<K>> {
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(cursor.trim()).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// The validated `limit` and `cursor` query parameters of a list.
+pub fn request<K: DeserializeOwned>(
+    limit: Option<u32>,
+    cursor: Option<&str>,
+    default_limit: u32,
+    max_limit: u32,
+) -> Result<(Keyset<K>, u32), FieldError> {
+    let limit = limit.unwrap_or(default_limit);
+    if limit == 0 || limit > max_limit {
+        return Err(FieldError {
+            field: "limit",
+            message: format!("must be between 1 and {}", max_limit),
+        });
+    }
+    let keyset = match cursor.filter(|c| !c.is_empty()) {
+        None => Keyset::First,
+        Some(cursor) => decode_cursor(cursor).ok_or_else(|| FieldError {
+            field: "cursor",
+            message: "is not a cursor returned by this list".to_string(),
+        })?,
+    };
+    Ok((keyset, limit))
+}
+
+/// Build a page from up to `limit + 1` rows fetched for `keyset`, in the
+/// order the store returns them; the extra row only tells whether more
+/// rows follow in that direction.
+pub fn page<T, K: Serialize>(
+    mut rows: Vec<T>,
+    keyset: &Keyset<K>,
+    limit: u32,
+    total_estimate: Option<u64>,
+    key: impl Fn(&T) -> K,
+) -> Page<T> {
+    let more = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+    let backward = matches!(keyset, Keyset::Before(_));
+    if backward {
+        rows.reverse();
+    }
+
+    // A cursor row exists on the side the request came from.
+    let has_next = if backward { keyset.key().is_some() } else { more };
+    let has_prev = if backward { more } else { keyset.key().is_some() };
+    let next_cursor = rows.last().filter(|_| has_next).map(|row| encode_cursor(&Keyset::After(key(row))));
+    let prev_cursor = rows.first().filter(|_| has_prev).map(|row| encode_cursor(&Keyset::Before(key(row))));
+
+    Page {
+        success: true,
+        items: rows,
+        page: PageInfo {
+            limit,
+            next_cursor,
+            prev_cursor,
+            total_estimate,
+        },
+    }
+}
+
+/// Page through a list held in memory, already sorted by `key`.
+pub fn page_sorted<T, K: Serialize + Ord>(
+    rows: Vec<T>,
+    keyset: &Keyset<K>,
+    limit: u32,
+    key: impl Fn(&T) -> K,
+) -> Page<T> {
+    let total = rows.len() as u64;
+    let fetched: Vec<T> = match keyset {
+        Keyset::First => rows.into_iter().take(limit as usize + 1).collect(),
+        Keyset::After(after) => rows
+            .into_iter()
+            .filter(|row| key(row) > *after)
+            .take(limit as usize + 1)
+            .collect(),
+        Keyset::Before(before) => rows
+            .into_iter()
+            .rev()
+            .filter(|row| key(row) < *before)
+            .take(limit as usize + 1)
+            .collect(),
+    };
+    page(fetched, keyset, limit, Some(total), key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next(page: &Page<i64>) -> Keyset<i64> {
+        request(None, page.page.next_cursor.as_deref(), 3, 10).unwrap().0
+    }
+
+    fn prev(page: &Page<i64>) -> Keyset<i64> {
+        request(None, page.page.prev_cursor.as_deref(), 3, 10).unwrap().0
+    }
+
+    #[test]
+    fn cursors_round_trip_and_stay_url_safe() {
+        for keyset in [Keyset::After(42), Keyset::Before(-7)] {
+            let cursor = encode_cursor(&keyset);
+            assert!(cursor.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'), "{}", cursor);
+            assert_eq!(request::<i64>(None, Some(&cursor), 3, 10).unwrap().0, keyset);
+        }
+        let keyset = Keyset::After(("2024-05-01 12:00:00".to_string(), "entry/7?x=1".to_string()));
+        let cursor = encode_cursor(&keyset);
+        assert_eq!(request::<(String, String)>(None, Some(&cursor), 3, 10).unwrap().0, keyset);
+
+        assert_eq!(request::<i64>(None, None, 3, 10).unwrap(), (Keyset::First, 3));
+        assert_eq!(request::<i64>(None, Some(""), 3, 10).unwrap().0, Keyset::First);
+        assert_eq!(request::<i64>(None, Some("not a cursor"), 3, 10).unwrap_err().field, "cursor");
+        assert_eq!(request::<(String, String)>(None, Some(&encode_cursor(&Keyset::After(1))), 3, 10).unwrap_err().field, "cursor");
+        assert_eq!(request::<i64>(Some(0), None, 3, 10).unwrap_err().field, "limit");
+        assert_eq!(request::<i64>(Some(11), None, 3, 10).unwrap_err().field, "limit");
+    }
+
+    /// Names of the properties `schema` in openapi.json declares.
+    fn documented_properties(schema: &str) -> Vec<String> {
+        let document: serde_json::Value = serde_json::from_str(include_str!("../openapi.json")).unwrap();
+        let mut names: Vec<String> =
+            document["components"]["schemas"][schema]["properties"].as_object().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn field_names(value: &serde_json::Value) -> Vec<String> {
+        let mut names: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn the_envelope_matches_the_openapi_components() {
+        let rows: Vec<i64> = (1..=8).collect();
+        for keyset in [Keyset::First, Keyset::After(3)] {
+            let page = serde_json::to_value(page_sorted(rows.clone(), &keyset, 3, |row| *row)).unwrap();
+            assert_eq!(field_names(&page), documented_properties("PageEnvelope"));
+            assert_eq!(field_names(&page["page"]), documented_properties("PageInfo"));
+        }
+    }
+
+    #[test]
+    fn prev_cursor_returns_to_the_first_page() {
+        let rows: Vec<i64> = (1..=8).collect();
+        let first = page_sorted(rows.clone(), &Keyset::First, 3, |row| *row);
+        assert_eq!(first.items, vec![1, 2, 3]);
+        assert_eq!(first.page.prev_cursor, None);
+
+        let second = page_sorted(rows.clone(), &next(&first), 3, |row| *row);
+        assert_eq!(second.items, vec![4, 5, 6]);
+        let third = page_sorted(rows.clone(), &next(&second), 3, |row| *row);
+        assert_eq!(third.items, vec![7, 8]);
+        assert_eq!(third.page.next_cursor, None);
+
+        let back = page_sorted(rows.clone(), &prev(&third), 3, |row| *row);
+        assert_eq!(back.items, second.items);
+        let start = page_sorted(rows, &prev(&back), 3, |row| *row);
+        assert_eq!(start.items, first.items);
+        assert_eq!(start.page.prev_cursor, None);
+        assert!(start.page.next_cursor.is_some());
+    }
+
+    #[test]
+    fn rows_inserted_between_pages_are_neither_skipped_nor_repeated() {
+        // Even keys at first; odd ones arrive while the list is paged.
+        let mut rows: Vec<i64> = (0..20).map(|i| i * 2).collect();
+        let original = rows.clone();
+        let mut ahead = Vec::new();
+        let mut seen = Vec::new();
+        let mut keyset = Keyset::First;
+        loop {
+            let page = page_sorted(rows.clone(), &keyset, 3, |row| *row);
+            seen.extend(page.items.iter().copied());
+            let Some(cursor) = page.page.next_cursor.as_deref() else {
+                break;
+            };
+            keyset = request(None, Some(cursor), 3, 10).unwrap().0;
+
+            // One row before the cursor, which this walk has passed, and
+            // one after it, which it must still reach.
+            let last = *page.items.last().unwrap();
+            if !rows.contains(&(last - 1)) {
+                rows.push(last - 1);
+            }
+            if !rows.contains(&(last + 1)) {
+                rows.push(last + 1);
+                ahead.push(last + 1);
+            }
+            rows.sort_unstable();
+        }
+
+        let mut deduped = seen.clone();
+        deduped.dedup();
+        assert_eq!(seen, deduped, "a row was returned twice");
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]));
+        for row in original.iter().chain(&ahead) {
+            assert!(seen.contains(row), "row {} was skipped", row);
+        }
+    }
+}
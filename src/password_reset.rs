@@ -0,0 +1,140 @@
+//! Self-service password reset for local accounts. A request mails a
+//! single-use link to the address on the account; only the token's hash
+//! is stored, and spending it sets the new password and ends every
+//! session of the user.
+
+use chrono::{Duration, Utc};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use log::{info, warn};
+use std::num::NonZeroU32;
+
+use crate::audit::AuditAction;
+use crate::break_glass::BREAK_GLASS_USERNAME;
+use crate::database::{User, SQLITE_TIMESTAMP_FORMAT};
+use crate::mailer::{self, Email};
+use crate::state::AppState;
+use crate::tokens;
+
+/// Page the link opens. The token travels in its fragment, so it never
+/// reaches a server log.
+pub const PAGE_PATH: &str = "/reset-password";
+
+const TOKEN_TTL_MINUTES: i64 = 30;
+const REQUESTS_PER_IP_PER_HOUR: u32 = 5;
+/// Links mailed per account per hour, however many addresses ask.
+const LINKS_PER_ACCOUNT_PER_HOUR: u32 = 3;
+
+/// Rate limits on `POST /api/password-reset/request`.
+pub struct ResetLimits {
+    per_ip: DefaultKeyedRateLimiter<String>,
+    per_account: DefaultKeyedRateLimiter<i64>,
+}
+
+impl Default for ResetLimits {
+    fn default() -> Self {
+        let per_ip = NonZeroU32::new(REQUESTS_PER_IP_PER_HOUR).unwrap();
+        let per_account = NonZeroU32::new(LINKS_PER_ACCOUNT_PER_HOUR).unwrap();
+        ResetLimits {
+            per_ip: RateLimiter::keyed(Quota::per_hour(per_ip)),
+            per_account: RateLimiter::keyed(Quota::per_hour(per_account)),
+        }
+    }
+}
+
+impl ResetLimits {
+    /// Whether another request from `ip` is allowed now.
+    pub fn allow_ip(&self, ip: &str) -> bool {
+        self.per_ip.retain_recent();
+        self.per_ip.check_key(&ip.to_string()).is_ok()
+    }
+
+    fn allow_account(&self, user_id: i64) -> bool {
+        self.per_account.retain_recent();
+        self.per_account.check_key(&user_id).is_ok()
+    }
+}
+
+/// The account `login` names, by username or else by email address.
+fn find_account(state: &AppState, login: &str) -> Result<Option<User>, String> {
+    let by_username = state
+        .db
+        .get_user_by_username(login)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if by_username.is_some() {
+        return Ok(by_username);
+    }
+    state.db.get_user_by_email(login).map_err(|e| format!("Database error: {}", e))
+}
+
+/// Mail a reset link to the account `login` names. Accounts that cannot
+/// reset a password here (unknown, external, disabled, without an email
+/// address or over their hourly limit) are skipped with a log line only,
+/// so the caller's answer is the same for every `login`.
+pub async fn send_link(state: AppState, login: String) {
+    let (Some(smtp), Some(self_url)) = (state.config.smtp.clone(), state.config.self_url.clone()) else {
+        return;
+    };
+    let user = match find_account(&state, login.trim()) {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            info!("Password reset requested for unknown account '{}'", login.trim());
+            return;
+        }
+        Err(e) => {
+            warn!("Password reset request failed: {}", e);
+            return;
+        }
+    };
+    let can_reset = !user.is_external_account() && user.is_active() && user.username != BREAK_GLASS_USERNAME;
+    let Some(email) = user.email.clone().filter(|_| can_reset) else {
+        info!("Password reset requested for {}, which cannot reset its password here", user.username);
+        return;
+    };
+    if !state.password_reset_limits.allow_account(user.id) {
+        warn!("Password reset for {} refused: too many links this hour", user.username);
+        return;
+    }
+
+    let token = tokens::generate_token();
+    let expires_at = (Utc::now() + Duration::minutes(TOKEN_TTL_MINUTES))
+        .format(SQLITE_TIMESTAMP_FORMAT)
+        .to_string();
+    if let Err(e) = state.db.create_password_reset_token(user.id, &tokens::hash_token(&token), &expires_at) {
+        let e = format!("Failed to store password reset token: {}", e);
+        warn!("Password reset for {} failed: {}", user.username, e);
+        state.audit_server(Some(user.id), AuditAction::PasswordResetRequested, "app", &Err(e));
+        return;
+    }
+
+    let link = format!("{}{}#{}", self_url.trim_end_matches('/'), PAGE_PATH, token);
+    let subject = "Reset your iDRAC Controller password".to_string();
+    let body = format!(
+        "Someone asked to reset the password of {} on the iDRAC Controller.\n\n\
+         Open this link within {} minutes to choose a new password:\n\n  {}\n\n\
+         The link works once. If you did not ask for it, ignore this email; your password is unchanged.\n",
+        user.username, TOKEN_TTL_MINUTES, link
+    );
+    let recipients = vec![email];
+    let sent = tokio::task::spawn_blocking(move || {
+        mailer::send(
+            &smtp,
+            &Email {
+                to: &recipients,
+                subject: &subject,
+                body: &body,
+            },
+        )
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    match &sent {
+        Ok(()) => info!("Mailed a password reset link to {}", user.username),
+        Err(e) => warn!("Failed to mail a password reset link to {}: {}", user.username, e),
+    }
+    state.audit_server(
+        Some(user.id),
+        AuditAction::PasswordResetRequested,
+        "app",
+        &sent.map(|()| format!("Password reset link mailed to {}", user.username)),
+    );
+}
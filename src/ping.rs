@@ -0,0 +1,238 @@
+//! ICMP echo probes of every server's iDRAC. Each round's latency and
+//! packet loss go to `ping_history`, the connectivity log, and a server
+//! losing more than `PING_ALERT_LOSS_PCT` of its echoes is reported by
+//! `/api/alerts` until a round comes in below it.
+
+use chrono::Utc;
+use futures_util::stream::{self, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io::{ErrorKind, Read};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::database::SQLITE_TIMESTAMP_FORMAT;
+use crate::state::{AppEvent, AppState};
+
+const ECHOES_PER_ROUND: u16 = 5;
+const ECHO_TIMEOUT: Duration = Duration::from_secs(1);
+const ECHO_SPACING: Duration = Duration::from_millis(200);
+/// Servers are pinged at most this many at a time.
+const PING_CONCURRENCY: usize = 8;
+const PAYLOAD: &[u8] = b"idrac-controller";
+
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV4_ECHO_REPLY: u8 = 0;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+/// A server dropping too many echoes, reported by `/api/alerts` while it
+/// lasts.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectivityAlert {
+    pub server: String,
+    pub packet_loss_pct: f64,
+    pub latency_ms: Option<f64>,
+    /// `Critical` when no echo came back.
+    pub severity: &'static str,
+    pub since: String,
+}
+
+/// Outcome of one round of echoes.
+#[derive(Debug, Clone, Copy)]
+pub struct PingRound {
+    pub latency_ms: Option<f64>,
+    pub packet_loss_pct: f64,
+}
+
+/// An ICMP socket for `address`: an unprivileged ping socket where
+/// `net.ipv4.ping_group_range` allows one, a raw socket otherwise. The
+/// flag tells whether it is raw.
+fn open_socket(address: IpAddr) -> Result<(Socket, bool), String> {
+    let (domain, protocol) = match address {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    };
+    if let Ok(socket) = Socket::new(domain, Type::DGRAM, Some(protocol)) {
+        return Ok((socket, false));
+    }
+    Socket::new(domain, Type::RAW, Some(protocol))
+        .map(|socket| (socket, true))
+        .map_err(|e| format!("cannot open an ICMP socket (needs CAP_NET_RAW or net.ipv4.ping_group_range): {}", e))
+}
+
+/// Why ICMP pings cannot be sent from this process, if they cannot.
+pub fn unavailable() -> Option<String> {
+    open_socket(IpAddr::V4(Ipv4Addr::LOCALHOST)).err()
+}
+
+/// RFC 1071 checksum. ICMPv6 checksums cover an IP pseudo-header, so the
+/// kernel fills those in.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn echo_request(address: IpAddr, identifier: u16, sequence: u16) -> Vec<u8> {
+    let kind = if address.is_ipv4() { ICMPV4_ECHO_REQUEST } else { ICMPV6_ECHO_REQUEST };
+    let mut packet = vec![kind, 0, 0, 0];
+    packet.extend_from_slice(&identifier.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(PAYLOAD);
+    if address.is_ipv4() {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+/// Whether `packet` answers echo `sequence`. Raw IPv4 sockets deliver the
+/// IP header and every echo reply the host receives; ping sockets deliver
+/// only their own replies, with the identifier the kernel picked.
+fn is_reply(packet: &[u8], address: IpAddr, raw: bool, identifier: u16, sequence: u16) -> bool {
+    let icmp = if raw && address.is_ipv4() {
+        let header = usize::from(packet.first().map_or(0, |b| b & 0x0f)) * 4;
+        packet.get(header..).unwrap_or_default()
+    } else {
+        packet
+    };
+    let reply = if address.is_ipv4() { ICMPV4_ECHO_REPLY } else { ICMPV6_ECHO_REPLY };
+    icmp.len() >= 8
+        && icmp[0] == reply
+        && (!raw || icmp[4..6] == identifier.to_be_bytes())
+        && icmp[6..8] == sequence.to_be_bytes()
+}
+
+/// Wait for the reply to echo `sequence` until `ECHO_TIMEOUT` after `sent_at`.
+fn await_reply(socket: &Socket, address: IpAddr, raw: bool, identifier: u16, sequence: u16, sent_at: Instant) -> Option<Duration> {
+    let mut buffer = [0u8; 1500];
+    loop {
+        let remaining = ECHO_TIMEOUT.checked_sub(sent_at.elapsed()).filter(|left| !left.is_zero())?;
+        socket.set_read_timeout(Some(remaining)).ok()?;
+        match (&*socket).read(&mut buffer) {
+            Ok(len) if is_reply(&buffer[..len], address, raw, identifier, sequence) => return Some(sent_at.elapsed()),
+            Ok(_) => continue,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => continue,
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Send `ECHOES_PER_ROUND` echoes to `address` and measure the replies.
+/// Blocking; run it on a blocking thread.
+pub fn ping(address: IpAddr) -> Result<PingRound, String> {
+    let (socket, raw) = open_socket(address)?;
+    let target = SocketAddr::new(address, 0).into();
+    let identifier: u16 = rand::random();
+
+    let mut round_trips = Vec::new();
+    for sequence in 0..ECHOES_PER_ROUND {
+        if sequence > 0 {
+            std::thread::sleep(ECHO_SPACING);
+        }
+        let sent_at = Instant::now();
+        // A send that fails, e.g. with no route to the host, counts as lost.
+        if socket.send_to(&echo_request(address, identifier, sequence), &target).is_err() {
+            continue;
+        }
+        if let Some(round_trip) = await_reply(&socket, address, raw, identifier, sequence, sent_at) {
+            round_trips.push(round_trip.as_secs_f64() * 1000.0);
+        }
+    }
+
+    let lost = usize::from(ECHOES_PER_ROUND) - round_trips.len();
+    Ok(PingRound {
+        latency_ms: (!round_trips.is_empty()).then(|| round_trips.iter().sum::<f64>() / round_trips.len() as f64),
+        packet_loss_pct: lost as f64 * 100.0 / f64::from(ECHOES_PER_ROUND),
+    })
+}
+
+/// The address of the iDRAC behind `base_url`.
+fn resolve(base_url: &str) -> Result<IpAddr, String> {
+    let url = reqwest::Url::parse(base_url).map_err(|e| format!("invalid iDRAC URL {}: {}", base_url, e))?;
+    let host = url.host_str().ok_or_else(|| format!("{} names no host", base_url))?;
+    if let Ok(address) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+        return Ok(address);
+    }
+    (host, 0)
+        .to_socket_addrs()
+        .map_err(|e| format!("cannot resolve {}: {}", host, e))?
+        .next()
+        .map(|address| address.ip())
+        .ok_or_else(|| format!("{} has no address", host))
+}
+
+/// Ping every server, store each round, and publish a `connectivity_alert`
+/// event for each server that newly loses more than the alert threshold.
+pub async fn check_all(state: &AppState) {
+    let rounds: Vec<(String, PingRound)> = stream::iter(state.servers.pollable())
+        .map(|server| async move {
+            let base_url = server.client.base_url().to_string();
+            let round = tokio::task::spawn_blocking(move || resolve(&base_url).and_then(ping))
+                .await
+                .unwrap_or_else(|e| Err(e.to_string()));
+            match round {
+                Ok(round) => Some((server.alias.clone(), round)),
+                Err(e) => {
+                    warn!("Ping of '{}' failed: {}", server.alias, e);
+                    None
+                }
+            }
+        })
+        .buffer_unordered(PING_CONCURRENCY)
+        .filter_map(|round| async move { round })
+        .collect()
+        .await;
+
+    for (alias, round) in &rounds {
+        if let Err(e) = state.db.record_ping(alias, round.latency_ms, round.packet_loss_pct) {
+            warn!("Failed to store ping of '{}': {}", alias, e);
+        }
+    }
+
+    let threshold = state.config.ping_alert_loss_pct;
+    let mut alerts = state.connectivity_alerts.write().unwrap();
+    for (alias, round) in rounds {
+        if round.packet_loss_pct <= threshold {
+            if alerts.remove(&alias).is_some() {
+                info!("'{}' answers pings again ({}% loss)", alias, round.packet_loss_pct);
+            }
+            continue;
+        }
+        let severity = if round.latency_ms.is_none() { "Critical" } else { "Warning" };
+        if let Some(alert) = alerts.get_mut(&alias) {
+            alert.packet_loss_pct = round.packet_loss_pct;
+            alert.latency_ms = round.latency_ms;
+            alert.severity = severity;
+            continue;
+        }
+
+        warn!("'{}' lost {}% of its pings, over the {}% threshold", alias, round.packet_loss_pct, threshold);
+        state.publish(AppEvent::ConnectivityAlert {
+            server: alias.clone(),
+            packet_loss_pct: round.packet_loss_pct,
+            latency_ms: round.latency_ms,
+            severity: severity.to_string(),
+        });
+        alerts.insert(
+            alias.clone(),
+            ConnectivityAlert {
+                server: alias,
+                packet_loss_pct: round.packet_loss_pct,
+                latency_ms: round.latency_ms,
+                severity,
+                since: Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+            },
+        );
+    }
+
+    // Servers removed since their alert was raised.
+    alerts.retain(|alias, _| state.servers.get(alias).is_some());
+}
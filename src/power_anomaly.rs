@@ -0,0 +1,226 @@
+//! Flags servers whose power draw strays from what they usually draw at
+//! that hour of the week, as a stuck fan or a runaway workload does. Each
+//! closed hour is summarized (median and median absolute deviation) into
+//! the baseline of its hour of the week, which keeps the last
+//! `POWER_ANOMALY_WEEKS` of them in the database, so nothing is recomputed
+//! from the sample history after a restart.
+
+use chrono::{DateTime, Datelike, Duration, Local, Timelike, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::database::{HourSummary, PowerBaseline, SQLITE_TIMESTAMP_FORMAT};
+use crate::state::{AppEvent, AppState};
+
+/// Weeks of an hour needed before its baseline is used.
+pub const MIN_BASELINE_WEEKS: usize = 2;
+/// Floor of the spread, so a server drawing a steady wattage is not
+/// flagged for a few watts of noise.
+const MIN_MAD_WATTS: f64 = 5.0;
+
+/// A server drawing unusual power, reported by `/api/alerts` while it lasts.
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerAnomaly {
+    pub server: String,
+    pub since: String,
+    /// Latest sample.
+    pub watts: u32,
+    pub median_watts: f64,
+    pub mad_watts: f64,
+    pub severity: &'static str,
+}
+
+#[derive(Default)]
+struct Tracker {
+    /// Start of the hour the latest sample fell in.
+    hour_start: Option<DateTime<Local>>,
+    /// Deviating samples in a row.
+    deviations: u32,
+    anomaly: Option<PowerAnomaly>,
+}
+
+/// Detector state per server alias. Only baselines are persisted; a
+/// restart starts every server's run of deviating samples over.
+#[derive(Default)]
+pub struct PowerAnomalies {
+    servers: Mutex<HashMap<String, Tracker>>,
+}
+
+impl PowerAnomalies {
+    pub fn active(&self) -> Vec<PowerAnomaly> {
+        let servers = self.servers.lock().unwrap();
+        servers.values().filter_map(|tracker| tracker.anomaly.clone()).collect()
+    }
+
+    /// The server's active anomaly and its current run of deviating samples.
+    pub fn get(&self, alias: &str) -> (Option<PowerAnomaly>, u32) {
+        let servers = self.servers.lock().unwrap();
+        servers
+            .get(alias)
+            .map_or((None, 0), |tracker| (tracker.anomaly.clone(), tracker.deviations))
+    }
+}
+
+/// 0 is Monday 00:00 local time.
+pub fn hour_of_week(at: DateTime<Local>) -> u32 {
+    at.weekday().num_days_from_monday() * 24 + at.hour()
+}
+
+fn hour_start(at: DateTime<Local>) -> DateTime<Local> {
+    at.with_minute(0)
+        .and_then(|at| at.with_second(0))
+        .and_then(|at| at.with_nanosecond(0))
+        .unwrap_or(at)
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+fn median_absolute_deviation(values: &[f64], median_value: f64) -> f64 {
+    let mut deviations: Vec<f64> = values.iter().map(|value| (value - median_value).abs()).collect();
+    median(&mut deviations)
+}
+
+/// Fold the server's samples of the hour starting at `start` into the
+/// baseline of that hour of the week. Folding an hour again replaces it.
+fn fold_hour(state: &AppState, alias: &str, start: DateTime<Local>) -> Result<(), String> {
+    let since = start.with_timezone(&Utc);
+    let hour_start = since.format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    let until = (since + Duration::hours(1)).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    let watts = state
+        .db
+        .power_sample_watts(alias, &hour_start, &until)
+        .map_err(|e| format!("Failed to load power samples: {}", e))?;
+    if watts.is_empty() {
+        return Ok(());
+    }
+
+    let mut values: Vec<f64> = watts.iter().map(|&watts| f64::from(watts)).collect();
+    let median_watts = median(&mut values);
+    let summary = HourSummary {
+        hour_start: hour_start.clone(),
+        median_watts,
+        mad_watts: median_absolute_deviation(&values, median_watts),
+        samples: watts.len() as u32,
+    };
+
+    let slot = hour_of_week(start);
+    let mut weeks = state
+        .db
+        .get_power_baseline(alias, slot)
+        .map_err(|e| format!("Failed to load power baseline: {}", e))?
+        .map(|baseline| baseline.weeks)
+        .unwrap_or_default();
+    let weeks_kept = state.config.power_anomaly_weeks;
+    let oldest = (since - Duration::weeks(i64::from(weeks_kept))).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    weeks.retain(|week| week.hour_start != hour_start && week.hour_start > oldest);
+    weeks.push(summary);
+    weeks.sort_by(|a, b| a.hour_start.cmp(&b.hour_start));
+
+    // The usual spread within the hour, widened when its level varies
+    // from week to week.
+    let mut medians: Vec<f64> = weeks.iter().map(|week| week.median_watts).collect();
+    let mut mads: Vec<f64> = weeks.iter().map(|week| week.mad_watts).collect();
+    let median_watts = median(&mut medians);
+    let mad_watts = median(&mut mads).max(median_absolute_deviation(&medians, median_watts));
+
+    state
+        .db
+        .save_power_baseline(&PowerBaseline {
+            server_alias: alias.to_string(),
+            hour_of_week: slot,
+            weeks,
+            median_watts,
+            mad_watts,
+            updated_at: Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+        })
+        .map_err(|e| format!("Failed to store power baseline: {}", e))
+}
+
+/// Compare a sampling round with each server's baseline for this hour of
+/// the week. `POWER_ANOMALY_SAMPLES` deviating samples in a row raise a
+/// `power_anomaly` event; the anomaly clears at the first sample back
+/// within the baseline. The first sample of a new hour folds the previous
+/// hour into its baseline.
+pub fn check(state: &AppState, samples: &[(String, u32)]) {
+    let now = Local::now();
+    let current_hour = hour_start(now);
+    let threshold = state.config.power_anomaly_threshold;
+
+    for (alias, watts) in samples {
+        let previous_hour = {
+            let mut servers = state.power_anomalies.servers.lock().unwrap();
+            let tracker = servers.entry(alias.clone()).or_default();
+            tracker.hour_start.replace(current_hour)
+        };
+        // After a restart the previous hour is folded again, which is
+        // harmless: it replaces its earlier summary.
+        if previous_hour != Some(current_hour) {
+            if let Err(e) = fold_hour(state, alias, current_hour - Duration::hours(1)) {
+                warn!("Power baseline update of '{}' failed: {}", alias, e);
+            }
+        }
+
+        let baseline = match state.db.get_power_baseline(alias, hour_of_week(now)) {
+            Ok(Some(baseline)) if baseline.weeks.len() >= MIN_BASELINE_WEEKS => baseline,
+            Ok(_) => continue,
+            Err(e) => {
+                warn!("Failed to load power baseline of '{}': {}", alias, e);
+                continue;
+            }
+        };
+        let deviates = (f64::from(*watts) - baseline.median_watts).abs() > threshold * baseline.mad_watts.max(MIN_MAD_WATTS);
+
+        let mut servers = state.power_anomalies.servers.lock().unwrap();
+        let tracker = servers.entry(alias.clone()).or_default();
+        if !deviates {
+            tracker.deviations = 0;
+            if tracker.anomaly.take().is_some() {
+                info!("Power draw of '{}' is back within its baseline at {} W", alias, watts);
+            }
+            continue;
+        }
+
+        tracker.deviations += 1;
+        if let Some(anomaly) = &mut tracker.anomaly {
+            anomaly.watts = *watts;
+            continue;
+        }
+        if tracker.deviations < state.config.power_anomaly_samples {
+            continue;
+        }
+        let anomaly = PowerAnomaly {
+            server: alias.clone(),
+            since: Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+            watts: *watts,
+            median_watts: baseline.median_watts,
+            mad_watts: baseline.mad_watts,
+            severity: "Warning",
+        };
+        warn!(
+            "'{}' draws {} W, usually {:.0} W (± {:.0} W) at this hour, for {} samples in a row",
+            alias, watts, baseline.median_watts, baseline.mad_watts, tracker.deviations
+        );
+        state.publish(AppEvent::PowerAnomaly {
+            server: alias.clone(),
+            watts: *watts,
+            median_watts: baseline.median_watts,
+            mad_watts: baseline.mad_watts,
+            severity: anomaly.severity.to_string(),
+        });
+        tracker.anomaly = Some(anomaly);
+    }
+
+    // Servers removed since they were tracked.
+    let mut servers = state.power_anomalies.servers.lock().unwrap();
+    servers.retain(|alias, _| state.servers.get(alias).is_some());
+}
@@ -0,0 +1,148 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::idrac::IdracClient;
+use crate::state::{AppEvent, AppState};
+
+const BURST_INTERVAL: Duration = Duration::from_secs(2);
+const BURST_DURATION: Duration = Duration::from_secs(60);
+
+/// A burst gives up early after this many failed polls in a row rather
+/// than keep hammering a BMC that is not answering.
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// How an observed power state relates to the one an action asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerProgress {
+    Reached,
+    /// `PoweringOn` / `PoweringOff` on the way to the expected state.
+    Transitioning,
+    Pending,
+}
+
+/// The power state a successful action should end in.
+pub fn expected_state(action: &str) -> Option<&'static str> {
+    match action {
+        "PowerOn" => Some("On"),
+        "ForceOff" | "GracefulShutdown" => Some("Off"),
+        _ => None,
+    }
+}
+
+pub fn progress(expected: &str, observed: &str) -> PowerProgress {
+    if observed == expected {
+        PowerProgress::Reached
+    } else if observed.strip_prefix("Powering") == Some(expected) {
+        PowerProgress::Transitioning
+    } else {
+        PowerProgress::Pending
+    }
+}
+
+struct Burst {
+    expected: &'static str,
+    deadline: Instant,
+}
+
+/// Short-poll bursts currently running, keyed by server alias. There is at
+/// most one per server; a new action on a server that is already bursting
+/// retargets the running burst instead of starting a second poller.
+#[derive(Default)]
+pub struct PowerBursts {
+    active: Mutex<HashMap<String, Burst>>,
+}
+
+impl PowerBursts {
+    /// The expected state and deadline of the server's burst, if still running.
+    fn current(&self, alias: &str) -> Option<(&'static str, Instant)> {
+        self.active
+            .lock()
+            .unwrap()
+            .get(alias)
+            .map(|burst| (burst.expected, burst.deadline))
+    }
+
+    /// End the burst unless another action retargeted it since `expected`
+    /// was read.
+    fn finish(&self, alias: &str, expected: &'static str) -> bool {
+        let mut active = self.active.lock().unwrap();
+        match active.get(alias) {
+            Some(burst) if burst.expected == expected => {
+                active.remove(alias);
+                true
+            }
+            Some(_) => false,
+            None => true,
+        }
+    }
+}
+
+/// Poll `alias` every couple of seconds until it reaches `expected` or the
+/// burst times out, publishing each observation on the event bus.
+pub fn start(state: &AppState, alias: &str, client: Arc<IdracClient>, expected: &'static str) {
+    {
+        let mut active = state.power_bursts.active.lock().unwrap();
+        let deadline = Instant::now() + BURST_DURATION;
+        if let Some(burst) = active.get_mut(alias) {
+            burst.expected = expected;
+            burst.deadline = deadline;
+            return;
+        }
+        active.insert(alias.to_string(), Burst { expected, deadline });
+    }
+
+    tokio::spawn(run(state.clone(), alias.to_string(), client));
+}
+
+async fn run(state: AppState, alias: String, client: Arc<IdracClient>) {
+    let mut failures = 0;
+
+    loop {
+        tokio::time::sleep(BURST_INTERVAL).await;
+        let Some((expected, deadline)) = state.power_bursts.current(&alias) else {
+            return;
+        };
+
+        match client.get_power_state().await {
+            Ok(power_state) => {
+                failures = 0;
+                let progress = progress(expected, &power_state);
+                state.publish(AppEvent::PowerStateObserved {
+                    server: alias.clone(),
+                    power_state,
+                    expected: expected.to_string(),
+                    progress,
+                });
+                if progress == PowerProgress::Reached && state.power_bursts.finish(&alias, expected) {
+                    info!("Server '{}' reached {}", alias, expected);
+                    let reason = client.get_last_power_reason().await.ok();
+                    state.record_power_event(&alias, expected, reason.as_ref());
+                    return;
+                }
+            }
+            Err(e) => {
+                failures += 1;
+                warn!("Power state poll for '{}' failed: {}", alias, e);
+                if failures >= MAX_CONSECUTIVE_FAILURES && state.power_bursts.finish(&alias, expected) {
+                    warn!("Stopping power state burst for '{}' after {} failed polls", alias, failures);
+                    return;
+                }
+            }
+        }
+
+        if Instant::now() >= deadline && state.power_bursts.finish(&alias, expected) {
+            warn!(
+                "Server '{}' did not reach {} within {}s",
+                alias,
+                expected,
+                BURST_DURATION.as_secs()
+            );
+            return;
+        }
+    }
+}
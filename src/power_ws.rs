@@ -0,0 +1,147 @@
+//! `/ws/power`: a WebSocket that pushes a server's power state whenever it
+//! changes, so the dashboard no longer needs refreshing. Each connection
+//! reads the state every `IDRAC_POLL_INTERVAL_SECS` and only sends what
+//! differs from its last message. Its task ends when the client closes
+//! the socket or goes away.
+
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws::{Codec, Frame, Message};
+use actix_web::body::{BodyStream, BoxBody};
+use actix_web::web::{self, Bytes, BytesMut};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::Utc;
+use futures_util::StreamExt;
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::idrac::IdracClient;
+
+/// Frames queued for a client that is slow to read.
+const OUTGOING_CAPACITY: usize = 16;
+
+#[derive(Debug, Serialize)]
+pub struct PowerStateMessage {
+    pub power_state: String,
+    pub timestamp: String,
+}
+
+/// Accept the WebSocket upgrade in `req` and start pushing the power state
+/// of `client`'s server. The caller has authenticated the request.
+pub fn start(
+    req: &HttpRequest,
+    payload: web::Payload,
+    alias: String,
+    client: Arc<IdracClient>,
+    every: Duration,
+) -> HttpResponse {
+    let mut response = match actix_http::ws::handshake(req.head()) {
+        Ok(response) => response,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+
+    let (outgoing, receiver) = mpsc::channel::<Bytes>(OUTGOING_CAPACITY);
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|frame| (Ok::<_, actix_web::Error>(frame), receiver))
+    });
+    let response = match response.message_body(BoxBody::new(BodyStream::new(body))) {
+        Ok(response) => response,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+
+    // The payload is tied to this worker's thread.
+    actix_web::rt::spawn(run(payload, outgoing, alias, client, every));
+    response.into()
+}
+
+/// Poll the server and answer the client's control frames until either
+/// side closes.
+async fn run(
+    mut payload: web::Payload,
+    outgoing: mpsc::Sender<Bytes>,
+    alias: String,
+    client: Arc<IdracClient>,
+    every: Duration,
+) {
+    info!("Power WebSocket for '{}' connected", alias);
+    let mut codec = Codec::new();
+    let mut incoming = BytesMut::new();
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut last_sent: Option<String> = None;
+    let mut last_error: Option<String> = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                match client.get_power_state().await {
+                    Ok(power_state) => {
+                        last_error = None;
+                        if last_sent.as_deref() == Some(power_state.as_str()) {
+                            continue;
+                        }
+                        let message = PowerStateMessage {
+                            power_state: power_state.clone(),
+                            timestamp: Utc::now().to_rfc3339(),
+                        };
+                        let text = serde_json::to_string(&message).unwrap_or_default();
+                        if send(&mut codec, &outgoing, Message::Text(text.into())).await.is_err() {
+                            break;
+                        }
+                        last_sent = Some(power_state);
+                    }
+                    // Kept quiet until it changes, as it recurs every tick.
+                    Err(e) if last_error.as_ref() != Some(&e) => {
+                        warn!("Power WebSocket for '{}' cannot read the power state: {}", alias, e);
+                        last_error = Some(e);
+                    }
+                    Err(_) => {}
+                }
+            }
+            chunk = payload.next() => {
+                let Some(Ok(chunk)) = chunk else {
+                    break;
+                };
+                incoming.extend_from_slice(&chunk);
+                if !answer_frames(&mut codec, &mut incoming, &outgoing).await {
+                    break;
+                }
+            }
+            // The response body was dropped with the connection.
+            _ = outgoing.closed() => break,
+        }
+    }
+    info!("Power WebSocket for '{}' closed", alias);
+}
+
+/// Answer the complete frames in `incoming`. Returns `false` once the
+/// connection should end.
+async fn answer_frames(codec: &mut Codec, incoming: &mut BytesMut, outgoing: &mpsc::Sender<Bytes>) -> bool {
+    loop {
+        let reply = match codec.decode(incoming) {
+            Ok(Some(Frame::Ping(data))) => Message::Pong(data),
+            Ok(Some(Frame::Close(reason))) => {
+                let _ = send(codec, outgoing, Message::Close(reason)).await;
+                return false;
+            }
+            // Nothing the client sends is acted on.
+            Ok(Some(_)) => continue,
+            Ok(None) => return true,
+            Err(e) => {
+                warn!("Power WebSocket protocol error: {}", e);
+                return false;
+            }
+        };
+        if send(codec, outgoing, reply).await.is_err() {
+            return false;
+        }
+    }
+}
+
+async fn send(codec: &mut Codec, outgoing: &mpsc::Sender<Bytes>, message: Message) -> Result<(), ()> {
+    let mut frame = BytesMut::new();
+    codec.encode(message, &mut frame).map_err(|_| ())?;
+    outgoing.send(frame.freeze()).await.map_err(|_| ())
+}
@@ -0,0 +1,122 @@
+use chrono::Utc;
+use futures_util::stream::{self, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::database::SQLITE_TIMESTAMP_FORMAT;
+use crate::idrac::PowerSupply;
+use crate::state::{AppEvent, AppState};
+
+/// Servers are checked at most this many at a time.
+const CHECK_CONCURRENCY: usize = 8;
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A power supply problem that `/api/alerts` reports while it lasts.
+#[derive(Debug, Clone, Serialize)]
+pub struct PsuIssue {
+    pub server: String,
+    pub psu_name: String,
+    /// `failed`, `input_lost` or `degraded`.
+    pub issue: &'static str,
+    pub voltage: Option<f64>,
+    /// `Critical` when the server has no other healthy supply left, so
+    /// losing this one takes it down.
+    pub severity: &'static str,
+    pub since: String,
+}
+
+/// What is wrong with `supply`, if anything. A low input voltage wins over
+/// the health the iDRAC reports, since a supply without input is also
+/// reported `Critical`.
+fn classify(supply: &PowerSupply, min_input_voltage: f64) -> Option<&'static str> {
+    if supply.state.as_deref() == Some("Absent") {
+        return None;
+    }
+    if supply.line_input_voltage.is_some_and(|voltage| voltage < min_input_voltage) {
+        return Some("input_lost");
+    }
+    match supply.health.as_deref() {
+        Some("Critical") => Some("failed"),
+        Some("Warning") => Some("degraded"),
+        _ => None,
+    }
+}
+
+/// Read every server's power supplies and publish a `psu_alert` event for
+/// each supply that newly has a problem, or a different one than before.
+/// Servers that cannot be read keep their known issues.
+pub async fn check_all(state: &AppState) {
+    let min_input_voltage = state.config.psu_min_input_voltage;
+    let readings: Vec<(String, Vec<PowerSupply>)> = stream::iter(state.servers.pollable())
+        .map(|server| async move {
+            match tokio::time::timeout(CHECK_TIMEOUT, server.client.get_power_supplies()).await {
+                Ok(Ok(supplies)) => Some((server.alias.clone(), supplies)),
+                Ok(Err(e)) => {
+                    warn!("PSU check of '{}' failed: {}", server.alias, e);
+                    None
+                }
+                Err(_) => {
+                    warn!("PSU check of '{}' timed out after {}s", server.alias, CHECK_TIMEOUT.as_secs());
+                    None
+                }
+            }
+        })
+        .buffer_unordered(CHECK_CONCURRENCY)
+        .filter_map(|reading| async move { reading })
+        .collect()
+        .await;
+
+    let mut issues = state.psu_issues.write().unwrap();
+    for (alias, supplies) in readings {
+        let found: Vec<(&PowerSupply, &'static str)> = supplies
+            .iter()
+            .filter_map(|supply| classify(supply, min_input_voltage).map(|issue| (supply, issue)))
+            .collect();
+        let healthy = supplies
+            .iter()
+            .filter(|supply| supply.state.as_deref() != Some("Absent") && classify(supply, min_input_voltage).is_none())
+            .count();
+
+        issues.retain(|(server, psu_name), _| {
+            let recovered = *server == alias && !found.iter().any(|(supply, _)| supply.name == *psu_name);
+            if recovered {
+                info!("PSU '{}' of '{}' is healthy again", psu_name, server);
+            }
+            !recovered
+        });
+
+        for (supply, issue) in found {
+            let key = (alias.clone(), supply.name.clone());
+            if issues.get(&key).is_some_and(|known| known.issue == issue) {
+                continue;
+            }
+            let severity = if issue == "degraded" || healthy > 0 { "Warning" } else { "Critical" };
+            warn!(
+                "PSU '{}' of '{}': {} ({} healthy supply(s) left)",
+                supply.name, alias, issue, healthy
+            );
+            state.publish(AppEvent::PsuAlert {
+                server: alias.clone(),
+                psu_name: supply.name.clone(),
+                issue: issue.to_string(),
+                voltage: supply.line_input_voltage,
+                severity: severity.to_string(),
+            });
+            issues.insert(
+                key,
+                PsuIssue {
+                    server: alias.clone(),
+                    psu_name: supply.name.clone(),
+                    issue,
+                    voltage: supply.line_input_voltage,
+                    severity,
+                    since: Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+                },
+            );
+        }
+    }
+
+    // Servers removed since their issue was seen.
+    issues.retain(|(server, _), _| state.servers.get(server).is_some());
+}
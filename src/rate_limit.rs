@@ -0,0 +1,179 @@
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use log::{error, info, warn};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::errors::{CREDENTIALS_REJECTED_PREFIX, RATE_LIMITED_PREFIX};
+
+/// iDRAC 9 throttles Redfish at roughly 120 requests a minute.
+const DEFAULT_REQUESTS_PER_SECOND: u32 = 2;
+const DEFAULT_QUEUE_DEPTH: usize = 10;
+
+/// Requests per second allowed to one iDRAC, and how many requests may
+/// wait for their turn before further ones are refused.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub per_second: NonZeroU32,
+    pub queue_depth: usize,
+}
+
+impl RateLimit {
+    /// `IDRAC_API_RPS` and `IDRAC_QUEUE_DEPTH`.
+    pub fn from_env() -> Self {
+        RateLimit {
+            per_second: std::env::var("IDRAC_API_RPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(NonZeroU32::new(DEFAULT_REQUESTS_PER_SECOND).unwrap()),
+            queue_depth: std::env::var("IDRAC_QUEUE_DEPTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_QUEUE_DEPTH),
+        }
+    }
+}
+
+/// Counts a request waiting for the limiter until it gets its turn or
+/// is dropped.
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The HTTP client of one iDRAC. Every request waits for the host's
+/// quota; once `queue_depth` requests are already waiting, further ones
+/// fail straight away instead.
+///
+/// A request answered 401 twice in a row marks the credentials rejected.
+/// From then on requests fail without reaching the iDRAC, which would
+/// otherwise lock the account out, until a connection test succeeds.
+#[derive(Clone)]
+pub struct RateLimitedIdracClient {
+    host: String,
+    client: Client,
+    limit: RateLimit,
+    limiter: Arc<DefaultDirectRateLimiter>,
+    waiting: Arc<AtomicUsize>,
+    /// When the credentials were rejected, as RFC 3339.
+    credentials_rejected_at: Arc<RwLock<Option<String>>>,
+}
+
+impl RateLimitedIdracClient {
+    pub fn new(host: &str, client: Client, limit: RateLimit) -> Self {
+        RateLimitedIdracClient {
+            host: host.to_string(),
+            client,
+            limit,
+            limiter: Arc::new(RateLimiter::direct(Quota::per_second(limit.per_second))),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            credentials_rejected_at: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// When the iDRAC last rejected the credentials, while it still counts.
+    pub fn credentials_rejected_at(&self) -> Option<String> {
+        self.credentials_rejected_at.read().unwrap().clone()
+    }
+
+    pub fn mark_credentials_rejected(&self) {
+        let mut rejected_at = self.credentials_rejected_at.write().unwrap();
+        if rejected_at.is_none() {
+            error!(
+                "{} rejected the credentials; requests to it are paused until they are updated and a connection test succeeds",
+                self.host
+            );
+            *rejected_at = Some(chrono::Utc::now().to_rfc3339());
+        }
+    }
+
+    pub fn clear_credentials_rejected(&self) {
+        if self.credentials_rejected_at.write().unwrap().take().is_some() {
+            info!("{} accepts the credentials again; resuming requests", self.host);
+        }
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    pub fn patch(&self, url: &str) -> RequestBuilder {
+        self.client.patch(url)
+    }
+
+    pub fn delete(&self, url: &str) -> RequestBuilder {
+        self.client.delete(url)
+    }
+
+    /// Wait until the host's quota allows another request.
+    pub async fn acquire(&self) -> Result<(), String> {
+        if self.limiter.check().is_ok() {
+            return Ok(());
+        }
+
+        let ahead = self.waiting.fetch_add(1, Ordering::SeqCst);
+        let _slot = QueueSlot(&self.waiting);
+        if ahead >= self.limit.queue_depth {
+            warn!(
+                "Refusing request to {}: {} requests are already waiting for its rate limit",
+                self.host, ahead
+            );
+            return Err(format!(
+                "{} for {}: {} requests are already queued",
+                RATE_LIMITED_PREFIX, self.host, ahead
+            ));
+        }
+        if ahead == 0 {
+            info!(
+                "Rate limiting requests to {} at {}/s",
+                self.host, self.limit.per_second
+            );
+        }
+        self.limiter.until_ready().await;
+        Ok(())
+    }
+
+    /// Send `request` once the quota allows it. A 401 is retried once, as
+    /// iDRACs sometimes answer 401 while they are busy; a second one
+    /// marks the credentials rejected.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, String> {
+        if let Some(rejected_at) = self.credentials_rejected_at() {
+            return Err(format!(
+                "{} by {} at {}; update the server's credentials, then run a connection test (GET /api/idrac/test-connection?server=<alias>) to resume",
+                CREDENTIALS_REJECTED_PREFIX, self.host, rejected_at
+            ));
+        }
+
+        let retry = request.try_clone();
+        let response = self.send_once(request).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(retry) = retry else {
+            return Ok(response);
+        };
+
+        warn!("{} answered 401; retrying once", self.host);
+        let response = self.send_once(retry).await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            self.mark_credentials_rejected();
+        }
+        Ok(response)
+    }
+
+    async fn send_once(&self, request: RequestBuilder) -> Result<Response, String> {
+        self.acquire().await?;
+        request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to iDRAC: {}", e))
+    }
+}
@@ -0,0 +1,89 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::validation::FieldError;
+
+/// Key of the stored policy in the `settings` table.
+const SETTINGS_KEY: &str = "retention_policy";
+const MAX_RETENTION_DAYS: u32 = 36500;
+const MAX_SESSION_TTL_HOURS: u32 = 24 * 365;
+
+/// How long data is kept. A value of 0 days keeps data forever.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub audit_days: u32,
+    /// Tracked operations such as escalating shutdowns, power and
+    /// telemetry samples, and SEL entries pulled by the nightly sweep.
+    pub history_days: u32,
+    pub connectivity_log_days: u32,
+    pub sessions_ttl_hours: u32,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        RetentionPolicy {
+            audit_days: config.audit_retention_days,
+            history_days: config.history_retention_days,
+            connectivity_log_days: config.connectivity_log_retention_days,
+            sessions_ttl_hours: config.session_ttl_hours,
+        }
+    }
+
+    /// The policy last saved through the API, or the one from the
+    /// environment if none was saved.
+    pub fn load(db: &Database, config: &Config) -> Self {
+        match db.get_setting(SETTINGS_KEY) {
+            Ok(Some(stored)) => match serde_json::from_str(&stored) {
+                Ok(policy) => return policy,
+                Err(e) => warn!("Ignoring invalid stored retention policy: {}", e),
+            },
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load retention policy: {}", e),
+        }
+        RetentionPolicy::from_config(config)
+    }
+
+    pub fn save(&self, db: &Database) -> Result<(), String> {
+        let value = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        db.put_setting(SETTINGS_KEY, &value)
+            .map_err(|e| format!("Failed to save retention policy: {}", e))
+    }
+
+    pub fn validate(&self) -> Result<(), FieldError> {
+        let days = [
+            ("audit_days", self.audit_days),
+            ("history_days", self.history_days),
+            ("connectivity_log_days", self.connectivity_log_days),
+        ];
+        for (field, value) in days {
+            if value > MAX_RETENTION_DAYS {
+                return Err(FieldError {
+                    field,
+                    message: format!("must be at most {}", MAX_RETENTION_DAYS),
+                });
+            }
+        }
+        if self.sessions_ttl_hours == 0 || self.sessions_ttl_hours > MAX_SESSION_TTL_HOURS {
+            return Err(FieldError {
+                field: "sessions_ttl_hours",
+                message: format!("must be between 1 and {}", MAX_SESSION_TTL_HOURS),
+            });
+        }
+        Ok(())
+    }
+
+    /// One warning per category that is kept forever.
+    pub fn warnings(&self) -> Vec<String> {
+        [
+            ("audit_days", self.audit_days),
+            ("history_days", self.history_days),
+            ("connectivity_log_days", self.connectivity_log_days),
+        ]
+        .iter()
+        .filter(|(_, days)| *days == 0)
+        .map(|(field, _)| format!("{} is 0: retention is infinite and nothing is cleaned up", field))
+        .collect()
+    }
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use log::{info, warn};
+
+use crate::config::Config;
+use crate::database::{Database, RollupGranularity, SQLITE_TIMESTAMP_FORMAT};
+use crate::validation::FieldError;
+
+/// Ranges up to this long are answered from raw samples by default.
+const RAW_MAX_RANGE_HOURS: i64 = 48;
+/// Ranges up to this long are answered from hourly rollups by default,
+/// longer ones from daily rollups.
+const HOURLY_MAX_RANGE_DAYS: i64 = 60;
+
+const USAGE: &str = "Usage: idrac-controller rollup-backfill
+
+Recomputes every hourly and daily metric rollup from the telemetry samples
+still stored, for databases upgraded from a version without rollups. Reads
+the same environment as the server and is safe to run more than once.";
+
+/// The granularity to answer a samples query with: `None` for raw
+/// samples. `requested` is `raw`, `hour`, `day` or `auto`; `auto` picks by
+/// the length of `since..until`, with raw samples for an open range.
+pub fn choose(
+    requested: Option<&str>,
+    since: Option<&str>,
+    until: Option<&str>,
+) -> Result<Option<RollupGranularity>, FieldError> {
+    match requested.map(str::trim).unwrap_or("auto") {
+        "raw" => Ok(None),
+        "hour" => Ok(Some(RollupGranularity::Hour)),
+        "day" => Ok(Some(RollupGranularity::Day)),
+        "auto" => {
+            let parse = |t: &str| NaiveDateTime::parse_from_str(t, SQLITE_TIMESTAMP_FORMAT).ok();
+            let Some(since) = since.and_then(parse) else {
+                return Ok(None);
+            };
+            let until = until.and_then(parse).unwrap_or_else(|| Utc::now().naive_utc());
+            let range = until - since;
+            Ok(if range <= Duration::hours(RAW_MAX_RANGE_HOURS) {
+                None
+            } else if range <= Duration::days(HOURLY_MAX_RANGE_DAYS) {
+                Some(RollupGranularity::Hour)
+            } else {
+                Some(RollupGranularity::Day)
+            })
+        }
+        _ => Err(FieldError {
+            field: "granularity",
+            message: "must be raw, hour, day or auto".to_string(),
+        }),
+    }
+}
+
+/// Start of the first bucket at or after `time`.
+fn bucket_at_or_after(time: DateTime<Utc>, granularity: RollupGranularity) -> String {
+    let size = granularity.seconds();
+    let start = (time.timestamp() + size - 1).div_euclid(size) * size;
+    DateTime::from_timestamp(start, 0)
+        .unwrap_or(time)
+        .format(SQLITE_TIMESTAMP_FORMAT)
+        .to_string()
+}
+
+/// Bring the rollups up to date before samples older than `history_days`
+/// are pruned. The newest stored bucket and everything after it are
+/// recomputed, as the bucket may have been partial when last written; a
+/// run that stopped half way is repeated by the next one. Buckets that
+/// already lost samples to pruning are left as they are.
+pub fn roll_up(db: &Database, history_days: u32) {
+    for granularity in RollupGranularity::ALL {
+        let latest = match db.latest_metric_rollup(granularity) {
+            Ok(latest) => latest,
+            Err(e) => {
+                warn!("Failed to read the newest {} metric rollup: {}", granularity.as_str(), e);
+                continue;
+            }
+        };
+        let complete_from = (history_days > 0).then(|| {
+            bucket_at_or_after(Utc::now() - Duration::days(i64::from(history_days)), granularity)
+        });
+        let from = match (latest, complete_from) {
+            (Some(latest), Some(complete_from)) => Some(latest.max(complete_from)),
+            (latest, complete_from) => latest.or(complete_from),
+        };
+
+        match db.roll_up_metric_samples(granularity, from.as_deref()) {
+            Ok(0) => {}
+            Ok(written) => info!("Updated {} {} metric rollup(s)", written, granularity.as_str()),
+            Err(e) => warn!("Failed to update {} metric rollups: {}", granularity.as_str(), e),
+        }
+    }
+}
+
+/// `idrac-controller rollup-backfill`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    if let Some(arg) = args.next() {
+        return match arg.as_str() {
+            "--help" | "-h" => Err(USAGE.to_string()),
+            other => Err(format!("Unknown argument '{}'\n{}", other, USAGE)),
+        };
+    }
+
+    let config = Config::from_env();
+    if config.standby_mode {
+        return Err("STANDBY_MODE is set; backfill rollups on the primary".to_string());
+    }
+    let db = Database::open(config.database_location()).map_err(|e| format!("Failed to open database: {}", e))?;
+
+    for granularity in RollupGranularity::ALL {
+        let written = db
+            .roll_up_metric_samples(granularity, None)
+            .map_err(|e| format!("Failed to compute {} rollups: {}", granularity.as_str(), e))?;
+        println!("Wrote {} {} rollup(s).", written, granularity.as_str());
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Duration, Utc};
+use cron::Schedule;
+use std::str::FromStr;
+
+use crate::audit::AuditAction;
+
+/// Parse a cron expression. Standard five-field expressions
+/// (`min hour day month weekday`) are accepted as well as the six- and
+/// seven-field forms with seconds and year.
+pub fn parse_cron(expr: &str) -> Result<Schedule, String> {
+    let expr = expr.trim();
+    let full = match expr.split_whitespace().count() {
+        5 => format!("0 {}", expr),
+        6 | 7 => expr.to_string(),
+        n => return Err(format!("expected 5 to 7 fields, got {}", n)),
+    };
+    Schedule::from_str(&full).map_err(|e| format!("invalid cron expression '{}': {}", expr, e))
+}
+
+/// Whether a window that opens at each firing of `schedule` and lasts
+/// `duration` covers `now`.
+pub fn is_window_active(schedule: &Schedule, duration: Duration, now: DateTime<Utc>) -> bool {
+    schedule
+        .after(&(now - duration))
+        .next()
+        .map(|start| start <= now)
+        .unwrap_or(false)
+}
+
+/// Actions a one-shot schedule may run, with the name each is audited under.
+pub const ONE_SHOT_ACTIONS: &[(&str, AuditAction)] = &[
+    ("on", AuditAction::PowerOn),
+    ("off", AuditAction::ForceOff),
+    ("shutdown", AuditAction::GracefulShutdown),
+];
+
+/// Audit name of a one-shot action, or `None` if it is not supported.
+pub fn one_shot_audit_name(action: &str) -> Option<AuditAction> {
+    ONE_SHOT_ACTIONS
+        .iter()
+        .find(|(name, _)| *name == action)
+        .map(|(_, audit_name)| *audit_name)
+}
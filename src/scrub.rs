@@ -0,0 +1,145 @@
+use base64::Engine;
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+
+/// Replacement for anything that looks like a credential.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Field names (matched case-insensitively as substrings) whose values are
+/// never written to logs or the database.
+const SENSITIVE_KEYS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "authorization",
+    "api_key",
+    "apikey",
+    "credential",
+    "private_key",
+    "licensefile",
+];
+
+/// `Authorization` header values: `Basic dXNlcjpwYXNz`, `Bearer idrac_...`.
+static AUTH_SCHEME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(basic|bearer)\s+[A-Za-z0-9._~+/=-]+").unwrap());
+
+/// `password=...`, `"token": "..."` and similar in free text.
+static KEY_VALUE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)("?[a-z_]*(?:password|passwd|secret|token|api_?key)[a-z_]*"?\s*[:=]\s*"?)([^"\s&,}]+)"#).unwrap()
+});
+
+/// Tokens issued by `tokens::generate_token`.
+static API_TOKEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bidrac_[A-Za-z0-9_-]{32,}").unwrap());
+
+/// Candidate base64 runs; only those decoding to `user:password` are redacted.
+static BASE64_RUN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[A-Za-z0-9+/]{8,}={0,2}").unwrap());
+
+pub fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.iter().any(|sensitive| key.contains(sensitive))
+}
+
+/// Whether `candidate` is base64 of printable `user:password` text.
+fn is_credential_blob(candidate: &str) -> bool {
+    match base64::engine::general_purpose::STANDARD.decode(candidate) {
+        Ok(bytes) => bytes.contains(&b':') && bytes.iter().all(|b| b.is_ascii_graphic()),
+        Err(_) => false,
+    }
+}
+
+/// Redact credentials from text bound for a log line or the database.
+pub fn scrub_text(text: &str) -> String {
+    let text = AUTH_SCHEME.replace_all(text, |caps: &Captures| format!("{} {}", &caps[1], REDACTED));
+    let text = API_TOKEN.replace_all(&text, REDACTED);
+    let text = KEY_VALUE.replace_all(&text, |caps: &Captures| format!("{}{}", &caps[1], REDACTED));
+    BASE64_RUN
+        .replace_all(&text, |caps: &Captures| {
+            // `/` is a base64 character but also separates URL path segments.
+            if is_credential_blob(&caps[0]) {
+                REDACTED.to_string()
+            } else {
+                caps[0]
+                    .split('/')
+                    .map(|segment| if is_credential_blob(segment) { REDACTED } else { segment })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            }
+        })
+        .into_owned()
+}
+
+/// A log record's message as it is written out.
+pub fn log_message(record: &log::Record) -> String {
+    scrub_text(&record.args().to_string())
+}
+
+/// Redact credentials from a JSON document in place: values under
+/// sensitive keys are replaced outright and other strings are scrubbed
+/// as text.
+pub fn scrub_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_sensitive_key(key) && !value.is_null() {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    scrub_json(value);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(scrub_json),
+        serde_json::Value::String(text) => *text = scrub_text(text),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorization_headers_are_redacted() {
+        let basic = base64::engine::general_purpose::STANDARD.encode("root:calvin");
+        let scrubbed = scrub_text(&format!("Authorization: Basic {}", basic));
+        assert_eq!(scrubbed, format!("Authorization: Basic {}", REDACTED));
+        assert_eq!(scrub_text("authorization: bearer abc.def-123"), format!("authorization: bearer {}", REDACTED));
+    }
+
+    #[test]
+    fn passwords_and_tokens_in_text_are_redacted() {
+        let token = crate::tokens::generate_token();
+        let scrubbed = scrub_text(&format!("login password=hunter2&user=root, token {} and \"api_key\": \"k-123\"", token));
+        for secret in ["hunter2", token.as_str(), "k-123"] {
+            assert!(!scrubbed.contains(secret), "{} survived: {}", secret, scrubbed);
+        }
+        assert!(scrubbed.contains("user=root"), "{}", scrubbed);
+    }
+
+    #[test]
+    fn credential_blobs_are_redacted_but_paths_are_kept() {
+        let blob = base64::engine::general_purpose::STANDARD.encode("admin:s3cret!");
+        assert_eq!(scrub_text(&format!("/api/proxy/{}/status", blob)), format!("/api/proxy/{}/status", REDACTED));
+        assert_eq!(scrub_text("/redfish/v1/Systems/System.Embedded.1"), "/redfish/v1/Systems/System.Embedded.1");
+    }
+
+    #[test]
+    fn sensitive_json_fields_are_redacted() {
+        let mut body = serde_json::json!({
+            "username": "root",
+            "Password": "calvin",
+            "nested": [{ "client_secret": "abc" }, "Bearer xyz"],
+            "token": null,
+        });
+        scrub_json(&mut body);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "username": "root",
+                "Password": REDACTED,
+                "nested": [{ "client_secret": REDACTED }, format!("Bearer {}", REDACTED)],
+                "token": null,
+            })
+        );
+    }
+}
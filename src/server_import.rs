@@ -0,0 +1,242 @@
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::database::NewServer;
+use crate::idrac::IdracClient;
+use crate::secret::SecretString;
+use crate::state::AppState;
+use crate::validation::{slugify, validate_new_server};
+
+/// Connection tests run at most this many at a time.
+const IMPORT_TEST_CONCURRENCY: usize = 4;
+const IMPORT_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tags within the `tags` column are separated by this character, since
+/// commas delimit the columns themselves.
+const TAG_SEPARATOR: char = ';';
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    /// Report what would happen without storing anything.
+    pub validate_only: bool,
+    /// Run a connection test against every valid row first.
+    pub test_connections: bool,
+}
+
+/// Outcome of one CSV row. Never includes the password.
+#[derive(Debug, Serialize)]
+pub struct ImportRow {
+    /// Line number in the uploaded file, counting the header as line 1.
+    pub row: u64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub validate_only: bool,
+    /// Rows stored, or with `validate_only` the rows that would be.
+    pub created: Vec<ImportRow>,
+    pub skipped: Vec<ImportRow>,
+    pub failed: Vec<ImportRow>,
+}
+
+impl ImportReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} created, {} skipped, {} failed",
+            self.created.len(),
+            self.skipped.len(),
+            self.failed.len()
+        )
+    }
+
+    fn fail(&mut self, row: u64, name: &str, reason: impl Into<String>) {
+        self.failed.push(ImportRow {
+            row,
+            name: name.to_string(),
+            alias: None,
+            reason: Some(reason.into()),
+        });
+    }
+}
+
+struct Columns {
+    name: usize,
+    host: usize,
+    username: usize,
+    password: Option<usize>,
+    credential_profile: Option<usize>,
+    tags: Option<usize>,
+    location: Option<usize>,
+}
+
+impl Columns {
+    fn from_headers(headers: &csv::StringRecord) -> Result<Self, String> {
+        let find = |column: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(column));
+        let require = |column: &str| find(column).ok_or_else(|| format!("CSV is missing the '{}' column", column));
+
+        let columns = Columns {
+            name: require("name")?,
+            host: require("host")?,
+            username: require("username")?,
+            password: find("password"),
+            credential_profile: find("credential_profile"),
+            tags: find("tags"),
+            location: find("location"),
+        };
+        if columns.password.is_none() && columns.credential_profile.is_none() {
+            return Err("CSV needs a 'password' or 'credential_profile' column".to_string());
+        }
+        Ok(columns)
+    }
+}
+
+/// Validate, optionally test, and store the servers listed in a CSV file.
+/// Fails only when the file as a whole cannot be read; problems with
+/// individual rows are reported in the returned `ImportReport`.
+pub async fn import_servers(state: &AppState, csv_data: &[u8], options: ImportOptions) -> Result<ImportReport, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(csv_data);
+    let headers = reader.headers().map_err(|e| format!("Invalid CSV header: {}", e))?.clone();
+    let columns = Columns::from_headers(&headers)?;
+
+    let mut report = ImportReport {
+        validate_only: options.validate_only,
+        ..Default::default()
+    };
+    let mut candidates: Vec<(u64, NewServer)> = Vec::new();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let row = e.position().map(|p| p.line()).unwrap_or(0);
+                report.fail(row, "", format!("unreadable row: {}", e));
+                continue;
+            }
+        };
+        let row = record.position().map(|p| p.line()).unwrap_or(0);
+        let field = |index: Option<usize>| index.and_then(|i| record.get(i)).unwrap_or("");
+        let name = field(Some(columns.name));
+        let password = field(columns.password);
+
+        if password.is_empty() && !field(columns.credential_profile).is_empty() {
+            report.fail(row, name, "credential profiles are not supported yet; provide a password");
+            continue;
+        }
+
+        let tags: Vec<String> = field(columns.tags)
+            .split(TAG_SEPARATOR)
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+        let location = Some(field(columns.location)).filter(|l| !l.is_empty());
+
+        let server = match validate_new_server(
+            name,
+            field(Some(columns.host)),
+            field(Some(columns.username)),
+            SecretString::from(password.to_string()),
+            &tags,
+            location,
+        ) {
+            Ok(server) => server,
+            Err(e) => {
+                report.fail(row, name, e.to_string());
+                continue;
+            }
+        };
+
+        let duplicate = state.servers.find_duplicate(&server.name, &server.host).or_else(|| {
+            candidates.iter().find_map(|(earlier, other)| {
+                (slugify(&other.name) == slugify(&server.name) || other.host == server.host)
+                    .then(|| format!("duplicate of row {}", earlier))
+            })
+        });
+        if let Some(reason) = duplicate {
+            report.skipped.push(ImportRow {
+                row,
+                name: server.name,
+                alias: None,
+                reason: Some(reason),
+            });
+            continue;
+        }
+
+        candidates.push((row, server));
+    }
+
+    if options.test_connections {
+        let tested: Vec<((u64, NewServer), Result<(), String>)> = stream::iter(candidates)
+            .map(|candidate| async move {
+                let result = test_candidate(&candidate.1).await;
+                (candidate, result)
+            })
+            .buffer_unordered(IMPORT_TEST_CONCURRENCY)
+            .collect()
+            .await;
+
+        candidates = Vec::new();
+        for ((row, server), result) in tested {
+            match result {
+                Ok(()) => candidates.push((row, server)),
+                Err(e) => report.fail(row, &server.name, format!("connection test failed: {}", e)),
+            }
+        }
+        candidates.sort_by_key(|(row, _)| *row);
+    }
+
+    for (row, server) in candidates {
+        let name = server.name.clone();
+        if options.validate_only {
+            report.created.push(ImportRow {
+                row,
+                name,
+                alias: Some(slugify(&server.name)),
+                reason: None,
+            });
+            continue;
+        }
+
+        // Another request may have added a conflicting server meanwhile.
+        if let Some(reason) = state.servers.find_duplicate(&server.name, &server.host) {
+            report.skipped.push(ImportRow {
+                row,
+                name,
+                alias: None,
+                reason: Some(reason),
+            });
+            continue;
+        }
+
+        match state.servers.create(&state.db, server) {
+            Ok(created) => report.created.push(ImportRow {
+                row,
+                name,
+                alias: Some(created.alias.clone()),
+                reason: None,
+            }),
+            Err(e) => report.fail(row, &name, e),
+        }
+    }
+
+    report.failed.sort_by_key(|r| r.row);
+    report.skipped.sort_by_key(|r| r.row);
+    Ok(report)
+}
+
+async fn test_candidate(server: &NewServer) -> Result<(), String> {
+    let client = IdracClient::new(&server.host, &server.username, &server.password)?;
+    match tokio::time::timeout(IMPORT_TEST_TIMEOUT, client.test_connection()).await {
+        Ok(result) => result.map(|_| ()),
+        Err(_) => Err(format!("timed out after {}s", IMPORT_TEST_TIMEOUT.as_secs())),
+    }
+}
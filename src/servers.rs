@@ -0,0 +1,298 @@
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::crypto::CredentialCipher;
+use crate::database::{Database, NewServer, ServerRecord};
+use crate::idrac::{normalize_host, IdracClient};
+use crate::os_health::OsHealthProbe;
+use crate::secret::SecretString;
+use crate::validation::slugify;
+
+/// Alias of the iDRAC configured through `IDRAC_HOST`.
+pub const DEFAULT_SERVER_ALIAS: &str = "default";
+
+/// One iDRAC the application can talk to.
+pub struct RegisteredServer {
+    /// Row id in `servers`; `None` for the `IDRAC_HOST` server.
+    pub id: Option<i64>,
+    /// URL-safe identifier used in API paths.
+    pub alias: String,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub location: Option<String>,
+    pub default_power_cap_watts: Option<u32>,
+    /// This application runs on the server, so powering it off takes the
+    /// controller down with it. Disruptive actions must be acknowledged.
+    pub hosts_this_app: bool,
+    /// Agent endpoint on the server's OS; see `os_health`.
+    pub os_health: Option<OsHealthProbe>,
+    pub client: Arc<IdracClient>,
+}
+
+/// Every known iDRAC: the one from the environment plus those stored in
+/// the `servers` table.
+pub struct ServerRegistry {
+    servers: RwLock<Vec<Arc<RegisteredServer>>>,
+    cipher: Arc<CredentialCipher>,
+    /// Servers in maintenance by alias, with the reason.
+    maintenance: RwLock<HashMap<String, String>>,
+}
+
+impl ServerRegistry {
+    pub fn load(
+        db: &Database,
+        cipher: Arc<CredentialCipher>,
+        default_client: Arc<IdracClient>,
+        default_hosts_this_app: bool,
+        default_os_health: Option<OsHealthProbe>,
+    ) -> Result<Self, String> {
+        let mut servers = vec![Arc::new(RegisteredServer {
+            id: None,
+            alias: DEFAULT_SERVER_ALIAS.to_string(),
+            name: default_client.base_url().to_string(),
+            tags: Vec::new(),
+            location: None,
+            default_power_cap_watts: None,
+            hosts_this_app: default_hosts_this_app,
+            os_health: default_os_health,
+            client: default_client,
+        })];
+
+        let records = db
+            .list_servers()
+            .map_err(|e| format!("Failed to load servers: {}", e))?;
+        for record in records {
+            match registered_from_record(&record, &cipher) {
+                Ok(server) => servers.push(Arc::new(server)),
+                Err(e) => warn!("Skipping server '{}': {}", record.name, e),
+            }
+        }
+
+        Ok(ServerRegistry {
+            servers: RwLock::new(servers),
+            cipher,
+            maintenance: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub fn all(&self) -> Vec<Arc<RegisteredServer>> {
+        self.servers.read().unwrap().clone()
+    }
+
+    /// Servers background tasks may poll: all but those whose iDRAC
+    /// rejected the credentials and those in maintenance.
+    pub fn pollable(&self) -> Vec<Arc<RegisteredServer>> {
+        let maintenance = self.maintenance.read().unwrap();
+        self.servers
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|server| server.client.credentials_rejected_at().is_none())
+            .filter(|server| !maintenance.contains_key(&server.alias))
+            .cloned()
+            .collect()
+    }
+
+    /// Put a server in maintenance, so that background tasks leave it
+    /// alone and the reboots of e.g. a firmware update raise no alerts.
+    /// Not persisted: whatever set it sets it again when it resumes.
+    pub fn begin_maintenance(&self, alias: &str, reason: &str) {
+        info!("Server '{}' in maintenance: {}", alias, reason);
+        self.maintenance.write().unwrap().insert(alias.to_string(), reason.to_string());
+    }
+
+    pub fn end_maintenance(&self, alias: &str) {
+        if self.maintenance.write().unwrap().remove(alias).is_some() {
+            info!("Server '{}' out of maintenance", alias);
+        }
+    }
+
+    /// Why the server is in maintenance, if it is.
+    pub fn maintenance(&self, alias: &str) -> Option<String> {
+        self.maintenance.read().unwrap().get(alias).cloned()
+    }
+
+    /// The `IDRAC_HOST` server, which is always registered first.
+    pub fn default_server(&self) -> Arc<RegisteredServer> {
+        self.servers.read().unwrap()[0].clone()
+    }
+
+    pub fn get(&self, alias: &str) -> Option<Arc<RegisteredServer>> {
+        self.servers.read().unwrap().iter().find(|s| s.alias == alias).cloned()
+    }
+
+    /// The server whose iDRAC lives at `host`, e.g. the address an event
+    /// arrived from. The port only has to match when `host` includes one.
+    pub fn find_by_host(&self, host: &str) -> Option<Arc<RegisteredServer>> {
+        let wanted = reqwest::Url::parse(&normalize_host(host).ok()?).ok()?;
+        let wanted_host = wanted.host_str()?;
+
+        self.servers.read().unwrap().iter().find(|server| {
+            reqwest::Url::parse(server.client.base_url())
+                .map(|url| {
+                    url.host_str().is_some_and(|h| h.eq_ignore_ascii_case(wanted_host))
+                        && (wanted.port().is_none() || url.port_or_known_default() == wanted.port_or_known_default())
+                })
+                .unwrap_or(false)
+        }).cloned()
+    }
+
+    /// Why a server with this name or base URL cannot be added, if it
+    /// collides with one already registered.
+    pub fn find_duplicate(&self, name: &str, base_url: &str) -> Option<String> {
+        let slug = slugify(name);
+        self.servers.read().unwrap().iter().find_map(|server| {
+            if server.name.eq_ignore_ascii_case(name) || server.alias == slug {
+                Some(format!("name conflicts with existing server '{}'", server.alias))
+            } else if server.client.base_url() == base_url {
+                Some(format!("host is already registered as '{}'", server.alias))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Store a new server with its password encrypted and make it available.
+    /// `server.host` must already be normalized.
+    pub fn create(&self, db: &Database, server: NewServer) -> Result<Arc<RegisteredServer>, String> {
+        let client = IdracClient::new(&server.host, &server.username, &server.password)?;
+
+        let os_health = match &server.os_health_url {
+            Some(url) => Some(OsHealthProbe::new(url, server.os_health_insecure, server.os_health_token.clone())?),
+            None => None,
+        };
+
+        let stored = NewServer {
+            password: SecretString::from(self.cipher.encrypt(server.password.expose())?),
+            os_health_token: server
+                .os_health_token
+                .as_ref()
+                .map(|token| self.cipher.encrypt(token.expose()).map(SecretString::from))
+                .transpose()?,
+            ..server
+        };
+        let record = db
+            .create_server(&stored)
+            .map_err(|e| format!("Failed to save server: {}", e))?;
+
+        let registered = Arc::new(RegisteredServer {
+            id: Some(record.id),
+            alias: record.slug,
+            name: record.name,
+            tags: record.tags,
+            location: record.location,
+            default_power_cap_watts: record.default_power_cap_watts,
+            hosts_this_app: record.hosts_this_app,
+            os_health,
+            client: Arc::new(client),
+        });
+        self.servers.write().unwrap().push(registered.clone());
+        Ok(registered)
+    }
+
+    /// Store new credentials for the server with row `id` and swap in a
+    /// client that logs in with them. Only the fields given change;
+    /// `password` is plaintext.
+    pub fn update_credentials(
+        &self,
+        db: &Database,
+        id: i64,
+        username: Option<&str>,
+        password: Option<&SecretString>,
+    ) -> Result<Arc<RegisteredServer>, String> {
+        let encrypted = password.map(|password| self.cipher.encrypt(password.expose())).transpose()?;
+        let record = db
+            .update_server_credentials(id, username, encrypted.as_deref())
+            .map_err(|e| format!("Failed to save credentials: {}", e))?
+            .ok_or_else(|| "Server no longer exists".to_string())?;
+
+        let registered = Arc::new(registered_from_record(&record, &self.cipher)?);
+        let mut servers = self.servers.write().unwrap();
+        if let Some(slot) = servers.iter_mut().find(|server| server.id == Some(id)) {
+            *slot = registered.clone();
+        }
+        Ok(registered)
+    }
+
+    /// Replace the OS health endpoint of the server with row `id`; no `url`
+    /// stops its probes. `token` is plaintext. The iDRAC client is kept.
+    pub fn update_os_health(
+        &self,
+        db: &Database,
+        id: i64,
+        url: Option<&str>,
+        insecure: bool,
+        token: Option<&SecretString>,
+    ) -> Result<Arc<RegisteredServer>, String> {
+        let os_health = url
+            .map(|url| OsHealthProbe::new(url, insecure, token.cloned()))
+            .transpose()?;
+        let encrypted = token.map(|token| self.cipher.encrypt(token.expose())).transpose()?;
+        db.update_server_os_health(id, url, insecure, encrypted.as_deref())
+            .map_err(|e| format!("Failed to save OS health settings: {}", e))?
+            .ok_or_else(|| "Server no longer exists".to_string())?;
+
+        let mut servers = self.servers.write().unwrap();
+        let slot = servers
+            .iter_mut()
+            .find(|server| server.id == Some(id))
+            .ok_or_else(|| "Server no longer exists".to_string())?;
+        let registered = Arc::new(RegisteredServer {
+            id: slot.id,
+            alias: slot.alias.clone(),
+            name: slot.name.clone(),
+            tags: slot.tags.clone(),
+            location: slot.location.clone(),
+            default_power_cap_watts: slot.default_power_cap_watts,
+            hosts_this_app: slot.hosts_this_app,
+            os_health,
+            client: slot.client.clone(),
+        });
+        *slot = registered.clone();
+        Ok(registered)
+    }
+
+    /// Delete the server with row `id` and stop using it. Returns whether
+    /// it existed.
+    pub fn remove(&self, db: &Database, id: i64) -> Result<bool, String> {
+        let deleted = db.delete_server(id).map_err(|e| format!("Failed to delete server: {}", e))?;
+        self.servers.write().unwrap().retain(|server| server.id != Some(id));
+        Ok(deleted)
+    }
+}
+
+/// The OS health probe a stored server is set up with. One that cannot be
+/// set up is left out with a warning rather than skipping the server.
+fn os_health_from_record(record: &ServerRecord, cipher: &CredentialCipher) -> Option<OsHealthProbe> {
+    let url = record.os_health_url.as_deref()?;
+    let probe = record
+        .os_health_token
+        .as_deref()
+        .map(|token| cipher.decrypt(token))
+        .transpose()
+        .and_then(|token| OsHealthProbe::new(url, record.os_health_insecure, token));
+    match probe {
+        Ok(probe) => Some(probe),
+        Err(e) => {
+            warn!("OS health of server '{}' not probed: {}", record.slug, e);
+            None
+        }
+    }
+}
+
+fn registered_from_record(record: &ServerRecord, cipher: &CredentialCipher) -> Result<RegisteredServer, String> {
+    let password = cipher.decrypt(&record.password)?;
+    let client = IdracClient::new(&record.host, &record.username, &password)?;
+    Ok(RegisteredServer {
+        id: Some(record.id),
+        alias: record.slug.clone(),
+        name: record.name.clone(),
+        tags: record.tags.clone(),
+        location: record.location.clone(),
+        default_power_cap_watts: record.default_power_cap_watts,
+        hosts_this_app: record.hosts_this_app,
+        os_health: os_health_from_record(record, cipher),
+        client: Arc::new(client),
+    })
+}
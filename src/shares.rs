@@ -0,0 +1,176 @@
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use futures_util::future::join_all;
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::database::{Share, SQLITE_TIMESTAMP_FORMAT};
+use crate::servers::{RegisteredServer, ServerRegistry};
+use crate::validation::{parse_timestamp, FieldError};
+
+/// Share links authenticate with `Authorization: Share <token>`. The link
+/// carries the token in its fragment, so it never reaches a server log.
+pub const AUTH_SCHEME: &str = "Share";
+pub const PASSCODE_HEADER: &str = "X-Share-Passcode";
+/// The only endpoint that accepts a share credential; see
+/// `middleware::ShareGuard`.
+pub const STATUS_PATH: &str = "/api/share/status";
+/// Page the link opens.
+pub const PAGE_PATH: &str = "/share";
+
+const MAX_DAYS: i64 = 90;
+/// Status older than this is read again from the iDRAC, so viewers cannot
+/// drive more traffic to it than one request per server per interval.
+const STATUS_TTL: Duration = Duration::from_secs(30);
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+/// Requests per share per minute, wrong passcodes included.
+const REQUESTS_PER_MINUTE: u32 = 30;
+
+/// Who is calling with a share credential. It names no user and holds
+/// nothing but the servers the share covers, so it cannot stand in where
+/// a user id is expected.
+pub struct ShareViewer {
+    name: String,
+    servers: Vec<Arc<RegisteredServer>>,
+}
+
+impl ShareViewer {
+    /// Resolve the share's scope against the servers registered now; a
+    /// removed server drops out and a newly tagged one joins.
+    pub fn new(share: &Share, registry: &ServerRegistry) -> Self {
+        let servers = match &share.tag {
+            Some(tag) => registry.all().into_iter().filter(|server| server.tags.contains(tag)).collect(),
+            None => share.servers.iter().filter_map(|alias| registry.get(alias)).collect(),
+        };
+        ShareViewer {
+            name: share.name.clone(),
+            servers,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn servers(&self) -> &[Arc<RegisteredServer>] {
+        &self.servers
+    }
+}
+
+/// What a share shows of one server.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerSnapshot {
+    pub alias: String,
+    pub reachable: bool,
+    pub power_state: Option<String>,
+    /// `OK`, `Warning`, `Critical` or `Unknown`.
+    pub health: String,
+    pub watts: Option<u32>,
+    pub checked_at: String,
+}
+
+/// Rate limits and cached server status shared by every share link.
+pub struct ShareAccess {
+    limiter: DefaultKeyedRateLimiter<i64>,
+    snapshots: Mutex<HashMap<String, (Instant, ServerSnapshot)>>,
+}
+
+impl Default for ShareAccess {
+    fn default() -> Self {
+        let per_minute = NonZeroU32::new(REQUESTS_PER_MINUTE).unwrap();
+        ShareAccess {
+            limiter: RateLimiter::keyed(Quota::per_minute(per_minute)),
+            snapshots: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ShareAccess {
+    /// Whether another request through `share_id` is allowed now.
+    pub fn allow(&self, share_id: i64) -> bool {
+        self.limiter.check_key(&share_id).is_ok()
+    }
+
+    /// Status of `servers`, read from the iDRACs only where the cached one
+    /// is older than `STATUS_TTL`.
+    pub async fn snapshots(&self, servers: &[Arc<RegisteredServer>]) -> Vec<ServerSnapshot> {
+        let reads = servers.iter().map(|server| async move {
+            let cached = self
+                .snapshots
+                .lock()
+                .unwrap()
+                .get(&server.alias)
+                .filter(|(read_at, _)| read_at.elapsed() < STATUS_TTL)
+                .map(|(_, snapshot)| snapshot.clone());
+            if let Some(snapshot) = cached {
+                return snapshot;
+            }
+            let snapshot = read_snapshot(server).await;
+            self.snapshots
+                .lock()
+                .unwrap()
+                .insert(server.alias.clone(), (Instant::now(), snapshot.clone()));
+            snapshot
+        });
+        join_all(reads).await
+    }
+}
+
+async fn read_snapshot(server: &RegisteredServer) -> ServerSnapshot {
+    let client = &server.client;
+    let (power_state, health, watts) = tokio::join!(
+        tokio::time::timeout(STATUS_TIMEOUT, client.get_power_state()),
+        tokio::time::timeout(STATUS_TIMEOUT, client.get_health()),
+        tokio::time::timeout(STATUS_TIMEOUT, client.get_power_consumed_watts()),
+    );
+    let power_state = power_state.ok().and_then(Result::ok);
+    ServerSnapshot {
+        alias: server.alias.clone(),
+        reachable: power_state.is_some(),
+        power_state,
+        health: match health.ok().and_then(Result::ok).map(|health| health.health) {
+            Some(health) if ["OK", "Warning", "Critical"].contains(&health.as_str()) => health,
+            _ => "Unknown".to_string(),
+        },
+        watts: watts.ok().and_then(Result::ok),
+        checked_at: Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+    }
+}
+
+/// The token of an `Authorization: Share <token>` header value.
+pub fn credential(authorization: &str) -> Option<&str> {
+    authorization
+        .strip_prefix(AUTH_SCHEME)
+        .and_then(|rest| rest.strip_prefix(' '))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// `expires_at` of a new share: in the future and at most `MAX_DAYS` away.
+pub fn parse_expiry(input: &str) -> Result<String, FieldError> {
+    let expires_at = parse_timestamp("expires_at", input)?;
+    let parsed = NaiveDateTime::parse_from_str(&expires_at, SQLITE_TIMESTAMP_FORMAT).map_err(|e| FieldError {
+        field: "expires_at",
+        message: e.to_string(),
+    })?;
+    let now = Utc::now().naive_utc();
+    if parsed <= now || parsed > now + ChronoDuration::days(MAX_DAYS) {
+        return Err(FieldError {
+            field: "expires_at",
+            message: format!("must be in the future and at most {} days away", MAX_DAYS),
+        });
+    }
+    Ok(expires_at)
+}
+
+/// Whether `passcode` opens `share`; shares without one need none.
+pub fn passcode_matches(share: &Share, passcode: Option<&str>) -> bool {
+    match (&share.passcode_hash, passcode) {
+        (None, _) => true,
+        (Some(hash), Some(passcode)) => bcrypt::verify(passcode, hash).unwrap_or(false),
+        (Some(_), None) => false,
+    }
+}
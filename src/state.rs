@@ -0,0 +1,332 @@
+use log::error;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::broadcast;
+
+use crate::api::OsHealth;
+use crate::audit::AuditAction;
+use crate::break_glass::BREAK_GLASS_USERNAME;
+use crate::config::Config;
+use crate::database::Database;
+use crate::group_power::BudgetExceeded;
+use crate::group_power_on::ServerTimeline;
+use crate::idrac::{IdracClient, PowerChangeReason};
+use crate::oidc::OidcClient;
+use crate::password_reset::ResetLimits;
+use crate::ping::ConnectivityAlert;
+use crate::power_anomaly::PowerAnomalies;
+use crate::power_burst::{self, PowerBursts, PowerProgress};
+use crate::psu::PsuIssue;
+use crate::retention::RetentionPolicy;
+use crate::scrub;
+use crate::servers::{ServerRegistry, DEFAULT_SERVER_ALIAS};
+use crate::shares::ShareAccess;
+use crate::sweep::ServerSweep;
+use crate::usage::UsageTracker;
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Events published to anything listening on the application event bus.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AppEvent {
+    PowerAction {
+        action: String,
+        success: bool,
+        message: String,
+    },
+    OperationNeedsAttention {
+        operation_id: String,
+        message: String,
+    },
+    RedfishEvent {
+        event_type: String,
+        message_id: String,
+        message: String,
+        severity: String,
+        timestamp: Option<String>,
+        origin: Option<String>,
+    },
+    CertExpiryWarning {
+        subject: String,
+        valid_not_after: String,
+        days_remaining: i64,
+    },
+    PowerStateObserved {
+        server: String,
+        power_state: String,
+        expected: String,
+        progress: PowerProgress,
+    },
+    PowerBudgetExceeded {
+        group_id: i64,
+        group: String,
+        total_watts: u64,
+        budget_watts: u32,
+        severity: String,
+    },
+    NightlySweep {
+        operation_id: Option<String>,
+        refreshed: usize,
+        /// Servers that could not be reached.
+        skipped: Vec<String>,
+        servers: Vec<ServerSweep>,
+    },
+    GroupPowerOn {
+        operation_id: String,
+        group: String,
+        status: String,
+        servers: Vec<ServerTimeline>,
+    },
+    FirmwareRollout {
+        operation_id: String,
+        group: String,
+        /// `completed`, `needs_attention`, `paused` or `cancelled`.
+        status: String,
+        servers: Vec<ServerTimeline>,
+    },
+    PowerAnomaly {
+        server: String,
+        watts: u32,
+        median_watts: f64,
+        mad_watts: f64,
+        severity: String,
+    },
+    ConnectivityAlert {
+        server: String,
+        packet_loss_pct: f64,
+        latency_ms: Option<f64>,
+        severity: String,
+    },
+    /// A server's OS stopped answering its health probe.
+    OsUnreachable {
+        server: String,
+        error: String,
+    },
+    PsuAlert {
+        server: String,
+        psu_name: String,
+        /// `failed`, `input_lost` or `degraded`.
+        issue: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        voltage: Option<f64>,
+        severity: String,
+    },
+}
+
+/// Last measured difference between a server's iDRAC clock and ours.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockOffset {
+    /// iDRAC time minus app host time; positive means the iDRAC is ahead.
+    pub offset_secs: i64,
+    pub idrac_time: String,
+    pub measured_at: String,
+    pub threshold_secs: i64,
+    pub exceeds_threshold: bool,
+    /// Request that points the iDRAC at `NTP_SERVERS`, offered while the
+    /// offset exceeds the threshold and NTP servers are configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<ClockRemediation>,
+}
+
+/// A request the dashboard can send as is to fix a drifting clock.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockRemediation {
+    pub method: &'static str,
+    pub href: String,
+    pub body: serde_json::Value,
+}
+
+/// Process-wide counters.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub power_actions: AtomicU64,
+    pub power_action_failures: AtomicU64,
+    /// Requests cut off by their route's time budget, by `METHOD /pattern`.
+    pub route_timeouts: Mutex<BTreeMap<String, u64>>,
+}
+
+/// Shared state registered once as `web::Data<AppState>` and handed to every
+/// handler and background task.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Arc<Database>,
+    pub idrac: Arc<IdracClient>,
+    pub servers: Arc<ServerRegistry>,
+    pub config: Arc<Config>,
+    pub events: broadcast::Sender<AppEvent>,
+    pub metrics: Arc<Metrics>,
+    /// Identifies this process as the holder of the primary lease.
+    pub instance_id: String,
+    /// Latest clock offset of each server, by alias.
+    pub clocks: Arc<RwLock<HashMap<String, ClockOffset>>>,
+    pub power_bursts: Arc<PowerBursts>,
+    /// Groups currently drawing more than their power budget, by group id.
+    pub power_budget_exceeded: Arc<RwLock<HashMap<i64, BudgetExceeded>>>,
+    /// Power supplies with a problem, by server alias and supply name.
+    pub psu_issues: Arc<RwLock<HashMap<(String, String), PsuIssue>>>,
+    pub power_anomalies: Arc<PowerAnomalies>,
+    /// Servers losing too many pings, by server alias.
+    pub connectivity_alerts: Arc<RwLock<HashMap<String, ConnectivityAlert>>>,
+    /// Latest OS health probe of each server with an endpoint, by alias.
+    pub os_health: Arc<RwLock<HashMap<String, OsHealth>>>,
+    /// Changed at runtime through `PUT /api/admin/retention-policy`.
+    pub retention: Arc<RwLock<RetentionPolicy>>,
+    pub usage: Arc<UsageTracker>,
+    pub share_access: Arc<ShareAccess>,
+    pub password_reset_limits: Arc<ResetLimits>,
+    /// Single sign-on, when `config.oidc` is set.
+    pub oidc: Option<Arc<OidcClient>>,
+}
+
+impl AppState {
+    pub fn new(
+        db: Arc<Database>,
+        idrac: Arc<IdracClient>,
+        servers: Arc<ServerRegistry>,
+        config: Config,
+    ) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let retention = RetentionPolicy::load(&db, &config);
+        let oidc = config.oidc.clone().map(|oidc| Arc::new(OidcClient::new(oidc)));
+
+        AppState {
+            db,
+            idrac,
+            servers,
+            config: Arc::new(config),
+            events,
+            metrics: Arc::new(Metrics::default()),
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            clocks: Arc::new(RwLock::new(HashMap::new())),
+            power_bursts: Arc::new(PowerBursts::default()),
+            power_budget_exceeded: Arc::new(RwLock::new(HashMap::new())),
+            psu_issues: Arc::new(RwLock::new(HashMap::new())),
+            power_anomalies: Arc::new(PowerAnomalies::default()),
+            connectivity_alerts: Arc::new(RwLock::new(HashMap::new())),
+            os_health: Arc::new(RwLock::new(HashMap::new())),
+            retention: Arc::new(RwLock::new(retention)),
+            usage: Arc::new(UsageTracker::default()),
+            share_access: Arc::new(ShareAccess::default()),
+            password_reset_limits: Arc::new(ResetLimits::default()),
+            oidc,
+        }
+    }
+
+    /// Publish an event. Having no subscribers is not an error.
+    pub fn publish(&self, event: AppEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Count a power action and announce its outcome on the event bus. A
+    /// successful action also starts a short-poll burst so listeners see
+    /// the new power state without waiting for their next refresh.
+    pub fn record_power_action(&self, action: &str, result: &Result<String, String>) {
+        self.record_server_power_action(DEFAULT_SERVER_ALIAS, &self.idrac, action, result);
+    }
+
+    /// `record_power_action` for an action taken on any registered server.
+    pub fn record_server_power_action(
+        &self,
+        alias: &str,
+        client: &Arc<IdracClient>,
+        action: &str,
+        result: &Result<String, String>,
+    ) {
+        self.metrics.power_actions.fetch_add(1, Ordering::Relaxed);
+        if result.is_err() {
+            self.metrics.power_action_failures.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if let (Ok(_), Some(expected)) = (result, power_burst::expected_state(action)) {
+            power_burst::start(self, alias, client.clone(), expected);
+        }
+
+        let (success, message) = match result {
+            Ok(msg) => (true, msg.clone()),
+            Err(e) => (false, e.clone()),
+        };
+        self.publish(AppEvent::PowerAction {
+            action: action.to_string(),
+            success,
+            message,
+        });
+    }
+
+    /// Write an audit entry for an action against the iDRAC. Failures to
+    /// persist are logged rather than failing the request.
+    pub fn audit(&self, user_id: Option<i64>, action: AuditAction, result: &Result<String, String>) {
+        self.write_audit(user_id, action, self.idrac.base_url(), result, None);
+    }
+
+    /// Audit an action against a server other than the `IDRAC_HOST` one.
+    pub fn audit_server(&self, user_id: Option<i64>, action: AuditAction, server_name: &str, result: &Result<String, String>) {
+        self.write_audit(user_id, action, server_name, result, None);
+    }
+
+    /// Like `audit`, with a structured summary stored alongside the entry.
+    pub fn audit_with_details(
+        &self,
+        user_id: Option<i64>,
+        action: AuditAction,
+        server_name: &str,
+        result: &Result<String, String>,
+        details: &serde_json::Value,
+    ) {
+        let mut details = details.clone();
+        scrub::scrub_json(&mut details);
+        self.write_audit(user_id, action, server_name, result, Some(&details.to_string()));
+    }
+
+    /// Add a settled (`On` or `Off`) power state and the reason for the
+    /// last change to the power history, if either changed. Failures to
+    /// persist are logged.
+    pub fn record_power_event(&self, alias: &str, power_state: &str, reason: Option<&PowerChangeReason>) {
+        if !matches!(power_state, "On" | "Off") || self.db.is_read_only() {
+            return;
+        }
+        if let Err(e) = self.db.record_power_event(alias, power_state, reason) {
+            error!("Failed to record power event for '{}': {}", alias, e);
+        }
+    }
+
+    /// Whether `user_id` is the account break-glass sessions run as.
+    pub fn is_break_glass_user(&self, user_id: i64) -> bool {
+        matches!(self.db.get_user_by_id(user_id), Ok(Some(user)) if user.username == BREAK_GLASS_USERNAME)
+    }
+
+    fn write_audit(
+        &self,
+        user_id: Option<i64>,
+        action: AuditAction,
+        server_name: &str,
+        result: &Result<String, String>,
+        details: Option<&str>,
+    ) {
+        let (outcome, error_message) = match result {
+            Ok(_) => ("success", None),
+            Err(e) => ("failure", Some(scrub::scrub_text(e))),
+        };
+
+        // Everything done under break-glass access is flagged.
+        let flagged;
+        let details = match user_id {
+            Some(id) if self.is_break_glass_user(id) => {
+                let mut flagged_details = details
+                    .and_then(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+                    .filter(|d| d.is_object())
+                    .unwrap_or_else(|| serde_json::json!({}));
+                flagged_details["break_glass"] = true.into();
+                flagged = flagged_details.to_string();
+                Some(flagged.as_str())
+            }
+            _ => details,
+        };
+
+        if let Err(e) = self.db.record_audit(user_id, action.as_str(), server_name, outcome, error_message.as_deref(), details) {
+            error!("Failed to write audit entry for {}: {}", action, e);
+        }
+    }
+}
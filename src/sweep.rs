@@ -0,0 +1,156 @@
+use futures_util::stream::{self, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::correlation;
+use crate::database::{SelRecord, EVENT_SOURCE_SEL};
+use crate::operations::record_stage;
+use crate::servers::RegisteredServer;
+use crate::state::{AppEvent, AppState};
+
+/// Servers are swept at most this many at a time.
+const SWEEP_CONCURRENCY: usize = 4;
+/// Time allowed for each of a server's requests.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What the nightly sweep did for one server.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerSweep {
+    pub server: String,
+    /// `refreshed`, `partial` when some steps failed, or `skipped` when the
+    /// server could not be reached.
+    pub status: &'static str,
+    pub health: Option<String>,
+    pub firmware_components: Option<usize>,
+    pub new_sel_entries: Option<usize>,
+    pub errors: Vec<String>,
+}
+
+async fn with_timeout<T>(request: impl std::future::Future<Output = Result<T, String>>) -> Result<T, String> {
+    tokio::time::timeout(REQUEST_TIMEOUT, request)
+        .await
+        .unwrap_or_else(|_| Err(format!("Timed out after {}s", REQUEST_TIMEOUT.as_secs())))
+}
+
+/// Read the health rollup, which doubles as the reachability check, then
+/// refresh the cached firmware inventory and store new SEL entries.
+async fn sweep_server(state: &AppState, server: &RegisteredServer) -> ServerSweep {
+    let mut sweep = ServerSweep {
+        server: server.alias.clone(),
+        status: "refreshed",
+        health: None,
+        firmware_components: None,
+        new_sel_entries: None,
+        errors: Vec::new(),
+    };
+
+    match with_timeout(server.client.get_health()).await {
+        Ok(health) => sweep.health = Some(health.health),
+        Err(e) => {
+            sweep.status = "skipped";
+            sweep.errors.push(e);
+            return sweep;
+        }
+    }
+
+    let inventory = with_timeout(server.client.get_firmware_inventory()).await.and_then(|components| {
+        let pairs: Vec<(String, String)> = components.into_iter().map(|c| (c.name, c.version)).collect();
+        state
+            .db
+            .replace_firmware_inventory(&server.alias, &pairs)
+            .map(|_| pairs.len())
+            .map_err(|e| format!("Failed to cache inventory: {}", e))
+    });
+    match inventory {
+        Ok(count) => sweep.firmware_components = Some(count),
+        Err(e) => sweep.errors.push(format!("Firmware inventory: {}", e)),
+    }
+
+    let sel = with_timeout(server.client.get_sel_entries()).await.and_then(|entries| {
+        let records: Vec<SelRecord> = entries
+            .into_iter()
+            .map(|entry| SelRecord {
+                server_alias: server.alias.clone(),
+                entry_id: entry.id,
+                created: entry.created,
+                severity: entry.severity,
+                message: entry.message,
+                message_id: entry.message_id,
+                source: EVENT_SOURCE_SEL.to_string(),
+                caused_by: None,
+            })
+            .collect();
+        state
+            .db
+            .record_sel_entries(&records)
+            .map_err(|e| format!("Failed to store SEL entries: {}", e))
+    });
+    match sel {
+        Ok(added) => {
+            sweep.new_sel_entries = Some(added);
+            // Also retries the past day's pushed events that arrived
+            // before their action was audited.
+            correlation::correlate(state, &server.alias, chrono::Utc::now() - chrono::Duration::days(1));
+        }
+        Err(e) => sweep.errors.push(format!("SEL: {}", e)),
+    }
+
+    if !sweep.errors.is_empty() {
+        sweep.status = "partial";
+    }
+    sweep
+}
+
+/// Sweep every registered server as a tracked `nightly_sweep` operation
+/// with one stage per server, then publish a single `nightly_sweep` event
+/// with every server's outcome.
+pub async fn run(state: &AppState) {
+    let operation_id = match state.db.create_operation("nightly_sweep", None) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("Failed to record the nightly sweep: {}", e);
+            None
+        }
+    };
+
+    let mut servers: Vec<ServerSweep> = stream::iter(state.servers.pollable())
+        .map(|server| async move { sweep_server(state, &server).await })
+        .buffer_unordered(SWEEP_CONCURRENCY)
+        .collect()
+        .await;
+    servers.sort_by(|a, b| a.server.cmp(&b.server));
+
+    let refreshed = servers.iter().filter(|sweep| sweep.status == "refreshed").count();
+    let skipped: Vec<String> = servers
+        .iter()
+        .filter(|sweep| sweep.status == "skipped")
+        .map(|sweep| sweep.server.clone())
+        .collect();
+    let summary = format!(
+        "{} of {} server(s) refreshed, {} skipped",
+        refreshed,
+        servers.len(),
+        skipped.len()
+    );
+    info!("Nightly sweep: {}", summary);
+
+    if let Some(operation_id) = &operation_id {
+        for sweep in &servers {
+            let detail = match sweep.errors.is_empty() {
+                true => sweep.server.clone(),
+                false => format!("{}: {}", sweep.server, sweep.errors.join("; ")),
+            };
+            record_stage(state, operation_id, sweep.status, Some(&detail), "running");
+        }
+        let status = if refreshed == servers.len() { "completed" } else { "needs_attention" };
+        record_stage(state, operation_id, "finished", Some(&summary), status);
+    }
+
+    state.publish(AppEvent::NightlySweep {
+        operation_id,
+        refreshed,
+        skipped,
+        servers,
+    });
+}
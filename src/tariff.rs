@@ -0,0 +1,249 @@
+//! Power caps tied to electricity tariff windows: weekday and time ranges
+//! during which a server, or every member of a group, is capped. A cap is
+//! only changed at a window's boundaries, so a cap set by hand during a
+//! window stays until the window ends. The cap in force before a window is
+//! kept in the database and restored when it ends, also across restarts.
+
+use chrono::{DateTime, Datelike, Local, Timelike, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::audit::AuditAction;
+use crate::database::{ServerGroup, TariffCap, TariffWindow, SQLITE_TIMESTAMP_FORMAT};
+use crate::servers::RegisteredServer;
+use crate::state::AppState;
+
+/// Weekday names a window accepts, Monday first.
+pub const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const MINUTES_PER_WEEK: u32 = 7 * MINUTES_PER_DAY;
+
+/// Minutes since midnight of an `HH:MM` time.
+pub fn parse_clock(value: &str) -> Result<u32, String> {
+    let invalid = || format!("'{}' is not a time as HH:MM", value);
+    let (hours, minutes) = value.trim().split_once(':').ok_or_else(invalid)?;
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    Ok(hours * 60 + minutes)
+}
+
+/// Lowercased weekday names in week order, without duplicates.
+pub fn normalize_days(days: &[String]) -> Result<Vec<String>, String> {
+    let mut indexes = Vec::new();
+    for day in days {
+        let day = day.trim().to_lowercase();
+        let index = WEEKDAYS
+            .iter()
+            .position(|name| *name == day)
+            .ok_or_else(|| format!("unknown weekday '{}'; use {}", day, WEEKDAYS.join(", ")))?;
+        indexes.push(index);
+    }
+    if indexes.is_empty() {
+        return Err("name at least one weekday".to_string());
+    }
+    indexes.sort_unstable();
+    indexes.dedup();
+    Ok(indexes.into_iter().map(|index| WEEKDAYS[index].to_string()).collect())
+}
+
+/// Minute-of-week ranges a window covers, 0 being Monday 00:00. A window
+/// running from Sunday into Monday is split at the end of the week.
+fn ranges(window: &TariffWindow) -> Vec<(u32, u32)> {
+    let start = parse_clock(&window.start).unwrap_or(0);
+    let end = parse_clock(&window.end).unwrap_or(0);
+    let length = if end > start { end - start } else { end + MINUTES_PER_DAY - start };
+
+    let mut ranges = Vec::new();
+    for day in &window.days {
+        let Some(index) = WEEKDAYS.iter().position(|name| name == day) else {
+            continue;
+        };
+        let from = index as u32 * MINUTES_PER_DAY + start;
+        let until = from + length;
+        if until > MINUTES_PER_WEEK {
+            ranges.push((from, MINUTES_PER_WEEK));
+            ranges.push((0, until - MINUTES_PER_WEEK));
+        } else {
+            ranges.push((from, until));
+        }
+    }
+    ranges
+}
+
+/// Whether two windows share any minute of the week.
+pub fn overlaps(a: &TariffWindow, b: &TariffWindow) -> bool {
+    let b_ranges = ranges(b);
+    ranges(a)
+        .iter()
+        .any(|(a_from, a_until)| b_ranges.iter().any(|(b_from, b_until)| a_from < b_until && b_from < a_until))
+}
+
+/// Whether `window` covers `at`.
+pub fn is_active(window: &TariffWindow, at: DateTime<Local>) -> bool {
+    let minute = at.weekday().num_days_from_monday() * MINUTES_PER_DAY + at.hour() * 60 + at.minute();
+    ranges(window).iter().any(|(from, until)| (*from..*until).contains(&minute))
+}
+
+/// Aliases of the servers a window caps.
+pub fn targets(window: &TariffWindow, groups: &[ServerGroup]) -> Vec<String> {
+    match (&window.server_alias, window.group_id) {
+        (Some(alias), _) => vec![alias.clone()],
+        (None, Some(id)) => groups
+            .iter()
+            .find(|group| group.id == id)
+            .map(|group| group.members.clone())
+            .unwrap_or_default(),
+        (None, None) => Vec::new(),
+    }
+}
+
+/// The window covering each server at `at`. Should group membership have
+/// changed so that two windows cover a server, the lower cap wins.
+fn active_windows<'a>(windows: &'a [TariffWindow], groups: &[ServerGroup], at: DateTime<Local>) -> HashMap<String, &'a TariffWindow> {
+    let mut active: HashMap<String, &TariffWindow> = HashMap::new();
+    for window in windows.iter().filter(|window| is_active(window, at)) {
+        for alias in targets(window, groups) {
+            active
+                .entry(alias)
+                .and_modify(|current| {
+                    if window.watts < current.watts {
+                        *current = window;
+                    }
+                })
+                .or_insert(window);
+        }
+    }
+    active
+}
+
+/// Tariff state of one server, as `GET /api/power/tariff-status` shows it.
+#[derive(Debug, Serialize)]
+pub struct TariffStatus {
+    pub server: String,
+    /// Window covering the server now.
+    pub active_window: Option<TariffWindow>,
+    /// Cap applied at the start of the window and kept until it ends.
+    pub applied: Option<TariffCap>,
+}
+
+/// Every server with a tariff window or a tariff cap in force.
+pub fn status(state: &AppState) -> Result<Vec<TariffStatus>, String> {
+    let windows = state.db.list_tariff_windows().map_err(|e| e.to_string())?;
+    let groups = state.db.list_server_groups().map_err(|e| e.to_string())?;
+    let mut caps: HashMap<String, TariffCap> = state
+        .db
+        .list_tariff_caps()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|cap| (cap.server_alias.clone(), cap))
+        .collect();
+    let active = active_windows(&windows, &groups, Local::now());
+
+    let mut aliases: Vec<String> = windows
+        .iter()
+        .flat_map(|window| targets(window, &groups))
+        .chain(caps.keys().cloned())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    aliases.sort();
+
+    Ok(aliases
+        .into_iter()
+        .map(|alias| TariffStatus {
+            active_window: active.get(&alias).map(|window| (*window).clone()),
+            applied: caps.remove(&alias),
+            server: alias,
+        })
+        .collect())
+}
+
+/// Apply the cap of each window that opened since the last round and
+/// restore the caps of windows that ended. Servers whose window is
+/// unchanged are left alone.
+pub async fn apply_windows(state: &AppState) {
+    let (windows, groups, caps) = match (state.db.list_tariff_windows(), state.db.list_server_groups(), state.db.list_tariff_caps()) {
+        (Ok(windows), Ok(groups), Ok(caps)) => (windows, groups, caps),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            warn!("Failed to load tariff windows: {}", e);
+            return;
+        }
+    };
+    let active = active_windows(&windows, &groups, Local::now());
+    let caps: HashMap<&str, &TariffCap> = caps.iter().map(|cap| (cap.server_alias.as_str(), cap)).collect();
+
+    for server in state.servers.pollable() {
+        match (active.get(&server.alias), caps.get(server.alias.as_str())) {
+            (Some(window), Some(cap)) if cap.window_id == window.id => {}
+            (Some(window), cap) => open_window(state, &server, window, cap.map(|cap| cap.previous_watts)).await,
+            (None, Some(cap)) => close_window(state, &server, cap).await,
+            (None, None) => {}
+        }
+    }
+}
+
+/// Cap `server` for `window`. `previous_watts` is set when the server goes
+/// straight from another window, whose starting cap is still the one to
+/// restore; otherwise the cap in force now is read first.
+async fn open_window(state: &AppState, server: &RegisteredServer, window: &TariffWindow, previous_watts: Option<Option<u32>>) {
+    let previous_watts = match previous_watts {
+        Some(previous_watts) => previous_watts,
+        None => match server.client.get_power_cap().await {
+            Ok(watts) => watts,
+            Err(e) => {
+                warn!("Tariff window {} not applied to {}: cannot read its power cap: {}", window.id, server.alias, e);
+                return;
+            }
+        },
+    };
+
+    let result = server.client.set_power_cap(Some(window.watts)).await;
+    let details = serde_json::json!({
+        "initiated_by": "automation",
+        "window_id": window.id,
+        "watts": window.watts,
+        "previous_watts": previous_watts,
+    });
+    state.audit_with_details(None, AuditAction::TariffCapApply, server.client.base_url(), &result, &details);
+    if let Err(e) = result {
+        warn!("Failed to apply tariff window {} to {}: {}", window.id, server.alias, e);
+        return;
+    }
+
+    info!("Tariff window {} capped {} at {} W", window.id, server.alias, window.watts);
+    let cap = TariffCap {
+        server_alias: server.alias.clone(),
+        window_id: window.id,
+        watts: window.watts,
+        previous_watts,
+        applied_at: Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+    };
+    if let Err(e) = state.db.set_tariff_cap(&cap) {
+        warn!("Failed to record the tariff cap of {}: {}", server.alias, e);
+    }
+}
+
+/// Restore the cap `server` had before its window opened.
+async fn close_window(state: &AppState, server: &RegisteredServer, cap: &TariffCap) {
+    let result = server.client.set_power_cap(cap.previous_watts).await;
+    let details = serde_json::json!({
+        "initiated_by": "automation",
+        "window_id": cap.window_id,
+        "watts": cap.previous_watts,
+    });
+    state.audit_with_details(None, AuditAction::TariffCapRestore, server.client.base_url(), &result, &details);
+    if let Err(e) = result {
+        warn!("Failed to restore the power cap of {} after tariff window {}: {}", server.alias, cap.window_id, e);
+        return;
+    }
+
+    info!("Tariff window {} ended on {}", cap.window_id, server.alias);
+    if let Err(e) = state.db.delete_tariff_cap(&server.alias) {
+        warn!("Failed to clear the tariff cap of {}: {}", server.alias, e);
+    }
+}
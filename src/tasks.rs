@@ -0,0 +1,753 @@
+use chrono::{Local, Utc};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::audit::AuditAction;
+use crate::database::{OneShotSchedule, SQLITE_TIMESTAMP_FORMAT};
+use crate::firmware;
+use crate::firmware_schedule;
+use crate::group_power;
+use crate::health_report;
+use crate::os_health;
+use crate::power_anomaly;
+use crate::ping;
+use crate::psu;
+use crate::rollups;
+use crate::schedule::{is_window_active, one_shot_audit_name, parse_cron};
+use crate::servers::RegisteredServer;
+use crate::state::{AppEvent, AppState, ClockOffset, ClockRemediation};
+use crate::sweep;
+use crate::tariff;
+use crate::vault::{VaultClient, VaultLease};
+
+const CERT_CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Periodically check the iDRAC HTTPS certificate and publish a
+/// `cert_expiry_warning` event when it is close to expiring.
+pub fn spawn_cert_expiry_check(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CERT_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let cert = match state.idrac.get_ssl_certificate_info().await {
+                Ok(cert) => cert,
+                Err(e) => {
+                    warn!("Certificate expiry check failed: {}", e);
+                    continue;
+                }
+            };
+
+            match cert.days_remaining() {
+                Some(days) if days <= state.config.cert_expiry_warn_days => {
+                    warn!(
+                        "iDRAC certificate {} expires in {} day(s) ({})",
+                        cert.subject, days, cert.valid_not_after
+                    );
+                    state.publish(AppEvent::CertExpiryWarning {
+                        subject: cert.subject,
+                        valid_not_after: cert.valid_not_after,
+                        days_remaining: days,
+                    });
+                }
+                Some(days) => info!("iDRAC certificate valid for another {} day(s)", days),
+                None => warn!("Could not parse iDRAC certificate expiry '{}'", cert.valid_not_after),
+            }
+        }
+    });
+}
+
+const LEASE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A standby refuses to promote while the primary lease is younger than this.
+pub const LEASE_TTL_SECS: i64 = 30;
+
+/// Keep the primary lease fresh while this instance holds a writable
+/// database. A standby skips the heartbeat until it is promoted, and so
+/// does an instance while its database refuses writes.
+pub fn spawn_lease_heartbeat(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(LEASE_HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            if state.db.writes_paused() {
+                continue;
+            }
+            if let Err(e) = state.db.heartbeat_lease(&state.instance_id) {
+                warn!("Failed to renew primary lease: {}", e);
+            }
+        }
+    });
+}
+
+const ACCOUNT_EXPIRY_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Once a day, disable accounts past their expiry. Login and existing
+/// sessions are already refused from the moment an account expires; this
+/// makes it permanent until an admin extends the expiry.
+pub fn spawn_account_expiry(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ACCOUNT_EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            if state.db.writes_paused() {
+                continue;
+            }
+            disable_expired_accounts(&state);
+        }
+    });
+}
+
+/// Disable every account past its expiry and audit each one.
+fn disable_expired_accounts(state: &AppState) {
+    match state.db.disable_expired_users() {
+        Ok(users) => {
+            for user in users {
+                info!("Disabled expired account: {}", user.username);
+                state.audit_with_details(
+                    None,
+                    AuditAction::AccountExpired,
+                    "app",
+                    &Ok(format!("Disabled '{}'", user.username)),
+                    &serde_json::json!({ "user": user.username, "expires_at": user.expires_at }),
+                );
+            }
+        }
+        Err(e) => warn!("Failed to disable expired accounts: {}", e),
+    }
+}
+
+const RETENTION_CLEANUP_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Delete audit entries, finished operations, power and metric samples,
+/// stored SEL entries and API usage counts older than the retention policy allows.
+/// Categories set to 0 days are kept forever. Metric rollups are brought up
+/// to date first and are never pruned.
+pub fn spawn_retention_cleanup(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if state.db.writes_paused() {
+                continue;
+            }
+
+            let policy = *state.retention.read().unwrap();
+            rollups::roll_up(&state.db, policy.history_days);
+            if policy.audit_days > 0 {
+                match state.db.purge_audit_older_than(policy.audit_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} audit entries older than {} days", removed, policy.audit_days),
+                    Err(e) => warn!("Audit retention cleanup failed: {}", e),
+                }
+            }
+            if policy.history_days > 0 {
+                match state.db.purge_operations_older_than(policy.history_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} operations older than {} days", removed, policy.history_days),
+                    Err(e) => warn!("Operation history cleanup failed: {}", e),
+                }
+                match state.db.purge_group_apply_jobs_older_than(policy.history_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} group apply jobs older than {} days", removed, policy.history_days),
+                    Err(e) => warn!("Group apply job cleanup failed: {}", e),
+                }
+                match state.db.purge_power_samples_older_than(policy.history_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} power samples older than {} days", removed, policy.history_days),
+                    Err(e) => warn!("Power sample cleanup failed: {}", e),
+                }
+                match state.db.purge_power_events_older_than(policy.history_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} power events older than {} days", removed, policy.history_days),
+                    Err(e) => warn!("Power event cleanup failed: {}", e),
+                }
+                match state.db.purge_metric_samples_older_than(policy.history_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} metric samples older than {} days", removed, policy.history_days),
+                    Err(e) => warn!("Metric sample cleanup failed: {}", e),
+                }
+                match state.db.purge_sel_entries_older_than(policy.history_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} SEL entries older than {} days", removed, policy.history_days),
+                    Err(e) => warn!("SEL entry cleanup failed: {}", e),
+                }
+                match state.db.purge_api_usage_older_than(policy.history_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} hourly API usage counts older than {} days", removed, policy.history_days),
+                    Err(e) => warn!("API usage cleanup failed: {}", e),
+                }
+                match state.db.purge_health_reports_older_than(policy.history_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} health reports older than {} days", removed, policy.history_days),
+                    Err(e) => warn!("Health report cleanup failed: {}", e),
+                }
+            }
+            if policy.connectivity_log_days > 0 {
+                match state.db.purge_ping_history_older_than(policy.connectivity_log_days) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Removed {} ping rounds older than {} days", removed, policy.connectivity_log_days),
+                    Err(e) => warn!("Ping history cleanup failed: {}", e),
+                }
+            }
+        }
+    });
+}
+
+const WRITE_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// While database writes are failing, retry a small write so the app
+/// leaves degraded mode on its own once space is freed.
+pub fn spawn_write_probe(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(WRITE_PROBE_INTERVAL);
+        loop {
+            interval.tick().await;
+            state.db.probe_writes();
+        }
+    });
+}
+
+const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Write the API usage counted since the last flush to the database.
+/// A standby keeps counting and writes once it is promoted, as does an
+/// instance whose database is refusing writes once they succeed again.
+pub fn spawn_usage_flush(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(USAGE_FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if state.db.writes_paused() {
+                continue;
+            }
+            state.usage.flush(&state.db);
+        }
+    });
+}
+
+const VAULT_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+const VAULT_MIN_RENEW_WAIT: Duration = Duration::from_secs(5);
+
+/// Renew the Vault token when two thirds of its TTL have passed, logging
+/// in again with AppRole once it can no longer be renewed.
+pub fn spawn_vault_token_renewal(vault: VaultClient, mut lease: VaultLease) {
+    if lease.ttl.is_zero() {
+        info!("Vault token does not expire");
+        return;
+    }
+    tokio::spawn(async move {
+        let mut wait = (lease.ttl * 2 / 3).max(VAULT_MIN_RENEW_WAIT);
+        while vault.can_refresh(&lease) {
+            tokio::time::sleep(wait).await;
+            match vault.refresh(&lease).await {
+                Ok(refreshed) => {
+                    info!("Renewed Vault token for {}s", refreshed.ttl.as_secs());
+                    lease = refreshed;
+                    wait = (lease.ttl * 2 / 3).max(VAULT_MIN_RENEW_WAIT);
+                }
+                Err(e) => {
+                    warn!("Failed to renew Vault token: {}", e);
+                    wait = VAULT_RETRY_INTERVAL;
+                }
+            }
+        }
+        warn!("Vault token is not renewable and will expire");
+    });
+}
+
+const FIRMWARE_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Refresh the cached firmware inventory of every server once a day so the
+/// fleet firmware report stays current without anyone asking for it.
+pub fn spawn_firmware_inventory_refresh(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FIRMWARE_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if state.db.writes_paused() {
+                continue;
+            }
+
+            let results = firmware::refresh_all(&state).await;
+            let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+            for (alias, result) in &results {
+                if let Err(e) = result {
+                    warn!("Firmware inventory refresh for '{}' failed: {}", alias, e);
+                }
+            }
+            info!("Refreshed firmware inventory of {} of {} server(s)", results.len() - failed, results.len());
+        }
+    });
+}
+
+/// Run the nightly sweep at every firing of `NIGHTLY_SWEEP_CRON`, in
+/// local time, so morning checks find a warm inventory and SEL.
+pub fn spawn_nightly_sweep(state: AppState) {
+    let Some(expr) = state.config.nightly_sweep_cron.clone() else {
+        info!("Nightly sweep disabled");
+        return;
+    };
+    let schedule = match parse_cron(&expr) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            error!("Nightly sweep disabled, NIGHTLY_SWEEP_CRON is invalid: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let mut after = Local::now();
+        while let Some(next) = schedule.after(&after).next() {
+            info!("Next nightly sweep at {}", next.to_rfc3339());
+            tokio::time::sleep((next - Local::now()).to_std().unwrap_or_default()).await;
+            if !state.db.writes_paused() {
+                sweep::run(&state).await;
+            }
+            // Firings missed while the sweep ran are skipped.
+            after = next.max(Local::now());
+        }
+    });
+}
+
+/// Write the daily health report at every firing of `HEALTH_REPORT_CRON`,
+/// in local time.
+pub fn spawn_health_report(state: AppState) {
+    let Some(expr) = state.config.health_report_cron.clone() else {
+        info!("Health reports disabled");
+        return;
+    };
+    let schedule = match parse_cron(&expr) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            error!("Health reports disabled, HEALTH_REPORT_CRON is invalid: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let mut after = Local::now();
+        while let Some(next) = schedule.after(&after).next() {
+            info!("Next health report at {}", next.to_rfc3339());
+            tokio::time::sleep((next - Local::now()).to_std().unwrap_or_default()).await;
+            if !state.db.writes_paused() {
+                health_report::run(&state).await;
+            }
+            after = next.max(Local::now());
+        }
+    });
+}
+
+/// Sample every server's power draw every `POWER_SAMPLE_INTERVAL_SECS`
+/// for group power summaries, then check group power budgets and look for
+/// unusual draw.
+pub fn spawn_power_sampling(state: AppState) {
+    let every = state.config.power_sample_interval_secs;
+    if every == 0 {
+        info!("Power sampling disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(every));
+        loop {
+            interval.tick().await;
+            if state.db.writes_paused() {
+                continue;
+            }
+
+            let samples = match group_power::sample_all(&state).await {
+                Ok(samples) => samples,
+                Err(e) => {
+                    warn!("Power sampling failed: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = group_power::check_budgets(&state) {
+                warn!("Power budget check failed: {}", e);
+            }
+            power_anomaly::check(&state, &samples);
+        }
+    });
+}
+
+/// Check every server's power supplies every `PSU_CHECK_INTERVAL_SECS`.
+pub fn spawn_psu_check(state: AppState) {
+    let every = state.config.psu_check_interval_secs;
+    if every == 0 {
+        info!("PSU checks disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(every));
+        loop {
+            interval.tick().await;
+            // The primary raises the alerts.
+            if state.db.writes_paused() {
+                continue;
+            }
+            psu::check_all(&state).await;
+        }
+    });
+}
+
+/// Ping every iDRAC every `PING_INTERVAL_SECS`.
+pub fn spawn_ping_check(state: AppState) {
+    let every = state.config.ping_interval_secs;
+    if every == 0 {
+        info!("Ping checks disabled");
+        return;
+    }
+    if let Some(reason) = ping::unavailable() {
+        warn!("Ping checks disabled: {}", reason);
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(every));
+        loop {
+            interval.tick().await;
+            // The primary keeps the connectivity log.
+            if state.db.writes_paused() {
+                continue;
+            }
+            ping::check_all(&state).await;
+        }
+    });
+}
+
+/// Probe the OS of every server with a health endpoint every
+/// `OS_HEALTH_INTERVAL_SECS`. Nothing is stored, so a standby probes too.
+pub fn spawn_os_health_check(state: AppState) {
+    let every = state.config.os_health_interval_secs;
+    if every == 0 {
+        info!("OS health probes disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(every));
+        loop {
+            interval.tick().await;
+            os_health::check_all(&state).await;
+        }
+    });
+}
+
+/// Touch every iDRAC every `IDRAC_SESSION_KEEPALIVE_SECS` so idle sessions
+/// and pooled connections do not time out. Returns `None` when disabled;
+/// abort the handle to stop it.
+pub fn spawn_idrac_keepalive(state: AppState) -> Option<JoinHandle<()>> {
+    let every = state.config.idrac_session_keepalive_secs;
+    if every == 0 {
+        info!("iDRAC keepalive disabled");
+        return None;
+    }
+
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(every));
+        // The first tick fires immediately; startup traffic already keeps things warm.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            for server in state.servers.pollable() {
+                match server.client.keepalive().await {
+                    Ok(()) => info!("iDRAC keepalive to '{}' succeeded", server.alias),
+                    Err(e) => warn!("iDRAC keepalive to '{}' failed: {}", server.alias, e),
+                }
+            }
+        }
+    }))
+}
+
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Periodically compare each server's iDRAC clock with ours and keep the
+/// latest offset in `AppState::clocks`.
+pub fn spawn_clock_check(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLOCK_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            for server in state.servers.pollable() {
+                match measure_clock_offset(&state, &server).await {
+                    Ok(offset) => {
+                        if offset.exceeds_threshold {
+                            warn!(
+                                "iDRAC clock of '{}' is {}s {} the app host (threshold {}s)",
+                                server.alias,
+                                offset.offset_secs.abs(),
+                                if offset.offset_secs > 0 { "ahead of" } else { "behind" },
+                                offset.threshold_secs
+                            );
+                        }
+                        state.clocks.write().unwrap().insert(server.alias.clone(), offset);
+                    }
+                    Err(e) => warn!("Clock offset check of '{}' failed: {}", server.alias, e),
+                }
+            }
+        }
+    });
+}
+
+/// Read `server`'s iDRAC clock and compare it with ours.
+pub async fn measure_clock_offset(state: &AppState, server: &RegisteredServer) -> Result<ClockOffset, String> {
+    let sent = Utc::now();
+    let idrac_time = server.client.get_system_time().await?;
+    let received = Utc::now();
+
+    // Compare against the midpoint of the round trip.
+    let local = sent + (received - sent) / 2;
+    let offset_secs = ((idrac_time - local).num_milliseconds() as f64 / 1000.0).round() as i64;
+    let threshold_secs = state.config.clock_drift_threshold_secs;
+    let exceeds_threshold = offset_secs.abs() > threshold_secs;
+
+    Ok(ClockOffset {
+        offset_secs,
+        idrac_time: idrac_time.to_rfc3339(),
+        measured_at: received.to_rfc3339(),
+        threshold_secs,
+        exceeds_threshold,
+        remediation: exceeds_threshold.then(|| ntp_remediation(&server.alias, &state.config.ntp_servers)).flatten(),
+    })
+}
+
+/// `PUT /api/servers/{alias}/ntp` pre-filled with `ntp_servers`, if any.
+fn ntp_remediation(alias: &str, ntp_servers: &[String]) -> Option<ClockRemediation> {
+    if ntp_servers.is_empty() {
+        return None;
+    }
+    Some(ClockRemediation {
+        method: "PUT",
+        href: format!("/api/servers/{}/ntp", alias),
+        body: serde_json::json!({ "servers": ntp_servers, "enabled": true }),
+    })
+}
+
+const POWER_CAP_TICK: Duration = Duration::from_secs(60);
+
+/// Apply power cap schedules. While any enabled schedule for a server is
+/// inside its window the lowest scheduled cap wins; otherwise the server's
+/// default cap is restored, or the cap removed if it has none. Servers that
+/// have never had a schedule are left alone.
+pub fn spawn_power_cap_scheduler(state: AppState) {
+    tokio::spawn(async move {
+        let mut applied: HashMap<String, Option<u32>> = HashMap::new();
+        let mut interval = tokio::time::interval(POWER_CAP_TICK);
+        loop {
+            interval.tick().await;
+
+            if state.db.writes_paused() {
+                continue;
+            }
+
+            let schedules = match state.db.list_power_cap_schedules(None) {
+                Ok(schedules) => schedules,
+                Err(e) => {
+                    warn!("Failed to load power cap schedules: {}", e);
+                    continue;
+                }
+            };
+            let now = Utc::now();
+
+            for server in state.servers.pollable() {
+                let own: Vec<_> = schedules.iter().filter(|s| s.server_alias == server.alias).collect();
+                if own.is_empty() && !applied.contains_key(&server.alias) {
+                    continue;
+                }
+
+                let desired = own
+                    .iter()
+                    .filter(|s| s.enabled)
+                    .filter(|s| match parse_cron(&s.cron_expr) {
+                        Ok(cron) => is_window_active(&cron, chrono::Duration::minutes(s.duration_minutes.into()), now),
+                        Err(e) => {
+                            warn!("Power cap schedule {}: {}", s.id, e);
+                            false
+                        }
+                    })
+                    .map(|s| s.watts)
+                    .min()
+                    .or(server.default_power_cap_watts);
+
+                if applied.get(&server.alias) == Some(&desired) {
+                    continue;
+                }
+
+                let result = server.client.set_power_cap(desired).await;
+                state.audit_server(None, AuditAction::PowerCapSchedule, server.client.base_url(), &result);
+                match result {
+                    Ok(_) => {
+                        applied.insert(server.alias.clone(), desired);
+                    }
+                    Err(e) => warn!("Failed to apply power cap to {}: {}", server.alias, e),
+                }
+            }
+        }
+    });
+}
+
+/// Apply and lift tariff window power caps at window boundaries.
+pub fn spawn_tariff_scheduler(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POWER_CAP_TICK);
+        loop {
+            interval.tick().await;
+
+            if state.db.writes_paused() {
+                continue;
+            }
+            tariff::apply_windows(&state).await;
+        }
+    });
+}
+
+const ONE_SHOT_TICK: Duration = Duration::from_secs(15);
+/// A one-shot action found more than this late, e.g. because the app was
+/// down at its time, is skipped rather than run at an unplanned moment.
+const ONE_SHOT_MAX_LATENESS_SECS: i64 = 15 * 60;
+
+/// Run one-shot power schedules once their time has come.
+pub fn spawn_one_shot_scheduler(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ONE_SHOT_TICK);
+        loop {
+            interval.tick().await;
+
+            if state.db.writes_paused() {
+                continue;
+            }
+
+            let due = match state.db.claim_due_one_shot_schedules() {
+                Ok(due) => due,
+                Err(e) => {
+                    warn!("Failed to load one-shot schedules: {}", e);
+                    continue;
+                }
+            };
+
+            for schedule in due {
+                let (outcome, message) = run_one_shot(&state, &schedule).await;
+                info!(
+                    "One-shot schedule {} ({} on {}): {}",
+                    schedule.id, schedule.action, schedule.server_alias, message
+                );
+                if let Err(e) = state.db.finish_one_shot_schedule(schedule.id, outcome, &message) {
+                    warn!("Failed to record outcome of one-shot schedule {}: {}", schedule.id, e);
+                }
+            }
+        }
+    });
+}
+
+/// Start the reboot of each firmware update schedule once its time has
+/// come. Each is followed in its own task, as the job can take an hour.
+pub fn spawn_firmware_update_scheduler(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ONE_SHOT_TICK);
+        loop {
+            interval.tick().await;
+
+            if state.db.writes_paused() {
+                continue;
+            }
+
+            match state.db.claim_due_firmware_update_schedules() {
+                Ok(due) => {
+                    for schedule in due {
+                        tokio::spawn(firmware_schedule::run(state.clone(), schedule));
+                    }
+                }
+                Err(e) => warn!("Failed to load firmware update schedules: {}", e),
+            }
+        }
+    });
+}
+
+async fn run_one_shot(state: &AppState, schedule: &OneShotSchedule) -> (&'static str, String) {
+    let lateness = chrono::NaiveDateTime::parse_from_str(&schedule.execute_at, SQLITE_TIMESTAMP_FORMAT)
+        .map(|execute_at| (Utc::now().naive_utc() - execute_at).num_seconds())
+        .unwrap_or(0);
+    if lateness > ONE_SHOT_MAX_LATENESS_SECS {
+        return ("skipped", format!("Not run: found {} minute(s) after its scheduled time", lateness / 60));
+    }
+
+    let Some(server) = state.servers.get(&schedule.server_alias) else {
+        return ("failure", format!("No server with alias '{}'", schedule.server_alias));
+    };
+    let Some(audit_name) = one_shot_audit_name(&schedule.action) else {
+        return ("failure", format!("Unsupported action '{}'", schedule.action));
+    };
+
+    let result = match schedule.action.as_str() {
+        "on" => server.client.power_on().await,
+        "off" => server.client.power_off().await,
+        _ => server.client.graceful_shutdown().await,
+    };
+    state.record_server_power_action(&server.alias, &server.client, audit_name.as_str(), &result);
+    state.audit_with_details(
+        schedule.created_by,
+        audit_name,
+        server.client.base_url(),
+        &result,
+        &serde_json::json!({ "one_shot_schedule_id": schedule.id }),
+    );
+
+    match result {
+        Ok(message) => ("success", message),
+        Err(e) => ("failure", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Keyset, UserRole};
+    use crate::testing::{app_state_over, databases, unique, ScratchDir};
+
+    #[test]
+    fn expired_accounts_are_disabled_and_audited() {
+        for db in databases() {
+            let dir = ScratchDir::new();
+            let state = app_state_over(db.db.clone(), &dir);
+            let username = unique("vendor");
+            let expired_at = (Utc::now() - chrono::Duration::hours(1)).format(SQLITE_TIMESTAMP_FORMAT).to_string();
+            let id = db.create_user(&username, "password123", Some(&expired_at), UserRole::ReadOnly).unwrap();
+
+            let disabled = db.disable_expired_users().unwrap();
+            let user = disabled.iter().find(|user| user.username == username).expect("expired user returned");
+            assert_eq!(user.id, id, "{}", db.backend);
+            assert_eq!(user.expires_at.as_deref(), Some(expired_at.as_str()), "{}", db.backend);
+            assert!(user.disabled_at.is_some(), "{}", db.backend);
+            assert_eq!(user.auth_source, "local", "{}", db.backend);
+            assert!(db.disable_expired_users().unwrap().iter().all(|user| user.username != username), "{}", db.backend);
+
+            // A second user expires through the task, which audits it.
+            let task_username = unique("vendor");
+            db.create_user(&task_username, "password123", Some(&expired_at), UserRole::ReadOnly).unwrap();
+            disable_expired_accounts(&state);
+            let audit = db.list_audit_page(&Keyset::First, 100, true).unwrap();
+            let entry = audit
+                .iter()
+                .find(|entry| entry.action == "AccountExpired" && entry.details.as_ref().unwrap()["user"] == task_username.as_str())
+                .unwrap_or_else(|| panic!("{}: no AccountExpired audit entry", db.backend));
+            assert_eq!(entry.result, "success", "{}", db.backend);
+            assert_eq!(entry.details.as_ref().unwrap()["expires_at"], expired_at.as_str(), "{}", db.backend);
+        }
+    }
+
+    #[test]
+    fn ntp_remediation_targets_the_server() {
+        assert!(ntp_remediation("rack-2", &[]).is_none());
+
+        let remediation = ntp_remediation("rack-2", &["ntp1.example.com".to_string()]).unwrap();
+        assert_eq!(remediation.method, "PUT");
+        assert_eq!(remediation.href, "/api/servers/rack-2/ntp");
+        assert_eq!(remediation.body, serde_json::json!({ "servers": ["ntp1.example.com"], "enabled": true }));
+    }
+}
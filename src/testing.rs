@@ -0,0 +1,102 @@
+//! Helpers shared by the unit tests.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::crypto::CredentialCipher;
+use crate::database::Database;
+use crate::idrac::IdracClient;
+use crate::secret::SecretString;
+use crate::servers::ServerRegistry;
+use crate::state::AppState;
+
+/// A directory under the system temp dir, removed with its contents on drop.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("idrac-controller-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).expect("create scratch dir");
+        ScratchDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A fresh SQLite database in its own scratch directory.
+pub fn sqlite_database() -> (Database, ScratchDir) {
+    let dir = ScratchDir::new();
+    let db = Database::open(dir.path().join("db.sqlite").to_str().unwrap()).expect("open sqlite database");
+    (db, dir)
+}
+
+/// A database under test, named after its backend for assertion messages.
+pub struct TestDatabase {
+    pub backend: &'static str,
+    /// What `db` was opened from, for opening it a second time.
+    pub location: String,
+    pub db: Arc<Database>,
+    _dir: Option<ScratchDir>,
+}
+
+impl Deref for TestDatabase {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        &self.db
+    }
+}
+
+/// One database per backend: a fresh SQLite file, plus the PostgreSQL
+/// database at `TEST_POSTGRES_URL` when the `postgres` feature is built and
+/// the variable is set. The PostgreSQL one is shared between tests and
+/// runs, so tests name what they create with `unique`.
+pub fn databases() -> Vec<TestDatabase> {
+    let (db, dir) = sqlite_database();
+    let location = dir.path().join("db.sqlite").to_string_lossy().into_owned();
+    #[allow(unused_mut)]
+    let mut databases = vec![TestDatabase { backend: "sqlite", location, db: Arc::new(db), _dir: Some(dir) }];
+    #[cfg(feature = "postgres")]
+    if let Ok(url) = std::env::var("TEST_POSTGRES_URL") {
+        let db = Database::open(&url).expect("open postgres database");
+        databases.push(TestDatabase { backend: "postgres", location: url, db: Arc::new(db), _dir: None });
+    }
+    databases
+}
+
+/// `prefix` followed by a random suffix, for names that must not clash
+/// with rows left by other tests.
+pub fn unique(prefix: &str) -> String {
+    format!("{}-{}", prefix, &uuid::Uuid::new_v4().simple().to_string()[..12])
+}
+
+/// Application state over a fresh SQLite database. The iDRAC client points
+/// at a closed local port, so any call that reaches it fails fast.
+pub fn app_state() -> (AppState, ScratchDir) {
+    let (db, dir) = sqlite_database();
+    let state = app_state_over(Arc::new(db), &dir);
+    (state, dir)
+}
+
+/// Application state over `db`, keeping its credential key in `dir`.
+pub fn app_state_over(db: Arc<Database>, dir: &ScratchDir) -> AppState {
+    let mut config = Config::from_env();
+    config.database_path = dir.path().join("db.sqlite").to_string_lossy().into_owned();
+    config.database_url = None;
+
+    let idrac = Arc::new(IdracClient::new("127.0.0.1:9", "root", &SecretString::from("calvin".to_string())).unwrap());
+    let cipher = Arc::new(CredentialCipher::load(None, &dir.path().join("credential.key")).unwrap());
+    let servers = Arc::new(ServerRegistry::load(&db, cipher, idrac.clone(), false, None).unwrap());
+
+    AppState::new(db, idrac, servers, config)
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use log::warn;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::database::{ApiToken, Database, UsageKey, SQLITE_TIMESTAMP_FORMAT};
+
+/// Per-user and per-token request counts. Counts build up in memory and
+/// `flush` adds them to the `api_usage` table; token quotas are checked
+/// against the in-memory count so a busy token costs no extra queries.
+#[derive(Default)]
+pub struct UsageTracker {
+    pending: Mutex<HashMap<UsageKey, u64>>,
+    /// Requests of each token with a quota in the current hour, flushed or
+    /// not, by token id.
+    windows: Mutex<HashMap<i64, (DateTime<Utc>, u64)>>,
+}
+
+fn current_hour() -> DateTime<Utc> {
+    Utc::now().duration_trunc(Duration::hours(1)).unwrap()
+}
+
+impl UsageTracker {
+    fn count(&self, key: UsageKey) {
+        *self.pending.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    /// Count a request made with a session.
+    pub fn record_session(&self, user_id: i64) {
+        self.count(UsageKey {
+            user_id,
+            token_id: None,
+            hour: current_hour().format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+        });
+    }
+
+    /// Count a request made with `token` unless that would exceed its hourly
+    /// quota, in which case nothing is counted and the time the quota resets
+    /// is returned.
+    pub fn record_token(&self, db: &Database, token: &ApiToken) -> Result<(), DateTime<Utc>> {
+        let hour = current_hour();
+        let key = UsageKey {
+            user_id: token.user_id,
+            token_id: Some(token.id),
+            hour: hour.format(SQLITE_TIMESTAMP_FORMAT).to_string(),
+        };
+        if let Some(limit) = token.requests_per_hour {
+            let seeded = self.windows.lock().unwrap().get(&token.id).is_some_and(|(at, _)| *at == hour);
+            // After a restart or a quota change the stored and not yet
+            // flushed counts of this hour still apply.
+            let counted = match seeded {
+                true => 0,
+                false => {
+                    let stored = db.api_token_usage(token.id, &key.hour).unwrap_or_else(|e| {
+                        warn!("Failed to read usage of API token {}: {}", token.id, e);
+                        0
+                    });
+                    stored + self.pending.lock().unwrap().get(&key).copied().unwrap_or(0)
+                }
+            };
+
+            let mut windows = self.windows.lock().unwrap();
+            let window = windows.entry(token.id).or_insert((hour, counted));
+            if window.0 != hour {
+                *window = (hour, counted);
+            }
+            if window.1 >= u64::from(limit) {
+                return Err(hour + Duration::hours(1));
+            }
+            window.1 += 1;
+        }
+        self.count(key);
+        Ok(())
+    }
+
+    /// Forget the counted window of a token whose quota changed, so the
+    /// next request reads its count back from the database.
+    pub fn reset_window(&self, token_id: i64) {
+        self.windows.lock().unwrap().remove(&token_id);
+    }
+
+    /// Add the pending counts to the database. They are kept for the next
+    /// flush if that fails.
+    pub fn flush(&self, db: &Database) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return;
+        }
+
+        let counts: Vec<(UsageKey, u64)> = pending.into_iter().collect();
+        if let Err(e) = db.record_api_usage(&counts) {
+            warn!("Failed to store API usage: {}", e);
+            let mut pending = self.pending.lock().unwrap();
+            for (key, requests) in counts {
+                *pending.entry(key).or_default() += requests;
+            }
+        }
+
+        let hour = current_hour();
+        self.windows.lock().unwrap().retain(|_, (at, _)| *at == hour);
+    }
+}
@@ -0,0 +1,426 @@
+use std::fmt;
+
+use crate::break_glass::BREAK_GLASS_USERNAME;
+use crate::database::{ComplianceRequirement, NewServer, SQLITE_TIMESTAMP_FORMAT};
+use crate::firmware::compare_versions;
+use crate::idrac::normalize_host;
+use crate::secret::SecretString;
+use crate::servers::DEFAULT_SERVER_ALIAS;
+
+const USERNAME_MIN: usize = 3;
+const USERNAME_MAX: usize = 32;
+const IDRAC_USERNAME_MAX: usize = 16;
+const OEM_ACTION_PATH_MAX: usize = 512;
+const SERVER_NAME_MIN: usize = 1;
+const SERVER_NAME_MAX: usize = 64;
+const TOKEN_NAME_MIN: usize = 1;
+const TOKEN_NAME_MAX: usize = 64;
+const GROUP_NAME_MIN: usize = 1;
+const GROUP_NAME_MAX: usize = 64;
+const COMPLIANCE_PROFILE_NAME_MIN: usize = 1;
+const COMPLIANCE_PROFILE_NAME_MAX: usize = 64;
+const SHARE_NAME_MIN: usize = 1;
+const SHARE_NAME_MAX: usize = 64;
+const SHARE_PASSCODE_MIN: usize = 4;
+const SHARE_PASSCODE_MAX: usize = 72;
+const EMAIL_MAX: usize = 254;
+const TAG_MAX: usize = 32;
+const LOCATION_MAX: usize = 128;
+const PREFERENCE_KEY_MAX: usize = 64;
+const PREFERENCE_VALUE_MAX_BYTES: usize = 16 * 1024;
+const PREFERENCES_MAX: usize = 100;
+
+/// A validation failure tied to the request field that caused it.
+#[derive(Debug, Clone)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Trim a display name and enforce length and character rules.
+fn normalize_name(field: &'static str, input: &str, min: usize, max: usize) -> Result<String, FieldError> {
+    let trimmed = input.trim();
+
+    if trimmed.chars().any(|c| c.is_control()) {
+        return Err(FieldError {
+            field,
+            message: "must not contain control characters or line breaks".to_string(),
+        });
+    }
+
+    let length = trimmed.chars().count();
+    if length < min || length > max {
+        return Err(FieldError {
+            field,
+            message: format!("must be between {} and {} characters", min, max),
+        });
+    }
+
+    Ok(trimmed.to_string())
+}
+
+pub fn normalize_username(input: &str) -> Result<String, FieldError> {
+    let username = normalize_name("username", input, USERNAME_MIN, USERNAME_MAX)?;
+    if username.eq_ignore_ascii_case(BREAK_GLASS_USERNAME) {
+        return Err(FieldError {
+            field: "username",
+            message: "is reserved".to_string(),
+        });
+    }
+    Ok(username)
+}
+
+/// An address password reset links can be mailed to: one `@` with text on
+/// both sides and no whitespace or angle brackets, which would break the
+/// SMTP envelope.
+pub fn normalize_email(input: &str) -> Result<String, FieldError> {
+    let email = input.trim();
+    let valid = email.len() <= EMAIL_MAX
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | ','))
+        && matches!(email.split_once('@'), Some((local, domain)) if !local.is_empty() && !domain.is_empty() && !domain.contains('@'));
+    if !valid {
+        return Err(FieldError {
+            field: "email",
+            message: format!("must be an email address of at most {} characters", EMAIL_MAX),
+        });
+    }
+    Ok(email.to_string())
+}
+
+/// iDRAC account names are at most 16 characters; letters, digits, `-`
+/// and `_` are accepted by every firmware version.
+pub fn validate_idrac_username(input: &str) -> Result<String, FieldError> {
+    let username = input.trim();
+    let valid = (1..=IDRAC_USERNAME_MAX).contains(&username.len())
+        && username.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(FieldError {
+            field: "username",
+            message: format!(
+                "must be 1 to {} letters, digits, '-' or '_'",
+                IDRAC_USERNAME_MAX
+            ),
+        });
+    }
+    Ok(username.to_string())
+}
+
+/// Path of a Redfish action for `POST /api/idrac/oem-action`. It must
+/// start with `/redfish/v1/` and hold only letters, digits, `.`, `_`, `-`
+/// and `/`, with no empty or `..` segments, so that appended to an iDRAC's
+/// base URL it cannot name another host, port or path outside Redfish.
+pub fn validate_oem_action_path(input: &str) -> Result<String, FieldError> {
+    let path = input.trim();
+    let invalid = |message: &str| FieldError {
+        field: "url_suffix",
+        message: message.to_string(),
+    };
+    if !path.starts_with("/redfish/v1/") {
+        return Err(invalid("must start with /redfish/v1/"));
+    }
+    if path.len() > OEM_ACTION_PATH_MAX {
+        return Err(invalid(&format!("must be at most {} characters", OEM_ACTION_PATH_MAX)));
+    }
+    if !path.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/')) {
+        return Err(invalid("may only contain letters, digits, '.', '_', '-' and '/'"));
+    }
+    if path[1..].split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..") {
+        return Err(invalid("must not contain empty, '.' or '..' segments"));
+    }
+    Ok(path.to_string())
+}
+
+pub fn normalize_server_name(input: &str) -> Result<String, FieldError> {
+    normalize_name("name", input, SERVER_NAME_MIN, SERVER_NAME_MAX)
+}
+
+pub fn normalize_token_name(input: &str) -> Result<String, FieldError> {
+    normalize_name("name", input, TOKEN_NAME_MIN, TOKEN_NAME_MAX)
+}
+
+pub fn normalize_group_name(input: &str) -> Result<String, FieldError> {
+    normalize_name("name", input, GROUP_NAME_MIN, GROUP_NAME_MAX)
+}
+
+pub fn normalize_share_name(input: &str) -> Result<String, FieldError> {
+    normalize_name("name", input, SHARE_NAME_MIN, SHARE_NAME_MAX)
+}
+
+/// A share covers either a list of server aliases, each of which
+/// `is_known`, or a tag; exactly one must be given.
+pub fn normalize_share_scope(
+    servers: Option<&[String]>,
+    tag: Option<&str>,
+    is_known: impl Fn(&str) -> bool,
+) -> Result<(Vec<String>, Option<String>), FieldError> {
+    match (servers.filter(|servers| !servers.is_empty()), tag.map(str::trim).filter(|tag| !tag.is_empty())) {
+        (Some(servers), None) => {
+            let mut normalized: Vec<String> = Vec::new();
+            for alias in servers.iter().map(|alias| alias.trim()) {
+                if !is_known(alias) {
+                    return Err(FieldError {
+                        field: "servers",
+                        message: format!("'{}' is not a registered server", alias),
+                    });
+                }
+                if !normalized.iter().any(|known| known == alias) {
+                    normalized.push(alias.to_string());
+                }
+            }
+            Ok((normalized, None))
+        }
+        (None, Some(tag)) => Ok((Vec::new(), Some(normalize_name("tag", tag, 1, TAG_MAX)?))),
+        _ => Err(FieldError {
+            field: "servers",
+            message: "give either servers or tag".to_string(),
+        }),
+    }
+}
+
+/// bcrypt only reads the first 72 bytes, so longer passcodes are refused
+/// rather than silently shortened.
+pub fn validate_share_passcode(passcode: &str) -> Result<(), FieldError> {
+    if !(SHARE_PASSCODE_MIN..=SHARE_PASSCODE_MAX).contains(&passcode.len()) {
+        return Err(FieldError {
+            field: "passcode",
+            message: format!("must be {} to {} bytes", SHARE_PASSCODE_MIN, SHARE_PASSCODE_MAX),
+        });
+    }
+    Ok(())
+}
+
+/// Trim and deduplicate group members; every one must be a server alias
+/// for which `is_known` holds.
+pub fn normalize_group_members(members: &[String], is_known: impl Fn(&str) -> bool) -> Result<Vec<String>, FieldError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for member in members {
+        let member = member.trim();
+        if !is_known(member) {
+            return Err(FieldError {
+                field: "members",
+                message: format!("'{}' is not a registered server", member),
+            });
+        }
+        if !normalized.iter().any(|m| m == member) {
+            normalized.push(member.to_string());
+        }
+    }
+    Ok(normalized)
+}
+
+pub fn normalize_compliance_profile_name(input: &str) -> Result<String, FieldError> {
+    normalize_name("name", input, COMPLIANCE_PROFILE_NAME_MIN, COMPLIANCE_PROFILE_NAME_MAX)
+}
+
+/// Trim a profile's requirements and check that each component appears
+/// once, has a minimum version and is not recommended below it.
+pub fn normalize_compliance_requirements(
+    requirements: &[ComplianceRequirement],
+) -> Result<Vec<ComplianceRequirement>, FieldError> {
+    if requirements.is_empty() {
+        return Err(FieldError {
+            field: "components",
+            message: "must list at least one component".to_string(),
+        });
+    }
+
+    let mut normalized: Vec<ComplianceRequirement> = Vec::new();
+    for requirement in requirements {
+        let component = requirement.component.trim();
+        if component.is_empty() {
+            return Err(FieldError {
+                field: "components",
+                message: "component names must not be empty".to_string(),
+            });
+        }
+        if normalized.iter().any(|r| r.component.eq_ignore_ascii_case(component)) {
+            return Err(FieldError {
+                field: "components",
+                message: format!("'{}' is listed more than once", component),
+            });
+        }
+        let minimum_version = requirement.minimum_version.trim();
+        if minimum_version.is_empty() {
+            return Err(FieldError {
+                field: "components",
+                message: format!("'{}' needs a minimum_version", component),
+            });
+        }
+        let recommended_version = requirement
+            .recommended_version
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty());
+        if recommended_version.is_some_and(|v| compare_versions(v, minimum_version).is_lt()) {
+            return Err(FieldError {
+                field: "components",
+                message: format!("'{}' has a recommended_version below its minimum_version", component),
+            });
+        }
+        normalized.push(ComplianceRequirement {
+            component: component.to_string(),
+            minimum_version: minimum_version.to_string(),
+            recommended_version: recommended_version.map(str::to_string),
+        });
+    }
+    Ok(normalized)
+}
+
+/// Validate and normalize the fields of a server about to be registered.
+/// The password is returned as given, unencrypted.
+pub fn validate_new_server(
+    name: &str,
+    host: &str,
+    username: &str,
+    password: SecretString,
+    tags: &[String],
+    location: Option<&str>,
+) -> Result<NewServer, FieldError> {
+    let name = normalize_server_name(name)?;
+    if slugify(&name) == DEFAULT_SERVER_ALIAS {
+        return Err(FieldError {
+            field: "name",
+            message: format!("'{}' is reserved for the IDRAC_HOST server", DEFAULT_SERVER_ALIAS),
+        });
+    }
+
+    let host = normalize_host(host).map_err(|message| FieldError {
+        field: "host",
+        message,
+    })?;
+
+    let username = username.trim();
+    if username.is_empty() {
+        return Err(FieldError {
+            field: "username",
+            message: "is required".to_string(),
+        });
+    }
+    if password.is_empty() {
+        return Err(FieldError {
+            field: "password",
+            message: "is required".to_string(),
+        });
+    }
+
+    let mut normalized_tags = Vec::new();
+    for tag in tags {
+        let tag = normalize_name("tags", tag, 1, TAG_MAX)?;
+        if !normalized_tags.contains(&tag) {
+            normalized_tags.push(tag);
+        }
+    }
+
+    let location = match location.map(str::trim).filter(|l| !l.is_empty()) {
+        Some(location) => Some(normalize_name("location", location, 1, LOCATION_MAX)?),
+        None => None,
+    };
+
+    Ok(NewServer {
+        name,
+        host,
+        username: username.to_string(),
+        password,
+        tags: normalized_tags,
+        location,
+        default_power_cap_watts: None,
+        hosts_this_app: false,
+        os_health_url: None,
+        os_health_insecure: false,
+        os_health_token: None,
+    })
+}
+
+/// An absolute `http` or `https` URL of an OS health endpoint.
+pub fn normalize_os_health_url(field: &'static str, input: &str) -> Result<String, FieldError> {
+    let input = input.trim();
+    let invalid = || FieldError {
+        field,
+        message: "must be an absolute http or https URL".to_string(),
+    };
+    let url = reqwest::Url::parse(input).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(invalid());
+    }
+    Ok(url.to_string())
+}
+
+/// Split a preferences document into `(key, serialized value)` pairs. The
+/// document must be a JSON object; values may be any JSON.
+pub fn validate_preferences(document: &serde_json::Value) -> Result<Vec<(String, String)>, FieldError> {
+    let invalid = |message: String| FieldError {
+        field: "preferences",
+        message,
+    };
+
+    let object = document
+        .as_object()
+        .ok_or_else(|| invalid("must be a JSON object".to_string()))?;
+
+    if object.len() > PREFERENCES_MAX {
+        return Err(invalid(format!("must not have more than {} keys", PREFERENCES_MAX)));
+    }
+
+    let mut pairs = Vec::with_capacity(object.len());
+    for (key, value) in object {
+        if key.is_empty() || key.chars().count() > PREFERENCE_KEY_MAX || key.chars().any(|c| c.is_control()) {
+            return Err(invalid(format!(
+                "key '{}' must be 1 to {} printable characters",
+                key, PREFERENCE_KEY_MAX
+            )));
+        }
+
+        let serialized = value.to_string();
+        if serialized.len() > PREFERENCE_VALUE_MAX_BYTES {
+            return Err(invalid(format!(
+                "value for '{}' must not exceed {} bytes",
+                key, PREFERENCE_VALUE_MAX_BYTES
+            )));
+        }
+        pairs.push((key.clone(), serialized));
+    }
+
+    Ok(pairs)
+}
+
+/// Derive an identifier safe for MQTT topics, metric labels and CSV columns:
+/// lowercase ASCII letters, digits and single dashes.
+/// Parse an RFC 3339 timestamp or a `YYYY-MM-DD` date (midnight UTC) into
+/// the format SQLite uses for `CURRENT_TIMESTAMP` columns.
+pub fn parse_timestamp(field: &'static str, input: &str) -> Result<String, FieldError> {
+    let input = input.trim();
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&chrono::Utc).format(SQLITE_TIMESTAMP_FORMAT).to_string());
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(date.and_time(chrono::NaiveTime::MIN).format(SQLITE_TIMESTAMP_FORMAT).to_string());
+    }
+    Err(FieldError {
+        field,
+        message: "must be an RFC 3339 timestamp or a YYYY-MM-DD date".to_string(),
+    })
+}
+
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "item".to_string()
+    } else {
+        slug.to_string()
+    }
+}
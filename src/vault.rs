@@ -0,0 +1,230 @@
+use log::{info, warn};
+use reqwest::{Client, Method, RequestBuilder};
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::secret::SecretString;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum VaultAuth {
+    Token(SecretString),
+    AppRole { role_id: String, secret_id: SecretString },
+}
+
+/// Where to find the iDRAC credentials in Vault and how to log in.
+pub struct VaultConfig {
+    addr: String,
+    namespace: Option<String>,
+    auth: VaultAuth,
+    /// KV version 2 path such as `secret/idrac`, whose first segment is the
+    /// mount.
+    secret_path: String,
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+impl VaultConfig {
+    /// `VAULT_ADDR` with `VAULT_TOKEN` or `VAULT_ROLE_ID` and
+    /// `VAULT_SECRET_ID`, `VAULT_IDRAC_SECRET_PATH` and the optional
+    /// `VAULT_NAMESPACE`. `None` when Vault is not configured.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(addr) = env("VAULT_ADDR") else {
+            return Ok(None);
+        };
+        let auth = match (env("VAULT_TOKEN"), env("VAULT_ROLE_ID"), env("VAULT_SECRET_ID")) {
+            (_, Some(role_id), Some(secret_id)) => VaultAuth::AppRole {
+                role_id,
+                secret_id: SecretString::from(secret_id),
+            },
+            (Some(token), _, _) => VaultAuth::Token(SecretString::from(token)),
+            _ => return Err("VAULT_ADDR is set but neither VAULT_TOKEN nor VAULT_ROLE_ID and VAULT_SECRET_ID are".to_string()),
+        };
+        let secret_path = env("VAULT_IDRAC_SECRET_PATH")
+            .map(|path| path.trim_matches('/').to_string())
+            .filter(|path| path.contains('/'))
+            .ok_or("VAULT_IDRAC_SECRET_PATH must be set to a KV path such as secret/idrac")?;
+
+        Ok(Some(VaultConfig {
+            addr: addr.trim_end_matches('/').to_string(),
+            namespace: env("VAULT_NAMESPACE"),
+            auth,
+            secret_path,
+        }))
+    }
+}
+
+/// How long the current Vault token lives; a `ttl` of zero never expires.
+#[derive(Debug, Clone, Copy)]
+pub struct VaultLease {
+    pub ttl: Duration,
+    pub renewable: bool,
+}
+
+/// iDRAC credentials read from Vault in place of `IDRAC_USERNAME` and
+/// `IDRAC_PASSWORD`.
+pub struct IdracCredentials {
+    pub username: String,
+    pub password: SecretString,
+}
+
+#[derive(Deserialize)]
+struct AuthResponse {
+    auth: AuthInfo,
+}
+
+#[derive(Deserialize)]
+struct AuthInfo {
+    client_token: SecretString,
+    lease_duration: u64,
+    renewable: bool,
+}
+
+#[derive(Deserialize)]
+struct KvResponse {
+    data: KvVersion,
+}
+
+#[derive(Deserialize)]
+struct KvVersion {
+    data: KvCredentials,
+}
+
+#[derive(Deserialize)]
+struct KvCredentials {
+    username: Option<String>,
+    password: Option<SecretString>,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    data: LookupData,
+}
+
+#[derive(Deserialize)]
+struct LookupData {
+    ttl: u64,
+    #[serde(default)]
+    renewable: bool,
+}
+
+/// A logged-in Vault client. Cloning shares the token.
+#[derive(Clone)]
+pub struct VaultClient {
+    config: Arc<VaultConfig>,
+    http: Client,
+    token: Arc<RwLock<SecretString>>,
+}
+
+impl VaultClient {
+    /// Log in with the configured method and return the token's lease.
+    pub async fn login(config: VaultConfig) -> Result<(Self, VaultLease), String> {
+        let http = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        let token = match &config.auth {
+            VaultAuth::Token(token) => token.clone(),
+            VaultAuth::AppRole { .. } => SecretString::from(String::new()),
+        };
+        let client = VaultClient {
+            config: Arc::new(config),
+            http,
+            token: Arc::new(RwLock::new(token)),
+        };
+
+        let lease = match &client.config.auth {
+            VaultAuth::Token(_) => {
+                let lookup: LookupResponse = client.send(client.authenticated(Method::GET, "auth/token/lookup-self")).await?;
+                VaultLease {
+                    ttl: Duration::from_secs(lookup.data.ttl),
+                    renewable: lookup.data.renewable,
+                }
+            }
+            VaultAuth::AppRole { .. } => client.approle_login().await?,
+        };
+        Ok((client, lease))
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}/v1/{}", self.config.addr, path));
+        match &self.config.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        }
+    }
+
+    fn authenticated(&self, method: Method, path: &str) -> RequestBuilder {
+        let token = self.token.read().unwrap().clone();
+        self.request(method, path).header("X-Vault-Token", token.expose())
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, String> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to Vault: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            // Vault error bodies list messages only, never secrets.
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Vault returned HTTP {}: {}", status.as_u16(), body.trim()));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid Vault response: {}", e))
+    }
+
+    async fn approle_login(&self) -> Result<VaultLease, String> {
+        let VaultAuth::AppRole { role_id, secret_id } = &self.config.auth else {
+            return Err("Vault is not configured for AppRole".to_string());
+        };
+        let body = serde_json::json!({ "role_id": role_id, "secret_id": secret_id.expose() });
+        let response: AuthResponse = self.send(self.request(Method::POST, "auth/approle/login").json(&body)).await?;
+        *self.token.write().unwrap() = response.auth.client_token;
+        info!("Logged in to Vault with AppRole");
+        Ok(VaultLease {
+            ttl: Duration::from_secs(response.auth.lease_duration),
+            renewable: response.auth.renewable,
+        })
+    }
+
+    /// Read `username` and `password` from the configured secret.
+    pub async fn idrac_credentials(&self) -> Result<IdracCredentials, String> {
+        let (mount, path) = self.config.secret_path.split_once('/').unwrap_or_default();
+        let secret: KvResponse = self.send(self.authenticated(Method::GET, &format!("{}/data/{}", mount, path))).await?;
+        let missing = |name: &str| format!("Vault secret {} has no '{}' field", self.config.secret_path, name);
+        Ok(IdracCredentials {
+            username: secret.data.data.username.ok_or_else(|| missing("username"))?,
+            password: secret.data.data.password.ok_or_else(|| missing("password"))?,
+        })
+    }
+
+    /// Whether `refresh` can extend a token with `lease`.
+    pub fn can_refresh(&self, lease: &VaultLease) -> bool {
+        lease.renewable || matches!(self.config.auth, VaultAuth::AppRole { .. })
+    }
+
+    /// Renew the token, or log in again with AppRole when it cannot be
+    /// renewed any further.
+    pub async fn refresh(&self, lease: &VaultLease) -> Result<VaultLease, String> {
+        if lease.renewable {
+            let renewed: Result<AuthResponse, String> = self.send(self.authenticated(Method::POST, "auth/token/renew-self")).await;
+            match renewed {
+                Ok(response) => {
+                    return Ok(VaultLease {
+                        ttl: Duration::from_secs(response.auth.lease_duration),
+                        renewable: response.auth.renewable,
+                    })
+                }
+                Err(e) if !matches!(self.config.auth, VaultAuth::AppRole { .. }) => return Err(e),
+                Err(e) => warn!("Vault token renewal failed, logging in again: {}", e),
+            }
+        }
+        self.approle_login().await
+    }
+}
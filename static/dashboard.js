@@ -0,0 +1,352 @@
+const messageDiv = document.getElementById('message');
+const statusDiv = document.getElementById('powerStatus');
+const buttons = ['btnOn', 'btnOff', 'btnShutdown', 'btnGracefulRestart', 'btnRestart', 'btnCycle', 'btnRefresh'];
+let hostsThisApp = false;
+
+// Power-off style actions against the server running this app need
+// an extra acknowledgment, asked for separately.
+function acknowledgeHostsThisApp() {
+    if (!hostsThisApp) {
+        return {};
+    }
+    if (!confirm('This server hosts the iDRAC Controller. The dashboard will go down and you will need another way to power it back on. Continue?')) {
+        return null;
+    }
+    return { i_understand_this_hosts_the_controller: true };
+}
+
+function powerRequest(body) {
+    return {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(body),
+    };
+}
+
+async function loadServerFlags() {
+    try {
+        const response = await fetch('/api/servers?limit=1000');
+        const data = await response.json();
+        const server = data.success && data.items.find(s => s.alias === 'default');
+        hostsThisApp = Boolean(server && server.hosts_this_app);
+        document.getElementById('hostsAppBanner').style.display = hostsThisApp ? 'block' : 'none';
+    } catch (error) {
+        // Leave the banner hidden; the API still asks for the acknowledgment
+    }
+}
+
+async function loadAlerts() {
+    try {
+        const response = await fetch('/api/alerts');
+        const data = await response.json();
+        const container = document.getElementById('alerts');
+        container.replaceChildren();
+        for (const alert of (data.success ? data.alerts : [])) {
+            const banner = document.createElement('div');
+            banner.className = 'alert-banner';
+            banner.textContent = '🚨 ' + alert.message;
+            container.appendChild(banner);
+        }
+    } catch (error) {
+        // Alerts are informational; keep whatever is shown
+    }
+}
+
+async function loadChanges() {
+    try {
+        const response = await fetch('/api/changes?limit=10');
+        const data = await response.json();
+        const list = document.getElementById('changes');
+        list.replaceChildren();
+        for (const change of (data.success ? data.changes : [])) {
+            const item = document.createElement('li');
+            if (!change.success) {
+                item.className = 'change-failed';
+            }
+            const time = document.createElement('span');
+            time.className = 'change-time';
+            time.textContent = change.relative_time;
+            time.title = change.at;
+            item.append(time, change.message);
+            list.appendChild(item);
+        }
+    } catch (error) {
+        // The feed is informational; keep whatever is shown
+    }
+}
+
+function showMessage(text, type) {
+    messageDiv.textContent = text;
+    messageDiv.className = 'message ' + type;
+    messageDiv.style.display = 'block';
+    setTimeout(() => {
+        messageDiv.style.display = 'none';
+    }, 5000);
+}
+
+function setButtonsEnabled(enabled) {
+    buttons.forEach(id => {
+        document.getElementById(id).disabled = !enabled;
+    });
+}
+
+function showPowerState(state) {
+    statusDiv.textContent = state;
+
+    if (state === 'On') {
+        statusDiv.className = 'status-value status-on';
+    } else if (state === 'Off') {
+        statusDiv.className = 'status-value status-off';
+    } else {
+        statusDiv.className = 'status-value status-unknown';
+    }
+}
+
+async function refreshStatus() {
+    statusDiv.innerHTML = '<div class="loading"><div class="spinner"></div>Loading...</div>';
+    statusDiv.className = 'status-value status-unknown';
+
+    try {
+        const response = await fetch('/api/power/status');
+        const data = await response.json();
+
+        if (data.success) {
+            showPowerState(data.power_state);
+        } else {
+            statusDiv.textContent = 'Error';
+            statusDiv.className = 'status-value status-unknown';
+            showMessage(data.message, 'error');
+        }
+    } catch (error) {
+        statusDiv.textContent = 'Network Error';
+        statusDiv.className = 'status-value status-unknown';
+        showMessage('Failed to fetch server status', 'error');
+    }
+}
+
+async function powerOn() {
+    setButtonsEnabled(false);
+    showMessage('Sending power on command...', 'info');
+
+    try {
+        const response = await fetch('/api/power/on', { method: 'POST' });
+        const data = await response.json();
+
+        if (data.success) {
+            showMessage(data.message, 'success');
+            setTimeout(refreshStatus, 2000);
+        } else {
+            showMessage(data.message, 'error');
+        }
+    } catch (error) {
+        showMessage('Failed to send power on command', 'error');
+    } finally {
+        setButtonsEnabled(true);
+    }
+}
+
+async function powerOff() {
+    if (!confirm('Are you sure you want to force power off the server? This may cause data loss.')) {
+        return;
+    }
+    const acknowledgment = acknowledgeHostsThisApp();
+    if (acknowledgment === null) {
+        return;
+    }
+
+    setButtonsEnabled(false);
+    showMessage('Sending force power off command...', 'info');
+
+    try {
+        const response = await fetch('/api/power/off', powerRequest(acknowledgment));
+        const data = await response.json();
+
+        if (data.success) {
+            showMessage(data.warning || data.message, 'success');
+            setTimeout(refreshStatus, 2000);
+        } else {
+            showMessage(data.message, 'error');
+        }
+    } catch (error) {
+        showMessage('Failed to send power off command', 'error');
+    } finally {
+        setButtonsEnabled(true);
+    }
+}
+
+async function gracefulShutdown() {
+    if (!confirm('Initiate graceful shutdown of the server?')) {
+        return;
+    }
+    const acknowledgment = acknowledgeHostsThisApp();
+    if (acknowledgment === null) {
+        return;
+    }
+
+    setButtonsEnabled(false);
+    showMessage('Sending graceful shutdown command...', 'info');
+
+    try {
+        const response = await fetch('/api/power/shutdown', powerRequest(acknowledgment));
+        const data = await response.json();
+
+        if (data.success) {
+            showMessage(data.warning || data.message, 'success');
+            setTimeout(refreshStatus, 2000);
+        } else {
+            showMessage(data.message, 'error');
+        }
+    } catch (error) {
+        showMessage('Failed to send shutdown command', 'error');
+    } finally {
+        setButtonsEnabled(true);
+    }
+}
+
+async function gracefulRestart() {
+    if (!confirm('Ask the operating system to restart the server?')) {
+        return;
+    }
+    const acknowledgment = acknowledgeHostsThisApp();
+    if (acknowledgment === null) {
+        return;
+    }
+
+    setButtonsEnabled(false);
+    showMessage('Sending graceful restart command...', 'info');
+
+    try {
+        const response = await fetch('/api/power/restart/graceful', powerRequest(acknowledgment));
+        const data = await response.json();
+
+        if (data.success) {
+            showMessage(data.warning || data.message, 'success');
+            setTimeout(refreshStatus, 2000);
+        } else {
+            showMessage(data.message, 'error');
+        }
+    } catch (error) {
+        showMessage('Failed to send restart command', 'error');
+    } finally {
+        setButtonsEnabled(true);
+    }
+}
+
+async function forceRestart() {
+    if (!confirm('Are you sure you want to force restart the server? This may cause data loss.')) {
+        return;
+    }
+    const acknowledgment = acknowledgeHostsThisApp();
+    if (acknowledgment === null) {
+        return;
+    }
+
+    setButtonsEnabled(false);
+    showMessage('Sending force restart command...', 'info');
+
+    try {
+        const response = await fetch('/api/power/restart', powerRequest(acknowledgment));
+        const data = await response.json();
+
+        if (data.success) {
+            showMessage(data.warning || data.message, 'success');
+            setTimeout(refreshStatus, 2000);
+        } else {
+            showMessage(data.message, 'error');
+        }
+    } catch (error) {
+        showMessage('Failed to send restart command', 'error');
+    } finally {
+        setButtonsEnabled(true);
+    }
+}
+
+async function powerCycle() {
+    if (!confirm('Are you sure you want to power cycle the server? This may cause data loss.')) {
+        return;
+    }
+    const acknowledgment = acknowledgeHostsThisApp();
+    if (acknowledgment === null) {
+        return;
+    }
+
+    setButtonsEnabled(false);
+    showMessage('Sending power cycle command...', 'info');
+
+    try {
+        const response = await fetch('/api/power/cycle', powerRequest(acknowledgment));
+        const data = await response.json();
+
+        if (data.success) {
+            showMessage(data.warning || data.message, 'success');
+            setTimeout(refreshStatus, 2000);
+        } else {
+            showMessage(data.message, 'error');
+        }
+    } catch (error) {
+        showMessage('Failed to send power cycle command', 'error');
+    } finally {
+        setButtonsEnabled(true);
+    }
+}
+
+async function logout() {
+    try {
+        const response = await fetch('/api/logout', { method: 'POST' });
+        const data = await response.json();
+        // Single sign-on sessions may also end at the provider.
+        window.location.href = data.end_session_url || '/';
+    } catch (error) {
+        showMessage('Failed to logout', 'error');
+    }
+}
+
+// Layout preferences are stored server-side so they follow the user
+// across browsers and devices.
+let preferences = {};
+
+async function loadPreferences() {
+    try {
+        const response = await fetch('/api/users/me/preferences');
+        const data = await response.json();
+        if (data.success) {
+            preferences = data.preferences;
+        }
+    } catch (error) {
+        // Fall back to defaults
+    }
+}
+
+// Load status on page load
+refreshStatus();
+loadServerFlags();
+loadAlerts();
+setInterval(loadAlerts, 60000);
+loadChanges();
+setInterval(loadChanges, 60000);
+
+// After a power action the server polls the iDRAC every couple of
+// seconds and pushes each observed state here.
+const events = new EventSource('/api/events/stream');
+events.onmessage = (message) => {
+    const event = JSON.parse(message.data);
+    if (event.type === 'power_state_observed' && event.server === 'default') {
+        showPowerState(event.power_state);
+    }
+};
+
+// The power state is also pushed whenever it changes, e.g. after a power
+// action from another session; reconnect if the socket drops.
+function watchPowerState() {
+    const scheme = location.protocol === 'https:' ? 'wss:' : 'ws:';
+    const socket = new WebSocket(`${scheme}//${location.host}/ws/power`);
+    socket.onmessage = (message) => showPowerState(JSON.parse(message.data).power_state);
+    socket.onclose = () => setTimeout(watchPowerState, 10000);
+}
+watchPowerState();
+
+// Auto-refresh every 30 seconds unless the user chose otherwise
+loadPreferences().then(() => {
+    const seconds = Number(preferences.refresh_interval_secs) || 30;
+    setInterval(refreshStatus, seconds * 1000);
+});
@@ -0,0 +1,81 @@
+const form = document.getElementById('loginForm');
+const messageDiv = document.getElementById('message');
+const submitBtn = document.getElementById('submitBtn');
+const ssoBtn = document.getElementById('ssoBtn');
+const resetLink = document.getElementById('resetLink');
+
+const SSO_ERRORS = {
+    denied: 'Single sign-on was cancelled or refused.',
+    unavailable: 'The single sign-on provider could not be reached. Please try again later.',
+    expired: 'This account has expired.',
+    failed: 'Single sign-on failed. Please try again.',
+};
+
+function showMessage(text, type) {
+    messageDiv.textContent = text;
+    messageDiv.className = 'message ' + type;
+    messageDiv.style.display = 'block';
+}
+
+form.addEventListener('submit', async (e) => {
+    e.preventDefault();
+
+    const username = document.getElementById('username').value;
+    const password = document.getElementById('password').value;
+
+    submitBtn.disabled = true;
+    submitBtn.textContent = 'Signing In...';
+
+    try {
+        const response = await fetch('/api/login', {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ username, password }),
+        });
+
+        const data = await response.json();
+
+        if (data.success) {
+            showMessage(data.message, 'success');
+            setTimeout(() => {
+                window.location.href = '/';
+            }, 500);
+        } else {
+            showMessage(data.message, 'error');
+            submitBtn.disabled = false;
+            submitBtn.textContent = 'Sign In';
+        }
+    } catch (error) {
+        showMessage('Network error. Please try again.', 'error');
+        submitBtn.disabled = false;
+        submitBtn.textContent = 'Sign In';
+    }
+});
+
+ssoBtn.addEventListener('click', () => {
+    window.location.href = '/api/auth/oidc/login';
+});
+
+// Offer single sign-on when it is configured, and hide the form when it
+// is the only way in.
+async function loadAuthMethods() {
+    try {
+        const response = await fetch('/api/auth/methods');
+        const data = await response.json();
+        if (data.success) {
+            ssoBtn.hidden = !data.oidc;
+            form.hidden = !data.password;
+            resetLink.hidden = !data.password_reset;
+        }
+    } catch (error) {
+        // Keep the password form.
+    }
+}
+
+const ssoError = new URLSearchParams(window.location.search).get('sso_error');
+if (ssoError) {
+    showMessage(SSO_ERRORS[ssoError] || SSO_ERRORS.failed, 'error');
+}
+loadAuthMethods();
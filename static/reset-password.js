@@ -0,0 +1,81 @@
+const requestForm = document.getElementById('requestForm');
+const resetForm = document.getElementById('resetForm');
+const messageDiv = document.getElementById('message');
+const requestBtn = document.getElementById('requestBtn');
+const resetBtn = document.getElementById('resetBtn');
+
+// The link in the email carries the token in its fragment, which is never
+// sent to the server.
+const token = window.location.hash.slice(1);
+
+function showMessage(text, type) {
+    messageDiv.textContent = text;
+    messageDiv.className = 'message ' + type;
+    messageDiv.style.display = 'block';
+}
+
+async function post(url, body) {
+    const response = await fetch(url, {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(body),
+    });
+    return response.json();
+}
+
+requestForm.addEventListener('submit', async (e) => {
+    e.preventDefault();
+
+    const username = document.getElementById('username').value;
+
+    requestBtn.disabled = true;
+    requestBtn.textContent = 'Sending...';
+
+    try {
+        const data = await post('/api/password-reset/request', { username });
+        showMessage(data.message, data.success ? 'success' : 'error');
+        if (data.success) {
+            requestForm.hidden = true;
+            return;
+        }
+    } catch (error) {
+        showMessage('Network error. Please try again.', 'error');
+    }
+    requestBtn.disabled = false;
+    requestBtn.textContent = 'Email Me a Reset Link';
+});
+
+resetForm.addEventListener('submit', async (e) => {
+    e.preventDefault();
+
+    const password = document.getElementById('password').value;
+    const confirm_password = document.getElementById('confirm_password').value;
+
+    resetBtn.disabled = true;
+    resetBtn.textContent = 'Saving...';
+
+    try {
+        const data = await post('/api/password-reset/complete', { token, password, confirm_password });
+        showMessage(data.message, data.success ? 'success' : 'error');
+        if (data.success) {
+            resetForm.hidden = true;
+            history.replaceState(null, '', window.location.pathname);
+            setTimeout(() => {
+                window.location.href = '/';
+            }, 1500);
+            return;
+        }
+    } catch (error) {
+        showMessage('Network error. Please try again.', 'error');
+    }
+    resetBtn.disabled = false;
+    resetBtn.textContent = 'Set Password';
+});
+
+if (token) {
+    requestForm.hidden = true;
+    resetForm.hidden = false;
+    document.getElementById('subtitle').textContent = 'Choose a new password';
+}
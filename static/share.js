@@ -0,0 +1,98 @@
+// The token lives in the URL fragment, which browsers never send to the
+// server, and is passed on only as an Authorization header.
+const token = decodeURIComponent(window.location.hash.slice(1));
+const messageDiv = document.getElementById('message');
+const passcodeForm = document.getElementById('passcodeForm');
+const table = document.getElementById('servers');
+const REFRESH_MS = 30000;
+
+let passcode = null;
+let timer = null;
+
+function showMessage(text, type) {
+    messageDiv.textContent = text;
+    messageDiv.className = 'message ' + type;
+    messageDiv.style.display = 'block';
+}
+
+function cell(row, text, className) {
+    const td = row.insertCell();
+    td.textContent = text;
+    if (className) {
+        td.className = className;
+    }
+}
+
+function render(data) {
+    document.getElementById('title').textContent = '🖥️ ' + data.name;
+    const body = table.tBodies[0];
+    body.replaceChildren();
+    for (const server of data.servers) {
+        const row = body.insertRow();
+        cell(row, server.alias);
+        cell(row, server.reachable ? server.power_state : 'Unreachable', server.reachable ? '' : 'unknown');
+        cell(row, server.health, 'health-' + server.health.toLowerCase());
+        cell(row, server.watts == null ? '-' : server.watts + ' W');
+        cell(row, server.checked_at);
+    }
+    table.hidden = false;
+    if (data.servers.length) {
+        messageDiv.style.display = 'none';
+    } else {
+        showMessage('No servers are shared through this link any more.', 'error');
+    }
+}
+
+async function refresh() {
+    const headers = { 'Authorization': 'Share ' + token };
+    if (passcode !== null) {
+        headers['X-Share-Passcode'] = passcode;
+    }
+
+    try {
+        const response = await fetch('/api/share/status', { headers, cache: 'no-store' });
+        const data = await response.json();
+        if (data.success) {
+            passcodeForm.hidden = true;
+            render(data);
+            return;
+        }
+        if (data.error_code === 'auth.share_passcode_required') {
+            clearInterval(timer);
+            timer = null;
+            passcodeForm.hidden = false;
+            table.hidden = true;
+            if (passcode !== null) {
+                showMessage('Wrong passcode.', 'error');
+            }
+            return;
+        }
+        showMessage(data.message, 'error');
+        if (response.status === 401) {
+            clearInterval(timer);
+            timer = null;
+            table.hidden = true;
+        }
+    } catch (error) {
+        showMessage('Network error. Retrying...', 'error');
+    }
+}
+
+function start() {
+    refresh();
+    if (timer === null) {
+        timer = setInterval(refresh, REFRESH_MS);
+    }
+}
+
+passcodeForm.addEventListener('submit', (e) => {
+    e.preventDefault();
+    passcode = document.getElementById('passcode').value;
+    start();
+});
+
+if (token) {
+    start();
+} else {
+    showMessage('This link is incomplete.', 'error');
+}
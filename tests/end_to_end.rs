@@ -0,0 +1,156 @@
+//! Runs the controller against the fake iDRAC and drives the main flow
+//! over HTTP: log in, power on and cycle, read health and read the SEL.
+
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// A child process killed when the test ends, pass or fail.
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("idrac-controller-e2e-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+async fn wait_until_listening(url: &str) {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while reqwest::get(url).await.is_err() {
+        assert!(Instant::now() < deadline, "{} never started listening", url);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Logged-in session against the controller at `base`.
+struct Session {
+    http: reqwest::Client,
+    base: String,
+    cookie: String,
+}
+
+impl Session {
+    async fn get(&self, path: &str) -> Value {
+        let response = self
+            .http
+            .get(format!("{}{}", self.base, path))
+            .header("Cookie", &self.cookie)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "GET {} answered {}", path, response.status());
+        response.json().await.unwrap()
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Value {
+        let response = self
+            .http
+            .post(format!("{}{}", self.base, path))
+            .header("Cookie", &self.cookie)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "POST {} answered {}", path, response.status());
+        response.json().await.unwrap()
+    }
+
+    async fn wait_for_power_state(&self, target: &str) {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        loop {
+            let status = self.get("/api/power/status").await;
+            if status["power_state"] == target {
+                return;
+            }
+            assert!(Instant::now() < deadline, "power state stayed {}", status["power_state"]);
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    }
+}
+
+#[tokio::test]
+async fn login_power_cycle_health_and_sel() {
+    let dir = scratch_dir();
+    let (idrac_port, app_port) = (free_port(), free_port());
+
+    let _idrac = Process(
+        Command::new(env!("CARGO_BIN_EXE_fake-idrac"))
+            .args(["--port", &idrac_port.to_string(), "--no-tls", "--power-delay-secs", "1", "--sel-interval-secs", "1"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    let database = dir.join("db.sqlite");
+    let _app = Process(
+        Command::new(env!("CARGO_BIN_EXE_idrac-controller"))
+            .env("IDRAC_HOST", format!("http://127.0.0.1:{}", idrac_port))
+            .env("IDRAC_USERNAME", "root")
+            .env("IDRAC_PASSWORD", "calvin")
+            .env("DATABASE_PATH", &database)
+            .env("BIND_ADDRESS", format!("127.0.0.1:{}", app_port))
+            .env("RUST_LOG", "warn")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    let base = format!("http://127.0.0.1:{}", app_port);
+    wait_until_listening(&format!("http://127.0.0.1:{}/redfish/v1", idrac_port)).await;
+    wait_until_listening(&format!("{}/api/auth/methods", base)).await;
+
+    // The first start creates a placeholder admin, which closes
+    // registration; remove it so the test can register its own account.
+    rusqlite::Connection::open(&database).unwrap().execute("DELETE FROM users", []).unwrap();
+
+    let http = reqwest::Client::new();
+    let credentials = json!({"username": "tester", "password": "password123", "confirm_password": "password123"});
+    let registered = http.post(format!("{}/api/register", base)).json(&credentials).send().await.unwrap();
+    assert!(registered.status().is_success(), "register answered {}", registered.status());
+
+    let login = http.post(format!("{}/api/login", base)).json(&credentials).send().await.unwrap();
+    assert!(login.status().is_success(), "login answered {}", login.status());
+    let cookie = login
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .filter_map(|value| value.to_str().ok()?.split(';').next().map(str::to_string))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let session = Session { http, base, cookie };
+
+    session.wait_for_power_state("Off").await;
+    let powered_on = session.post("/api/power/on", json!({})).await;
+    assert_eq!(powered_on["success"], true);
+    session.wait_for_power_state("On").await;
+    let cycled = session.post("/api/power/cycle", json!({})).await;
+    assert_eq!(cycled["success"], true);
+    session.wait_for_power_state("On").await;
+
+    let health = session.get("/api/fleet/health").await;
+    let servers = health["servers"].as_array().unwrap();
+    assert!(!servers.is_empty());
+    assert!(servers.iter().all(|server| server["status"] == "ok"), "{}", health);
+
+    // The fake SEL gains an entry every second.
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let logs = session.get("/api/idrac/logs?sources=sel").await;
+    assert!(!logs["entries"].as_array().unwrap().is_empty(), "{}", logs);
+
+    drop(session);
+    let _ = std::fs::remove_dir_all(&dir);
+}